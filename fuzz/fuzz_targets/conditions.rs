@@ -0,0 +1,19 @@
+// Conditions as they appear in exports, pushed through validation and the
+// native encoder and decoder.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sls_wfp_gui::wfp::{round_trip_conditions, Condition};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(conditions) = serde_json::from_slice::<Vec<Condition>>(data) else {
+        return;
+    };
+    for condition in &conditions {
+        let _ = condition.validate();
+        let _ = condition.to_string();
+    }
+    if conditions.iter().all(|c| c.validate().is_ok()) {
+        let _ = round_trip_conditions(&conditions);
+    }
+});
@@ -0,0 +1,25 @@
+// Rule files from disk, a rule source or the clipboard: whatever parses must
+// either pass the pre-transaction check or be rejected by it, and nothing
+// may panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sls_wfp_gui::{
+    config::{self, RuleFormat},
+    wfp::FilterConfig,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = RuleFormat::sniff(text);
+    for format in [RuleFormat::Json, RuleFormat::Yaml, RuleFormat::Toml] {
+        let Ok(configs) = config::parse_rules(text, format) else {
+            continue;
+        };
+        let _ = configs.iter().try_for_each(FilterConfig::validate);
+        let exported = serde_json::to_string(&configs).expect("parsed rules serialize");
+        config::parse_rules(&exported, RuleFormat::Json).expect("exported rules parse");
+    }
+});
@@ -0,0 +1,100 @@
+#ifndef SLS_WFP_H
+#define SLS_WFP_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum SlsWfpResult {
+  SLS_WFP_RESULT_OK = 0,
+  // A required pointer was null, a string was not valid UTF-8 or a value
+  // was out of range.
+  SLS_WFP_RESULT_INVALID_ARGUMENT = 1,
+  // The call failed; see `sls_wfp_last_error`.
+  SLS_WFP_RESULT_FAILED = 2,
+} SlsWfpResult;
+
+typedef enum SlsWfpAction {
+  SLS_WFP_ACTION_PERMIT = 0,
+  SLS_WFP_ACTION_BLOCK = 1,
+} SlsWfpAction;
+
+// An open BFE session. Opaque to callers.
+//
+// A session may move between threads but must not be used from more
+// than one at a time: callers sharing one serialize their calls, e.g.
+// behind a mutex, or open a session per thread.
+typedef struct SlsWfpEngine SlsWfpEngine;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Opens a BFE session. Returns null on failure.
+struct SlsWfpEngine *sls_wfp_engine_open(void);
+
+// Closes a session from `sls_wfp_engine_open`. Null is ignored.
+//
+// # Safety
+// `engine` must be null or a pointer returned by `sls_wfp_engine_open`
+// that has not been closed.
+void sls_wfp_engine_close(struct SlsWfpEngine *engine);
+
+// Adds an outbound TCP rule for `port_count` remote ports. `action` is an
+// `SlsWfpAction`; any other value is an invalid argument. On success the
+// new rule key is written to `key_out` (free it with `sls_wfp_string_free`)
+// when `key_out` is not null.
+//
+// # Safety
+// `engine` must be an open session, `name` a NUL-terminated string and
+// `ports` point to `port_count` values.
+enum SlsWfpResult sls_wfp_add_tcp_filter(struct SlsWfpEngine *engine,
+                                         const char *name,
+                                         const uint16_t *ports,
+                                         size_t port_count,
+                                         uint32_t action,
+                                         char **key_out);
+
+// Deletes an owned rule by its key, e.g. `"6f1d0c52-..."`.
+//
+// # Safety
+// `engine` must be an open session and `key` a NUL-terminated string.
+enum SlsWfpResult sls_wfp_delete_filter(struct SlsWfpEngine *engine, const char *key);
+
+// Imports rules from the same JSON the GUI exports. Counts are written to
+// `added_out` and `updated_out` when they are not null.
+//
+// # Safety
+// `engine` must be an open session and `json` a NUL-terminated string.
+enum SlsWfpResult sls_wfp_import_json(struct SlsWfpEngine *engine,
+                                      const char *json,
+                                      size_t *added_out,
+                                      size_t *updated_out);
+
+// Writes every filter BFE reports as a JSON array to `json_out`; free it
+// with `sls_wfp_string_free`.
+//
+// # Safety
+// `engine` must be an open session and `json_out` a valid pointer.
+enum SlsWfpResult sls_wfp_filters_json(struct SlsWfpEngine *engine, char **json_out);
+
+// Frees a string returned by this library. Null is ignored.
+//
+// # Safety
+// `s` must be null or a string returned by this library that has not been
+// freed.
+void sls_wfp_string_free(char *s);
+
+// Message for the last failed call on this thread, or null. Valid until the
+// next call into this library on the same thread.
+const char *sls_wfp_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SLS_WFP_H */
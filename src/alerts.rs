@@ -0,0 +1,436 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    process::Command,
+    sync::{
+        mpsc::{self, Sender, SyncSender},
+        OnceLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    log_rotation::{self, LogRetention},
+    wfp::{
+        protocol_name, uuid_from_guid, CancelToken, Cancelled, Engine, FilterSummary, NetEvent,
+        NetEventKind, NetEventQuery,
+    },
+};
+
+const ALERTS_FILE: &str = "alerts.json";
+const ALERT_LOG_FILE: &str = "alerts.log";
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Alerts waiting for the webhook worker; more are dropped while it is
+/// this far behind.
+const WEBHOOK_QUEUE: usize = 64;
+/// Least time between two posts for one rule. Alerts in between are
+/// counted in the next post's `suppressed` instead of sent.
+const WEBHOOK_GAP: Duration = Duration::from_secs(30);
+
+/// A user-defined condition over net events. Empty fields match anything.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertRule {
+    pub name: String,
+    pub enabled: bool,
+    /// Case-insensitive fragment of the application path.
+    pub app: Option<String>,
+    pub protocol: Option<u8>,
+    pub remote_port: Option<u16>,
+    pub kind: Option<NetEventKind>,
+    /// Fire only once more than this many matches land within a minute;
+    /// 0 fires on every match.
+    pub per_minute: u32,
+    /// Run through `cmd /C` when the alert fires, with the details in
+    /// `SLS_ALERT_*` environment variables.
+    pub command: Option<String>,
+    /// HTTPS endpoint that receives a JSON payload when the alert fires.
+    pub webhook: Option<String>,
+}
+
+impl Default for AlertRule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            app: None,
+            protocol: None,
+            remote_port: None,
+            kind: Some(NetEventKind::Drop),
+            per_minute: 0,
+            command: None,
+            webhook: None,
+        }
+    }
+}
+
+impl AlertRule {
+    pub fn matches(&self, event: &NetEvent) -> bool {
+        let app = match (self.app.as_deref(), &event.app_id) {
+            (None, _) => true,
+            (Some(fragment), Some(app)) => app
+                .to_ascii_lowercase()
+                .contains(&fragment.to_ascii_lowercase()),
+            (Some(_), None) => false,
+        };
+        app && self.kind.is_none_or(|k| event.kind == k)
+            && self.protocol.is_none_or(|p| event.protocol == Some(p))
+            && self
+                .remote_port
+                .is_none_or(|p| event.remote_port == Some(p))
+    }
+
+    /// One-line summary of the conditions, e.g. `Blocked, tcp, remote port 22`.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![self
+            .kind
+            .map_or("Any verdict", NetEventKind::as_str)
+            .to_string()];
+        if let Some(proto) = self.protocol {
+            parts.push(protocol_name(proto).to_string());
+        }
+        if let Some(port) = self.remote_port {
+            parts.push(format!("remote port {port}"));
+        }
+        if let Some(app) = &self.app {
+            parts.push(format!("app contains '{app}'"));
+        }
+        if self.per_minute > 0 {
+            parts.push(format!("more than {}/min", self.per_minute));
+        }
+        parts.join(", ")
+    }
+}
+
+/// A fired alert: the rule and the event that tipped it over.
+#[derive(Clone, Debug)]
+pub struct Alert {
+    pub rule: AlertRule,
+    pub event: NetEvent,
+    /// Matches within the last minute, including this one.
+    pub count: usize,
+}
+
+impl Alert {
+    pub fn message(&self) -> String {
+        let app = self.event.app_id.as_deref().unwrap_or("unknown app");
+        if self.rule.per_minute > 0 {
+            format!(
+                "{}: {} matching connections in the last minute, latest {} {} ({app})",
+                self.rule.name,
+                self.count,
+                self.event.kind.as_str(),
+                self.event.flow()
+            )
+        } else {
+            format!(
+                "{}: {} {} ({app})",
+                self.rule.name,
+                self.event.kind.as_str(),
+                self.event.flow()
+            )
+        }
+    }
+
+    /// Appends the alert to `alerts.log` in the app data directory.
+    pub fn log(&self) -> Result<()> {
+        append_log(self.event.time, &self.message())
+    }
+
+    /// Queues the alert for the rule's webhook, if any. One background
+    /// thread posts them in turn, at most one per rule every
+    /// [`WEBHOOK_GAP`]; the queue is bounded, so a flood of alerts drops
+    /// the excess rather than piling up. `filter` is the WFP filter that
+    /// decided the event; delivery failures are written to the alert log.
+    pub fn send_webhook(&self, filter: Option<&FilterSummary>) {
+        let Some(url) = self.rule.webhook.clone() else {
+            return;
+        };
+        let payload = WebhookPayload {
+            text: self.message(),
+            alert: WebhookAlert {
+                name: self.rule.name.clone(),
+                kind: self.rule.kind,
+                per_minute: self.rule.per_minute,
+            },
+            count: self.count,
+            suppressed: 0,
+            event: self.event.clone(),
+            filter: filter.map(|f| WebhookFilter {
+                id: f.id,
+                key: uuid_from_guid(f.key).to_string(),
+                name: f.name.clone(),
+                layer: f.layer.clone(),
+                action: f.action.as_str(),
+            }),
+        };
+        let _ = webhook_queue().try_send((url, payload));
+    }
+
+    /// Starts the rule's command, if any, without waiting for it.
+    pub fn run_command(&self) -> Result<()> {
+        let Some(command) = &self.rule.command else {
+            return Ok(());
+        };
+        let event = &self.event;
+        let text = |v: Option<String>| v.unwrap_or_default();
+        Command::new("cmd")
+            .args(["/C", command])
+            .env("SLS_ALERT_RULE", &self.rule.name)
+            .env("SLS_ALERT_MESSAGE", self.message())
+            .env("SLS_ALERT_COUNT", self.count.to_string())
+            .env("SLS_ALERT_VERDICT", event.kind.as_str())
+            .env("SLS_ALERT_APP", text(event.app_id.clone()))
+            .env(
+                "SLS_ALERT_REMOTE_ADDRESS",
+                text(event.remote_addr.map(|a| a.to_string())),
+            )
+            .env(
+                "SLS_ALERT_REMOTE_PORT",
+                text(event.remote_port.map(|p| p.to_string())),
+            )
+            .spawn()
+            .map_err(|e| anyhow!("Failed to run alert command for {}: {e}", self.rule.name))?;
+        Ok(())
+    }
+}
+
+/// The configured alert rules plus the per-rule match history needed for
+/// rate thresholds.
+#[derive(Default)]
+pub struct Alerts {
+    pub rules: Vec<AlertRule>,
+    recent: HashMap<String, VecDeque<DateTime<Utc>>>,
+}
+
+impl Alerts {
+    /// Loads the saved rules; a missing file means no rules.
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            rules: config::load_settings(ALERTS_FILE)?,
+            recent: HashMap::new(),
+        })
+    }
+
+    /// Saves the rules encrypted, since webhook URLs carry access tokens.
+    pub fn save(&self) -> Result<()> {
+        config::save_protected_settings(ALERTS_FILE, &self.rules)
+    }
+
+    /// Checks `events` (oldest first) against every enabled rule. A rate
+    /// rule fires when its one-minute window goes over the threshold, then
+    /// starts counting afresh so a sustained flood alerts once per burst.
+    pub fn evaluate(&mut self, events: &[NetEvent]) -> Vec<Alert> {
+        let mut fired = Vec::new();
+        for rule in self.rules.iter().filter(|r| r.enabled) {
+            let window = self.recent.entry(rule.name.clone()).or_default();
+            for event in events.iter().filter(|e| rule.matches(e)) {
+                window.push_back(event.time);
+                while window
+                    .front()
+                    .is_some_and(|t| event.time - *t >= chrono::Duration::minutes(1))
+                {
+                    window.pop_front();
+                }
+                if window.len() > rule.per_minute as usize {
+                    fired.push(Alert {
+                        rule: rule.clone(),
+                        event: event.clone(),
+                        count: window.len(),
+                    });
+                    window.clear();
+                }
+            }
+        }
+        self.recent
+            .retain(|name, _| self.rules.iter().any(|r| &r.name == name));
+        fired
+    }
+}
+
+pub fn alert_log_path() -> PathBuf {
+    config::app_data_dir().join(ALERT_LOG_FILE)
+}
+
+fn append_log(time: DateTime<Utc>, line: &str) -> Result<()> {
+    let path = alert_log_path();
+    log_rotation::rotate(&path, &LogRetention::load()?)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| anyhow!("Failed to open {}: {e}", path.display()))?;
+    writeln!(file, "{}\t{line}", time.to_rfc3339())?;
+    Ok(())
+}
+
+/// Checks a webhook URL before it is saved. Only HTTPS is accepted since
+/// the payload carries addresses and application paths.
+pub fn validate_webhook(url: &str) -> Result<()> {
+    let rest = url
+        .strip_prefix("https://")
+        .ok_or_else(|| anyhow!("Webhook URL must start with https://"))?;
+    if rest.split('/').next().is_none_or(str::is_empty) {
+        return Err(anyhow!("Webhook URL has no host"));
+    }
+    Ok(())
+}
+
+/// Body posted to webhooks. `text` is what Slack and Teams incoming
+/// webhooks display; the rest is for SOAR tooling.
+#[derive(Serialize)]
+struct WebhookPayload {
+    text: String,
+    alert: WebhookAlert,
+    count: usize,
+    /// Alerts from the same rule held back since the previous post.
+    suppressed: usize,
+    event: NetEvent,
+    filter: Option<WebhookFilter>,
+}
+
+/// The rule behind a posted alert. The webhook URL carries an access token
+/// and the command is local, so neither leaves the machine.
+#[derive(Serialize)]
+struct WebhookAlert {
+    name: String,
+    kind: Option<NetEventKind>,
+    per_minute: u32,
+}
+
+#[derive(Serialize)]
+struct WebhookFilter {
+    id: u64,
+    key: String,
+    name: String,
+    layer: String,
+    action: &'static str,
+}
+
+/// The webhook worker's queue, started with the first alert.
+fn webhook_queue() -> &'static SyncSender<(String, WebhookPayload)> {
+    static QUEUE: OnceLock<SyncSender<(String, WebhookPayload)>> = OnceLock::new();
+    QUEUE.get_or_init(|| {
+        let (queue, alerts) = mpsc::sync_channel::<(String, WebhookPayload)>(WEBHOOK_QUEUE);
+        thread::spawn(move || {
+            // Per rule: when it last posted, and the alerts held back since.
+            let mut posted: HashMap<String, (Instant, usize)> = HashMap::new();
+            for (url, mut payload) in alerts {
+                match posted.get_mut(&payload.alert.name) {
+                    Some((last, held)) if last.elapsed() < WEBHOOK_GAP => {
+                        *held += 1;
+                        continue;
+                    }
+                    Some((last, held)) => {
+                        payload.suppressed = std::mem::take(held);
+                        *last = Instant::now();
+                    }
+                    None => {
+                        posted.insert(payload.alert.name.clone(), (Instant::now(), 0));
+                    }
+                }
+                let sent = ureq::post(&url)
+                    .timeout(WEBHOOK_TIMEOUT)
+                    .send_json(&payload);
+                if let Err(err) = sent {
+                    let _ = append_log(
+                        Utc::now(),
+                        &format!("{}: webhook delivery failed: {err}", payload.alert.name),
+                    );
+                }
+            }
+        });
+        queue
+    })
+}
+
+/// Polls BFE for net events on a background thread and sends each batch of
+/// events newer than the last one seen. Events already in the buffer when
+/// the feed starts are skipped. Dropping the feed stops the thread, and
+/// cancels a read in progress, such as the first one through a full buffer.
+pub struct EventFeed {
+    stop: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EventFeed {
+    pub fn start(interval: Duration, events: Sender<Result<Vec<NetEvent>>>) -> Self {
+        let stop = CancelToken::new();
+        let token = stop.clone();
+        let thread = thread::spawn(move || {
+            let mut newest = None;
+            let mut engine: Option<Engine> = None;
+            while !token.is_cancelled() {
+                let batch = engine
+                    .take()
+                    .map_or_else(Engine::open, Ok)
+                    .and_then(|opened| {
+                        let batch = poll(&opened, &mut newest, &token)?;
+                        // Dropped on failure and reopened next round, in case
+                        // BFE restarted.
+                        engine = Some(opened);
+                        Ok(batch)
+                    });
+                let sent = match batch {
+                    Err(err) if err.is::<Cancelled>() => break,
+                    Ok(batch) if batch.is_empty() => Ok(()),
+                    other => events.send(other),
+                };
+                if sent.is_err() {
+                    break;
+                }
+                sleep_unless_stopped(&token, interval);
+            }
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for EventFeed {
+    fn drop(&mut self) {
+        self.stop.cancel();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn poll(
+    engine: &Engine,
+    newest: &mut Option<DateTime<Utc>>,
+    stop: &CancelToken,
+) -> Result<Vec<NetEvent>> {
+    let mut events = engine.query_net_events_cancellable(&NetEventQuery::default(), stop)?;
+    events.sort_by_key(|e| e.time);
+    let baseline = newest.is_none();
+    let fresh: Vec<NetEvent> = events
+        .into_iter()
+        .filter(|e| newest.is_none_or(|n| e.time > n))
+        .collect();
+    if let Some(last) = fresh.last() {
+        *newest = Some(last.time);
+    } else if baseline {
+        *newest = Some(Utc::now());
+    }
+    Ok(if baseline { Vec::new() } else { fresh })
+}
+
+fn sleep_unless_stopped(stop: &CancelToken, total: Duration) {
+    let step = Duration::from_millis(100);
+    let mut waited = Duration::ZERO;
+    while waited < total && !stop.is_cancelled() {
+        thread::sleep(step);
+        waited += step;
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::{
+    naming,
+    wfp::{Engine, NetEvent},
+};
+
+/// How long an exception from the event log lasts.
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// A permit for one blocked flow that lifts on its own. Like a lockdown
+/// it lives in a dynamic session, so BFE removes it when it is dropped or
+/// the process exits, and a forgotten exception never becomes a permanent
+/// hole.
+pub struct TemporaryAllow {
+    _engine: Engine,
+    /// The flow allowed, for display.
+    pub flow: String,
+    until: Instant,
+}
+
+impl TemporaryAllow {
+    /// Allows the flow of `event` on this machine for `duration`.
+    pub fn start(event: &NetEvent, duration: Duration) -> Result<Self> {
+        let flow = event.flow();
+        let builder = event
+            .permit(&naming::preset_name(&format!("Allowed once: {flow}")))?
+            // Blocks in lower-weight sublayers, e.g. Windows Firewall,
+            // cannot override it.
+            .clear_action_right(true);
+        let engine = Engine::open_dynamic()?;
+        engine.add_filter(&builder)?;
+        Ok(Self {
+            _engine: engine,
+            flow,
+            until: Instant::now() + duration,
+        })
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.until.saturating_duration_since(Instant::now())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
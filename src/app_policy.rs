@@ -0,0 +1,150 @@
+use std::cmp::Reverse;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::wfp::{
+    app_id, Condition, ConditionField, ConditionValue, FilterSummary, MatchType, SublayerInfo,
+    WfpAction, GUID,
+};
+
+/// How a filter's app ID conditions relate to one application.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppScope {
+    /// Names the application.
+    App,
+    /// Not limited to applications, or limited to every one but others.
+    Any,
+    /// Compares app IDs in a way we cannot evaluate, e.g. by prefix.
+    Maybe,
+}
+
+impl AppScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AppScope::App => "this app",
+            AppScope::Any => "any app",
+            AppScope::Maybe => "may apply",
+        }
+    }
+
+    /// Whether a filter with app ID `conditions` could match the
+    /// application `id`. Conditions on the same field are ORed.
+    fn of(conditions: &[Condition], id: &ConditionValue) -> Option<Self> {
+        let apps: Vec<_> = conditions
+            .iter()
+            .filter(|c| c.field == ConditionField::AppId)
+            .collect();
+        if apps.is_empty() {
+            return Some(AppScope::Any);
+        }
+        let mut scope = None;
+        for condition in apps {
+            let found = match condition.match_type {
+                MatchType::Equal if condition.value == *id => AppScope::App,
+                MatchType::NotEqual if condition.value != *id => AppScope::Any,
+                MatchType::Equal | MatchType::NotEqual => continue,
+                _ => AppScope::Maybe,
+            };
+            scope = match (scope, found) {
+                (Some(AppScope::App), _) | (_, AppScope::App) => Some(AppScope::App),
+                (Some(AppScope::Any), _) | (_, AppScope::Any) => Some(AppScope::Any),
+                _ => Some(AppScope::Maybe),
+            };
+        }
+        scope
+    }
+}
+
+/// A filter that could match the application's traffic.
+#[derive(Clone)]
+pub struct AppPolicyEntry {
+    pub filter: FilterSummary,
+    pub scope: AppScope,
+    /// Weight of the filter's sublayer, when the sublayer is known.
+    pub sublayer_weight: Option<u16>,
+    /// An earlier filter in the same layer and sublayer matches all of the
+    /// application's traffic, so this one is never reached.
+    pub shadowed: bool,
+}
+
+/// Every filter, from any provider, whose conditions could match one
+/// application, in the order BFE arbitrates them: by layer, then sublayer
+/// weight and filter weight, highest first. Each sublayer reaches a verdict
+/// of its own; see [`crate::coexistence::explain_arbitration`].
+pub struct AppPolicy {
+    pub app: String,
+    pub entries: Vec<AppPolicyEntry>,
+}
+
+impl AppPolicy {
+    /// The policy for the executable `app`, which must exist so its app ID
+    /// can be compared.
+    pub fn build(app: &str, filters: &[FilterSummary], sublayers: &[SublayerInfo]) -> Result<Self> {
+        let id = app_id(app)?;
+        let mut entries: Vec<AppPolicyEntry> = filters
+            .iter()
+            .filter_map(|filter| {
+                Some(AppPolicyEntry {
+                    scope: AppScope::of(&filter.conditions, &id)?,
+                    sublayer_weight: sublayers
+                        .iter()
+                        .find(|s| s.key == filter.sublayer_key)
+                        .map(|s| s.weight),
+                    shadowed: false,
+                    filter: filter.clone(),
+                })
+            })
+            .collect();
+        entries.sort_by_key(|e| {
+            (
+                e.filter.layer.clone(),
+                Reverse(e.sublayer_weight),
+                Reverse(e.filter.effective_weight.or(e.filter.weight)),
+            )
+        });
+        for i in 1..entries.len() {
+            let (earlier, rest) = entries.split_at_mut(i);
+            let entry = &mut rest[0];
+            entry.shadowed = earlier.iter().any(|e| {
+                e.filter.layer_key == entry.filter.layer_key
+                    && e.filter.sublayer_key == entry.filter.sublayer_key
+                    && matches_all_traffic(e)
+            });
+        }
+        Ok(Self {
+            app: app.to_string(),
+            entries,
+        })
+    }
+
+    /// `12 filter(s) in 3 layer(s) could match C:\app.exe, 2 never reached`.
+    pub fn summary(&self) -> String {
+        let mut layers: Vec<GUID> = self.entries.iter().map(|e| e.filter.layer_key).collect();
+        layers.dedup();
+        let mut text = format!(
+            "{} filter(s) in {} layer(s) could match {}",
+            self.entries.len(),
+            layers.len(),
+            self.app
+        );
+        let shadowed = self.entries.iter().filter(|e| e.shadowed).count();
+        if shadowed > 0 {
+            text += &format!(", {shadowed} never reached");
+        }
+        text
+    }
+}
+
+/// Whether the entry decides all of the application's traffic that reaches
+/// it. Callouts may hand the decision on, so they never do.
+fn matches_all_traffic(entry: &AppPolicyEntry) -> bool {
+    entry.filter.action != WfpAction::Callout
+        && entry.scope != AppScope::Maybe
+        && entry
+            .filter
+            .conditions
+            .iter()
+            .all(|c| c.field == ConditionField::AppId)
+}
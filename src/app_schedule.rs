@@ -0,0 +1,131 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    chart::app_name,
+    config, naming,
+    wfp::{app_id, app_rule, guid_from_uuid, Engine, WfpAction, GUID},
+};
+
+const SCHEDULE_FILE: &str = "app_schedules.json";
+
+/// Daily stretch of local time during which an app may use the network. A
+/// window that ends before it starts runs past midnight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessWindow {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+}
+
+impl AccessWindow {
+    /// Parses `18:00-22:00`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let (from, to) = text
+            .split_once(['-', '–'])
+            .ok_or_else(|| anyhow!("Window must look like 18:00-22:00"))?;
+        let time = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .map_err(|_| anyhow!("'{}' is not a time like 18:00", t.trim()))
+        };
+        let window = Self {
+            from: time(from)?,
+            to: time(to)?,
+        };
+        if window.from == window.to {
+            return Err(anyhow!("Window must not be empty"));
+        }
+        Ok(window)
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.from < self.to {
+            self.from <= time && time < self.to
+        } else {
+            self.from <= time || time < self.to
+        }
+    }
+}
+
+impl fmt::Display for AccessWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}–{}",
+            self.from.format("%H:%M"),
+            self.to.format("%H:%M")
+        )
+    }
+}
+
+/// An app that may only reach the network inside `window`. Outside it the
+/// scheduler installs a block rule keyed by `key`, and removes it again
+/// when the window opens.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppSchedule {
+    pub key: Uuid,
+    pub app: String,
+    pub window: AccessWindow,
+    pub enabled: bool,
+}
+
+impl AppSchedule {
+    pub fn new(app: &str, window: AccessWindow) -> Result<Self> {
+        let app = app.trim();
+        if app.is_empty() {
+            return Err(anyhow!("Application path is required"));
+        }
+        Ok(Self {
+            key: Uuid::new_v4(),
+            app: app.to_string(),
+            window,
+            enabled: true,
+        })
+    }
+
+    pub fn rule_key(&self) -> GUID {
+        guid_from_uuid(self.key)
+    }
+
+    /// Whether the app should be allowed at `time`. Disabled schedules
+    /// never block.
+    pub fn is_open(&self, time: NaiveTime) -> bool {
+        !self.enabled || self.window.contains(time)
+    }
+
+    /// Installs or removes the block rule so the app is allowed exactly
+    /// when `open`.
+    pub fn apply(&self, engine: &Engine, open: bool) -> Result<()> {
+        let key = self.rule_key();
+        if open {
+            let installed = engine
+                .snapshot()?
+                .filters
+                .iter()
+                .any(|f| f.owned_by_app && f.rule_key() == key);
+            if installed {
+                engine.delete_filter_by_key(key)?;
+            }
+            return Ok(());
+        }
+        let name = naming::preset_name(&format!(
+            "{} blocked outside {}",
+            app_name(&self.app),
+            self.window
+        ));
+        let builders = app_rule(key, &name, &app_id(&self.app)?, &[], WfpAction::Block);
+        engine.replace_rule(key, &name, &builders)?;
+        Ok(())
+    }
+}
+
+pub fn load_schedules() -> Result<Vec<AppSchedule>> {
+    config::load_settings(SCHEDULE_FILE)
+}
+
+pub fn save_schedules(schedules: &[AppSchedule]) -> Result<()> {
+    config::save_settings(SCHEDULE_FILE, schedules)
+}
@@ -0,0 +1,176 @@
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::wfp::{Engine, FilterConfig, ImportSummary, Snapshot, GUID};
+
+type Job = Box<dyn FnOnce(&Engine) + Send>;
+
+/// An [`Engine`] owned by a dedicated thread. Calls queue a job for that
+/// thread and return an [`EngineFuture`], so async callers can await WFP
+/// operations without blocking their executor. Works with any executor;
+/// jobs run one at a time in the order they were queued.
+pub struct AsyncEngine {
+    jobs: Option<mpsc::Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AsyncEngine {
+    /// Opens the local engine on a new thread.
+    pub fn open() -> Result<Self> {
+        Self::open_on(None)
+    }
+
+    /// Opens the engine on `server`, or locally for `None`, and waits until
+    /// the session is open so a failure is reported here.
+    pub fn open_on(server: Option<&str>) -> Result<Self> {
+        let server = server.map(str::to_string);
+        let (opened, open_result) = mpsc::channel();
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let thread = thread::Builder::new()
+            .name("wfp-engine".into())
+            .spawn(move || {
+                let engine = match Engine::open_on(server.as_deref()) {
+                    Ok(engine) => {
+                        let _ = opened.send(Ok(()));
+                        engine
+                    }
+                    Err(err) => {
+                        let _ = opened.send(Err(err));
+                        return;
+                    }
+                };
+                for job in queue {
+                    job(&engine);
+                }
+            })?;
+        open_result
+            .recv()
+            .unwrap_or_else(|_| Err(anyhow!("Engine thread stopped while opening")))?;
+        Ok(Self {
+            jobs: Some(jobs),
+            thread: Some(thread),
+        })
+    }
+
+    /// Runs `job` on the engine thread. A job that panics fails its own
+    /// future; the thread carries on with the jobs after it.
+    pub fn run<T: Send + 'static>(
+        &self,
+        job: impl FnOnce(&Engine) -> Result<T> + Send + 'static,
+    ) -> EngineFuture<T> {
+        let (future, completer) = EngineFuture::pending();
+        let job: Job = Box::new(move |engine| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| job(engine)))
+                .unwrap_or_else(|_| Err(anyhow!("Engine job panicked")));
+            completer.complete(result);
+        });
+        // A job refused by a stopped thread is dropped, which fails its
+        // future.
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(job);
+        }
+        future
+    }
+
+    pub fn snapshot(&self) -> EngineFuture<Snapshot> {
+        self.run(Engine::snapshot)
+    }
+
+    /// Adds or replaces rules by key, like [`Engine::import_filters`].
+    pub fn add(&self, configs: Vec<FilterConfig>) -> EngineFuture<ImportSummary> {
+        self.run(move |engine| engine.import_filters(&configs))
+    }
+
+    pub fn delete(&self, key: GUID) -> EngineFuture<()> {
+        self.run(move |engine| engine.delete_filter_by_key(key))
+    }
+}
+
+impl Drop for AsyncEngine {
+    /// Lets queued jobs finish, then closes the session.
+    fn drop(&mut self) {
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Slot<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// The result of a job queued on an [`AsyncEngine`].
+pub struct EngineFuture<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> EngineFuture<T> {
+    fn pending() -> (Self, Completer<T>) {
+        let slot = Arc::new(Mutex::new(Slot {
+            result: None,
+            waker: None,
+        }));
+        (
+            Self { slot: slot.clone() },
+            Completer {
+                slot,
+                completed: false,
+            },
+        )
+    }
+}
+
+impl<T> Future for EngineFuture<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Fills in an [`EngineFuture`]. Dropped without a result, e.g. when the
+/// thread stopped first, it fails the future instead of leaving it pending.
+struct Completer<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+    completed: bool,
+}
+
+impl<T> Completer<T> {
+    fn complete(mut self, result: Result<T>) {
+        self.completed = true;
+        self.fill(result);
+    }
+
+    fn fill(&self, result: Result<T>) {
+        let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+        slot.result = Some(result);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.fill(Err(anyhow!("Engine thread stopped before the job ran")));
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::wfp::{Engine, SnapshotTimings};
+
+/// Spread of one enumeration phase over several runs.
+#[derive(Clone, Debug, Serialize)]
+pub struct PhaseStats {
+    pub phase: &'static str,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Timings of repeated enumerations of one engine.
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchReport {
+    pub runs: usize,
+    pub filters: usize,
+    pub phases: Vec<PhaseStats>,
+}
+
+impl BenchReport {
+    /// Enumerates `engine` `runs` times, timing each phase.
+    pub fn run(engine: &Engine, runs: usize) -> Result<Self> {
+        if runs == 0 {
+            return Err(anyhow!("At least one run is needed"));
+        }
+        let timings = (0..runs)
+            .map(|_| engine.timed_snapshot())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::from_timings(&timings))
+    }
+
+    fn from_timings(timings: &[SnapshotTimings]) -> Self {
+        let phases = SnapshotTimings::PHASES
+            .iter()
+            .enumerate()
+            .map(|(i, &phase)| {
+                let mut samples: Vec<Duration> = timings.iter().map(|t| t.phases()[i]).collect();
+                samples.sort();
+                PhaseStats {
+                    phase,
+                    min_ms: ms(samples[0]),
+                    median_ms: ms(samples[samples.len() / 2]),
+                    max_ms: ms(samples[samples.len() - 1]),
+                }
+            })
+            .collect();
+        Self {
+            runs: timings.len(),
+            filters: timings.last().map_or(0, |t| t.filter_count),
+            phases,
+        }
+    }
+
+    /// Sum of the phase medians.
+    pub fn median_total_ms(&self) -> f64 {
+        self.phases.iter().map(|p| p.median_ms).sum()
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,117 @@
+#[cfg(windows)]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(windows)]
+use widestring::U16CString;
+#[cfg(windows)]
+use windows::{
+    core::PCWSTR,
+    Win32::System::Services::{
+        CloseServiceHandle, OpenSCManagerW, OpenServiceW, QueryServiceStatus, StartServiceW,
+        SC_HANDLE, SC_MANAGER_CONNECT, SERVICE_QUERY_STATUS, SERVICE_RUNNING, SERVICE_START,
+        SERVICE_START_PENDING, SERVICE_STATUS, SERVICE_STOPPED, SERVICE_STOP_PENDING,
+    },
+};
+
+/// State of the Base Filtering Engine service on this machine, as the
+/// Service Control Manager reports it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(windows), allow(dead_code))] // only read from the SCM on Windows
+pub enum BfeState {
+    Running,
+    Stopped,
+    Starting,
+    Stopping,
+    /// Paused or another state BFE does not normally enter.
+    Other(u32),
+}
+
+impl BfeState {
+    /// Whether the service is on its way to another state, so it is worth
+    /// looking again shortly.
+    pub fn is_pending(self) -> bool {
+        matches!(self, Self::Starting | Self::Stopping)
+    }
+
+    /// Banner text for a service that is not running.
+    pub fn describe(self) -> String {
+        match self {
+            Self::Running => "The Base Filtering Engine is running".into(),
+            Self::Stopped => "The Base Filtering Engine service is stopped. Filters cannot \
+                              be listed or changed, and Windows Firewall is not enforcing \
+                              rules."
+                .into(),
+            Self::Starting => "The Base Filtering Engine service is starting…".into(),
+            Self::Stopping => "The Base Filtering Engine service is stopping or restarting…".into(),
+            Self::Other(state) => format!("The Base Filtering Engine service is in state {state}"),
+        }
+    }
+}
+
+/// Asks the Service Control Manager for the state of BFE.
+#[cfg(windows)]
+pub fn state() -> Result<BfeState> {
+    let service = Service::open(SERVICE_QUERY_STATUS)?;
+    let mut status = SERVICE_STATUS::default();
+    unsafe { QueryServiceStatus(service.0, &mut status) }
+        .map_err(|e| anyhow!("Cannot query the BFE service: {e}"))?;
+    Ok(match status.dwCurrentState {
+        SERVICE_RUNNING => BfeState::Running,
+        SERVICE_STOPPED => BfeState::Stopped,
+        SERVICE_START_PENDING => BfeState::Starting,
+        SERVICE_STOP_PENDING => BfeState::Stopping,
+        other => BfeState::Other(other.0),
+    })
+}
+
+/// Starts BFE. This needs administrator rights and returns once the start
+/// has been requested; poll [`state`] to see it come up.
+#[cfg(windows)]
+pub fn start() -> Result<()> {
+    let service = Service::open(SERVICE_START)?;
+    unsafe { StartServiceW(service.0, None) }
+        .map_err(|e| anyhow!("Cannot start the BFE service: {e}"))
+}
+
+/// The simulated engine needs no service.
+#[cfg(not(windows))]
+pub fn state() -> Result<BfeState> {
+    Ok(BfeState::Running)
+}
+
+#[cfg(not(windows))]
+pub fn start() -> Result<()> {
+    Ok(())
+}
+
+/// The BFE service handle, closed on drop along with its manager.
+#[cfg(windows)]
+struct Service(SC_HANDLE, SC_HANDLE);
+
+#[cfg(windows)]
+impl Service {
+    fn open(access: u32) -> Result<Self> {
+        let manager = unsafe { OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_CONNECT) }
+            .map_err(|e| anyhow!("Cannot reach the Service Control Manager: {e}"))?;
+        let name = U16CString::from_str("BFE")?;
+        match unsafe { OpenServiceW(manager, PCWSTR(name.as_ptr()), access) } {
+            Ok(service) => Ok(Self(service, manager)),
+            Err(e) => {
+                unsafe {
+                    let _ = CloseServiceHandle(manager);
+                }
+                Err(anyhow!("Cannot open the BFE service: {e}"))
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Service {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseServiceHandle(self.0);
+            let _ = CloseServiceHandle(self.1);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    fmt,
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use thiserror::Error;
+
+use crate::wfp::{
+    protocol_name, Condition, ConditionField, ConditionValue, FilterSummary, MatchType, NetEvent,
+};
+
+/// Name of our pktmon filter as shown by `pktmon filter list`. pktmon can
+/// only remove all filters at once, so starting a capture clears any others.
+const PKTMON_FILTER_NAME: &str = "sls-wfp-capture";
+
+/// The 5-tuple parts a capture is narrowed to. pktmon accepts up to two
+/// addresses and two ports per filter; anything unset is captured in full.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CaptureScope {
+    pub protocol: Option<u8>,
+    pub addresses: Vec<IpAddr>,
+    pub ports: Vec<u16>,
+}
+
+impl CaptureScope {
+    pub fn from_event(event: &NetEvent) -> Self {
+        Self {
+            protocol: event.protocol,
+            addresses: [event.local_addr, event.remote_addr]
+                .into_iter()
+                .flatten()
+                .collect(),
+            ports: [event.local_port, event.remote_port]
+                .into_iter()
+                .flatten()
+                .collect(),
+        }
+    }
+
+    /// Scope from a filter's equality conditions. Ranges, masks and other
+    /// match types are left out, which widens the capture rather than
+    /// missing traffic.
+    pub fn from_filter(filter: &FilterSummary) -> Self {
+        let mut scope = Self::default();
+        for condition in &filter.conditions {
+            let Condition {
+                field,
+                match_type: MatchType::Equal,
+                value,
+            } = condition
+            else {
+                continue;
+            };
+            match (field, value) {
+                (ConditionField::IpProtocol, ConditionValue::Uint8(proto)) => {
+                    scope.protocol = Some(*proto)
+                }
+                (
+                    ConditionField::RemotePort | ConditionField::LocalPort,
+                    ConditionValue::Uint16(port),
+                ) if scope.ports.len() < 2 => scope.ports.push(*port),
+                (
+                    ConditionField::RemoteAddress | ConditionField::LocalAddress,
+                    ConditionValue::Uint32(addr),
+                ) if scope.addresses.len() < 2 => {
+                    scope.addresses.push(Ipv4Addr::from(*addr).into())
+                }
+                _ => {}
+            }
+        }
+        scope
+    }
+
+    fn pktmon_filter_args(&self) -> Vec<String> {
+        let mut args = vec!["filter".into(), "add".into(), PKTMON_FILTER_NAME.into()];
+        if let Some(proto) = self.protocol {
+            args.extend(["-t".into(), proto.to_string()]);
+        }
+        if !self.addresses.is_empty() {
+            args.push("-i".into());
+            args.extend(self.addresses.iter().take(2).map(ToString::to_string));
+        }
+        if !self.ports.is_empty() {
+            args.push("-p".into());
+            args.extend(self.ports.iter().take(2).map(ToString::to_string));
+        }
+        args
+    }
+
+    /// `netsh trace` filters for the ndiscap fallback. netsh only narrows
+    /// by protocol and address.
+    fn netsh_filter_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(proto) = self.protocol {
+            args.push(format!("Protocol={proto}"));
+        }
+        if !self.addresses.is_empty() {
+            let list: Vec<String> = self.addresses.iter().map(ToString::to_string).collect();
+            let key = if self.addresses.iter().all(IpAddr::is_ipv4) {
+                "IPv4.Address"
+            } else {
+                "IPv6.Address"
+            };
+            args.push(format!("{key}=({})", list.join(",")));
+        }
+        args
+    }
+}
+
+impl fmt::Display for CaptureScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(proto) = self.protocol {
+            parts.push(protocol_name(proto).to_string());
+        }
+        parts.extend(self.addresses.iter().map(ToString::to_string));
+        parts.extend(self.ports.iter().map(|p| format!("port {p}")));
+        if parts.is_empty() {
+            write!(f, "all traffic")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureTool {
+    Pktmon,
+    /// `netsh trace`, backed by ndiscap, for systems without pktmon.
+    Netsh,
+}
+
+/// A running packet capture. Call [`Capture::stop`] to finish it; the trace
+/// keeps running if the handle is simply dropped.
+pub struct Capture {
+    pub tool: CaptureTool,
+    pub scope: CaptureScope,
+    pub etl_path: PathBuf,
+}
+
+impl Capture {
+    /// Starts a capture scoped to `scope`, writing under `dir`. Uses pktmon
+    /// when present and falls back to `netsh trace`.
+    pub fn start(scope: CaptureScope, dir: &Path) -> Result<Self> {
+        let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+        let etl_path = dir.join(format!("sls-wfp-capture-{stamp}.etl"));
+
+        match run("pktmon", ["filter", "remove"]) {
+            Ok(()) => {
+                run("pktmon", scope.pktmon_filter_args())?;
+                let file = etl_path.to_string_lossy().into_owned();
+                run("pktmon", ["start", "--capture", "--file-name", &file])?;
+                Ok(Self {
+                    tool: CaptureTool::Pktmon,
+                    scope,
+                    etl_path,
+                })
+            }
+            Err(err) if is_not_found(&err) => {
+                let mut args = vec![
+                    "trace".to_string(),
+                    "start".into(),
+                    "capture=yes".into(),
+                    "report=disabled".into(),
+                    format!("tracefile={}", etl_path.display()),
+                ];
+                args.extend(scope.netsh_filter_args());
+                run("netsh", args)?;
+                Ok(Self {
+                    tool: CaptureTool::Netsh,
+                    scope,
+                    etl_path,
+                })
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Stops the capture and returns the file to open: a pcapng converted
+    /// from the pktmon trace, or the netsh ETL (open it with Network Monitor
+    /// or convert it with etl2pcapng).
+    pub fn stop(self) -> Result<PathBuf> {
+        match self.tool {
+            CaptureTool::Pktmon => {
+                run("pktmon", ["stop"])?;
+                let _ = run("pktmon", ["filter", "remove"]);
+                let pcap = self.etl_path.with_extension("pcapng");
+                let etl = self.etl_path.to_string_lossy().into_owned();
+                let out = pcap.to_string_lossy().into_owned();
+                run("pktmon", ["etl2pcap", &etl, "--out", &out])?;
+                Ok(pcap)
+            }
+            CaptureTool::Netsh => {
+                run("netsh", ["trace", "stop"])?;
+                Ok(self.etl_path)
+            }
+        }
+    }
+}
+
+/// Directory captures are written to by default.
+pub fn default_capture_dir() -> PathBuf {
+    std::env::temp_dir()
+}
+
+fn run<I, S>(program: &str, args: I) -> Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    let output = Command::new(program).args(args).output().map_err(|e| {
+        if e.kind() == ErrorKind::NotFound {
+            NotFound(program.to_string()).into()
+        } else {
+            anyhow!("Failed to run {program}: {e}")
+        }
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let detail = if stderr.trim().is_empty() {
+            stdout
+        } else {
+            stderr
+        };
+        bail!("{program} failed ({}): {}", output.status, detail.trim());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+#[error("{0} is not installed")]
+struct NotFound(String);
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<NotFound>().is_some()
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use crate::wfp::{NetEvent, NetEventKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SeriesKind {
+    /// Total blocked or allowed connections.
+    Verdict,
+    /// Connections decided by one rule.
+    Rule,
+    /// Connections made by one application.
+    App,
+}
+
+/// One line on the chart: a count per interval, zero-filled so gaps read as
+/// "nothing happened" rather than being interpolated across.
+pub struct Series {
+    pub kind: SeriesKind,
+    pub label: String,
+    /// `[bucket start as Unix seconds, count]`.
+    pub points: Vec<[f64; 2]>,
+    pub total: usize,
+}
+
+/// Bucket sizes offered in the chart, in seconds.
+pub const INTERVALS: [(i64, &str); 4] = [
+    (60, "1 minute"),
+    (300, "5 minutes"),
+    (3600, "1 hour"),
+    (86_400, "1 day"),
+];
+
+/// Counts `events` per `interval_secs` bucket: blocked and allowed totals,
+/// then one series per rule and per application, busiest first.
+/// `rule_label` names the rule behind a filter ID.
+pub fn build_series(
+    events: &[NetEvent],
+    interval_secs: i64,
+    rule_label: impl Fn(u64) -> String,
+) -> Vec<Series> {
+    let bucket_of = |event: &NetEvent| event.time.timestamp().div_euclid(interval_secs);
+    let (Some(first), Some(last)) = (
+        events.iter().map(bucket_of).min(),
+        events.iter().map(bucket_of).max(),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut groups: BTreeMap<(SeriesKind, String), BTreeMap<i64, usize>> = BTreeMap::new();
+    for event in events {
+        let bucket = bucket_of(event);
+        let verdict = match event.kind {
+            NetEventKind::Drop => "Blocked",
+            NetEventKind::Allow => "Allowed",
+        };
+        let mut count = |kind, label: String| {
+            *groups
+                .entry((kind, label))
+                .or_default()
+                .entry(bucket)
+                .or_default() += 1;
+        };
+        count(SeriesKind::Verdict, verdict.to_string());
+        count(SeriesKind::Rule, rule_label(event.filter_id));
+        if let Some(app) = &event.app_id {
+            count(SeriesKind::App, app_name(app).to_string());
+        }
+    }
+
+    let mut series: Vec<Series> = groups
+        .into_iter()
+        .map(|((kind, label), counts)| Series {
+            kind,
+            label,
+            total: counts.values().sum(),
+            points: (first..=last)
+                .map(|bucket| {
+                    [
+                        (bucket * interval_secs) as f64,
+                        counts.get(&bucket).copied().unwrap_or(0) as f64,
+                    ]
+                })
+                .collect(),
+        })
+        .collect();
+    series.sort_by(|a, b| a.kind.cmp(&b.kind).then(b.total.cmp(&a.total)));
+    series
+}
+
+/// Executable name from an app ID device path.
+pub fn app_name(app_id: &str) -> &str {
+    app_id.rsplit('\\').next().unwrap_or(app_id)
+}
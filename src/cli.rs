@@ -0,0 +1,2718 @@
+use std::{
+    ffi::OsStr,
+    io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::mpsc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use clap::{
+    builder::{PossibleValue, TypedValueParser},
+    error::ErrorKind,
+    Args, CommandFactory, Parser, Subcommand, ValueEnum,
+};
+use clap_complete::Shell;
+use serde::Serialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    alerts,
+    app_policy::AppPolicy,
+    bench::BenchReport,
+    capture::{self, Capture, CaptureScope},
+    coexistence::CoexistenceReport,
+    config,
+    diff::{RuleDiff, SnapshotDiff},
+    dns_lockdown::DnsLockdown,
+    event_export::{self, EventExportFormat},
+    event_store::EventStore,
+    flow_monitor::FlowMonitor,
+    hit_counters::{HitCounters, Hits},
+    hit_test::HitTest,
+    host_policy::HostPolicy,
+    importers::{self, FilterPresence},
+    installer_watch::{self, InstallerWatch},
+    interface_deny::{self, DeniedInterface, InterfaceDeny},
+    log_rotation,
+    naming::{self, NamingPolicy},
+    plugins,
+    preset::Preset,
+    profiles,
+    quic_block::QuicBlock,
+    rule_expr,
+    safety::{self, ConfirmPolicy, Safety},
+    schema::ValidationError,
+    scripting,
+    troubleshoot::Diagnosis,
+    tui,
+    unused::{self, UnusedReport},
+    watch::{self, WatchOptions},
+    wfp::{
+        self, blocked_system_ports, companion_rules, coverage_gaps, guid_from_uuid, parse_protocol,
+        uuid_from_guid, BulkEdit, Engine, FilterConfig, FilterRecord, FilterSummary, NetEvent,
+        NetEventQuery, RemotePorts, SavedSnapshot, TimeRange, WfpAction, WfpError,
+        FWPM_LAYER_ALE_AUTH_CONNECT_V4, FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+        FWPM_LAYER_ALE_AUTH_LISTEN_V4, FWPM_LAYER_ALE_AUTH_LISTEN_V6,
+        FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4, FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+        FWPM_LAYER_ALE_FLOW_ESTABLISHED_V4, FWPM_LAYER_ALE_FLOW_ESTABLISHED_V6,
+        FWPM_LAYER_INBOUND_TRANSPORT_V4, FWPM_LAYER_INBOUND_TRANSPORT_V6,
+        FWPM_LAYER_OUTBOUND_TRANSPORT_V4, FWPM_LAYER_OUTBOUND_TRANSPORT_V6, GUID,
+    },
+};
+
+const AFTER_HELP: &str = "\
+Starts the GUI when no command is given.
+
+With --output json every command prints one JSON object to stdout:
+{\"version\":1,\"ok\":true,\"command\":...,\"data\":...} on success, or
+{\"version\":1,\"ok\":false,\"command\":...,\"error\":{\"class\":...,\"exit_code\":...,
+\"message\":...}} on failure.
+
+Exit status:
+  0  Success; for diff, the files have the same rules
+  1  diff found differences
+  2  The command failed for another reason
+  3  Invalid command line
+  4  Access denied; run from an elevated prompt
+  5  The Base Filtering Engine service is not running
+  6  The rule file failed validation";
+
+/// Version of the `--output json` envelope. Bumped only for changes that
+/// break existing consumers; new fields may appear at any time.
+const JSON_VERSION: u32 = 1;
+
+/// `diff` exit status when the rule sets differ, as with diff(1).
+const EXIT_DIFFERENT: u8 = 1;
+
+/// Events listed by `events` and `history` when no count is given.
+const DEFAULT_EVENT_COUNT: usize = 20;
+
+#[derive(Parser)]
+#[command(
+    name = "sls_wfp_gui",
+    version,
+    about = "Manage Windows Filtering Platform rules",
+    after_help = AFTER_HELP
+)]
+struct Cli {
+    /// Print results as text or as a versioned JSON envelope
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    output: Output,
+
+    /// Only enumerate: refuse every change to the filtering engine. Given
+    /// alone, starts the GUI with its editing controls disabled
+    #[arg(long, global = true)]
+    read_only: bool,
+
+    /// Start the interactive terminal UI instead of the GUI
+    #[arg(long, exclusive = true)]
+    tui: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List all filters with their keys
+    List {
+        /// Only the boot-time policy active before BFE starts
+        #[arg(long)]
+        boot_time: bool,
+        /// Only filters at this layer
+        #[arg(long, value_enum)]
+        layer: Option<LayerArg>,
+        /// Read a saved snapshot instead of this machine's engine
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+    },
+    /// Save every filter, provider, sublayer and layer to a file, to be
+    /// analyzed offline on another machine
+    Snapshot { file: PathBuf },
+    /// Compare a saved snapshot with a later one, or with this machine now,
+    /// and list the filters each provider added, removed or changed
+    SnapshotDiff {
+        old: PathBuf,
+        /// Defaults to this machine's engine now
+        new: Option<PathBuf>,
+    },
+    /// Take a snapshot, wait while software is installed, then list what
+    /// changed and the filters other software added
+    InstallerWatch {
+        /// Add copies of the added filters as our own rules
+        #[arg(long)]
+        adopt: bool,
+        /// Write the added filters to a JSON file
+        #[arg(long)]
+        export: Option<PathBuf>,
+        /// Delete the added filters
+        #[arg(long, conflicts_with = "adopt")]
+        delete: bool,
+    },
+    /// Export owned filters as JSON (stdout by default)
+    Export { file: Option<PathBuf> },
+    /// Compare two rule files and list added, removed and changed rules
+    Diff { old: PathBuf, new: PathBuf },
+    /// Import a JSON, YAML or TOML rule file, or a simplewall or TinyWall export
+    Import { file: PathBuf },
+    /// Compare the filters in `netsh wfp show filters` output from another
+    /// machine with this one's
+    NetshCompare {
+        file: PathBuf,
+        /// Add the filters missing here as our own
+        #[arg(long)]
+        adopt: bool,
+    },
+    /// Add a rule written as an expression, e.g. `block out tcp to 10.0.0.0/8 port 445`
+    Add {
+        /// Sublayer of ours to put the rule in
+        #[arg(long, value_parser = parse_key, value_name = "KEY")]
+        sublayer: Option<GUID>,
+        /// Only report how many saved net events the rule would have matched
+        /// over the last N days (7 when no N is given); nothing is added
+        #[arg(long, value_name = "DAYS", num_args = 0..=1, default_missing_value = "7")]
+        test: Option<u32>,
+        /// With a block on ports alone, also add the rules blocking it over
+        /// IPv6 and inbound
+        #[arg(long)]
+        companions: bool,
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        expression: Vec<String>,
+    },
+    /// Rewrite an owned rule identified by its key
+    Update {
+        #[arg(value_parser = parse_key)]
+        key: GUID,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, visible_alias = "ports", value_name = "PORT[,PORT...]")]
+        port: Option<RemotePorts>,
+        #[arg(long, value_enum)]
+        action: Option<ActionArg>,
+    },
+    /// Change the action, weight, persistence or sublayer of several owned
+    /// rules in one transaction
+    BulkEdit {
+        /// Rule keys; every filter of each rule is changed
+        #[arg(required = true, value_parser = parse_key, value_name = "KEY")]
+        keys: Vec<GUID>,
+        #[arg(long, value_enum)]
+        action: Option<ActionArg>,
+        #[arg(long)]
+        weight: Option<u64>,
+        #[arg(long, value_enum)]
+        persistent: Option<SwitchArg>,
+        /// Sublayer of ours to move the rules to
+        #[arg(long, value_parser = parse_key, value_name = "KEY")]
+        sublayer: Option<GUID>,
+    },
+    /// Delete an owned filter by key or runtime ID
+    Delete {
+        #[arg(value_name = "KEY|ID")]
+        target: String,
+    },
+    /// List the most recent net events, newest first
+    #[command(args_conflicts_with_subcommands = true)]
+    Events {
+        #[command(subcommand)]
+        command: Option<EventsCommand>,
+        /// How many events to list
+        #[arg(default_value_t = DEFAULT_EVENT_COUNT)]
+        count: usize,
+        #[command(flatten)]
+        filters: EventFilters,
+    },
+    /// List net events saved to the on-disk history
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+        /// How many events to list
+        #[arg(default_value_t = DEFAULT_EVENT_COUNT)]
+        count: usize,
+        #[command(flatten)]
+        filters: EventFilters,
+    },
+    /// Explain the verdict of event N from `events`
+    Why {
+        n: usize,
+        #[command(flatten)]
+        filters: EventFilters,
+    },
+    /// Capture packets matching an event's 5-tuple or a rule's conditions
+    /// until Enter is pressed
+    Capture {
+        /// Event number from `events`
+        #[arg(
+            long,
+            value_name = "N",
+            required_unless_present = "rule",
+            conflicts_with = "rule"
+        )]
+        event: Option<usize>,
+        /// Rule key
+        #[arg(long, value_name = "KEY", value_parser = parse_key)]
+        rule: Option<GUID>,
+    },
+    /// Report other firewall products that can override our block rules
+    Coexistence,
+    /// Show how often each owned rule fired since its counter was reset,
+    /// counting BFE's recent net events first
+    Hits {
+        #[command(subcommand)]
+        command: Option<HitsCommand>,
+        /// Only list rules without a hit in this many days
+        #[arg(long, value_name = "DAYS")]
+        idle: Option<i64>,
+    },
+    /// List owned rules without hits in a window, and rules whose
+    /// hostnames no longer resolve to the addresses they were installed with
+    Unused {
+        /// Window without hits
+        #[arg(long, default_value_t = unused::DEFAULT_DAYS)]
+        days: u32,
+        /// Remove every rule listed
+        #[arg(long)]
+        remove: bool,
+    },
+    /// List every filter, from any provider, that could match an
+    /// executable's traffic, in arbitration order
+    AppPolicy {
+        /// Path of the executable
+        app: String,
+        /// Read a saved snapshot instead of this machine's engine
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+    },
+    /// List every filter, from any provider, whose remote address
+    /// conditions refer to a host, in arbitration order
+    HostPolicy {
+        /// Address or hostname
+        host: String,
+        /// Read a saved snapshot instead of this machine's engine
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+    },
+    /// Show whether DNS lockdown is on and the filters it installed
+    Dns {
+        #[command(subcommand)]
+        command: Option<DnsCommand>,
+    },
+    /// Show whether outbound QUIC (UDP 443) is blocked
+    Quic {
+        #[command(subcommand)]
+        command: Option<QuicCommand>,
+    },
+    /// Show whether established connections are recorded as allow events
+    Monitor {
+        #[command(subcommand)]
+        command: Option<MonitorCommand>,
+    },
+    /// List the network adapters and which of them deny traffic by default
+    Interfaces {
+        #[command(subcommand)]
+        command: Option<InterfacesCommand>,
+    },
+    /// Show or change whether destructive actions ask first and whether
+    /// rules blocking all traffic are refused while an RDP or SSH session
+    /// would be cut off
+    Safety {
+        /// Ask before destructive actions, or act at once
+        #[arg(long, value_enum)]
+        mode: Option<ModeArg>,
+        /// The remote lockout guard
+        #[arg(long, value_enum)]
+        lockout_guard: Option<SwitchArg>,
+        /// Permitting the RDP client while imports into the GUI are checked
+        #[arg(long, value_enum)]
+        peer_permit: Option<SwitchArg>,
+    },
+    /// Show or change the naming policy rule names must follow, and list
+    /// our filters breaking it
+    Naming {
+        /// Text every name starts with; empty for none
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Longest name allowed; 0 for no limit
+        #[arg(long)]
+        max_length: Option<usize>,
+        /// Characters no name may contain; empty for none
+        #[arg(long)]
+        forbidden: Option<String>,
+        /// Rename our filters breaking the policy
+        #[arg(long)]
+        migrate: bool,
+    },
+    /// Time each phase of enumerating the engine over several runs
+    Bench {
+        /// How many enumerations to time
+        #[arg(long, default_value_t = 5)]
+        runs: usize,
+    },
+    /// Run a rhai rule automation script once
+    Script { file: PathBuf },
+    /// List our sublayers, which keep rule groups apart in arbitration
+    Sublayers {
+        #[command(subcommand)]
+        command: Option<SublayersCommand>,
+    },
+    /// List our provider contexts, the data rules hand their callouts
+    Contexts {
+        #[command(subcommand)]
+        command: Option<ContextsCommand>,
+    },
+    /// List our callouts, the ones a companion driver registers against
+    Callouts {
+        #[command(subcommand)]
+        command: Option<CalloutsCommand>,
+    },
+    /// List saved rule profiles
+    Profiles {
+        #[command(subcommand)]
+        command: Option<ProfilesCommand>,
+    },
+    /// List external rule sources
+    Sources {
+        #[command(subcommand)]
+        command: Option<SourcesCommand>,
+    },
+    /// Stay running: repair tampered rules, run scheduled scripts and
+    /// record, forward and alert on net events. Logs to stdout as text
+    Watch {
+        /// Rule file to enforce instead of the owned rules at startup
+        #[arg(long, value_name = "FILE")]
+        rules: Option<PathBuf>,
+        /// Seconds between rule checks
+        #[arg(long, value_name = "SECS", default_value_t = 30)]
+        interval: u64,
+        /// Only log rule changes; do not undo them
+        #[arg(long)]
+        no_repair: bool,
+    },
+    /// Print a shell completion script
+    Completions { shell: Shell },
+}
+
+#[derive(Subcommand)]
+enum EventsCommand {
+    /// Export net events to JSON Lines or CSV
+    Export {
+        file: PathBuf,
+        /// Defaults to the file extension
+        #[arg(long, value_enum)]
+        format: Option<FormatArg>,
+        #[command(flatten)]
+        filters: EventFilters,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Save new net events from BFE to the history
+    Collect,
+    /// Delete every saved net event
+    Purge {
+        /// Also delete the alert log and its rotated files
+        #[arg(long)]
+        logs: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum HitsCommand {
+    /// Start hit counters afresh
+    Reset {
+        /// Only reset this rule's counter
+        #[arg(long, value_name = "KEY", value_parser = parse_key)]
+        rule: Option<GUID>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SublayersCommand {
+    /// Add a sublayer; higher weights are evaluated first
+    Add {
+        name: String,
+        #[arg(long)]
+        weight: u16,
+    },
+    /// Delete a sublayer; refused while filters use it unless --cascade
+    Delete {
+        #[arg(value_parser = parse_key)]
+        key: GUID,
+        /// Also delete the filters in the sublayer
+        #[arg(long)]
+        cascade: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ContextsCommand {
+    /// Add a provider context holding the bytes of a file
+    Add {
+        name: String,
+        /// File whose bytes the callout reads
+        #[arg(long)]
+        data: PathBuf,
+    },
+    /// Delete a provider context; refused while filters carry it
+    Delete {
+        #[arg(value_parser = parse_key)]
+        key: GUID,
+    },
+}
+
+#[derive(Subcommand)]
+enum CalloutsCommand {
+    /// Add a callout for a driver built with KEY to register against
+    Register {
+        #[arg(value_parser = parse_key)]
+        key: GUID,
+        name: String,
+        #[arg(long, value_enum)]
+        layer: LayerArg,
+        #[arg(long, default_value = "")]
+        description: String,
+    },
+    /// Delete a callout; refused while filters use it
+    Unregister {
+        #[arg(value_parser = parse_key)]
+        key: GUID,
+    },
+}
+
+#[derive(Subcommand)]
+enum DnsCommand {
+    /// Limit outbound DNS to the allowed resolvers, replacing the current
+    /// lockdown; the settings are saved for next time
+    On {
+        /// Allowed resolver; repeat for several. Defaults to the saved ones
+        #[arg(long = "resolver", value_name = "ADDR")]
+        resolvers: Vec<IpAddr>,
+        /// Leave DNS over TLS (port 853) unrestricted
+        #[arg(long)]
+        allow_dot: bool,
+        /// Also block DNS over HTTPS to well-known public resolvers
+        #[arg(long)]
+        block_doh: bool,
+    },
+    /// Remove the DNS lockdown
+    Off,
+}
+
+#[derive(Subcommand)]
+enum MonitorCommand {
+    /// Record established connections as allow events without blocking
+    /// any; the settings are saved for next time
+    On {
+        /// Only monitor this application (full path); repeat for several.
+        /// Defaults to the saved ones, or every application
+        #[arg(long = "app", value_name = "PATH")]
+        apps: Vec<String>,
+        /// Monitor every application, even if some were saved
+        #[arg(long, conflicts_with = "apps")]
+        all: bool,
+    },
+    /// Remove the monitoring filters
+    Off,
+}
+
+#[derive(Subcommand)]
+enum QuicCommand {
+    /// Block outbound UDP 443 so browsers fall back to TCP and TLS; the
+    /// settings are saved for next time
+    On {
+        /// Only block this application (full path); repeat for several.
+        /// Defaults to the saved ones, or every application
+        #[arg(long = "app", value_name = "PATH")]
+        apps: Vec<String>,
+        /// Block every application, even if some were saved
+        #[arg(long, conflicts_with = "apps")]
+        all: bool,
+    },
+    /// Allow QUIC again
+    Off,
+}
+
+#[derive(Subcommand)]
+enum InterfacesCommand {
+    /// Block all traffic on adapters unless a rule allows it, e.g. on the
+    /// cellular one; the settings are saved for next time
+    Deny {
+        /// Adapter name or LUID; repeat for several. Defaults to the saved
+        /// ones
+        interfaces: Vec<String>,
+    },
+    /// Remove the default-deny from every adapter
+    Allow,
+}
+
+#[derive(Subcommand)]
+enum ProfilesCommand {
+    /// Save the owned rules, or a rule file, as a new profile
+    Create {
+        name: String,
+        /// Rule file to save instead of the owned rules
+        #[arg(long, value_name = "FILE")]
+        from: Option<PathBuf>,
+    },
+    /// Rename a profile
+    Rename { name: String, new_name: String },
+    /// Delete a profile
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+enum SourcesCommand {
+    /// Fetch and reconcile all sources, or just NAME
+    Sync { name: Option<String> },
+}
+
+/// Event filters shared by `events`, `events export`, `history` and `why`.
+#[derive(Args)]
+struct EventFilters {
+    /// Start of the time window: RFC 3339, YYYY-MM-DD[ HH:MM] in UTC, or an
+    /// age like 30m, 6h, 7d
+    #[arg(long, value_name = "TIME")]
+    from: Option<String>,
+    /// End of the time window, in the same forms as --from
+    #[arg(long, value_name = "TIME")]
+    to: Option<String>,
+    /// Full executable path, or part of the device path
+    #[arg(long, value_name = "PATH|TEXT")]
+    app: Option<String>,
+    /// Remote IP address
+    #[arg(long, value_name = "ADDR")]
+    remote: Option<IpAddr>,
+    /// Remote port
+    #[arg(long, value_name = "N")]
+    port: Option<u16>,
+    /// Local port
+    #[arg(long, value_name = "N")]
+    local_port: Option<u16>,
+    /// IP protocol name or number
+    #[arg(long, value_parser = ProtocolParser)]
+    protocol: Option<u8>,
+    /// Runtime ID of the filter that decided the event
+    #[arg(long, value_name = "ID")]
+    filter: Option<u64>,
+}
+
+impl EventFilters {
+    fn query(&self) -> Result<NetEventQuery> {
+        let range = TimeRange::parse(
+            self.from.as_deref().unwrap_or_default(),
+            self.to.as_deref().unwrap_or_default(),
+        )
+        .map_err(|e| usage(e.to_string()))?;
+        Ok(NetEventQuery {
+            range,
+            app: self.app.clone(),
+            remote_address: self.remote,
+            remote_port: self.port,
+            local_port: self.local_port,
+            protocol: self.protocol,
+            filter_id: self.filter,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ActionArg {
+    #[value(alias = "allow")]
+    Permit,
+    #[value(alias = "deny")]
+    Block,
+}
+
+impl From<ActionArg> for WfpAction {
+    fn from(action: ActionArg) -> Self {
+        match action {
+            ActionArg::Permit => WfpAction::Permit,
+            ActionArg::Block => WfpAction::Block,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ModeArg {
+    Confirm,
+    PowerUser,
+}
+
+impl From<ModeArg> for ConfirmPolicy {
+    fn from(mode: ModeArg) -> Self {
+        match mode {
+            ModeArg::Confirm => ConfirmPolicy::Confirm,
+            ModeArg::PowerUser => ConfirmPolicy::PowerUser,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SwitchArg {
+    On,
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum FormatArg {
+    #[value(alias = "json")]
+    Jsonl,
+    Csv,
+}
+
+impl From<FormatArg> for EventExportFormat {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Jsonl => EventExportFormat::JsonLines,
+            FormatArg::Csv => EventExportFormat::Csv,
+        }
+    }
+}
+
+/// The layers rules are usually written against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LayerArg {
+    AleAuthConnectV4,
+    AleAuthConnectV6,
+    AleAuthRecvAcceptV4,
+    AleAuthRecvAcceptV6,
+    AleAuthListenV4,
+    AleAuthListenV6,
+    AleFlowEstablishedV4,
+    AleFlowEstablishedV6,
+    OutboundTransportV4,
+    OutboundTransportV6,
+    InboundTransportV4,
+    InboundTransportV6,
+}
+
+impl LayerArg {
+    fn key(self) -> GUID {
+        match self {
+            LayerArg::AleAuthConnectV4 => FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+            LayerArg::AleAuthConnectV6 => FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+            LayerArg::AleAuthRecvAcceptV4 => FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+            LayerArg::AleAuthRecvAcceptV6 => FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+            LayerArg::AleAuthListenV4 => FWPM_LAYER_ALE_AUTH_LISTEN_V4,
+            LayerArg::AleAuthListenV6 => FWPM_LAYER_ALE_AUTH_LISTEN_V6,
+            LayerArg::AleFlowEstablishedV4 => FWPM_LAYER_ALE_FLOW_ESTABLISHED_V4,
+            LayerArg::AleFlowEstablishedV6 => FWPM_LAYER_ALE_FLOW_ESTABLISHED_V6,
+            LayerArg::OutboundTransportV4 => FWPM_LAYER_OUTBOUND_TRANSPORT_V4,
+            LayerArg::OutboundTransportV6 => FWPM_LAYER_OUTBOUND_TRANSPORT_V6,
+            LayerArg::InboundTransportV4 => FWPM_LAYER_INBOUND_TRANSPORT_V4,
+            LayerArg::InboundTransportV6 => FWPM_LAYER_INBOUND_TRANSPORT_V6,
+        }
+    }
+}
+
+/// Accepts a protocol name or number, offering the names for completion.
+#[derive(Clone)]
+struct ProtocolParser;
+
+impl TypedValueParser for ProtocolParser {
+    type Value = u8;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        _arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<u8, clap::Error> {
+        value
+            .to_str()
+            .and_then(|text| parse_protocol(text).ok())
+            .ok_or_else(|| clap::Error::new(ErrorKind::InvalidValue).with_cmd(cmd))
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            ["tcp", "udp", "icmp", "icmpv6"]
+                .into_iter()
+                .map(PossibleValue::new),
+        ))
+    }
+}
+
+/// How a command failed, and the exit status scripts can branch on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FailureClass {
+    Failed,
+    Usage,
+    NotElevated,
+    BfeUnavailable,
+    Validation,
+}
+
+impl FailureClass {
+    fn of(err: &anyhow::Error) -> Self {
+        if err.downcast_ref::<UsageError>().is_some() || err.downcast_ref::<clap::Error>().is_some()
+        {
+            FailureClass::Usage
+        } else if err.downcast_ref::<ValidationError>().is_some()
+            || err.downcast_ref::<serde_json::Error>().is_some()
+            || err.downcast_ref::<serde_yaml::Error>().is_some()
+            || err.downcast_ref::<toml::de::Error>().is_some()
+        {
+            FailureClass::Validation
+        } else {
+            match err.downcast_ref::<WfpError>() {
+                Some(e) if e.is_access_denied() => FailureClass::NotElevated,
+                Some(e) if e.is_bfe_unavailable() => FailureClass::BfeUnavailable,
+                _ => FailureClass::Failed,
+            }
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            FailureClass::Failed => "failed",
+            FailureClass::Usage => "usage",
+            FailureClass::NotElevated => "not_elevated",
+            FailureClass::BfeUnavailable => "bfe_unavailable",
+            FailureClass::Validation => "validation",
+        }
+    }
+
+    fn exit_code(self) -> u8 {
+        match self {
+            FailureClass::Failed => 2,
+            FailureClass::Usage => 3,
+            FailureClass::NotElevated => 4,
+            FailureClass::BfeUnavailable => 5,
+            FailureClass::Validation => 6,
+        }
+    }
+}
+
+/// A command line clap accepted but the command cannot use.
+#[derive(Debug, Error)]
+#[error("{0}")]
+struct UsageError(String);
+
+fn usage(message: impl Into<String>) -> anyhow::Error {
+    UsageError(message.into()).into()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Text,
+    Json,
+}
+
+impl Output {
+    /// Prints `data` in the JSON envelope, or runs `text` to print the
+    /// human-readable form.
+    fn emit<T: Serialize>(self, command: &str, data: &T, text: impl FnOnce()) -> Result<()> {
+        match self {
+            Output::Text => text(),
+            Output::Json => {
+                let envelope = json!({
+                    "version": JSON_VERSION,
+                    "ok": true,
+                    "command": command,
+                    "data": data,
+                });
+                println!("{}", serde_json::to_string_pretty(&envelope)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// [`Self::emit`] for a stream of items; `record` gives each one's JSON
+    /// form. Text is printed as the items arrive. JSON is one document, so
+    /// the items are gathered first, and an error mid-stream is reported by
+    /// the failure envelope alone.
+    fn emit_stream<T, R: Serialize>(
+        self,
+        command: &str,
+        items: impl Iterator<Item = Result<T>>,
+        record: impl Fn(T) -> R,
+        text: impl FnOnce(&mut dyn Iterator<Item = Result<T>>) -> Result<()>,
+    ) -> Result<()> {
+        let mut items = items;
+        match self {
+            Output::Text => text(&mut items),
+            Output::Json => {
+                let data = items
+                    .map(|item| item.map(&record))
+                    .collect::<Result<Vec<R>>>()?;
+                self.emit(command, &data, || {})
+            }
+        }
+    }
+
+    fn fail(self, command: &str, err: &anyhow::Error) -> ExitCode {
+        let class = FailureClass::of(err);
+        match (self, err.downcast_ref::<clap::Error>()) {
+            (Output::Text, Some(clap_err)) => {
+                let _ = clap_err.print();
+            }
+            (Output::Text, None) => eprintln!("Error: {err:?}"),
+            (Output::Json, _) => {
+                let envelope = json!({
+                    "version": JSON_VERSION,
+                    "ok": false,
+                    "command": command,
+                    "error": {
+                        "class": class.as_str(),
+                        "exit_code": class.exit_code(),
+                        "message": format!("{err:#}").trim_end(),
+                    },
+                });
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&envelope).unwrap_or_default()
+                );
+            }
+        }
+        ExitCode::from(class.exit_code())
+    }
+}
+
+/// Runs a command line and returns the process exit status; errors are
+/// reported here, on stderr or in the JSON envelope.
+pub fn run(args: &[String]) -> ExitCode {
+    let argv = std::iter::once("sls_wfp_gui").chain(args.iter().map(String::as_str));
+    let cli = match Cli::try_parse_from(argv) {
+        Ok(cli) => cli,
+        Err(err) => {
+            // Parsing failed, so look for --output by hand to report the
+            // error in the requested form.
+            let mut output = Output::Text;
+            let mut command = "help";
+            let mut iter = args.iter();
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "--output" if iter.next().is_some_and(|value| value == "json") => {
+                        output = Output::Json;
+                    }
+                    "--output=json" => output = Output::Json,
+                    arg if command == "help" && !arg.starts_with('-') => command = arg,
+                    _ => {}
+                }
+            }
+            if !err.use_stderr() {
+                // --help, help or --version.
+                let text = err.to_string();
+                return match output.emit("help", &json!({ "usage": text }), || {
+                    let _ = err.print();
+                }) {
+                    Ok(()) => ExitCode::SUCCESS,
+                    Err(err) => output.fail(command, &err),
+                };
+            }
+            return output.fail(command, &err.into());
+        }
+    };
+    let out = cli.output;
+    if cli.read_only {
+        wfp::set_read_only();
+    }
+    let Some(command) = cli.command else {
+        let result = if cli.tui {
+            tui::run()
+        } else {
+            Cli::command().print_help().map_err(Into::into)
+        };
+        return match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => Output::Text.fail("tui", &err),
+        };
+    };
+    let name = command.name();
+    match dispatch(command, out) {
+        Ok(code) => code,
+        Err(err) => out.fail(name, &err),
+    }
+}
+
+impl Command {
+    /// Name used as `command` in the JSON envelope.
+    fn name(&self) -> &'static str {
+        match self {
+            Command::List { .. } => "list",
+            Command::Snapshot { .. } => "snapshot",
+            Command::SnapshotDiff { .. } => "snapshot-diff",
+            Command::InstallerWatch { .. } => "installer-watch",
+            Command::Export { .. } => "export",
+            Command::Diff { .. } => "diff",
+            Command::Import { .. } => "import",
+            Command::Add { .. } => "add",
+            Command::Sublayers {
+                command: Some(SublayersCommand::Add { .. }),
+            } => "sublayers add",
+            Command::Sublayers {
+                command: Some(SublayersCommand::Delete { .. }),
+            } => "sublayers delete",
+            Command::Sublayers { command: None } => "sublayers",
+            Command::Contexts {
+                command: Some(ContextsCommand::Add { .. }),
+            } => "contexts add",
+            Command::Contexts {
+                command: Some(ContextsCommand::Delete { .. }),
+            } => "contexts delete",
+            Command::Contexts { command: None } => "contexts",
+            Command::Callouts {
+                command: Some(CalloutsCommand::Register { .. }),
+            } => "callouts register",
+            Command::Callouts {
+                command: Some(CalloutsCommand::Unregister { .. }),
+            } => "callouts unregister",
+            Command::Callouts { command: None } => "callouts",
+            Command::Update { .. } => "update",
+            Command::BulkEdit { .. } => "bulk-edit",
+            Command::Delete { .. } => "delete",
+            Command::Events {
+                command: Some(EventsCommand::Export { .. }),
+                ..
+            } => "events export",
+            Command::Events { .. } => "events",
+            Command::History {
+                command: Some(HistoryCommand::Collect),
+                ..
+            } => "history collect",
+            Command::History {
+                command: Some(HistoryCommand::Purge { .. }),
+                ..
+            } => "history purge",
+            Command::History { .. } => "history",
+            Command::Why { .. } => "why",
+            Command::Capture { .. } => "capture",
+            Command::Coexistence => "coexistence",
+            Command::Hits {
+                command: Some(HitsCommand::Reset { .. }),
+                ..
+            } => "hits reset",
+            Command::Hits { .. } => "hits",
+            Command::Unused { .. } => "unused",
+            Command::AppPolicy { .. } => "app-policy",
+            Command::NetshCompare { .. } => "netsh-compare",
+            Command::HostPolicy { .. } => "host-policy",
+            Command::Dns {
+                command: Some(DnsCommand::On { .. }),
+            } => "dns on",
+            Command::Dns {
+                command: Some(DnsCommand::Off),
+            } => "dns off",
+            Command::Dns { command: None } => "dns",
+            Command::Quic {
+                command: Some(QuicCommand::On { .. }),
+            } => "quic on",
+            Command::Quic {
+                command: Some(QuicCommand::Off),
+            } => "quic off",
+            Command::Quic { command: None } => "quic",
+            Command::Monitor {
+                command: Some(MonitorCommand::On { .. }),
+            } => "monitor on",
+            Command::Monitor {
+                command: Some(MonitorCommand::Off),
+            } => "monitor off",
+            Command::Monitor { command: None } => "monitor",
+            Command::Interfaces {
+                command: Some(InterfacesCommand::Deny { .. }),
+            } => "interfaces deny",
+            Command::Interfaces {
+                command: Some(InterfacesCommand::Allow),
+            } => "interfaces allow",
+            Command::Interfaces { command: None } => "interfaces",
+            Command::Safety { .. } => "safety",
+            Command::Naming { .. } => "naming",
+            Command::Bench { .. } => "bench",
+            Command::Script { .. } => "script",
+            Command::Profiles {
+                command: Some(ProfilesCommand::Create { .. }),
+            } => "profiles create",
+            Command::Profiles {
+                command: Some(ProfilesCommand::Rename { .. }),
+            } => "profiles rename",
+            Command::Profiles {
+                command: Some(ProfilesCommand::Delete { .. }),
+            } => "profiles delete",
+            Command::Profiles { command: None } => "profiles",
+            Command::Sources {
+                command: Some(SourcesCommand::Sync { .. }),
+            } => "sources sync",
+            Command::Sources { .. } => "sources",
+            Command::Watch { .. } => "watch",
+            Command::Completions { .. } => "completions",
+        }
+    }
+}
+
+fn dispatch(command: Command, out: Output) -> Result<ExitCode> {
+    let result = match command {
+        Command::Diff { old, new } => {
+            return diff(&old, &new, out).map(|same| {
+                if same {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::from(EXIT_DIFFERENT)
+                }
+            })
+        }
+        Command::SnapshotDiff { old, new } => {
+            return snapshot_diff(&old, new.as_deref(), out).map(|same| {
+                if same {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::from(EXIT_DIFFERENT)
+                }
+            })
+        }
+        Command::InstallerWatch {
+            adopt,
+            export,
+            delete,
+        } => installer_watch(adopt, export.as_deref(), delete, out),
+        Command::List {
+            boot_time,
+            layer,
+            snapshot,
+        } => list(boot_time, layer, snapshot.as_deref(), out),
+        Command::Snapshot { file } => save_snapshot(&file, out),
+        Command::Export { file } => export(file.as_deref(), out),
+        Command::Import { file } => import(&file, out),
+        Command::NetshCompare { file, adopt } => netsh_compare(&file, adopt, out),
+        Command::Add {
+            sublayer,
+            test: Some(days),
+            expression,
+            ..
+        } => test_rule(sublayer, &expression, days, out),
+        Command::Add {
+            sublayer,
+            test: None,
+            companions,
+            expression,
+        } => add(sublayer, companions, &expression, out),
+        Command::Sublayers { command: None } => sublayers(out),
+        Command::Sublayers {
+            command: Some(SublayersCommand::Add { name, weight }),
+        } => add_sublayer(&name, weight, out),
+        Command::Sublayers {
+            command: Some(SublayersCommand::Delete { key, cascade }),
+        } => delete_sublayer(key, cascade, out),
+        Command::Contexts { command: None } => provider_contexts(out),
+        Command::Contexts {
+            command: Some(ContextsCommand::Add { name, data }),
+        } => add_provider_context(&name, &data, out),
+        Command::Contexts {
+            command: Some(ContextsCommand::Delete { key }),
+        } => delete_provider_context(key, out),
+        Command::Callouts { command: None } => callouts(out),
+        Command::Callouts {
+            command:
+                Some(CalloutsCommand::Register {
+                    key,
+                    name,
+                    layer,
+                    description,
+                }),
+        } => register_callout(key, &name, layer, &description, out),
+        Command::Callouts {
+            command: Some(CalloutsCommand::Unregister { key }),
+        } => unregister_callout(key, out),
+        Command::Update {
+            key,
+            name,
+            port,
+            action,
+        } => update(key, name, port, action.map(WfpAction::from), out),
+        Command::BulkEdit {
+            keys,
+            action,
+            weight,
+            persistent,
+            sublayer,
+        } => {
+            let edit = BulkEdit {
+                action: action.map(WfpAction::from),
+                weight,
+                persistent: persistent.map(|p| p == SwitchArg::On),
+                sublayer,
+            };
+            bulk_edit(&keys, &edit, out)
+        }
+        Command::Delete { target } => delete(&target, out),
+        Command::Events {
+            command:
+                Some(EventsCommand::Export {
+                    file,
+                    format,
+                    filters,
+                }),
+            ..
+        } => export_events(&file, format.map(EventExportFormat::from), &filters, out),
+        Command::Events { count, filters, .. } => events(count, &filters, out),
+        Command::History {
+            command: Some(HistoryCommand::Collect),
+            ..
+        } => collect_history(out),
+        Command::History {
+            command: Some(HistoryCommand::Purge { logs }),
+            ..
+        } => purge_history(logs, out),
+        Command::History { count, filters, .. } => history(count, &filters, out),
+        Command::Why { n, filters } => why(n, &filters, out),
+        Command::Capture { event, rule } => capture(event, rule, out),
+        Command::Coexistence => coexistence(out),
+        Command::Hits {
+            command: Some(HitsCommand::Reset { rule }),
+            ..
+        } => reset_hits(rule, out),
+        Command::Hits { idle, .. } => hits(idle, out),
+        Command::Unused { days, remove } => unused_rules(days, remove, out),
+        Command::AppPolicy { app, snapshot } => app_policy(&app, snapshot.as_deref(), out),
+        Command::HostPolicy { host, snapshot } => host_policy(&host, snapshot.as_deref(), out),
+        Command::Dns { command: None } => dns_status(out),
+        Command::Dns {
+            command:
+                Some(DnsCommand::On {
+                    resolvers,
+                    allow_dot,
+                    block_doh,
+                }),
+        } => dns_on(resolvers, !allow_dot, block_doh, out),
+        Command::Dns {
+            command: Some(DnsCommand::Off),
+        } => dns_off(out),
+        Command::Quic { command: None } => quic_status(out),
+        Command::Quic {
+            command: Some(QuicCommand::On { apps, all }),
+        } => quic_on(apps, all, out),
+        Command::Quic {
+            command: Some(QuicCommand::Off),
+        } => quic_off(out),
+        Command::Monitor { command: None } => monitor_status(out),
+        Command::Monitor {
+            command: Some(MonitorCommand::On { apps, all }),
+        } => monitor_on(apps, all, out),
+        Command::Monitor {
+            command: Some(MonitorCommand::Off),
+        } => monitor_off(out),
+        Command::Interfaces { command: None } => interfaces_status(out),
+        Command::Interfaces {
+            command: Some(InterfacesCommand::Deny { interfaces }),
+        } => interfaces_deny(&interfaces, out),
+        Command::Interfaces {
+            command: Some(InterfacesCommand::Allow),
+        } => interfaces_allow(out),
+        Command::Safety {
+            mode,
+            lockout_guard,
+            peer_permit,
+        } => safety(mode, lockout_guard, peer_permit, out),
+        Command::Naming {
+            prefix,
+            max_length,
+            forbidden,
+            migrate,
+        } => naming(prefix, max_length, forbidden, migrate, out),
+        Command::Bench { runs } => bench(runs, out),
+        Command::Script { file } => script(&file, out),
+        Command::Profiles { command: None } => list_profiles(out),
+        Command::Profiles {
+            command: Some(ProfilesCommand::Create { name, from }),
+        } => create_profile(&name, from.as_deref(), out),
+        Command::Profiles {
+            command: Some(ProfilesCommand::Rename { name, new_name }),
+        } => rename_profile(&name, &new_name, out),
+        Command::Profiles {
+            command: Some(ProfilesCommand::Delete { name }),
+        } => delete_profile(&name, out),
+        Command::Sources { command: None } => sources(out),
+        Command::Sources {
+            command: Some(SourcesCommand::Sync { name }),
+        } => sync_sources(name.as_deref(), out),
+        Command::Watch {
+            rules,
+            interval,
+            no_repair,
+        } => watch::run(&WatchOptions {
+            rules,
+            interval: Duration::from_secs(interval.max(1)),
+            repair: !no_repair,
+        }),
+        Command::Completions { shell } => {
+            completions(shell);
+            Ok(())
+        }
+    };
+    result.map(|()| ExitCode::SUCCESS)
+}
+
+/// Completion scripts are shell code, not command output, so they are never
+/// wrapped in the JSON envelope.
+fn completions(shell: Shell) {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, name, &mut io::stdout());
+}
+
+/// Run-time filters are streamed from the engine, so output, text or JSON,
+/// starts before the enumeration finishes and memory stays flat on busy
+/// machines.
+fn list(
+    boot_time: bool,
+    layer: Option<LayerArg>,
+    snapshot: Option<&Path>,
+    out: Output,
+) -> Result<()> {
+    let engine;
+    let filters: Box<dyn Iterator<Item = Result<FilterSummary>>> = match snapshot {
+        Some(path) => {
+            let snapshot = SavedSnapshot::load(path)?.snapshot;
+            let filters = if boot_time {
+                snapshot.boot_time_filters
+            } else {
+                snapshot.filters
+            };
+            Box::new(filters.into_iter().map(Ok))
+        }
+        None => {
+            engine = Engine::open()?;
+            if boot_time {
+                Box::new(engine.snapshot()?.boot_time_filters.into_iter().map(Ok))
+            } else {
+                Box::new(engine.filters_iter()?)
+            }
+        }
+    };
+    let filters = filters.filter(|f| {
+        f.as_ref().map_or(true, |f| {
+            layer.is_none_or(|layer| f.layer_key == layer.key())
+        })
+    });
+    out.emit_stream(
+        "list",
+        filters,
+        |f| FilterRecord::from(&f),
+        |filters| {
+            println!(
+                "{:>10}  {:<36}  {:<7}  {:>5}  {:<5}  NAME",
+                "ID", "RULE KEY", "ACTION", "PORT", "OWNED"
+            );
+            for filter in filters {
+                let filter = filter?;
+                println!(
+                    "{:>10}  {:<36}  {:<7}  {:>5}  {:<5}  {}",
+                    filter.id,
+                    uuid_from_guid(filter.rule_key()),
+                    filter.action.as_str(),
+                    filter
+                        .remote_port
+                        .map(|p| p.to_string())
+                        .unwrap_or_else(|| "-".into()),
+                    if filter.owned_by_app { "yes" } else { "no" },
+                    filter.name,
+                );
+            }
+            Ok(())
+        },
+    )
+}
+
+fn export(path: Option<&Path>, out: Output) -> Result<()> {
+    let json = Engine::open()?.export_owned_filters()?;
+    match path {
+        Some(path) => {
+            std::fs::write(path, json)
+                .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))?;
+            out.emit("export", &json!({ "path": path }), || {
+                println!("Exported owned filters to {}", path.display())
+            })
+        }
+        None => {
+            let rules: Value = serde_json::from_str(&json)?;
+            out.emit("export", &rules, || println!("{json}"))
+        }
+    }
+}
+
+fn save_snapshot(path: &Path, out: Output) -> Result<()> {
+    let saved = SavedSnapshot::take(&Engine::open()?, None)?;
+    saved.save(path)?;
+    out.emit(
+        "snapshot",
+        &json!({
+            "path": path,
+            "machine": saved.machine,
+            "taken": saved.taken,
+            "filters": saved.snapshot.filters.len(),
+            "boot_time_filters": saved.snapshot.boot_time_filters.len(),
+        }),
+        || {
+            println!(
+                "Saved {} filters ({} boot-time) from {} to {}",
+                saved.snapshot.filters.len(),
+                saved.snapshot.boot_time_filters.len(),
+                saved.machine,
+                path.display()
+            )
+        },
+    )
+}
+
+/// The snapshot saved at `path`, or this machine's engine now.
+fn saved_or_current(path: Option<&Path>) -> Result<SavedSnapshot> {
+    match path {
+        Some(path) => SavedSnapshot::load(path),
+        None => SavedSnapshot::take(&Engine::open()?, None),
+    }
+}
+
+/// Prints the filters that differ between the snapshot at `old` and the
+/// one at `new`, or this machine now, and returns whether none do.
+fn snapshot_diff(old: &Path, new: Option<&Path>, out: Output) -> Result<bool> {
+    let before = SavedSnapshot::load(old)?;
+    let after = saved_or_current(new)?;
+    let diff = SnapshotDiff::between(&before.snapshot, &after.snapshot);
+    let records = |filters: &[FilterSummary]| -> Vec<FilterRecord> {
+        filters.iter().map(FilterRecord::from).collect()
+    };
+    let data = json!({
+        "same": diff.is_empty(),
+        "before": { "machine": before.machine, "taken": before.taken },
+        "after": { "machine": after.machine, "taken": after.taken },
+        "added": records(&diff.added),
+        "removed": records(&diff.removed),
+        "changed": diff
+            .changed
+            .iter()
+            .map(|c| {
+                json!({
+                    "before": FilterRecord::from(&c.before),
+                    "after": FilterRecord::from(&c.after),
+                    "fields": c.fields(),
+                })
+            })
+            .collect::<Vec<_>>(),
+        "unchanged": diff.unchanged,
+    });
+    out.emit("snapshot-diff", &data, || {
+        println!("{} -> {}", before.label(), after.label());
+        print!("{diff}");
+    })?;
+    Ok(diff.is_empty())
+}
+
+fn installer_watch(adopt: bool, export: Option<&Path>, delete: bool, out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    let watch = InstallerWatch::start(&engine, None)?;
+    // The prompt is not part of the result, so keep it off stdout when that
+    // carries JSON.
+    let prompt = format!(
+        "Took a baseline of {} filters. Install the software, then press Enter.",
+        watch.baseline.snapshot.filters.len()
+    );
+    match out {
+        Output::Text => println!("{prompt}"),
+        Output::Json => eprintln!("{prompt}"),
+    }
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let changes = watch.finish(&engine)?;
+    if let Some(path) = export {
+        changes.export(path)?;
+    }
+    let adopted = if adopt {
+        installer_watch::adopt(&engine, &changes.added)?
+    } else {
+        0
+    };
+    let deleted = if delete {
+        installer_watch::delete(&engine, &changes.added)?
+    } else {
+        0
+    };
+    let diff = &changes.diff;
+    let data = json!({
+        "added": changes.added.iter().map(FilterRecord::from).collect::<Vec<_>>(),
+        "removed": diff.removed.iter().map(FilterRecord::from).collect::<Vec<_>>(),
+        "changed": diff
+            .changed
+            .iter()
+            .map(|c| json!({ "after": FilterRecord::from(&c.after), "fields": c.fields() }))
+            .collect::<Vec<_>>(),
+        "exported": export,
+        "adopted": adopted,
+        "deleted": deleted,
+    });
+    out.emit("installer-watch", &data, || {
+        print!("{diff}");
+        println!("{}.", changes.summary());
+        if let Some(path) = export {
+            println!("Exported them to {}.", path.display());
+        }
+        if adopt {
+            println!("Adopted {adopted} filter(s).");
+        }
+        if delete {
+            println!("Deleted {deleted} filter(s).");
+        }
+    })
+}
+
+fn import(path: &Path, out: Output) -> Result<()> {
+    let set = config::load_rule_set_file(path)?;
+    eprintln!("{}", set.describe());
+    let engine = Engine::open()?;
+    Safety::load()?.check_lockout_configs(&engine, &set.filters)?;
+    let summary = engine.import_rule_set(&set)?;
+    out.emit("import", &summary, || {
+        println!(
+            "Import complete: {} added, {} updated.",
+            summary.added, summary.updated
+        )
+    })
+}
+
+fn netsh_compare(path: &Path, adopt: bool, out: Output) -> Result<()> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let theirs = importers::netsh_filters(&text)?;
+    let engine = Engine::open()?;
+    let here = engine.snapshot()?.filters;
+    let compared: Vec<(FilterSummary, FilterPresence)> = theirs
+        .into_iter()
+        .map(|f| {
+            let presence = FilterPresence::of(&f, &here);
+            (f, presence)
+        })
+        .collect();
+    let mut adopted = 0;
+    if adopt {
+        for (filter, _) in compared
+            .iter()
+            .filter(|(_, p)| *p == FilterPresence::Missing)
+        {
+            engine.add_filter(&importers::adopt_filter(filter)?)?;
+            adopted += 1;
+        }
+    }
+    let filters: Vec<_> = compared
+        .iter()
+        .map(|(f, p)| json!({ "filter": FilterRecord::from(f), "presence": p }))
+        .collect();
+    out.emit(
+        "netsh-compare",
+        &json!({ "filters": filters, "adopted": adopted }),
+        || {
+            for (filter, presence) in &compared {
+                println!(
+                    "{:<13}  {:<28}  {:<7}  {} ({})",
+                    presence.as_str(),
+                    filter.layer,
+                    filter.action.as_str(),
+                    filter.name,
+                    filter.provider
+                );
+            }
+            if adopt {
+                println!("Adopted {adopted} filter(s).");
+            }
+        },
+    )
+}
+
+/// Adds the rule described by `args`. The shell has already removed the
+/// quotes, so arguments with spaces, such as app paths, are quoted again.
+/// Parses the words of a rule expression given as separate arguments.
+fn parse_rule_args(args: &[String]) -> Result<FilterConfig> {
+    let text = args
+        .iter()
+        .map(|arg| {
+            if arg.contains(char::is_whitespace) {
+                format!("\"{arg}\"")
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    rule_expr::parse(&text).map_err(|e| usage(e.to_string()))
+}
+
+fn add(sublayer: Option<GUID>, companions: bool, args: &[String], out: Output) -> Result<()> {
+    let mut config = parse_rule_args(args)?;
+    let key = Uuid::new_v4();
+    config.key = Some(key);
+    config.sublayer = sublayer.map(uuid_from_guid);
+    let engine = Engine::open()?;
+    for (kind, port) in blocked_system_ports(&config, &engine.system_ports()?) {
+        eprintln!(
+            "Warning: this blocks system port {port} ({}), which Windows services rely on.",
+            kind.label()
+        );
+    }
+    let mut rules = vec![config.clone()];
+    if companions {
+        rules.extend(companion_rules(&config));
+    } else {
+        for gap in coverage_gaps(&config) {
+            eprintln!(
+                "Warning: {}; add with --companions to block them too.",
+                gap.describe()
+            );
+        }
+    }
+    Safety::load()?.check_lockout_configs(&engine, &rules)?;
+    engine.import_filters(&rules)?;
+    out.emit("add", &rules, || {
+        for rule in &rules {
+            println!(
+                "Rule {} added: {}",
+                rule.key.unwrap_or(key),
+                rule_expr::format(rule)
+            );
+        }
+    })
+}
+
+fn test_rule(sublayer: Option<GUID>, args: &[String], days: u32, out: Output) -> Result<()> {
+    let mut config = parse_rule_args(args)?;
+    config.sublayer = sublayer.map(uuid_from_guid);
+    let report = HitTest::against_history(&config, days)?;
+    out.emit("add", &report, || {
+        println!("{}.", report.summary());
+        for (app, count) in report.apps.iter().take(10) {
+            println!("{count:>8}  {app}");
+        }
+        if !report.samples.is_empty() {
+            println!("Most recent matches:");
+            for event in &report.samples {
+                println!(
+                    "  {}  {:<7}  {}",
+                    event.time.format("%Y-%m-%d %H:%M:%S"),
+                    event.kind.as_str(),
+                    event.flow()
+                );
+            }
+        }
+    })
+}
+
+/// Prints the differences between two rule files and returns whether they
+/// hold the same rules.
+fn diff(before: &Path, after: &Path, out: Output) -> Result<bool> {
+    let before = config::load_rules_file(before)?;
+    let after = config::load_rules_file(after)?;
+    let diff = RuleDiff::between(&before, &after);
+    let data = json!({
+        "same": diff.is_empty(),
+        "added": diff.added,
+        "removed": diff.removed,
+        "changed": diff
+            .changed
+            .iter()
+            .map(|c| json!({ "before": c.before, "after": c.after, "fields": c.fields() }))
+            .collect::<Vec<_>>(),
+        "unchanged": diff.unchanged,
+    });
+    out.emit("diff", &data, || print!("{diff}"))?;
+    Ok(diff.is_empty())
+}
+
+fn update(
+    key: GUID,
+    name: Option<String>,
+    ports: Option<RemotePorts>,
+    action: Option<WfpAction>,
+    out: Output,
+) -> Result<()> {
+    let engine = Engine::open()?;
+    let members: Vec<FilterSummary> = engine
+        .snapshot()?
+        .filters
+        .into_iter()
+        .filter(|f| f.owned_by_app && f.rule_key() == key)
+        .collect();
+    let key = uuid_from_guid(key);
+    let current = members
+        .first()
+        .ok_or_else(|| anyhow!("Filter {key} not found"))?;
+    let name = name.unwrap_or_else(|| current.name.clone());
+    let action = action.unwrap_or(current.action);
+    let mut ports = ports.unwrap_or_else(|| {
+        RemotePorts::Many(members.iter().filter_map(|f| f.remote_port).collect())
+    });
+    ports.normalize();
+    if ports.as_slice().is_empty() {
+        return Err(usage(format!(
+            "Filter {key} has no remote port; pass --port"
+        )));
+    }
+
+    engine.update_filter_by_key(guid_from_uuid(key), &name, ports.as_slice(), action)?;
+    out.emit("update", &json!({ "key": key }), || {
+        println!("Filter {key} updated.")
+    })
+}
+
+fn bulk_edit(keys: &[GUID], edit: &BulkEdit, out: Output) -> Result<()> {
+    if edit.is_empty() {
+        return Err(usage(
+            "Nothing to change: give --action, --weight, --persistent or --sublayer",
+        ));
+    }
+    let engine = Engine::open()?;
+    let filters: Vec<FilterSummary> = engine
+        .snapshot()?
+        .filters
+        .into_iter()
+        .filter(|f| f.owned_by_app && keys.contains(&f.rule_key()))
+        .collect();
+    if let Some(missing) = keys
+        .iter()
+        .find(|k| !filters.iter().any(|f| f.rule_key() == **k))
+    {
+        return Err(anyhow!("Filter {} not found", uuid_from_guid(*missing)));
+    }
+    let builders = filters
+        .iter()
+        .map(|f| edit.apply(f))
+        .collect::<Result<Vec<_>>>()?;
+    Safety::load()?.check_lockout(&engine, &builders)?;
+    let changed = engine.bulk_edit(&filters, edit)?;
+    let what = edit.describe();
+    out.emit(
+        "bulk-edit",
+        &json!({ "rules": keys.len(), "filters": changed, "change": what }),
+        || {
+            println!(
+                "Set {what} on {changed} filter(s) of {} rule(s).",
+                keys.len()
+            )
+        },
+    )
+}
+
+fn delete(target: &str, out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    match target.parse::<u64>() {
+        Ok(id) => engine.delete_filter_by_id(id)?,
+        Err(_) => engine.delete_filter_by_key(parse_key(target)?)?,
+    }
+    out.emit("delete", &json!({ "target": target }), || {
+        println!("Filter {target} deleted.")
+    })
+}
+
+fn events(count: usize, filters: &EventFilters, out: Output) -> Result<()> {
+    let events = Engine::open()?.query_net_events(&filters.query()?)?;
+    print_events("events", &events, count, out)
+}
+
+fn history(count: usize, filters: &EventFilters, out: Output) -> Result<()> {
+    let store = EventStore::open(&EventStore::default_dir())?;
+    print_events("history", &store.query(&filters.query()?)?, count, out)
+}
+
+fn collect_history(out: Output) -> Result<()> {
+    let store = EventStore::open(&EventStore::default_dir())?;
+    let engine = Engine::open()?;
+    let events = engine.net_events()?;
+    let saved = store.append(&events)?;
+    HitCounters::add_events(&events, &engine.snapshot()?.filters)?;
+    let bytes = store.size()?;
+    out.emit(
+        "history collect",
+        &json!({ "saved": saved, "history_bytes": bytes }),
+        || println!("Saved {saved} new events ({bytes} bytes of history)"),
+    )
+}
+
+fn hits(idle: Option<i64>, out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    let filters = engine.snapshot()?.filters;
+    let counters = HitCounters::add_events(&engine.net_events()?, &filters)?;
+    let mut rules: Vec<(&FilterSummary, Hits)> = Vec::new();
+    for filter in filters.iter().filter(|f| f.owned_by_app) {
+        if rules.iter().all(|(f, _)| f.rule_key() != filter.rule_key()) {
+            rules.push((filter, counters.rule(filter.rule_key(), &filters)));
+        }
+    }
+    if let Some(days) = idle {
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
+        // Rules counted for less than `days` cannot have been idle that long.
+        rules.retain(|(_, hits)| hits.since <= cutoff && hits.last_hit.is_none_or(|t| t < cutoff));
+    }
+    rules.sort_by(|a, b| {
+        b.1.hits
+            .cmp(&a.1.hits)
+            .then_with(|| a.0.name.cmp(&b.0.name))
+    });
+    let data: Vec<Value> = rules
+        .iter()
+        .map(|(filter, hits)| {
+            json!({
+                "rule_key": uuid_from_guid(filter.rule_key()),
+                "name": filter.name,
+                "hits": hits.hits,
+                "last_hit": hits.last_hit,
+                "since": hits.since,
+            })
+        })
+        .collect();
+    out.emit("hits", &data, || {
+        for (filter, hits) in &rules {
+            println!(
+                "{:>8}  {}  {}",
+                hits.hits,
+                uuid_from_guid(filter.rule_key()),
+                filter.name
+            );
+            println!("          {}", hits.summary());
+        }
+    })
+}
+
+fn app_policy(app: &str, snapshot: Option<&Path>, out: Output) -> Result<()> {
+    let saved = saved_or_current(snapshot)?;
+    let policy = AppPolicy::build(app, &saved.snapshot.filters, &saved.sublayer_details)?;
+    let entries: Vec<_> = policy
+        .entries
+        .iter()
+        .map(|e| {
+            json!({
+                "filter": FilterRecord::from(&e.filter),
+                "scope": e.scope,
+                "sublayer_weight": e.sublayer_weight,
+                "shadowed": e.shadowed,
+            })
+        })
+        .collect();
+    out.emit(
+        "app-policy",
+        &json!({ "app": policy.app, "filters": entries }),
+        || {
+            println!("{}.", policy.summary());
+            for entry in &policy.entries {
+                let filter = &entry.filter;
+                println!(
+                    "{:<28}  {:<24}  {:<7}  #{} {} ({}; {}{})",
+                    filter.layer,
+                    filter.sublayer,
+                    filter.action.as_str(),
+                    filter.id,
+                    filter.name,
+                    filter.provider,
+                    entry.scope.as_str(),
+                    if entry.shadowed {
+                        ", never reached"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        },
+    )
+}
+
+fn host_policy(host: &str, snapshot: Option<&Path>, out: Output) -> Result<()> {
+    let saved = saved_or_current(snapshot)?;
+    let policy = HostPolicy::build(host, &saved.snapshot.filters, &saved.sublayer_details)?;
+    let entries: Vec<_> = policy
+        .entries
+        .iter()
+        .map(|e| {
+            json!({
+                "filter": FilterRecord::from(&e.filter),
+                "address": e.address,
+                "match": e.matched,
+                "condition": e.condition,
+                "sublayer_weight": e.sublayer_weight,
+            })
+        })
+        .collect();
+    out.emit(
+        "host-policy",
+        &json!({ "host": policy.host, "addresses": policy.addresses, "filters": entries }),
+        || {
+            println!("{}.", policy.summary());
+            for entry in &policy.entries {
+                let filter = &entry.filter;
+                println!(
+                    "{:<28}  {:<24}  {:<7}  #{} {} ({}; {}, {})",
+                    filter.layer,
+                    filter.sublayer,
+                    filter.action.as_str(),
+                    filter.id,
+                    filter.name,
+                    filter.provider,
+                    entry.condition,
+                    entry.matched.as_str()
+                );
+            }
+        },
+    )
+}
+
+fn unused_rules(days: u32, remove: bool, out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    let filters = engine.snapshot()?.filters;
+    HitCounters::add_events(&engine.net_events()?, &filters)?;
+    let report = UnusedReport::build(&filters, days)?;
+    let removed = if remove {
+        unused::remove_rules(&engine, &report.keys())?
+    } else {
+        0
+    };
+    out.emit(
+        "unused",
+        &json!({ "report": report, "removed": removed }),
+        || {
+            if report.is_empty() {
+                println!("Every owned rule is in use.");
+                return;
+            }
+            for rule in &report.idle {
+                println!(
+                    "idle   {}  {} ({})",
+                    rule.key,
+                    rule.name,
+                    rule.hits.summary()
+                );
+            }
+            for rule in &report.stale {
+                println!(
+                    "stale  {}  {} ({}): {}",
+                    rule.key,
+                    rule.name,
+                    rule.hosts.join(", "),
+                    rule.detail()
+                );
+            }
+            if remove {
+                println!("Removed {removed} rule(s).");
+            }
+        },
+    )
+}
+
+fn reset_hits(rule: Option<GUID>, out: Output) -> Result<()> {
+    let mut counters = HitCounters::load()?;
+    match rule {
+        Some(key) => counters.reset_rule(key, &Engine::open()?.snapshot()?.filters),
+        None => counters.reset_all(),
+    }
+    counters.save()?;
+    out.emit(
+        "hits reset",
+        &json!({ "rule": rule.map(uuid_from_guid) }),
+        || println!("Hit counters reset."),
+    )
+}
+
+fn purge_history(logs: bool, out: Output) -> Result<()> {
+    let history_bytes = EventStore::open(&EventStore::default_dir())?.purge()?;
+    let log_bytes = if logs {
+        log_rotation::purge(&alerts::alert_log_path())?
+    } else {
+        0
+    };
+    out.emit(
+        "history purge",
+        &json!({ "history_bytes": history_bytes, "log_bytes": log_bytes }),
+        || println!("Freed {} bytes", history_bytes + log_bytes),
+    )
+}
+
+/// Prints the newest `count` events, newest first.
+fn print_events(command: &str, events: &[NetEvent], count: usize, out: Output) -> Result<()> {
+    let newest: Vec<&NetEvent> = events.iter().rev().take(count).collect();
+    out.emit(command, &newest, || {
+        println!(
+            "{:>4}  {:<19}  {:<7}  {:<8}  {:>10}  FLOW",
+            "N", "TIME (UTC)", "VERDICT", "DIR", "FILTER ID"
+        );
+        for (idx, event) in newest.iter().enumerate() {
+            println!(
+                "{:>4}  {:<19}  {:<7}  {:<8}  {:>10}  {}",
+                idx,
+                event.time.format("%Y-%m-%d %H:%M:%S"),
+                event.kind.as_str(),
+                event.direction.map(|d| d.as_str()).unwrap_or("-"),
+                event.filter_id,
+                event.flow(),
+            );
+        }
+    })
+}
+
+fn export_events(
+    path: &Path,
+    format: Option<EventExportFormat>,
+    filters: &EventFilters,
+    out: Output,
+) -> Result<()> {
+    let query = filters.query()?;
+    let events = Engine::open()?.query_net_events(&query)?;
+    let count = event_export::export_events_file(&events, query.range, path, format)?;
+    out.emit(
+        "events export",
+        &json!({ "path": path, "count": count }),
+        || println!("Exported {count} events to {}", path.display()),
+    )
+}
+
+fn why(index: usize, filters: &EventFilters, out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    let events = engine.query_net_events(&filters.query()?)?;
+    let event = events
+        .iter()
+        .rev()
+        .nth(index)
+        .ok_or_else(|| anyhow!("No event {index}; only {} recorded", events.len()))?;
+    let snapshot = engine.snapshot()?;
+    let diagnosis = Diagnosis::explain(
+        event,
+        &snapshot.filters,
+        &snapshot.boot_time_filters,
+        &engine.sublayer_details()?,
+    );
+    let data = json!({
+        "event": diagnosis.event,
+        "deciding_filter": diagnosis.deciding.as_ref().map(FilterRecord::from),
+        "steps": diagnosis.steps,
+    });
+    out.emit("why", &data, || {
+        for (idx, step) in diagnosis.steps.iter().enumerate() {
+            println!("{}. {}", idx + 1, step.title);
+            for detail in &step.details {
+                println!("   {detail}");
+            }
+        }
+    })
+}
+
+fn capture(event: Option<usize>, rule: Option<GUID>, out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    let scope = match (event, rule) {
+        (Some(index), _) => {
+            let events = engine.net_events()?;
+            let event = events
+                .iter()
+                .rev()
+                .nth(index)
+                .ok_or_else(|| anyhow!("No event {index}; only {} recorded", events.len()))?;
+            CaptureScope::from_event(event)
+        }
+        (None, Some(key)) => {
+            let snapshot = engine.snapshot()?;
+            let filter = snapshot
+                .filters
+                .iter()
+                .find(|f| f.rule_key() == key)
+                .ok_or_else(|| anyhow!("Filter {} not found", uuid_from_guid(key)))?;
+            CaptureScope::from_filter(filter)
+        }
+        (None, None) => return Err(usage("capture requires --event N or --rule KEY")),
+    };
+
+    let capture = Capture::start(scope, &capture::default_capture_dir())?;
+    // The prompt is not part of the result, so keep it off stdout when that
+    // carries JSON.
+    let prompt = format!(
+        "Capturing {} to {}. Press Enter to stop.",
+        capture.scope,
+        capture.etl_path.display()
+    );
+    match out {
+        Output::Text => println!("{prompt}"),
+        Output::Json => eprintln!("{prompt}"),
+    }
+    let scope = capture.scope.to_string();
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let path = capture.stop()?;
+    out.emit("capture", &json!({ "scope": scope, "path": path }), || {
+        println!("Capture saved to {}", path.display())
+    })
+}
+
+fn coexistence(out: Output) -> Result<()> {
+    let report = CoexistenceReport::collect(&Engine::open()?)?;
+    let data = json!({
+        "our_weight": report.our_weight,
+        "sublayers": report
+            .sublayers
+            .iter()
+            .map(|s| json!({
+                "key": uuid_from_guid(s.info.key),
+                "name": s.info.name,
+                "provider": s.provider,
+                "weight": s.info.weight,
+                "standing": s.standing.as_str(),
+                "hard_permits": s.hard_permits,
+                "callout_filters": s.callout_filters,
+            }))
+            .collect::<Vec<_>>(),
+        "callouts": report
+            .callouts
+            .iter()
+            .map(|(c, provider)| json!({
+                "key": uuid_from_guid(c.key),
+                "id": c.id,
+                "name": c.name,
+                "provider": provider,
+            }))
+            .collect::<Vec<_>>(),
+        "warnings": report.warnings,
+    });
+    out.emit("coexistence", &data, || print!("{report}"))
+}
+
+fn dns_status(out: Output) -> Result<()> {
+    let settings = DnsLockdown::load()?;
+    let filters = Engine::open()?.snapshot()?.filters;
+    let members = DnsLockdown::members(&filters);
+    let data = json!({
+        "enabled": !members.is_empty(),
+        "settings": settings,
+        "filters": members.iter().map(|f| FilterRecord::from(*f)).collect::<Vec<_>>(),
+    });
+    out.emit("dns", &data, || {
+        if members.is_empty() {
+            println!("DNS lockdown is off.");
+        } else {
+            println!("DNS lockdown is on, {} filter(s):", members.len());
+            for filter in &members {
+                let conditions: Vec<String> =
+                    filter.conditions.iter().map(ToString::to_string).collect();
+                println!(
+                    "{:>10}  {:<7}  {}",
+                    filter.id,
+                    filter.action.as_str(),
+                    conditions.join(", ")
+                );
+            }
+        }
+        let resolvers: Vec<String> = settings.resolvers.iter().map(ToString::to_string).collect();
+        println!("Allowed resolvers: {}", resolvers.join(", "));
+    })
+}
+
+fn dns_on(resolvers: Vec<IpAddr>, restrict_dot: bool, block_doh: bool, out: Output) -> Result<()> {
+    let mut settings = DnsLockdown::load()?;
+    if !resolvers.is_empty() {
+        settings.resolvers = resolvers;
+    }
+    settings.restrict_dot = restrict_dot;
+    settings.block_doh = block_doh;
+    let added = settings.enable(&Engine::open()?)?;
+    settings.save()?;
+    out.emit("dns on", &json!({ "filters": added }), || {
+        println!("DNS lockdown on, {added} filter(s) installed.")
+    })
+}
+
+fn dns_off(out: Output) -> Result<()> {
+    DnsLockdown::disable(&Engine::open()?)?;
+    out.emit("dns off", &json!({}), || println!("DNS lockdown off."))
+}
+
+fn quic_status(out: Output) -> Result<()> {
+    let settings = QuicBlock::load()?;
+    let filters = Engine::open()?.snapshot()?.filters;
+    let members = QuicBlock::members(&filters);
+    let data = json!({
+        "enabled": !members.is_empty(),
+        "apps": settings.apps,
+        "filters": members.iter().map(|f| FilterRecord::from(*f)).collect::<Vec<_>>(),
+    });
+    out.emit("quic", &data, || {
+        if members.is_empty() {
+            println!("QUIC is allowed.");
+        } else if settings.apps.is_empty() {
+            println!("QUIC is blocked for every application.");
+        } else {
+            println!("QUIC is blocked for:");
+            for app in &settings.apps {
+                println!("  {app}");
+            }
+        }
+    })
+}
+
+fn quic_on(apps: Vec<String>, all: bool, out: Output) -> Result<()> {
+    let mut settings = QuicBlock::load()?;
+    if all {
+        settings.apps.clear();
+    } else if !apps.is_empty() {
+        settings.apps = apps;
+    }
+    let added = settings.enable(&Engine::open()?)?;
+    settings.save()?;
+    out.emit("quic on", &json!({ "filters": added }), || {
+        println!("QUIC blocked, {added} filter(s) installed.")
+    })
+}
+
+fn quic_off(out: Output) -> Result<()> {
+    QuicBlock::disable(&Engine::open()?)?;
+    out.emit("quic off", &json!({}), || println!("QUIC allowed again."))
+}
+
+fn monitor_status(out: Output) -> Result<()> {
+    let settings = FlowMonitor::load()?;
+    let engine = Engine::open()?;
+    let filters = engine.snapshot()?.filters;
+    let members = FlowMonitor::members(&filters);
+    let allow_events = engine
+        .engine_options()?
+        .keyword_names()
+        .contains(&"classify allow");
+    let data = json!({
+        "enabled": !members.is_empty(),
+        "allow_events": allow_events,
+        "apps": settings.apps,
+        "filters": members.iter().map(|f| FilterRecord::from(*f)).collect::<Vec<_>>(),
+    });
+    out.emit("monitor", &data, || {
+        if members.is_empty() {
+            println!("Connections are not monitored.");
+        } else if settings.apps.is_empty() {
+            println!("Connections of every application are monitored.");
+        } else {
+            println!("Connections are monitored for:");
+            for app in &settings.apps {
+                println!("  {app}");
+            }
+        }
+        if !members.is_empty() && !allow_events {
+            println!("Allow events are off, so nothing is recorded; run `monitor on` again.");
+        }
+    })
+}
+
+fn monitor_on(apps: Vec<String>, all: bool, out: Output) -> Result<()> {
+    let mut settings = FlowMonitor::load()?;
+    if all {
+        settings.apps.clear();
+    } else if !apps.is_empty() {
+        settings.apps = apps;
+    }
+    let added = settings.enable(&Engine::open()?)?;
+    settings.save()?;
+    out.emit("monitor on", &json!({ "filters": added }), || {
+        println!("Connections monitored, {added} filter(s) installed.")
+    })
+}
+
+fn monitor_off(out: Output) -> Result<()> {
+    FlowMonitor::disable(&Engine::open()?)?;
+    out.emit("monitor off", &json!({}), || {
+        println!("Connection monitoring stopped.")
+    })
+}
+
+fn safety(
+    mode: Option<ModeArg>,
+    lockout_guard: Option<SwitchArg>,
+    peer_permit: Option<SwitchArg>,
+    out: Output,
+) -> Result<()> {
+    let mut settings = Safety::load()?;
+    if let Some(mode) = mode {
+        settings.confirm = mode.into();
+    }
+    if let Some(guard) = lockout_guard {
+        settings.lockout_guard = guard == SwitchArg::On;
+    }
+    if let Some(permit) = peer_permit {
+        settings.peer_permit = permit == SwitchArg::On;
+    }
+    if mode.is_some() || lockout_guard.is_some() || peer_permit.is_some() {
+        settings.save()?;
+    }
+    let sessions = safety::remote_sessions()?;
+    let data = json!({
+        "mode": settings.confirm,
+        "lockout_guard": settings.lockout_guard,
+        "peer_permit": settings.peer_permit,
+        "rdp_client": safety::rdp_peer(),
+        "remote_sessions": sessions
+            .iter()
+            .map(|s| json!({ "protocol": s.protocol, "remote": s.remote }))
+            .collect::<Vec<_>>(),
+    });
+    out.emit("safety", &data, || {
+        println!("Mode:          {}", settings.confirm.label());
+        println!(
+            "Lockout guard: {}",
+            if settings.lockout_guard { "on" } else { "off" }
+        );
+        println!(
+            "RDP permit:    {}",
+            if settings.peer_permit { "on" } else { "off" }
+        );
+        for session in &sessions {
+            println!(
+                "Remote session: {} from {}",
+                session.protocol, session.remote
+            );
+        }
+    })
+}
+
+fn naming(
+    prefix: Option<String>,
+    max_length: Option<usize>,
+    forbidden: Option<String>,
+    migrate: bool,
+    out: Output,
+) -> Result<()> {
+    let mut policy = NamingPolicy::load()?;
+    let changed = prefix.is_some() || max_length.is_some() || forbidden.is_some();
+    if let Some(prefix) = prefix {
+        policy.prefix = prefix;
+    }
+    if let Some(max) = max_length {
+        policy.max_length = (max > 0).then_some(max);
+    }
+    if let Some(forbidden) = forbidden {
+        policy.forbidden = forbidden;
+    }
+    if changed {
+        policy.save()?;
+    }
+    let engine = Engine::open()?;
+    let renames = policy.renames(&engine.snapshot()?.filters);
+    let renamed = if migrate {
+        naming::migrate(&engine, &renames)?
+    } else {
+        0
+    };
+    let data = json!({
+        "prefix": policy.prefix,
+        "max_length": policy.max_length,
+        "forbidden": policy.forbidden,
+        "breaking": renames
+            .iter()
+            .map(|(f, name)| json!({ "key": uuid_from_guid(f.key), "name": f.name, "rename": name }))
+            .collect::<Vec<_>>(),
+        "renamed": renamed,
+    });
+    out.emit("naming", &data, || {
+        if policy.is_empty() {
+            println!("No naming policy: any name is allowed.");
+        } else {
+            println!("Prefix:     {:?}", policy.prefix);
+            match policy.max_length {
+                Some(max) => println!("Max length: {max}"),
+                None => println!("Max length: none"),
+            }
+            println!("Forbidden:  {:?}", policy.forbidden);
+        }
+        for (filter, name) in &renames {
+            println!(
+                "{} {:?} -> {name:?}",
+                uuid_from_guid(filter.key),
+                filter.name
+            );
+        }
+        if migrate {
+            println!("Renamed {renamed} filter(s).");
+        } else if !renames.is_empty() {
+            println!("Run with --migrate to rename them.");
+        }
+    })
+}
+
+fn interfaces_status(out: Output) -> Result<()> {
+    let settings = InterfaceDeny::load()?;
+    let adapters = interface_deny::interfaces()?;
+    let filters = Engine::open()?.snapshot()?.filters;
+    let members = InterfaceDeny::members(&filters);
+    let data = json!({
+        "enabled": !members.is_empty(),
+        "interfaces": adapters
+            .iter()
+            .map(|a| {
+                json!({
+                    "luid": a.luid,
+                    "name": a.name,
+                    "description": a.description,
+                    "media": a.media,
+                    "up": a.up,
+                    "denied": settings.is_denied(a.luid),
+                })
+            })
+            .collect::<Vec<_>>(),
+        "denied": settings.interfaces,
+        "filters": members.iter().map(|f| FilterRecord::from(*f)).collect::<Vec<_>>(),
+    });
+    out.emit("interfaces", &data, || {
+        println!(
+            "{:<20}  {:<24}  {:<12}  {:<4}  DEFAULT",
+            "LUID", "NAME", "TYPE", "UP"
+        );
+        for adapter in &adapters {
+            let denied = settings.is_denied(adapter.luid) && !members.is_empty();
+            println!(
+                "{:<20}  {:<24}  {:<12}  {:<4}  {}",
+                adapter.luid,
+                adapter.name,
+                adapter.media.map_or("other", |m| m.label()),
+                if adapter.up { "yes" } else { "no" },
+                if denied { "deny" } else { "allow" }
+            );
+        }
+        for missing in settings
+            .interfaces
+            .iter()
+            .filter(|i| !adapters.iter().any(|a| a.luid == i.luid))
+        {
+            println!("{:<20}  {:<24}  (not present)", missing.luid, missing.name);
+        }
+    })
+}
+
+fn interfaces_deny(names: &[String], out: Output) -> Result<()> {
+    let mut settings = InterfaceDeny::load()?;
+    if !names.is_empty() {
+        let adapters = interface_deny::interfaces()?;
+        settings.interfaces = names
+            .iter()
+            .map(|name| {
+                adapters
+                    .iter()
+                    .find(|a| a.name.eq_ignore_ascii_case(name) || a.luid.to_string() == *name)
+                    .map(|a| DeniedInterface {
+                        luid: a.luid,
+                        name: a.name.clone(),
+                    })
+                    .ok_or_else(|| usage(format!("No network adapter is named '{name}'")))
+            })
+            .collect::<Result<_>>()?;
+    }
+    let engine = Engine::open()?;
+    Safety::load()?.check_lockout(&engine, &settings.builders()?)?;
+    let added = settings.enable(&engine)?;
+    settings.save()?;
+    let names: Vec<&str> = settings
+        .interfaces
+        .iter()
+        .map(|i| i.name.as_str())
+        .collect();
+    out.emit(
+        "interfaces deny",
+        &json!({ "denied": settings.interfaces, "filters": added }),
+        || {
+            println!(
+                "Traffic on {} denied unless allowed, {added} filter(s) installed.",
+                names.join(", ")
+            )
+        },
+    )
+}
+
+fn interfaces_allow(out: Output) -> Result<()> {
+    InterfaceDeny::disable(&Engine::open()?)?;
+    out.emit("interfaces allow", &json!({}), || {
+        println!("Every adapter allows traffic by default again.")
+    })
+}
+
+fn bench(runs: usize, out: Output) -> Result<()> {
+    let report = BenchReport::run(&Engine::open()?, runs)?;
+    out.emit("bench", &report, || {
+        println!(
+            "{} run(s), {} filters; times in ms",
+            report.runs, report.filters
+        );
+        println!(
+            "{:<10}  {:>9}  {:>9}  {:>9}",
+            "PHASE", "MIN", "MEDIAN", "MAX"
+        );
+        for phase in &report.phases {
+            println!(
+                "{:<10}  {:>9.2}  {:>9.2}  {:>9.2}",
+                phase.phase, phase.min_ms, phase.median_ms, phase.max_ms
+            );
+        }
+        println!(
+            "{:<10}  {:>9}  {:>9.2}",
+            "total",
+            "",
+            report.median_total_ms()
+        );
+    })
+}
+
+fn script(path: &Path, out: Output) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let result = scripting::run_script(path, &sender);
+    drop(sender);
+    let lines: Vec<String> = receiver.into_iter().map(|output| output.line).collect();
+    // Print what the script managed to output before reporting its error.
+    if out == Output::Text {
+        for line in &lines {
+            println!("{line}");
+        }
+    }
+    result?;
+    out.emit("script", &json!({ "output": lines }), || {})
+}
+
+fn sublayers(out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    let filters = engine.snapshot()?.filters;
+    let sublayers: Vec<Value> = engine
+        .sublayer_details()?
+        .into_iter()
+        .filter(|s| s.ours)
+        .map(|s| {
+            json!({
+                "key": uuid_from_guid(s.key),
+                "name": s.name,
+                "weight": s.weight,
+                "filters": filters.iter().filter(|f| f.sublayer_key == s.key).count(),
+            })
+        })
+        .collect();
+    out.emit("sublayers", &sublayers, || {
+        for s in &sublayers {
+            println!(
+                "{}\t{}\t{} filter(s)\t{}",
+                s["key"].as_str().unwrap_or_default(),
+                s["weight"],
+                s["filters"],
+                s["name"].as_str().unwrap_or_default()
+            );
+        }
+    })
+}
+
+fn add_sublayer(name: &str, weight: u16, out: Output) -> Result<()> {
+    let key = uuid_from_guid(Engine::open()?.create_sublayer(name, weight)?);
+    out.emit("sublayers add", &json!({ "key": key }), || {
+        println!("Sublayer {key} added.")
+    })
+}
+
+fn delete_sublayer(key: GUID, cascade: bool, out: Output) -> Result<()> {
+    Engine::open()?.delete_sublayer(key, cascade)?;
+    let key = uuid_from_guid(key);
+    out.emit("sublayers delete", &json!({ "key": key }), || {
+        println!("Sublayer {key} deleted.")
+    })
+}
+
+fn provider_contexts(out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    let filters = engine.snapshot()?.filters;
+    let contexts: Vec<Value> = engine
+        .provider_contexts()?
+        .into_iter()
+        .filter(|c| c.ours)
+        .map(|c| {
+            json!({
+                "key": uuid_from_guid(c.key),
+                "name": c.name,
+                "bytes": c.data.as_ref().map_or(0, Vec::len),
+                "filters": filters
+                    .iter()
+                    .filter(|f| f.provider_context_key == Some(c.key))
+                    .count(),
+            })
+        })
+        .collect();
+    out.emit("contexts", &contexts, || {
+        for c in &contexts {
+            println!(
+                "{}\t{} byte(s)\t{} filter(s)\t{}",
+                c["key"].as_str().unwrap_or_default(),
+                c["bytes"],
+                c["filters"],
+                c["name"].as_str().unwrap_or_default()
+            );
+        }
+    })
+}
+
+fn add_provider_context(name: &str, data: &Path, out: Output) -> Result<()> {
+    let bytes =
+        std::fs::read(data).map_err(|e| anyhow!("Failed to read {}: {e}", data.display()))?;
+    let key = uuid_from_guid(Engine::open()?.create_provider_context(name, &bytes)?);
+    out.emit("contexts add", &json!({ "key": key }), || {
+        println!("Provider context {key} added.")
+    })
+}
+
+fn delete_provider_context(key: GUID, out: Output) -> Result<()> {
+    Engine::open()?.delete_provider_context(key)?;
+    let key = uuid_from_guid(key);
+    out.emit("contexts delete", &json!({ "key": key }), || {
+        println!("Provider context {key} deleted.")
+    })
+}
+
+fn callouts(out: Output) -> Result<()> {
+    let engine = Engine::open()?;
+    let filters = engine.snapshot()?.filters;
+    let callouts: Vec<Value> = engine
+        .callouts()?
+        .into_iter()
+        .filter(|c| c.ours)
+        .map(|c| {
+            json!({
+                "key": uuid_from_guid(c.key),
+                "name": c.name,
+                "description": c.description,
+                "layer": uuid_from_guid(c.layer_key),
+                "registered": c.registered,
+                "filters": filters
+                    .iter()
+                    .filter(|f| f.callout.is_some_and(|a| a.key == c.key))
+                    .count(),
+            })
+        })
+        .collect();
+    out.emit("callouts", &callouts, || {
+        for c in &callouts {
+            println!(
+                "{}\tlayer {}\t{}\t{} filter(s)\t{}",
+                c["key"].as_str().unwrap_or_default(),
+                c["layer"].as_str().unwrap_or_default(),
+                if c["registered"] == true {
+                    "driver loaded"
+                } else {
+                    "no driver"
+                },
+                c["filters"],
+                c["name"].as_str().unwrap_or_default()
+            );
+        }
+    })
+}
+
+fn register_callout(
+    key: GUID,
+    name: &str,
+    layer: LayerArg,
+    description: &str,
+    out: Output,
+) -> Result<()> {
+    Engine::open()?.register_callout(key, name, description, layer.key())?;
+    let key = uuid_from_guid(key);
+    out.emit("callouts register", &json!({ "key": key }), || {
+        println!("Callout {key} added.")
+    })
+}
+
+fn unregister_callout(key: GUID, out: Output) -> Result<()> {
+    Engine::open()?.unregister_callout(key)?;
+    let key = uuid_from_guid(key);
+    out.emit("callouts unregister", &json!({ "key": key }), || {
+        println!("Callout {key} deleted.")
+    })
+}
+
+fn list_profiles(out: Output) -> Result<()> {
+    let profiles = profiles::list()?;
+    out.emit("profiles", &profiles, || {
+        if profiles.is_empty() {
+            println!("No profiles in {}.", profiles::profiles_dir().display());
+        }
+        for profile in &profiles {
+            let rules = match profile.rules {
+                Some(count) => format!("{count} rule(s)"),
+                None => "invalid".into(),
+            };
+            println!("{}\t{rules}", profile.name);
+        }
+    })
+}
+
+fn create_profile(name: &str, from: Option<&Path>, out: Output) -> Result<()> {
+    let set = match from {
+        Some(file) => config::load_rule_set_file(file)?,
+        None => config::parse_rule_set(
+            &Engine::open()?.export_owned_filters()?,
+            config::RuleFormat::Json,
+        )?,
+    };
+    let path = profiles::create(name, &set)?;
+    out.emit("profiles create", &json!({ "path": path }), || {
+        println!("Saved {} rule(s) to {}", set.filters.len(), path.display())
+    })
+}
+
+fn rename_profile(name: &str, new_name: &str, out: Output) -> Result<()> {
+    let path = profiles::rename(name, new_name)?;
+    out.emit("profiles rename", &json!({ "path": path }), || {
+        println!("Renamed to {}", path.display())
+    })
+}
+
+fn delete_profile(name: &str, out: Output) -> Result<()> {
+    profiles::delete(name)?;
+    out.emit("profiles delete", &json!({ "name": name }), || {
+        println!("Profile '{name}' deleted.")
+    })
+}
+
+fn sources(out: Output) -> Result<()> {
+    let sources = plugins::load_sources()?;
+    out.emit("sources", &sources, || {
+        if sources.is_empty() {
+            println!("No rule sources configured.");
+        }
+        for source in &sources {
+            println!(
+                "{}\t{} {}",
+                source.name,
+                source.command.display(),
+                source.args.join(" ")
+            );
+        }
+    })
+}
+
+fn sync_sources(only: Option<&str>, out: Output) -> Result<()> {
+    let mut sources = plugins::load_sources()?;
+    let engine = Engine::open()?;
+    let mut synced = Vec::new();
+    for source in sources
+        .iter_mut()
+        .filter(|s| only.is_none_or(|name| s.name == name))
+    {
+        let summary = plugins::sync_source(&engine, source)?;
+        if out == Output::Text {
+            println!(
+                "{}: {} added, {} updated, {} removed.",
+                source.name, summary.added, summary.updated, summary.removed
+            );
+        }
+        synced.push(json!({ "source": source.name, "summary": summary }));
+    }
+    if synced.is_empty() {
+        bail!("No rule source named '{}'", only.unwrap_or_default());
+    }
+    out.emit("sources sync", &synced, || {})
+}
+
+pub(crate) fn parse_key(text: &str) -> Result<GUID> {
+    let trimmed = text.trim_matches(|c| c == '{' || c == '}');
+    Uuid::parse_str(trimmed)
+        .map(guid_from_uuid)
+        .map_err(|_| usage(format!("'{text}' is not a valid filter key")))
+}
+
+pub(crate) fn parse_action(text: &str) -> Result<WfpAction> {
+    match text.to_ascii_lowercase().as_str() {
+        "permit" | "allow" => Ok(WfpAction::Permit),
+        "block" | "deny" => Ok(WfpAction::Block),
+        _ => Err(usage("--action must be permit or block")),
+    }
+}
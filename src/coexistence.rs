@@ -0,0 +1,306 @@
+use std::{collections::HashMap, fmt};
+
+use anyhow::Result;
+
+use crate::wfp::{
+    CalloutInfo, Engine, FilterSummary, NamedGuid, Snapshot, SublayerInfo, WeightKind, WeightTier,
+    WfpAction, GUID, PROVIDER_KEY,
+};
+
+/// Where a foreign sublayer sits relative to ours in arbitration order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Standing {
+    /// Evaluated before ours.
+    Outranks,
+    /// Same weight as ours; BFE does not define which goes first.
+    Ties,
+    Below,
+}
+
+impl Standing {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Standing::Outranks => "outranks ours",
+            Standing::Ties => "ties ours",
+            Standing::Below => "below ours",
+        }
+    }
+}
+
+pub struct SublayerStanding {
+    pub info: SublayerInfo,
+    pub provider: String,
+    pub standing: Standing,
+    /// Permits that clear the action write right, across all layers.
+    pub hard_permits: usize,
+    /// Filters that hand the verdict to a callout, across all layers.
+    pub callout_filters: usize,
+}
+
+/// Other products' providers, sublayers and callouts, with an estimate of
+/// which of them can override our block rules.
+///
+/// A block in our sublayer loses when a sublayer evaluated earlier returns a
+/// hard permit, or a callout there does the equivalent. Plain permits in
+/// other sublayers cannot override our blocks.
+pub struct CoexistenceReport {
+    pub our_weight: Option<u16>,
+    pub providers: Vec<NamedGuid>,
+    pub sublayers: Vec<SublayerStanding>,
+    pub callouts: Vec<(CalloutInfo, String)>,
+    pub warnings: Vec<String>,
+}
+
+impl CoexistenceReport {
+    pub fn collect(engine: &Engine) -> Result<Self> {
+        let snapshot = engine.snapshot()?;
+        let sublayers = engine.sublayer_details()?;
+        let callouts = engine.callouts()?;
+        Ok(Self::build(&snapshot, sublayers, callouts))
+    }
+
+    pub fn build(
+        snapshot: &Snapshot,
+        sublayers: Vec<SublayerInfo>,
+        callouts: Vec<CalloutInfo>,
+    ) -> Self {
+        let provider_names: HashMap<GUID, &str> = snapshot
+            .providers
+            .iter()
+            .map(|p| (p.key, p.name.as_str()))
+            .collect();
+        let provider_name = |key: Option<GUID>| match key {
+            Some(key) => provider_names
+                .get(&key)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| format!("{key:?}")),
+            None => "<no provider>".to_string(),
+        };
+
+        let our_weight = sublayers.iter().find(|s| s.ours).map(|s| s.weight);
+
+        // (sublayer, layer) -> (hard permits, callout filters)
+        let mut overriders: HashMap<(GUID, GUID), (usize, usize)> = HashMap::new();
+        for filter in snapshot.filters.iter().filter(|f| !f.owned_by_app) {
+            let counts = overriders
+                .entry((filter.sublayer_key, filter.layer_key))
+                .or_default();
+            match filter.action {
+                WfpAction::Permit if filter.clear_action_right => counts.0 += 1,
+                WfpAction::Callout => counts.1 += 1,
+                _ => {}
+            }
+        }
+
+        let mut standings: Vec<SublayerStanding> = sublayers
+            .into_iter()
+            .filter(|s| !s.ours)
+            .map(|info| {
+                let standing = match our_weight {
+                    Some(ours) if info.weight > ours => Standing::Outranks,
+                    Some(ours) if info.weight == ours => Standing::Ties,
+                    _ => Standing::Below,
+                };
+                let (hard_permits, callout_filters) = overriders
+                    .iter()
+                    .filter(|((sublayer, _), _)| *sublayer == info.key)
+                    .fold((0, 0), |acc, (_, counts)| {
+                        (acc.0 + counts.0, acc.1 + counts.1)
+                    });
+                SublayerStanding {
+                    provider: provider_name(info.provider_key),
+                    info,
+                    standing,
+                    hard_permits,
+                    callout_filters,
+                }
+            })
+            .collect();
+        standings.sort_by_key(|s| std::cmp::Reverse(s.info.weight));
+
+        let mut warnings = Vec::new();
+        match our_weight {
+            None => warnings.push(
+                "Our sublayer is not installed yet, so none of our rules are enforced.".into(),
+            ),
+            Some(_) => {
+                for standing in standings.iter().filter(|s| s.standing == Standing::Ties) {
+                    warnings.push(format!(
+                        "Sublayer '{}' ({}) shares our weight {}; which one is evaluated first is undefined.",
+                        standing.info.name, standing.provider, standing.info.weight
+                    ));
+                }
+                warnings.extend(block_rule_warnings(
+                    &snapshot.filters,
+                    &standings,
+                    &overriders,
+                ));
+            }
+        }
+
+        let providers = snapshot
+            .providers
+            .iter()
+            .filter(|p| p.key != PROVIDER_KEY)
+            .cloned()
+            .collect();
+        let callouts = callouts
+            .into_iter()
+            .filter(|c| c.provider_key != Some(PROVIDER_KEY))
+            .map(|c| {
+                let provider = provider_name(c.provider_key);
+                (c, provider)
+            })
+            .collect();
+
+        Self {
+            our_weight,
+            providers,
+            sublayers: standings,
+            callouts,
+            warnings,
+        }
+    }
+}
+
+/// One warning per owned block rule and layer that a sublayer at or above
+/// ours can override.
+fn block_rule_warnings(
+    filters: &[FilterSummary],
+    standings: &[SublayerStanding],
+    overriders: &HashMap<(GUID, GUID), (usize, usize)>,
+) -> Vec<String> {
+    let mut seen: Vec<(GUID, GUID)> = Vec::new();
+    let mut warnings = Vec::new();
+    for filter in filters
+        .iter()
+        .filter(|f| f.owned_by_app && f.action == WfpAction::Block)
+    {
+        let rule = (filter.rule_key(), filter.layer_key);
+        if seen.contains(&rule) {
+            continue;
+        }
+        seen.push(rule);
+
+        for standing in standings.iter().filter(|s| s.standing != Standing::Below) {
+            let Some(&(hard, callouts)) = overriders.get(&(standing.info.key, filter.layer_key))
+            else {
+                continue;
+            };
+            if hard == 0 && callouts == 0 {
+                continue;
+            }
+            warnings.push(format!(
+                "Block rule '{}' at {} can be overridden by sublayer '{}' ({}, weight {}, {}): {hard} hard permit(s), {callouts} callout filter(s) on that layer.",
+                filter.name,
+                filter.layer,
+                standing.info.name,
+                standing.provider,
+                standing.info.weight,
+                standing.standing.as_str(),
+            ));
+        }
+    }
+    warnings
+}
+
+/// Why `filter` wins or loses against others: where its sublayer sits, how
+/// its weight was derived, and what its action can override. Shown when
+/// hovering a filter row.
+pub fn explain_arbitration(filter: &FilterSummary, sublayers: &[SublayerInfo]) -> String {
+    let mut lines = Vec::new();
+    lines.push(
+        match sublayers.iter().find(|s| s.key == filter.sublayer_key) {
+            Some(sublayer) => format!(
+                "Sublayer '{}' has weight {}; {} sublayer(s) are evaluated before it. Each sublayer reaches its own verdict.",
+                sublayer.name,
+                sublayer.weight,
+                sublayers.iter().filter(|s| s.weight > sublayer.weight).count()
+            ),
+            None => format!("Sublayer '{}' (weight unknown).", filter.sublayer),
+        },
+    );
+    lines.push(match filter.weight_kind {
+        WeightKind::Auto => {
+            "No weight given: BFE weighed it by its conditions, more specific first.".into()
+        }
+        WeightKind::Range(range) => {
+            let low = u64::from(range) << 60;
+            format!(
+                "Weight range {range} of 0–15: BFE placed it between 0x{low:016X} and 0x{:016X}.",
+                low | ((1 << 60) - 1)
+            )
+        }
+        WeightKind::Exact => "Exact weight set by its provider.".into(),
+    });
+    if let Some(weight) = filter.effective_weight {
+        let tier = WeightTier::of_weight(weight)
+            .filter(|_| filter.owned_by_app)
+            .map(|tier| format!(" ({} tier)", tier.as_str()))
+            .unwrap_or_default();
+        lines.push(format!(
+            "Effective weight 0x{weight:016X}{tier}; the highest matching weight in the sublayer decides."
+        ));
+    }
+    lines.push(
+        match filter.action {
+            WfpAction::Permit if filter.clear_action_right => {
+                "Hard permit: sublayers evaluated after it cannot block the traffic."
+            }
+            WfpAction::Permit => "Soft permit: a block from any other sublayer still wins.",
+            WfpAction::Block => {
+                "Block: beats permits elsewhere unless an earlier sublayer hard-permits."
+            }
+            WfpAction::Callout => "Callout: its driver decides, and can act as a hard permit.",
+        }
+        .into(),
+    );
+    lines.join("\n")
+}
+
+impl fmt::Display for CoexistenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.our_weight {
+            Some(weight) => writeln!(f, "Our sublayer weight: {weight}")?,
+            None => writeln!(f, "Our sublayer: not installed")?,
+        }
+
+        writeln!(f, "\nOther providers ({}):", self.providers.len())?;
+        for provider in &self.providers {
+            writeln!(f, "  {:?}  {}", provider.key, provider.name)?;
+        }
+
+        writeln!(
+            f,
+            "\nOther sublayers ({}), highest priority first:",
+            self.sublayers.len()
+        )?;
+        for s in &self.sublayers {
+            writeln!(
+                f,
+                "  {:>5}  {:<13}  {}  [{}]  hard permits: {}, callout filters: {}",
+                s.info.weight,
+                s.standing.as_str(),
+                s.info.name,
+                s.provider,
+                s.hard_permits,
+                s.callout_filters
+            )?;
+        }
+
+        writeln!(f, "\nOther callouts ({}):", self.callouts.len())?;
+        for (callout, provider) in &self.callouts {
+            writeln!(f, "  {:>5}  {}  [{}]", callout.id, callout.name, provider)?;
+        }
+
+        if self.warnings.is_empty() {
+            writeln!(f, "\nNo products found that can override our block rules.")
+        } else {
+            writeln!(f, "\nWarnings ({}):", self.warnings.len())?;
+            for warning in &self.warnings {
+                writeln!(f, "  ! {warning}")?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    dpapi, importers, schema,
+    wfp::{FilterConfig, RuleSet},
+};
+
+/// Starts a file written by [`write_protected`].
+const PROTECTED_MAGIC: &[u8] = b"SLSWFP-DPAPI1\n";
+
+/// On-disk formats accepted for rule files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleFormat {
+    Json,
+    Yaml,
+    Toml,
+    /// simplewall `profile.xml`.
+    Simplewall,
+    /// TinyWall settings export.
+    TinyWall,
+}
+
+impl RuleFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RuleFormat::Json => "JSON",
+            RuleFormat::Yaml => "YAML",
+            RuleFormat::Toml => "TOML",
+            RuleFormat::Simplewall => "simplewall",
+            RuleFormat::TinyWall => "TinyWall",
+        }
+    }
+
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "json" => Some(RuleFormat::Json),
+            "yaml" | "yml" => Some(RuleFormat::Yaml),
+            "toml" => Some(RuleFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the document text. XML comes from TinyWall
+    /// when it holds firewall exceptions and from simplewall otherwise. TOML
+    /// rule files start with `[[filters]]` tables or `key = value` lines;
+    /// anything else that opens with a bracket or brace is JSON, and the
+    /// remainder is treated as YAML.
+    pub fn sniff(text: &str) -> Self {
+        let trimmed = text.trim_start();
+        if trimmed.starts_with('<') {
+            return if text.contains("FirewallException") {
+                RuleFormat::TinyWall
+            } else {
+                RuleFormat::Simplewall
+            };
+        }
+        if trimmed.starts_with("[[") {
+            return RuleFormat::Toml;
+        }
+        if trimmed.starts_with('[') || trimmed.starts_with('{') {
+            return RuleFormat::Json;
+        }
+        let looks_like_toml = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .any(|line| match (line.find('='), line.find(':')) {
+                (Some(eq), Some(colon)) => eq < colon,
+                (Some(_), None) => true,
+                _ => false,
+            });
+        if looks_like_toml {
+            RuleFormat::Toml
+        } else {
+            RuleFormat::Yaml
+        }
+    }
+
+    /// Picks the format from the file extension when one is known, falling
+    /// back to sniffing the content.
+    pub fn detect(path: Option<&Path>, text: &str) -> Self {
+        path.and_then(Self::from_extension)
+            .unwrap_or_else(|| Self::sniff(text))
+    }
+}
+
+/// Parses a rule file in the given format. Every format is normalised to the
+/// JSON export layout so the same schema validation applies to all of them.
+/// Rules from other firewalls are mapped as far as this tool can express
+/// them; see [`importers`].
+pub fn parse_rules(text: &str, format: RuleFormat) -> Result<Vec<FilterConfig>> {
+    Ok(parse_rule_set(text, format)?.filters)
+}
+
+/// Like [`parse_rules`], keeping the provider and sublayers of a complete
+/// export.
+pub fn parse_rule_set(text: &str, format: RuleFormat) -> Result<RuleSet> {
+    let value = match format {
+        RuleFormat::Json => serde_json::from_str::<Value>(text)?,
+        RuleFormat::Yaml => serde_yaml::from_str::<Value>(text)?,
+        RuleFormat::Toml => {
+            // TOML has no top-level arrays, so rules live in `[[filters]]`
+            // next to optional `[provider]` and `[[sublayers]]` tables.
+            let mut doc = toml::from_str::<Value>(text)?;
+            if doc.get("filters").is_none() {
+                return Err(anyhow!("TOML rule files must contain [[filters]] tables"));
+            }
+            let mut set = serde_json::Map::new();
+            for key in ["provider", "sublayers", "filters"] {
+                if let Some(value) = doc.get_mut(key) {
+                    set.insert(key.into(), value.take());
+                }
+            }
+            Value::Object(set)
+        }
+        RuleFormat::Simplewall => serde_json::to_value(importers::simplewall_rules(text)?)?,
+        RuleFormat::TinyWall => serde_json::to_value(importers::tinywall_rules(text)?)?,
+    };
+    schema::rule_set_from_value(value)
+}
+
+/// `%LOCALAPPDATA%\SLS WFP Manager`, or the temp directory when
+/// LOCALAPPDATA is unset. History, alerts and other app state live here.
+pub fn app_data_dir() -> PathBuf {
+    std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("SLS WFP Manager")
+}
+
+/// Reads a settings file written by [`write_protected`]; `None` when it
+/// does not exist. Files saved before encryption was added are plain text
+/// and are read as they are, then encrypted on their next save.
+pub fn read_protected(path: &Path) -> Result<Option<String>> {
+    let Ok(bytes) = std::fs::read(path) else {
+        return Ok(None);
+    };
+    let bytes = match bytes.strip_prefix(PROTECTED_MAGIC) {
+        Some(sealed) => {
+            dpapi::unprotect(sealed).map_err(|e| anyhow!("Cannot read {}: {e}", path.display()))?
+        }
+        None => bytes,
+    };
+    String::from_utf8(bytes)
+        .map(Some)
+        .map_err(|_| anyhow!("{} is not UTF-8 text", path.display()))
+}
+
+/// Writes settings that hold secrets, such as webhook tokens, encrypted
+/// with DPAPI for the current user. Off Windows they are stored as is.
+pub fn write_protected(path: &Path, text: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("Failed to create {}: {e}", dir.display()))?;
+    }
+    let sealed = [PROTECTED_MAGIC, &dpapi::protect(text.as_bytes())?].concat();
+    std::fs::write(path, sealed).map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))
+}
+
+/// Settings saved as `file` in [`app_data_dir`], or the defaults before
+/// any are saved. Files written by [`save_settings`] and
+/// [`save_protected_settings`] are both read.
+pub fn load_settings<T: DeserializeOwned + Default>(file: &str) -> Result<T> {
+    let path = app_data_dir().join(file);
+    match read_protected(&path)? {
+        Some(text) => serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Invalid settings in {}: {e}", path.display())),
+        None => Ok(T::default()),
+    }
+}
+
+/// Saves `settings` as `file` in [`app_data_dir`].
+pub fn save_settings<T: Serialize + ?Sized>(file: &str, settings: &T) -> Result<()> {
+    let dir = app_data_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| anyhow!("Failed to create {}: {e}", dir.display()))?;
+    let path = dir.join(file);
+    std::fs::write(&path, serde_json::to_string_pretty(settings)?)
+        .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))
+}
+
+/// [`save_settings`] for settings that hold secrets, encrypted as
+/// [`write_protected`] does.
+pub fn save_protected_settings<T: Serialize + ?Sized>(file: &str, settings: &T) -> Result<()> {
+    write_protected(
+        &app_data_dir().join(file),
+        &serde_json::to_string_pretty(settings)?,
+    )
+}
+
+pub fn load_rules_file(path: &Path) -> Result<Vec<FilterConfig>> {
+    Ok(load_rule_set_file(path)?.filters)
+}
+
+pub fn load_rule_set_file(path: &Path) -> Result<RuleSet> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    parse_rule_set(&text, RuleFormat::detect(Some(path), &text))
+}
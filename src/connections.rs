@@ -0,0 +1,275 @@
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{
+    chart::app_name,
+    wfp::{Direction, FilterConfig, RemoteAddress, RemotePorts, RuleProtocol, WfpAction},
+};
+#[cfg(windows)]
+use {
+    anyhow::anyhow,
+    std::{
+        collections::HashMap,
+        ffi::c_void,
+        net::{Ipv4Addr, Ipv6Addr},
+    },
+    windows::{
+        core::PWSTR,
+        Win32::{
+            Foundation::{CloseHandle, ERROR_INSUFFICIENT_BUFFER, NO_ERROR},
+            NetworkManagement::IpHelper::{
+                GetExtendedTcpTable, GetExtendedUdpTable, MIB_TCP6ROW_OWNER_PID,
+                MIB_TCPROW_OWNER_PID, MIB_UDP6ROW_OWNER_PID, MIB_UDPROW_OWNER_PID,
+                TCP_TABLE_OWNER_PID_ALL, UDP_TABLE_OWNER_PID,
+            },
+            Networking::WinSock::{AF_INET, AF_INET6},
+            System::Threading::{
+                OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+                PROCESS_QUERY_LIMITED_INFORMATION,
+            },
+        },
+    },
+};
+
+/// One socket from the TCP or UDP table of this machine.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    pub protocol: RuleProtocol,
+    pub local: SocketAddr,
+    /// `None` for UDP sockets and TCP sockets that are not connected.
+    pub remote: Option<SocketAddr>,
+    /// Accepted by a listening socket of the same process, rather than
+    /// opened by it.
+    pub inbound: bool,
+    /// TCP state such as `ESTABLISHED`; empty for UDP.
+    pub state: &'static str,
+    pub pid: u32,
+    /// Executable path, `None` when the process cannot be opened.
+    pub process: Option<String>,
+}
+
+impl Connection {
+    /// A rule blocking this process from the remote address over this
+    /// protocol, or `None` when the socket has no remote end or its process
+    /// is unknown.
+    pub fn block_rule(&self) -> Option<FilterConfig> {
+        let remote = self.remote?;
+        let app = self.process.as_deref()?;
+        let addr = remote.ip();
+        let prefix = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let name = format!("Block {} to {addr}", app_name(app));
+        let mut config = FilterConfig::tcp_ports(&name, RemotePorts::default(), WfpAction::Block);
+        config.key = Some(Uuid::new_v4());
+        config.app = Some(app.to_string());
+        config.protocol = Some(self.protocol);
+        config.remote_address = vec![RemoteAddress { addr, prefix }];
+        config.direction = if self.inbound {
+            Direction::In
+        } else {
+            Direction::Out
+        };
+        Some(config)
+    }
+}
+
+/// Every TCP and UDP socket on this machine, IPv4 first, with the process
+/// that owns it.
+#[cfg(windows)]
+pub fn list() -> Result<Vec<Connection>> {
+    let mut paths = HashMap::new();
+    let mut process = |pid: u32| {
+        paths
+            .entry(pid)
+            .or_insert_with(|| process_path(pid))
+            .clone()
+    };
+    let mut out = Vec::new();
+    let tcp4 = table(|buf, size| unsafe {
+        GetExtendedTcpTable(
+            buf,
+            size,
+            false,
+            AF_INET.0.into(),
+            TCP_TABLE_OWNER_PID_ALL,
+            0,
+        )
+    })?;
+    for row in unsafe { rows::<MIB_TCPROW_OWNER_PID>(&tcp4) } {
+        let remote = socket(ipv4(row.dwRemoteAddr), row.dwRemotePort);
+        out.push(Connection {
+            protocol: RuleProtocol::Tcp,
+            local: socket(ipv4(row.dwLocalAddr), row.dwLocalPort),
+            remote: connected(row.dwState, remote),
+            inbound: false,
+            state: tcp_state(row.dwState),
+            pid: row.dwOwningPid,
+            process: process(row.dwOwningPid),
+        });
+    }
+    let tcp6 = table(|buf, size| unsafe {
+        GetExtendedTcpTable(
+            buf,
+            size,
+            false,
+            AF_INET6.0.into(),
+            TCP_TABLE_OWNER_PID_ALL,
+            0,
+        )
+    })?;
+    for row in unsafe { rows::<MIB_TCP6ROW_OWNER_PID>(&tcp6) } {
+        let remote = socket(Ipv6Addr::from(row.ucRemoteAddr).into(), row.dwRemotePort);
+        out.push(Connection {
+            protocol: RuleProtocol::Tcp,
+            local: socket(Ipv6Addr::from(row.ucLocalAddr).into(), row.dwLocalPort),
+            remote: connected(row.dwState, remote),
+            inbound: false,
+            state: tcp_state(row.dwState),
+            pid: row.dwOwningPid,
+            process: process(row.dwOwningPid),
+        });
+    }
+    let udp4 = table(|buf, size| unsafe {
+        GetExtendedUdpTable(buf, size, false, AF_INET.0.into(), UDP_TABLE_OWNER_PID, 0)
+    })?;
+    for row in unsafe { rows::<MIB_UDPROW_OWNER_PID>(&udp4) } {
+        out.push(Connection {
+            protocol: RuleProtocol::Udp,
+            local: socket(ipv4(row.dwLocalAddr), row.dwLocalPort),
+            remote: None,
+            inbound: false,
+            state: "",
+            pid: row.dwOwningPid,
+            process: process(row.dwOwningPid),
+        });
+    }
+    let udp6 = table(|buf, size| unsafe {
+        GetExtendedUdpTable(buf, size, false, AF_INET6.0.into(), UDP_TABLE_OWNER_PID, 0)
+    })?;
+    for row in unsafe { rows::<MIB_UDP6ROW_OWNER_PID>(&udp6) } {
+        out.push(Connection {
+            protocol: RuleProtocol::Udp,
+            local: socket(Ipv6Addr::from(row.ucLocalAddr).into(), row.dwLocalPort),
+            remote: None,
+            inbound: false,
+            state: "",
+            pid: row.dwOwningPid,
+            process: process(row.dwOwningPid),
+        });
+    }
+    let listening: Vec<(u16, u32)> = out
+        .iter()
+        .filter(|c| c.state == "LISTEN")
+        .map(|c| (c.local.port(), c.pid))
+        .collect();
+    for conn in out.iter_mut().filter(|c| c.remote.is_some()) {
+        conn.inbound = listening.contains(&(conn.local.port(), conn.pid));
+    }
+    Ok(out)
+}
+
+/// The simulated engine has no sockets to show.
+#[cfg(not(windows))]
+pub fn list() -> Result<Vec<Connection>> {
+    Ok(Vec::new())
+}
+
+/// Fetches an IP Helper table, growing the buffer until it fits. The buffer
+/// is made of `u32`s so the rows in it are aligned.
+#[cfg(windows)]
+fn table(fetch: impl Fn(Option<*mut c_void>, &mut u32) -> u32) -> Result<Vec<u32>> {
+    let mut size = 0;
+    let mut buf: Vec<u32> = Vec::new();
+    loop {
+        let ptr = (!buf.is_empty()).then(|| buf.as_mut_ptr().cast::<c_void>());
+        match fetch(ptr, &mut size) {
+            status if status == NO_ERROR.0 => return Ok(buf),
+            status if status == ERROR_INSUFFICIENT_BUFFER.0 => {
+                buf = vec![0; (size as usize).div_ceil(4) + 1];
+            }
+            status => {
+                return Err(anyhow!(
+                    "Reading the connection table failed: error {status}"
+                ))
+            }
+        }
+    }
+}
+
+/// The rows of a `MIB_*TABLE_OWNER_PID`: a `u32` count followed by the
+/// rows, which all have 4-byte alignment.
+///
+/// # Safety
+/// `buf` must hold a table of `T` rows as filled in by IP Helper.
+#[cfg(windows)]
+unsafe fn rows<T>(buf: &[u32]) -> &[T] {
+    match buf.first() {
+        Some(&count) => std::slice::from_raw_parts(buf.as_ptr().add(1).cast(), count as usize),
+        None => &[],
+    }
+}
+
+#[cfg(windows)]
+fn ipv4(addr: u32) -> IpAddr {
+    Ipv4Addr::from(addr.to_ne_bytes()).into()
+}
+
+/// Ports are stored in network byte order in the low 16 bits.
+#[cfg(windows)]
+fn socket(addr: IpAddr, port: u32) -> SocketAddr {
+    SocketAddr::new(addr, u16::from_be(port as u16))
+}
+
+/// The remote end of a TCP socket past the listening and closed states.
+#[cfg(windows)]
+fn connected(state: u32, remote: SocketAddr) -> Option<SocketAddr> {
+    (state > 2 && !remote.ip().is_unspecified()).then_some(remote)
+}
+
+#[cfg(windows)]
+fn tcp_state(state: u32) -> &'static str {
+    match state {
+        1 => "CLOSED",
+        2 => "LISTEN",
+        3 => "SYN_SENT",
+        4 => "SYN_RCVD",
+        5 => "ESTABLISHED",
+        6 => "FIN_WAIT1",
+        7 => "FIN_WAIT2",
+        8 => "CLOSE_WAIT",
+        9 => "CLOSING",
+        10 => "LAST_ACK",
+        11 => "TIME_WAIT",
+        12 => "DELETE_TCB",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Executable path of `pid`. System processes and ones running as another
+/// user without admin rights cannot be opened.
+#[cfg(windows)]
+fn process_path(pid: u32) -> Option<String> {
+    if pid == 0 || pid == 4 {
+        return None;
+    }
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }.ok()?;
+    let mut buf = [0u16; 1024];
+    let mut len = buf.len() as u32;
+    let result = unsafe {
+        QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buf.as_mut_ptr()),
+            &mut len,
+        )
+    };
+    unsafe {
+        let _ = CloseHandle(process);
+    }
+    result.ok()?;
+    Some(String::from_utf16_lossy(&buf[..len as usize]))
+}
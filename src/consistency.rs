@@ -0,0 +1,54 @@
+use anyhow::Result;
+
+use crate::{
+    config,
+    diff::RuleDiff,
+    wfp::{Engine, FilterConfig, ImportSummary, RuleSet},
+};
+
+const RECORD_FILE: &str = "owned_rules.json";
+
+/// Owned rules recorded on the last run that are gone or different now,
+/// e.g. after a system cleanup or another administrator removed them.
+pub struct ConsistencyReport {
+    /// The rule set as recorded, used to put rules back.
+    pub recorded: RuleSet,
+    pub diff: RuleDiff,
+}
+
+impl ConsistencyReport {
+    /// Compares the recorded rules with `current`; `None` when nothing
+    /// recorded is missing or changed. Rules added since are not reported.
+    pub fn check(recorded: RuleSet, current: &[FilterConfig]) -> Option<Self> {
+        let diff = RuleDiff::between(&recorded.filters, current);
+        if diff.removed.is_empty() && diff.changed.is_empty() {
+            return None;
+        }
+        Some(Self { recorded, diff })
+    }
+
+    /// Reinstalls the missing rules and restores the changed ones as they
+    /// were recorded, with the sublayers they need.
+    pub fn repair(&self, engine: &Engine) -> Result<ImportSummary> {
+        let filters = self
+            .diff
+            .removed
+            .iter()
+            .chain(self.diff.changed.iter().map(|change| &change.before))
+            .cloned()
+            .collect();
+        engine.import_rule_set(&RuleSet {
+            filters,
+            ..self.recorded.clone()
+        })
+    }
+}
+
+/// The owned rules saved by [`save_record`], or `None` before the first run.
+pub fn load_record() -> Result<Option<RuleSet>> {
+    config::load_settings(RECORD_FILE)
+}
+
+pub fn save_record(set: &RuleSet) -> Result<()> {
+    config::save_settings(RECORD_FILE, set)
+}
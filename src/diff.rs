@@ -0,0 +1,407 @@
+use std::{collections::BTreeSet, fmt};
+
+use crate::{
+    rule_expr,
+    wfp::{uuid_from_guid, FilterConfig, FilterSummary, RemotePorts, Snapshot},
+};
+
+/// One rule present in both exports whose name, ports, action, app,
+/// addresses, protocol, direction, sublayer, interface type or
+/// dependencies differ.
+pub struct ChangedRule {
+    pub before: FilterConfig,
+    pub after: FilterConfig,
+}
+
+impl ChangedRule {
+    /// Names of the fields that differ, e.g. `["remote_port", "action"]`.
+    pub fn fields(&self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.before.name != self.after.name {
+            fields.push("name");
+        }
+        if normalized(&self.before.remote_port) != normalized(&self.after.remote_port) {
+            fields.push("remote_port");
+        }
+        if normalized(&self.before.local_port) != normalized(&self.after.local_port) {
+            fields.push("local_port");
+        }
+        if self.before.action != self.after.action {
+            fields.push("action");
+        }
+        if self.before.app != self.after.app {
+            fields.push("app");
+        }
+        if self.before.remote_address != self.after.remote_address
+            || self.before.remote_host != self.after.remote_host
+        {
+            fields.push("remote_address");
+        }
+        if self.before.protocol() != self.after.protocol() {
+            fields.push("protocol");
+        }
+        if self.before.direction != self.after.direction {
+            fields.push("direction");
+        }
+        if self.before.sublayer != self.after.sublayer {
+            fields.push("sublayer");
+        }
+        if self.before.interface != self.after.interface {
+            fields.push("interface");
+        }
+        if self.before.depends_on != self.after.depends_on {
+            fields.push("depends_on");
+        }
+        fields
+    }
+}
+
+/// Rule-level differences between two exports. Rules are matched by key,
+/// falling back to the name for rules exported without one.
+#[derive(Default)]
+pub struct RuleDiff {
+    pub added: Vec<FilterConfig>,
+    pub removed: Vec<FilterConfig>,
+    pub changed: Vec<ChangedRule>,
+    pub unchanged: usize,
+}
+
+impl RuleDiff {
+    pub fn between(before: &[FilterConfig], after: &[FilterConfig]) -> Self {
+        let same_rule = |a: &FilterConfig, b: &FilterConfig| match (a.key, b.key) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => a.name == b.name,
+            _ => false,
+        };
+        let mut diff = RuleDiff::default();
+        for old in before {
+            match after.iter().find(|new| same_rule(old, new)) {
+                Some(new) => {
+                    let change = ChangedRule {
+                        before: old.clone(),
+                        after: new.clone(),
+                    };
+                    if change.fields().is_empty() {
+                        diff.unchanged += 1;
+                    } else {
+                        diff.changed.push(change);
+                    }
+                }
+                None => diff.removed.push(old.clone()),
+            }
+        }
+        diff.added = after
+            .iter()
+            .filter(|new| !before.iter().any(|old| same_rule(old, new)))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for RuleDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rule in &self.added {
+            writeln!(f, "+ {}", describe(rule))?;
+        }
+        for rule in &self.removed {
+            writeln!(f, "- {}", describe(rule))?;
+        }
+        for change in &self.changed {
+            writeln!(f, "~ {}", rule_id(&change.after))?;
+            for field in change.fields() {
+                let (before, after) = match field {
+                    "name" => (change.before.name.clone(), change.after.name.clone()),
+                    "remote_port" => (
+                        normalized(&change.before.remote_port).to_string(),
+                        normalized(&change.after.remote_port).to_string(),
+                    ),
+                    "local_port" => (
+                        normalized(&change.before.local_port).to_string(),
+                        normalized(&change.after.local_port).to_string(),
+                    ),
+                    "app" => (
+                        app_label(&change.before).to_string(),
+                        app_label(&change.after).to_string(),
+                    ),
+                    "remote_address" => {
+                        (address_label(&change.before), address_label(&change.after))
+                    }
+                    "protocol" => (
+                        change.before.protocol().as_str().to_string(),
+                        change.after.protocol().as_str().to_string(),
+                    ),
+                    "direction" => (
+                        change.before.direction.as_str().to_string(),
+                        change.after.direction.as_str().to_string(),
+                    ),
+                    "sublayer" => (
+                        sublayer_label(&change.before),
+                        sublayer_label(&change.after),
+                    ),
+                    "interface" => (
+                        interface_label(&change.before).to_string(),
+                        interface_label(&change.after).to_string(),
+                    ),
+                    "depends_on" => (
+                        dependency_label(&change.before),
+                        dependency_label(&change.after),
+                    ),
+                    _ => (
+                        change.before.action.as_str().to_string(),
+                        change.after.action.as_str().to_string(),
+                    ),
+                };
+                writeln!(f, "    {field}: {before} -> {after}")?;
+            }
+        }
+        writeln!(
+            f,
+            "{} added, {} removed, {} changed, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged
+        )
+    }
+}
+
+/// One filter present in both snapshots whose name, layer, sublayer,
+/// action, weight, conditions or flags differ.
+pub struct ChangedFilter {
+    pub before: FilterSummary,
+    pub after: FilterSummary,
+}
+
+impl ChangedFilter {
+    /// Names of the fields that differ, e.g. `["action", "conditions"]`.
+    pub fn fields(&self) -> Vec<&'static str> {
+        let (before, after) = (&self.before, &self.after);
+        let mut fields = Vec::new();
+        if before.name != after.name {
+            fields.push("name");
+        }
+        if before.layer_key != after.layer_key {
+            fields.push("layer");
+        }
+        if before.sublayer_key != after.sublayer_key {
+            fields.push("sublayer");
+        }
+        if before.action != after.action {
+            fields.push("action");
+        }
+        if before.effective_weight != after.effective_weight {
+            fields.push("weight");
+        }
+        if before.conditions != after.conditions {
+            fields.push("conditions");
+        }
+        if flags_label(before) != flags_label(after) {
+            fields.push("flags");
+        }
+        fields
+    }
+
+    /// The `field` before and after, as displayed.
+    pub fn values(&self, field: &str) -> (String, String) {
+        let value = |filter: &FilterSummary| match field {
+            "name" => filter.name.clone(),
+            "layer" => filter.layer.clone(),
+            "sublayer" => filter.sublayer.clone(),
+            "action" => filter.action.as_str().to_string(),
+            "weight" => filter
+                .effective_weight
+                .map_or_else(|| "none".into(), |w| w.to_string()),
+            "conditions" => conditions_label(filter),
+            _ => flags_label(filter),
+        };
+        (value(&self.before), value(&self.after))
+    }
+}
+
+/// Filter-level differences between two snapshots, from every provider and
+/// including the boot-time policy. Filters are matched by key.
+#[derive(Default)]
+pub struct SnapshotDiff {
+    pub added: Vec<FilterSummary>,
+    pub removed: Vec<FilterSummary>,
+    pub changed: Vec<ChangedFilter>,
+    pub unchanged: usize,
+}
+
+impl SnapshotDiff {
+    pub fn between(before: &Snapshot, after: &Snapshot) -> Self {
+        let all = |s: &Snapshot| -> Vec<FilterSummary> {
+            s.filters
+                .iter()
+                .chain(&s.boot_time_filters)
+                .cloned()
+                .collect()
+        };
+        let (before, after) = (all(before), all(after));
+        let mut diff = SnapshotDiff::default();
+        for old in &before {
+            match after.iter().find(|new| new.key == old.key) {
+                Some(new) => {
+                    let change = ChangedFilter {
+                        before: old.clone(),
+                        after: new.clone(),
+                    };
+                    if change.fields().is_empty() {
+                        diff.unchanged += 1;
+                    } else {
+                        diff.changed.push(change);
+                    }
+                }
+                None => diff.removed.push(old.clone()),
+            }
+        }
+        diff.added = after
+            .iter()
+            .filter(|new| !before.iter().any(|old| old.key == new.key))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Providers with an added, removed or changed filter, by name.
+    pub fn providers(&self) -> BTreeSet<&str> {
+        self.added
+            .iter()
+            .chain(&self.removed)
+            .chain(self.changed.iter().map(|c| &c.after))
+            .map(|f| f.provider.as_str())
+            .collect()
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for provider in self.providers() {
+            writeln!(f, "{provider}:")?;
+            let ours = |filter: &&FilterSummary| filter.provider == provider;
+            for filter in self.added.iter().filter(ours) {
+                writeln!(f, "  + {}", describe_filter(filter))?;
+            }
+            for filter in self.removed.iter().filter(ours) {
+                writeln!(f, "  - {}", describe_filter(filter))?;
+            }
+            for change in self.changed.iter().filter(|c| c.after.provider == provider) {
+                writeln!(f, "  ~ {}", filter_id(&change.after))?;
+                for field in change.fields() {
+                    let (before, after) = change.values(field);
+                    writeln!(f, "      {field}: {before} -> {after}")?;
+                }
+            }
+        }
+        writeln!(
+            f,
+            "{} added, {} removed, {} changed, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged
+        )
+    }
+}
+
+fn filter_id(filter: &FilterSummary) -> String {
+    format!("{} \"{}\"", uuid_from_guid(filter.key), filter.name)
+}
+
+/// `KEY "Name" block at ALE_AUTH_CONNECT_V4 when remote port = 443`.
+pub fn describe_filter(filter: &FilterSummary) -> String {
+    format!(
+        "{} {} at {} when {}",
+        filter_id(filter),
+        filter.action.as_str(),
+        filter.layer,
+        conditions_label(filter)
+    )
+}
+
+fn conditions_label(filter: &FilterSummary) -> String {
+    if filter.conditions.is_empty() {
+        return "always".into();
+    }
+    let conditions: Vec<String> = filter.conditions.iter().map(ToString::to_string).collect();
+    conditions.join(" and ")
+}
+
+fn flags_label(filter: &FilterSummary) -> String {
+    let flags: Vec<&str> = [
+        (filter.boot_time, "boot-time"),
+        (filter.persistent, "persistent"),
+        (filter.clear_action_right, "hard permit"),
+        (filter.indexed, "indexed"),
+    ]
+    .into_iter()
+    .filter_map(|(set, flag)| set.then_some(flag))
+    .collect();
+    if flags.is_empty() {
+        "none".into()
+    } else {
+        flags.join(", ")
+    }
+}
+
+fn rule_id(rule: &FilterConfig) -> String {
+    match rule.key {
+        Some(key) => format!("{key} \"{}\"", rule.name),
+        None => format!("\"{}\"", rule.name),
+    }
+}
+
+fn describe(rule: &FilterConfig) -> String {
+    format!("{} {}", rule_id(rule), rule_expr::format(rule))
+}
+
+fn app_label(rule: &FilterConfig) -> &str {
+    rule.app.as_deref().unwrap_or("any app")
+}
+
+fn sublayer_label(rule: &FilterConfig) -> String {
+    rule.sublayer
+        .map(|key| key.to_string())
+        .unwrap_or_else(|| "default".into())
+}
+
+fn interface_label(rule: &FilterConfig) -> &'static str {
+    rule.interface
+        .map_or("any interface", |media| media.as_str())
+}
+
+fn dependency_label(rule: &FilterConfig) -> String {
+    if rule.depends_on.is_empty() {
+        return "no dependencies".into();
+    }
+    let keys: Vec<String> = rule.depends_on.iter().map(ToString::to_string).collect();
+    keys.join(", ")
+}
+
+fn address_label(rule: &FilterConfig) -> String {
+    if rule.remote_address.is_empty() && rule.remote_host.is_empty() {
+        return "any address".into();
+    }
+    let addresses: Vec<String> = rule
+        .remote_address
+        .iter()
+        .map(|a| a.to_string())
+        .chain(rule.remote_host.iter().cloned())
+        .collect();
+    addresses.join(", ")
+}
+
+fn normalized(ports: &RemotePorts) -> RemotePorts {
+    let mut ports = ports.clone();
+    ports.normalize();
+    ports
+}
@@ -0,0 +1,101 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    preset::Preset,
+    wfp::{dns_lockdown_rule, FilterBuilder, DNS_PORT, DOT_PORT, GUID},
+};
+
+/// Public resolvers that answer DNS over HTTPS, blocked on port 443 when
+/// [`DnsLockdown::block_doh`] is set.
+pub const KNOWN_DOH_SERVERS: [IpAddr; 16] = [
+    // Cloudflare
+    IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+    IpAddr::V4(Ipv4Addr::new(1, 0, 0, 1)),
+    IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111)),
+    IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1001)),
+    // Google
+    IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+    IpAddr::V4(Ipv4Addr::new(8, 8, 4, 4)),
+    IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888)),
+    IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8844)),
+    // Quad9
+    IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)),
+    IpAddr::V4(Ipv4Addr::new(149, 112, 112, 112)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0xfe, 0, 0, 0, 0, 0, 0xfe)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0xfe, 0, 0, 0, 0, 0, 0x9)),
+    // AdGuard
+    IpAddr::V4(Ipv4Addr::new(94, 140, 14, 14)),
+    IpAddr::V4(Ipv4Addr::new(94, 140, 15, 15)),
+    // OpenDNS
+    IpAddr::V4(Ipv4Addr::new(208, 67, 222, 222)),
+    IpAddr::V4(Ipv4Addr::new(208, 67, 220, 220)),
+];
+
+/// Outbound DNS limited to an allowlist of resolvers, switched on and off
+/// as one rule. The settings are kept so the lockdown can be re-applied.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsLockdown {
+    /// The only resolvers DNS may reach.
+    pub resolvers: Vec<IpAddr>,
+    /// Also limit DNS over TLS (port 853) to the resolvers.
+    pub restrict_dot: bool,
+    /// Block DNS over HTTPS to [`KNOWN_DOH_SERVERS`] not among the resolvers.
+    pub block_doh: bool,
+}
+
+impl Default for DnsLockdown {
+    fn default() -> Self {
+        Self {
+            resolvers: Vec::new(),
+            restrict_dot: true,
+            block_doh: false,
+        }
+    }
+}
+
+impl DnsLockdown {
+    /// Ports limited to the resolvers.
+    pub fn ports(&self) -> Vec<u16> {
+        if self.restrict_dot {
+            vec![DNS_PORT, DOT_PORT]
+        } else {
+            vec![DNS_PORT]
+        }
+    }
+}
+
+impl Preset for DnsLockdown {
+    const SETTINGS_FILE: &'static str = "dns_lockdown.json";
+    const RULE_NAME: &'static str = "DNS lockdown";
+    const RULE_KEY: GUID = GUID::from_values(
+        0x3c1e6f42,
+        0x8d0b,
+        0x4b5e,
+        [0xa2, 0x7f, 0x19, 0xc4, 0x5e, 0x0d, 0x63, 0xb8],
+    );
+
+    fn rule(&self, name: &str) -> Result<Vec<FilterBuilder>> {
+        let doh: &[IpAddr] = if self.block_doh {
+            &KNOWN_DOH_SERVERS
+        } else {
+            &[]
+        };
+        dns_lockdown_rule(Self::RULE_KEY, name, &self.resolvers, &self.ports(), doh)
+    }
+}
+
+/// Resolvers written as a comma- or space-separated list.
+pub fn parse_resolvers(text: &str) -> Result<Vec<IpAddr>> {
+    text.split([',', ' '])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            part.parse()
+                .map_err(|_| anyhow!("'{part}' is not an IP address"))
+        })
+        .collect()
+}
@@ -0,0 +1,85 @@
+#[cfg(windows)]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(windows)]
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{LocalFree, HLOCAL},
+        Security::Cryptography::{
+            CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+        },
+    },
+};
+
+/// Encrypts `data` so only the current Windows user can decrypt it.
+#[cfg(windows)]
+pub fn protect(data: &[u8]) -> Result<Vec<u8>> {
+    let input = blob(data);
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    unsafe {
+        CryptProtectData(
+            &input,
+            PCWSTR::null(),
+            None,
+            None,
+            None,
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    }
+    .map_err(|e| anyhow!("Encrypting with DPAPI failed: {e}"))?;
+    Ok(take(output))
+}
+
+/// Decrypts data from [`protect`]; fails for another user or machine.
+#[cfg(windows)]
+pub fn unprotect(data: &[u8]) -> Result<Vec<u8>> {
+    let input = blob(data);
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    unsafe {
+        CryptUnprotectData(
+            &input,
+            None,
+            None,
+            None,
+            None,
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    }
+    .map_err(|e| anyhow!("Decrypting with DPAPI failed: {e}"))?;
+    Ok(take(output))
+}
+
+/// Without DPAPI the data is kept as it is.
+#[cfg(not(windows))]
+pub fn protect(data: &[u8]) -> Result<Vec<u8>> {
+    Ok(data.to_vec())
+}
+
+#[cfg(not(windows))]
+pub fn unprotect(data: &[u8]) -> Result<Vec<u8>> {
+    Ok(data.to_vec())
+}
+
+#[cfg(windows)]
+fn blob(data: &[u8]) -> CRYPT_INTEGER_BLOB {
+    CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr().cast_mut(),
+    }
+}
+
+/// Copies out a blob DPAPI allocated and frees it.
+#[cfg(windows)]
+fn take(blob: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+    if blob.pbData.is_null() {
+        return Vec::new();
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(blob.pbData, blob.cbData as usize) }.to_vec();
+    unsafe {
+        let _ = LocalFree(HLOCAL(blob.pbData.cast()));
+    }
+    bytes
+}
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+
+#[cfg(not(feature = "simulation"))]
+mod session;
+#[cfg(not(feature = "simulation"))]
+pub use session::EtwSession;
+#[cfg(feature = "simulation")]
+pub use simulated::EtwSession;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "simulation", allow(dead_code))] // built by the ETW session
+pub enum EtwCategory {
+    Classify,
+    Reauthorization,
+    Error,
+}
+
+impl EtwCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EtwCategory::Classify => "Classify",
+            EtwCategory::Reauthorization => "Reauth",
+            EtwCategory::Error => "Error",
+        }
+    }
+}
+
+/// One Microsoft-Windows-WFP event from the live session.
+#[derive(Clone, Debug)]
+pub struct EtwEvent {
+    pub time: DateTime<Utc>,
+    pub category: EtwCategory,
+    pub id: u16,
+    pub task: String,
+    pub opcode: String,
+    pub process_id: u32,
+}
+
+#[cfg(feature = "simulation")]
+mod simulated {
+    use std::sync::mpsc::Sender;
+
+    use anyhow::{anyhow, Result};
+
+    use super::EtwEvent;
+
+    /// Stands in for the ETW session under the `simulation` feature, where
+    /// there is no Microsoft-Windows-WFP provider to consume.
+    pub struct EtwSession;
+
+    impl EtwSession {
+        pub fn start(_events: Sender<EtwEvent>) -> Result<Self> {
+            Err(anyhow!(
+                "Live logging needs ETW, which the simulated engine does not provide"
+            ))
+        }
+    }
+}
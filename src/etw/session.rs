@@ -0,0 +1,238 @@
+use std::{
+    ffi::c_void,
+    mem::size_of,
+    sync::mpsc::Sender,
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use widestring::{U16CStr, U16CString};
+use windows::{
+    core::{GUID, PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{ERROR_ALREADY_EXISTS, ERROR_INSUFFICIENT_BUFFER, ERROR_SUCCESS, FILETIME},
+        System::Diagnostics::Etw::*,
+    },
+};
+
+use super::{EtwCategory, EtwEvent};
+
+/// Microsoft-Windows-WFP: BFE and the base filtering engine driver.
+const WFP_PROVIDER: GUID = GUID::from_values(
+    0x0c478c5b,
+    0x0351,
+    0x41b1,
+    [0x8c, 0x58, 0x4a, 0x67, 0x37, 0xda, 0x32, 0xe3],
+);
+const SESSION_NAME: &str = "SLS WFP Manager Live Log";
+
+/// `INVALID_PROCESSTRACE_HANDLE` as returned by OpenTraceW.
+const INVALID_TRACE_HANDLE: u64 = u64::MAX;
+
+const TRACE_LEVEL_ERROR: u8 = 2;
+const TRACE_LEVEL_VERBOSE: u8 = 5;
+
+impl EtwCategory {
+    /// Sorts an event by the task and opcode names in the provider
+    /// manifest; errors are anything logged at error level or above.
+    fn classify(level: u8, task: &str, opcode: &str) -> Option<Self> {
+        let name = format!("{task} {opcode}").to_ascii_lowercase();
+        if (1..=TRACE_LEVEL_ERROR).contains(&level) {
+            Some(EtwCategory::Error)
+        } else if name.contains("reauth") {
+            Some(EtwCategory::Reauthorization)
+        } else if name.contains("classify") {
+            Some(EtwCategory::Classify)
+        } else {
+            None
+        }
+    }
+}
+
+/// A real-time ETW session consuming the Microsoft-Windows-WFP provider.
+/// Classify, reauthorization and error events are sent to the channel given
+/// to [`EtwSession::start`]; everything else is dropped in the callback.
+/// Dropping the session stops the trace.
+pub struct EtwSession {
+    control: CONTROLTRACE_HANDLE,
+    consumer: Option<JoinHandle<()>>,
+}
+
+impl EtwSession {
+    pub fn start(events: Sender<EtwEvent>) -> Result<Self> {
+        let control = start_trace()?;
+        unsafe {
+            let status = EnableTraceEx2(
+                control,
+                &WFP_PROVIDER,
+                EVENT_CONTROL_CODE_ENABLE_PROVIDER.0,
+                TRACE_LEVEL_VERBOSE,
+                0,
+                0,
+                0,
+                None,
+            );
+            if status != ERROR_SUCCESS {
+                stop_trace(control);
+                return Err(anyhow!("EnableTraceEx2 failed: 0x{:08X}", status.0));
+            }
+        }
+
+        // The consumer owns the sender; ProcessTrace returns once the
+        // session is stopped, after which the box is freed.
+        let context = Box::into_raw(Box::new(events)) as usize;
+        let consumer = thread::spawn(move || unsafe {
+            let mut name = U16CString::from_str(SESSION_NAME).expect("session name has no NULs");
+            let mut logfile = EVENT_TRACE_LOGFILEW {
+                LoggerName: PWSTR(name.as_mut_ptr()),
+                Context: context as *mut c_void,
+                ..Default::default()
+            };
+            logfile.Anonymous1.ProcessTraceMode =
+                PROCESS_TRACE_MODE_REAL_TIME | PROCESS_TRACE_MODE_EVENT_RECORD;
+            logfile.Anonymous2.EventRecordCallback = Some(on_event);
+
+            let handle = OpenTraceW(&mut logfile);
+            if handle.Value != INVALID_TRACE_HANDLE {
+                let _ = ProcessTrace(&[handle], None, None);
+                let _ = CloseTrace(handle);
+            }
+            drop(Box::from_raw(context as *mut Sender<EtwEvent>));
+        });
+
+        Ok(Self {
+            control,
+            consumer: Some(consumer),
+        })
+    }
+}
+
+impl Drop for EtwSession {
+    fn drop(&mut self) {
+        stop_trace(self.control);
+        if let Some(consumer) = self.consumer.take() {
+            let _ = consumer.join();
+        }
+    }
+}
+
+/// `EVENT_TRACE_PROPERTIES` followed by room for the session name, as
+/// StartTraceW and ControlTraceW require.
+#[repr(C)]
+struct TraceProperties {
+    properties: EVENT_TRACE_PROPERTIES,
+    #[allow(dead_code)] // filled in by ETW
+    name: [u16; 64],
+}
+
+impl TraceProperties {
+    fn new() -> Box<Self> {
+        let mut props: Box<Self> = Box::new(unsafe { std::mem::zeroed() });
+        props.properties.Wnode.BufferSize = size_of::<Self>() as u32;
+        props.properties.Wnode.Flags = WNODE_FLAG_TRACED_GUID;
+        // QueryPerformanceCounter resolution; ProcessTrace hands consumers
+        // system time.
+        props.properties.Wnode.ClientContext = 1;
+        props.properties.LogFileMode = EVENT_TRACE_REAL_TIME_MODE;
+        props.properties.LoggerNameOffset = size_of::<EVENT_TRACE_PROPERTIES>() as u32;
+        props
+    }
+}
+
+fn start_trace() -> Result<CONTROLTRACE_HANDLE> {
+    let name = U16CString::from_str(SESSION_NAME)?;
+    unsafe {
+        let mut handle = CONTROLTRACE_HANDLE::default();
+        let mut props = TraceProperties::new();
+        let mut status = StartTraceW(&mut handle, PCWSTR(name.as_ptr()), &mut props.properties);
+        if status == ERROR_ALREADY_EXISTS {
+            // Left behind by a previous run that did not shut down cleanly.
+            let mut stale = TraceProperties::new();
+            let _ = ControlTraceW(
+                CONTROLTRACE_HANDLE::default(),
+                PCWSTR(name.as_ptr()),
+                &mut stale.properties,
+                EVENT_TRACE_CONTROL_STOP,
+            );
+            props = TraceProperties::new();
+            status = StartTraceW(&mut handle, PCWSTR(name.as_ptr()), &mut props.properties);
+        }
+        if status != ERROR_SUCCESS {
+            return Err(anyhow!("StartTraceW failed: 0x{:08X}", status.0));
+        }
+        Ok(handle)
+    }
+}
+
+fn stop_trace(handle: CONTROLTRACE_HANDLE) {
+    let mut props = TraceProperties::new();
+    unsafe {
+        let _ = ControlTraceW(
+            handle,
+            PCWSTR::null(),
+            &mut props.properties,
+            EVENT_TRACE_CONTROL_STOP,
+        );
+    }
+}
+
+unsafe extern "system" fn on_event(record: *mut EVENT_RECORD) {
+    let Some(record) = record.as_ref() else {
+        return;
+    };
+    let Some(events) = (record.UserContext as *const Sender<EtwEvent>).as_ref() else {
+        return;
+    };
+    let descriptor = &record.EventHeader.EventDescriptor;
+    let (task, opcode) = event_names(record);
+    let Some(category) = EtwCategory::classify(descriptor.Level, &task, &opcode) else {
+        return;
+    };
+    let _ = events.send(EtwEvent {
+        time: filetime_to_utc(record.EventHeader.TimeStamp),
+        category,
+        id: descriptor.Id,
+        task,
+        opcode,
+        process_id: record.EventHeader.ProcessId,
+    });
+}
+
+/// Task and opcode names from the provider manifest via TDH.
+unsafe fn event_names(record: &EVENT_RECORD) -> (String, String) {
+    let mut size = 0u32;
+    let status = TdhGetEventInformation(record, None, None, &mut size);
+    if status != ERROR_INSUFFICIENT_BUFFER.0 || size == 0 {
+        return (String::new(), String::new());
+    }
+    // u64 storage keeps the TRACE_EVENT_INFO header aligned.
+    let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+    let info = buffer.as_mut_ptr() as *mut TRACE_EVENT_INFO;
+    if TdhGetEventInformation(record, None, Some(info), &mut size) != 0 {
+        return (String::new(), String::new());
+    }
+    let base = buffer.as_ptr() as *const u8;
+    let string_at = |offset: u32| {
+        if offset == 0 {
+            String::new()
+        } else {
+            U16CStr::from_ptr_str(base.add(offset as usize) as *const u16)
+                .to_string_lossy()
+                .trim()
+                .to_string()
+        }
+    };
+    (
+        string_at((*info).TaskNameOffset),
+        string_at((*info).OpcodeNameOffset),
+    )
+}
+
+fn filetime_to_utc(timestamp: i64) -> DateTime<Utc> {
+    let time = FILETIME {
+        dwLowDateTime: timestamp as u32,
+        dwHighDateTime: (timestamp >> 32) as u32,
+    };
+    crate::wfp::filetime_to_utc(time)
+}
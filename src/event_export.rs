@@ -0,0 +1,95 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::wfp::{NetEvent, TimeRange};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventExportFormat {
+    /// One JSON object per line, the usual SIEM ingest format.
+    JsonLines,
+    Csv,
+}
+
+impl EventExportFormat {
+    pub const ALL: [EventExportFormat; 2] = [EventExportFormat::JsonLines, EventExportFormat::Csv];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventExportFormat::JsonLines => "JSON Lines",
+            EventExportFormat::Csv => "CSV",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            EventExportFormat::JsonLines => "jsonl",
+            EventExportFormat::Csv => "csv",
+        }
+    }
+
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "jsonl" | "ndjson" | "json" => Some(EventExportFormat::JsonLines),
+            "csv" => Some(EventExportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the events inside `range` to `out` and returns how many were
+/// written.
+pub fn write_events<W: Write>(
+    events: &[NetEvent],
+    range: TimeRange,
+    format: EventExportFormat,
+    mut out: W,
+) -> Result<usize> {
+    let selected = events.iter().filter(|e| range.contains(e.time));
+    let mut written = 0;
+    match format {
+        EventExportFormat::JsonLines => {
+            for event in selected {
+                serde_json::to_writer(&mut out, event)?;
+                out.write_all(b"\n")?;
+                written += 1;
+            }
+            out.flush()?;
+        }
+        EventExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for event in selected {
+                writer.serialize(event)?;
+                written += 1;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(written)
+}
+
+/// Exports to `path`, picking the format from its extension unless one is
+/// given.
+pub fn export_events_file(
+    events: &[NetEvent],
+    range: TimeRange,
+    path: &Path,
+    format: Option<EventExportFormat>,
+) -> Result<usize> {
+    let format = format
+        .or_else(|| EventExportFormat::from_extension(path))
+        .ok_or_else(|| {
+            anyhow!(
+                "Cannot tell the export format of {}; use .jsonl or .csv",
+                path.display()
+            )
+        })?;
+    let file =
+        File::create(path).map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))?;
+    write_events(events, range, format, BufWriter::new(file))
+}
@@ -0,0 +1,246 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    wfp::{NetEvent, NetEventQuery},
+};
+
+const RETENTION_FILE: &str = "retention.json";
+const SEGMENT_PREFIX: &str = "events-";
+const SEGMENT_SUFFIX: &str = ".jsonl";
+
+/// Limits applied after every append. The oldest segment is dropped first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Retention {
+    /// Total size of all segments.
+    pub max_bytes: u64,
+    /// Segments whose newest event is older than this are dropped.
+    pub max_age_days: Option<u32>,
+    /// Size at which a new segment is started.
+    pub segment_bytes: u64,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_bytes: 256 * 1024 * 1024,
+            max_age_days: Some(30),
+            segment_bytes: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Size-capped on-disk history of net events: numbered JSON Lines segments
+/// in one directory, so blocked-connection history survives restarts and
+/// whole segments can be dropped cheaply when limits are hit.
+pub struct EventStore {
+    dir: PathBuf,
+    retention: Retention,
+}
+
+impl EventStore {
+    /// Opens (creating if needed) the store in `dir`, loading its saved
+    /// retention settings.
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).map_err(|e| anyhow!("Failed to create {}: {e}", dir.display()))?;
+        let retention = match fs::read_to_string(dir.join(RETENTION_FILE)) {
+            Ok(text) => serde_json::from_str(&text)?,
+            Err(_) => Retention::default(),
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            retention,
+        })
+    }
+
+    /// `events` under the app data directory.
+    pub fn default_dir() -> PathBuf {
+        config::app_data_dir().join("events")
+    }
+
+    pub fn retention(&self) -> Retention {
+        self.retention
+    }
+
+    pub fn set_retention(&mut self, retention: Retention) -> Result<()> {
+        if retention.segment_bytes == 0 || retention.max_bytes < retention.segment_bytes {
+            return Err(anyhow!(
+                "History size must be at least one segment ({} bytes)",
+                retention.segment_bytes
+            ));
+        }
+        fs::write(
+            self.dir.join(RETENTION_FILE),
+            serde_json::to_string_pretty(&retention)?,
+        )?;
+        self.retention = retention;
+        self.enforce_retention()
+    }
+
+    /// Appends the events newer than anything already stored and returns
+    /// how many were added. BFE hands back its whole buffer on every query,
+    /// so the newest stored timestamp is the high-water mark.
+    pub fn append(&self, events: &[NetEvent]) -> Result<usize> {
+        let newest = self.newest_time()?;
+        let mut fresh: Vec<&NetEvent> = events
+            .iter()
+            .filter(|e| newest.is_none_or(|newest| e.time > newest))
+            .collect();
+        if fresh.is_empty() {
+            return Ok(0);
+        }
+        fresh.sort_by_key(|e| e.time);
+
+        let mut segments = self.segments()?;
+        let mut path = match segments.pop() {
+            Some((_, path)) if file_len(&path) < self.retention.segment_bytes => path,
+            Some((number, _)) => self.segment_path(number + 1),
+            None => self.segment_path(1),
+        };
+        let mut writer = open_append(&path)?;
+        let mut size = file_len(&path);
+        for event in &fresh {
+            if size >= self.retention.segment_bytes {
+                writer.flush()?;
+                path = self.segment_path(segment_number(&path).unwrap_or(0) + 1);
+                writer = open_append(&path)?;
+                size = 0;
+            }
+            let mut line = serde_json::to_vec(event)?;
+            line.push(b'\n');
+            writer.write_all(&line)?;
+            size += line.len() as u64;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        self.enforce_retention()?;
+        Ok(fresh.len())
+    }
+
+    /// Stored events matching `query`, oldest first.
+    pub fn query(&self, query: &NetEventQuery) -> Result<Vec<NetEvent>> {
+        let mut out = Vec::new();
+        for (_, path) in self.segments()? {
+            for event in read_segment(&path)? {
+                if query.matches(&event) {
+                    out.push(event);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Total bytes on disk across all segments.
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.segments()?.iter().map(|(_, p)| file_len(p)).sum())
+    }
+
+    /// Deletes every stored event, returning the bytes freed.
+    pub fn purge(&self) -> Result<u64> {
+        let mut freed = 0;
+        for (_, path) in self.segments()? {
+            freed += file_len(&path);
+            fs::remove_file(&path)
+                .map_err(|e| anyhow!("Failed to remove {}: {e}", path.display()))?;
+        }
+        Ok(freed)
+    }
+
+    fn enforce_retention(&self) -> Result<()> {
+        let mut segments = self.segments()?;
+        let cutoff = self
+            .retention
+            .max_age_days
+            .map(|days| Utc::now() - Duration::days(i64::from(days)));
+        let mut total: u64 = segments.iter().map(|(_, p)| file_len(p)).sum();
+
+        // Never drop the segment currently being written.
+        while segments.len() > 1 {
+            let (_, oldest) = &segments[0];
+            let expired = match cutoff {
+                Some(cutoff) => last_time(oldest)?.is_none_or(|t| t < cutoff),
+                None => false,
+            };
+            if !expired && total <= self.retention.max_bytes {
+                break;
+            }
+            total -= file_len(oldest);
+            fs::remove_file(oldest)
+                .map_err(|e| anyhow!("Failed to remove {}: {e}", oldest.display()))?;
+            segments.remove(0);
+        }
+        Ok(())
+    }
+
+    fn newest_time(&self) -> Result<Option<DateTime<Utc>>> {
+        match self.segments()?.last() {
+            Some((_, path)) => last_time(path),
+            None => Ok(None),
+        }
+    }
+
+    /// Segment files sorted by number, oldest first.
+    fn segments(&self) -> Result<Vec<(u64, PathBuf)>> {
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some(number) = segment_number(&path) {
+                segments.push((number, path));
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    fn segment_path(&self, number: u64) -> PathBuf {
+        self.dir
+            .join(format!("{SEGMENT_PREFIX}{number:06}{SEGMENT_SUFFIX}"))
+    }
+}
+
+fn segment_number(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(SEGMENT_PREFIX)?
+        .strip_suffix(SEGMENT_SUFFIX)?
+        .parse()
+        .ok()
+}
+
+fn file_len(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn open_append(path: &Path) -> Result<BufWriter<File>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| anyhow!("Failed to open {}: {e}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+/// Reads a segment, skipping a torn last line left by a crash mid-write.
+fn read_segment(path: &Path) -> Result<Vec<NetEvent>> {
+    let file = File::open(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let mut events = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(event) = serde_json::from_str(&line?) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+fn last_time(path: &Path) -> Result<Option<DateTime<Utc>>> {
+    Ok(read_segment(path)?.last().map(|e| e.time))
+}
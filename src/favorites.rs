@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    wfp::{guid_from_uuid, Engine, FilterConfig, FilterSummary, GUID},
+};
+
+const FAVORITES_FILE: &str = "favorites.json";
+
+/// A rule pinned to the Favorites strip. The rule is kept in export form so
+/// switching it off, which deletes its filters, leaves enough to install it
+/// again.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Favorite {
+    pub config: FilterConfig,
+}
+
+impl Favorite {
+    /// Pins `config`, which must carry the key of the installed rule.
+    pub fn new(config: FilterConfig) -> Result<Self> {
+        if config.key.is_none() {
+            return Err(anyhow!("Only rules with a key can be pinned"));
+        }
+        Ok(Self { config })
+    }
+
+    pub fn rule_key(&self) -> GUID {
+        guid_from_uuid(self.config.key.unwrap_or_default())
+    }
+
+    /// Whether the rule currently has filters among `filters`.
+    pub fn is_on(&self, filters: &[FilterSummary]) -> bool {
+        let key = self.rule_key();
+        filters
+            .iter()
+            .any(|f| f.owned_by_app && f.rule_key() == key)
+    }
+
+    /// Installs the rule, or deletes its filters when `on` is false.
+    pub fn set(&self, engine: &Engine, on: bool) -> Result<()> {
+        if on {
+            engine.import_filters(std::slice::from_ref(&self.config))?;
+        } else {
+            engine.delete_filter_by_key(self.rule_key())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn load_favorites() -> Result<Vec<Favorite>> {
+    config::load_settings(FAVORITES_FILE)
+}
+
+pub fn save_favorites(favorites: &[Favorite]) -> Result<()> {
+    config::save_settings(FAVORITES_FILE, favorites)
+}
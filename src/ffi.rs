@@ -0,0 +1,245 @@
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use anyhow::{anyhow, Result};
+use uuid::Uuid;
+
+use crate::{
+    config::{self, RuleFormat},
+    wfp::{guid_from_uuid, uuid_from_guid, Engine, FilterRecord, WfpAction},
+};
+
+/// An open BFE session. Opaque to callers.
+///
+/// A session may move between threads but must not be used from more
+/// than one at a time: callers sharing one serialize their calls, e.g.
+/// behind a mutex, or open a session per thread.
+pub struct SlsWfpEngine(Engine);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlsWfpResult {
+    Ok = 0,
+    /// A required pointer was null, a string was not valid UTF-8 or a value
+    /// was out of range.
+    InvalidArgument = 1,
+    /// The call failed; see `sls_wfp_last_error`.
+    Failed = 2,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlsWfpAction {
+    Permit = 0,
+    Block = 1,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Opens a BFE session. Returns null on failure.
+#[no_mangle]
+pub extern "C" fn sls_wfp_engine_open() -> *mut SlsWfpEngine {
+    match guard(Engine::open) {
+        Ok(engine) => Box::into_raw(Box::new(SlsWfpEngine(engine))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Closes a session from `sls_wfp_engine_open`. Null is ignored.
+///
+/// # Safety
+/// `engine` must be null or a pointer returned by `sls_wfp_engine_open`
+/// that has not been closed.
+#[no_mangle]
+pub unsafe extern "C" fn sls_wfp_engine_close(engine: *mut SlsWfpEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Adds an outbound TCP rule for `port_count` remote ports. `action` is an
+/// `SlsWfpAction`; any other value is an invalid argument. On success the
+/// new rule key is written to `key_out` (free it with `sls_wfp_string_free`)
+/// when `key_out` is not null.
+///
+/// # Safety
+/// `engine` must be an open session, `name` a NUL-terminated string and
+/// `ports` point to `port_count` values.
+#[no_mangle]
+pub unsafe extern "C" fn sls_wfp_add_tcp_filter(
+    engine: *mut SlsWfpEngine,
+    name: *const c_char,
+    ports: *const u16,
+    port_count: usize,
+    action: u32,
+    key_out: *mut *mut c_char,
+) -> SlsWfpResult {
+    let (Some(engine), Some(name)) = (engine.as_ref(), str_arg(name)) else {
+        return invalid_argument("engine and name are required");
+    };
+    if ports.is_null() || port_count == 0 {
+        return invalid_argument("at least one port is required");
+    }
+    let ports = std::slice::from_raw_parts(ports, port_count);
+    // Taken as an integer: a C caller can pass any value, and one outside
+    // a Rust enum would be undefined behaviour before this check.
+    let action = match action {
+        a if a == SlsWfpAction::Permit as u32 => WfpAction::Permit,
+        a if a == SlsWfpAction::Block as u32 => WfpAction::Block,
+        a => return invalid_argument(&format!("unknown action {a}")),
+    };
+    status(guard(|| {
+        let key = engine.0.add_simple_tcp_filter_v4(name, ports, action)?;
+        if !key_out.is_null() {
+            *key_out = into_c_string(uuid_from_guid(key).to_string());
+        }
+        Ok(())
+    }))
+}
+
+/// Deletes an owned rule by its key, e.g. `"6f1d0c52-..."`.
+///
+/// # Safety
+/// `engine` must be an open session and `key` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sls_wfp_delete_filter(
+    engine: *mut SlsWfpEngine,
+    key: *const c_char,
+) -> SlsWfpResult {
+    let (Some(engine), Some(key)) = (engine.as_ref(), str_arg(key)) else {
+        return invalid_argument("engine and key are required");
+    };
+    status(guard(|| {
+        let key = Uuid::parse_str(key.trim()).map_err(|e| anyhow!("Invalid key '{key}': {e}"))?;
+        engine.0.delete_filter_by_key(guid_from_uuid(key))
+    }))
+}
+
+/// Imports rules from the same JSON the GUI exports. Counts are written to
+/// `added_out` and `updated_out` when they are not null.
+///
+/// # Safety
+/// `engine` must be an open session and `json` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sls_wfp_import_json(
+    engine: *mut SlsWfpEngine,
+    json: *const c_char,
+    added_out: *mut usize,
+    updated_out: *mut usize,
+) -> SlsWfpResult {
+    let (Some(engine), Some(json)) = (engine.as_ref(), str_arg(json)) else {
+        return invalid_argument("engine and json are required");
+    };
+    status(guard(|| {
+        let configs = config::parse_rules(json, RuleFormat::Json)?;
+        let summary = engine.0.import_filters(&configs)?;
+        if !added_out.is_null() {
+            *added_out = summary.added;
+        }
+        if !updated_out.is_null() {
+            *updated_out = summary.updated;
+        }
+        Ok(())
+    }))
+}
+
+/// Writes every filter BFE reports as a JSON array to `json_out`; free it
+/// with `sls_wfp_string_free`.
+///
+/// # Safety
+/// `engine` must be an open session and `json_out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sls_wfp_filters_json(
+    engine: *mut SlsWfpEngine,
+    json_out: *mut *mut c_char,
+) -> SlsWfpResult {
+    let Some(engine) = engine.as_ref() else {
+        return invalid_argument("engine is required");
+    };
+    if json_out.is_null() {
+        return invalid_argument("json_out is required");
+    }
+    status(guard(|| {
+        let filters: Vec<FilterRecord> = engine
+            .0
+            .snapshot()?
+            .filters
+            .iter()
+            .map(FilterRecord::from)
+            .collect();
+        *json_out = into_c_string(serde_json::to_string(&filters)?);
+        Ok(())
+    }))
+}
+
+/// Frees a string returned by this library. Null is ignored.
+///
+/// # Safety
+/// `s` must be null or a string returned by this library that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn sls_wfp_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Message for the last failed call on this thread, or null. Valid until the
+/// next call into this library on the same thread.
+#[no_mangle]
+pub extern "C" fn sls_wfp_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Runs `f`, recording any error or panic as the thread's last error.
+/// Unwinding into C is undefined behaviour, so panics stop here.
+fn guard<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    set_last_error(None);
+    let result = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(anyhow!("Internal error (panic) in sls_wfp")));
+    if let Err(err) = &result {
+        set_last_error(Some(err.to_string()));
+    }
+    result
+}
+
+fn status(result: Result<()>) -> SlsWfpResult {
+    match result {
+        Ok(()) => SlsWfpResult::Ok,
+        Err(_) => SlsWfpResult::Failed,
+    }
+}
+
+fn invalid_argument(message: &str) -> SlsWfpResult {
+    set_last_error(Some(message.to_string()));
+    SlsWfpResult::InvalidArgument
+}
+
+fn set_last_error(message: Option<String>) {
+    let message = message.map(|m| CString::new(m.replace('\0', " ")).expect("NULs replaced"));
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok()
+    }
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', " "))
+        .expect("NULs replaced")
+        .into_raw()
+}
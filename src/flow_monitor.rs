@@ -0,0 +1,44 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    preset::{self, Preset},
+    wfp::{self, flow_monitor_rule, Engine, FilterBuilder, GUID},
+};
+
+/// Established connections recorded as allow events without blocking
+/// anything, for every application or only the listed ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlowMonitor {
+    /// Full executable paths; empty monitors every application.
+    pub apps: Vec<String>,
+}
+
+/// Disabling leaves allow events on, as other tools may rely on them.
+impl Preset for FlowMonitor {
+    const SETTINGS_FILE: &'static str = "flow_monitor.json";
+    const RULE_NAME: &'static str = "Monitor connections";
+    const RULE_KEY: GUID = GUID::from_values(
+        0x410850d3,
+        0x0ced,
+        0x4285,
+        [0x87, 0xb4, 0x89, 0xf2, 0xe8, 0x0a, 0xe5, 0x20],
+    );
+
+    fn rule(&self, name: &str) -> Result<Vec<FilterBuilder>> {
+        let app_ids = self
+            .apps
+            .iter()
+            .map(|path| wfp::app_id(path))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(flow_monitor_rule(Self::RULE_KEY, name, &app_ids))
+    }
+
+    /// Also turns on classify-allow net events, which the filters log to.
+    fn enable(&self, engine: &Engine) -> Result<usize> {
+        let added = preset::install(self, engine)?;
+        engine.record_allow_events()?;
+        Ok(added)
+    }
+}
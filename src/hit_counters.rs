@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config,
+    wfp::{uuid_from_guid, FilterSummary, NetEvent, GUID},
+};
+
+const COUNTERS_FILE: &str = "hit_counters.json";
+
+/// How often one filter decided a flow since its counter was last reset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hits {
+    pub hits: u64,
+    pub last_hit: Option<DateTime<Utc>>,
+    /// When counting started, or the counter was last reset.
+    pub since: DateTime<Utc>,
+}
+
+impl Hits {
+    fn new(since: DateTime<Utc>) -> Self {
+        Self {
+            hits: 0,
+            last_hit: None,
+            since,
+        }
+    }
+
+    /// `12 hits, last 2026-10-01 14:03` or `no hits in 90 days`.
+    pub fn summary(&self) -> String {
+        match self.last_hit {
+            Some(last) => format!(
+                "{} hit(s), last {}",
+                self.hits,
+                last.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => format!("no hits in {} days", (Utc::now() - self.since).num_days()),
+        }
+    }
+}
+
+/// Hit counters per filter, counted from net events and kept across
+/// restarts. They are keyed by filter key rather than runtime ID, which
+/// BFE reassigns whenever a filter is re-added.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HitCounters {
+    /// When counting started for filters without a counter of their own.
+    started: Option<DateTime<Utc>>,
+    /// Newest event counted, so events read twice are counted once.
+    counted_until: Option<DateTime<Utc>>,
+    filters: BTreeMap<Uuid, Hits>,
+}
+
+impl HitCounters {
+    /// The saved counters, or none before any are saved.
+    pub fn load() -> Result<Self> {
+        config::load_settings(COUNTERS_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        config::save_settings(COUNTERS_FILE, self)
+    }
+
+    /// Loads the saved counters, counts `events` in and saves them again.
+    /// Several processes may count the same events; the file is re-read so
+    /// each is counted once.
+    pub fn add_events(events: &[NetEvent], filters: &[FilterSummary]) -> Result<Self> {
+        let mut counters = Self::load()?;
+        let first = counters.started.is_none();
+        if counters.record(events, filters) > 0 || first {
+            counters.save()?;
+        }
+        Ok(counters)
+    }
+
+    /// Counts the events newer than any counted before against the filters
+    /// that decided them, and returns how many were counted.
+    pub fn record(&mut self, events: &[NetEvent], filters: &[FilterSummary]) -> usize {
+        let now = Utc::now();
+        let started = *self.started.get_or_insert(now);
+        let newest = self.counted_until;
+        let mut counted = 0;
+        for event in events
+            .iter()
+            .filter(|e| newest.is_none_or(|newest| e.time > newest))
+        {
+            self.counted_until = self.counted_until.max(Some(event.time));
+            let Some(filter) = filters.iter().find(|f| f.id == event.filter_id) else {
+                continue;
+            };
+            let hits = self
+                .filters
+                .entry(uuid_from_guid(filter.key))
+                .or_insert_with(|| Hits::new(started));
+            hits.hits += 1;
+            hits.last_hit = hits.last_hit.max(Some(event.time));
+            counted += 1;
+        }
+        counted
+    }
+
+    /// The counter of one filter.
+    pub fn filter(&self, key: GUID) -> Hits {
+        self.filters
+            .get(&uuid_from_guid(key))
+            .copied()
+            .unwrap_or_else(|| Hits::new(self.started.unwrap_or_else(Utc::now)))
+    }
+
+    /// The counters of the members of rule `key` in `filters`, added up.
+    /// Counting starts at the most recent start among them.
+    pub fn rule(&self, key: GUID, filters: &[FilterSummary]) -> Hits {
+        let mut total = self.filter(key);
+        for filter in filters
+            .iter()
+            .filter(|f| f.rule_key() == key && f.key != key)
+        {
+            let hits = self.filter(filter.key);
+            total.hits += hits.hits;
+            total.last_hit = total.last_hit.max(hits.last_hit);
+            total.since = total.since.max(hits.since);
+        }
+        total
+    }
+
+    /// Starts the counters of rule `key` and its members afresh.
+    pub fn reset_rule(&mut self, key: GUID, filters: &[FilterSummary]) {
+        let now = Utc::now();
+        let members = filters
+            .iter()
+            .filter(|f| f.rule_key() == key)
+            .map(|f| f.key)
+            .chain([key]);
+        for member in members {
+            self.filters.insert(uuid_from_guid(member), Hits::new(now));
+        }
+    }
+
+    /// Starts every counter afresh.
+    pub fn reset_all(&mut self) {
+        self.filters.clear();
+        self.started = Some(Utc::now());
+    }
+}
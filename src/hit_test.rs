@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde::Serialize;
+
+use crate::{
+    event_store::EventStore,
+    troubleshoot::EventMatch,
+    wfp::{Direction, FilterConfig, FlowDirection, NetEvent, NetEventQuery, TimeRange},
+};
+
+/// How far back a proposed rule is tested by default.
+pub const DEFAULT_DAYS: u32 = 7;
+
+/// Matched events kept as examples.
+const MAX_SAMPLES: usize = 10;
+
+/// How a proposed rule would have fared against past traffic.
+#[derive(Clone, Debug, Serialize)]
+pub struct HitTest {
+    pub days: u32,
+    /// Saved events in the window.
+    pub scanned: usize,
+    pub matched: usize,
+    /// Events the rule may match, depending on fields BFE did not record
+    /// or the rule's network type.
+    pub possible: usize,
+    /// Matched events per application, most first.
+    pub apps: Vec<(String, usize)>,
+    /// The most recent matched events.
+    pub samples: Vec<NetEvent>,
+}
+
+impl HitTest {
+    /// Tests `config` against the saved events of the last `days` days.
+    pub fn against_history(config: &FilterConfig, days: u32) -> Result<Self> {
+        let query = NetEventQuery {
+            range: TimeRange {
+                from: Some(Utc::now() - Duration::days(i64::from(days))),
+                to: None,
+            },
+            ..Default::default()
+        };
+        let events = EventStore::open(&EventStore::default_dir())?.query(&query)?;
+        Ok(Self::run(config, &events, days))
+    }
+
+    pub fn run(config: &FilterConfig, events: &[NetEvent], days: u32) -> Self {
+        let mut matched = Vec::new();
+        let mut possible = 0;
+        for event in events {
+            match match_config(config, event) {
+                EventMatch::Yes => matched.push(event),
+                EventMatch::Unknown => possible += 1,
+                EventMatch::No => {}
+            }
+        }
+        let mut apps: HashMap<&str, usize> = HashMap::new();
+        for event in &matched {
+            *apps
+                .entry(event.app_id.as_deref().unwrap_or("(unknown)"))
+                .or_default() += 1;
+        }
+        let mut apps: Vec<(String, usize)> = apps
+            .into_iter()
+            .map(|(app, count)| (app.to_string(), count))
+            .collect();
+        apps.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Self {
+            days,
+            scanned: events.len(),
+            matched: matched.len(),
+            possible,
+            apps,
+            samples: matched
+                .iter()
+                .rev()
+                .take(MAX_SAMPLES)
+                .map(|e| (*e).clone())
+                .collect(),
+        }
+    }
+
+    /// One line for the status bar or terminal.
+    pub fn summary(&self) -> String {
+        if self.scanned == 0 {
+            return format!(
+                "No saved net events in the last {} days to test against",
+                self.days
+            );
+        }
+        let mut text = format!(
+            "Would have matched {} of {} saved connections in the last {} days",
+            self.matched, self.scanned, self.days
+        );
+        if self.possible > 0 {
+            text += &format!(", and may match {} more", self.possible);
+        }
+        text
+    }
+}
+
+/// Whether the rule matches the flow in `event`, as far as the event
+/// records it. Addresses are compared by network, apps by path.
+fn match_config(config: &FilterConfig, event: &NetEvent) -> EventMatch {
+    let want_direction = match config.direction {
+        Direction::Out => FlowDirection::Outbound,
+        Direction::In => FlowDirection::Inbound,
+    };
+    let ports = config.remote_port.as_slice();
+    let local_ports = config.local_port.as_slice();
+    let checks = [
+        Some(event.direction.map(|d| d == want_direction)),
+        config
+            .protocol()
+            .number()
+            .map(|p| event.protocol.map(|q| q == p)),
+        (!ports.is_empty()).then(|| event.remote_port.map(|p| ports.contains(&p))),
+        (!local_ports.is_empty()).then(|| event.local_port.map(|p| local_ports.contains(&p))),
+        (!config.remote_address.is_empty() || !config.remote_host.is_empty()).then(|| {
+            // Hostnames that do not resolve cannot be compared.
+            let addresses = config.remote_addresses().ok()?;
+            event
+                .remote_addr
+                .map(|addr| addresses.iter().any(|net| net.contains(addr)))
+        }),
+        config.app.as_ref().map(|_| {
+            // A pattern that no longer resolves cannot be compared.
+            let apps = config.app_paths().ok()?;
+            event
+                .app_id
+                .as_deref()
+                .map(|device_path| apps.iter().any(|app| same_app(app, device_path)))
+        }),
+        // Events do not record the interface.
+        config.interface.map(|_| None),
+    ];
+    let mut overall = EventMatch::Yes;
+    for check in checks.into_iter().flatten() {
+        match check {
+            Some(true) => {}
+            Some(false) => return EventMatch::No,
+            None => overall = EventMatch::Unknown,
+        }
+    }
+    overall
+}
+
+/// `C:\dir\app.exe` against BFE's `\device\harddiskvolume3\dir\app.exe`:
+/// the drive cannot be mapped offline, so the rest of the path is compared.
+fn same_app(path: &str, device_path: &str) -> bool {
+    let path = path.trim().to_ascii_lowercase();
+    let rest = match path.as_bytes() {
+        [_, b':', ..] => &path[2..],
+        _ => path.as_str(),
+    };
+    !rest.is_empty() && device_path.to_ascii_lowercase().ends_with(rest)
+}
@@ -0,0 +1,135 @@
+use std::{cmp::Reverse, net::IpAddr};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::wfp::{
+    is_hostname, resolve_host, ConditionField, ConditionValue, FilterSummary, MatchType,
+    SublayerInfo,
+};
+
+/// How a filter's remote address condition refers to the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressMatch {
+    /// Names the address itself.
+    Exact,
+    /// A network the address falls in.
+    Network,
+    /// An address range spanning it.
+    Range,
+    /// Every address but this one.
+    Except,
+}
+
+impl AddressMatch {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AddressMatch::Exact => "exact",
+            AddressMatch::Network => "network",
+            AddressMatch::Range => "range",
+            AddressMatch::Except => "all but it",
+        }
+    }
+}
+
+/// A filter whose conditions refer to one of the host's addresses.
+#[derive(Clone)]
+pub struct HostPolicyEntry {
+    pub filter: FilterSummary,
+    pub address: IpAddr,
+    pub matched: AddressMatch,
+    /// The condition, e.g. `remote address = 10.0.0.0/255.0.0.0`.
+    pub condition: String,
+    /// Weight of the filter's sublayer, when the sublayer is known.
+    pub sublayer_weight: Option<u16>,
+}
+
+/// Every filter, from any provider, with a remote address condition that
+/// refers to a host, in the order BFE arbitrates them. Filters without an
+/// address condition apply to the host too, but are left out.
+pub struct HostPolicy {
+    pub host: String,
+    pub addresses: Vec<IpAddr>,
+    pub entries: Vec<HostPolicyEntry>,
+}
+
+impl HostPolicy {
+    /// The policy for `host`, an address or a hostname resolved now.
+    pub fn build(
+        host: &str,
+        filters: &[FilterSummary],
+        sublayers: &[SublayerInfo],
+    ) -> Result<Self> {
+        let host = host.trim();
+        let addresses = match host.parse::<IpAddr>() {
+            Ok(addr) => vec![addr],
+            Err(_) if is_hostname(host) => resolve_host(host)?,
+            Err(_) => return Err(anyhow!("'{host}' is not an address or hostname")),
+        };
+        let mut entries = Vec::new();
+        for filter in filters {
+            let found = filter
+                .conditions
+                .iter()
+                .filter(|c| c.field == ConditionField::RemoteAddress)
+                .find_map(|c| {
+                    addresses.iter().find_map(|addr| {
+                        if !c.value.contains_address(*addr)? {
+                            return None;
+                        }
+                        let matched = match (c.match_type, &c.value) {
+                            (MatchType::NotEqual, _) => AddressMatch::Except,
+                            (MatchType::Range, ConditionValue::Range { .. }) => AddressMatch::Range,
+                            (
+                                MatchType::Equal,
+                                ConditionValue::Uint32(_) | ConditionValue::ByteArray16(_),
+                            ) => AddressMatch::Exact,
+                            (MatchType::Equal, _) => AddressMatch::Network,
+                            _ => return None,
+                        };
+                        Some((*addr, matched, c.to_string()))
+                    })
+                });
+            if let Some((address, matched, condition)) = found {
+                entries.push(HostPolicyEntry {
+                    filter: filter.clone(),
+                    address,
+                    matched,
+                    condition,
+                    sublayer_weight: sublayers
+                        .iter()
+                        .find(|s| s.key == filter.sublayer_key)
+                        .map(|s| s.weight),
+                });
+            }
+        }
+        entries.sort_by_key(|e| {
+            (
+                e.filter.layer.clone(),
+                Reverse(e.sublayer_weight),
+                Reverse(e.filter.effective_weight.or(e.filter.weight)),
+            )
+        });
+        Ok(Self {
+            host: host.to_string(),
+            addresses,
+            entries,
+        })
+    }
+
+    /// `4 filter(s) refer to example.com (93.184.216.34)`.
+    pub fn summary(&self) -> String {
+        let addresses: Vec<String> = self.addresses.iter().map(ToString::to_string).collect();
+        let addresses = addresses.join(", ");
+        if addresses == self.host {
+            format!("{} filter(s) refer to {}", self.entries.len(), self.host)
+        } else {
+            format!(
+                "{} filter(s) refer to {} ({addresses})",
+                self.entries.len(),
+                self.host
+            )
+        }
+    }
+}
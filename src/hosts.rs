@@ -0,0 +1,161 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+
+use crate::{
+    config::{self, RuleFormat},
+    wfp::{Engine, ImportSummary},
+};
+
+const HOSTS_FILE: &str = "hosts.json";
+
+/// What the last look at a host found.
+pub enum HostStatus {
+    Reachable {
+        filters: usize,
+        owned: usize,
+        checked: DateTime<Local>,
+    },
+    Unreachable(String),
+}
+
+pub struct Host {
+    /// `None` for this machine.
+    pub name: Option<String>,
+    pub status: Option<HostStatus>,
+}
+
+impl Host {
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or("This machine")
+    }
+
+    pub fn open(&self) -> Result<Engine> {
+        Engine::open_on(self.name.as_deref())
+    }
+
+    /// Opens the host and records its filter counts, or why it could not
+    /// be reached.
+    pub fn check(&mut self) {
+        let result = self.open().and_then(|engine| engine.snapshot());
+        self.status = Some(match result {
+            Ok(snapshot) => HostStatus::Reachable {
+                filters: snapshot.filters.len(),
+                owned: snapshot.filters.iter().filter(|f| f.owned_by_app).count(),
+                checked: Local::now(),
+            },
+            Err(err) => HostStatus::Unreachable(err.to_string()),
+        });
+    }
+}
+
+/// The machines managed from one window, shown as tabs. The first host is
+/// always this machine; remote hosts are saved across runs.
+pub struct Hosts {
+    pub hosts: Vec<Host>,
+    pub active: usize,
+}
+
+impl Hosts {
+    pub fn load() -> Result<Self> {
+        let names: Vec<String> = config::load_settings(HOSTS_FILE)?;
+        let local = Host {
+            name: None,
+            status: None,
+        };
+        let remote = names.into_iter().map(|name| Host {
+            name: Some(name),
+            status: None,
+        });
+        Ok(Self {
+            hosts: std::iter::once(local).chain(remote).collect(),
+            active: 0,
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        let names: Vec<&str> = self
+            .hosts
+            .iter()
+            .filter_map(|h| h.name.as_deref())
+            .collect();
+        config::save_protected_settings(HOSTS_FILE, &names)
+    }
+
+    pub fn active(&self) -> &Host {
+        &self.hosts[self.active]
+    }
+
+    /// Opens a session on the host of the active tab.
+    pub fn open(&self) -> Result<Engine> {
+        self.active().open()
+    }
+
+    pub fn add(&mut self, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("Host name is required"));
+        }
+        if self.hosts.iter().any(|h| {
+            h.name
+                .as_deref()
+                .is_some_and(|n| n.eq_ignore_ascii_case(name))
+        }) {
+            return Err(anyhow!("{name} is already open"));
+        }
+        self.hosts.push(Host {
+            name: Some(name.to_string()),
+            status: None,
+        });
+        self.save()
+    }
+
+    /// Removes a remote host. This machine cannot be removed.
+    pub fn remove(&mut self, idx: usize) -> Result<()> {
+        if idx == 0 || idx >= self.hosts.len() {
+            return Ok(());
+        }
+        self.hosts.remove(idx);
+        if self.active >= idx {
+            self.active -= 1;
+        }
+        self.save()
+    }
+
+    pub fn check_all(&mut self) {
+        for host in &mut self.hosts {
+            host.check();
+        }
+    }
+
+    /// Copies the owned rules and sublayers of the active host to every
+    /// other host. Rules are imported by key, so running it again updates
+    /// rather than duplicates. Returns each target host with its result.
+    pub fn push_rules(&mut self) -> Result<Vec<(String, Result<ImportSummary>)>> {
+        let json = self.open()?.export_owned_filters()?;
+        let set = config::parse_rule_set(&json, RuleFormat::Json)?;
+        let active = self.active;
+        let mut results = Vec::new();
+        for (idx, host) in self.hosts.iter_mut().enumerate() {
+            if idx == active {
+                continue;
+            }
+            let result = host.open().and_then(|engine| engine.import_rule_set(&set));
+            host.check();
+            results.push((host.label().to_string(), result));
+        }
+        Ok(results)
+    }
+}
+
+impl Default for Hosts {
+    /// Just this machine.
+    fn default() -> Self {
+        Self {
+            hosts: vec![Host {
+                name: None,
+                status: None,
+            }],
+            active: 0,
+        }
+    }
+}
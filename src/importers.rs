@@ -0,0 +1,546 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use anyhow::{anyhow, Result};
+use roxmltree::{Document, Node};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::wfp::{
+    guid_from_uuid, known_provider, remote_port, CalloutAction, CalloutKind, Condition,
+    ConditionField, ConditionValue, FilterBuilder, FilterConfig, FilterSummary, MatchType,
+    RemotePorts, WeightKind, WfpAction, GUID, PROVIDER_KEY,
+};
+
+/// Rules from a simplewall `profile.xml`. Enabled apps become allow rules
+/// for the whole app. Enabled outbound custom rules are kept when they list
+/// only remote TCP ports, or nothing at all; rules naming addresses, port
+/// ranges or other protocols have no equivalent here and are skipped.
+pub fn simplewall_rules(text: &str) -> Result<Vec<FilterConfig>> {
+    let doc = Document::parse(text).map_err(|e| anyhow!("Invalid simplewall profile: {e}"))?;
+    let mut configs = Vec::new();
+    for item in doc.descendants().filter(|n| n.has_tag_name("item")) {
+        if item.attribute("is_enabled") != Some("true") {
+            continue;
+        }
+        match item.parent_element().map(|p| p.tag_name().name()) {
+            Some("apps") => {
+                if let Some(path) = item.attribute("path") {
+                    configs.push(app_config("simplewall", path, WfpAction::Permit, None));
+                }
+            }
+            Some("rules_custom") => configs.extend(simplewall_custom_rule(item)),
+            _ => {}
+        }
+    }
+    finish("simplewall profile", configs)
+}
+
+fn simplewall_custom_rule(item: Node) -> Vec<FilterConfig> {
+    let name = item.attribute("name").unwrap_or("simplewall rule");
+    let inbound = item.attribute("dir") == Some("1");
+    let protocol = item.attribute("protocol").unwrap_or("0");
+    let Some(ports) = port_list(item.attribute("rule").unwrap_or(""), ';') else {
+        return Vec::new();
+    };
+    let tcp = protocol == "6" || (protocol == "0" && ports.is_empty());
+    if inbound || !tcp {
+        return Vec::new();
+    }
+    let action = if item.attribute("is_block") == Some("true") {
+        WfpAction::Block
+    } else {
+        WfpAction::Permit
+    };
+    let apps: Vec<&str> = item
+        .attribute("apps")
+        .unwrap_or("")
+        .split('|')
+        .map(str::trim)
+        .filter(|app| !app.is_empty())
+        .collect();
+    if apps.is_empty() {
+        if ports.is_empty() {
+            return Vec::new();
+        }
+        let mut config = FilterConfig::tcp_ports(name, ports, action);
+        config.key = Some(derived_key("simplewall", name, ""));
+        return vec![config];
+    }
+    apps.into_iter()
+        .map(|app| {
+            let mut config = app_config("simplewall", app, action, Some(name));
+            config.remote_port = ports.clone();
+            config
+        })
+        .collect()
+}
+
+/// Rules from a TinyWall settings export. Each application exception with
+/// an executable path maps to one rule: unrestricted exceptions allow the
+/// app, hard blocks block it, and TCP/UDP exceptions allow outbound TCP to
+/// the listed ports. Exceptions for services without a path, UWP apps and
+/// listening or UDP-only exceptions are skipped.
+pub fn tinywall_rules(text: &str) -> Result<Vec<FilterConfig>> {
+    let doc = Document::parse(text).map_err(|e| anyhow!("Invalid TinyWall export: {e}"))?;
+    let mut configs = Vec::new();
+    for exception in doc
+        .descendants()
+        .filter(|n| n.tag_name().name().starts_with("FirewallException"))
+    {
+        let Some(path) = child_text(exception, "ExecutablePath") else {
+            continue;
+        };
+        let Some(policy) = exception
+            .descendants()
+            .find(|n| n.tag_name().name() == "Policy")
+        else {
+            continue;
+        };
+        let kind = policy
+            .attributes()
+            .find(|a| a.name() == "type")
+            .map(|a| a.value().rsplit(':').next().unwrap_or(a.value()))
+            .unwrap_or("");
+        let (action, ports) = match kind {
+            "UnrestrictedPolicy" => (WfpAction::Permit, RemotePorts::default()),
+            "HardBlockPolicy" => (WfpAction::Block, RemotePorts::default()),
+            "TcpUdpPolicy" => {
+                let ports =
+                    child_text(policy, "AllowedRemoteTcpConnectPorts").map(|list| {
+                        match list.trim() {
+                            "*" => Some(RemotePorts::default()),
+                            list => port_list(list, ',').filter(|ports| !ports.is_empty()),
+                        }
+                    });
+                match ports {
+                    Some(Some(ports)) => (WfpAction::Permit, ports),
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+        let mut config = app_config("TinyWall", path, action, None);
+        config.remote_port = ports;
+        configs.push(config);
+    }
+    finish("TinyWall export", configs)
+}
+
+/// Names `netsh wfp` prints for well-known layers, sublayers, providers and
+/// condition fields, with their keys from fwpmu.h.
+const WFP_NAMES: &str = include_str!("../data/wfp_names.json");
+
+/// The filters in the XML `netsh wfp show filters` writes, e.g. a
+/// `filters.xml` collected on another machine, for comparing with this one
+/// or adopting. Layers, sublayers and providers keep the names netsh gave
+/// them; keys netsh names symbolically are looked up in [`WFP_NAMES`], and
+/// names it does not know leave a nil key.
+pub fn netsh_filters(text: &str) -> Result<Vec<FilterSummary>> {
+    let doc = Document::parse(text.trim_start_matches('\u{feff}').trim())
+        .map_err(|e| anyhow!("Invalid netsh output: {e}"))?;
+    let names: HashMap<String, Uuid> =
+        serde_json::from_str(WFP_NAMES).expect("embedded WFP names are valid");
+    let key = |text: &str| -> GUID {
+        let text = text.trim();
+        text.trim_matches(['{', '}'])
+            .parse::<Uuid>()
+            .ok()
+            .or_else(|| names.get(text).copied())
+            .map(guid_from_uuid)
+            .unwrap_or_else(|| guid_from_uuid(Uuid::nil()))
+    };
+    let Some(list) = doc.descendants().find(|n| n.has_tag_name("filters")) else {
+        return Err(anyhow!(
+            "No <filters> element; paste the file `netsh wfp show filters` writes"
+        ));
+    };
+    let mut filters = Vec::new();
+    for item in list.children().filter(|n| n.has_tag_name("item")) {
+        let conditions = child(item, "filterCondition")
+            .map(|list| {
+                list.children()
+                    .filter(|n| n.has_tag_name("item"))
+                    .map(|c| netsh_condition(c, &key))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let flags: Vec<&str> = child(item, "flags")
+            .map(|list| {
+                list.children()
+                    .filter_map(|n| n.text())
+                    .map(str::trim)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let weight = child(item, "weight");
+        let provider = child_value(item, "providerKey");
+        let provider_key = provider.map(key);
+        let layer = child_value(item, "layerKey").unwrap_or("");
+        let sublayer = child_value(item, "subLayerKey").unwrap_or("");
+        filters.push(FilterSummary {
+            id: child_value(item, "filterId")
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(0),
+            key: child_value(item, "filterKey")
+                .map(key)
+                .ok_or_else(|| anyhow!("A filter in the netsh output has no filterKey"))?,
+            rule_key: None,
+            name: child(item, "displayData")
+                .and_then(|d| child_value(d, "name"))
+                .unwrap_or("<no name>")
+                .to_string(),
+            layer: layer.to_string(),
+            layer_key: key(layer),
+            sublayer: sublayer.to_string(),
+            sublayer_key: key(sublayer),
+            provider: match provider_key {
+                Some(k) => known_provider(k)
+                    .unwrap_or(provider.unwrap_or(""))
+                    .to_string(),
+                None => "<unknown provider>".into(),
+            },
+            provider_key,
+            action: match child(item, "action").and_then(|a| child_value(a, "type")) {
+                Some("FWP_ACTION_PERMIT") => WfpAction::Permit,
+                Some("FWP_ACTION_BLOCK") => WfpAction::Block,
+                _ => WfpAction::Callout,
+            },
+            remote_port: remote_port(&conditions),
+            conditions,
+            weight: weight.and_then(|w| netsh_integer(w)),
+            weight_kind: match weight.and_then(|w| child_value(w, "type")) {
+                Some("FWP_UINT8") => WeightKind::Range(
+                    weight
+                        .and_then(|w| netsh_integer(w))
+                        .and_then(|w| u8::try_from(w).ok())
+                        .unwrap_or(0),
+                ),
+                Some("FWP_UINT64") => WeightKind::Exact,
+                _ => WeightKind::Auto,
+            },
+            effective_weight: child(item, "effectiveWeight").and_then(|w| netsh_integer(w)),
+            boot_time: flags.contains(&"FWPM_FILTER_FLAG_BOOTTIME"),
+            persistent: flags.contains(&"FWPM_FILTER_FLAG_PERSISTENT"),
+            clear_action_right: flags.contains(&"FWPM_FILTER_FLAG_CLEAR_ACTION_RIGHT"),
+            indexed: flags.contains(&"FWPM_FILTER_FLAG_INDEXED"),
+            owned_by_app: provider_key == Some(PROVIDER_KEY),
+            callout: child(item, "action").and_then(|action| {
+                let kind = match child_value(action, "type")? {
+                    "FWP_ACTION_CALLOUT_TERMINATING" => CalloutKind::Terminating,
+                    "FWP_ACTION_CALLOUT_INSPECTION" => CalloutKind::Inspection,
+                    "FWP_ACTION_CALLOUT_UNKNOWN" => CalloutKind::Unknown,
+                    _ => return None,
+                };
+                Some(CalloutAction {
+                    key: key(child_value(action, "calloutKey")?),
+                    kind,
+                })
+            }),
+            provider_context_key: flags
+                .contains(&"FWPM_FILTER_FLAG_HAS_PROVIDER_CONTEXT")
+                .then(|| child_value(item, "providerContextKey").map(key))
+                .flatten(),
+        });
+    }
+    Ok(filters)
+}
+
+fn netsh_condition(item: Node, key: &impl Fn(&str) -> GUID) -> Result<Condition> {
+    let field = child_value(item, "fieldKey")
+        .ok_or_else(|| anyhow!("A condition in the netsh output has no fieldKey"))?;
+    let match_type = match child_value(item, "matchType").unwrap_or("") {
+        "FWP_MATCH_EQUAL" => MatchType::Equal,
+        "FWP_MATCH_NOT_EQUAL" => MatchType::NotEqual,
+        "FWP_MATCH_GREATER" => MatchType::Greater,
+        "FWP_MATCH_LESS" => MatchType::Less,
+        "FWP_MATCH_GREATER_OR_EQUAL" => MatchType::GreaterOrEqual,
+        "FWP_MATCH_LESS_OR_EQUAL" => MatchType::LessOrEqual,
+        "FWP_MATCH_RANGE" => MatchType::Range,
+        "FWP_MATCH_FLAGS_ALL_SET" => MatchType::FlagsAllSet,
+        "FWP_MATCH_FLAGS_ANY_SET" => MatchType::FlagsAnySet,
+        "FWP_MATCH_FLAGS_NONE_SET" => MatchType::FlagsNoneSet,
+        "FWP_MATCH_EQUAL_CASE_INSENSITIVE" => MatchType::EqualCaseInsensitive,
+        "FWP_MATCH_PREFIX" => MatchType::Prefix,
+        "FWP_MATCH_NOT_PREFIX" => MatchType::NotPrefix,
+        other => return Err(anyhow!("Unknown match type '{other}' in the netsh output")),
+    };
+    let value = child(item, "conditionValue")
+        .map(netsh_value)
+        .unwrap_or(ConditionValue::Unsupported { data_type: 0 });
+    Ok(Condition::new(
+        ConditionField::from_guid(key(field)),
+        match_type,
+        value,
+    ))
+}
+
+/// A `conditionValue`, `valueLow` or `valueHigh`: a `<type>` and an element
+/// holding the value. Values that do not parse are kept as unsupported.
+fn netsh_value(node: Node) -> ConditionValue {
+    let data_type = child_value(node, "type").unwrap_or("");
+    let unsupported = |data_type: i32| ConditionValue::Unsupported { data_type };
+    let integer = netsh_integer(node);
+    let parsed = match data_type {
+        "FWP_UINT8" => integer
+            .and_then(|v| u8::try_from(v).ok())
+            .map(ConditionValue::Uint8),
+        "FWP_UINT16" => integer
+            .and_then(|v| u16::try_from(v).ok())
+            .map(ConditionValue::Uint16),
+        "FWP_UINT32" => integer
+            .and_then(|v| u32::try_from(v).ok())
+            .map(ConditionValue::Uint32),
+        "FWP_UINT64" => integer.map(ConditionValue::Uint64),
+        "FWP_BYTE_ARRAY16_TYPE" => child_value(node, "byteArray16").and_then(|text| {
+            text.parse::<Ipv6Addr>()
+                .map(|addr| addr.octets())
+                .ok()
+                .or_else(|| hex(text)?.try_into().ok())
+                .map(ConditionValue::ByteArray16)
+        }),
+        "FWP_BYTE_ARRAY6_TYPE" => child_value(node, "byteArray6")
+            .and_then(|text| hex(&text.replace(['-', ':'], ""))?.try_into().ok())
+            .map(ConditionValue::ByteArray6),
+        "FWP_BYTE_BLOB_TYPE" => child(node, "byteBlob")
+            .and_then(|blob| child_value(blob, "data"))
+            .and_then(hex)
+            .map(ConditionValue::ByteBlob),
+        "FWP_UNICODE_STRING_TYPE" => {
+            child_value(node, "unicodeString").map(|text| ConditionValue::Unicode(text.to_string()))
+        }
+        "FWP_SECURITY_DESCRIPTOR_TYPE" => {
+            child_value(node, "sd").map(|sddl| ConditionValue::SecurityDescriptor(sddl.into()))
+        }
+        "FWP_V4_ADDR_MASK" => child(node, "v4AddrMask").and_then(|mask| {
+            Some(ConditionValue::V4AddrMask {
+                addr: child_value(mask, "addr")?.parse().ok()?,
+                mask: child_value(mask, "mask")?.parse().ok()?,
+            })
+        }),
+        "FWP_V6_ADDR_MASK" => child(node, "v6AddrMask").and_then(|mask| {
+            Some(ConditionValue::V6AddrMask {
+                addr: child_value(mask, "addr")?.parse().ok()?,
+                prefix_length: child_value(mask, "prefixLength")?.parse().ok()?,
+            })
+        }),
+        "FWP_RANGE_TYPE" => child(node, "rangeValue").and_then(|range| {
+            Some(ConditionValue::Range {
+                low: Box::new(netsh_value(child(range, "valueLow")?)),
+                high: Box::new(netsh_value(child(range, "valueHigh")?)),
+            })
+        }),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| unsupported(netsh_data_type(data_type)))
+}
+
+/// The integer under a value node, written in decimal, hex or, for IPv4
+/// addresses, dotted form.
+fn netsh_integer(node: Node) -> Option<u64> {
+    let text = ["uint8", "uint16", "uint32", "uint64"]
+        .iter()
+        .find_map(|name| child_value(node, name))?;
+    if let Some(hex) = text.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+    text.parse().ok().or_else(|| {
+        text.parse::<Ipv4Addr>()
+            .ok()
+            .map(|a| u64::from(u32::from(a)))
+    })
+}
+
+/// `FWP_DATA_TYPE` numbers of the types kept as unsupported.
+fn netsh_data_type(name: &str) -> i32 {
+    match name {
+        "FWP_EMPTY" => 0,
+        "FWP_INT8" => 5,
+        "FWP_INT16" => 6,
+        "FWP_INT32" => 7,
+        "FWP_INT64" => 8,
+        "FWP_FLOAT" => 9,
+        "FWP_DOUBLE" => 10,
+        "FWP_SID" => 13,
+        "FWP_TOKEN_INFORMATION_TYPE" => 15,
+        "FWP_TOKEN_ACCESS_INFORMATION_TYPE" => 16,
+        _ => -1,
+    }
+}
+
+fn hex(text: &str) -> Option<Vec<u8>> {
+    // An odd length leaves a last slice past the end, so it fails.
+    let text = text.trim();
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// How a filter from another machine compares with this one's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterPresence {
+    /// A filter with the same key, layer, action and conditions is here.
+    Same,
+    /// A filter with the same key is here, but differs.
+    Differs,
+    /// No filter with the key is here.
+    Missing,
+}
+
+impl FilterPresence {
+    pub fn of(filter: &FilterSummary, here: &[FilterSummary]) -> Self {
+        match here.iter().find(|f| f.key == filter.key) {
+            Some(f)
+                if f.layer_key == filter.layer_key
+                    && f.action == filter.action
+                    && f.conditions == filter.conditions =>
+            {
+                FilterPresence::Same
+            }
+            Some(_) => FilterPresence::Differs,
+            None => FilterPresence::Missing,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FilterPresence::Same => "same here",
+            FilterPresence::Differs => "differs here",
+            FilterPresence::Missing => "missing here",
+        }
+    }
+}
+
+/// A filter like `filter`, to be added here as one of ours under a new
+/// key, its name conformed to the naming policy. Refused for callouts, which need their driver, and for layers or
+/// values netsh named in a way we cannot rebuild.
+pub fn adopt_filter(filter: &FilterSummary) -> Result<FilterBuilder> {
+    if filter.layer_key == guid_from_uuid(Uuid::nil()) {
+        return Err(anyhow!(
+            "Filter '{}' is at layer {}, which is not known here",
+            filter.name,
+            filter.layer
+        ));
+    }
+    if filter.action == WfpAction::Callout {
+        return Err(anyhow!(
+            "Filter '{}' hands traffic to a callout, which cannot be adopted",
+            filter.name
+        ));
+    }
+    let unsupported = |value: &ConditionValue| match value {
+        ConditionValue::Unsupported { .. } => true,
+        ConditionValue::Range { low, high } => {
+            matches!(**low, ConditionValue::Unsupported { .. })
+                || matches!(**high, ConditionValue::Unsupported { .. })
+        }
+        _ => false,
+    };
+    if let Some(condition) = filter
+        .conditions
+        .iter()
+        .find(|c| unsupported(&c.value) || c.field == ConditionField::Other(Uuid::nil()))
+    {
+        return Err(anyhow!(
+            "Filter '{}' has a condition that cannot be rebuilt: {condition}",
+            filter.name
+        ));
+    }
+    let builder = filter.conditions.iter().cloned().fold(
+        FilterBuilder::new(&crate::naming::preset_name(&filter.name), filter.layer_key)
+            .key(guid_from_uuid(Uuid::new_v4()))
+            .action(filter.action)
+            .clear_action_right(filter.clear_action_right),
+        FilterBuilder::condition,
+    );
+    builder.validate()?;
+    Ok(builder)
+}
+
+fn finish(what: &str, configs: Vec<FilterConfig>) -> Result<Vec<FilterConfig>> {
+    if configs.is_empty() {
+        return Err(anyhow!("The {what} has no rules that can be imported"));
+    }
+    Ok(configs)
+}
+
+/// An app rule keyed by product, rule and path, so importing the same
+/// file again updates the rules instead of adding copies.
+fn app_config(product: &str, path: &str, action: WfpAction, rule: Option<&str>) -> FilterConfig {
+    let app = expand_env(path.trim());
+    let file = app.rsplit(['\\', '/']).next().unwrap_or(&app).to_string();
+    let name = match rule {
+        Some(rule) => format!("{rule} ({file})"),
+        None => format!("{product}: {file}"),
+    };
+    let mut config = FilterConfig::tcp_ports(&name, RemotePorts::default(), action);
+    config.key = Some(derived_key(product, rule.unwrap_or(""), &app));
+    config.app = Some(app);
+    config
+}
+
+fn derived_key(product: &str, rule: &str, app: &str) -> Uuid {
+    Uuid::new_v5(
+        &Uuid::NAMESPACE_OID,
+        format!("{product}/{rule}/{}", app.to_lowercase()).as_bytes(),
+    )
+}
+
+/// Remote ports separated by `sep`; `None` when any entry is not a single
+/// port, such as an address or a range.
+fn port_list(text: &str, sep: char) -> Option<RemotePorts> {
+    let ports = text
+        .split(sep)
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<u16>().ok().filter(|port| *port != 0))
+        .collect::<Option<Vec<u16>>>()?;
+    Some(RemotePorts::from(ports))
+}
+
+/// The first child element of `node` named `name`.
+fn child<'a, 'i>(node: Node<'a, 'i>, name: &str) -> Option<Node<'a, 'i>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+/// The trimmed text of the child element `name`, when not empty.
+fn child_value<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.descendants()
+        .find(|n| n.tag_name().name() == name)
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+/// Expands `%NAME%` variables, which both products store in paths. Unknown
+/// variables are left as they are.
+fn expand_env(path: &str) -> String {
+    let mut out = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else {
+            break;
+        };
+        let name = &rest[start + 1..start + 1 + len];
+        out.push_str(&rest[..start]);
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => out.push_str(&value),
+            _ => out.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
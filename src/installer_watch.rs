@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    diff::SnapshotDiff,
+    importers,
+    wfp::{Engine, FilterRecord, FilterSummary, SavedSnapshot},
+};
+
+/// A baseline of the engine's state, taken before installing software so
+/// what the installer did can be seen afterwards.
+pub struct InstallerWatch {
+    pub baseline: SavedSnapshot,
+    server: Option<String>,
+}
+
+impl InstallerWatch {
+    /// Takes the baseline on `engine`, open on `server` or this machine.
+    pub fn start(engine: &Engine, server: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            baseline: SavedSnapshot::take(engine, server)?,
+            server: server.map(String::from),
+        })
+    }
+
+    /// Compares the engine's state now with the baseline.
+    pub fn finish(&self, engine: &Engine) -> Result<InstallerChanges> {
+        let now = SavedSnapshot::take(engine, self.server.as_deref())?;
+        let diff = SnapshotDiff::between(&self.baseline.snapshot, &now.snapshot);
+        let added = diff
+            .added
+            .iter()
+            .filter(|f| !f.owned_by_app)
+            .cloned()
+            .collect();
+        Ok(InstallerChanges { diff, added })
+    }
+}
+
+/// Everything that changed since the baseline, and the filters other
+/// software added in that time.
+pub struct InstallerChanges {
+    pub diff: SnapshotDiff,
+    pub added: Vec<FilterSummary>,
+}
+
+impl InstallerChanges {
+    /// `3 filter(s) added by Contoso VPN, Windows Defender Firewall`.
+    pub fn summary(&self) -> String {
+        if self.added.is_empty() {
+            return "No filters added by other software".into();
+        }
+        let mut providers: Vec<&str> = self.added.iter().map(|f| f.provider.as_str()).collect();
+        providers.sort_unstable();
+        providers.dedup();
+        format!(
+            "{} filter(s) added by {}",
+            self.added.len(),
+            providers.join(", ")
+        )
+    }
+
+    /// Writes the added filters to `path` as JSON.
+    pub fn export(&self, path: &Path) -> Result<()> {
+        let records: Vec<FilterRecord> = self.added.iter().map(FilterRecord::from).collect();
+        std::fs::write(path, serde_json::to_string_pretty(&records)?)
+            .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))
+    }
+}
+
+/// Adds a copy of each of `filters` as one of our rules, so they stay when
+/// the software that added them is removed. Nothing is added when any of
+/// them cannot be adopted.
+pub fn adopt(engine: &Engine, filters: &[FilterSummary]) -> Result<usize> {
+    let builders = filters
+        .iter()
+        .map(importers::adopt_filter)
+        .collect::<Result<Vec<_>>>()?;
+    for builder in &builders {
+        engine.add_filter(builder)?;
+    }
+    Ok(builders.len())
+}
+
+/// Deletes `filters`, whichever software added them.
+pub fn delete(engine: &Engine, filters: &[FilterSummary]) -> Result<usize> {
+    let keys: Vec<_> = filters.iter().map(|f| f.key).collect();
+    engine.delete_foreign_filters(&keys)?;
+    Ok(keys.len())
+}
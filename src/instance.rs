@@ -0,0 +1,63 @@
+use anyhow::Result;
+#[cfg(windows)]
+use widestring::U16CString;
+#[cfg(windows)]
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, HANDLE},
+        System::Threading::CreateMutexW,
+        UI::WindowsAndMessaging::{
+            FindWindowW, IsIconic, SetForegroundWindow, ShowWindow, SW_RESTORE,
+        },
+    },
+};
+
+/// Title of the main window, also used to find it from a second instance.
+pub const WINDOW_TITLE: &str = "SLS WFP Manager";
+
+/// Held for as long as this process is the one running the GUI.
+pub struct InstanceLock {
+    #[cfg(windows)]
+    mutex: HANDLE,
+}
+
+/// Claims the single GUI slot for this user session. When another instance
+/// already holds it, that instance's window is brought to the front and
+/// `None` is returned so the caller can exit. Two GUIs would otherwise race
+/// each other over the owned rules.
+#[cfg(windows)]
+pub fn acquire() -> Result<Option<InstanceLock>> {
+    let name = U16CString::from_str(r"Local\SLS WFP Manager")?;
+    let mutex = unsafe { CreateMutexW(None, false, PCWSTR(name.as_ptr()))? };
+    if unsafe { GetLastError() } != ERROR_ALREADY_EXISTS {
+        return Ok(Some(InstanceLock { mutex }));
+    }
+    unsafe {
+        let _ = CloseHandle(mutex);
+        let title = U16CString::from_str(WINDOW_TITLE)?;
+        if let Ok(window) = FindWindowW(PCWSTR::null(), PCWSTR(title.as_ptr())) {
+            if IsIconic(window).as_bool() {
+                let _ = ShowWindow(window, SW_RESTORE);
+            }
+            let _ = SetForegroundWindow(window);
+        }
+    }
+    Ok(None)
+}
+
+/// Only the simulated engine runs off Windows, and its state is private to
+/// each process, so a second instance cannot interfere.
+#[cfg(not(windows))]
+pub fn acquire() -> Result<Option<InstanceLock>> {
+    Ok(Some(InstanceLock {}))
+}
+
+#[cfg(windows)]
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.mutex);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    preset::Preset,
+    wfp::{interface_deny_rule, FilterBuilder, InterfaceMedia, GUID},
+};
+#[cfg(windows)]
+use windows::Win32::{
+    Foundation::{ERROR_BUFFER_OVERFLOW, NO_ERROR},
+    NetworkManagement::{
+        IpHelper::{
+            GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_DNS_SERVER,
+            GAA_FLAG_SKIP_MULTICAST, GAA_FLAG_SKIP_UNICAST, IP_ADAPTER_ADDRESSES_LH,
+        },
+        Ndis::IfOperStatusUp,
+    },
+    Networking::WinSock::AF_UNSPEC,
+};
+
+/// An interface whose traffic is blocked unless a rule allows it. The
+/// name is kept for display while the adapter is absent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeniedInterface {
+    pub luid: u64,
+    pub name: String,
+}
+
+/// Default-deny on some interfaces, e.g. the cellular adapter, leaving the
+/// others open.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterfaceDeny {
+    pub interfaces: Vec<DeniedInterface>,
+}
+
+impl InterfaceDeny {
+    pub fn is_denied(&self, luid: u64) -> bool {
+        self.interfaces.iter().any(|i| i.luid == luid)
+    }
+}
+
+impl Preset for InterfaceDeny {
+    const SETTINGS_FILE: &'static str = "interface_deny.json";
+    const RULE_NAME: &'static str = "Default-deny by interface";
+    const RULE_KEY: GUID = GUID::from_values(
+        0x3c8e5b17,
+        0x92d4,
+        0x4a6f,
+        [0xb0, 0x2e, 0x6f, 0x91, 0xc4, 0x58, 0x0d, 0x73],
+    );
+
+    /// Blocks on the selected interfaces; refused while none is selected.
+    fn rule(&self, name: &str) -> Result<Vec<FilterBuilder>> {
+        if self.interfaces.is_empty() {
+            return Err(anyhow!("No interface is selected for default-deny"));
+        }
+        let luids: Vec<u64> = self.interfaces.iter().map(|i| i.luid).collect();
+        Ok(interface_deny_rule(Self::RULE_KEY, name, &luids))
+    }
+}
+
+/// A network adapter of this machine.
+#[derive(Clone, Debug)]
+pub struct NetworkInterface {
+    pub luid: u64,
+    /// The name in Network Connections, e.g. `Cellular`.
+    pub name: String,
+    pub description: String,
+    pub media: Option<InterfaceMedia>,
+    pub up: bool,
+}
+
+/// The adapters of this machine, without the loopback one.
+#[cfg(windows)]
+pub fn interfaces() -> Result<Vec<NetworkInterface>> {
+    const IF_TYPE_SOFTWARE_LOOPBACK: u32 = 24;
+    let flags = GAA_FLAG_SKIP_UNICAST
+        | GAA_FLAG_SKIP_ANYCAST
+        | GAA_FLAG_SKIP_MULTICAST
+        | GAA_FLAG_SKIP_DNS_SERVER;
+    // `u64`s keep the adapter structures in the buffer aligned.
+    let mut size = 16 * 1024;
+    let mut buf: Vec<u64>;
+    loop {
+        buf = vec![0; (size as usize).div_ceil(8)];
+        let status = unsafe {
+            GetAdaptersAddresses(
+                AF_UNSPEC.0.into(),
+                flags,
+                None,
+                Some(buf.as_mut_ptr().cast()),
+                &mut size,
+            )
+        };
+        match status {
+            status if status == NO_ERROR.0 => break,
+            status if status == ERROR_BUFFER_OVERFLOW.0 => continue,
+            status => {
+                return Err(anyhow!(
+                    "Listing the network adapters failed: error {status}"
+                ))
+            }
+        }
+    }
+    let mut out = Vec::new();
+    let mut next = buf.as_ptr().cast::<IP_ADAPTER_ADDRESSES_LH>();
+    while let Some(adapter) = unsafe { next.as_ref() } {
+        next = adapter.Next;
+        if adapter.IfType == IF_TYPE_SOFTWARE_LOOPBACK {
+            continue;
+        }
+        out.push(NetworkInterface {
+            luid: unsafe { adapter.Luid.Value },
+            name: unsafe { adapter.FriendlyName.to_string() }.unwrap_or_default(),
+            description: unsafe { adapter.Description.to_string() }.unwrap_or_default(),
+            media: InterfaceMedia::ALL
+                .into_iter()
+                .find(|m| m.if_types().contains(&adapter.IfType)),
+            up: adapter.OperStatus == IfOperStatusUp,
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(not(windows))]
+pub fn interfaces() -> Result<Vec<NetworkInterface>> {
+    Ok(Vec::new())
+}
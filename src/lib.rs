@@ -0,0 +1,15 @@
+#[cfg(not(any(windows, feature = "simulation")))]
+compile_error!("WFP needs Windows; build with `--features simulation` elsewhere.");
+
+pub mod async_engine;
+pub mod config;
+pub mod dpapi;
+pub mod ffi;
+pub mod importers;
+pub mod lockdown;
+pub mod naming;
+pub mod plugins;
+pub mod rule_expr;
+pub mod schema;
+pub mod syslog;
+pub mod wfp;
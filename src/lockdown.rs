@@ -0,0 +1,158 @@
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use uuid::Uuid;
+
+use crate::wfp::{
+    guid_from_uuid, Condition, ConditionField, ConditionValue, Engine, FilterBuilder, WeightTier,
+    WfpAction, FWPM_LAYER_ALE_AUTH_CONNECT_V4, FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+    FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4, FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6, GUID,
+};
+
+const RULE_NAME: &str = "Panic button: block all traffic";
+
+/// How long a lockdown lasts before it lifts on its own.
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(15 * 60);
+
+/// A remote administration session connected to this machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteSession {
+    /// `RDP` or `SSH`.
+    pub protocol: &'static str,
+    pub remote: IpAddr,
+}
+
+/// Fails when `builders` include a block-all filter (a default-deny one,
+/// with no conditions or only an interface) while this machine has a
+/// remote session from an address none of our allow rules, installed or
+/// among `builders`, lets through. Sessions on other machines cannot be
+/// seen, so only `engine`s on this one are checked, and `sessions` is only
+/// asked for when there is something to check.
+pub fn check_lockout(
+    engine: &Engine,
+    builders: &[FilterBuilder],
+    sessions: impl FnOnce() -> Result<Vec<RemoteSession>>,
+) -> Result<()> {
+    if !engine.is_local() || !builders.iter().any(|b| b.tier() == WeightTier::DefaultDeny) {
+        return Ok(());
+    }
+    let sessions = sessions()?;
+    if sessions.is_empty() {
+        return Ok(());
+    }
+    let mut filters = engine.snapshot()?.filters;
+    filters.extend(builders.iter().map(|b| b.to_summary(0)));
+    match sessions
+        .iter()
+        .find(|s| !filters.iter().any(|f| f.permits_remote(s.remote)))
+    {
+        Some(session) => Err(anyhow!(
+            "Refusing to block all traffic: this {} session from {} would be cut off. \
+             Add an allow rule for {} first, or turn off the lockout guard.",
+            session.protocol,
+            session.remote,
+            session.remote
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Hard permits for everything to and from `peer`, members of the rule
+/// `key` at `weight`. Blocks in lower-weight sublayers cannot override them.
+pub fn peer_permits(name: &str, key: GUID, peer: IpAddr, weight: u64) -> Vec<FilterBuilder> {
+    let (address, layers) = match peer {
+        IpAddr::V4(addr) => (
+            ConditionValue::Uint32(u32::from(addr)),
+            [
+                FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+                FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+            ],
+        ),
+        IpAddr::V6(addr) => (
+            ConditionValue::ByteArray16(addr.octets()),
+            [
+                FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+                FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+            ],
+        ),
+    };
+    layers
+        .into_iter()
+        .map(|layer| {
+            FilterBuilder::new(name, layer)
+                .rule(key)
+                .action(WfpAction::Permit)
+                .condition(Condition::equal(
+                    ConditionField::RemoteAddress,
+                    address.clone(),
+                ))
+                .weight(weight)
+                .clear_action_right(true)
+        })
+        .collect()
+}
+
+/// An emergency block of all new connections on this machine. The filters
+/// live in a dynamic session, so BFE removes them as soon as the lockdown
+/// is dropped or the process exits, crash included.
+pub struct Lockdown {
+    _engine: Engine,
+    until: Instant,
+}
+
+impl Lockdown {
+    /// Blocks everything except `sessions`, the remote sessions to keep.
+    /// Each must already be let through by one of our allow rules, as
+    /// [`check_lockout`] requires, or the lockdown is refused.
+    pub fn start(duration: Duration, sessions: &[RemoteSession]) -> Result<Self> {
+        let engine = Engine::open_dynamic()?;
+        let key = guid_from_uuid(Uuid::new_v4());
+        let name = crate::naming::preset_name(RULE_NAME);
+        let blocks: Vec<FilterBuilder> = [
+            FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+            FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+            FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+            FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+        ]
+        .into_iter()
+        .map(|layer| {
+            FilterBuilder::new(&name, layer)
+                .rule(key)
+                .action(WfpAction::Block)
+                // Above every weight tier, so none of our allow rules
+                // punches through; only the permits for `sessions` sit
+                // higher.
+                .weight(u64::MAX - 1)
+        })
+        .collect();
+        check_lockout(&engine, &blocks, || Ok(sessions.to_vec()))?;
+        let mut peers: Vec<IpAddr> = sessions.iter().map(|s| s.remote).collect();
+        peers.sort();
+        peers.dedup();
+        let mut builders: Vec<FilterBuilder> = peers
+            .into_iter()
+            .flat_map(|peer| peer_permits(&name, key, peer, u64::MAX))
+            .collect();
+        builders.extend(blocks);
+        engine.replace_rule(key, &name, &builders)?;
+        Ok(Self {
+            _engine: engine,
+            until: Instant::now() + duration,
+        })
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.until.saturating_duration_since(Instant::now())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    pub fn extend(&mut self, by: Duration) {
+        self.until += by;
+    }
+}
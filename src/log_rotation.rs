@@ -0,0 +1,133 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+const SETTINGS_FILE: &str = "log_retention.json";
+
+/// Limits for the application's text logs, checked before every write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRetention {
+    /// Size at which the log is moved aside and a new one started.
+    pub max_bytes: u64,
+    /// Rotated files kept next to the live log.
+    pub keep: u32,
+    /// Rotated files last written longer ago than this are deleted.
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self {
+            max_bytes: 4 * 1024 * 1024,
+            keep: 5,
+            max_age_days: Some(30),
+        }
+    }
+}
+
+impl LogRetention {
+    pub fn load() -> Result<Self> {
+        config::load_settings(SETTINGS_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.max_bytes == 0 {
+            return Err(anyhow!("Log size limit must be above zero"));
+        }
+        config::save_settings(SETTINGS_FILE, self)
+    }
+}
+
+/// Called before appending to `path`: once the log has reached
+/// `max_bytes` it becomes `name.1.ext`, older files move up one number,
+/// and rotated files past `keep` or `max_age_days` are deleted.
+pub fn rotate(path: &Path, retention: &LogRetention) -> Result<()> {
+    let full = fs::metadata(path).is_ok_and(|m| m.len() >= retention.max_bytes);
+    if full {
+        for (number, old) in rotated_files(path)?.into_iter().rev() {
+            rename(&old, &rotated_path(path, number + 1))?;
+        }
+        rename(path, &rotated_path(path, 1))?;
+    }
+    let cutoff = retention.max_age_days.and_then(|days| {
+        SystemTime::now().checked_sub(Duration::from_secs(u64::from(days) * 86_400))
+    });
+    for (number, old) in rotated_files(path)? {
+        let expired = cutoff.is_some_and(|cutoff| {
+            fs::metadata(&old)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified < cutoff)
+        });
+        if number > u64::from(retention.keep) || expired {
+            remove(&old)?;
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the log and all of its rotated files, returning the bytes freed.
+pub fn purge(path: &Path) -> Result<u64> {
+    let mut freed = 0;
+    let files = rotated_files(path)?.into_iter().map(|(_, p)| p);
+    for file in files.chain(path.exists().then(|| path.to_path_buf())) {
+        freed += fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+        remove(&file)?;
+    }
+    Ok(freed)
+}
+
+/// `alerts.log` → `alerts.3.log`.
+fn rotated_path(path: &Path, number: u64) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{stem}.{number}.{}", ext.to_string_lossy())),
+        None => path.with_file_name(format!("{stem}.{number}")),
+    }
+}
+
+/// Rotated files of `path` sorted by number, newest first.
+fn rotated_files(path: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let Some(dir) = path.parent() else {
+        return Ok(Vec::new());
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let suffix = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let mut files = Vec::new();
+    for entry in entries {
+        let file = entry?.path();
+        let number = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(stem.as_ref()))
+            .and_then(|rest| rest.strip_prefix('.'))
+            .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+            .and_then(|number| number.parse().ok());
+        if let Some(number) = number {
+            files.push((number, file));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn rename(from: &Path, to: &Path) -> Result<()> {
+    fs::rename(from, to).map_err(|e| anyhow!("Failed to rotate {}: {e}", from.display()))
+}
+
+fn remove(path: &Path) -> Result<()> {
+    fs::remove_file(path).map_err(|e| anyhow!("Failed to remove {}: {e}", path.display()))
+}
@@ -1,52 +1,887 @@
-use anyhow::Result;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
 use eframe::egui;
-use windows::core::GUID;
+use sls_wfp_gui::{
+    config,
+    importers::{self, FilterPresence},
+    lockdown::{self, Lockdown},
+    naming::{self, NamingPolicy},
+    plugins, rule_expr, schema, syslog, wfp,
+};
+use uuid::Uuid;
 
-mod wfp;
-use wfp::{Engine, FilterConfig, FilterSummary, NamedGuid, Snapshot, WfpAction};
+mod alerts;
+mod allow_once;
+mod app_policy;
+mod app_schedule;
+mod bench;
+mod bfe;
+mod capture;
+mod chart;
+mod cli;
+mod coexistence;
+mod connections;
+mod consistency;
+mod diff;
+mod dns_lockdown;
+mod etw;
+mod event_export;
+mod event_store;
+mod favorites;
+mod flow_monitor;
+mod hit_counters;
+mod hit_test;
+mod host_policy;
+mod hosts;
+mod installer_watch;
+mod instance;
+mod interface_deny;
+mod log_rotation;
+mod notifications;
+mod preset;
+mod profiles;
+mod quic_block;
+mod refresh;
+mod safety;
+mod scripting;
+mod troubleshoot;
+mod tui;
+mod unused;
+mod watch;
+use alerts::{Alert, AlertRule, Alerts, EventFeed};
+use allow_once::TemporaryAllow;
+use app_policy::AppPolicy;
+use app_schedule::{AccessWindow, AppSchedule};
+use bench::BenchReport;
+use bfe::BfeState;
+use capture::{Capture, CaptureScope};
+use chart::{Series, SeriesKind};
+use coexistence::CoexistenceReport;
+use config::RuleFormat;
+use connections::Connection;
+use consistency::ConsistencyReport;
+use diff::{describe_filter, SnapshotDiff};
+use dns_lockdown::DnsLockdown;
+use etw::{EtwCategory, EtwEvent, EtwSession};
+use event_export::EventExportFormat;
+use event_store::{EventStore, Retention};
+use favorites::Favorite;
+use flow_monitor::FlowMonitor;
+use hit_counters::HitCounters;
+use hit_test::HitTest;
+use host_policy::HostPolicy;
+use hosts::{HostStatus, Hosts};
+use installer_watch::{InstallerChanges, InstallerWatch};
+use interface_deny::{DeniedInterface, InterfaceDeny, NetworkInterface};
+use log_rotation::LogRetention;
+use notifications::{Notifications, Severity};
+use preset::Preset;
+use profiles::Profile;
+use quic_block::QuicBlock;
+use refresh::{AutoRefresh, RefreshScheduler};
+use safety::{ConfirmPolicy, PeerPermit, RemoteSession, Safety};
+use scripting::{ScheduledScript, ScriptOutput, ScriptScheduler};
+use syslog::{SyslogConfig, SyslogTransport};
+use troubleshoot::Diagnosis;
+use unused::UnusedReport;
+use wfp::{
+    protocol_name, BulkEdit, CalloutInfo, CancelToken, Condition, ConditionField, ConditionValue,
+    EngineState, FilterBuilder, FilterConfig, FilterSummary, ImportSummary, InterfaceMedia,
+    IpsecConnection, IpsecEvent, IpsecSubscription, LayerSchema, MatchType, NamedGuid, NetEvent,
+    NetEventKind, NetEventQuery, RemotePorts, RuleSet, SavedSnapshot, SessionInfo, Snapshot,
+    SublayerInfo, SystemPorts, TimeRange, WeightTier, WfpAction, GUID,
+};
 
 struct AppState {
-    status: String,
+    notices: Notifications,
+    tab: Tab,
     filters: Vec<FilterSummary>,
+    boot_time_filters: Vec<FilterSummary>,
     providers: Vec<NamedGuid>,
     sublayers: Vec<NamedGuid>,
+    sublayer_details: Vec<SublayerInfo>,
     layers: Vec<NamedGuid>,
-    refresh_pending: bool,
+    refresh: RefreshScheduler,
+    /// The snapshot being loaded in the background, until it arrives.
+    refresh_job: Option<RefreshJob>,
     add_name: String,
-    add_tcp_port: u16,
+    add_ports: String,
+    add_expression: String,
+    /// Sublayer the expression rule goes in; the default one when `None`.
+    add_sublayer: Option<GUID>,
+    new_sublayer_name: String,
+    new_sublayer_weight: String,
+    /// Sublayer whose delete is waiting for the user to confirm removing
+    /// the filters in it.
+    sublayer_delete: Option<GUID>,
+    /// Our callouts, for a companion driver to register against.
+    callout_details: Vec<CalloutInfo>,
+    new_callout_key: String,
+    new_callout_name: String,
+    new_callout_description: String,
+    new_callout_layer: Option<GUID>,
     add_block: bool,
+    /// Whether blocks are added with the rules closing their coverage gaps.
+    add_companions: bool,
     export_text: String,
+    import_path: String,
+    profiles: Vec<Profile>,
+    new_profile_name: String,
+    /// Profile being renamed and the name typed so far.
+    profile_rename: Option<(String, String)>,
+    /// The export text last previewed and what it would import.
+    import_preview: Option<(String, Result<String, String>)>,
+    /// An import running in the background, until it finishes.
+    import_job: Option<ImportJob>,
+    filter_view: FilterView,
+    /// Executable the "For an app" view is about.
+    app_policy_path: String,
+    app_policy: Option<Result<AppPolicy, String>>,
+    /// Address or hostname the "For a host" view is about.
+    host_policy_query: String,
+    host_policy: Option<Result<HostPolicy, String>>,
+    coexistence_report: String,
+    sessions: Vec<SessionInfo>,
+    system_ports: Vec<SystemPorts>,
+    /// Sockets for the Connections tab, or why they could not be read.
+    connections: Option<Result<Vec<Connection>, String>>,
+    connections_established_only: bool,
+    /// IPsec connections on the active host, kept current by `ipsec_feed`.
+    ipsec: Option<Result<Vec<IpsecConnection>, String>>,
+    ipsec_feed: Option<(IpsecSubscription, Receiver<IpsecEvent>)>,
+    /// Last overview for the State tab, or why it could not be read.
+    engine_state: Option<Result<EngineState, String>>,
+    bench: Option<Result<BenchReport, String>>,
+    net_events: Vec<NetEvent>,
+    diagnosis: Option<Diagnosis>,
+    event_filter: EventFilterForm,
+    record_history: bool,
+    history_max_mb: String,
+    history_max_days: String,
+    log_max_mb: String,
+    log_keep: String,
+    log_max_days: String,
+    chart: ChartState,
+    event_export_path: String,
+    event_export_format: EventExportFormat,
+    capture: Option<Capture>,
+    live_log: VecDeque<EtwEvent>,
+    etw: Option<(EtwSession, Receiver<EtwEvent>)>,
+    alerts: Alerts,
+    alert_form: AlertForm,
+    alert_feed: Option<(EventFeed, Receiver<Result<Vec<NetEvent>>>)>,
+    fired_alerts: VecDeque<Alert>,
+    syslog: SyslogForm,
+    scripts: Vec<ScheduledScript>,
+    script_path: String,
+    script_every: String,
+    app_schedules: Vec<AppSchedule>,
+    /// Rules pinned to the strip under the tabs.
+    favorites: Vec<Favorite>,
+    schedule_app: String,
+    schedule_window: String,
+    scheduler: Option<ScriptScheduler>,
+    script_output: (Sender<ScriptOutput>, Receiver<ScriptOutput>),
+    script_log: VecDeque<ScriptOutput>,
     edit_state: Option<EditState>,
     delete_state: Option<DeleteState>,
+    /// Ids of the owned filters ticked in the table.
+    selected_filters: Vec<u64>,
+    bulk_edit: Option<BulkEditState>,
+    hosts: Hosts,
+    new_host: String,
+    lockdown: Option<Lockdown>,
+    /// The panic button was pressed and waits for confirmation.
+    lockdown_confirm: bool,
+    /// Blocked flows allowed from the event log until they expire.
+    allowances: Vec<TemporaryAllow>,
+    /// The expression last tested against saved events, and the result.
+    hit_test: Option<(String, HitTest)>,
+    /// Hits per filter on this machine, from the net events seen.
+    hit_counters: HitCounters,
+    /// The last unused-rule report and the keys ticked for removal.
+    unused: Option<(UnusedReport, Vec<Uuid>)>,
+    unused_days: String,
+    /// Pasted `netsh wfp show filters` output and the filters read from it.
+    netsh_text: String,
+    netsh_filters: Option<Result<Vec<FilterSummary>, String>>,
+    snapshot_path: String,
+    /// A saved snapshot shown in place of the engine's state, read-only.
+    offline: Option<SavedSnapshot>,
+    /// A later snapshot to compare the one above with, or empty for the
+    /// active host now, and the last comparison with what it compared.
+    snapshot_compare_path: String,
+    snapshot_diff: Option<Result<(String, SnapshotDiff), String>>,
+    /// The baseline taken before installing software, what changed since,
+    /// and where the filters it added are exported to.
+    installer_watch: Option<InstallerWatch>,
+    installer_changes: Option<InstallerChanges>,
+    installer_export_path: String,
+    installer_confirm_delete: bool,
+    dns_lockdown: DnsLockdown,
+    /// The allowed resolvers as typed, applied with the lockdown.
+    dns_resolvers: String,
+    quic_block: QuicBlock,
+    flow_monitor: FlowMonitor,
+    interface_deny: InterfaceDeny,
+    /// This machine's adapters, as listed when the app started or the list
+    /// was refreshed.
+    network_interfaces: Vec<NetworkInterface>,
+    safety: Safety,
+    /// Remote sessions found when last checked from the Safety section.
+    remote_sessions: Option<Vec<RemoteSession>>,
+    /// Keeps the RDP client allowed until the user confirms the connection
+    /// survived an import.
+    peer_permit: Option<PeerPermit>,
+    naming_policy: NamingPolicy,
+    /// The policy's maximum length as typed.
+    naming_max_length: String,
+    /// Applications to block QUIC for as typed, one path per line.
+    quic_apps: String,
+    /// Applications to monitor as typed, one path per line.
+    flow_monitor_apps: String,
+    /// BFE on this machine when it was found not running, and when.
+    bfe: Option<(BfeState, Instant)>,
+    /// Owned rules missing or changed since the last run, until the user
+    /// repairs or accepts them.
+    consistency: Option<ConsistencyReport>,
+    /// Whether this launch has compared the rules with the record yet.
+    consistency_checked: bool,
+    /// Opens the net events section on the next frame.
+    reveal_events: bool,
+    detached: Detached,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Rules,
+    Alerts,
+    Scripts,
+    Connections,
+    Ipsec,
+    State,
+}
+
+impl Tab {
+    fn label(self) -> &'static str {
+        match self {
+            Tab::Rules => "Rules & events",
+            Tab::Alerts => "Alerts",
+            Tab::Scripts => "Scripts",
+            Tab::Connections => "Connections",
+            Tab::Ipsec => "IPsec",
+            Tab::State => "State",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FilterView {
+    Table,
+    LayerTree,
+    ByProvider,
+    ForApp,
+    ForHost,
+    BootTime,
+}
+
+impl FilterView {
+    fn label(self) -> &'static str {
+        match self {
+            FilterView::Table => "Table",
+            FilterView::LayerTree => "By layer",
+            FilterView::ByProvider => "By provider",
+            FilterView::ForApp => "For an app",
+            FilterView::ForHost => "For a host",
+            FilterView::BootTime => "Boot-time",
+        }
+    }
+}
+
+/// One condition in the editor, with its value as typed.
+struct ConditionRow {
+    field: ConditionField,
+    match_type: MatchType,
+    value: String,
+    /// The value the row opened with and its text. It is kept while the
+    /// text is unchanged, so values that cannot be typed survive a save.
+    original: Option<(String, ConditionValue)>,
+}
+
+impl ConditionRow {
+    fn of(condition: &Condition) -> Self {
+        let text = condition.value.edit_text(condition.field);
+        Self {
+            field: condition.field,
+            match_type: condition.match_type,
+            value: text.clone(),
+            original: Some((text, condition.value.clone())),
+        }
+    }
+
+    fn new(field: ConditionField) -> Self {
+        Self {
+            field,
+            match_type: MatchType::Equal,
+            value: String::new(),
+            original: None,
+        }
+    }
+
+    /// The condition as edited, checked against the layer when its fields
+    /// are known.
+    fn condition(&self, v6: bool, schema: Option<LayerSchema>) -> Result<Condition> {
+        let value = match &self.original {
+            Some((text, value)) if *text == self.value => value.clone(),
+            _ => ConditionValue::parse(self.field, v6, &self.value)?,
+        };
+        let condition = Condition::new(self.field, self.match_type, value);
+        condition.validate()?;
+        if let Some(schema) = schema {
+            schema.check(&condition)?;
+        }
+        Ok(condition)
+    }
 }
 
+/// The name, action and conditions of one of our filters, on its layer.
 struct EditState {
     id: u64,
+    /// The filter as it was when the dialog opened.
+    filter: FilterSummary,
     name: String,
-    remote_port: u16,
     action: WfpAction,
+    rows: Vec<ConditionRow>,
+    /// Saving adds a new filter instead of rewriting filter `id`.
+    copy: bool,
+}
+
+impl EditState {
+    fn of(filter: &FilterSummary) -> Self {
+        Self {
+            id: filter.id,
+            filter: filter.clone(),
+            name: filter.name.clone(),
+            action: filter.action,
+            rows: filter.conditions.iter().map(ConditionRow::of).collect(),
+            copy: false,
+        }
+    }
+
+    /// A copy of `filter` as a rule of its own, under a new key.
+    fn copy_of(filter: &FilterSummary) -> Self {
+        Self {
+            name: format!("{} (copy)", filter.name),
+            copy: true,
+            ..Self::of(filter)
+        }
+    }
+
+    fn schema(&self) -> Option<LayerSchema> {
+        LayerSchema::of(self.filter.layer_key)
+    }
+
+    /// Whether the layer is an IPv6 one, by its name when its schema is
+    /// not known.
+    fn v6(&self) -> bool {
+        self.schema().map_or_else(
+            || self.filter.layer.to_ascii_lowercase().contains("v6"),
+            |schema| schema.v6,
+        )
+    }
+
+    /// Fields a new row can test: the layer's, or every named one.
+    fn fields(&self) -> Vec<ConditionField> {
+        match self.schema() {
+            Some(schema) => schema.fields.to_vec(),
+            None => ConditionField::named().collect(),
+        }
+    }
+
+    /// The filter as edited. One that moves to another weight tier gets
+    /// the next weight there, and only permits keep a hard permit's flag.
+    fn builder(&self) -> Result<FilterBuilder> {
+        let v6 = self.v6();
+        let schema = self.schema();
+        let conditions = self
+            .rows
+            .iter()
+            .map(|row| row.condition(v6, schema))
+            .collect::<Result<Vec<_>>>()?;
+        if self.copy {
+            return Ok(FilterBuilder::new(&self.name, self.filter.layer_key)
+                .action(self.action)
+                .sublayer(self.filter.sublayer_key)
+                .indexed(self.filter.indexed)
+                .bound_like(&self.filter)
+                .conditions(conditions));
+        }
+        let builder = FilterBuilder::rebuild(&self.filter)
+            .name(&self.name)
+            .action(self.action)
+            .clear_action_right(self.filter.clear_action_right && self.action == WfpAction::Permit)
+            .conditions(conditions);
+        let tier = WeightTier::classify(self.filter.action, &self.filter.conditions);
+        Ok(if builder.tier() == tier {
+            builder
+        } else {
+            builder.auto_weight()
+        })
+    }
+
+    fn save(&self, engine: &wfp::Engine, builder: &FilterBuilder) -> Result<()> {
+        if self.copy {
+            engine.add_filter(builder)?;
+        } else {
+            engine.reinstall_filters(std::slice::from_ref(builder), "conditions edited")?;
+        }
+        Ok(())
+    }
+}
+
+/// The bulk edit dialog's choices; the weight is kept as typed.
+#[derive(Default)]
+struct BulkEditState {
+    edit: BulkEdit,
+    weight: String,
+}
+
+impl BulkEditState {
+    fn edit(&self) -> Result<BulkEdit> {
+        let weight = match self.weight.trim() {
+            "" => None,
+            weight => Some(
+                weight
+                    .parse()
+                    .map_err(|_| anyhow!("Weight '{weight}' is not a number"))?,
+            ),
+        };
+        let edit = BulkEdit {
+            weight,
+            ..self.edit.clone()
+        };
+        if edit.is_empty() {
+            return Err(anyhow!("Nothing to change"));
+        }
+        Ok(edit)
+    }
+}
+
+/// Commands from a filter row's context menu, run after the row is drawn.
+#[derive(Clone, Copy)]
+enum RowAction {
+    Duplicate(u64),
+    Export(u64),
+    Opposite(u64),
+    Pin(u64),
+    ShowEvents(u64),
+    Details(u64),
+    ResetHits(u64),
+}
+
+impl RowAction {
+    fn id(self) -> u64 {
+        match self {
+            RowAction::Duplicate(id)
+            | RowAction::Export(id)
+            | RowAction::Opposite(id)
+            | RowAction::Pin(id)
+            | RowAction::ShowEvents(id)
+            | RowAction::Details(id)
+            | RowAction::ResetHits(id) => id,
+        }
+    }
+}
+
+/// An import on a worker thread, so the window stays responsive and the
+/// import can be cancelled.
+struct ImportJob {
+    cancel: CancelToken,
+    result: Receiver<Result<ImportSummary>>,
+}
+
+impl ImportJob {
+    fn start(host: Option<String>, set: RuleSet) -> Self {
+        let cancel = CancelToken::new();
+        let (sender, result) = mpsc::channel();
+        let token = cancel.clone();
+        std::thread::spawn(move || {
+            let imported = wfp::Engine::open_on(host.as_deref())
+                .and_then(|engine| engine.import_rule_set_cancellable(&set, &token));
+            let _ = sender.send(imported);
+        });
+        Self { cancel, result }
+    }
+}
+
+/// What a refresh reads from the engine.
+struct Loaded {
+    snapshot: Snapshot,
+    sublayer_details: Vec<SublayerInfo>,
+    system_ports: Result<Vec<SystemPorts>>,
+    callouts: Result<Vec<CalloutInfo>>,
+}
+
+/// A snapshot taken on a worker thread, so a slow or remote engine does not
+/// freeze the window. Dropping the job, e.g. when a newer refresh replaces
+/// it, cancels it.
+struct RefreshJob {
+    /// The host it was started for, as in [`hosts::Host::name`].
+    host: Option<String>,
+    cancel: CancelToken,
+    result: Receiver<Result<Loaded>>,
+}
+
+impl RefreshJob {
+    fn start(host: Option<String>) -> Self {
+        let cancel = CancelToken::new();
+        let (sender, result) = mpsc::channel();
+        let token = cancel.clone();
+        let name = host.clone();
+        std::thread::spawn(move || {
+            let loaded = wfp::Engine::open_on(name.as_deref()).and_then(|eng| {
+                Ok(Loaded {
+                    snapshot: eng.snapshot_cancellable(&token)?,
+                    sublayer_details: eng.sublayer_details()?,
+                    system_ports: eng.system_ports(),
+                    callouts: eng.callouts(),
+                })
+            });
+            let _ = sender.send(loaded);
+        });
+        Self {
+            host,
+            cancel,
+            result,
+        }
+    }
+}
+
+impl Drop for RefreshJob {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// Views moved out of the main window into windows of their own, e.g. to
+/// keep monitoring on a second screen.
+#[derive(Default)]
+struct Detached {
+    events: bool,
+    chart: bool,
+    /// IDs of filters with an open detail window.
+    filters: Vec<u64>,
+}
+
+/// Text fields behind the net event query, parsed on submit.
+#[derive(Default)]
+struct EventFilterForm {
+    from: String,
+    to: String,
+    protocol: Option<u8>,
+    remote_address: String,
+    remote_port: String,
+    local_port: String,
+    app: String,
+    filter_id: String,
+}
+
+impl EventFilterForm {
+    fn to_query(&self) -> Result<NetEventQuery> {
+        let port = |text: &str, what: &str| -> Result<Option<u16>> {
+            match text.trim() {
+                "" => Ok(None),
+                text => text
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| anyhow!("{what} must be a port number")),
+            }
+        };
+        Ok(NetEventQuery {
+            range: TimeRange::parse(&self.from, &self.to)?,
+            protocol: self.protocol,
+            local_port: port(&self.local_port, "Local port")?,
+            remote_port: port(&self.remote_port, "Remote port")?,
+            remote_address: match self.remote_address.trim() {
+                "" => None,
+                text => Some(
+                    text.parse()
+                        .map_err(|_| anyhow!("Invalid remote address '{text}'"))?,
+                ),
+            },
+            app: Some(self.app.trim().to_string()).filter(|app| !app.is_empty()),
+            filter_id: match self.filter_id.trim() {
+                "" => None,
+                text => Some(
+                    text.parse()
+                        .map_err(|_| anyhow!("Filter ID must be a number"))?,
+                ),
+            },
+        })
+    }
+}
+
+/// Series behind the blocked/allowed chart and which of them are shown.
+struct ChartState {
+    interval_secs: i64,
+    series: Vec<Series>,
+    shown: BTreeMap<String, bool>,
+}
+
+impl Default for ChartState {
+    fn default() -> Self {
+        Self {
+            interval_secs: chart::INTERVALS[0].0,
+            series: Vec::new(),
+            shown: BTreeMap::new(),
+        }
+    }
+}
+
+/// Text fields behind a new alert rule, parsed on submit.
+#[derive(Default)]
+struct AlertForm {
+    name: String,
+    app: String,
+    protocol: Option<u8>,
+    remote_port: String,
+    kind: Option<NetEventKind>,
+    per_minute: String,
+    command: String,
+    webhook: String,
+}
+
+impl AlertForm {
+    fn to_rule(&self) -> Result<AlertRule> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("Alert name is required"));
+        }
+        let optional = |text: &str| Some(text.trim().to_string()).filter(|t| !t.is_empty());
+        Ok(AlertRule {
+            name: name.to_string(),
+            enabled: true,
+            app: optional(&self.app),
+            protocol: self.protocol,
+            remote_port: optional(&self.remote_port)
+                .map(|p| p.parse())
+                .transpose()
+                .map_err(|_| anyhow!("Remote port must be a port number"))?,
+            kind: self.kind,
+            per_minute: optional(&self.per_minute)
+                .map(|n| n.parse())
+                .transpose()
+                .map_err(|_| anyhow!("Drops per minute must be a whole number"))?
+                .unwrap_or(0),
+            command: optional(&self.command),
+            webhook: optional(&self.webhook)
+                .map(|url| alerts::validate_webhook(&url).map(|()| url))
+                .transpose()?,
+        })
+    }
+}
+
+/// Syslog settings as edited; the port stays text until saved.
+struct SyslogForm {
+    config: SyslogConfig,
+    port: String,
+}
+
+impl SyslogForm {
+    fn new(config: SyslogConfig) -> Self {
+        Self {
+            port: config.port.to_string(),
+            config,
+        }
+    }
+
+    fn to_config(&self) -> Result<SyslogConfig> {
+        Ok(SyslogConfig {
+            port: self
+                .port
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Syslog port must be a port number"))?,
+            ..self.config.clone()
+        })
+    }
 }
 
 struct DeleteState {
     id: u64,
+    key: GUID,
     name: String,
 }
 
+impl DeleteState {
+    fn of(filter: &FilterSummary) -> Self {
+        Self {
+            id: filter.id,
+            key: filter.rule_key(),
+            name: filter.name.clone(),
+        }
+    }
+}
+
 impl Default for AppState {
     fn default() -> Self {
+        let mut notices = Notifications::default();
+        let alerts = Alerts::load().unwrap_or_else(|err| {
+            notices.error(format!("Alert rules not loaded: {err}"));
+            Alerts::default()
+        });
+        let retention = EventStore::open(&EventStore::default_dir())
+            .map(|store| store.retention())
+            .unwrap_or_default();
+        let log_retention = LogRetention::load().unwrap_or_default();
+        let dns_lockdown = DnsLockdown::load().unwrap_or_default();
+        let quic_block = QuicBlock::load().unwrap_or_default();
+        let flow_monitor = FlowMonitor::load().unwrap_or_default();
+        let naming_policy = NamingPolicy::load().unwrap_or_else(|err| {
+            notices.error(format!("Naming policy not loaded: {err}"));
+            NamingPolicy::default()
+        });
         Self {
-            status: "Ready".into(),
+            notices,
+            tab: Tab::Rules,
             filters: Vec::new(),
+            boot_time_filters: Vec::new(),
             providers: Vec::new(),
             sublayers: Vec::new(),
+            sublayer_details: Vec::new(),
             layers: Vec::new(),
-            refresh_pending: true,
+            refresh: RefreshScheduler::default(),
+            refresh_job: None,
             add_name: "My Filter".into(),
-            add_tcp_port: 445,
+            add_ports: "445".into(),
+            add_expression: String::new(),
+            add_sublayer: None,
+            new_sublayer_name: String::new(),
+            new_sublayer_weight: String::new(),
+            sublayer_delete: None,
+            callout_details: Vec::new(),
+            new_callout_key: String::new(),
+            new_callout_name: String::new(),
+            new_callout_description: String::new(),
+            new_callout_layer: None,
             add_block: true,
+            add_companions: true,
             export_text: String::new(),
+            import_path: String::new(),
+            import_preview: None,
+            import_job: None,
+            profiles: profiles::list().unwrap_or_default(),
+            new_profile_name: String::new(),
+            profile_rename: None,
+            filter_view: FilterView::Table,
+            app_policy_path: String::new(),
+            app_policy: None,
+            host_policy_query: String::new(),
+            host_policy: None,
+            coexistence_report: String::new(),
+            sessions: Vec::new(),
+            system_ports: Vec::new(),
+            connections: None,
+            connections_established_only: true,
+            ipsec: None,
+            ipsec_feed: None,
+            engine_state: None,
+            bench: None,
+            net_events: Vec::new(),
+            diagnosis: None,
+            event_filter: EventFilterForm::default(),
+            record_history: true,
+            history_max_mb: (retention.max_bytes / MIB).to_string(),
+            history_max_days: retention
+                .max_age_days
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            log_max_mb: (log_retention.max_bytes / MIB).to_string(),
+            log_keep: log_retention.keep.to_string(),
+            log_max_days: log_retention
+                .max_age_days
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            chart: ChartState::default(),
+            event_export_path: String::new(),
+            event_export_format: EventExportFormat::JsonLines,
+            capture: None,
+            live_log: VecDeque::new(),
+            etw: None,
+            alerts,
+            alert_form: AlertForm {
+                kind: Some(NetEventKind::Drop),
+                ..Default::default()
+            },
+            alert_feed: None,
+            fired_alerts: VecDeque::new(),
+            syslog: SyslogForm::new(SyslogConfig::load().unwrap_or_default()),
+            scripts: scripting::load_schedule().unwrap_or_default(),
+            script_path: String::new(),
+            script_every: "60".into(),
+            app_schedules: app_schedule::load_schedules().unwrap_or_default(),
+            favorites: favorites::load_favorites().unwrap_or_default(),
+            schedule_app: String::new(),
+            schedule_window: "18:00-22:00".into(),
+            scheduler: None,
+            script_output: mpsc::channel(),
+            script_log: VecDeque::new(),
             edit_state: None,
             delete_state: None,
+            selected_filters: Vec::new(),
+            bulk_edit: None,
+            hosts: Hosts::load().unwrap_or_default(),
+            new_host: String::new(),
+            lockdown: None,
+            lockdown_confirm: false,
+            allowances: Vec::new(),
+            hit_test: None,
+            hit_counters: HitCounters::load().unwrap_or_default(),
+            unused: None,
+            unused_days: unused::DEFAULT_DAYS.to_string(),
+            netsh_text: String::new(),
+            netsh_filters: None,
+            snapshot_path: String::new(),
+            offline: None,
+            snapshot_compare_path: String::new(),
+            snapshot_diff: None,
+            installer_watch: None,
+            installer_changes: None,
+            installer_export_path: String::new(),
+            installer_confirm_delete: false,
+            dns_resolvers: dns_lockdown
+                .resolvers
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            dns_lockdown,
+            quic_apps: quic_block.apps.join("\n"),
+            quic_block,
+            flow_monitor_apps: flow_monitor.apps.join("\n"),
+            flow_monitor,
+            interface_deny: InterfaceDeny::load().unwrap_or_default(),
+            network_interfaces: interface_deny::interfaces().unwrap_or_default(),
+            safety: Safety::load().unwrap_or_default(),
+            remote_sessions: None,
+            peer_permit: None,
+            naming_max_length: naming_policy
+                .max_length
+                .map(|max| max.to_string())
+                .unwrap_or_default(),
+            naming_policy,
+            bfe: None,
+            consistency: None,
+            consistency_checked: false,
+            reveal_events: false,
+            detached: Detached::default(),
         }
     }
 }
@@ -54,53 +889,563 @@ impl Default for AppState {
 impl eframe::App for AppState {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
-            ui.heading("SLS WFP Manager");
             ui.horizontal(|ui| {
-                if ui.button("Refresh").clicked() {
-                    self.refresh_pending = true;
+                ui.heading("SLS WFP Manager");
+                if let Some(saved) = &self.offline {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(255, 140, 0),
+                        format!("Offline: {}", saved.label()),
+                    )
+                    .on_hover_text("Showing a saved snapshot, not this machine's engine");
+                } else if wfp::is_read_only() {
+                    ui.colored_label(egui::Color32::from_rgb(255, 140, 0), "Read-only")
+                        .on_hover_text(
+                            "Started with --read-only; rules can be viewed but not changed",
+                        );
+                }
+            });
+            self.render_host_tabs(ui);
+            ui.horizontal(|ui| {
+                for tab in [
+                    Tab::Rules,
+                    Tab::Alerts,
+                    Tab::Scripts,
+                    Tab::Connections,
+                    Tab::Ipsec,
+                    Tab::State,
+                ] {
+                    ui.selectable_value(&mut self.tab, tab, tab.label());
+                }
+            });
+            self.render_favorites(ui);
+            ui.horizontal(|ui| {
+                if let Some(job) = &self.refresh_job {
+                    ui.spinner();
+                    if ui
+                        .add_enabled(!job.cancel.is_cancelled(), egui::Button::new("Cancel"))
+                        .on_hover_text("Stop loading filters")
+                        .clicked()
+                    {
+                        job.cancel.cancel();
+                    }
+                } else if ui.button("Refresh").clicked() {
+                    self.refresh.request();
                 }
-                ui.label(&self.status);
+                egui::ComboBox::from_id_source("auto_refresh")
+                    .selected_text(format!("Auto: {}", self.refresh.auto.label()))
+                    .show_ui(ui, |ui| {
+                        for auto in AutoRefresh::ALL {
+                            ui.selectable_value(&mut self.refresh.auto, auto, auto.label());
+                        }
+                    });
+                if self.lockdown_confirm {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        "Block every new connection on this machine?",
+                    );
+                    if ui.button("Block all traffic").clicked() {
+                        self.lockdown_confirm = false;
+                        self.start_lockdown();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.lockdown_confirm = false;
+                    }
+                } else if self.lockdown.is_none()
+                    && !wfp::is_read_only()
+                    && ui
+                        .add(
+                            egui::Button::new(
+                                egui::RichText::new("Block all traffic now")
+                                    .color(egui::Color32::WHITE),
+                            )
+                            .fill(egui::Color32::DARK_RED),
+                        )
+                        .on_hover_text(
+                            "Block every new connection on this machine until undone, \
+                             the timer runs out or the app closes",
+                        )
+                        .clicked()
+                {
+                    if self.safety.confirms() {
+                        self.lockdown_confirm = true;
+                    } else {
+                        self.start_lockdown();
+                    }
+                }
+                self.render_status(ui);
             });
+            self.render_bfe_bar(ui);
+            self.render_lockdown_bar(ui);
+            self.render_peer_permit_bar(ui);
+            self.render_allowances_bar(ui);
+            self.render_capture_bar(ui);
         });
 
-        if self.refresh_pending {
+        self.render_host_status(ctx);
+
+        if let Some((state, checked)) = self.bfe {
+            if state.is_pending() {
+                if checked.elapsed() >= BFE_POLL {
+                    self.refresh.request();
+                }
+                ctx.request_repaint_after(BFE_POLL);
+            }
+        }
+        // A request made while this host is loading waits for that load, so
+        // it sees any edit made meanwhile; a host switch replaces it.
+        let loading = self
+            .refresh_job
+            .as_ref()
+            .is_some_and(|job| job.host == self.hosts.active().name);
+        if self.refresh.due() && !loading {
             self.load_snapshot();
-            self.refresh_pending = false;
+            self.refresh.done();
+        }
+        self.poll_refresh(ctx);
+        if let Some(wait) = self.refresh.wait() {
+            ctx.request_repaint_after(wait);
+        }
+        if let Some((_, receiver)) = &self.etw {
+            self.live_log.extend(receiver.try_iter());
+            let excess = self.live_log.len().saturating_sub(MAX_LIVE_LOG);
+            self.live_log.drain(..excess);
+            ctx.request_repaint_after(Duration::from_millis(500));
+        }
+        self.poll_alerts(ctx);
+        self.poll_import(ctx);
+        self.script_log.extend(self.script_output.1.try_iter());
+        let excess = self.script_log.len().saturating_sub(MAX_SCRIPT_LOG);
+        self.script_log.drain(..excess);
+        if self.scheduler.is_some() {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+        if self.lockdown.as_ref().is_some_and(Lockdown::expired) {
+            self.stop_lockdown("Lockdown expired, traffic allowed again");
+        }
+        if self.lockdown.is_some() {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+        let before = self.allowances.len();
+        self.allowances.retain(|allow| !allow.expired());
+        if self.allowances.len() < before {
+            self.notices
+                .info("Temporary allow expired, the flow is blocked again");
+        }
+        if !self.allowances.is_empty() {
+            ctx.request_repaint_after(Duration::from_secs(1));
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            self.render_add_section(ui);
+            match self.tab {
+                Tab::Rules => {}
+                Tab::Alerts => return self.render_alerts(ui),
+                Tab::Scripts => return self.render_scripts(ui),
+                Tab::Connections => return self.render_connections(ui),
+                Tab::Ipsec => return self.render_ipsec(ui),
+                Tab::State => {
+                    self.render_state(ui);
+                    ui.separator();
+                    return self.render_bench(ui);
+                }
+            }
+            if !wfp::is_read_only() {
+                self.render_add_section(ui);
+                ui.separator();
+            }
+            self.render_sublayers(ui);
+            ui.separator();
+            self.render_callouts(ui);
+            ui.separator();
+            self.render_dns_lockdown(ui);
+            ui.separator();
+            self.render_quic_block(ui);
+            ui.separator();
+            self.render_flow_monitor(ui);
+            ui.separator();
+            self.render_interface_deny(ui);
+            ui.separator();
+            self.render_safety(ui);
+            ui.separator();
+            self.render_naming_policy(ui);
             ui.separator();
             self.render_export_import(ui);
             ui.separator();
+            self.render_profiles(ui);
+            ui.separator();
+            self.render_netsh_import(ui);
+            ui.separator();
+            self.render_snapshot_file(ui);
+            ui.separator();
+            self.render_installer_watch(ui);
+            ui.separator();
             self.render_filters(ui);
             ui.separator();
+            self.render_net_events(ui);
+            ui.separator();
+            self.render_chart(ui);
+            ui.separator();
+            self.render_live_log(ui);
+            ui.separator();
             self.render_metadata(ui);
+            ui.separator();
+            self.render_coexistence(ui);
+            ui.separator();
+            self.render_unused_rules(ui);
         });
 
         self.render_edit_window(ctx);
+        self.render_bulk_edit_window(ctx);
         self.render_delete_window(ctx);
+        self.render_consistency_window(ctx);
+        self.render_diagnosis_window(ctx);
+        self.render_detached(ctx);
+        self.render_toasts(ctx);
     }
 }
 
 impl AppState {
+    /// Starts loading the active host's filters, cancelling a load still
+    /// running for another host.
     fn load_snapshot(&mut self) {
-        match Engine::open().and_then(|eng| eng.snapshot()) {
-            Ok(snapshot) => {
+        // An offline snapshot stays as saved until it is closed.
+        if self.offline.is_some() {
+            return;
+        }
+        self.refresh_job = Some(RefreshJob::start(self.hosts.active().name.clone()));
+    }
+
+    fn poll_refresh(&mut self, ctx: &egui::Context) {
+        let Some(job) = &self.refresh_job else {
+            return;
+        };
+        let loaded = match job.result.try_recv() {
+            Ok(loaded) => loaded,
+            Err(mpsc::TryRecvError::Empty) => {
+                ctx.request_repaint_after(Duration::from_millis(100));
+                return;
+            }
+            Err(mpsc::TryRecvError::Disconnected) => Err(anyhow!("Refresh worker stopped")),
+        };
+        let stale = self.offline.is_some() || job.host != self.hosts.active().name;
+        self.refresh_job = None;
+        if stale {
+            return;
+        }
+        match loaded {
+            Ok(Loaded {
+                snapshot,
+                sublayer_details,
+                system_ports,
+                callouts,
+            }) => {
+                self.bfe = None;
+                // Only used for warnings, so a failure leaves the list empty.
+                self.system_ports = system_ports.unwrap_or_default();
+                self.callout_details = callouts.unwrap_or_default();
+                let owned = self
+                    .hosts
+                    .active()
+                    .name
+                    .is_none()
+                    .then(|| RuleSet::owned(snapshot.clone(), &sublayer_details));
                 self.apply_snapshot(snapshot);
-                self.status = format!("Loaded {} filters", self.filters.len());
+                self.sublayer_details = sublayer_details;
+                self.notices.info(format!(
+                    "Loaded {} filters from {}",
+                    self.filters.len(),
+                    self.hosts.active().label()
+                ));
+                self.hosts.hosts[self.hosts.active].status = Some(HostStatus::Reachable {
+                    filters: self.filters.len(),
+                    owned: self.filters.iter().filter(|f| f.owned_by_app).count(),
+                    checked: chrono::Local::now(),
+                });
+                if let Some(owned) = owned.filter(|_| !wfp::is_read_only()) {
+                    self.check_consistency(owned);
+                }
             }
+            Err(err) if err.is::<wfp::Cancelled>() => self
+                .notices
+                .info("Refresh cancelled; showing the filters loaded before."),
             Err(err) => {
-                self.status = format!("Error loading filters: {err}");
+                self.notices.error(format!("Error loading filters: {err}"));
+                self.hosts.hosts[self.hosts.active].status =
+                    Some(HostStatus::Unreachable(err.to_string()));
+                // A stopped or restarting BFE is the usual reason this
+                // machine cannot be opened; say so instead of the raw error.
+                if self.hosts.active().name.is_none() {
+                    self.bfe = match bfe::state() {
+                        Ok(BfeState::Running) | Err(_) => None,
+                        Ok(state) => {
+                            self.notices.info(state.describe());
+                            Some((state, Instant::now()))
+                        }
+                    };
+                }
+            }
+        }
+    }
+
+    /// Compares this machine's owned rules with the record from the last
+    /// run once per launch, then keeps the record current while nothing is
+    /// waiting to be repaired.
+    fn check_consistency(&mut self, owned: RuleSet) {
+        if !self.consistency_checked {
+            self.consistency_checked = true;
+            match consistency::load_record() {
+                Ok(Some(recorded)) => {
+                    self.consistency = ConsistencyReport::check(recorded, &owned.filters);
+                }
+                Ok(None) => {}
+                Err(err) => self.notices.error(format!("Rule record not read: {err}")),
+            }
+        }
+        if self.consistency.is_none() {
+            if let Err(err) = consistency::save_record(&owned) {
+                self.notices.error(format!("Rule record not saved: {err}"));
+            }
+        }
+    }
+
+    /// One toggle per pinned rule, lit while its filters are installed on
+    /// the active host.
+    fn render_favorites(&mut self, ui: &mut egui::Ui) {
+        if self.favorites.is_empty() {
+            return;
+        }
+        let mut toggle = None;
+        let mut unpin = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.set_enabled(!wfp::is_read_only());
+            ui.label("Favorites:");
+            for (i, favorite) in self.favorites.iter().enumerate() {
+                let on = favorite.is_on(&self.filters);
+                let response = ui
+                    .selectable_label(on, &favorite.config.name)
+                    .on_hover_text(if on {
+                        "On; click to remove its filters"
+                    } else {
+                        "Off; click to install it"
+                    });
+                if response.clicked() {
+                    toggle = Some((i, !on));
+                }
+                response.context_menu(|ui| {
+                    if ui.button("Unpin").clicked() {
+                        ui.close_menu();
+                        unpin = Some(i);
+                    }
+                });
             }
+        });
+        if let Some((i, on)) = toggle {
+            // Keep edits made since pinning, so switching back on restores
+            // the rule as it was.
+            let key = self.favorites[i].rule_key();
+            let current = self
+                .filters
+                .iter()
+                .find(|f| f.owned_by_app && f.rule_key() == key)
+                .and_then(|f| self.rule_config(f));
+            if let Some(config) = current {
+                self.favorites[i].config = config;
+            }
+            let favorite = &self.favorites[i];
+            match self.hosts.open().and_then(|eng| {
+                if on {
+                    self.safety
+                        .check_lockout_configs(&eng, std::slice::from_ref(&favorite.config))?;
+                }
+                favorite.set(&eng, on)
+            }) {
+                Ok(()) => {
+                    self.refresh.request();
+                    let state = if on { "on" } else { "off" };
+                    self.notices
+                        .info(format!("Turned '{}' {state}.", favorite.config.name))
+                }
+                Err(err) => self
+                    .notices
+                    .error(format!("'{}' not changed: {err}", favorite.config.name)),
+            }
+            if let Err(err) = favorites::save_favorites(&self.favorites) {
+                self.notices.error(format!("Favorites not saved: {err}"));
+            }
+        }
+        if let Some(i) = unpin {
+            let favorite = self.favorites.remove(i);
+            match favorites::save_favorites(&self.favorites) {
+                Ok(()) => self
+                    .notices
+                    .info(format!("Unpinned '{}'.", favorite.config.name)),
+                Err(err) => self.notices.error(format!("Favorites not saved: {err}")),
+            }
+        }
+    }
+
+    /// Banner shown while BFE on this machine is not running, with a way
+    /// to start it.
+    fn render_bfe_bar(&mut self, ui: &mut egui::Ui) {
+        let Some((state, _)) = self.bfe else {
+            return;
+        };
+        let mut start = false;
+        ui.horizontal(|ui| {
+            ui.colored_label(egui::Color32::from_rgb(255, 140, 0), state.describe());
+            if state == BfeState::Stopped && !wfp::is_read_only() {
+                start = ui
+                    .button("Start service")
+                    .on_hover_text("Starts the Base Filtering Engine; needs administrator rights")
+                    .clicked();
+            }
+        });
+        if start {
+            match bfe::start() {
+                Ok(()) => {
+                    self.bfe = Some((BfeState::Starting, Instant::now()));
+                    self.notices.info("Starting the Base Filtering Engine…");
+                }
+                Err(err) => self.notices.error(format!("{err}")),
+            }
+        }
+    }
+
+    /// One tab per managed machine, plus adding hosts and copying rules
+    /// between them. Alerts, scripts and the live log always follow this
+    /// machine.
+    fn render_host_tabs(&mut self, ui: &mut egui::Ui) {
+        let mut switch_to = None;
+        let mut remove = None;
+        ui.horizontal(|ui| {
+            ui.label("Host:");
+            for (idx, host) in self.hosts.hosts.iter().enumerate() {
+                if ui
+                    .selectable_label(idx == self.hosts.active, host.label())
+                    .clicked()
+                    && idx != self.hosts.active
+                {
+                    switch_to = Some(idx);
+                }
+                if idx > 0 && ui.small_button("x").on_hover_text("Close host").clicked() {
+                    remove = Some(idx);
+                }
+            }
+            ui.separator();
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_host)
+                    .desired_width(140.0)
+                    .hint_text("server name or IP"),
+            );
+            if ui.button("Add host").clicked() {
+                match self.hosts.add(&self.new_host) {
+                    Ok(()) => {
+                        self.new_host.clear();
+                        switch_to = Some(self.hosts.hosts.len() - 1);
+                    }
+                    Err(err) => self.notices.error(format!("Add host failed: {err}")),
+                }
+            }
+            ui.separator();
+            if ui.button("Check all hosts").clicked() {
+                self.hosts.check_all();
+            }
+            if ui
+                .add_enabled(
+                    self.hosts.hosts.len() > 1,
+                    egui::Button::new("Push rules to all hosts"),
+                )
+                .on_hover_text("Import this host's owned rules on every other host")
+                .clicked()
+            {
+                match self.hosts.push_rules() {
+                    Ok(results) => self.notices.info(
+                        results
+                            .into_iter()
+                            .map(|(host, result)| match result {
+                                Ok(summary) => format!(
+                                    "{host}: {} added, {} updated",
+                                    summary.added, summary.updated
+                                ),
+                                Err(err) => format!("{host}: {err}"),
+                            })
+                            .collect::<Vec<_>>()
+                            .join("; "),
+                    ),
+                    Err(err) => self.notices.error(format!("Push failed: {err}")),
+                }
+            }
+        });
+        if let Some(idx) = remove {
+            if let Err(err) = self.hosts.remove(idx) {
+                self.notices.error(format!("Close host failed: {err}"));
+            }
+            switch_to = Some(self.hosts.active);
+        }
+        if let Some(idx) = switch_to {
+            self.hosts.active = idx;
+            self.net_events.clear();
+            self.diagnosis = None;
+            self.coexistence_report.clear();
+            self.sessions.clear();
+            self.engine_state = None;
+            self.ipsec = None;
+            self.ipsec_feed = None;
+            self.edit_state = None;
+            self.delete_state = None;
+            self.sublayer_delete = None;
+            self.refresh.request();
         }
     }
 
+    /// Status bar summarising every host from its last check.
+    fn render_host_status(&self, ctx: &egui::Context) {
+        if self.hosts.hosts.len() < 2 {
+            return;
+        }
+        egui::TopBottomPanel::bottom("host_status").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for host in &self.hosts.hosts {
+                    match &host.status {
+                        Some(HostStatus::Reachable {
+                            filters,
+                            owned,
+                            checked,
+                        }) => {
+                            ui.label(format!(
+                                "{}: {filters} filters, {owned} owned",
+                                host.label()
+                            ))
+                            .on_hover_text(format!("Checked {}", checked.format("%H:%M:%S")));
+                        }
+                        Some(HostStatus::Unreachable(err)) => {
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                format!("{}: unreachable", host.label()),
+                            )
+                            .on_hover_text(err);
+                        }
+                        None => {
+                            ui.weak(format!("{}: not checked", host.label()));
+                        }
+                    }
+                    ui.separator();
+                }
+            });
+        });
+    }
+
     fn apply_snapshot(&mut self, snapshot: Snapshot) {
         self.filters = snapshot.filters;
+        self.boot_time_filters = snapshot.boot_time_filters;
         self.providers = snapshot.providers;
         self.sublayers = snapshot.sublayers;
         self.layers = snapshot.layers;
+        if self.app_policy.is_some() {
+            self.build_app_policy();
+        }
+        if self.host_policy.is_some() {
+            self.build_host_policy();
+        }
     }
 
     fn render_add_section(&mut self, ui: &mut egui::Ui) {
@@ -110,211 +1455,4338 @@ impl AppState {
                 ui.horizontal(|ui| {
                     ui.label("Name:");
                     ui.text_edit_singleline(&mut self.add_name);
-                    ui.label("TCP Port:");
-                    ui.add(egui::DragValue::new(&mut self.add_tcp_port).clamp_range(1..=65535));
+                    ui.label("TCP Ports:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.add_ports)
+                            .desired_width(120.0)
+                            .hint_text("e.g. 445, 139"),
+                    );
                     ui.checkbox(&mut self.add_block, "Block (unchecked = Allow)");
                 });
+                if self.add_block {
+                    if let Ok(ports) = self.add_ports.parse::<RemotePorts>() {
+                        let config = FilterConfig::tcp_ports("", ports, WfpAction::Block);
+                        self.system_port_warning(ui, &config);
+                        self.coverage_warning(ui, &config);
+                    }
+                }
                 if ui.button("Add Filter at ALE_AUTH_CONNECT_V4").clicked() {
                     let action = if self.add_block {
                         WfpAction::Block
                     } else {
                         WfpAction::Permit
                     };
-                    let res = Engine::open().and_then(|eng| {
-                        eng.add_simple_tcp_filter_v4(&self.add_name, self.add_tcp_port, action)
+                    let res = self.add_ports.parse::<RemotePorts>().and_then(|ports| {
+                        let mut config = FilterConfig::tcp_ports(&self.add_name, ports, action);
+                        let companions = self.companions(&config);
+                        self.hosts.open().and_then(|eng| {
+                            if companions.is_empty() {
+                                return eng
+                                    .add_simple_tcp_filter_v4(
+                                        &self.add_name,
+                                        config.remote_port.as_slice(),
+                                        action,
+                                    )
+                                    .map(|_| ());
+                            }
+                            config.key = Some(Uuid::new_v4());
+                            let rules = [vec![config], companions].concat();
+                            self.safety.check_lockout_configs(&eng, &rules)?;
+                            eng.import_filters(&rules).map(|_| ())
+                        })
                     });
-                    self.status = match res {
-                        Ok(_) => "Filter added.".into(),
-                        Err(e) => format!("Add failed: {e}"),
-                    };
-                    self.refresh_pending = true;
+                    match res {
+                        Ok(_) => self.notices.info("Filter added."),
+                        Err(e) => self.notices.error(format!("Add failed: {e}")),
+                    }
+                    self.refresh.request();
                 }
-            });
-    }
-
-    fn render_export_import(&mut self, ui: &mut egui::Ui) {
-        egui::CollapsingHeader::new("Export / Import Owned Rules")
-            .default_open(false)
-            .show(ui, |ui| {
+                ui.separator();
                 ui.horizontal(|ui| {
-                    if ui.button("Export to JSON").clicked() {
-                        self.status =
-                            match Engine::open().and_then(|eng| eng.export_owned_filters()) {
-                                Ok(json) => {
-                                    self.export_text = json;
-                                    "Exported owned filters.".into()
-                                }
-                                Err(err) => format!("Export failed: {err}"),
-                            };
-                    }
-                    if ui.button("Import from JSON").clicked() {
-                        let parsed: Result<Vec<FilterConfig>, _> =
-                            serde_json::from_str(&self.export_text);
-                        match parsed {
-                            Ok(configs) => {
-                                self.status = match Engine::open()
-                                    .and_then(|eng| eng.import_filters(&configs))
-                                {
-                                    Ok(_) => {
-                                        self.refresh_pending = true;
-                                        "Import complete.".into()
-                                    }
-                                    Err(err) => format!("Import failed: {err}"),
-                                };
+                    ui.label("Rule:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.add_expression)
+                            .desired_width(360.0)
+                            .hint_text("e.g. block out tcp to 10.0.0.0/8 port 445"),
+                    );
+                    let group = self
+                        .add_sublayer
+                        .and_then(|key| self.sublayer_details.iter().find(|s| s.key == key))
+                        .map(|s| s.name.as_str())
+                        .unwrap_or("Default sublayer");
+                    egui::ComboBox::from_id_source("add_sublayer")
+                        .selected_text(group)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.add_sublayer, None, "Default sublayer");
+                            for sublayer in self.sublayer_details.iter().filter(|s| s.ours) {
+                                ui.selectable_value(
+                                    &mut self.add_sublayer,
+                                    Some(sublayer.key),
+                                    &sublayer.name,
+                                );
                             }
-                            Err(err) => {
-                                self.status = format!("JSON parse error: {err}");
+                        });
+                    if ui
+                        .button("Test against recent traffic")
+                        .on_hover_text(format!(
+                            "Count the saved net events of the last {} days this rule \
+                             would have matched",
+                            hit_test::DEFAULT_DAYS
+                        ))
+                        .clicked()
+                    {
+                        let result = rule_expr::parse(&self.add_expression).and_then(|config| {
+                            HitTest::against_history(&config, hit_test::DEFAULT_DAYS)
+                        });
+                        match result {
+                            Ok(report) => {
+                                self.hit_test = Some((self.add_expression.clone(), report));
                             }
+                            Err(e) => self.notices.error(format!("Test failed: {e}")),
                         }
                     }
-                });
+                    if ui.button("Add rule").clicked() {
+                        let sublayer = self.add_sublayer.map(wfp::uuid_from_guid);
+                        let res = rule_expr::parse(&self.add_expression).and_then(|mut config| {
+                            // Keyed, so hostnames are recorded for the rule.
+                            config.key = Some(Uuid::new_v4());
+                            config.sublayer = sublayer;
+                            let companions = self.companions(&config);
+                            self.hosts.open().and_then(|eng| {
+                                let rules = [vec![config], companions].concat();
+                                self.safety.check_lockout_configs(&eng, &rules)?;
+                                eng.import_filters(&rules)
+                            })
+                        });
+                        match res {
+                            Ok(_) => {
+                                self.add_expression.clear();
+                                self.notices.info("Rule added.")
+                            }
+                            Err(e) => self.notices.error(format!("Add failed: {e}")),
+                        }
+                        self.refresh.request();
+                    }
+                });
+                if let Ok(config) = rule_expr::parse(&self.add_expression) {
+                    self.system_port_warning(ui, &config);
+                    self.coverage_warning(ui, &config);
+                }
+                self.render_hit_test(ui);
+            });
+    }
+
+    /// The last hit test, while the expression it tested is unchanged.
+    fn render_hit_test(&mut self, ui: &mut egui::Ui) {
+        if self
+            .hit_test
+            .as_ref()
+            .is_some_and(|(expression, _)| *expression != self.add_expression)
+        {
+            self.hit_test = None;
+        }
+        let Some((_, report)) = &self.hit_test else {
+            return;
+        };
+        // A rule that catches much of the traffic is likely broader than
+        // meant.
+        let broad = report.scanned > 0 && report.matched * 4 >= report.scanned;
+        let summary = report.summary();
+        if broad {
+            ui.colored_label(egui::Color32::YELLOW, format!("⚠ {summary}"));
+        } else {
+            ui.label(summary);
+        }
+        for (app, count) in report.apps.iter().take(5) {
+            ui.label(format!("    {count} from {app}"));
+        }
+    }
+
+    /// Warns when `config` would block ports the system has reserved.
+    fn system_port_warning(&self, ui: &mut egui::Ui, config: &FilterConfig) {
+        let blocked = wfp::blocked_system_ports(config, &self.system_ports);
+        if blocked.is_empty() {
+            return;
+        }
+        let ports: Vec<String> = blocked
+            .iter()
+            .map(|(kind, port)| format!("{port} ({})", kind.label()))
+            .collect();
+        ui.colored_label(
+            egui::Color32::YELLOW,
+            format!(
+                "⚠ This blocks system port(s) {}; Windows services using them may stop working.",
+                ports.join(", ")
+            ),
+        );
+    }
+
+    /// Warns when the block `config` leaves IPv6 or inbound connections
+    /// open, offering to add the rules that close them along with it.
+    fn coverage_warning(&mut self, ui: &mut egui::Ui, config: &FilterConfig) {
+        let gaps = wfp::coverage_gaps(config);
+        if gaps.is_empty() {
+            return;
+        }
+        let gaps: Vec<&str> = gaps.iter().map(|gap| gap.describe()).collect();
+        ui.colored_label(
+            egui::Color32::YELLOW,
+            format!(
+                "⚠ Only part of the traffic is covered: {}.",
+                gaps.join("; ")
+            ),
+        );
+        ui.checkbox(
+            &mut self.add_companions,
+            "Also add rules blocking the remaining paths",
+        );
+    }
+
+    /// The rules added along with `config`, when the user wants them.
+    fn companions(&self, config: &FilterConfig) -> Vec<FilterConfig> {
+        if !self.add_companions {
+            return Vec::new();
+        }
+        wfp::companion_rules(config)
+    }
+
+    /// Outbound DNS limited to allowed resolvers, shown with its filters.
+    fn render_dns_lockdown(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("DNS lockdown")
+            .default_open(false)
+            .show(ui, |ui| {
+                let members = DnsLockdown::members(&self.filters);
+                let on = !members.is_empty();
+                if on {
+                    ui.colored_label(
+                        egui::Color32::LIGHT_GREEN,
+                        format!(
+                            "On: outbound DNS may only reach the allowed resolvers ({} filters)",
+                            members.len()
+                        ),
+                    );
+                } else {
+                    ui.label("Off: DNS may reach any server");
+                }
+                egui::ScrollArea::vertical()
+                    .id_source("dns_lockdown_filters")
+                    .max_height(120.0)
+                    .show(ui, |ui| {
+                        for filter in &members {
+                            let conditions: Vec<String> =
+                                filter.conditions.iter().map(ToString::to_string).collect();
+                            ui.label(format!(
+                                "{} {} {}",
+                                filter.id,
+                                filter.action.as_str(),
+                                conditions.join(", ")
+                            ));
+                        }
+                    });
+                ui.add_enabled_ui(!wfp::is_read_only(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Allowed resolvers:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.dns_resolvers)
+                                .desired_width(240.0)
+                                .hint_text("e.g. 192.168.1.1, 2001:db8::53"),
+                        );
+                    });
+                    ui.checkbox(
+                        &mut self.dns_lockdown.restrict_dot,
+                        "Also limit DNS over TLS (port 853)",
+                    );
+                    ui.checkbox(
+                        &mut self.dns_lockdown.block_doh,
+                        "Block DNS over HTTPS to well-known public resolvers",
+                    );
+                    ui.horizontal(|ui| {
+                        let label = if on { "Apply changes" } else { "Turn on" };
+                        if ui.button(label).clicked() {
+                            self.enable_dns_lockdown();
+                        }
+                        if on && ui.button("Turn off").clicked() {
+                            match self
+                                .hosts
+                                .open()
+                                .and_then(|engine| DnsLockdown::disable(&engine))
+                            {
+                                Ok(()) => {
+                                    self.refresh.request();
+                                    self.notices.info("DNS lockdown off.")
+                                }
+                                Err(err) => self
+                                    .notices
+                                    .error(format!("DNS lockdown not removed: {err}")),
+                            }
+                        }
+                    });
+                });
+            });
+    }
+
+    fn enable_dns_lockdown(&mut self) {
+        let result = dns_lockdown::parse_resolvers(&self.dns_resolvers).and_then(|resolvers| {
+            self.dns_lockdown.resolvers = resolvers;
+            let added = self.dns_lockdown.enable(&self.hosts.open()?)?;
+            self.dns_lockdown.save()?;
+            Ok(added)
+        });
+        match result {
+            Ok(added) => {
+                self.refresh.request();
+                self.notices
+                    .info(format!("DNS lockdown on, {added} filters installed."))
+            }
+            Err(err) => self
+                .notices
+                .error(format!("DNS lockdown not applied: {err}")),
+        }
+    }
+
+    fn render_safety(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Safety")
+            .default_open(false)
+            .show(ui, |ui| {
+                let mut changed = false;
+                for policy in ConfirmPolicy::ALL {
+                    changed |= ui
+                        .radio_value(&mut self.safety.confirm, policy, policy.label())
+                        .changed();
+                }
+                changed |= ui
+                    .checkbox(
+                        &mut self.safety.peer_permit,
+                        "In an RDP session, permit the RDP client before imports that could \
+                         block it, until the connection is confirmed",
+                    )
+                    .changed();
+                changed |= ui
+                    .checkbox(
+                        &mut self.safety.lockout_guard,
+                        "Refuse rules blocking all traffic while an RDP or SSH session to \
+                         this machine has no allow rule, and keep such sessions through the \
+                         panic button",
+                    )
+                    .changed();
+                if changed {
+                    match self.safety.save() {
+                        Ok(()) => self.notices.info("Safety settings saved."),
+                        Err(err) => self
+                            .notices
+                            .error(format!("Safety settings not saved: {err}")),
+                    }
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Check remote sessions").clicked() {
+                        match safety::remote_sessions() {
+                            Ok(sessions) => self.remote_sessions = Some(sessions),
+                            Err(err) => self
+                                .notices
+                                .error(format!("Remote sessions not listed: {err}")),
+                        }
+                    }
+                    match &self.remote_sessions {
+                        Some(sessions) if sessions.is_empty() => {
+                            ui.label("No RDP or SSH session is connected.");
+                        }
+                        Some(sessions) => {
+                            for session in sessions {
+                                let allowed = self
+                                    .filters
+                                    .iter()
+                                    .any(|f| f.permits_remote(session.remote));
+                                let text = format!("{} from {}", session.protocol, session.remote);
+                                if allowed {
+                                    ui.colored_label(egui::Color32::LIGHT_GREEN, text);
+                                } else {
+                                    ui.colored_label(egui::Color32::YELLOW, text)
+                                        .on_hover_text("No allow rule covers this address");
+                                }
+                            }
+                        }
+                        None => {}
+                    }
+                });
+            });
+    }
+
+    fn render_naming_policy(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Naming policy")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label(
+                    "Rule names must follow this policy to be added or imported. \
+                     Leave a field empty to allow anything.",
+                );
+                egui::Grid::new("naming_policy_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Prefix:");
+                        ui.text_edit_singleline(&mut self.naming_policy.prefix);
+                        ui.end_row();
+                        ui.label("Maximum length:");
+                        ui.text_edit_singleline(&mut self.naming_max_length);
+                        ui.end_row();
+                        ui.label("Forbidden characters:");
+                        ui.text_edit_singleline(&mut self.naming_policy.forbidden);
+                        ui.end_row();
+                    });
+                let renames = self.naming_policy.renames(&self.filters);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        let result = match self.naming_max_length.trim() {
+                            "" => Ok(None),
+                            max => max
+                                .parse()
+                                .map(Some)
+                                .map_err(|_| anyhow!("Maximum length '{max}' is not a number")),
+                        }
+                        .and_then(|max| {
+                            self.naming_policy.max_length = max;
+                            self.naming_policy.save()
+                        });
+                        match result {
+                            Ok(()) => self.notices.info("Naming policy saved."),
+                            Err(err) => self
+                                .notices
+                                .error(format!("Naming policy not saved: {err}")),
+                        }
+                    }
+                    if ui
+                        .add_enabled(
+                            !renames.is_empty(),
+                            egui::Button::new(format!("Rename {} filter(s)", renames.len())),
+                        )
+                        .on_hover_text("Rename our filters whose names break the policy")
+                        .clicked()
+                    {
+                        match self
+                            .hosts
+                            .open()
+                            .and_then(|eng| naming::migrate(&eng, &renames))
+                        {
+                            Ok(renamed) => {
+                                self.refresh.request();
+                                self.notices.info(format!("Renamed {renamed} filter(s)."))
+                            }
+                            Err(err) => self.notices.error(format!("Rename failed: {err}")),
+                        }
+                    }
+                });
+                for (filter, name) in renames.iter().take(20) {
+                    ui.label(format!("{} → {name}", filter.name));
+                }
+                if renames.len() > 20 {
+                    ui.label(format!("…and {} more", renames.len() - 20));
+                }
+            });
+    }
+
+    fn render_quic_block(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Block QUIC")
+            .default_open(false)
+            .show(ui, |ui| {
+                let members = QuicBlock::members(&self.filters);
+                let on = !members.is_empty();
+                if on {
+                    ui.colored_label(
+                        egui::Color32::LIGHT_GREEN,
+                        format!(
+                            "On: outbound UDP 443 is blocked, so browsers use TCP and TLS \
+                             ({} filters)",
+                            members.len()
+                        ),
+                    );
+                } else {
+                    ui.label("Off: QUIC (HTTP/3 over UDP 443) is allowed");
+                }
+                ui.add_enabled_ui(!wfp::is_read_only(), |ui| {
+                    ui.label("Only for these applications, one path per line; empty for all:");
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.quic_apps)
+                            .desired_rows(2)
+                            .desired_width(400.0)
+                            .hint_text(r"C:\Program Files\Google\Chrome\Application\chrome.exe"),
+                    );
+                    ui.horizontal(|ui| {
+                        let label = if on { "Apply changes" } else { "Block QUIC" };
+                        if ui.button(label).clicked() {
+                            self.enable_quic_block();
+                        }
+                        if on && ui.button("Allow QUIC").clicked() {
+                            match self
+                                .hosts
+                                .open()
+                                .and_then(|engine| QuicBlock::disable(&engine))
+                            {
+                                Ok(()) => {
+                                    self.refresh.request();
+                                    self.notices.info("QUIC allowed again.")
+                                }
+                                Err(err) => {
+                                    self.notices.error(format!("QUIC block not removed: {err}"))
+                                }
+                            }
+                        }
+                    });
+                });
+            });
+    }
+
+    fn enable_quic_block(&mut self) {
+        self.quic_block.apps = self
+            .quic_apps
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        let result = self.hosts.open().and_then(|engine| {
+            let added = self.quic_block.enable(&engine)?;
+            self.quic_block.save()?;
+            Ok(added)
+        });
+        match result {
+            Ok(added) => {
+                self.refresh.request();
+                self.notices
+                    .info(format!("QUIC blocked, {added} filters installed."))
+            }
+            Err(err) => self.notices.error(format!("QUIC not blocked: {err}")),
+        }
+    }
+
+    fn render_flow_monitor(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Connection monitoring")
+            .default_open(false)
+            .show(ui, |ui| {
+                let members = FlowMonitor::members(&self.filters);
+                let on = !members.is_empty();
+                if on {
+                    ui.colored_label(
+                        egui::Color32::LIGHT_GREEN,
+                        format!(
+                            "On: established connections are recorded as allow events \
+                             ({} filters)",
+                            members.len()
+                        ),
+                    );
+                } else {
+                    ui.label("Off: only dropped connections are recorded");
+                }
+                ui.label(
+                    "The filters sit at the flow-established layers, which only see \
+                     connections already allowed, so they never block anything.",
+                );
+                ui.add_enabled_ui(!wfp::is_read_only(), |ui| {
+                    ui.label("Only for these applications, one path per line; empty for all:");
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.flow_monitor_apps)
+                            .desired_rows(2)
+                            .desired_width(400.0)
+                            .hint_text(r"C:\Program Files\Vendor\agent.exe"),
+                    );
+                    ui.horizontal(|ui| {
+                        let label = if on {
+                            "Apply changes"
+                        } else {
+                            "Monitor connections"
+                        };
+                        if ui.button(label).clicked() {
+                            self.enable_flow_monitor();
+                        }
+                        if on && ui.button("Stop monitoring").clicked() {
+                            match self
+                                .hosts
+                                .open()
+                                .and_then(|engine| FlowMonitor::disable(&engine))
+                            {
+                                Ok(()) => {
+                                    self.refresh.request();
+                                    self.notices.info("Connection monitoring stopped.")
+                                }
+                                Err(err) => self
+                                    .notices
+                                    .error(format!("Monitoring filters not removed: {err}")),
+                            }
+                        }
+                    });
+                });
+            });
+    }
+
+    fn enable_flow_monitor(&mut self) {
+        self.flow_monitor.apps = self
+            .flow_monitor_apps
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        let result = self.hosts.open().and_then(|engine| {
+            let added = self.flow_monitor.enable(&engine)?;
+            self.flow_monitor.save()?;
+            Ok(added)
+        });
+        match result {
+            Ok(added) => {
+                self.refresh.request();
+                self.notices
+                    .info(format!("Connections monitored, {added} filters installed."))
+            }
+            Err(err) => self
+                .notices
+                .error(format!("Connection monitoring not started: {err}")),
+        }
+    }
+
+    /// Adapters that block all traffic unless a rule allows it, e.g. the
+    /// cellular one while Ethernet stays open.
+    fn render_interface_deny(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Default-deny by interface")
+            .default_open(false)
+            .show(ui, |ui| {
+                let members = InterfaceDeny::members(&self.filters);
+                let on = !members.is_empty();
+                if on {
+                    let names: Vec<&str> = self
+                        .interface_deny
+                        .interfaces
+                        .iter()
+                        .map(|i| i.name.as_str())
+                        .collect();
+                    ui.colored_label(
+                        egui::Color32::LIGHT_GREEN,
+                        format!(
+                            "On: traffic on {} is blocked unless a rule allows it ({} filters)",
+                            names.join(", "),
+                            members.len()
+                        ),
+                    );
+                } else {
+                    ui.label("Off: every adapter allows traffic by default");
+                }
+                if ui.button("Refresh adapters").clicked() {
+                    match interface_deny::interfaces() {
+                        Ok(adapters) => self.network_interfaces = adapters,
+                        Err(err) => self.notices.error(format!("Adapters not listed: {err}")),
+                    }
+                }
+                ui.add_enabled_ui(!wfp::is_read_only(), |ui| {
+                    for adapter in &self.network_interfaces {
+                        let mut denied = self.interface_deny.is_denied(adapter.luid);
+                        let label = format!(
+                            "{} ({}, {})",
+                            adapter.name,
+                            adapter.media.map_or("other", InterfaceMedia::label),
+                            if adapter.up { "up" } else { "down" }
+                        );
+                        if ui
+                            .checkbox(&mut denied, label)
+                            .on_hover_text(&adapter.description)
+                            .changed()
+                        {
+                            if denied {
+                                self.interface_deny.interfaces.push(DeniedInterface {
+                                    luid: adapter.luid,
+                                    name: adapter.name.clone(),
+                                });
+                            } else {
+                                self.interface_deny
+                                    .interfaces
+                                    .retain(|i| i.luid != adapter.luid);
+                            }
+                        }
+                    }
+                    for missing in self
+                        .interface_deny
+                        .interfaces
+                        .iter()
+                        .filter(|i| !self.network_interfaces.iter().any(|a| a.luid == i.luid))
+                    {
+                        ui.label(format!("{} (not present, still denied)", missing.name));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Apply").clicked() {
+                            self.apply_interface_deny();
+                        }
+                        if on && ui.button("Allow on every adapter").clicked() {
+                            match self
+                                .hosts
+                                .open()
+                                .and_then(|engine| InterfaceDeny::disable(&engine))
+                            {
+                                Ok(()) => {
+                                    self.refresh.request();
+                                    self.notices
+                                        .info("Every adapter allows traffic by default again.")
+                                }
+                                Err(err) => self
+                                    .notices
+                                    .error(format!("Default-deny not removed: {err}")),
+                            }
+                        }
+                    });
+                });
+            });
+    }
+
+    fn apply_interface_deny(&mut self) {
+        let result = self.hosts.open().and_then(|engine| {
+            let added = if self.interface_deny.interfaces.is_empty() {
+                if !InterfaceDeny::members(&self.filters).is_empty() {
+                    InterfaceDeny::disable(&engine)?;
+                }
+                0
+            } else {
+                self.safety
+                    .check_lockout(&engine, &self.interface_deny.builders()?)?;
+                self.interface_deny.enable(&engine)?
+            };
+            self.interface_deny.save()?;
+            Ok(added)
+        });
+        match result {
+            Ok(0) => {
+                self.refresh.request();
+                self.notices.info("No adapter denies traffic by default.")
+            }
+            Ok(added) => {
+                self.refresh.request();
+                self.notices
+                    .info(format!("Default-deny applied, {added} filters installed."))
+            }
+            Err(err) => self
+                .notices
+                .error(format!("Default-deny not applied: {err}")),
+        }
+    }
+
+    /// Our callouts, whether their driver has loaded and how many filters
+    /// use each, with a form to register another.
+    fn render_callouts(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Callouts for a companion driver")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label(
+                    "Callouts added here stay until deleted. A driver built with the same \
+                     key registers its classify functions against one when it loads.",
+                );
+                let layer_name = |key: GUID| {
+                    self.layers
+                        .iter()
+                        .find(|l| l.key == key)
+                        .map_or_else(|| format_guid(key), |l| l.name.clone())
+                };
+                let mut delete = None;
+                egui::Grid::new("callouts_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Name");
+                        ui.label("Key");
+                        ui.label("Layer");
+                        ui.label("Driver");
+                        ui.label("Filters");
+                        ui.label("");
+                        ui.end_row();
+                        for callout in self.callout_details.iter().filter(|c| c.ours) {
+                            ui.label(&callout.name)
+                                .on_hover_text(callout.description.as_deref().unwrap_or(""));
+                            ui.monospace(format_guid(callout.key));
+                            ui.label(layer_name(callout.layer_key));
+                            ui.label(if callout.registered {
+                                "Loaded"
+                            } else {
+                                "Not loaded"
+                            });
+                            ui.label(
+                                self.filters
+                                    .iter()
+                                    .filter(|f| f.callout.is_some_and(|c| c.key == callout.key))
+                                    .count()
+                                    .to_string(),
+                            );
+                            if ui
+                                .add_enabled(!wfp::is_read_only(), egui::Button::new("Delete"))
+                                .clicked()
+                            {
+                                delete = Some(callout.key);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.add_enabled_ui(!wfp::is_read_only(), |ui| {
+                    egui::Grid::new("callout_form").show(ui, |ui| {
+                        ui.label("Key:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.new_callout_key)
+                                .desired_width(280.0)
+                                .hint_text("GUID the driver is built with"),
+                        );
+                        ui.end_row();
+                        ui.label("Name:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.new_callout_name)
+                                .desired_width(280.0),
+                        );
+                        ui.end_row();
+                        ui.label("Description:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.new_callout_description)
+                                .desired_width(280.0),
+                        );
+                        ui.end_row();
+                        ui.label("Layer:");
+                        egui::ComboBox::from_id_source("new_callout_layer")
+                            .selected_text(
+                                self.new_callout_layer
+                                    .map_or_else(|| "Choose a layer".to_string(), layer_name),
+                            )
+                            .show_ui(ui, |ui| {
+                                for layer in &self.layers {
+                                    ui.selectable_value(
+                                        &mut self.new_callout_layer,
+                                        Some(layer.key),
+                                        &layer.name,
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                    });
+                    if ui.button("Add callout").clicked() {
+                        let result = self
+                            .new_callout_key
+                            .trim()
+                            .trim_matches(|c| c == '{' || c == '}')
+                            .parse::<Uuid>()
+                            .map_err(|_| anyhow!("The key must be a GUID"))
+                            .and_then(|key| {
+                                let layer = self
+                                    .new_callout_layer
+                                    .ok_or_else(|| anyhow!("Choose the layer it applies at"))?;
+                                self.hosts.open()?.register_callout(
+                                    wfp::guid_from_uuid(key),
+                                    self.new_callout_name.trim(),
+                                    self.new_callout_description.trim(),
+                                    layer,
+                                )
+                            });
+                        match result {
+                            Ok(()) => {
+                                self.new_callout_key.clear();
+                                self.new_callout_name.clear();
+                                self.new_callout_description.clear();
+                                self.refresh.request();
+                                self.notices.info("Callout added.")
+                            }
+                            Err(err) => self.notices.error(format!("Callout not added: {err}")),
+                        }
+                    }
+                });
+                if let Some(key) = delete {
+                    match self
+                        .hosts
+                        .open()
+                        .and_then(|eng| eng.unregister_callout(key))
+                    {
+                        Ok(()) => {
+                            self.refresh.request();
+                            self.notices.info("Callout deleted.")
+                        }
+                        Err(err) => self.notices.error(format!("Callout not deleted: {err}")),
+                    }
+                }
+            });
+    }
+
+    /// Our sublayers. Rules in a higher-weight sublayer are arbitrated
+    /// first, and permits there are hard permits that blocks below cannot
+    /// override.
+    fn render_sublayers(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Rule groups (sublayers)")
+            .default_open(false)
+            .show(ui, |ui| {
+                let mut delete = None;
+                let mut cascade = false;
+                egui::Grid::new("sublayers_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Name");
+                        ui.label("Weight");
+                        ui.label("Filters");
+                        ui.label("");
+                        ui.end_row();
+                        let mut ours: Vec<&SublayerInfo> =
+                            self.sublayer_details.iter().filter(|s| s.ours).collect();
+                        ours.sort_by_key(|s| std::cmp::Reverse(s.weight));
+                        for sublayer in ours {
+                            ui.label(&sublayer.name);
+                            ui.label(sublayer.weight.to_string());
+                            ui.label(
+                                self.filters
+                                    .iter()
+                                    .filter(|f| f.sublayer_key == sublayer.key)
+                                    .count()
+                                    .to_string(),
+                            );
+                            if ui
+                                .add_enabled(
+                                    sublayer.key != wfp::SUBLAYER_KEY && !wfp::is_read_only(),
+                                    egui::Button::new("Delete"),
+                                )
+                                .clicked()
+                            {
+                                let used =
+                                    self.filters.iter().any(|f| f.sublayer_key == sublayer.key);
+                                if used && self.safety.confirms() {
+                                    self.sublayer_delete = Some(sublayer.key);
+                                } else {
+                                    delete = Some(sublayer.key);
+                                    cascade = used;
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(key) = self.sublayer_delete {
+                    let users: Vec<&FilterSummary> = self
+                        .filters
+                        .iter()
+                        .filter(|f| f.sublayer_key == key)
+                        .collect();
+                    let name = self
+                        .sublayer_details
+                        .iter()
+                        .find(|s| s.key == key)
+                        .map_or("this sublayer", |s| s.name.as_str());
+                    ui.group(|ui| {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            format!("{} filter(s) are in '{name}':", users.len()),
+                        );
+                        egui::ScrollArea::vertical()
+                            .max_height(120.0)
+                            .show(ui, |ui| {
+                                for filter in &users {
+                                    ui.label(format!("{} {}", filter.id, filter.name));
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button(format!("Delete sublayer and {} filter(s)", users.len()))
+                                .clicked()
+                            {
+                                delete = Some(key);
+                                cascade = true;
+                            }
+                            if ui.button("Cancel").clicked() {
+                                self.sublayer_delete = None;
+                            }
+                        });
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.set_enabled(!wfp::is_read_only());
+                    ui.label("Name:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_sublayer_name)
+                            .desired_width(140.0)
+                            .hint_text("e.g. Allowlist"),
+                    );
+                    ui.label("Weight:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_sublayer_weight)
+                            .desired_width(60.0)
+                            .hint_text("0-65535"),
+                    );
+                    if ui.button("Add sublayer").clicked() {
+                        let result = self
+                            .new_sublayer_weight
+                            .trim()
+                            .parse::<u16>()
+                            .map_err(|_| anyhow!("Weight must be a number from 0 to 65535"))
+                            .and_then(|weight| {
+                                self.hosts
+                                    .open()?
+                                    .create_sublayer(self.new_sublayer_name.trim(), weight)
+                            });
+                        match result {
+                            Ok(_) => {
+                                self.new_sublayer_name.clear();
+                                self.refresh.request();
+                                self.notices.info("Sublayer added.")
+                            }
+                            Err(err) => self.notices.error(format!("Sublayer not added: {err}")),
+                        }
+                    }
+                });
+                if let Some(key) = delete {
+                    self.sublayer_delete = None;
+                    match self
+                        .hosts
+                        .open()
+                        .and_then(|eng| eng.delete_sublayer(key, cascade))
+                    {
+                        Ok(()) => {
+                            if self.add_sublayer == Some(key) {
+                                self.add_sublayer = None;
+                            }
+                            self.refresh.request();
+                            self.notices.info("Sublayer deleted.")
+                        }
+                        Err(err) => self.notices.error(format!("Sublayer not deleted: {err}")),
+                    }
+                }
+            });
+    }
+
+    fn render_export_import(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Export / Import Owned Rules")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Export to JSON").clicked() {
+                        match self.hosts.open().and_then(|eng| eng.export_owned_filters()) {
+                            Ok(json) => {
+                                self.export_text = json;
+                                self.notices.info("Exported owned filters.");
+                            }
+                            Err(err) => self.notices.error(format!("Export failed: {err}")),
+                        }
+                    }
+                    if let Some(job) = &self.import_job {
+                        ui.spinner();
+                        ui.label("Importing…");
+                        if ui
+                            .add_enabled(!job.cancel.is_cancelled(), egui::Button::new("Cancel"))
+                            .clicked()
+                        {
+                            job.cancel.cancel();
+                        }
+                    } else if ui
+                        .add_enabled(!wfp::is_read_only(), egui::Button::new("Import"))
+                        .clicked()
+                    {
+                        let path = Some(Path::new(&self.import_path))
+                            .filter(|_| !self.import_path.trim().is_empty());
+                        let format = RuleFormat::detect(path, &self.export_text);
+                        match config::parse_rule_set(&self.export_text, format).and_then(|set| {
+                            self.safety
+                                .check_lockout_configs(&self.hosts.open()?, &set.filters)?;
+                            self.protect_rdp_peer(&set.filters)?;
+                            Ok(set)
+                        }) {
+                            Ok(set) => {
+                                let host = self.hosts.active().name.clone();
+                                self.import_job = Some(ImportJob::start(host, set));
+                            }
+                            Err(err) => {
+                                self.notices.error(format!("Import rejected: {err}"));
+                            }
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Rule file:");
+                    ui.text_edit_singleline(&mut self.import_path);
+                    if ui.button("Load file").clicked() {
+                        let path = Path::new(self.import_path.trim());
+                        match std::fs::read_to_string(path) {
+                            Ok(text) => {
+                                let format = RuleFormat::detect(Some(path), &text);
+                                self.export_text = text;
+                                self.notices.info(format!("Loaded {} ({})", path.display(), format.as_str()))
+                            }
+                            Err(err) => self.notices.error(format!("Failed to read {}: {err}", path.display())),
+                        }
+                    }
+                });
                 ui.add(
                     egui::TextEdit::multiline(&mut self.export_text)
                         .desired_rows(6)
-                        .hint_text("JSON export area"),
+                        .hint_text("JSON export area (YAML, TOML and simplewall/TinyWall XML are also accepted)"),
+                );
+                self.render_import_preview(ui);
+            });
+    }
+
+    /// Filters from `netsh wfp show filters` output collected elsewhere,
+    /// compared with this machine's and adopted one by one.
+    fn render_netsh_import(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Filters from netsh output").show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut self.netsh_text)
+                    .desired_rows(4)
+                    .hint_text("Paste the filters.xml `netsh wfp show filters` writes"),
+            );
+            if ui.button("Compare with this machine").clicked() {
+                self.netsh_filters =
+                    Some(importers::netsh_filters(&self.netsh_text).map_err(|e| e.to_string()));
+            }
+            let filters = match &self.netsh_filters {
+                Some(Ok(filters)) => filters,
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::RED, err);
+                    return;
+                }
+                None => return,
+            };
+            let presence: Vec<FilterPresence> = filters
+                .iter()
+                .map(|f| FilterPresence::of(f, &self.filters))
+                .collect();
+            let missing: Vec<&FilterSummary> = filters
+                .iter()
+                .zip(&presence)
+                .filter(|(_, p)| **p == FilterPresence::Missing)
+                .map(|(f, _)| f)
+                .collect();
+            let mut adopt = Vec::new();
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} filter(s), {} missing here",
+                    filters.len(),
+                    missing.len()
+                ));
+                if !missing.is_empty() && ui.button("Adopt all missing").clicked() {
+                    adopt.extend(missing.iter().copied());
+                }
+            });
+            egui::ScrollArea::vertical()
+                .id_source("netsh_filters")
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("netsh_grid").striped(true).show(ui, |ui| {
+                        for heading in ["", "Layer", "Action", "Name", "Provider", ""] {
+                            ui.heading(heading);
+                        }
+                        ui.end_row();
+                        for (filter, presence) in filters.iter().zip(&presence) {
+                            ui.label(presence.as_str());
+                            ui.label(&filter.layer);
+                            ui.label(filter.action.as_str());
+                            let conditions: Vec<String> =
+                                filter.conditions.iter().map(ToString::to_string).collect();
+                            ui.label(&filter.name).on_hover_text(conditions.join("\n"));
+                            ui.label(&filter.provider);
+                            if *presence != FilterPresence::Same && ui.button("Adopt").clicked() {
+                                adopt.push(filter);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            if adopt.is_empty() {
+                return;
+            }
+            let result = self.hosts.open().and_then(|eng| {
+                for filter in &adopt {
+                    eng.add_filter(&importers::adopt_filter(filter)?)?;
+                }
+                Ok(())
+            });
+            match result {
+                Ok(()) => self
+                    .notices
+                    .info(format!("Adopted {} filter(s).", adopt.len())),
+                Err(e) => self.notices.error(format!("Adopt failed: {e}")),
+            }
+            self.refresh.request();
+        });
+    }
+
+    /// Saves the active host's state for analysis elsewhere, or shows a
+    /// state saved earlier in place of this machine's.
+    fn render_snapshot_file(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Snapshot file").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                ui.text_edit_singleline(&mut self.snapshot_path);
+            });
+            let path = PathBuf::from(self.snapshot_path.trim());
+            let has_path = !self.snapshot_path.trim().is_empty();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        has_path && self.offline.is_none(),
+                        egui::Button::new("Save snapshot"),
+                    )
+                    .clicked()
+                {
+                    let server = self.hosts.active().name.clone();
+                    let saved = self
+                        .hosts
+                        .open()
+                        .and_then(|eng| SavedSnapshot::take(&eng, server.as_deref()))
+                        .and_then(|saved| saved.save(&path).map(|()| saved));
+                    match saved {
+                        Ok(saved) => self.notices.info(format!(
+                            "Saved {} filters from {} to {}",
+                            saved.snapshot.filters.len(),
+                            saved.machine,
+                            path.display()
+                        )),
+                        Err(err) => self.notices.error(format!("Snapshot not saved: {err}")),
+                    }
+                }
+                if ui
+                    .add_enabled(has_path, egui::Button::new("Open offline"))
+                    .on_hover_text("Changes stay disabled until the app restarts")
+                    .clicked()
+                {
+                    match SavedSnapshot::load(&path) {
+                        Ok(saved) => {
+                            wfp::set_read_only();
+                            self.notices.info(format!(
+                                "Showing {} filters saved on {}",
+                                saved.snapshot.filters.len(),
+                                saved.label()
+                            ));
+                            self.apply_snapshot(saved.snapshot.clone());
+                            self.sublayer_details = saved.sublayer_details.clone();
+                            self.offline = Some(saved);
+                        }
+                        Err(err) => self.notices.error(format!("Snapshot not opened: {err}")),
+                    }
+                }
+                if self.offline.is_some() && ui.button("Back to live").clicked() {
+                    self.offline = None;
+                    self.refresh.request();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Compare with:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.snapshot_compare_path)
+                        .hint_text("a later snapshot, or empty for this host now"),
+                );
+                if ui
+                    .add_enabled(has_path, egui::Button::new("Compare snapshots"))
+                    .clicked()
+                {
+                    self.snapshot_diff =
+                        Some(self.compare_snapshots(&path).map_err(|e| e.to_string()));
+                }
+            });
+            match &self.snapshot_diff {
+                Some(Ok((label, diff))) => render_snapshot_diff(ui, label, diff),
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::RED, err);
+                }
+                None => {}
+            }
+        });
+    }
+
+    /// A baseline taken before installing software, compared afterwards,
+    /// with the filters the software added to adopt, export or delete.
+    fn render_installer_watch(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Installer watch").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Take baseline").clicked() {
+                    let server = self.hosts.active().name.clone();
+                    let started = self
+                        .hosts
+                        .open()
+                        .and_then(|eng| InstallerWatch::start(&eng, server.as_deref()));
+                    match started {
+                        Ok(watch) => {
+                            self.notices.info(format!(
+                                "Took a baseline of {} filters; install the software, then compare",
+                                watch.baseline.snapshot.filters.len()
+                            ));
+                            self.installer_watch = Some(watch);
+                            self.installer_changes = None;
+                        }
+                        Err(err) => self.notices.error(format!("Baseline not taken: {err}")),
+                    }
+                }
+                if let Some(watch) = &self.installer_watch {
+                    ui.label(format!("Baseline: {}", watch.baseline.label()));
+                    if ui.button("Compare now").clicked() {
+                        match self.hosts.open().and_then(|eng| watch.finish(&eng)) {
+                            Ok(changes) => {
+                                self.notices.info(changes.summary());
+                                self.installer_changes = Some(changes);
+                                self.installer_confirm_delete = false;
+                            }
+                            Err(err) => self.notices.error(format!("Comparison failed: {err}")),
+                        }
+                    }
+                }
+            });
+            let Some(changes) = &self.installer_changes else {
+                return;
+            };
+            render_snapshot_diff(ui, "Since the baseline", &changes.diff);
+            ui.separator();
+            ui.label(changes.summary());
+            if changes.added.is_empty() {
+                return;
+            }
+            let mut adopt = Vec::new();
+            let mut delete = false;
+            ui.horizontal(|ui| {
+                ui.label("Export to:");
+                ui.text_edit_singleline(&mut self.installer_export_path);
+                let path = Path::new(self.installer_export_path.trim());
+                if ui
+                    .add_enabled(
+                        !self.installer_export_path.trim().is_empty(),
+                        egui::Button::new("Export"),
+                    )
+                    .clicked()
+                {
+                    match changes.export(path) {
+                        Ok(()) => self.notices.info(format!(
+                            "Exported {} filter(s) to {}",
+                            changes.added.len(),
+                            path.display()
+                        )),
+                        Err(err) => self.notices.error(format!("Export failed: {err}")),
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.set_enabled(!wfp::is_read_only());
+                if ui
+                    .button("Adopt all")
+                    .on_hover_text(
+                        "Add copies as our own rules, which stay when the software is removed",
+                    )
+                    .clicked()
+                {
+                    adopt.extend(changes.added.iter().cloned());
+                }
+                if self.installer_confirm_delete {
+                    let label = format!("Really delete {} filter(s)", changes.added.len());
+                    if ui
+                        .button(egui::RichText::new(label).color(egui::Color32::RED))
+                        .clicked()
+                    {
+                        delete = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.installer_confirm_delete = false;
+                    }
+                } else if ui
+                    .button("Delete all")
+                    .on_hover_text("The software that added them may stop working")
+                    .clicked()
+                {
+                    if self.safety.confirms() {
+                        self.installer_confirm_delete = true;
+                    } else {
+                        delete = true;
+                    }
+                }
+            });
+            egui::Grid::new("installer_added")
+                .striped(true)
+                .show(ui, |ui| {
+                    for heading in ["Layer", "Action", "Name", "Provider", ""] {
+                        ui.heading(heading);
+                    }
+                    ui.end_row();
+                    for filter in &changes.added {
+                        ui.label(&filter.layer);
+                        ui.label(filter.action.as_str());
+                        let conditions: Vec<String> =
+                            filter.conditions.iter().map(ToString::to_string).collect();
+                        ui.label(&filter.name).on_hover_text(conditions.join("\n"));
+                        ui.label(&filter.provider);
+                        if ui
+                            .add_enabled(!wfp::is_read_only(), egui::Button::new("Adopt"))
+                            .clicked()
+                        {
+                            adopt.push(filter.clone());
+                        }
+                        ui.end_row();
+                    }
+                });
+            if !adopt.is_empty() {
+                let result = self
+                    .hosts
+                    .open()
+                    .and_then(|eng| installer_watch::adopt(&eng, &adopt));
+                match result {
+                    Ok(adopted) => self.notices.info(format!("Adopted {adopted} filter(s).")),
+                    Err(err) => self.notices.error(format!("Adopt failed: {err}")),
+                }
+                self.refresh.request();
+            }
+            if delete {
+                let result = self
+                    .hosts
+                    .open()
+                    .and_then(|eng| installer_watch::delete(&eng, &changes.added));
+                match result {
+                    Ok(deleted) => {
+                        self.installer_changes = None;
+                        self.notices.info(format!("Deleted {deleted} filter(s)."))
+                    }
+                    Err(err) => self.notices.error(format!("Delete failed: {err}")),
+                }
+                self.installer_confirm_delete = false;
+                self.refresh.request();
+            }
+        });
+    }
+
+    /// The differences from the snapshot at `path` to the one to compare
+    /// with, labelled with when and where both were taken.
+    fn compare_snapshots(&self, path: &Path) -> Result<(String, SnapshotDiff)> {
+        let before = SavedSnapshot::load(path)?;
+        let after = match self.snapshot_compare_path.trim() {
+            "" => {
+                let server = self.hosts.active().name.as_deref();
+                SavedSnapshot::take(&self.hosts.open()?, server)?
+            }
+            other => SavedSnapshot::load(Path::new(other))?,
+        };
+        let diff = SnapshotDiff::between(&before.snapshot, &after.snapshot);
+        Ok((format!("{} -> {}", before.label(), after.label()), diff))
+    }
+
+    /// Named rule sets in the profiles directory. Loading one puts it in
+    /// the export box, where it is previewed and imported as usual.
+    fn render_profiles(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Profiles").show(ui, |ui| {
+            ui.label(format!("Saved in {}", profiles::profiles_dir().display()));
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut self.new_profile_name);
+                if ui.button("Save current rules as profile").clicked() {
+                    let created = self
+                        .hosts
+                        .open()
+                        .and_then(|eng| eng.export_owned_filters())
+                        .and_then(|json| config::parse_rule_set(&json, RuleFormat::Json))
+                        .and_then(|set| profiles::create(&self.new_profile_name, &set));
+                    match created {
+                        Ok(path) => {
+                            self.new_profile_name.clear();
+                            self.notices
+                                .info(format!("Saved profile to {}", path.display()))
+                        }
+                        Err(err) => self.notices.error(format!("Profile not saved: {err}")),
+                    }
+                    self.profiles = profiles::list().unwrap_or_default();
+                }
+                if ui.button("Reload list").clicked() {
+                    self.profiles = profiles::list().unwrap_or_default();
+                }
+            });
+            let mut changed = false;
+            for profile in &self.profiles {
+                ui.horizontal(|ui| {
+                    match &mut self.profile_rename {
+                        Some((old, new)) if *old == profile.name => {
+                            ui.text_edit_singleline(new);
+                            if ui.button("Save").clicked() {
+                                match profiles::rename(old, new) {
+                                    Ok(_) => self.notices.info(format!(
+                                        "Renamed profile '{old}' to '{}'.",
+                                        new.trim()
+                                    )),
+                                    Err(err) => self.notices.error(format!("Rename failed: {err}")),
+                                }
+                                self.profile_rename = None;
+                                changed = true;
+                            } else if ui.button("Cancel").clicked() {
+                                self.profile_rename = None;
+                            }
+                            return;
+                        }
+                        _ => {}
+                    }
+                    ui.label(&profile.name);
+                    match profile.rules {
+                        Some(count) => ui.label(format!("{count} rule(s)")),
+                        None => ui.colored_label(egui::Color32::RED, "invalid"),
+                    };
+                    if ui.button("Load").clicked() {
+                        match std::fs::read_to_string(&profile.path) {
+                            Ok(text) => {
+                                self.export_text = text;
+                                self.import_path = profile.path.display().to_string();
+                                self.notices.info(format!(
+                                    "Loaded profile '{}' into the Export / Import box.",
+                                    profile.name
+                                ))
+                            }
+                            Err(err) => self
+                                .notices
+                                .error(format!("Failed to read {}: {err}", profile.path.display())),
+                        }
+                    }
+                    if ui.button("Rename").clicked() {
+                        self.profile_rename = Some((profile.name.clone(), profile.name.clone()));
+                    }
+                    if ui.button("Delete").clicked() {
+                        match profiles::delete(&profile.name) {
+                            Ok(()) => self
+                                .notices
+                                .info(format!("Deleted profile '{}'.", profile.name)),
+                            Err(err) => self.notices.error(format!("Delete failed: {err}")),
+                        }
+                        changed = true;
+                    }
+                });
+            }
+            if changed {
+                self.profiles = profiles::list().unwrap_or_default();
+            }
+        });
+    }
+
+    /// What the text in the export box would import and where it was
+    /// exported from, parsed again only when the text changes.
+    fn render_import_preview(&mut self, ui: &mut egui::Ui) {
+        if self.export_text.trim().is_empty() {
+            return;
+        }
+        if self
+            .import_preview
+            .as_ref()
+            .is_none_or(|(text, _)| *text != self.export_text)
+        {
+            let path =
+                Some(Path::new(&self.import_path)).filter(|_| !self.import_path.trim().is_empty());
+            let format = RuleFormat::detect(path, &self.export_text);
+            let preview = config::parse_rule_set(&self.export_text, format)
+                .map(|set| set.describe())
+                .map_err(|err| err.to_string());
+            self.import_preview = Some((self.export_text.clone(), preview));
+        }
+        match &self.import_preview {
+            Some((_, Ok(preview))) => {
+                ui.label(preview);
+            }
+            Some((_, Err(err))) => {
+                ui.colored_label(egui::Color32::RED, format!("Cannot import: {err}"));
+            }
+            None => {}
+        }
+    }
+
+    fn render_filters(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Current WFP Filters (subset of fields):");
+            ui.separator();
+            for view in [
+                FilterView::Table,
+                FilterView::LayerTree,
+                FilterView::ByProvider,
+                FilterView::ForApp,
+                FilterView::ForHost,
+                FilterView::BootTime,
+            ] {
+                ui.selectable_value(&mut self.filter_view, view, view.label());
+            }
+            ui.separator();
+            if ui
+                .button("Reset hit counters")
+                .on_hover_text("Start counting every filter's hits afresh")
+                .clicked()
+            {
+                match HitCounters::load().and_then(|mut counters| {
+                    counters.reset_all();
+                    counters.save()?;
+                    Ok(counters)
+                }) {
+                    Ok(counters) => {
+                        self.hit_counters = counters;
+                        self.notices.info("Hit counters reset.")
+                    }
+                    Err(err) => self.notices.error(format!("Hit counters not reset: {err}")),
+                }
+            }
+        });
+        match self.filter_view {
+            FilterView::Table => self.render_filter_table(ui),
+            FilterView::LayerTree => self.render_layer_tree(ui),
+            FilterView::ByProvider => self.render_provider_groups(ui),
+            FilterView::ForApp => self.render_app_policy(ui),
+            FilterView::ForHost => self.render_host_policy(ui),
+            FilterView::BootTime => self.render_boot_time_filters(ui),
+        }
+    }
+
+    /// Every filter that could match one executable's traffic, in
+    /// arbitration order: what the system currently does to the app.
+    fn render_app_policy(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Executable:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.app_policy_path)
+                    .desired_width(360.0)
+                    .hint_text(r"e.g. C:\Program Files\Vendor\app.exe"),
+            );
+            if ui.button("Show").clicked() {
+                self.build_app_policy();
+            }
+        });
+        let policy = match &self.app_policy {
+            Some(Ok(policy)) => policy,
+            Some(Err(err)) => {
+                ui.colored_label(egui::Color32::RED, err);
+                return;
+            }
+            None => return,
+        };
+        ui.label(policy.summary());
+        let mut row_action = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("app_policy_grid")
+                .striped(true)
+                .min_col_width(60.0)
+                .show(ui, |ui| {
+                    for heading in [
+                        "Layer",
+                        "Sublayer",
+                        "Weight",
+                        "Action",
+                        "Name",
+                        "Provider",
+                        "Applies to",
+                    ] {
+                        ui.heading(heading);
+                    }
+                    ui.end_row();
+                    for entry in &policy.entries {
+                        let filter = &entry.filter;
+                        let text = |text: String| {
+                            if entry.shadowed {
+                                egui::RichText::new(text).weak()
+                            } else {
+                                egui::RichText::new(text)
+                            }
+                        };
+                        ui.label(text(filter.layer.clone()));
+                        ui.label(text(match entry.sublayer_weight {
+                            Some(weight) => format!("{} ({weight})", filter.sublayer),
+                            None => filter.sublayer.clone(),
+                        }));
+                        ui.label(text(
+                            filter
+                                .effective_weight
+                                .map(|w| format!("0x{w:016X}"))
+                                .unwrap_or_else(|| "-".into()),
+                        ));
+                        ui.label(text(filter.action.as_str().into()));
+                        ui.label(text(filter.name.clone()))
+                            .on_hover_text(coexistence::explain_arbitration(
+                                filter,
+                                &self.sublayer_details,
+                            ))
+                            .context_menu(|ui| {
+                                row_action = filter_context_menu(
+                                    ui,
+                                    filter,
+                                    &mut self.edit_state,
+                                    &mut self.delete_state,
+                                );
+                            });
+                        ui.label(text(filter.provider.clone()));
+                        let scope = if entry.shadowed {
+                            format!("{}, never reached", entry.scope.as_str())
+                        } else {
+                            entry.scope.as_str().into()
+                        };
+                        ui.label(text(scope));
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(action) = row_action {
+            self.run_row_action(action);
+        }
+    }
+
+    /// Every filter referring to one remote host's addresses, in
+    /// arbitration order.
+    fn render_host_policy(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Remote host:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.host_policy_query)
+                    .desired_width(240.0)
+                    .hint_text("e.g. 10.0.0.5 or example.com"),
+            );
+            if ui.button("Show").clicked() {
+                self.build_host_policy();
+            }
+        });
+        let policy = match &self.host_policy {
+            Some(Ok(policy)) => policy,
+            Some(Err(err)) => {
+                ui.colored_label(egui::Color32::RED, err);
+                return;
+            }
+            None => return,
+        };
+        ui.label(policy.summary());
+        let mut row_action = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("host_policy_grid")
+                .striped(true)
+                .min_col_width(60.0)
+                .show(ui, |ui| {
+                    for heading in [
+                        "Layer",
+                        "Sublayer",
+                        "Weight",
+                        "Action",
+                        "Name",
+                        "Provider",
+                        "Condition",
+                    ] {
+                        ui.heading(heading);
+                    }
+                    ui.end_row();
+                    for entry in &policy.entries {
+                        let filter = &entry.filter;
+                        ui.label(&filter.layer);
+                        ui.label(match entry.sublayer_weight {
+                            Some(weight) => format!("{} ({weight})", filter.sublayer),
+                            None => filter.sublayer.clone(),
+                        });
+                        ui.label(
+                            filter
+                                .effective_weight
+                                .map(|w| format!("0x{w:016X}"))
+                                .unwrap_or_else(|| "-".into()),
+                        );
+                        ui.label(filter.action.as_str());
+                        ui.label(&filter.name)
+                            .on_hover_text(coexistence::explain_arbitration(
+                                filter,
+                                &self.sublayer_details,
+                            ))
+                            .context_menu(|ui| {
+                                row_action = filter_context_menu(
+                                    ui,
+                                    filter,
+                                    &mut self.edit_state,
+                                    &mut self.delete_state,
+                                );
+                            });
+                        ui.label(&filter.provider);
+                        ui.label(format!("{} ({})", entry.condition, entry.matched.as_str()));
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(action) = row_action {
+            self.run_row_action(action);
+        }
+    }
+
+    fn build_host_policy(&mut self) {
+        let host = self.host_policy_query.trim();
+        self.host_policy = (!host.is_empty()).then(|| {
+            HostPolicy::build(host, &self.filters, &self.sublayer_details)
+                .map_err(|e| format!("Cannot show {host}: {e}"))
+        });
+    }
+
+    fn build_app_policy(&mut self) {
+        let path = self.app_policy_path.trim();
+        self.app_policy = (!path.is_empty()).then(|| {
+            AppPolicy::build(path, &self.filters, &self.sublayer_details)
+                .map_err(|e| format!("Cannot show {path}: {e}"))
+        });
+    }
+
+    /// Read-only list of the boot-time policy: what is enforced from boot
+    /// until BFE starts and loads the run-time filters.
+    fn render_boot_time_filters(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!(
+            "{} boot-time filter(s) active before BFE starts. These are replaced by \
+             persistent and run-time filters once BFE is running.",
+            self.boot_time_filters.len()
+        ));
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("boot_time_grid")
+                .striped(true)
+                .min_col_width(80.0)
+                .show(ui, |ui| {
+                    ui.heading("ID");
+                    ui.heading("Key");
+                    ui.heading("Name");
+                    ui.heading("Provider");
+                    ui.heading("Layer");
+                    ui.heading("Action");
+                    ui.heading("Remote Port");
+                    ui.heading("Persistent");
+                    ui.end_row();
+
+                    for filter in &self.boot_time_filters {
+                        ui.label(filter.id.to_string());
+                        ui.label(format_guid(filter.key));
+                        ui.label(&filter.name)
+                            .on_hover_text(format_conditions(&filter.conditions));
+                        ui.label(&filter.provider);
+                        ui.label(&filter.layer);
+                        ui.label(filter.action.as_str());
+                        ui.label(format_port(filter.remote_port));
+                        ui.label(if filter.persistent { "Yes" } else { "No" });
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    fn render_filter_table(&mut self, ui: &mut egui::Ui) {
+        let mut capture_request = None;
+        let mut row_action = None;
+        // Hits are counted from this machine's net events only.
+        let local = self.hosts.active().name.is_none();
+        self.selected_filters
+            .retain(|id| self.filters.iter().any(|f| f.id == *id));
+        if !self.selected_filters.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} selected", self.selected_filters.len()));
+                if ui.button("Bulk edit…").clicked() {
+                    self.bulk_edit = Some(BulkEditState::default());
+                }
+                if ui.button("Clear selection").clicked() {
+                    self.selected_filters.clear();
+                }
+            });
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("filters_grid")
+                .striped(true)
+                .min_col_width(80.0)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.heading("ID");
+                    ui.heading("Key");
+                    ui.heading("Name");
+                    ui.heading("Provider");
+                    ui.heading("Layer");
+                    ui.heading("Action");
+                    ui.heading("Remote Port");
+                    ui.heading("Owned");
+                    ui.heading("Hits");
+                    ui.heading("Schedule");
+                    ui.heading("Actions");
+                    ui.end_row();
+
+                    for filter in &self.filters {
+                        if filter.owned_by_app {
+                            let mut selected = self.selected_filters.contains(&filter.id);
+                            if ui.checkbox(&mut selected, "").changed() {
+                                if selected {
+                                    self.selected_filters.push(filter.id);
+                                } else {
+                                    self.selected_filters.retain(|id| *id != filter.id);
+                                }
+                            }
+                        } else {
+                            ui.label("");
+                        }
+                        ui.label(filter.id.to_string());
+                        ui.label(format_guid(filter.key));
+                        ui.label(&filter.name)
+                            .on_hover_text(format!(
+                                "{}\n{}",
+                                format_conditions(&filter.conditions),
+                                coexistence::explain_arbitration(filter, &self.sublayer_details)
+                            ))
+                            .context_menu(|ui| {
+                                row_action = filter_context_menu(
+                                    ui,
+                                    filter,
+                                    &mut self.edit_state,
+                                    &mut self.delete_state,
+                                );
+                            });
+                        ui.label(&filter.provider);
+                        ui.label(&filter.layer);
+                        ui.label(filter.action.as_str());
+                        ui.label(format_port(filter.remote_port));
+                        ui.label(if filter.owned_by_app { "Yes" } else { "No" });
+                        if local {
+                            let hits = self.hit_counters.filter(filter.key);
+                            ui.label(hits.hits.to_string()).on_hover_text(format!(
+                                "{}\nRule: {}",
+                                hits.summary(),
+                                self.hit_counters
+                                    .rule(filter.rule_key(), &self.filters)
+                                    .summary()
+                            ));
+                        } else {
+                            ui.label("-");
+                        }
+                        match self
+                            .app_schedules
+                            .iter()
+                            .find(|s| filter.owned_by_app && s.rule_key() == filter.rule_key())
+                        {
+                            Some(schedule) => ui.label(format!("allowed {}", schedule.window)),
+                            None => ui.label(""),
+                        };
+                        ui.horizontal(|ui| {
+                            filter_row_actions(
+                                ui,
+                                filter,
+                                &mut self.edit_state,
+                                &mut self.delete_state,
+                            );
+                            if ui.button("Capture").clicked() {
+                                capture_request = Some(CaptureScope::from_filter(filter));
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(scope) = capture_request {
+            self.start_capture(scope);
+        }
+        if let Some(action) = row_action {
+            self.run_row_action(action);
+        }
+    }
+
+    /// Layer → Sublayer → Filters hierarchy, so the structure of the policy
+    /// is visible rather than flattened into one grid.
+    fn render_layer_tree(&mut self, ui: &mut egui::Ui) {
+        let mut row_action = None;
+        let mut tree: BTreeMap<&str, BTreeMap<&str, Vec<&FilterSummary>>> = BTreeMap::new();
+        for filter in &self.filters {
+            tree.entry(filter.layer.as_str())
+                .or_default()
+                .entry(filter.sublayer.as_str())
+                .or_default()
+                .push(filter);
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (layer, sublayers) in &tree {
+                let count: usize = sublayers.values().map(Vec::len).sum();
+                egui::CollapsingHeader::new(format!("{layer} ({count})"))
+                    .id_source(("layer", *layer))
+                    .show(ui, |ui| {
+                        for (sublayer, filters) in sublayers {
+                            egui::CollapsingHeader::new(format!("{sublayer} ({})", filters.len()))
+                                .id_source(("sublayer", *layer, *sublayer))
+                                .show(ui, |ui| {
+                                    for filter in filters {
+                                        ui.horizontal(|ui| {
+                                            ui.label(format!(
+                                                "{} — {} [{} {}]",
+                                                filter.id,
+                                                filter.name,
+                                                filter.action.as_str(),
+                                                format_port(filter.remote_port),
+                                            ))
+                                            .context_menu(|ui| {
+                                                row_action = filter_context_menu(
+                                                    ui,
+                                                    filter,
+                                                    &mut self.edit_state,
+                                                    &mut self.delete_state,
+                                                );
+                                            });
+                                            filter_row_actions(
+                                                ui,
+                                                filter,
+                                                &mut self.edit_state,
+                                                &mut self.delete_state,
+                                            );
+                                        });
+                                    }
+                                });
+                        }
+                    });
+            }
+        });
+        if let Some(action) = row_action {
+            self.run_row_action(action);
+        }
+    }
+
+    /// Buckets filters under the provider that installed them, for auditing
+    /// what each product (Defender, VPN clients, ours) puts on the machine.
+    fn render_provider_groups(&mut self, ui: &mut egui::Ui) {
+        let mut row_action = None;
+        let mut groups: BTreeMap<&str, Vec<&FilterSummary>> = BTreeMap::new();
+        for filter in &self.filters {
+            groups
+                .entry(filter.provider.as_str())
+                .or_default()
+                .push(filter);
+        }
+
+        // Bulk expand/collapse forces the header state for a single frame.
+        let mut force_open = None;
+        ui.horizontal(|ui| {
+            ui.label(format!("{} providers", groups.len()));
+            if ui.button("Expand all").clicked() {
+                force_open = Some(true);
+            }
+            if ui.button("Collapse all").clicked() {
+                force_open = Some(false);
+            }
+        });
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (provider, filters) in &groups {
+                let owned = filters.iter().filter(|f| f.owned_by_app).count();
+                let title = if owned > 0 {
+                    format!("{provider} ({} filters, {owned} ours)", filters.len())
+                } else {
+                    format!("{provider} ({} filters)", filters.len())
+                };
+                egui::CollapsingHeader::new(title)
+                    .id_source(("provider", *provider))
+                    .open(force_open)
+                    .show(ui, |ui| {
+                        egui::Grid::new(("provider_grid", *provider))
+                            .striped(true)
+                            .min_col_width(80.0)
+                            .show(ui, |ui| {
+                                for filter in filters {
+                                    ui.label(filter.id.to_string());
+                                    ui.label(&filter.name).context_menu(|ui| {
+                                        row_action = filter_context_menu(
+                                            ui,
+                                            filter,
+                                            &mut self.edit_state,
+                                            &mut self.delete_state,
+                                        );
+                                    });
+                                    ui.label(&filter.layer);
+                                    ui.label(filter.action.as_str());
+                                    ui.label(format_port(filter.remote_port));
+                                    ui.horizontal(|ui| {
+                                        filter_row_actions(
+                                            ui,
+                                            filter,
+                                            &mut self.edit_state,
+                                            &mut self.delete_state,
+                                        );
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            }
+        });
+        if let Some(action) = row_action {
+            self.run_row_action(action);
+        }
+    }
+
+    /// Sockets on this machine with their processes. "Block this" adds a
+    /// rule on this machine blocking the process from the remote address.
+    fn render_connections(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Reload").clicked() || self.connections.is_none() {
+                self.connections = Some(connections::list().map_err(|err| err.to_string()));
+            }
+            ui.checkbox(&mut self.connections_established_only, "Connected TCP only");
+            if self.hosts.active().name.is_some() {
+                ui.weak("Connections are always those of this machine.");
+            }
+        });
+        let list = match &self.connections {
+            Some(Ok(list)) => list,
+            Some(Err(err)) => {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("Cannot read connections: {err}"),
+                );
+                return;
+            }
+            None => return,
+        };
+        let mut block = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("connections_grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    for heading in ["Proto", "Local", "Remote", "State", "PID", "Process", ""] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+                    for conn in list
+                        .iter()
+                        .filter(|c| !self.connections_established_only || c.remote.is_some())
+                    {
+                        ui.label(conn.protocol.as_str());
+                        ui.label(conn.local.to_string());
+                        ui.label(conn.remote.map(|r| r.to_string()).unwrap_or_default());
+                        ui.label(conn.state);
+                        ui.label(conn.pid.to_string());
+                        match &conn.process {
+                            Some(path) => ui.label(chart::app_name(path)).on_hover_text(path),
+                            None => ui.weak("(unknown)"),
+                        };
+                        let rule = conn.block_rule();
+                        if ui
+                            .add_enabled(
+                                rule.is_some() && !wfp::is_read_only(),
+                                egui::Button::new("Block this"),
+                            )
+                            .on_disabled_hover_text("Needs a remote address and a known process")
+                            .clicked()
+                        {
+                            block = rule;
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(config) = block {
+            let name = config.name.clone();
+            match self.hosts.hosts[0]
+                .open()
+                .and_then(|eng| eng.import_filters(&[config]))
+            {
+                Ok(_) => {
+                    self.refresh.request();
+                    self.notices.info(format!("Added rule '{name}'."))
+                }
+                Err(err) => self.notices.error(format!("Block failed: {err}")),
+            }
+        }
+    }
+
+    /// IPsec-protected connections on the active host. The list is read
+    /// once and then follows a connection subscription.
+    fn render_ipsec(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Reload").clicked() || self.ipsec.is_none() {
+            self.load_ipsec();
+        }
+        if let (Some((_, events)), Some(Ok(list))) = (&self.ipsec_feed, &mut self.ipsec) {
+            for event in events.try_iter() {
+                match event {
+                    IpsecEvent::Added(conn) => {
+                        list.retain(|c| c.id != conn.id);
+                        list.push(conn);
+                    }
+                    IpsecEvent::Deleted(id) => list.retain(|c| c.id != id),
+                }
+            }
+            ui.ctx().request_repaint_after(Duration::from_secs(1));
+        }
+        let list = match &self.ipsec {
+            Some(Ok(list)) => list,
+            Some(Err(err)) => {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("Cannot read IPsec connections: {err}"),
+                );
+                return;
+            }
+            None => return,
+        };
+        if list.is_empty() {
+            ui.weak("No IPsec-protected connections.");
+            return;
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("ipsec_grid").striped(true).show(ui, |ui| {
+                for heading in [
+                    "Local",
+                    "Remote",
+                    "Mode",
+                    "Keying",
+                    "Main mode",
+                    "Peer auth",
+                    "In",
+                    "Out",
+                    "Since",
+                ] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for conn in list {
+                    ui.label(conn.local.to_string());
+                    ui.label(conn.remote.to_string());
+                    ui.label(if conn.tunnel { "tunnel" } else { "transport" });
+                    ui.label(conn.key_module);
+                    ui.label(&conn.main_mode);
+                    ui.label(&conn.peer_auth);
+                    ui.label(conn.bytes_in.to_string());
+                    ui.label(conn.bytes_out.to_string());
+                    ui.label(
+                        conn.started
+                            .with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M:%S")
+                            .to_string(),
+                    );
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    /// Reads the IPsec connections and subscribes to changes, on a
+    /// separate session that stays open with the subscription.
+    fn load_ipsec(&mut self) {
+        self.ipsec_feed = None;
+        let (sender, receiver) = mpsc::channel();
+        let loaded = self.hosts.open().and_then(|engine| {
+            let list = engine.ipsec_connections()?;
+            let feed = self.hosts.open()?.subscribe_ipsec_connections(sender)?;
+            Ok((list, feed))
+        });
+        self.ipsec = Some(match loaded {
+            Ok((list, feed)) => {
+                self.ipsec_feed = Some((feed, receiver));
+                Ok(list)
+            }
+            Err(err) => Err(err.to_string()),
+        });
+    }
+
+    /// Engine options and object counts for the active host, read when the
+    /// tab is first shown and on demand.
+    fn render_state(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Reload").clicked() || self.engine_state.is_none() {
+            self.engine_state = Some(
+                self.hosts
+                    .open()
+                    .and_then(|engine| EngineState::load(&engine))
+                    .map_err(|err| err.to_string()),
+            );
+        }
+        let state = match &self.engine_state {
+            Some(Ok(state)) => state,
+            Some(Err(err)) => {
+                ui.colored_label(egui::Color32::RED, format!("Cannot read state: {err}"));
+                return;
+            }
+            None => return,
+        };
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let options = &state.options;
+        let keywords = options.keyword_names();
+        let queuing = match options.packet_queuing {
+            0 => "off".to_string(),
+            flags => {
+                let mut names = Vec::new();
+                for (flag, name) in [(1, "inbound"), (2, "forward"), (4, "inbound batching")] {
+                    if flags & flag != 0 {
+                        names.push(name);
+                    }
+                }
+                names.join(", ")
+            }
+        };
+        let rows = [
+            ("Host", self.hosts.active().label().to_string()),
+            (
+                "Net event collection",
+                on_off(options.collect_net_events).to_string(),
+            ),
+            (
+                "Extra net events",
+                if keywords.is_empty() {
+                    "none".to_string()
+                } else {
+                    keywords.join(", ")
+                },
+            ),
+            ("Net events held", state.net_events.to_string()),
+            ("Name cache", on_off(options.name_cache).to_string()),
+            (
+                "IPsec connection monitoring",
+                on_off(options.monitor_ipsec_connections).to_string(),
+            ),
+            ("Packet queuing", queuing),
+            (
+                "Transaction watchdog",
+                match options.txn_watchdog_ms {
+                    0 => "default".to_string(),
+                    ms => format!("{ms} ms"),
+                },
+            ),
+            ("Sessions", state.sessions.to_string()),
+            ("Providers", state.providers.to_string()),
+            ("Sublayers", state.sublayers.to_string()),
+            ("Layers", state.layers.to_string()),
+            (
+                "Filters",
+                format!("{} ({} ours)", state.filters, state.owned_filters),
+            ),
+            ("Boot-time filters", state.boot_time_filters.to_string()),
+            ("Callouts", state.callouts.to_string()),
+        ];
+        egui::Grid::new("engine_state_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for (label, value) in rows {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        if !options.collect_net_events {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "Net event collection is off, so the events view and alerts stay empty.",
+            );
+        }
+    }
+
+    /// Debug panel timing each phase of enumerating the active host.
+    fn render_bench(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Enumeration benchmark").show(ui, |ui| {
+            if ui.button("Run 5 times").clicked() {
+                self.bench = Some(
+                    self.hosts
+                        .open()
+                        .and_then(|engine| BenchReport::run(&engine, 5))
+                        .map_err(|err| err.to_string()),
+                );
+            }
+            let report = match &self.bench {
+                Some(Ok(report)) => report,
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::RED, format!("Benchmark failed: {err}"));
+                    return;
+                }
+                None => return,
+            };
+            ui.label(format!(
+                "{} run(s) over {} filters; {:.1} ms in total (median)",
+                report.runs,
+                report.filters,
+                report.median_total_ms()
+            ));
+            egui::Grid::new("bench_grid").striped(true).show(ui, |ui| {
+                for heading in ["Phase", "Min (ms)", "Median (ms)", "Max (ms)"] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for phase in &report.phases {
+                    ui.label(phase.phase);
+                    ui.label(format!("{:.2}", phase.min_ms));
+                    ui.label(format!("{:.2}", phase.median_ms));
+                    ui.label(format!("{:.2}", phase.max_ms));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    fn render_metadata(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Providers").show(ui, |ui| {
+            for item in &self.providers {
+                ui.label(format!("{} — {}", format_guid(item.key), item.name));
+                if let Some(desc) = &item.description {
+                    ui.label(egui::RichText::new(desc).small());
+                }
+            }
+        });
+        egui::CollapsingHeader::new("Sublayers").show(ui, |ui| {
+            for item in &self.sublayers {
+                ui.label(format!("{} — {}", format_guid(item.key), item.name));
+                if let Some(desc) = &item.description {
+                    ui.label(egui::RichText::new(desc).small());
+                }
+            }
+        });
+        egui::CollapsingHeader::new("Layers").show(ui, |ui| {
+            for item in &self.layers {
+                ui.label(format!("{} — {}", format_guid(item.key), item.name));
+                if let Some(desc) = &item.description {
+                    ui.label(egui::RichText::new(desc).small());
+                }
+            }
+        });
+        egui::CollapsingHeader::new("System ports").show(ui, |ui| {
+            ui.label("Ports Windows has reserved for its own services.");
+            if self.system_ports.is_empty() {
+                ui.weak("None reported.");
+            }
+            for system in &self.system_ports {
+                let ports: Vec<String> = system.ports.iter().map(u16::to_string).collect();
+                ui.label(format!("{}: {}", system.kind.label(), ports.join(", ")));
+            }
+        });
+        egui::CollapsingHeader::new("Sessions").show(ui, |ui| {
+            ui.label("Who is connected to BFE; useful when transactions time out.");
+            if ui.button("Load sessions").clicked() {
+                match self.hosts.open().and_then(|engine| engine.sessions()) {
+                    Ok(sessions) => {
+                        self.notices
+                            .info(format!("Loaded {} sessions", sessions.len()));
+                        self.sessions = sessions;
+                    }
+                    Err(err) => self
+                        .notices
+                        .error(format!("Loading sessions failed: {err}")),
+                }
+            }
+            for session in &self.sessions {
+                let mut title = format!(
+                    "{} — pid {}, {}",
+                    if session.name.is_empty() {
+                        "(unnamed)"
+                    } else {
+                        &session.name
+                    },
+                    session.process_id,
+                    session.username
+                );
+                if session.kernel_mode {
+                    title.push_str(", kernel");
+                }
+                if session.ours {
+                    title.push_str(" (this app)");
+                }
+                let text = egui::RichText::new(title);
+                ui.label(if session.ours { text.strong() } else { text });
+                let timeout = match session.txn_wait_timeout_ms {
+                    0 => "default".to_string(),
+                    ms => format!("{ms} ms"),
+                };
+                ui.label(
+                    egui::RichText::new(format!(
+                        "{} · txn wait {timeout}{}",
+                        format_guid(session.key),
+                        session
+                            .description
+                            .as_deref()
+                            .map(|d| format!(" · {d}"))
+                            .unwrap_or_default()
+                    ))
+                    .small(),
+                );
+            }
+        });
+    }
+
+    fn render_net_events(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Network events")
+            .open(self.reveal_events.then_some(true))
+            .show(ui, |ui| {
+                if self.detached.events {
+                    return detached_placeholder(ui, &mut self.detached.events);
+                }
+                if ui.small_button("Open in new window").clicked() {
+                    self.detached.events = true;
+                }
+                self.net_events_body(ui);
+            });
+        self.reveal_events = false;
+    }
+
+    fn net_events_body(&mut self, ui: &mut egui::Ui) {
+        let mut capture_request = None;
+        let mut allow_request = None;
+        // Temporary allows live in a dynamic session, which is local only.
+        let local = self.hosts.active().name.is_none();
+        let form = &mut self.event_filter;
+        ui.horizontal(|ui| {
+            ui.label("From:");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.from)
+                    .desired_width(140.0)
+                    .hint_text("e.g. 24h or 2024-05-01 08:00"),
+            );
+            ui.label("To:");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.to)
+                    .desired_width(140.0)
+                    .hint_text("now"),
+            );
+            egui::ComboBox::from_id_source("event_protocol")
+                .selected_text(form.protocol.map(protocol_name).unwrap_or("any"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut form.protocol, None, "any");
+                    for proto in [6, 17, 1, 58] {
+                        ui.selectable_value(&mut form.protocol, Some(proto), protocol_name(proto));
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Remote:");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.remote_address)
+                    .desired_width(120.0)
+                    .hint_text("address"),
+            );
+            ui.add(
+                egui::TextEdit::singleline(&mut form.remote_port)
+                    .desired_width(50.0)
+                    .hint_text("port"),
+            );
+            ui.label("Local port:");
+            ui.add(egui::TextEdit::singleline(&mut form.local_port).desired_width(50.0));
+            ui.label("App:");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.app)
+                    .desired_width(200.0)
+                    .hint_text("C:\\path\\app.exe or part of it"),
+            );
+            ui.label("Filter ID:");
+            ui.add(egui::TextEdit::singleline(&mut form.filter_id).desired_width(60.0));
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Query events").clicked() {
+                self.query_net_events();
+            }
+            if ui.button("Query history").clicked() {
+                match self
+                    .event_filter
+                    .to_query()
+                    .and_then(|query| EventStore::open(&EventStore::default_dir())?.query(&query))
+                {
+                    Ok(events) => {
+                        self.notices
+                            .info(format!("Loaded {} events from history", events.len()));
+                        self.net_events = events;
+                    }
+                    Err(err) => self.notices.error(format!("Reading history failed: {err}")),
+                }
+            }
+            ui.checkbox(&mut self.record_history, "Save queried events to history");
+            ui.label("Pick \"Why?\" on an event to trace the filter behind its verdict.");
+        });
+        ui.horizontal(|ui| {
+            ui.label("History limit (MB):");
+            ui.add(egui::TextEdit::singleline(&mut self.history_max_mb).desired_width(60.0));
+            ui.label("Keep days:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.history_max_days)
+                    .desired_width(40.0)
+                    .hint_text("forever"),
+            );
+            if ui.button("Apply retention").clicked() {
+                match self.apply_retention() {
+                    Ok(()) => self.notices.info("History retention updated."),
+                    Err(err) => self.notices.error(format!("Retention not applied: {err}")),
+                }
+            }
+            if ui.button("Purge now").clicked() {
+                match EventStore::open(&EventStore::default_dir()).and_then(|store| store.purge()) {
+                    Ok(freed) => self
+                        .notices
+                        .info(format!("History purged, {} MB freed.", freed / MIB)),
+                    Err(err) => self.notices.error(format!("History not purged: {err}")),
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("event_export_format")
+                .selected_text(self.event_export_format.as_str())
+                .show_ui(ui, |ui| {
+                    for format in EventExportFormat::ALL {
+                        ui.selectable_value(&mut self.event_export_format, format, format.as_str());
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut self.event_export_path)
+                    .desired_width(200.0)
+                    .hint_text(format!("events.{}", self.event_export_format.extension())),
+            );
+            if ui.button("Export events").clicked() {
+                match self.export_events() {
+                    Ok((count, path)) => self
+                        .notices
+                        .info(format!("Exported {count} events to {path}")),
+                    Err(err) => self.notices.error(format!("Event export failed: {err}")),
+                }
+            }
+        });
+        egui::ScrollArea::vertical()
+            .id_source("net_events_scroll")
+            .max_height(240.0)
+            .show(ui, |ui| {
+                egui::Grid::new("net_events_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.heading("Time (UTC)");
+                        ui.heading("Verdict");
+                        ui.heading("Direction");
+                        ui.heading("Flow");
+                        ui.heading("Filter ID");
+                        ui.heading("Application");
+                        ui.heading("");
+                        ui.end_row();
+
+                        for event in self.net_events.iter().rev().take(MAX_EVENTS_SHOWN) {
+                            ui.label(event.time.format("%H:%M:%S").to_string());
+                            ui.label(event.kind.as_str());
+                            ui.label(event.direction.map(|d| d.as_str()).unwrap_or("-"));
+                            ui.label(event.flow());
+                            ui.label(event.filter_id.to_string());
+                            ui.label(event.app_id.as_deref().unwrap_or("-"));
+                            if ui.button("Why?").clicked() {
+                                self.diagnosis = Some(Diagnosis::explain(
+                                    event,
+                                    &self.filters,
+                                    &self.boot_time_filters,
+                                    &self.sublayer_details,
+                                ));
+                            }
+                            if ui.button("Capture").clicked() {
+                                capture_request = Some(CaptureScope::from_event(event));
+                            }
+                            if event.kind == NetEventKind::Drop
+                                && ui
+                                    .add_enabled(
+                                        local && !wfp::is_read_only(),
+                                        egui::Button::new("Allow 10 min"),
+                                    )
+                                    .on_hover_text(
+                                        "Permit just this flow for 10 minutes; the exception \
+                                         is removed when it expires or the app closes",
+                                    )
+                                    .on_disabled_hover_text("Only for events of this machine")
+                                    .clicked()
+                            {
+                                allow_request = Some(event.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if let Some(scope) = capture_request {
+            self.start_capture(scope);
+        }
+        if let Some(event) = allow_request {
+            match TemporaryAllow::start(&event, allow_once::DEFAULT_DURATION) {
+                Ok(allow) => {
+                    let status = format!("Allowed {} for 10 minutes", allow.flow);
+                    self.allowances.push(allow);
+                    self.refresh.request();
+                    self.notices.info(status)
+                }
+                Err(err) => self.notices.error(format!("Allow failed: {err}")),
+            }
+        }
+    }
+
+    fn render_chart(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Connections chart").show(ui, |ui| {
+            if self.detached.chart {
+                return detached_placeholder(ui, &mut self.detached.chart);
+            }
+            if ui.small_button("Open in new window").clicked() {
+                self.detached.chart = true;
+            }
+            self.chart_body(ui);
+        });
+    }
+
+    fn chart_body(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("chart_interval")
+                .selected_text(
+                    chart::INTERVALS
+                        .iter()
+                        .find(|(secs, _)| *secs == self.chart.interval_secs)
+                        .map(|(_, label)| *label)
+                        .unwrap_or("custom"),
+                )
+                .show_ui(ui, |ui| {
+                    for (secs, label) in chart::INTERVALS {
+                        ui.selectable_value(&mut self.chart.interval_secs, secs, label);
+                    }
+                });
+            if ui.button("Load from history").clicked() {
+                match self.load_chart() {
+                    Ok(count) => self
+                        .notices
+                        .info(format!("Charted {count} events from history")),
+                    Err(err) => self.notices.error(format!("Chart failed: {err}")),
+                }
+            }
+            ui.label("Uses the time range and filters from Network events.");
+        });
+        if self.chart.series.is_empty() {
+            return;
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            for kind in [SeriesKind::Verdict, SeriesKind::Rule, SeriesKind::App] {
+                let heading = match kind {
+                    SeriesKind::Verdict => "Totals:",
+                    SeriesKind::Rule => "Rules:",
+                    SeriesKind::App => "Apps:",
+                };
+                ui.label(heading);
+                for series in self
+                    .chart
+                    .series
+                    .iter()
+                    .filter(|s| s.kind == kind)
+                    .take(MAX_CHART_TOGGLES)
+                {
+                    let shown = self
+                        .chart
+                        .shown
+                        .entry(series.label.clone())
+                        .or_insert(false);
+                    ui.checkbox(shown, format!("{} ({})", series.label, series.total));
+                }
+                ui.separator();
+            }
+        });
+
+        egui_plot::Plot::new("connections_plot")
+            .height(220.0)
+            .legend(egui_plot::Legend::default())
+            .include_y(0.0)
+            .x_axis_formatter(|mark, _, _| {
+                chrono::DateTime::from_timestamp(mark.value as i64, 0)
+                    .map(|t| t.format("%m-%d %H:%M").to_string())
+                    .unwrap_or_default()
+            })
+            .show(ui, |plot| {
+                for series in &self.chart.series {
+                    if self
+                        .chart
+                        .shown
+                        .get(&series.label)
+                        .copied()
+                        .unwrap_or(false)
+                    {
+                        plot.line(egui_plot::Line::new(series.points.clone()).name(&series.label));
+                    }
+                }
+            });
+    }
+
+    fn load_chart(&mut self) -> Result<usize> {
+        let query = self.event_filter.to_query()?;
+        let events = EventStore::open(&EventStore::default_dir())?.query(&query)?;
+        let filters = &self.filters;
+        self.chart.series = chart::build_series(&events, self.chart.interval_secs, |id| {
+            filters
+                .iter()
+                .find(|f| f.id == id)
+                .map(|f| f.name.clone())
+                .unwrap_or_else(|| format!("filter #{id}"))
+        });
+        for series in &self.chart.series {
+            self.chart
+                .shown
+                .entry(series.label.clone())
+                .or_insert(series.kind == SeriesKind::Verdict);
+        }
+        Ok(events.len())
+    }
+
+    fn poll_import(&mut self, ctx: &egui::Context) {
+        let Some(job) = &self.import_job else {
+            return;
+        };
+        let result = match job.result.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => {
+                ctx.request_repaint_after(Duration::from_millis(100));
+                return;
+            }
+            Err(mpsc::TryRecvError::Disconnected) => Err(anyhow!("Import worker stopped")),
+        };
+        self.import_job = None;
+        match result {
+            Ok(summary) => {
+                self.refresh.request();
+                self.notices.info(format!(
+                    "Import complete: {} added, {} updated.",
+                    summary.added, summary.updated
+                ))
+            }
+            Err(err) if err.is::<wfp::Cancelled>() => self
+                .notices
+                .info("Import cancelled; no rules were changed."),
+            Err(err) => self.notices.error(format!("Import failed: {err}")),
+        }
+    }
+
+    /// Feeds newly seen net events through the alert rules and acts on
+    /// whatever fires.
+    fn poll_alerts(&mut self, ctx: &egui::Context) {
+        let Some((_, receiver)) = &self.alert_feed else {
+            return;
+        };
+        let batches: Vec<_> = receiver.try_iter().collect();
+        ctx.request_repaint_after(ALERT_POLL_INTERVAL);
+        for batch in batches {
+            let events = match batch {
+                Ok(events) => events,
+                Err(err) => {
+                    self.notices.error(format!("Alert monitoring: {err}"));
+                    continue;
+                }
+            };
+            if let Err(err) = syslog::forward_events(&events) {
+                self.notices
+                    .error(format!("Syslog forwarding failed: {err}"));
+            }
+            self.count_hits(&events);
+            for alert in self.alerts.evaluate(&events) {
+                let message = alert.message();
+                if let Err(err) = alert.log().and_then(|()| alert.run_command()) {
+                    self.notices
+                        .error(format!("Alert {}: {err}", alert.rule.name));
+                }
+                alert.send_webhook(self.filters.iter().find(|f| f.id == alert.event.filter_id));
+                self.notices.warn(format!("Alert {message}"));
+                self.fired_alerts.push_front(alert);
+                self.fired_alerts.truncate(MAX_FIRED_ALERTS);
+            }
+        }
+    }
+
+    /// The latest message, and the history of them behind a button
+    /// counting the warnings and errors not seen yet.
+    fn render_status(&mut self, ui: &mut egui::Ui) {
+        let history = match self.notices.unread() {
+            0 => "History".to_string(),
+            unread => format!("History ({unread})"),
+        };
+        let response = ui
+            .button(history)
+            .on_hover_text("Every status message, warning and error so far");
+        let popup = ui.make_persistent_id("notification_history");
+        if response.clicked() {
+            ui.memory_mut(|m| m.toggle_popup(popup));
+            self.notices.mark_read();
+        }
+        egui::popup_below_widget(ui, popup, &response, |ui| {
+            ui.set_min_width(480.0);
+            egui::ScrollArea::vertical()
+                .max_height(320.0)
+                .show(ui, |ui| {
+                    for notice in self.notices.history() {
+                        ui.colored_label(severity_color(ui, notice.severity), notice.line());
+                    }
+                });
+            if ui.button("Clear").clicked() {
+                self.notices.clear();
+            }
+        });
+        match self.notices.latest() {
+            Some(notice) => {
+                ui.colored_label(severity_color(ui, notice.severity), &notice.message);
+            }
+            None => {
+                ui.label("Ready");
+            }
+        }
+    }
+
+    fn render_toasts(&mut self, ctx: &egui::Context) {
+        if self.notices.toasts().next().is_none() {
+            return;
+        }
+        let mut dismissed = Vec::new();
+        egui::Area::new(egui::Id::new("notification_toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-12.0, -12.0])
+            .show(ctx, |ui| {
+                for (i, notice) in self.notices.toasts().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.set_max_width(360.0);
+                        ui.horizontal(|ui| {
+                            ui.colored_label(
+                                severity_color(ui, notice.severity),
+                                notice.severity.label(),
+                            );
+                            if ui.small_button("✖").clicked() {
+                                dismissed.push(i);
+                            }
+                        });
+                        ui.label(notice.line());
+                    });
+                }
+            });
+        self.notices.dismiss_toasts(&dismissed);
+        ctx.request_repaint_after(Duration::from_millis(250));
+    }
+
+    fn render_alerts(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut monitoring = self.alert_feed.is_some();
+            if ui.checkbox(&mut monitoring, "Monitor net events").changed() {
+                if monitoring {
+                    let (sender, receiver) = mpsc::channel();
+                    self.alert_feed =
+                        Some((EventFeed::start(ALERT_POLL_INTERVAL, sender), receiver));
+                    self.notices.info("Alert monitoring started.");
+                } else {
+                    self.alert_feed = None;
+                    self.notices.info("Alert monitoring stopped.");
+                }
+            }
+            ui.label(format!(
+                "Fired alerts are logged to {}",
+                alerts::alert_log_path().display()
+            ));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Rotate log at (MB):");
+            ui.add(egui::TextEdit::singleline(&mut self.log_max_mb).desired_width(40.0));
+            ui.label("Rotated files kept:");
+            ui.add(egui::TextEdit::singleline(&mut self.log_keep).desired_width(30.0));
+            ui.label("Keep days:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.log_max_days)
+                    .desired_width(40.0)
+                    .hint_text("forever"),
+            );
+            if ui.button("Apply").clicked() {
+                match self.apply_log_retention() {
+                    Ok(()) => self.notices.info("Log retention updated."),
+                    Err(err) => self
+                        .notices
+                        .error(format!("Log retention not applied: {err}")),
+                }
+            }
+            if ui.button("Purge log now").clicked() {
+                match log_rotation::purge(&alerts::alert_log_path()) {
+                    Ok(freed) => self
+                        .notices
+                        .info(format!("Alert log purged, {freed} bytes freed.")),
+                    Err(err) => self.notices.error(format!("Alert log not purged: {err}")),
+                }
+            }
+        });
+        ui.separator();
+
+        ui.heading("Alert rules");
+        let mut remove = None;
+        let mut changed = false;
+        egui::Grid::new("alert_rules_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("On");
+                ui.label("Name");
+                ui.label("Conditions");
+                ui.label("Actions");
+                ui.label("");
+                ui.end_row();
+                for (index, rule) in self.alerts.rules.iter_mut().enumerate() {
+                    changed |= ui.checkbox(&mut rule.enabled, "").changed();
+                    ui.label(&rule.name);
+                    ui.label(rule.describe());
+                    let actions: Vec<&str> = [
+                        rule.command.as_deref().map(|_| "command"),
+                        rule.webhook.as_deref().map(|_| "webhook"),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                    let label = ui.label(if actions.is_empty() {
+                        "toast, log".to_string()
+                    } else {
+                        format!("toast, log, {}", actions.join(", "))
+                    });
+                    if let Some(command) = &rule.command {
+                        label.on_hover_text(command);
+                    }
+                    if ui.button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(index) = remove {
+            self.alerts.rules.remove(index);
+            changed = true;
+        }
+
+        ui.separator();
+        let form = &mut self.alert_form;
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.add(egui::TextEdit::singleline(&mut form.name).desired_width(140.0));
+            egui::ComboBox::from_id_source("alert_kind")
+                .selected_text(form.kind.map_or("any verdict", NetEventKind::as_str))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut form.kind, None, "any verdict");
+                    for kind in [NetEventKind::Drop, NetEventKind::Allow] {
+                        ui.selectable_value(&mut form.kind, Some(kind), kind.as_str());
+                    }
+                });
+            egui::ComboBox::from_id_source("alert_protocol")
+                .selected_text(form.protocol.map(protocol_name).unwrap_or("any"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut form.protocol, None, "any");
+                    for proto in [6, 17, 1, 58] {
+                        ui.selectable_value(&mut form.protocol, Some(proto), protocol_name(proto));
+                    }
+                });
+            ui.label("Remote port:");
+            ui.add(egui::TextEdit::singleline(&mut form.remote_port).desired_width(50.0));
+        });
+        ui.horizontal(|ui| {
+            ui.label("App:");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.app)
+                    .desired_width(200.0)
+                    .hint_text("part of the app path"),
+            );
+            ui.label("More than")
+                .on_hover_text("Leave blank to alert on every matching event.");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.per_minute)
+                    .desired_width(40.0)
+                    .hint_text("N"),
+            );
+            ui.label("per minute");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Run command:");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.command)
+                    .desired_width(320.0)
+                    .hint_text("optional, gets SLS_ALERT_* variables"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Webhook:");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.webhook)
+                    .desired_width(320.0)
+                    .hint_text("optional https:// URL for Slack, Teams or SOAR"),
+            );
+            if ui.button("Add alert").clicked() {
+                match form.to_rule() {
+                    Ok(rule) if self.alerts.rules.iter().any(|r| r.name == rule.name) => {
+                        self.notices
+                            .warn(format!("An alert named '{}' already exists", rule.name));
+                    }
+                    Ok(rule) => {
+                        self.alerts.rules.push(rule);
+                        changed = true;
+                    }
+                    Err(err) => self.notices.error(format!("Invalid alert: {err}")),
+                }
+            }
+        });
+        if changed {
+            match self.alerts.save() {
+                Ok(()) => self.notices.info("Alert rules saved."),
+                Err(err) => self
+                    .notices
+                    .error(format!("Saving alert rules failed: {err}")),
+            }
+        }
+
+        ui.separator();
+        self.render_syslog(ui);
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.heading("Fired alerts");
+            if ui.button("Clear").clicked() {
+                self.fired_alerts.clear();
+            }
+        });
+        egui::ScrollArea::vertical()
+            .id_source("fired_alerts_scroll")
+            .show(ui, |ui| {
+                for alert in &self.fired_alerts {
+                    ui.label(format!(
+                        "{}  {}",
+                        alert.event.time.format("%Y-%m-%d %H:%M:%S"),
+                        alert.message()
+                    ));
+                }
+            });
+    }
+
+    fn start_scheduler(&self) -> ScriptScheduler {
+        ScriptScheduler::start(
+            self.scripts.clone(),
+            self.app_schedules.clone(),
+            self.script_output.0.clone(),
+        )
+    }
+
+    /// Apps limited to a daily network window. The scheduler blocks each
+    /// one outside its window while it runs.
+    fn render_app_schedules(&mut self, ui: &mut egui::Ui) {
+        ui.heading("App network windows");
+        let now = chrono::Local::now().time();
+        let mut changed = false;
+        let mut remove = None;
+        egui::Grid::new("app_schedules_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("On");
+                ui.label("Application");
+                ui.label("Allowed");
+                ui.label("Now");
+                ui.label("");
+                ui.end_row();
+                for (index, schedule) in self.app_schedules.iter_mut().enumerate() {
+                    changed |= ui.checkbox(&mut schedule.enabled, "").changed();
+                    ui.label(&schedule.app);
+                    ui.label(schedule.window.to_string());
+                    if schedule.is_open(now) {
+                        ui.label("allowed");
+                    } else {
+                        ui.colored_label(egui::Color32::RED, "blocked");
+                    }
+                    if ui.button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(index) = remove {
+            let schedule = self.app_schedules.remove(index);
+            // The scheduler forgets removed schedules, so lift the block here.
+            if let Err(err) = wfp::Engine::open().and_then(|engine| schedule.apply(&engine, true)) {
+                self.notices.error(format!(
+                    "Removing the block on {} failed: {err}",
+                    schedule.app
+                ));
+            }
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("App:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.schedule_app)
+                    .desired_width(320.0)
+                    .hint_text("C:\\Program Files\\App\\app.exe"),
+            );
+            ui.label("allowed");
+            ui.add(egui::TextEdit::singleline(&mut self.schedule_window).desired_width(90.0));
+            if ui.button("Add").clicked() {
+                match AccessWindow::parse(&self.schedule_window)
+                    .and_then(|window| AppSchedule::new(&self.schedule_app, window))
+                {
+                    Ok(schedule) => {
+                        self.app_schedules.push(schedule);
+                        self.schedule_app.clear();
+                        changed = true;
+                    }
+                    Err(err) => self.notices.error(format!("Invalid app schedule: {err}")),
+                }
+            }
+        });
+        if changed {
+            match app_schedule::save_schedules(&self.app_schedules) {
+                Ok(()) if self.scheduler.is_some() => {
+                    self.scheduler = Some(self.start_scheduler());
+                    self.notices.info("App schedules saved and reloaded.")
+                }
+                Ok(()) => self
+                    .notices
+                    .info("App schedules saved; start the scheduler to enforce them."),
+                Err(err) => self
+                    .notices
+                    .error(format!("Saving app schedules failed: {err}")),
+            }
+        }
+    }
+
+    fn render_scripts(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut running = self.scheduler.is_some();
+            if ui.checkbox(&mut running, "Run scheduler").changed() {
+                self.scheduler = running.then(|| self.start_scheduler());
+                self.notices.info(if running {
+                    "Scheduler started."
+                } else {
+                    "Scheduler stopped."
+                });
+            }
+            ui.label("rhai scripts with filters(), add_tcp_rule(), set_address_rule(), resolve() and more.");
+        });
+        ui.separator();
+
+        let mut changed = false;
+        let mut remove = None;
+        let mut run_now = None;
+        egui::Grid::new("scripts_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("On");
+                ui.label("Script");
+                ui.label("Every (min)");
+                ui.label("");
+                ui.end_row();
+                for (index, script) in self.scripts.iter_mut().enumerate() {
+                    changed |= ui.checkbox(&mut script.enabled, "").changed();
+                    ui.label(script.path.display().to_string());
+                    ui.label(script.every_minutes.to_string());
+                    ui.horizontal(|ui| {
+                        if ui.button("Run now").clicked() {
+                            run_now = Some(script.path.clone());
+                        }
+                        if ui.button("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        if let Some(index) = remove {
+            self.scripts.remove(index);
+            changed = true;
+        }
+        if let Some(path) = run_now {
+            let output = self.script_output.0.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = scripting::run_script(&path, &output) {
+                    let _ = output.send(ScriptOutput {
+                        time: chrono::Utc::now(),
+                        script: path.display().to_string(),
+                        line: err.to_string(),
+                        error: true,
+                    });
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Script:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.script_path)
+                    .desired_width(320.0)
+                    .hint_text("C:\\path\\update-hosts.rhai"),
+            );
+            ui.label("every");
+            ui.add(egui::TextEdit::singleline(&mut self.script_every).desired_width(40.0));
+            ui.label("minutes");
+            if ui.button("Add").clicked() {
+                match self.script_every.trim().parse::<u32>() {
+                    Ok(every) if every > 0 && !self.script_path.trim().is_empty() => {
+                        self.scripts.push(ScheduledScript {
+                            path: self.script_path.trim().into(),
+                            every_minutes: every,
+                            enabled: true,
+                        });
+                        self.script_path.clear();
+                        changed = true;
+                    }
+                    _ => self
+                        .notices
+                        .warn("Give a script path and a whole number of minutes"),
+                }
+            }
+        });
+        if changed {
+            match scripting::save_schedule(&self.scripts) {
+                Ok(()) if self.scheduler.is_some() => {
+                    self.scheduler = Some(self.start_scheduler());
+                    self.notices.info("Script schedule saved and reloaded.")
+                }
+                Ok(()) => self.notices.info("Script schedule saved."),
+                Err(err) => self
+                    .notices
+                    .error(format!("Saving script schedule failed: {err}")),
+            }
+        }
+
+        ui.separator();
+        self.render_app_schedules(ui);
+
+        ui.separator();
+        self.render_rule_sources(ui);
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.heading("Output");
+            if ui.button("Clear").clicked() {
+                self.script_log.clear();
+            }
+        });
+        egui::ScrollArea::vertical()
+            .id_source("script_log_scroll")
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for output in &self.script_log {
+                    let line = format!(
+                        "{}  {}  {}",
+                        output.time.format("%H:%M:%S"),
+                        output.script,
+                        output.line
+                    );
+                    if output.error {
+                        ui.colored_label(egui::Color32::RED, line);
+                    } else {
+                        ui.label(line);
+                    }
+                }
+            });
+    }
+
+    fn render_rule_sources(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Rule sources").show(ui, |ui| {
+            ui.label(format!(
+                "Programs that print a rule set, configured in {}. Syncing installs \
+                 their rules and removes the ones they no longer list.",
+                config::app_data_dir().join("rule_sources.json").display()
+            ));
+            let sources = match plugins::load_sources() {
+                Ok(sources) => sources,
+                Err(err) => {
+                    ui.colored_label(egui::Color32::RED, err.to_string());
+                    return;
+                }
+            };
+            for mut source in sources {
+                ui.horizontal(|ui| {
+                    ui.label(&source.name);
+                    ui.label(format!(
+                        "{} {}",
+                        source.command.display(),
+                        source.args.join(" ")
+                    ));
+                    if ui
+                        .add_enabled(!wfp::is_read_only(), egui::Button::new("Sync"))
+                        .clicked()
+                    {
+                        let result = self
+                            .hosts
+                            .open()
+                            .and_then(|eng| plugins::sync_source(&eng, &mut source));
+                        match result {
+                            Ok(summary) => {
+                                self.refresh.request();
+                                self.notices.info(format!(
+                                    "{}: {} added, {} updated, {} removed.",
+                                    source.name, summary.added, summary.updated, summary.removed
+                                ))
+                            }
+                            Err(err) => self
+                                .notices
+                                .error(format!("Sync of {} failed: {err}", source.name)),
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    fn render_syslog(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Syslog forwarding").show(ui, |ui| {
+            let form = &mut self.syslog;
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut form.config.enabled, "Forward to");
+                ui.add(
+                    egui::TextEdit::singleline(&mut form.config.host)
+                        .desired_width(180.0)
+                        .hint_text("collector host"),
+                );
+                ui.label("Port:");
+                ui.add(egui::TextEdit::singleline(&mut form.port).desired_width(50.0));
+                for transport in [SyslogTransport::Udp, SyslogTransport::Tcp] {
+                    ui.radio_value(&mut form.config.transport, transport, transport.as_str());
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut form.config.forward_audit, "Rule changes");
+                ui.checkbox(&mut form.config.forward_events, "Net events")
+                    .on_hover_text("Sent while net events are being monitored.");
+                if ui.button("Save").clicked() {
+                    match form.to_config().and_then(syslog::configure) {
+                        Ok(()) => self.notices.info("Syslog settings saved."),
+                        Err(err) => self
+                            .notices
+                            .error(format!("Syslog settings not saved: {err}")),
+                    }
+                }
+            });
+            ui.label("Messages are RFC 5424; TCP uses octet-counted framing.");
+        });
+    }
+
+    fn render_live_log(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Live log").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                let mut enabled = self.etw.is_some();
+                if ui
+                    .checkbox(&mut enabled, "WFP diagnostic events (ETW)")
+                    .changed()
+                {
+                    if enabled {
+                        let (sender, receiver) = mpsc::channel();
+                        match EtwSession::start(sender) {
+                            Ok(session) => {
+                                self.etw = Some((session, receiver));
+                                self.notices.info("Live log started.");
+                            }
+                            Err(err) => self
+                                .notices
+                                .error(format!("Live log failed to start: {err}")),
+                        }
+                    } else {
+                        self.etw = None;
+                        self.notices.info("Live log stopped.");
+                    }
+                }
+                if ui.button("Clear").clicked() {
+                    self.live_log.clear();
+                }
+                ui.label("Classify, reauthorization and error events from Microsoft-Windows-WFP.");
+            });
+            egui::ScrollArea::vertical()
+                .id_source("live_log_scroll")
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("live_log_grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.heading("Time (UTC)");
+                            ui.heading("Kind");
+                            ui.heading("Event ID");
+                            ui.heading("Task");
+                            ui.heading("Opcode");
+                            ui.heading("PID");
+                            ui.end_row();
+
+                            for event in self.live_log.iter().rev() {
+                                ui.label(event.time.format("%H:%M:%S%.3f").to_string());
+                                let kind = event.category.as_str();
+                                if event.category == EtwCategory::Error {
+                                    ui.colored_label(egui::Color32::RED, kind);
+                                } else {
+                                    ui.label(kind);
+                                }
+                                ui.label(event.id.to_string());
+                                ui.label(&event.task);
+                                ui.label(&event.opcode);
+                                ui.label(event.process_id.to_string());
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+    }
+
+    fn query_net_events(&mut self) {
+        match self
+            .event_filter
+            .to_query()
+            .and_then(|query| self.hosts.open()?.query_net_events(&query))
+        {
+            Ok(events) => {
+                let loaded = format!("Loaded {} net events", events.len());
+                if self.record_history {
+                    match EventStore::open(&EventStore::default_dir())
+                        .and_then(|store| store.append(&events))
+                    {
+                        Ok(saved) => self
+                            .notices
+                            .info(format!("{loaded} ({saved} new saved to history)")),
+                        Err(err) => self
+                            .notices
+                            .error(format!("{loaded} (saving history failed: {err})")),
+                    }
+                } else {
+                    self.notices.info(loaded);
+                }
+                self.count_hits(&events);
+                self.net_events = events;
+            }
+            Err(err) => self
+                .notices
+                .error(format!("Loading net events failed: {err}")),
+        }
+    }
+
+    /// Counts `events` into the hit counters when they are this machine's,
+    /// whose filters are the ones listed.
+    fn count_hits(&mut self, events: &[NetEvent]) {
+        if self.hosts.active().name.is_some() {
+            return;
+        }
+        match HitCounters::add_events(events, &self.filters) {
+            Ok(counters) => self.hit_counters = counters,
+            Err(err) => self.notices.error(format!("Hit counters not saved: {err}")),
+        }
+    }
+
+    /// The owned rule `filter` belongs to, in export form. `None` for
+    /// foreign filters and rules that cannot be exported.
+    fn rule_config(&self, filter: &FilterSummary) -> Option<FilterConfig> {
+        let rule = filter.rule_key();
+        wfp::rules_from_filters(
+            self.filters
+                .iter()
+                .filter(|f| f.owned_by_app && f.rule_key() == rule)
+                .cloned(),
+        )
+        .into_iter()
+        .next()
+    }
+
+    fn run_row_action(&mut self, action: RowAction) {
+        let Some(filter) = self.filters.iter().find(|f| f.id == action.id()).cloned() else {
+            return;
+        };
+        let outcome = match action {
+            RowAction::Duplicate(_) => {
+                self.edit_state = Some(EditState::copy_of(&filter));
+                return;
+            }
+            RowAction::Export(_) => match self.rule_config(&filter) {
+                Some(config) => match serde_json::to_string_pretty(&[config]) {
+                    Ok(json) => {
+                        self.export_text = json;
+                        Ok(format!(
+                            "Exported '{}' to the Export / Import box.",
+                            filter.name
+                        ))
+                    }
+                    Err(err) => Err(format!("Export failed: {err}")),
+                },
+                None => Err(format!("'{}' cannot be exported.", filter.name)),
+            },
+            RowAction::Opposite(_) => match self.add_opposite_rule(&filter) {
+                Ok(name) => {
+                    self.refresh.request();
+                    Ok(format!("Added '{name}'."))
+                }
+                Err(err) => Err(format!("Opposite rule not added: {err}")),
+            },
+            RowAction::Pin(_) => match self.rule_config(&filter).map(Favorite::new) {
+                Some(Ok(favorite)) => {
+                    let key = favorite.rule_key();
+                    self.favorites.retain(|f| f.rule_key() != key);
+                    self.favorites.push(favorite);
+                    match favorites::save_favorites(&self.favorites) {
+                        Ok(()) => Ok(format!("Pinned '{}' to Favorites.", filter.name)),
+                        Err(err) => Err(format!("Favorites not saved: {err}")),
+                    }
+                }
+                Some(Err(err)) => Err(format!("'{}' cannot be pinned: {err}", filter.name)),
+                None => Err(format!("'{}' cannot be pinned.", filter.name)),
+            },
+            RowAction::ShowEvents(id) => {
+                self.event_filter = EventFilterForm {
+                    filter_id: id.to_string(),
+                    ..EventFilterForm::default()
+                };
+                self.reveal_events = true;
+                self.query_net_events();
+                return;
+            }
+            RowAction::Details(id) => {
+                if !self.detached.filters.contains(&id) {
+                    self.detached.filters.push(id);
+                }
+                return;
+            }
+            RowAction::ResetHits(_) => {
+                let rule = filter.rule_key();
+                match HitCounters::load().and_then(|mut counters| {
+                    counters.reset_rule(rule, &self.filters);
+                    counters.save()?;
+                    Ok(counters)
+                }) {
+                    Ok(counters) => {
+                        self.hit_counters = counters;
+                        Ok(format!("Hit counter of '{}' reset.", filter.name))
+                    }
+                    Err(err) => Err(format!("Hit counter not reset: {err}")),
+                }
+            }
+        };
+        self.notices.outcome(outcome);
+    }
+
+    /// Adds a rule with the same layers and conditions as the rule
+    /// `filter` belongs to, or as `filter` alone when it is not ours, with
+    /// the action flipped.
+    fn add_opposite_rule(&self, filter: &FilterSummary) -> Result<String> {
+        let action = match filter.action {
+            WfpAction::Permit => WfpAction::Block,
+            WfpAction::Block => WfpAction::Permit,
+            WfpAction::Callout => return Err(anyhow!("callout filters have no opposite")),
+        };
+        let members: Vec<&FilterSummary> = if filter.owned_by_app {
+            self.filters
+                .iter()
+                .filter(|f| f.owned_by_app && f.rule_key() == filter.rule_key())
+                .collect()
+        } else {
+            vec![filter]
+        };
+        let key = wfp::guid_from_uuid(Uuid::new_v4());
+        let name = naming::preset_name(&format!("{} (opposite)", filter.name));
+        let builders: Vec<FilterBuilder> = members
+            .iter()
+            .map(|member| {
+                member.conditions.iter().cloned().fold(
+                    FilterBuilder::new(&name, member.layer_key)
+                        .rule(key)
+                        .action(action),
+                    FilterBuilder::condition,
+                )
+            })
+            .collect();
+        self.hosts.open()?.replace_rule(key, &name, &builders)?;
+        Ok(name)
+    }
+
+    fn start_capture(&mut self, scope: CaptureScope) {
+        if self.capture.is_some() {
+            self.notices
+                .warn("A capture is already running; stop it first.");
+            return;
+        }
+        match Capture::start(scope, &capture::default_capture_dir()) {
+            Ok(capture) => {
+                let status = format!(
+                    "Capturing {} to {}",
+                    capture.scope,
+                    capture.etl_path.display()
+                );
+                self.capture = Some(capture);
+                self.notices.info(status)
+            }
+            Err(err) => self
+                .notices
+                .error(format!("Capture failed to start: {err}")),
+        }
+    }
+
+    /// Engages the panic button. With the lockout guard on, remote sessions
+    /// stay connected, and it is refused while one has no allow rule.
+    fn start_lockdown(&mut self) {
+        let started = self.safety.lockdown_sessions().and_then(|sessions| {
+            Lockdown::start(lockdown::DEFAULT_DURATION, &sessions).map(|l| (l, sessions.len()))
+        });
+        match started {
+            Ok((lockdown, 0)) => {
+                self.lockdown = Some(lockdown);
+                self.notices.info("All traffic on this machine is blocked")
+            }
+            Ok((lockdown, kept)) => {
+                self.lockdown = Some(lockdown);
+                self.notices.info(format!(
+                    "All traffic on this machine is blocked except {kept} remote session(s)"
+                ))
+            }
+            Err(err) => self.notices.error(format!("Lockdown failed: {err}")),
+        }
+        self.refresh.request();
+    }
+
+    fn stop_lockdown(&mut self, status: &str) {
+        self.lockdown = None;
+        self.notices.info(status);
+        self.refresh.request();
+    }
+
+    /// Countdown banner shown while the panic button is engaged.
+    fn render_lockdown_bar(&mut self, ui: &mut egui::Ui) {
+        let Some(lockdown) = &mut self.lockdown else {
+            return;
+        };
+        let mut undo = false;
+        ui.horizontal(|ui| {
+            let secs = lockdown.remaining().as_secs();
+            ui.colored_label(
+                egui::Color32::RED,
+                format!(
+                    "● All traffic blocked on this machine, lifting in {}:{:02}",
+                    secs / 60,
+                    secs % 60
+                ),
+            );
+            undo = ui.button("Undo").clicked();
+            if ui.button("+15 min").clicked() {
+                lockdown.extend(lockdown::DEFAULT_DURATION);
+            }
+        });
+        if undo {
+            self.stop_lockdown("Lockdown lifted, traffic allowed again");
+        }
+    }
+
+    /// Permits the RDP client before `configs` are imported, when this app
+    /// runs in an RDP session and they could block it.
+    fn protect_rdp_peer(&mut self, configs: &[FilterConfig]) -> Result<()> {
+        if !self.safety.peer_permit
+            || self.peer_permit.is_some()
+            || self.hosts.active().name.is_some()
+        {
+            return Ok(());
+        }
+        let Some(peer) = safety::rdp_peer().filter(|peer| safety::may_block(configs, *peer)) else {
+            return Ok(());
+        };
+        self.peer_permit = Some(
+            PeerPermit::add(peer)
+                .map_err(|e| anyhow!("The RDP client {peer} could not be permitted: {e}"))?,
+        );
+        self.notices.warn(format!(
+            "The RDP client {peer} is permitted until you confirm the connection still works"
+        ));
+        Ok(())
+    }
+
+    fn render_peer_permit_bar(&mut self, ui: &mut egui::Ui) {
+        let Some(permit) = &self.peer_permit else {
+            return;
+        };
+        let peer = permit.peer;
+        ui.horizontal(|ui| {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("● Temporary permit for the RDP client {peer}"),
+            );
+            if ui
+                .button("Connection works, remove it")
+                .on_hover_text("If the new rules block RDP, the next connection fails")
+                .clicked()
+            {
+                self.peer_permit = None;
+                self.notices
+                    .info(format!("Temporary permit for {peer} removed."));
+            }
+        });
+    }
+
+    fn render_allowances_bar(&mut self, ui: &mut egui::Ui) {
+        let mut revoke = None;
+        for (i, allow) in self.allowances.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let secs = allow.remaining().as_secs();
+                ui.colored_label(
+                    egui::Color32::LIGHT_GREEN,
+                    format!(
+                        "● Allowing {} for {}:{:02}",
+                        allow.flow,
+                        secs / 60,
+                        secs % 60
+                    ),
                 );
+                if ui.button("Revoke").clicked() {
+                    revoke = Some(i);
+                }
             });
+        }
+        if let Some(i) = revoke {
+            self.allowances.remove(i);
+            self.notices.info("Temporary allow revoked");
+            self.refresh.request();
+        }
     }
 
-    fn render_filters(&mut self, ui: &mut egui::Ui) {
-        ui.label("Current WFP Filters (subset of fields):");
-        egui::ScrollArea::vertical().show(ui, |ui| {
-            egui::Grid::new("filters_grid")
-                .striped(true)
-                .min_col_width(80.0)
-                .show(ui, |ui| {
-                    ui.heading("ID");
-                    ui.heading("Name");
-                    ui.heading("Provider");
-                    ui.heading("Layer");
-                    ui.heading("Action");
-                    ui.heading("Remote Port");
-                    ui.heading("Owned");
-                    ui.heading("Actions");
-                    ui.end_row();
+    fn render_capture_bar(&mut self, ui: &mut egui::Ui) {
+        let Some(capture) = &self.capture else {
+            return;
+        };
+        let mut stop = false;
+        ui.horizontal(|ui| {
+            ui.colored_label(egui::Color32::RED, format!("● Capturing {}", capture.scope));
+            stop = ui.button("Stop capture").clicked();
+        });
+        if stop {
+            if let Some(capture) = self.capture.take() {
+                match capture.stop() {
+                    Ok(path) => self
+                        .notices
+                        .info(format!("Capture saved to {}", path.display())),
+                    Err(err) => self
+                        .notices
+                        .error(format!("Stopping capture failed: {err}")),
+                }
+            }
+        }
+    }
 
-                    for filter in &self.filters {
-                        ui.label(filter.id.to_string());
-                        ui.label(&filter.name);
-                        ui.label(&filter.provider);
-                        ui.label(&filter.layer);
-                        ui.label(filter.action.as_str());
-                        ui.label(
-                            filter
-                                .remote_port
-                                .map(|p| p.to_string())
-                                .unwrap_or_else(|| "-".into()),
-                        );
-                        ui.label(if filter.owned_by_app { "Yes" } else { "No" });
-                        ui.horizontal(|ui| {
-                            let can_edit = filter.owned_by_app && filter.remote_port.is_some();
-                            if ui
-                                .add_enabled(can_edit, egui::Button::new("Edit"))
-                                .clicked()
-                            {
-                                if let Some(port) = filter.remote_port {
-                                    self.edit_state = Some(EditState {
-                                        id: filter.id,
-                                        name: filter.name.clone(),
-                                        remote_port: port,
-                                        action: filter.action,
-                                    });
-                                }
-                            }
-                            if ui
-                                .add_enabled(filter.owned_by_app, egui::Button::new("Delete"))
-                                .clicked()
-                            {
-                                self.delete_state = Some(DeleteState {
-                                    id: filter.id,
-                                    name: filter.name.clone(),
-                                });
-                            }
-                        });
-                        ui.end_row();
+    fn apply_retention(&self) -> Result<()> {
+        let max_mb: u64 = self
+            .history_max_mb
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("History limit must be a whole number of MB"))?;
+        let max_age_days = match self.history_max_days.trim() {
+            "" => None,
+            days => Some(
+                days.parse()
+                    .map_err(|_| anyhow!("Keep days must be a whole number"))?,
+            ),
+        };
+        let mut store = EventStore::open(&EventStore::default_dir())?;
+        store.set_retention(Retention {
+            max_bytes: max_mb * MIB,
+            max_age_days,
+            ..store.retention()
+        })
+    }
+
+    fn apply_log_retention(&self) -> Result<()> {
+        let max_mb: u64 = self
+            .log_max_mb
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Log size must be a whole number of MB"))?;
+        let keep = self
+            .log_keep
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Rotated files kept must be a whole number"))?;
+        let max_age_days = match self.log_max_days.trim() {
+            "" => None,
+            days => Some(
+                days.parse()
+                    .map_err(|_| anyhow!("Keep days must be a whole number"))?,
+            ),
+        };
+        let retention = LogRetention {
+            max_bytes: max_mb * MIB,
+            keep,
+            max_age_days,
+        };
+        retention.save()?;
+        log_rotation::rotate(&alerts::alert_log_path(), &retention)
+    }
+
+    fn export_events(&self) -> Result<(usize, String)> {
+        let range = self.event_filter.to_query()?.range;
+        let path = match self.event_export_path.trim() {
+            "" => format!("events.{}", self.event_export_format.extension()),
+            path => path.to_string(),
+        };
+        let count = event_export::export_events_file(
+            &self.net_events,
+            range,
+            Path::new(&path),
+            Some(self.event_export_format),
+        )?;
+        Ok((count, path))
+    }
+
+    /// Views popped out of the main window, each in its own viewport.
+    fn render_detached(&mut self, ctx: &egui::Context) {
+        if self.detached.events {
+            self.detached.events = detached_window(
+                ctx,
+                egui::Id::new("events_window"),
+                "Network events",
+                |ui| self.net_events_body(ui),
+            );
+        }
+        if self.detached.chart {
+            self.detached.chart = detached_window(
+                ctx,
+                egui::Id::new("chart_window"),
+                "Connections chart",
+                |ui| self.chart_body(ui),
+            );
+        }
+        for id in self.detached.filters.clone() {
+            let filter = self.filters.iter().find(|f| f.id == id).cloned();
+            let title = match &filter {
+                Some(filter) => format!("Filter {id}: {}", filter.name),
+                None => format!("Filter {id}"),
+            };
+            let open = detached_window(ctx, egui::Id::new(("filter_window", id)), &title, |ui| {
+                let Some(filter) = &filter else {
+                    ui.label("This filter no longer exists.");
+                    return;
+                };
+                filter_details(ui, filter, &self.sublayer_details);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    filter_row_actions(ui, filter, &mut self.edit_state, &mut self.delete_state);
+                });
+            });
+            if !open {
+                self.detached.filters.retain(|&open_id| open_id != id);
+            }
+        }
+    }
+
+    fn render_diagnosis_window(&mut self, ctx: &egui::Context) {
+        let Some(diagnosis) = &self.diagnosis else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Why was this blocked/allowed?")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (idx, step) in diagnosis.steps.iter().enumerate() {
+                        ui.strong(format!("{}. {}", idx + 1, step.title));
+                        for detail in &step.details {
+                            ui.label(detail);
+                        }
+                        ui.add_space(6.0);
                     }
                 });
-        });
+                if let Some(filter) = &diagnosis.deciding {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        filter_row_actions(
+                            ui,
+                            filter,
+                            &mut self.edit_state,
+                            &mut self.delete_state,
+                        );
+                    });
+                }
+            });
+        if !open {
+            self.diagnosis = None;
+        }
     }
 
-    fn render_metadata(&self, ui: &mut egui::Ui) {
-        egui::CollapsingHeader::new("Providers").show(ui, |ui| {
-            for item in &self.providers {
-                ui.label(format!("{} — {}", format_guid(item.key), item.name));
-                if let Some(desc) = &item.description {
-                    ui.label(egui::RichText::new(desc).small());
+    fn render_coexistence(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Third-party firewall coexistence").show(ui, |ui| {
+            ui.label(
+                "Checks whether other security products' sublayers or callouts \
+                 can override our block rules.",
+            );
+            if ui.button("Run report").clicked() {
+                match self
+                    .hosts
+                    .open()
+                    .and_then(|eng| CoexistenceReport::collect(&eng))
+                {
+                    Ok(report) => {
+                        self.notices.info(format!(
+                            "Coexistence report: {} warning(s)",
+                            report.warnings.len()
+                        ));
+                        self.coexistence_report = report.to_string();
+                    }
+                    Err(err) => self
+                        .notices
+                        .error(format!("Coexistence report failed: {err}")),
                 }
             }
+            if !self.coexistence_report.is_empty() {
+                ui.label(egui::RichText::new(&self.coexistence_report).monospace());
+            }
         });
-        egui::CollapsingHeader::new("Sublayers").show(ui, |ui| {
-            for item in &self.sublayers {
-                ui.label(format!("{} — {}", format_guid(item.key), item.name));
-                if let Some(desc) = &item.description {
-                    ui.label(egui::RichText::new(desc).small());
+    }
+
+    /// Owned rules without hits or with stale hostname addresses, any of
+    /// which can be ticked and removed together.
+    fn render_unused_rules(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Unused rules").show(ui, |ui| {
+            if self.hosts.active().name.is_some() {
+                ui.weak("Hits and hostnames are only recorded for this machine.");
+                return;
+            }
+            ui.horizontal(|ui| {
+                ui.label("No hits in (days):");
+                ui.add(egui::TextEdit::singleline(&mut self.unused_days).desired_width(40.0));
+                if ui.button("Run report").clicked() {
+                    let report = self
+                        .unused_days
+                        .trim()
+                        .parse()
+                        .map_err(|_| anyhow!("Days must be a number"))
+                        .and_then(|days| UnusedReport::build(&self.filters, days));
+                    match report {
+                        Ok(report) => {
+                            self.notices.info(format!(
+                                "{} idle and {} stale rule(s)",
+                                report.idle.len(),
+                                report.stale.len()
+                            ));
+                            self.unused = Some((report, Vec::new()));
+                        }
+                        Err(err) => self
+                            .notices
+                            .error(format!("Unused rule report failed: {err}")),
+                    }
                 }
+            });
+            let Some((report, selected)) = &mut self.unused else {
+                return;
+            };
+            if report.is_empty() {
+                ui.label("Every owned rule is in use.");
+                return;
             }
-        });
-        egui::CollapsingHeader::new("Layers").show(ui, |ui| {
-            for item in &self.layers {
-                ui.label(format!("{} — {}", format_guid(item.key), item.name));
-                if let Some(desc) = &item.description {
-                    ui.label(egui::RichText::new(desc).small());
+            let mut tick = |ui: &mut egui::Ui, key: Uuid, text: String| {
+                let mut on = selected.contains(&key);
+                if ui.checkbox(&mut on, text).changed() {
+                    selected.retain(|k| *k != key);
+                    if on {
+                        selected.push(key);
+                    }
+                }
+            };
+            if !report.idle.is_empty() {
+                ui.strong(format!("No hits in {} days", report.days));
+                for rule in &report.idle {
+                    tick(
+                        ui,
+                        rule.key,
+                        format!("{} ({})", rule.name, rule.hits.summary()),
+                    );
+                }
+            }
+            if !report.stale.is_empty() {
+                ui.strong("Hostnames no longer resolve to the installed addresses");
+                for rule in &report.stale {
+                    let text = format!(
+                        "{} ({}): {}",
+                        rule.name,
+                        rule.hosts.join(", "),
+                        rule.detail()
+                    );
+                    tick(ui, rule.key, text);
                 }
             }
+            ui.horizontal(|ui| {
+                if ui.button("Select all").clicked() {
+                    *selected = report.keys();
+                }
+                let remove = ui
+                    .add_enabled(
+                        !selected.is_empty() && !wfp::is_read_only(),
+                        egui::Button::new(format!("Remove {} selected", selected.len())),
+                    )
+                    .clicked();
+                if remove {
+                    let keys = std::mem::take(selected);
+                    match self
+                        .hosts
+                        .open()
+                        .and_then(|eng| unused::remove_rules(&eng, &keys))
+                    {
+                        Ok(removed) => {
+                            self.refresh.request();
+                            report.idle.retain(|r| !keys.contains(&r.key));
+                            report.stale.retain(|r| !keys.contains(&r.key));
+                            self.notices
+                                .info(format!("Removed {removed} unused rule(s)."))
+                        }
+                        Err(err) => self
+                            .notices
+                            .error(format!("Removing unused rules failed: {err}")),
+                    }
+                }
+            });
         });
     }
 
     fn render_edit_window(&mut self, ctx: &egui::Context) {
-        if let Some(edit) = &mut self.edit_state {
-            let mut open = true;
-            egui::Window::new(format!("Edit Filter {}", edit.id))
-                .open(&mut open)
-                .show(ctx, |ui| {
-                    ui.label(format!("Editing filter '{}'", edit.name));
-                    ui.label("Name:");
-                    ui.text_edit_singleline(&mut edit.name);
-                    ui.label("Remote TCP Port:");
-                    ui.add(egui::DragValue::new(&mut edit.remote_port).clamp_range(1..=65535));
-                    ui.label("Action:");
-                    egui::ComboBox::from_id_source("action_combo")
-                        .selected_text(edit.action.as_str())
-                        .show_ui(ui, |ui| {
-                            ui.selectable_value(&mut edit.action, WfpAction::Permit, "Permit");
-                            ui.selectable_value(&mut edit.action, WfpAction::Block, "Block");
-                        });
-                    ui.horizontal(|ui| {
-                        if ui.button("Save").clicked() {
-                            let result = Engine::open().and_then(|eng| {
-                                eng.update_simple_tcp_filter_v4(
-                                    edit.id,
-                                    &edit.name,
-                                    edit.remote_port,
-                                    edit.action,
-                                )
+        let Some(edit) = &mut self.edit_state else {
+            return;
+        };
+        let mut open = true;
+        let mut done = false;
+        let title = if edit.copy {
+            format!("Duplicate Filter {}", edit.id)
+        } else {
+            format!("Edit Filter {}", edit.id)
+        };
+        egui::Window::new(title).open(&mut open).show(ctx, |ui| {
+            if edit.copy {
+                ui.label("Saving adds a new filter; the original is left as it is.");
+            }
+            ui.label(format!("Layer: {}", edit.filter.layer));
+            egui::Grid::new("edit_grid").num_columns(2).show(ui, |ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut edit.name);
+                ui.end_row();
+                ui.label("Action:");
+                egui::ComboBox::from_id_source("action_combo")
+                    .selected_text(edit.action.as_str())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut edit.action, WfpAction::Permit, "Permit");
+                        ui.selectable_value(&mut edit.action, WfpAction::Block, "Block");
+                        // Only back to the callout the filter already had.
+                        if edit.filter.callout.is_some() {
+                            ui.selectable_value(&mut edit.action, WfpAction::Callout, "Callout");
+                        }
+                    });
+                ui.end_row();
+            });
+            ui.separator();
+            if edit.rows.is_empty() {
+                ui.label("No conditions: the filter matches all traffic at this layer.");
+            }
+            let (v6, schema, fields) = (edit.v6(), edit.schema(), edit.fields());
+            let mut remove = None;
+            let mut valid = true;
+            egui::Grid::new("conditions_grid")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    for (idx, row) in edit.rows.iter_mut().enumerate() {
+                        egui::ComboBox::from_id_source(("condition_field", idx))
+                            .selected_text(row.field.label())
+                            .show_ui(ui, |ui| {
+                                for field in &fields {
+                                    ui.selectable_value(&mut row.field, *field, field.label());
+                                }
                             });
-                            self.status = match result {
-                                Ok(_) => {
-                                    self.refresh_pending = true;
-                                    "Filter updated.".into()
+                        egui::ComboBox::from_id_source(("condition_match", idx))
+                            .selected_text(row.match_type.to_string())
+                            .show_ui(ui, |ui| {
+                                for match_type in MatchType::ALL {
+                                    ui.selectable_value(
+                                        &mut row.match_type,
+                                        match_type,
+                                        match_type.to_string(),
+                                    );
                                 }
-                                Err(err) => format!("Update failed: {err}"),
-                            };
+                            });
+                        ui.add(egui::TextEdit::singleline(&mut row.value).desired_width(240.0))
+                            .on_hover_text(
+                                "Numbers, protocol names, addresses or networks, app \
+                                 paths, SDDL, 0x bytes, or low..=high for a range",
+                            );
+                        match row.condition(v6, schema) {
+                            Ok(_) => {
+                                ui.label("");
+                            }
+                            Err(err) => {
+                                valid = false;
+                                ui.colored_label(egui::Color32::LIGHT_RED, err.to_string());
+                            }
                         }
-                        if ui.button("Cancel").clicked() {
-                            open = false;
+                        if ui.button("✖").on_hover_text("Remove").clicked() {
+                            remove = Some(idx);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(idx) = remove {
+                edit.rows.remove(idx);
+            }
+            if ui.button("Add condition").clicked() {
+                let unused = fields
+                    .iter()
+                    .find(|f| !edit.rows.iter().any(|row| row.field == **f));
+                edit.rows
+                    .push(ConditionRow::new(*unused.unwrap_or(&fields[0])));
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.add_enabled(valid, egui::Button::new("Save")).clicked() {
+                    let result = self.hosts.open().and_then(|eng| {
+                        NamingPolicy::load()?.check(&edit.name)?;
+                        let builder = edit.builder()?;
+                        self.safety
+                            .check_lockout(&eng, std::slice::from_ref(&builder))?;
+                        edit.save(&eng, &builder)
+                    });
+                    match result {
+                        Ok(()) => {
+                            self.refresh.request();
+                            self.notices.info(if edit.copy {
+                                format!("Added '{}'.", edit.name)
+                            } else {
+                                "Filter updated.".into()
+                            });
+                            done = true;
+                        }
+                        Err(err) => self.notices.error(format!("Update failed: {err}")),
+                    }
+                }
+                if ui.button("Cancel").clicked() {
+                    done = true;
+                }
+            });
+        });
+        if !open || done {
+            self.edit_state = None;
+        }
+    }
+
+    /// Changes the action, weight, persistence or sublayer of every ticked
+    /// filter in one transaction.
+    fn render_bulk_edit_window(&mut self, ctx: &egui::Context) {
+        let Some(state) = &mut self.bulk_edit else {
+            return;
+        };
+        let mut open = true;
+        let mut done = false;
+        let count = self.selected_filters.len();
+        egui::Window::new(format!("Bulk Edit {count} Filter(s)"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label("Attributes left unchanged keep each filter's own value.");
+                egui::Grid::new("bulk_edit_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Action:");
+                        egui::ComboBox::from_id_source("bulk_action")
+                            .selected_text(state.edit.action.map_or("Unchanged", WfpAction::as_str))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut state.edit.action, None, "Unchanged");
+                                for action in [WfpAction::Permit, WfpAction::Block] {
+                                    ui.selectable_value(
+                                        &mut state.edit.action,
+                                        Some(action),
+                                        action.as_str(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Weight:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut state.weight)
+                                .hint_text("unchanged")
+                                .desired_width(120.0),
+                        )
+                        .on_hover_text(
+                            "Leave empty to keep each weight, or to pick a new one in the \
+                             new action's tier",
+                        );
+                        ui.end_row();
+                        ui.label("Persistent:");
+                        ui.horizontal(|ui| {
+                            ui.radio_value(&mut state.edit.persistent, None, "Unchanged");
+                            ui.radio_value(&mut state.edit.persistent, Some(true), "Yes");
+                            ui.radio_value(&mut state.edit.persistent, Some(false), "No");
+                        });
+                        ui.end_row();
+                        ui.label("Sublayer:");
+                        let group = match state.edit.sublayer {
+                            None => "Unchanged",
+                            Some(key) if key == wfp::SUBLAYER_KEY => "Default sublayer",
+                            Some(key) => self
+                                .sublayer_details
+                                .iter()
+                                .find(|s| s.key == key)
+                                .map_or("?", |s| s.name.as_str()),
+                        };
+                        egui::ComboBox::from_id_source("bulk_sublayer")
+                            .selected_text(group)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut state.edit.sublayer, None, "Unchanged");
+                                ui.selectable_value(
+                                    &mut state.edit.sublayer,
+                                    Some(wfp::SUBLAYER_KEY),
+                                    "Default sublayer",
+                                );
+                                for sublayer in self.sublayer_details.iter().filter(|s| s.ours) {
+                                    ui.selectable_value(
+                                        &mut state.edit.sublayer,
+                                        Some(sublayer.key),
+                                        &sublayer.name,
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Apply").clicked() {
+                        let selected: Vec<FilterSummary> = self
+                            .filters
+                            .iter()
+                            .filter(|f| self.selected_filters.contains(&f.id))
+                            .cloned()
+                            .collect();
+                        let result = state.edit().and_then(|edit| {
+                            let engine = self.hosts.open()?;
+                            let builders = selected
+                                .iter()
+                                .map(|f| edit.apply(f))
+                                .collect::<Result<Vec<_>>>()?;
+                            self.safety.check_lockout(&engine, &builders)?;
+                            let changed = engine.bulk_edit(&selected, &edit)?;
+                            Ok((changed, edit.describe()))
+                        });
+                        match result {
+                            Ok((changed, what)) => {
+                                self.notices
+                                    .info(format!("Set {what} on {changed} filter(s)."));
+                                self.selected_filters.clear();
+                                self.refresh.request();
+                                done = true;
+                            }
+                            Err(err) => self.notices.error(format!("Bulk edit failed: {err}")),
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        done = true;
+                    }
+                });
+            });
+        if !open || done {
+            self.bulk_edit = None;
+        }
+    }
+
+    /// Summary of owned rules that went missing or changed since the last
+    /// run, with a way to put them back.
+    fn render_consistency_window(&mut self, ctx: &egui::Context) {
+        let Some(report) = &self.consistency else {
+            return;
+        };
+        let mut repair = false;
+        let mut keep = false;
+        egui::Window::new("Rules changed since the last run")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    "These rules were installed when the app last ran and are now \
+                     missing or different on this machine.",
+                );
+                egui::ScrollArea::vertical()
+                    .max_height(240.0)
+                    .show(ui, |ui| {
+                        for rule in &report.diff.removed {
+                            ui.label(format!("Missing: {}", rule.name));
+                        }
+                        for change in &report.diff.changed {
+                            ui.label(format!(
+                                "Changed: {} ({})",
+                                change.before.name,
+                                change.fields().join(", ")
+                            ));
                         }
                     });
+                ui.horizontal(|ui| {
+                    let count = report.diff.removed.len() + report.diff.changed.len();
+                    repair = ui.button(format!("Re-create {count} rule(s)")).clicked();
+                    keep = ui
+                        .button("Keep current rules")
+                        .on_hover_text("Accept the rules as they are now")
+                        .clicked();
                 });
-            if !open {
-                self.edit_state = None;
+            });
+        if repair {
+            match self.hosts.hosts[0]
+                .open()
+                .and_then(|eng| report.repair(&eng))
+            {
+                Ok(summary) => {
+                    self.notices.info(format!(
+                        "Repaired rules: {} added, {} updated.",
+                        summary.added, summary.updated
+                    ));
+                    keep = true;
+                }
+                // The report stays up so the repair can be tried again.
+                Err(err) => self.notices.error(format!("Repair failed: {err}")),
             }
         }
+        if keep {
+            self.consistency = None;
+            self.refresh.request();
+        }
     }
 
     fn render_delete_window(&mut self, ctx: &egui::Context) {
+        if !self.safety.confirms() {
+            if let Some(delete) = self.delete_state.take() {
+                self.delete_rule(delete.key);
+            }
+            return;
+        }
         if let Some(delete) = &self.delete_state {
             let mut open = true;
+            let mut cancelled = false;
             let id = delete.id;
+            let key = delete.key;
             let name = delete.name.clone();
             egui::Window::new("Confirm delete")
                 .collapsible(false)
@@ -323,37 +5795,355 @@ impl AppState {
                     ui.label(format!("Delete filter '{}' (ID {})?", name, id));
                     ui.horizontal(|ui| {
                         if ui.button("Delete").clicked() {
-                            let result = Engine::open().and_then(|eng| eng.delete_filter_by_id(id));
-                            self.status = match result {
-                                Ok(_) => {
-                                    self.refresh_pending = true;
-                                    "Filter deleted.".into()
-                                }
-                                Err(err) => format!("Delete failed: {err}"),
-                            };
+                            self.delete_rule(key);
                         }
                         if ui.button("Cancel").clicked() {
-                            open = false;
+                            cancelled = true;
                         }
                     });
                 });
-            if !open {
+            if !open || cancelled {
                 self.delete_state = None;
             }
         }
     }
+
+    fn delete_rule(&mut self, key: GUID) {
+        match self
+            .hosts
+            .open()
+            .and_then(|eng| eng.delete_filter_by_key(key))
+        {
+            Ok(()) => {
+                self.refresh.request();
+                self.notices.info("Filter deleted.")
+            }
+            Err(err) => self.notices.error(format!("Delete failed: {err}")),
+        }
+    }
 }
 
+const MIB: u64 = 1024 * 1024;
+
+/// Script output lines kept before the oldest are dropped.
+const MAX_SCRIPT_LOG: usize = 1000;
+
+const ALERT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_FIRED_ALERTS: usize = 500;
+
+/// Rules and apps offered as chart toggles, busiest first.
+const MAX_CHART_TOGGLES: usize = 10;
+
+/// Live log entries kept before the oldest are dropped.
+const MAX_LIVE_LOG: usize = 1000;
+
+/// Newest net events listed in the events grid.
+const MAX_EVENTS_SHOWN: usize = 200;
+
+/// How often to look again while BFE is starting or stopping.
+const BFE_POLL: Duration = Duration::from_secs(2);
+
 fn format_guid(guid: GUID) -> String {
     format!("{guid:?}")
 }
 
-fn main() -> Result<()> {
+fn format_conditions(conditions: &[Condition]) -> String {
+    if conditions.is_empty() {
+        return "No conditions (matches all traffic at this layer)".into();
+    }
+    conditions
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_port(port: Option<u16>) -> String {
+    port.map(|p| p.to_string()).unwrap_or_else(|| "-".into())
+}
+
+fn severity_color(ui: &egui::Ui, severity: Severity) -> egui::Color32 {
+    match severity {
+        Severity::Info => ui.visuals().text_color(),
+        Severity::Warning => egui::Color32::YELLOW,
+        Severity::Error => egui::Color32::LIGHT_RED,
+    }
+}
+
+/// Edit/Delete buttons shared by every filter view. Only owned filters can be
+/// changed. Editing acts on the filter itself; deleting on the whole logical
+/// rule it belongs to.
+fn filter_row_actions(
+    ui: &mut egui::Ui,
+    filter: &FilterSummary,
+    edit_state: &mut Option<EditState>,
+    delete_state: &mut Option<DeleteState>,
+) {
+    if ui
+        .add_enabled(can_change(filter), egui::Button::new("Edit"))
+        .clicked()
+    {
+        *edit_state = Some(EditState::of(filter));
+    }
+    if ui
+        .add_enabled(can_change(filter), egui::Button::new("Delete"))
+        .clicked()
+    {
+        *delete_state = Some(DeleteState::of(filter));
+    }
+}
+
+/// Right-click menu on a filter row. Edit and delete open their dialogs
+/// here; commands needing the rest of the app state are returned.
+fn filter_context_menu(
+    ui: &mut egui::Ui,
+    filter: &FilterSummary,
+    edit_state: &mut Option<EditState>,
+    delete_state: &mut Option<DeleteState>,
+) -> Option<RowAction> {
+    let mut action = None;
+    let editable = can_change(filter);
+    let exportable = filter.owned_by_app && filter.remote_port.is_some();
+    if ui
+        .add_enabled(editable, egui::Button::new("Edit…"))
+        .clicked()
+    {
+        ui.close_menu();
+        *edit_state = Some(EditState::of(filter));
+    }
+    if ui
+        .add_enabled(can_change(filter), egui::Button::new("Delete…"))
+        .clicked()
+    {
+        ui.close_menu();
+        *delete_state = Some(DeleteState::of(filter));
+    }
+    if ui
+        .add_enabled(editable, egui::Button::new("Duplicate"))
+        .clicked()
+    {
+        ui.close_menu();
+        action = Some(RowAction::Duplicate(filter.id));
+    }
+    if ui
+        .add_enabled(exportable, egui::Button::new("Export"))
+        .clicked()
+    {
+        ui.close_menu();
+        action = Some(RowAction::Export(filter.id));
+    }
+    ui.separator();
+    if ui.button("Copy GUID").clicked() {
+        ui.close_menu();
+        ui.ctx().copy_text(format_guid(filter.key));
+    }
+    if ui
+        .add_enabled(
+            filter.action != WfpAction::Callout && !wfp::is_read_only(),
+            egui::Button::new("Create opposite rule"),
+        )
+        .clicked()
+    {
+        ui.close_menu();
+        action = Some(RowAction::Opposite(filter.id));
+    }
+    if ui
+        .add_enabled(filter.owned_by_app, egui::Button::new("Pin to Favorites"))
+        .clicked()
+    {
+        ui.close_menu();
+        action = Some(RowAction::Pin(filter.id));
+    }
+    if ui.button("Show matching events").clicked() {
+        ui.close_menu();
+        action = Some(RowAction::ShowEvents(filter.id));
+    }
+    if ui.button("Open details in new window").clicked() {
+        ui.close_menu();
+        action = Some(RowAction::Details(filter.id));
+    }
+    if ui.button("Reset hit counter").clicked() {
+        ui.close_menu();
+        action = Some(RowAction::ResetHits(filter.id));
+    }
+    action
+}
+
+/// Everything known about one filter, for its detail window.
+fn filter_details(ui: &mut egui::Ui, filter: &FilterSummary, sublayers: &[SublayerInfo]) {
+    let yes_no = |on: bool| if on { "Yes" } else { "No" };
+    egui::Grid::new("filter_details_grid")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            let mut row = |label: &str, value: String| {
+                ui.strong(label);
+                ui.label(value);
+                ui.end_row();
+            };
+            row("ID", filter.id.to_string());
+            row("Key", format_guid(filter.key));
+            row(
+                "Rule key",
+                filter.rule_key.map(format_guid).unwrap_or_default(),
+            );
+            row("Name", filter.name.clone());
+            row("Provider", filter.provider.clone());
+            row("Layer", filter.layer.clone());
+            row("Sublayer", filter.sublayer.clone());
+            row("Action", filter.action.as_str().into());
+            row(
+                "Weight",
+                filter
+                    .effective_weight
+                    .map_or_else(|| "-".into(), |w| w.to_string()),
+            );
+            row("Persistent", yes_no(filter.persistent).into());
+            row("Boot-time", yes_no(filter.boot_time).into());
+            row("Hard permit", yes_no(filter.clear_action_right).into());
+            row("Owned", yes_no(filter.owned_by_app).into());
+            if let Some(callout) = filter.callout {
+                row(
+                    "Callout",
+                    format!("{} ({:?})", format_guid(callout.key), callout.kind),
+                );
+            }
+            if let Some(context) = filter.provider_context_key {
+                row("Provider context", format_guid(context));
+            }
+        });
+    ui.separator();
+    ui.strong("Conditions");
+    if filter.conditions.is_empty() {
+        ui.label(format_conditions(&filter.conditions));
+    }
+    for (i, condition) in filter.conditions.iter().enumerate() {
+        ui.label(condition.to_string());
+        // Byte values also show their bytes and what they likely say.
+        let Some(bytes) = condition.value.bytes() else {
+            continue;
+        };
+        ui.indent(("condition_bytes", i), |ui| {
+            if let Some(text) = condition
+                .value
+                .decoded()
+                .filter(|text| *text != condition.value.to_string())
+            {
+                ui.label(format!("Decoded: {text}"));
+            }
+            ui.label(egui::RichText::new(wfp::hex_dump(bytes)).monospace());
+        });
+    }
+    ui.separator();
+    ui.strong("Arbitration");
+    ui.label(coexistence::explain_arbitration(filter, sublayers));
+}
+
+/// Shows `add_contents` in an OS window of its own, or in a floating window
+/// where the platform has only one. Returns false once the user closes it.
+fn detached_window(
+    ctx: &egui::Context,
+    id: egui::Id,
+    title: &str,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) -> bool {
+    ctx.show_viewport_immediate(
+        egui::ViewportId::from_hash_of(id),
+        egui::ViewportBuilder::default()
+            .with_title(title)
+            .with_inner_size([720.0, 480.0]),
+        |ctx, class| {
+            if class == egui::ViewportClass::Embedded {
+                let mut open = true;
+                egui::Window::new(title)
+                    .id(id)
+                    .open(&mut open)
+                    .show(ctx, add_contents);
+                return open;
+            }
+            egui::CentralPanel::default().show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, add_contents)
+            });
+            !ctx.input(|i| i.viewport().close_requested())
+        },
+    )
+}
+
+/// Stands in for a view while it is in its own window.
+fn detached_placeholder(ui: &mut egui::Ui, detached: &mut bool) {
+    ui.horizontal(|ui| {
+        ui.weak("Shown in a separate window.");
+        if ui.button("Bring back").clicked() {
+            *detached = false;
+        }
+    });
+}
+
+/// Filters one snapshot has over another, grouped by their provider.
+fn render_snapshot_diff(ui: &mut egui::Ui, label: &str, diff: &SnapshotDiff) {
+    ui.label(format!(
+        "{label}: {} added, {} removed, {} changed, {} unchanged",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        diff.unchanged
+    ));
+    egui::ScrollArea::vertical()
+        .id_source("snapshot_diff")
+        .max_height(240.0)
+        .show(ui, |ui| {
+            for provider in diff.providers() {
+                egui::CollapsingHeader::new(provider)
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for filter in diff.added.iter().filter(|f| f.provider == provider) {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(0, 160, 0),
+                                format!("+ {}", describe_filter(filter)),
+                            );
+                        }
+                        for filter in diff.removed.iter().filter(|f| f.provider == provider) {
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                format!("- {}", describe_filter(filter)),
+                            );
+                        }
+                        for change in diff.changed.iter().filter(|c| c.after.provider == provider) {
+                            ui.label(format!("~ {}", describe_filter(&change.after)));
+                            for field in change.fields() {
+                                let (before, after) = change.values(field);
+                                ui.label(format!("    {field}: {before} -> {after}"));
+                            }
+                        }
+                    });
+            }
+        });
+}
+
+/// Owned filters can be changed, unless the app runs read-only.
+fn can_change(filter: &FilterSummary) -> bool {
+    filter.owned_by_app && !wfp::is_read_only()
+}
+
+fn main() -> Result<ExitCode> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // `--read-only` on its own starts the GUI as a viewer.
+    if args.iter().any(|arg| arg != "--read-only") {
+        return Ok(cli::run(&args));
+    }
+    if !args.is_empty() {
+        wfp::set_read_only();
+    }
+
+    let Some(_instance) = instance::acquire()? else {
+        return Ok(ExitCode::SUCCESS);
+    };
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
-        "SLS WFP Manager",
+        instance::WINDOW_TITLE,
         native_options,
         Box::new(|_| Box::<AppState>::default()),
-    )?;
-    Ok(())
+    )
+    .map_err(|e| anyhow!("Failed to start the GUI: {e}"))?;
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    wfp::{Engine, FilterBuilder, FilterConfig, FilterSummary},
+};
+
+const POLICY_FILE: &str = "naming_policy.json";
+
+/// What rule names must look like, for environments where they feed into
+/// log pipelines. Empty fields impose nothing, so the default allows any
+/// name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NamingPolicy {
+    /// Text every name starts with, e.g. `CORP-FW-`.
+    pub prefix: String,
+    /// Longest name allowed, in characters, prefix included.
+    pub max_length: Option<usize>,
+    /// Characters no name may contain, e.g. `|,;"`.
+    pub forbidden: String,
+}
+
+impl NamingPolicy {
+    /// The saved policy, or no policy before one is saved.
+    pub fn load() -> Result<Self> {
+        config::load_settings(POLICY_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.validate()?;
+        config::save_settings(POLICY_FILE, self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fails when no name could follow the policy.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(c) = self.prefix.chars().find(|c| self.forbidden.contains(*c)) {
+            return Err(anyhow!("The prefix contains the forbidden character {c:?}"));
+        }
+        match self.max_length {
+            Some(0) => Err(anyhow!("The maximum length must be at least 1")),
+            Some(max) if self.prefix.chars().count() >= max => Err(anyhow!(
+                "The prefix leaves no room for a name within {max} characters"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// What `name` breaks, e.g. `must start with "CORP-"`; empty when it
+    /// follows the policy.
+    pub fn violations(&self, name: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        if !name.starts_with(&self.prefix) {
+            out.push(format!("must start with {:?}", self.prefix));
+        }
+        if let Some(max) = self.max_length.filter(|max| name.chars().count() > *max) {
+            out.push(format!("must be at most {max} characters"));
+        }
+        let mut forbidden: Vec<char> = name
+            .chars()
+            .filter(|c| self.forbidden.contains(*c))
+            .collect();
+        forbidden.dedup();
+        if !forbidden.is_empty() {
+            let chars: String = forbidden.into_iter().collect();
+            out.push(format!("must not contain {chars:?}"));
+        }
+        out
+    }
+
+    pub fn check(&self, name: &str) -> Result<()> {
+        match self.violations(name).as_slice() {
+            [] => Ok(()),
+            broken => Err(anyhow!(
+                "Rule name {name:?} breaks the naming policy: it {}",
+                broken.join(", ")
+            )),
+        }
+    }
+
+    /// `name` changed as little as it takes to follow the policy: forbidden
+    /// characters dropped, the prefix added, then the end cut off.
+    pub fn conform(&self, name: &str) -> String {
+        let mut name: String = name
+            .chars()
+            .filter(|c| !self.forbidden.contains(*c))
+            .collect();
+        if !name.starts_with(&self.prefix) {
+            name.insert_str(0, &self.prefix);
+        }
+        if let Some(max) = self.max_length {
+            // Only what follows the prefix is cut and trimmed, so a prefix
+            // ending in a space keeps it.
+            let room = max.saturating_sub(self.prefix.chars().count());
+            let rest: String = name[self.prefix.len()..].chars().take(room).collect();
+            name = format!("{}{}", self.prefix, rest.trim_end());
+        }
+        name
+    }
+
+    /// Our filters whose names break the policy, each with the name it
+    /// would follow it under.
+    pub fn renames(&self, filters: &[FilterSummary]) -> Vec<(FilterSummary, String)> {
+        filters
+            .iter()
+            .filter(|f| f.owned_by_app && !self.violations(&f.name).is_empty())
+            .map(|f| (f.clone(), self.conform(&f.name)))
+            .collect()
+    }
+}
+
+/// `name` for a rule the app creates on its own, such as the panic button
+/// or a one-off allow, conformed to the saved [`NamingPolicy`] so the
+/// engine accepts it.
+pub fn preset_name(name: &str) -> String {
+    NamingPolicy::load().unwrap_or_default().conform(name)
+}
+
+/// Checks each rule, and its name against the saved [`NamingPolicy`].
+pub fn validate_configs(configs: &[FilterConfig]) -> Result<()> {
+    configs.iter().try_for_each(FilterConfig::validate)?;
+    let policy = NamingPolicy::load()?;
+    configs.iter().try_for_each(|c| policy.check(&c.name))
+}
+
+/// Renames our filters as [`NamingPolicy::renames`] lists them, in one
+/// transaction, and returns how many were renamed.
+pub fn migrate(engine: &Engine, renames: &[(FilterSummary, String)]) -> Result<usize> {
+    let builders: Vec<FilterBuilder> = renames
+        .iter()
+        .map(|(filter, name)| FilterBuilder::rebuild(filter).name(name))
+        .collect();
+    if builders.is_empty() {
+        return Ok(0);
+    }
+    engine.reinstall_filters(&builders, "renamed by the naming policy")
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+
+/// Messages kept for the history, oldest dropped first.
+const MAX_HISTORY: usize = 200;
+/// Toasts on screen at once; more are only listed in the history.
+const MAX_TOASTS: usize = 4;
+const TOAST_DURATION: Duration = Duration::from_secs(8);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Info => "Info",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub severity: Severity,
+    pub message: String,
+    /// When it last arrived.
+    pub time: DateTime<Local>,
+    /// How many more times it arrived right after itself.
+    pub repeats: u32,
+    /// Shown as a toast since then, until it times out.
+    toast: Option<Instant>,
+}
+
+/// Status messages from the UI and from background tasks, newest first.
+/// The latest stays in the status line; warnings and errors also show as
+/// toasts, so one is not lost when another replaces it, and every message
+/// stays in the history. A message repeating itself is counted rather
+/// than listed again.
+#[derive(Default)]
+pub struct Notifications {
+    history: VecDeque<Notification>,
+    /// Warnings and errors that arrived since the history was last opened.
+    unread: usize,
+}
+
+impl Notifications {
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Severity::Info, message.into());
+    }
+
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.push(Severity::Warning, message.into());
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(Severity::Error, message.into());
+    }
+
+    /// A success's message as info, a failure's as an error.
+    pub fn outcome(&mut self, outcome: Result<String, String>) {
+        match outcome {
+            Ok(message) => self.info(message),
+            Err(message) => self.error(message),
+        }
+    }
+
+    pub fn push(&mut self, severity: Severity, message: String) {
+        let now = Instant::now();
+        if severity > Severity::Info {
+            self.unread += 1;
+        }
+        if let Some(last) = self
+            .history
+            .front_mut()
+            .filter(|n| n.severity == severity && n.message == message)
+        {
+            last.repeats += 1;
+            last.time = Local::now();
+            // A toast still up is kept up; another is not added.
+            if let Some(shown) = &mut last.toast {
+                *shown = now;
+            }
+            return;
+        }
+        let toasts = self.toasts().count();
+        self.history.push_front(Notification {
+            severity,
+            message,
+            time: Local::now(),
+            repeats: 0,
+            toast: (severity > Severity::Info && toasts < MAX_TOASTS).then_some(now),
+        });
+        self.history.truncate(MAX_HISTORY);
+    }
+
+    /// The latest message, for the status line.
+    pub fn latest(&self) -> Option<&Notification> {
+        self.history.front()
+    }
+
+    /// Every message kept, newest first.
+    pub fn history(&self) -> impl Iterator<Item = &Notification> {
+        self.history.iter()
+    }
+
+    /// Messages shown as toasts now, newest first.
+    pub fn toasts(&self) -> impl Iterator<Item = &Notification> {
+        self.history.iter().filter(|n| n.toast_shown())
+    }
+
+    /// Takes down the toasts at `indices` of [`Self::toasts`].
+    pub fn dismiss_toasts(&mut self, indices: &[usize]) {
+        for (i, notice) in self
+            .history
+            .iter_mut()
+            .filter(|n| n.toast_shown())
+            .enumerate()
+        {
+            if indices.contains(&i) {
+                notice.toast = None;
+            }
+        }
+    }
+
+    pub fn unread(&self) -> usize {
+        self.unread
+    }
+
+    pub fn mark_read(&mut self) {
+        self.unread = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.unread = 0;
+    }
+}
+
+impl Notification {
+    /// `14:03:12  Import rejected: … (×3)`.
+    pub fn line(&self) -> String {
+        let mut line = format!("{}  {}", self.time.format("%H:%M:%S"), self.message);
+        if self.repeats > 0 {
+            line += &format!(" (×{})", self.repeats + 1);
+        }
+        line
+    }
+
+    fn toast_shown(&self) -> bool {
+        self.toast
+            .is_some_and(|shown| shown.elapsed() < TOAST_DURATION)
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config::{self, RuleFormat},
+    wfp::{Engine, FilterConfig, ImportSummary},
+};
+
+const SOURCES_FILE: &str = "rule_sources.json";
+const STATE_DIR: &str = "rule_sources";
+
+/// Supplies a rule set from outside the crate: a threat-intel feed, a
+/// corporate policy server, a CMDB export. Implement it directly when
+/// linking the library, or use [`CommandSource`] to plug in any program.
+pub trait RuleSource {
+    /// Stable name, used to remember which rules the source installed.
+    fn name(&self) -> &str;
+
+    /// The complete rule set the source wants installed right now. Rules
+    /// without a key get one derived from the source and rule names.
+    fn fetch(&mut self) -> Result<Vec<FilterConfig>>;
+}
+
+/// A rule source run as a subprocess. The program prints the rule set to
+/// stdout in any format `import` accepts (JSON, YAML or TOML) and exits 0;
+/// anything else is a failed fetch and leaves the installed rules alone.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandSource {
+    pub name: String,
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl RuleSource for CommandSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch(&mut self) -> Result<Vec<FilterConfig>> {
+        let output = Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| anyhow!("Failed to run {}: {e}", self.command.display()))?;
+        if !output.status.success() {
+            bail!(
+                "{} failed ({}): {}",
+                self.name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let text = String::from_utf8(output.stdout)
+            .map_err(|_| anyhow!("{} printed output that is not UTF-8", self.name))?;
+        config::parse_rules(&text, RuleFormat::detect(None, &text))
+    }
+}
+
+/// Configured subprocess sources; a missing file means none.
+pub fn load_sources() -> Result<Vec<CommandSource>> {
+    config::load_settings(SOURCES_FILE)
+}
+
+/// Fetches `source` and reconciles its rules with what it installed last
+/// time. Rules the source stopped listing are removed; rules from other
+/// sources or added by hand are never touched.
+pub fn sync_source(engine: &Engine, source: &mut dyn RuleSource) -> Result<ImportSummary> {
+    let name = source.name().to_string();
+    let mut configs = source.fetch()?;
+    for cfg in &mut configs {
+        cfg.key.get_or_insert_with(|| derived_key(&name, &cfg.name));
+    }
+    let previous = load_state(&name)?;
+    let summary = engine.reconcile(&configs, &previous)?;
+    let keys: Vec<Uuid> = configs.iter().filter_map(|cfg| cfg.key).collect();
+    save_state(&name, &keys)?;
+    Ok(summary)
+}
+
+/// Key for a rule that arrived without one, stable across fetches as long
+/// as the rule keeps its name.
+fn derived_key(source: &str, rule: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, format!("{source}/{rule}").as_bytes())
+}
+
+fn state_path(source: &str) -> PathBuf {
+    let file: String = source
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    config::app_data_dir()
+        .join(STATE_DIR)
+        .join(format!("{file}.json"))
+}
+
+fn load_state(source: &str) -> Result<Vec<Uuid>> {
+    let path = state_path(source);
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Invalid rule source state in {}: {e}", path.display())),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+fn save_state(source: &str, keys: &[Uuid]) -> Result<()> {
+    let path = state_path(source);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| anyhow!("Failed to create {}: {e}", dir.display()))?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(keys)?)
+        .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))
+}
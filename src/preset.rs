@@ -0,0 +1,61 @@
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    config, naming,
+    wfp::{Engine, FilterBuilder, FilterSummary, GUID},
+};
+
+/// A rule the app switches on and off as a whole, such as the QUIC block.
+/// Its settings live in a file of their own so it can be applied again,
+/// and its filters are members of one rule under a fixed key, so it is
+/// found, replaced and removed as one.
+pub trait Preset: Serialize + DeserializeOwned + Default {
+    const SETTINGS_FILE: &'static str;
+    /// Made to follow the naming policy when the rule is installed.
+    const RULE_NAME: &'static str;
+    const RULE_KEY: GUID;
+
+    /// The filters of the rule, named `name`.
+    fn rule(&self, name: &str) -> Result<Vec<FilterBuilder>>;
+
+    fn load() -> Result<Self> {
+        config::load_settings(Self::SETTINGS_FILE)
+    }
+
+    fn save(&self) -> Result<()> {
+        config::save_settings(Self::SETTINGS_FILE, self)
+    }
+
+    /// The filters the rule would be installed with now.
+    fn builders(&self) -> Result<Vec<FilterBuilder>> {
+        self.rule(&naming::preset_name(Self::RULE_NAME))
+    }
+
+    /// Installs the rule, replacing the one already on, and returns the
+    /// number of filters added.
+    fn enable(&self, engine: &Engine) -> Result<usize> {
+        install(self, engine)
+    }
+
+    /// Removes the rule's filters.
+    fn disable(engine: &Engine) -> Result<()> {
+        engine.delete_filter_by_key(Self::RULE_KEY)
+    }
+
+    /// The installed filters of the rule; empty while it is off.
+    fn members(filters: &[FilterSummary]) -> Vec<&FilterSummary> {
+        filters
+            .iter()
+            .filter(|f| f.owned_by_app && f.rule_key() == Self::RULE_KEY)
+            .collect()
+    }
+}
+
+/// What [`Preset::enable`] does, for presets that do more on top.
+pub fn install<P: Preset>(preset: &P, engine: &Engine) -> Result<usize> {
+    let name = naming::preset_name(P::RULE_NAME);
+    let builders = preset.rule(&name)?;
+    engine.replace_rule(P::RULE_KEY, &name, &builders)?;
+    Ok(builders.len())
+}
@@ -0,0 +1,103 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::{config, wfp::RuleSet};
+
+/// A rule set saved under a name in [`profiles_dir`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub path: PathBuf,
+    /// Number of rules, or `None` when the file does not parse.
+    pub rules: Option<usize>,
+}
+
+/// `%ProgramData%\SLSWFP\profiles`, shared by every user of the machine,
+/// or under the temp directory when ProgramData is unset.
+pub fn profiles_dir() -> PathBuf {
+    std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("SLSWFP")
+        .join("profiles")
+}
+
+/// Every `*.json` profile, sorted by name.
+pub fn list() -> Result<Vec<Profile>> {
+    let dir = profiles_dir();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut profiles: Vec<Profile> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_string();
+            let rules = config::load_rule_set_file(&path)
+                .ok()
+                .map(|set| set.filters.len());
+            Some(Profile { name, path, rules })
+        })
+        .collect();
+    profiles.sort_by_key(|p| p.name.to_lowercase());
+    Ok(profiles)
+}
+
+/// Saves `set` as a new profile; an existing one is never overwritten.
+pub fn create(name: &str, set: &RuleSet) -> Result<PathBuf> {
+    let path = path_of(name)?;
+    if path.exists() {
+        return Err(anyhow!("Profile '{}' already exists", name.trim()));
+    }
+    let dir = profiles_dir();
+    fs::create_dir_all(&dir).map_err(|e| anyhow!("Failed to create {}: {e}", dir.display()))?;
+    fs::write(&path, serde_json::to_string_pretty(set)?)
+        .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+pub fn rename(from: &str, to: &str) -> Result<PathBuf> {
+    let source = existing(from)?;
+    let target = path_of(to)?;
+    // Renaming to a different case of the same name is allowed.
+    if target.exists() && !from.trim().eq_ignore_ascii_case(to.trim()) {
+        return Err(anyhow!("Profile '{}' already exists", to.trim()));
+    }
+    fs::rename(&source, &target)
+        .map_err(|e| anyhow!("Failed to rename {}: {e}", source.display()))?;
+    Ok(target)
+}
+
+pub fn delete(name: &str) -> Result<()> {
+    let path = existing(name)?;
+    fs::remove_file(&path).map_err(|e| anyhow!("Failed to delete {}: {e}", path.display()))
+}
+
+/// The file for `name`, which must be usable as a Windows file name.
+fn path_of(name: &str) -> Result<PathBuf> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("Profile name is required"));
+    }
+    if name.starts_with('.') || name.ends_with('.') {
+        return Err(anyhow!("Profile name cannot start or end with '.'"));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| c.is_control() || r#"<>:"/\|?*"#.contains(*c))
+    {
+        return Err(anyhow!("Profile name cannot contain '{c}'"));
+    }
+    Ok(profiles_dir().join(format!("{name}.json")))
+}
+
+fn existing(name: &str) -> Result<PathBuf> {
+    let path = path_of(name)?;
+    if !path.exists() {
+        return Err(anyhow!("No profile named '{}'", name.trim()));
+    }
+    Ok(path)
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    preset::Preset,
+    wfp::{self, quic_block_rule, FilterBuilder, GUID},
+};
+
+/// Outbound UDP 443 blocked so browsers use TCP and TLS, for every
+/// application or only the listed ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuicBlock {
+    /// Full executable paths; empty blocks QUIC for every application.
+    pub apps: Vec<String>,
+}
+
+impl Preset for QuicBlock {
+    const SETTINGS_FILE: &'static str = "quic_block.json";
+    const RULE_NAME: &'static str = "Block QUIC";
+    const RULE_KEY: GUID = GUID::from_values(
+        0x7a4d2c91,
+        0x5e3f,
+        0x4c08,
+        [0x9b, 0x61, 0xd2, 0x0e, 0x47, 0xa8, 0x3c, 0x15],
+    );
+
+    fn rule(&self, name: &str) -> Result<Vec<FilterBuilder>> {
+        let app_ids = self
+            .apps
+            .iter()
+            .map(|path| wfp::app_id(path))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(quic_block_rule(Self::RULE_KEY, name, &app_ids))
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+/// Minimum gap between snapshots; requests within it are folded into one.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AutoRefresh {
+    Off,
+    FiveSeconds,
+    ThirtySeconds,
+    FiveMinutes,
+}
+
+impl AutoRefresh {
+    pub const ALL: [AutoRefresh; 4] = [
+        AutoRefresh::Off,
+        AutoRefresh::FiveSeconds,
+        AutoRefresh::ThirtySeconds,
+        AutoRefresh::FiveMinutes,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AutoRefresh::Off => "off",
+            AutoRefresh::FiveSeconds => "5s",
+            AutoRefresh::ThirtySeconds => "30s",
+            AutoRefresh::FiveMinutes => "5min",
+        }
+    }
+
+    fn interval(self) -> Option<Duration> {
+        match self {
+            AutoRefresh::Off => None,
+            AutoRefresh::FiveSeconds => Some(Duration::from_secs(5)),
+            AutoRefresh::ThirtySeconds => Some(Duration::from_secs(30)),
+            AutoRefresh::FiveMinutes => Some(Duration::from_secs(300)),
+        }
+    }
+}
+
+/// Decides when the filter snapshot is reloaded. Edits, host switches and
+/// the Refresh button call `request`; the timer and those requests are
+/// merged so only one snapshot is taken at a time.
+pub struct RefreshScheduler {
+    pub auto: AutoRefresh,
+    requested: bool,
+    last: Option<Instant>,
+}
+
+impl Default for RefreshScheduler {
+    /// Auto-refresh off, with the first snapshot due at once.
+    fn default() -> Self {
+        Self {
+            auto: AutoRefresh::Off,
+            requested: true,
+            last: None,
+        }
+    }
+}
+
+impl RefreshScheduler {
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// True when a snapshot should be taken now. The caller must follow up
+    /// with `done` once it has been taken.
+    pub fn due(&self) -> bool {
+        self.wait().is_some_and(|wait| wait.is_zero())
+    }
+
+    pub fn done(&mut self) {
+        self.requested = false;
+        self.last = Some(Instant::now());
+    }
+
+    /// Time until the next snapshot is due, or `None` if nothing is
+    /// scheduled. Used to ask egui for a repaint.
+    pub fn wait(&self) -> Option<Duration> {
+        let after = |gap: Duration| {
+            self.last
+                .map_or(Duration::ZERO, |last| gap.saturating_sub(last.elapsed()))
+        };
+        let requested = self.requested.then(|| after(DEBOUNCE));
+        let timer = self.auto.interval().map(after);
+        match (requested, timer) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
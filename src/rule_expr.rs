@@ -0,0 +1,272 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+
+use crate::wfp::{
+    is_hostname, Direction, FilterConfig, RemoteAddress, RemotePorts, RuleProtocol, WfpAction,
+};
+
+/// Parses a one-line rule such as `block out tcp to 10.0.0.0/8 port 445` or
+/// `permit app "C:\app.exe" udp port 53`.
+///
+/// The action (`block`/`deny` or `permit`/`allow`) comes first, then these
+/// clauses in any order, each at most once: `in`/`out`, `tcp`/`udp`/`any`,
+/// `app "PATH"`, `to ADDR[,ADDR...]`, `from ADDR[,ADDR...]`,
+/// `port PORT[,PORT...]`, `local port PORT[,PORT...]`,
+/// `on ethernet|wifi|cellular|vpn`, `depends KEY[,KEY...]` and
+/// `name "NAME"`. `to` implies `out` and `from` implies `in`. Addresses may
+/// be hostnames, resolved when the rule is installed. Without a name the
+/// rule is named after its expression.
+pub fn parse(text: &str) -> Result<FilterConfig> {
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+    let action = match tokens.next() {
+        Some(Token::Word(word)) => ACTIONS
+            .iter()
+            .find(|(w, _)| word.eq_ignore_ascii_case(w))
+            .map(|&(_, action)| action)
+            .ok_or_else(|| anyhow!("Rule must start with block or permit, not '{word}'"))?,
+        _ => return Err(anyhow!("Rule must start with block or permit")),
+    };
+    let mut config = FilterConfig::tcp_ports("", RemotePorts::default(), action);
+    let mut direction = None;
+    let mut name = None;
+    let mut seen = HashSet::new();
+    while let Some(token) = tokens.next() {
+        let word = match token {
+            Token::Word(word) => word.to_ascii_lowercase(),
+            Token::Quoted(text) => return Err(anyhow!("Unexpected \"{text}\"")),
+            Token::Comma => return Err(anyhow!("Unexpected ','")),
+        };
+        let clause = match word.as_str() {
+            "out" | "in" => {
+                let dir = if word == "in" {
+                    Direction::In
+                } else {
+                    Direction::Out
+                };
+                set_direction(&mut direction, dir)?;
+                "direction"
+            }
+            "tcp" | "udp" | "any" => {
+                config.protocol = Some(match word.as_str() {
+                    "tcp" => RuleProtocol::Tcp,
+                    "udp" => RuleProtocol::Udp,
+                    _ => RuleProtocol::Any,
+                });
+                "protocol"
+            }
+            "app" => {
+                config.app = Some(value(&mut tokens, "app")?);
+                "app"
+            }
+            "on" => {
+                config.interface = Some(value(&mut tokens, "on")?.parse()?);
+                "interface type"
+            }
+            "name" => {
+                name = Some(value(&mut tokens, "name")?);
+                "name"
+            }
+            "to" | "from" => {
+                let dir = if word == "from" {
+                    Direction::In
+                } else {
+                    Direction::Out
+                };
+                set_direction(&mut direction, dir)?;
+                for address in list(&mut tokens, &word)? {
+                    match address.parse() {
+                        Ok(address) => config.remote_address.push(address),
+                        Err(_) if is_hostname(&address) => config.remote_host.push(address),
+                        Err(err) => return Err(err),
+                    }
+                }
+                "addresses"
+            }
+            "depends" => {
+                for key in list(&mut tokens, "depends")? {
+                    config.depends_on.push(
+                        key.parse()
+                            .map_err(|_| anyhow!("'{key}' is not a rule key"))?,
+                    );
+                }
+                "dependencies"
+            }
+            "port" | "ports" => {
+                for ports in list(&mut tokens, "port")? {
+                    for port in ports.parse::<RemotePorts>()?.as_slice() {
+                        config.remote_port.push(*port);
+                    }
+                }
+                "ports"
+            }
+            "local" => {
+                if !matches!(tokens.next(), Some(Token::Word(w)) if w.eq_ignore_ascii_case("port")
+                    || w.eq_ignore_ascii_case("ports"))
+                {
+                    return Err(anyhow!("'local' must be followed by 'port'"));
+                }
+                for ports in list(&mut tokens, "local port")? {
+                    for port in ports.parse::<RemotePorts>()?.as_slice() {
+                        config.local_port.push(*port);
+                    }
+                }
+                "local ports"
+            }
+            other => return Err(anyhow!("Unknown word '{other}' in rule")),
+        };
+        if !seen.insert(clause) {
+            return Err(anyhow!("Rule's {clause} given twice"));
+        }
+    }
+    config.direction = direction.unwrap_or_default();
+    config.remote_port.normalize();
+    config.local_port.normalize();
+    config.name = name.unwrap_or_else(|| format(&config));
+    config.validate()?;
+    Ok(config)
+}
+
+/// Writes `config` back as an expression, leaving out the name. It parses
+/// back into the same rule whenever `config` is valid.
+pub fn format(config: &FilterConfig) -> String {
+    let action = match config.action {
+        WfpAction::Permit => "permit",
+        WfpAction::Block => "block",
+        WfpAction::Callout => "callout",
+    };
+    let mut words = vec![action.to_string(), config.direction.as_str().to_string()];
+    if let Some(app) = &config.app {
+        words.push(format!("app \"{app}\""));
+    }
+    if config.protocol() != RuleProtocol::Any {
+        words.push(config.protocol().as_str().to_string());
+    }
+    if !config.remote_address.is_empty() || !config.remote_host.is_empty() {
+        let addresses: Vec<String> = config
+            .remote_address
+            .iter()
+            .map(RemoteAddress::to_string)
+            .chain(config.remote_host.iter().cloned())
+            .collect();
+        let word = match config.direction {
+            Direction::Out => "to",
+            Direction::In => "from",
+        };
+        words.push(format!("{word} {}", addresses.join(",")));
+    }
+    if !config.remote_port.is_empty() {
+        let ports: Vec<String> = config
+            .remote_port
+            .as_slice()
+            .iter()
+            .map(u16::to_string)
+            .collect();
+        words.push(format!("port {}", ports.join(",")));
+    }
+    if !config.local_port.is_empty() {
+        let ports: Vec<String> = config
+            .local_port
+            .as_slice()
+            .iter()
+            .map(u16::to_string)
+            .collect();
+        words.push(format!("local port {}", ports.join(",")));
+    }
+    if let Some(media) = config.interface {
+        words.push(format!("on {}", media.as_str()));
+    }
+    if !config.depends_on.is_empty() {
+        let keys: Vec<String> = config.depends_on.iter().map(ToString::to_string).collect();
+        words.push(format!("depends {}", keys.join(",")));
+    }
+    words.join(" ")
+}
+
+/// Words an expression may start with: every word [`format`] writes, and
+/// aliases. `callout` reads back what `format` writes for a callout rule,
+/// which [`FilterConfig::validate`] then refuses.
+const ACTIONS: [(&str, WfpAction); 5] = [
+    ("block", WfpAction::Block),
+    ("deny", WfpAction::Block),
+    ("permit", WfpAction::Permit),
+    ("allow", WfpAction::Permit),
+    ("callout", WfpAction::Callout),
+];
+
+#[derive(Debug)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Comma,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ',' {
+            chars.next();
+            tokens.push(Token::Comma);
+        } else if c == '"' {
+            chars.next();
+            let mut quoted = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => quoted.push(c),
+                    None => return Err(anyhow!("Unterminated quote in rule")),
+                }
+            }
+            tokens.push(Token::Quoted(quoted));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == ',' || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+fn set_direction(current: &mut Option<Direction>, direction: Direction) -> Result<()> {
+    match current {
+        Some(existing) if *existing != direction => Err(anyhow!(
+            "Rule cannot be both {} and {}",
+            existing.as_str(),
+            direction.as_str()
+        )),
+        _ => {
+            *current = Some(direction);
+            Ok(())
+        }
+    }
+}
+
+/// The quoted or bare value after `keyword`.
+fn value(tokens: &mut impl Iterator<Item = Token>, keyword: &str) -> Result<String> {
+    match tokens.next() {
+        Some(Token::Quoted(text) | Token::Word(text)) if !text.is_empty() => Ok(text),
+        _ => Err(anyhow!("'{keyword}' needs a value")),
+    }
+}
+
+/// Comma-separated values after `keyword`.
+fn list(
+    tokens: &mut std::iter::Peekable<impl Iterator<Item = Token>>,
+    keyword: &str,
+) -> Result<Vec<String>> {
+    let mut items = vec![value(tokens, keyword)?];
+    while matches!(tokens.peek(), Some(Token::Comma)) {
+        tokens.next();
+        items.push(value(tokens, keyword)?);
+    }
+    Ok(items)
+}
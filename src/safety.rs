@@ -0,0 +1,235 @@
+use std::net::IpAddr;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use uuid::Uuid;
+
+pub use crate::lockdown::RemoteSession;
+use crate::{
+    config, connections, lockdown, naming,
+    wfp::{guid_from_uuid, Engine, FilterBuilder, FilterConfig, WeightTier, WfpAction, GUID},
+};
+#[cfg(windows)]
+use {
+    std::net::{Ipv4Addr, Ipv6Addr},
+    windows::{
+        core::PWSTR,
+        Win32::{
+            Networking::WinSock::{AF_INET, AF_INET6},
+            System::RemoteDesktop::{
+                WTSClientAddress, WTSClientProtocolType, WTSFreeMemory,
+                WTSQuerySessionInformationW, WTS_CLIENT_ADDRESS, WTS_CURRENT_SERVER_HANDLE,
+                WTS_CURRENT_SESSION, WTS_INFO_CLASS,
+            },
+        },
+    },
+};
+
+const SETTINGS_FILE: &str = "safety.json";
+
+/// Whether destructive actions such as deletes ask first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmPolicy {
+    #[default]
+    Confirm,
+    /// Deletes happen on the first click.
+    PowerUser,
+}
+
+impl ConfirmPolicy {
+    pub const ALL: [ConfirmPolicy; 2] = [ConfirmPolicy::Confirm, ConfirmPolicy::PowerUser];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ConfirmPolicy::Confirm => "Require confirmation for destructive actions",
+            ConfirmPolicy::PowerUser => "Power-user mode",
+        }
+    }
+}
+
+/// Safeguards against changes that are hard to take back.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Safety {
+    pub confirm: ConfirmPolicy,
+    /// Refuse to block all traffic on this machine while an RDP or SSH
+    /// session from an address no rule allows is connected, and keep the
+    /// sessions connected through a lockdown.
+    pub lockout_guard: bool,
+    /// While the app runs in an RDP session, permit the RDP client for
+    /// the time it takes to check imports that could block it.
+    pub peer_permit: bool,
+}
+
+impl Default for Safety {
+    fn default() -> Self {
+        Self {
+            confirm: ConfirmPolicy::Confirm,
+            lockout_guard: true,
+            peer_permit: true,
+        }
+    }
+}
+
+impl Safety {
+    pub fn load() -> Result<Self> {
+        config::load_settings(SETTINGS_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        config::save_settings(SETTINGS_FILE, self)
+    }
+
+    pub fn confirms(&self) -> bool {
+        self.confirm == ConfirmPolicy::Confirm
+    }
+
+    /// [`lockdown::check_lockout`] against this machine's remote sessions,
+    /// unless the lockout guard is off.
+    pub fn check_lockout(&self, engine: &Engine, builders: &[FilterBuilder]) -> Result<()> {
+        if !self.lockout_guard {
+            return Ok(());
+        }
+        lockdown::check_lockout(engine, builders, remote_sessions)
+    }
+
+    /// The remote sessions a lockdown must keep and be checked against: all
+    /// of them with the lockout guard on, none with it off.
+    pub fn lockdown_sessions(&self) -> Result<Vec<RemoteSession>> {
+        if !self.lockout_guard {
+            return Ok(Vec::new());
+        }
+        remote_sessions()
+    }
+
+    /// [`Self::check_lockout`] for rules about to be imported.
+    pub fn check_lockout_configs(&self, engine: &Engine, configs: &[FilterConfig]) -> Result<()> {
+        // A rule whose app cannot be resolved is never block-all; the
+        // import reports it.
+        let builders: Vec<FilterBuilder> = configs
+            .iter()
+            .filter_map(|c| c.builders(GUID::default()).ok())
+            .flatten()
+            .collect();
+        self.check_lockout(engine, &builders)
+    }
+}
+
+/// Inbound connections to the RDP and SSH ports, and the SSH session this
+/// process runs in.
+pub fn remote_sessions() -> Result<Vec<RemoteSession>> {
+    let mut sessions: Vec<RemoteSession> = connections::list()?
+        .into_iter()
+        .filter(|c| c.inbound && c.state == "ESTABLISHED")
+        .filter_map(|c| {
+            let protocol = match c.local.port() {
+                3389 => "RDP",
+                22 => "SSH",
+                _ => return None,
+            };
+            Some(RemoteSession {
+                protocol,
+                remote: c.remote?.ip(),
+            })
+        })
+        .collect();
+    // `SSH_CLIENT` is `address port local-port`.
+    if let Some(remote) = std::env::var("SSH_CLIENT")
+        .ok()
+        .and_then(|v| v.split_whitespace().next()?.parse().ok())
+    {
+        sessions.push(RemoteSession {
+            protocol: "SSH",
+            remote,
+        });
+    }
+    sessions.sort_by_key(|s| (s.remote, s.protocol));
+    sessions.dedup();
+    Ok(sessions)
+}
+
+/// Whether any block among `configs` could stop traffic from `peer`: one
+/// limited neither to some apps nor to addresses leaving `peer` out.
+pub fn may_block(configs: &[FilterConfig], peer: IpAddr) -> bool {
+    configs.iter().any(|c| {
+        c.action == WfpAction::Block
+            && c.app.is_none()
+            && if c.remote_address.is_empty() {
+                c.remote_host.is_empty()
+            } else {
+                c.remote_address.iter().any(|a| a.contains(peer))
+            }
+    })
+}
+
+/// A hard permit for everything to and from the RDP client, added before
+/// rules that could block it and removed once the user sees the
+/// connection still works. It lives in a dynamic session, so BFE also
+/// removes it when the process exits.
+pub struct PeerPermit {
+    _engine: Engine,
+    pub peer: IpAddr,
+}
+
+impl PeerPermit {
+    pub fn add(peer: IpAddr) -> Result<Self> {
+        let name = naming::preset_name(&format!("Temporary permit for the RDP client {peer}"));
+        let key = guid_from_uuid(Uuid::new_v4());
+        let builders = lockdown::peer_permits(&name, key, peer, WeightTier::Allow.top());
+        let engine = Engine::open_dynamic()?;
+        engine.replace_rule(key, &name, &builders)?;
+        Ok(Self {
+            _engine: engine,
+            peer,
+        })
+    }
+}
+
+/// The address of the RDP client when this process runs in a remote
+/// desktop session.
+#[cfg(windows)]
+pub fn rdp_peer() -> Option<IpAddr> {
+    const WTS_PROTOCOL_TYPE_RDP: u16 = 2;
+    /// Queries a fixed-size value about this process's session.
+    fn query<T: Copy>(class: WTS_INFO_CLASS) -> Option<T> {
+        let mut buf = PWSTR::null();
+        let mut len = 0;
+        unsafe {
+            WTSQuerySessionInformationW(
+                WTS_CURRENT_SERVER_HANDLE,
+                WTS_CURRENT_SESSION,
+                class,
+                &mut buf,
+                &mut len,
+            )
+        }
+        .ok()?;
+        let value = (len as usize >= std::mem::size_of::<T>())
+            .then(|| unsafe { buf.0.cast::<T>().read_unaligned() });
+        unsafe { WTSFreeMemory(buf.0.cast()) };
+        value
+    }
+    if query::<u16>(WTSClientProtocolType)? != WTS_PROTOCOL_TYPE_RDP {
+        return None;
+    }
+    // The address starts at the third byte, in network order.
+    let client = query::<WTS_CLIENT_ADDRESS>(WTSClientAddress)?;
+    let bytes = client.Address;
+    match client.AddressFamily {
+        family if family == u32::from(AF_INET.0) => {
+            Some(Ipv4Addr::new(bytes[2], bytes[3], bytes[4], bytes[5]).into())
+        }
+        family if family == u32::from(AF_INET6.0) => {
+            let octets: [u8; 16] = bytes[2..18].try_into().ok()?;
+            Some(Ipv6Addr::from(octets).into())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(windows))]
+pub fn rdp_peer() -> Option<IpAddr> {
+    None
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+
+use anyhow::Result;
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::wfp::{FilterConfig, RuleSet};
+
+/// JSON Schema describing the export format, shipped alongside the binary.
+pub const EXPORT_SCHEMA: &str = include_str!("../schema/filters.schema.json");
+
+/// Name used for the document root when reporting violation paths.
+const ROOT_PATH: &str = "filters";
+
+#[derive(Clone, Debug)]
+pub struct SchemaViolation {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{}", join_violations(.0))]
+pub struct ValidationError(pub Vec<SchemaViolation>);
+
+fn join_violations(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Converts a parsed import document into rule configs, validating it against
+/// [`EXPORT_SCHEMA`] first so errors point at the offending field.
+pub fn configs_from_value(value: Value) -> Result<Vec<FilterConfig>> {
+    Ok(rule_set_from_value(value)?.filters)
+}
+
+/// Like [`configs_from_value`], keeping the provider and sublayers of a
+/// complete export. A bare array of rules yields a set with only rules.
+pub fn rule_set_from_value(value: Value) -> Result<RuleSet> {
+    validate(&value)?;
+    if value.is_array() {
+        return Ok(RuleSet::from_rules(serde_json::from_value(value)?));
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+pub fn validate(value: &Value) -> Result<(), ValidationError> {
+    let schema: Value =
+        serde_json::from_str(EXPORT_SCHEMA).expect("embedded export schema is valid JSON");
+    let mut violations = Vec::new();
+    check(&schema, &schema, value, ROOT_PATH, &mut violations);
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError(violations))
+    }
+}
+
+/// Checks `value` against the subset of JSON Schema used by the shipped
+/// schema: `$ref`, `oneOf`, `type`, `enum`, numeric and length bounds, the
+/// `uuid` string format, `items`, `minItems`, `required`, `properties` and
+/// `additionalProperties: false`.
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, out: &mut Vec<SchemaViolation>) {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match resolve_ref(root, reference) {
+            Some(target) => check(root, target, value, path, out),
+            None => out.push(violation(
+                path,
+                format!("references unknown schema {reference}"),
+            )),
+        }
+        return;
+    }
+
+    if let Some(alternatives) = schema.get("oneOf").and_then(Value::as_array) {
+        check_one_of(root, alternatives, value, path, out);
+        return;
+    }
+
+    if let Some(expected) = schema.get("type").and_then(Value::as_str) {
+        if !type_matches(expected, value) {
+            out.push(violation(path, format!("must be {}", type_label(expected))));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            let names: Vec<String> = options.iter().map(Value::to_string).collect();
+            out.push(violation(
+                path,
+                format!("must be one of {}", names.join(", ")),
+            ));
+        }
+    }
+
+    match value {
+        Value::Number(number) => check_bounds(schema, number.as_f64(), path, out),
+        Value::String(text) => {
+            check_length(schema, text.chars().count(), path, out);
+            check_format(schema, text, path, out);
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    out.push(violation(path, format!("must list at least {min} item(s)")));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (idx, item) in items.iter().enumerate() {
+                    check(root, item_schema, item, &format!("{path}[{idx}]"), out);
+                }
+            }
+        }
+        Value::Object(map) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !map.contains_key(name) {
+                        out.push(violation(&format!("{path}.{name}"), "is required".into()));
+                    }
+                }
+            }
+            let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+            for (name, field) in map {
+                let field_path = format!("{path}.{name}");
+                match properties.and_then(|props| props.get(name)) {
+                    Some(field_schema) => check(root, field_schema, field, &field_path, out),
+                    None if closed => {
+                        out.push(violation(&field_path, "is not a recognised field".into()))
+                    }
+                    None => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Passes when any alternative matches. Otherwise reports the violations of
+/// the alternative closest to the value: one whose `type` fits, then the one
+/// that got furthest into the value. That is almost always the shape the
+/// author intended.
+fn check_one_of(
+    root: &Value,
+    alternatives: &[Value],
+    value: &Value,
+    path: &str,
+    out: &mut Vec<SchemaViolation>,
+) {
+    let mut best: Option<((bool, usize), Vec<SchemaViolation>)> = None;
+    for alternative in alternatives {
+        let mut attempt = Vec::new();
+        check(root, alternative, value, path, &mut attempt);
+        if attempt.is_empty() {
+            return;
+        }
+        let resolved = alternative
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| resolve_ref(root, reference))
+            .unwrap_or(alternative);
+        let type_fits = resolved
+            .get("type")
+            .and_then(Value::as_str)
+            .map(|expected| type_matches(expected, value))
+            .unwrap_or(true);
+        let depth = attempt.first().map(|v| v.path.len()).unwrap_or(0);
+        let rank = (type_fits, depth);
+        if best.as_ref().map(|(r, _)| rank > *r).unwrap_or(true) {
+            best = Some((rank, attempt));
+        }
+    }
+    out.extend(best.map(|(_, violations)| violations).unwrap_or_default());
+}
+
+fn check_bounds(schema: &Value, number: Option<f64>, path: &str, out: &mut Vec<SchemaViolation>) {
+    let Some(number) = number else {
+        return;
+    };
+    let min = schema.get("minimum");
+    let max = schema.get("maximum");
+    let below = min
+        .and_then(Value::as_f64)
+        .map(|m| number < m)
+        .unwrap_or(false);
+    let above = max
+        .and_then(Value::as_f64)
+        .map(|m| number > m)
+        .unwrap_or(false);
+    if !below && !above {
+        return;
+    }
+    let message = match (min, max) {
+        (Some(min), Some(max)) => format!("must be {min}–{max}"),
+        (Some(min), None) => format!("must be at least {min}"),
+        (None, Some(max)) => format!("must be at most {max}"),
+        (None, None) => return,
+    };
+    out.push(violation(path, message));
+}
+
+fn check_length(schema: &Value, len: usize, path: &str, out: &mut Vec<SchemaViolation>) {
+    if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+        if (len as u64) < min {
+            let message = if min == 1 {
+                "must not be empty".to_string()
+            } else {
+                format!("must be at least {min} characters")
+            };
+            out.push(violation(path, message));
+        }
+    }
+    if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+        if len as u64 > max {
+            out.push(violation(path, format!("must be at most {max} characters")));
+        }
+    }
+}
+
+fn check_format(schema: &Value, text: &str, path: &str, out: &mut Vec<SchemaViolation>) {
+    if schema.get("format").and_then(Value::as_str) == Some("uuid")
+        && Uuid::parse_str(text).is_err()
+    {
+        out.push(violation(path, "must be a GUID".into()));
+    }
+}
+
+fn resolve_ref<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    reference
+        .strip_prefix('#')
+        .and_then(|ptr| root.pointer(ptr))
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_u64() || value.is_i64(),
+        _ => true,
+    }
+}
+
+fn type_label(expected: &str) -> &str {
+    match expected {
+        "object" => "an object",
+        "array" => "a list",
+        "string" => "a string",
+        "boolean" => "true or false",
+        "null" => "null",
+        "number" => "a number",
+        "integer" => "a whole number",
+        other => other,
+    }
+}
+
+fn violation(path: &str, message: String) -> SchemaViolation {
+    SchemaViolation {
+        path: path.to_string(),
+        message,
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::{IpAddr, ToSocketAddrs},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, Utc};
+use rhai::{Array, Dynamic, EvalAltResult, Map};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_schedule::AppSchedule,
+    cli::{parse_action, parse_key},
+    config,
+    wfp::{address_rule_v4, uuid_from_guid, Engine, RemotePorts},
+};
+
+const SCHEDULE_FILE: &str = "scripts.json";
+/// Rhai operations a script may run before it is stopped, which catches
+/// runaway loops well before they would hit the time limit.
+const MAX_OPERATIONS: u64 = 50_000_000;
+/// Wall-clock time a script may run, mostly spent waiting on BFE or DNS.
+const TIME_LIMIT: Duration = Duration::from_secs(120);
+
+/// A rhai script run by the scheduler every `every_minutes`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledScript {
+    pub path: PathBuf,
+    pub every_minutes: u32,
+    pub enabled: bool,
+}
+
+pub fn load_schedule() -> Result<Vec<ScheduledScript>> {
+    config::load_settings(SCHEDULE_FILE)
+}
+
+pub fn save_schedule(scripts: &[ScheduledScript]) -> Result<()> {
+    config::save_settings(SCHEDULE_FILE, scripts)
+}
+
+/// A line printed by a script, or the error that stopped it.
+#[derive(Clone, Debug)]
+pub struct ScriptOutput {
+    pub time: DateTime<Utc>,
+    pub script: String,
+    pub line: String,
+    pub error: bool,
+}
+
+/// Runs the script at `path` once against a fresh BFE session, sending its
+/// `print` output to `output`.
+pub fn run_script(path: &Path, output: &Sender<ScriptOutput>) -> Result<()> {
+    run_script_until(path, output, Arc::new(AtomicBool::new(false)))
+}
+
+/// [`run_script`], also stopping the script once `stop` is set. A script
+/// is stopped too after [`MAX_OPERATIONS`] or [`TIME_LIMIT`].
+fn run_script_until(
+    path: &Path,
+    output: &Sender<ScriptOutput>,
+    stop: Arc<AtomicBool>,
+) -> Result<()> {
+    let source =
+        fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let engine = Rc::new(Engine::open()?);
+    let mut rhai = script_engine(engine);
+    let deadline = Instant::now() + TIME_LIMIT;
+    rhai.on_progress(move |_| {
+        if stop.load(Ordering::Relaxed) {
+            Some("stopped with the scheduler".into())
+        } else if Instant::now() >= deadline {
+            Some(format!("timed out after {}s", TIME_LIMIT.as_secs()).into())
+        } else {
+            None
+        }
+    });
+    let sender = output.clone();
+    let script = name.clone();
+    rhai.on_print(move |line| {
+        let _ = sender.send(ScriptOutput {
+            time: Utc::now(),
+            script: script.clone(),
+            line: line.to_string(),
+            error: false,
+        });
+    });
+    rhai.run(&source).map_err(|e| match *e {
+        EvalAltResult::ErrorTerminated(reason, _) => anyhow!("{name}: {reason}"),
+        e => anyhow!("{name}: {e}"),
+    })
+}
+
+/// Script API over the engine:
+///
+/// - `filters()`: array of maps with `id`, `key`, `rule_key`, `name`,
+///   `layer`, `action`, `remote_port` and `owned`
+/// - `add_tcp_rule(name, ports, action)`: returns the new rule key
+/// - `update_tcp_rule(key, name, ports, action)`
+/// - `set_address_rule(key, name, addresses, action)`: replaces the rule
+///   with one outbound filter per IPv4 address, creating it if missing
+/// - `delete_rule(key)`
+/// - `resolve(host)`: IPv4 addresses as strings
+/// - `new_key()`: a fresh rule key
+///
+/// `action` is `"permit"` or `"block"`; `ports` and `addresses` are arrays.
+fn script_engine(engine: Rc<Engine>) -> rhai::Engine {
+    let mut rhai = rhai::Engine::new();
+    rhai.set_max_operations(MAX_OPERATIONS);
+
+    let eng = engine.clone();
+    rhai.register_fn("filters", move || -> ScriptResult<Array> {
+        let snapshot = eng.snapshot().map_err(script_error)?;
+        Ok(snapshot
+            .filters
+            .into_iter()
+            .map(|f| {
+                let mut map = Map::new();
+                map.insert("id".into(), (f.id as i64).into());
+                map.insert("key".into(), uuid_from_guid(f.key).to_string().into());
+                map.insert(
+                    "rule_key".into(),
+                    uuid_from_guid(f.rule_key()).to_string().into(),
+                );
+                map.insert("name".into(), f.name.into());
+                map.insert("layer".into(), f.layer.into());
+                map.insert("action".into(), f.action.as_str().into());
+                map.insert(
+                    "remote_port".into(),
+                    f.remote_port.map_or(Dynamic::UNIT, |p| i64::from(p).into()),
+                );
+                map.insert("owned".into(), f.owned_by_app.into());
+                map.into()
+            })
+            .collect())
+    });
+
+    let eng = engine.clone();
+    rhai.register_fn(
+        "add_tcp_rule",
+        move |name: &str, ports: Array, action: &str| -> ScriptResult<String> {
+            let ports = ports_arg(ports)?;
+            let action = parse_action(action).map_err(script_error)?;
+            let key = eng
+                .add_simple_tcp_filter_v4(name, ports.as_slice(), action)
+                .map_err(script_error)?;
+            Ok(uuid_from_guid(key).to_string())
+        },
+    );
+
+    let eng = engine.clone();
+    rhai.register_fn(
+        "update_tcp_rule",
+        move |key: &str, name: &str, ports: Array, action: &str| -> ScriptResult<()> {
+            let key = parse_key(key).map_err(script_error)?;
+            let ports = ports_arg(ports)?;
+            let action = parse_action(action).map_err(script_error)?;
+            eng.update_filter_by_key(key, name, ports.as_slice(), action)
+                .map_err(script_error)
+        },
+    );
+
+    let eng = engine.clone();
+    rhai.register_fn(
+        "set_address_rule",
+        move |key: &str, name: &str, addresses: Array, action: &str| -> ScriptResult<()> {
+            let key = parse_key(key).map_err(script_error)?;
+            let action = parse_action(action).map_err(script_error)?;
+            let addresses = addresses
+                .into_iter()
+                .map(|value| match value.into_string().ok()?.parse() {
+                    Ok(IpAddr::V4(addr)) => Some(addr),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or("addresses must be IPv4 address strings")?;
+            if addresses.is_empty() {
+                return Err("set_address_rule needs at least one address".into());
+            }
+            let builders = address_rule_v4(key, name, &addresses, action);
+            eng.replace_rule(key, name, &builders)
+                .map(|_| ())
+                .map_err(script_error)
+        },
+    );
+
+    let eng = engine;
+    rhai.register_fn("delete_rule", move |key: &str| -> ScriptResult<()> {
+        let key = parse_key(key).map_err(script_error)?;
+        eng.delete_filter_by_key(key).map_err(script_error)
+    });
+
+    rhai.register_fn("resolve", |host: &str| -> ScriptResult<Array> {
+        let addrs = (host, 0)
+            .to_socket_addrs()
+            .map_err(|e| format!("Cannot resolve {host}: {e}"))?;
+        let mut v4: Vec<String> = addrs
+            .filter(|a| a.is_ipv4())
+            .map(|a| a.ip().to_string())
+            .collect();
+        v4.sort();
+        v4.dedup();
+        Ok(v4.into_iter().map(Dynamic::from).collect())
+    });
+
+    rhai.register_fn("new_key", || Uuid::new_v4().to_string());
+    rhai
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+fn script_error(err: anyhow::Error) -> Box<EvalAltResult> {
+    err.to_string().into()
+}
+
+fn ports_arg(ports: Array) -> ScriptResult<RemotePorts> {
+    let ports = ports
+        .into_iter()
+        .map(|p| p.as_int().ok().and_then(|p| u16::try_from(p).ok()))
+        .collect::<Option<Vec<u16>>>()
+        .filter(|ports| !ports.is_empty() && !ports.contains(&0))
+        .ok_or("ports must be a non-empty array of port numbers")?;
+    Ok(RemotePorts::from(ports))
+}
+
+/// Runs enabled scripts on their schedules on a background thread. The
+/// first run of each script happens one interval after the scheduler
+/// starts. App schedules are applied at start and then at each window
+/// boundary, with the change reported as output. Dropping the scheduler
+/// stops it, cutting short a script that is running.
+pub struct ScriptScheduler {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ScriptScheduler {
+    pub fn start(
+        scripts: Vec<ScheduledScript>,
+        apps: Vec<AppSchedule>,
+        output: Sender<ScriptOutput>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let thread = thread::spawn(move || {
+            let started = Instant::now();
+            let mut last_run: HashMap<PathBuf, Instant> = HashMap::new();
+            let mut applied: HashMap<Uuid, bool> = HashMap::new();
+            while !flag.load(Ordering::Relaxed) {
+                let now = Local::now().time();
+                for app in &apps {
+                    let open = app.is_open(now);
+                    if applied.insert(app.key, open) == Some(open) {
+                        continue;
+                    }
+                    let result = Engine::open().and_then(|engine| app.apply(&engine, open));
+                    let _ = output.send(ScriptOutput {
+                        time: Utc::now(),
+                        script: app.app.clone(),
+                        line: match &result {
+                            Ok(()) if open => format!("network allowed ({})", app.window),
+                            Ok(()) => {
+                                format!("network blocked until {}", app.window.from.format("%H:%M"))
+                            }
+                            Err(err) => err.to_string(),
+                        },
+                        error: result.is_err(),
+                    });
+                }
+                for script in scripts.iter().filter(|s| s.enabled && s.every_minutes > 0) {
+                    let every = Duration::from_secs(u64::from(script.every_minutes) * 60);
+                    let since = last_run.get(&script.path).copied().unwrap_or(started);
+                    if since.elapsed() < every {
+                        continue;
+                    }
+                    last_run.insert(script.path.clone(), Instant::now());
+                    if let Err(err) = run_script_until(&script.path, &output, flag.clone()) {
+                        let _ = output.send(ScriptOutput {
+                            time: Utc::now(),
+                            script: script.path.display().to_string(),
+                            line: err.to_string(),
+                            error: true,
+                        });
+                    }
+                }
+                thread::sleep(Duration::from_secs(1));
+            }
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for ScriptScheduler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        mpsc::{self, SyncSender},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    wfp::{protocol_name, NetEvent, NetEventKind},
+};
+
+const SYSLOG_FILE: &str = "syslog.json";
+const APP_NAME: &str = "sls-wfp";
+/// Structured data ID. 32473 is the documentation enterprise number from
+/// RFC 5612; no number is registered for this tool.
+const SD_ID: &str = "wfp@32473";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Messages waiting to be sent; more are dropped while the sender is this
+/// far behind.
+const QUEUE: usize = 1024;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// RFC 5424 facility 13, "log audit".
+const FACILITY_LOG_AUDIT: u8 = 13;
+const SEVERITY_WARNING: u8 = 4;
+const SEVERITY_NOTICE: u8 = 5;
+const SEVERITY_INFO: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyslogTransport {
+    Udp,
+    /// Octet-counted framing (RFC 6587).
+    Tcp,
+}
+
+impl SyslogTransport {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SyslogTransport::Udp => "UDP",
+            SyslogTransport::Tcp => "TCP",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyslogConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub transport: SyslogTransport,
+    pub forward_audit: bool,
+    pub forward_events: bool,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 514,
+            transport: SyslogTransport::Udp,
+            forward_audit: true,
+            forward_events: true,
+        }
+    }
+}
+
+impl SyslogConfig {
+    /// Loads the saved settings; a missing file means forwarding is off.
+    pub fn load() -> Result<Self> {
+        config::load_settings(SYSLOG_FILE)
+    }
+
+    fn save(&self) -> Result<()> {
+        config::save_settings(SYSLOG_FILE, self)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditAction {
+    Add,
+    Update,
+    Delete,
+    Import,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Add => "add",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Import => "import",
+        }
+    }
+}
+
+/// A rule change made through this tool.
+pub struct AuditRecord {
+    pub action: AuditAction,
+    /// Rule key, or the filter ID when only that is known.
+    pub rule: String,
+    pub name: Option<String>,
+    /// Free-form summary, e.g. ports and action.
+    pub detail: String,
+}
+
+/// Queues the record if audit forwarding is configured. Forwarding is best
+/// effort: a background thread sends the queue, so a rule change neither
+/// fails nor waits because the collector is down.
+pub fn audit(record: AuditRecord) {
+    with_forwarder(|forwarder| {
+        if forwarder.config.forward_audit {
+            forwarder.queue(forwarder.format_audit(&record));
+        }
+    });
+}
+
+/// Queues net events if event forwarding is configured, and returns the
+/// delivery error the sender has met since the last call, if any.
+pub fn forward_events(events: &[NetEvent]) -> Result<()> {
+    let mut result = Ok(());
+    with_forwarder(|forwarder| {
+        if forwarder.config.forward_events {
+            for event in events {
+                forwarder.queue(forwarder.format_event(event));
+            }
+        }
+        if let Some(err) = forwarder
+            .failed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            result = Err(anyhow!(err));
+        }
+    });
+    result
+}
+
+/// Saves `config` and applies it to later records.
+pub fn configure(config: SyslogConfig) -> Result<()> {
+    if config.enabled && config.host.trim().is_empty() {
+        return Err(anyhow!("Syslog host is required"));
+    }
+    config.save()?;
+    *forwarder().lock().unwrap_or_else(|e| e.into_inner()) =
+        Some(config).filter(|c| c.enabled).map(Forwarder::start);
+    Ok(())
+}
+
+fn forwarder() -> &'static Mutex<Option<Forwarder>> {
+    static FORWARDER: OnceLock<Mutex<Option<Forwarder>>> = OnceLock::new();
+    FORWARDER.get_or_init(|| {
+        let forwarder = SyslogConfig::load()
+            .ok()
+            .filter(|config| config.enabled)
+            .map(Forwarder::start);
+        Mutex::new(forwarder)
+    })
+}
+
+fn with_forwarder(f: impl FnOnce(&Forwarder)) {
+    let guard = forwarder().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(forwarder) = guard.as_ref() {
+        f(forwarder);
+    }
+}
+
+/// Formats records and hands them to a [`Sender`] thread. Replacing the
+/// forwarder closes the queue, which stops its thread once drained.
+struct Forwarder {
+    config: SyslogConfig,
+    hostname: String,
+    queue: SyncSender<String>,
+    /// The latest delivery error, for [`forward_events`] to report.
+    failed: Arc<Mutex<Option<String>>>,
+}
+
+impl Forwarder {
+    fn start(config: SyslogConfig) -> Self {
+        let (queue, messages) = mpsc::sync_channel::<String>(QUEUE);
+        let failed = Arc::new(Mutex::new(None));
+        let mut sender = Sender {
+            config: config.clone(),
+            addr: None,
+            udp: None,
+            tcp: None,
+            retry: None,
+            backoff: MIN_BACKOFF,
+        };
+        let errors = failed.clone();
+        thread::spawn(move || {
+            for message in messages {
+                if let Err(err) = sender.send(&message) {
+                    *errors.lock().unwrap_or_else(|e| e.into_inner()) = Some(err.to_string());
+                }
+            }
+        });
+        Self {
+            config,
+            hostname: std::env::var("COMPUTERNAME").unwrap_or_else(|_| "-".into()),
+            queue,
+            failed,
+        }
+    }
+
+    /// Records past a full queue are dropped, as is anything sent to a
+    /// collector that is down.
+    fn queue(&self, message: String) {
+        let _ = self.queue.try_send(message);
+    }
+
+    fn format_audit(&self, record: &AuditRecord) -> String {
+        let mut params = vec![
+            ("action", record.action.as_str().to_string()),
+            ("rule", record.rule.clone()),
+        ];
+        if let Some(name) = &record.name {
+            params.push(("name", name.clone()));
+        }
+        let name = record.name.as_deref().unwrap_or(&record.rule);
+        self.format(
+            Utc::now(),
+            SEVERITY_NOTICE,
+            "RULE_CHANGE",
+            &params,
+            &format!(
+                "Rule {} {}: {}",
+                name,
+                record.action.as_str(),
+                record.detail
+            ),
+        )
+    }
+
+    fn format_event(&self, event: &NetEvent) -> String {
+        let mut params = vec![
+            ("verdict", event.kind.as_str().to_string()),
+            ("filterId", event.filter_id.to_string()),
+            ("layerId", event.layer_id.to_string()),
+        ];
+        let optional = [
+            (
+                "protocol",
+                event.protocol.map(|p| protocol_name(p).to_string()),
+            ),
+            ("direction", event.direction.map(|d| d.as_str().to_string())),
+            ("localAddr", event.local_addr.map(|a| a.to_string())),
+            ("localPort", event.local_port.map(|p| p.to_string())),
+            ("remoteAddr", event.remote_addr.map(|a| a.to_string())),
+            ("remotePort", event.remote_port.map(|p| p.to_string())),
+            ("app", event.app_id.clone()),
+        ];
+        params.extend(
+            optional
+                .into_iter()
+                .filter_map(|(name, value)| value.map(|v| (name, v))),
+        );
+        let severity = match event.kind {
+            NetEventKind::Drop => SEVERITY_WARNING,
+            NetEventKind::Allow => SEVERITY_INFO,
+        };
+        self.format(
+            event.time,
+            severity,
+            "NET_EVENT",
+            &params,
+            &format!("{} {}", event.kind.as_str(), event.flow()),
+        )
+    }
+
+    /// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID [SD] MSG`
+    fn format(
+        &self,
+        time: DateTime<Utc>,
+        severity: u8,
+        msg_id: &str,
+        params: &[(&str, String)],
+        message: &str,
+    ) -> String {
+        let sd: String = params
+            .iter()
+            .map(|(name, value)| format!(" {name}=\"{}\"", escape_param(value)))
+            .collect();
+        format!(
+            "<{}>1 {} {} {APP_NAME} {} {msg_id} [{SD_ID}{sd}] {message}",
+            FACILITY_LOG_AUDIT * 8 + severity,
+            time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.hostname,
+            std::process::id(),
+        )
+    }
+}
+
+/// Delivers queued messages on the forwarder's thread. The collector's
+/// address is resolved once and the socket kept; after a failure, messages
+/// are dropped until a backoff that doubles up to [`MAX_BACKOFF`] has
+/// passed.
+struct Sender {
+    config: SyslogConfig,
+    addr: Option<SocketAddr>,
+    udp: Option<UdpSocket>,
+    tcp: Option<TcpStream>,
+    /// When the next attempt may be made after a failure.
+    retry: Option<Instant>,
+    backoff: Duration,
+}
+
+impl Sender {
+    fn send(&mut self, message: &str) -> Result<()> {
+        if self.retry.is_some_and(|at| Instant::now() < at) {
+            return Ok(());
+        }
+        match self.try_send(message) {
+            Ok(()) => {
+                self.retry = None;
+                self.backoff = MIN_BACKOFF;
+                Ok(())
+            }
+            Err(err) => {
+                // Resolved and connected again on the next attempt.
+                self.addr = None;
+                self.tcp = None;
+                self.retry = Some(Instant::now() + self.backoff);
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                Err(err)
+            }
+        }
+    }
+
+    fn try_send(&mut self, message: &str) -> Result<()> {
+        let addr = match self.addr {
+            Some(addr) => addr,
+            None => {
+                let host = self.config.host.trim();
+                let addr = (host, self.config.port)
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| anyhow!("Cannot resolve {host}"))?;
+                *self.addr.insert(addr)
+            }
+        };
+        match self.config.transport {
+            SyslogTransport::Udp => {
+                let socket = match &self.udp {
+                    Some(socket) => socket,
+                    None => self.udp.insert(UdpSocket::bind(("0.0.0.0", 0))?),
+                };
+                socket.send_to(message.as_bytes(), addr)?;
+            }
+            SyslogTransport::Tcp => {
+                let frame = format!("{} {message}", message.len());
+                // One reconnect covers a collector that dropped an idle
+                // connection.
+                for attempt in 0..2 {
+                    let stream = match &mut self.tcp {
+                        Some(stream) => stream,
+                        None => self
+                            .tcp
+                            .insert(TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?),
+                    };
+                    match stream.write_all(frame.as_bytes()) {
+                        Ok(()) => break,
+                        Err(err) => {
+                            self.tcp = None;
+                            if attempt == 1 {
+                                return Err(err.into());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escapes `"`, `\` and `]` in an SD-PARAM value as RFC 5424 requires.
+fn escape_param(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,326 @@
+use std::{collections::BTreeMap, net::IpAddr};
+
+use serde::Serialize;
+
+use crate::wfp::{
+    Condition, ConditionField, ConditionValue, FilterSummary, MatchType, NetEvent, NetEventKind,
+    SublayerInfo, WfpAction,
+};
+
+/// Whether a filter's conditions match the flow in an event, as far as the
+/// event records it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventMatch {
+    Yes,
+    No,
+    /// Depends on a field the event does not record, or a condition type we
+    /// cannot evaluate offline (app IDs, masks, security descriptors).
+    Unknown,
+}
+
+impl EventMatch {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventMatch::Yes => "matches",
+            EventMatch::No => "does not match",
+            EventMatch::Unknown => "may match",
+        }
+    }
+}
+
+struct Candidate {
+    filter: FilterSummary,
+    matches: EventMatch,
+}
+
+#[derive(Serialize)]
+pub struct Step {
+    pub title: &'static str,
+    pub details: Vec<String>,
+}
+
+/// Guided root-cause for one net event: the filter that decided it, what
+/// else was in play at that layer, and where to go next.
+pub struct Diagnosis {
+    pub event: NetEvent,
+    pub deciding: Option<FilterSummary>,
+    pub steps: Vec<Step>,
+}
+
+/// Candidates listed in the "in play" step; the rest are only counted.
+const MAX_LISTED: usize = 20;
+
+impl Diagnosis {
+    pub fn explain(
+        event: &NetEvent,
+        filters: &[FilterSummary],
+        boot_time_filters: &[FilterSummary],
+        sublayers: &[SublayerInfo],
+    ) -> Self {
+        let deciding = filters
+            .iter()
+            .chain(boot_time_filters)
+            .find(|f| f.id == event.filter_id)
+            .cloned();
+
+        let mut in_play: Vec<Candidate> = match &deciding {
+            Some(decider) => filters
+                .iter()
+                .filter(|f| f.layer_key == decider.layer_key && f.id != decider.id)
+                .map(|f| Candidate {
+                    matches: match_event(&f.conditions, event),
+                    filter: f.clone(),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        // In evaluation order: heavier sublayers first, then heavier
+        // filters within each. Sublayers of unknown weight go last.
+        let sublayer_weight = |filter: &FilterSummary| {
+            sublayers
+                .iter()
+                .find(|s| s.key == filter.sublayer_key)
+                .map(|s| s.weight)
+        };
+        in_play.sort_by_key(|c| {
+            std::cmp::Reverse((
+                sublayer_weight(&c.filter),
+                c.filter.effective_weight.or(c.filter.weight),
+            ))
+        });
+
+        let steps = vec![
+            what_happened(event),
+            deciding_filter(event, deciding.as_ref()),
+            filters_in_play(&in_play),
+            conclusion(event, deciding.as_ref(), &in_play),
+        ];
+
+        Self {
+            event: event.clone(),
+            deciding,
+            steps,
+        }
+    }
+}
+
+fn what_happened(event: &NetEvent) -> Step {
+    let mut details = vec![format!(
+        "{} {} {} at {}",
+        event.kind.as_str(),
+        event
+            .direction
+            .map(|d| d.as_str())
+            .unwrap_or("unknown-direction"),
+        event.flow(),
+        event.time.format("%Y-%m-%d %H:%M:%S UTC")
+    )];
+    if let Some(app) = &event.app_id {
+        details.push(format!("Application: {app}"));
+    }
+    details.push(format!(
+        "Decided by filter ID {} at layer ID {}",
+        event.filter_id, event.layer_id
+    ));
+    Step {
+        title: "What happened",
+        details,
+    }
+}
+
+fn deciding_filter(event: &NetEvent, deciding: Option<&FilterSummary>) -> Step {
+    let details = match deciding {
+        Some(filter) => {
+            let mut details = vec![
+                format!(
+                    "#{} '{}' ({}{})",
+                    filter.id,
+                    filter.name,
+                    filter.action.as_str(),
+                    if filter.owned_by_app { ", ours" } else { "" }
+                ),
+                format!("Provider: {}", filter.provider),
+                format!("Layer: {}", filter.layer),
+                format!("Sublayer: {}", filter.sublayer),
+                match filter.weight {
+                    Some(weight) => format!("Weight: 0x{weight:016X}"),
+                    None => "Weight: assigned by BFE".into(),
+                },
+            ];
+            if filter.conditions.is_empty() {
+                details.push("No conditions: matches all traffic at this layer".into());
+            }
+            details.extend(filter.conditions.iter().map(|c| format!("Condition: {c}")));
+            details
+        }
+        None => vec![format!(
+            "Filter #{} is not in the current snapshot. It may have been deleted since, \
+             or belonged to a dynamic session that has closed.",
+            event.filter_id
+        )],
+    };
+    Step {
+        title: "Deciding filter",
+        details,
+    }
+}
+
+fn filters_in_play(in_play: &[Candidate]) -> Step {
+    if in_play.is_empty() {
+        return Step {
+            title: "Other filters at this layer",
+            details: vec!["None".into()],
+        };
+    }
+    let mut by_sublayer: BTreeMap<&str, usize> = BTreeMap::new();
+    for candidate in in_play {
+        *by_sublayer
+            .entry(candidate.filter.sublayer.as_str())
+            .or_default() += 1;
+    }
+    let mut details = vec![format!(
+        "{} other filter(s) in {} sublayer(s)",
+        in_play.len(),
+        by_sublayer.len()
+    )];
+    let relevant: Vec<&Candidate> = in_play
+        .iter()
+        .filter(|c| c.matches != EventMatch::No)
+        .collect();
+    for candidate in relevant.iter().take(MAX_LISTED) {
+        let filter = &candidate.filter;
+        details.push(format!(
+            "[{}] #{} '{}' ({}, sublayer {}, provider {})",
+            candidate.matches.as_str(),
+            filter.id,
+            filter.name,
+            filter.action.as_str(),
+            filter.sublayer,
+            filter.provider
+        ));
+    }
+    if relevant.len() > MAX_LISTED {
+        details.push(format!(
+            "… and {} more that may match",
+            relevant.len() - MAX_LISTED
+        ));
+    }
+    Step {
+        title: "Other filters at this layer",
+        details,
+    }
+}
+
+fn conclusion(event: &NetEvent, deciding: Option<&FilterSummary>, in_play: &[Candidate]) -> Step {
+    let our_matching_block = in_play.iter().find(|c| {
+        c.filter.owned_by_app && c.filter.action == WfpAction::Block && c.matches != EventMatch::No
+    });
+    let detail = match (deciding, event.kind) {
+        (None, _) => "Reproduce the traffic, refresh, and run this again while the deciding \
+                      filter still exists."
+            .to_string(),
+        (Some(filter), _) if filter.owned_by_app => format!(
+            "The verdict came from our rule '{}'. Edit or delete that rule to change it.",
+            filter.name
+        ),
+        (Some(filter), NetEventKind::Allow) => match our_matching_block {
+            Some(ours) => format!(
+                "'{}' from {} allowed this even though our block rule '{}' {} the flow. \
+                 A higher-priority sublayer overrode it; run the coexistence report.",
+                filter.name,
+                filter.provider,
+                ours.filter.name,
+                ours.matches.as_str()
+            ),
+            None => format!(
+                "'{}' from {} allowed this and none of our block rules match the flow.",
+                filter.name, filter.provider
+            ),
+        },
+        (Some(filter), NetEventKind::Drop) => format!(
+            "Another product ({}) blocked this flow with '{}'. A block in any sublayer wins \
+             unless a higher-priority sublayer hard-permits the flow.",
+            filter.provider, filter.name
+        ),
+    };
+    Step {
+        title: "Conclusion",
+        details: vec![detail],
+    }
+}
+
+/// Evaluates `conditions` against the event's flow. As in WFP, conditions on
+/// the same field are OR-ed and different fields are AND-ed.
+fn match_event(conditions: &[Condition], event: &NetEvent) -> EventMatch {
+    let mut by_field: Vec<(ConditionField, Vec<Option<bool>>)> = Vec::new();
+    for condition in conditions {
+        let result = match_condition(condition, event);
+        match by_field
+            .iter_mut()
+            .find(|(field, _)| *field == condition.field)
+        {
+            Some((_, results)) => results.push(result),
+            None => by_field.push((condition.field, vec![result])),
+        }
+    }
+
+    let mut overall = EventMatch::Yes;
+    for (_, results) in by_field {
+        if results.contains(&Some(true)) {
+            continue;
+        }
+        if results.iter().all(|r| *r == Some(false)) {
+            return EventMatch::No;
+        }
+        overall = EventMatch::Unknown;
+    }
+    overall
+}
+
+fn match_condition(condition: &Condition, event: &NetEvent) -> Option<bool> {
+    let actual = match condition.field {
+        ConditionField::IpProtocol => u64::from(event.protocol?),
+        ConditionField::RemotePort => u64::from(event.remote_port?),
+        ConditionField::LocalPort => u64::from(event.local_port?),
+        ConditionField::RemoteAddress => ipv4_value(event.remote_addr?)?,
+        ConditionField::LocalAddress => ipv4_value(event.local_addr?)?,
+        _ => return None,
+    };
+    compare(condition.match_type, actual, &condition.value)
+}
+
+fn ipv4_value(addr: IpAddr) -> Option<u64> {
+    match addr {
+        IpAddr::V4(v4) => Some(u64::from(u32::from(v4))),
+        IpAddr::V6(_) => None,
+    }
+}
+
+fn compare(match_type: MatchType, actual: u64, value: &ConditionValue) -> Option<bool> {
+    if let (MatchType::Range, ConditionValue::Range { low, high }) = (match_type, value) {
+        return Some(integer(low)? <= actual && actual <= integer(high)?);
+    }
+    let expected = integer(value)?;
+    Some(match match_type {
+        MatchType::Equal => actual == expected,
+        MatchType::NotEqual => actual != expected,
+        MatchType::Greater => actual > expected,
+        MatchType::Less => actual < expected,
+        MatchType::GreaterOrEqual => actual >= expected,
+        MatchType::LessOrEqual => actual <= expected,
+        MatchType::FlagsAllSet => actual & expected == expected,
+        MatchType::FlagsAnySet => actual & expected != 0,
+        MatchType::FlagsNoneSet => actual & expected == 0,
+        _ => return None,
+    })
+}
+
+fn integer(value: &ConditionValue) -> Option<u64> {
+    match value {
+        ConditionValue::Uint8(v) => Some(u64::from(*v)),
+        ConditionValue::Uint16(v) => Some(u64::from(*v)),
+        ConditionValue::Uint32(v) => Some(u64::from(*v)),
+        ConditionValue::Uint64(v) => Some(*v),
+        _ => None,
+    }
+}
@@ -0,0 +1,393 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+use crate::wfp::{uuid_from_guid, Engine, FilterSummary, RemotePorts, WfpAction};
+
+/// How long to wait for a key before redrawing.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs the terminal UI until the user quits. Meant for Server Core and
+/// remote shells where the egui window is not an option.
+pub fn run() -> Result<()> {
+    let engine = Engine::open()?;
+    let mut app = TuiApp::new(engine);
+    app.refresh();
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+enum Mode {
+    Browse,
+    Search,
+    Add(AddForm),
+    ConfirmDelete,
+}
+
+#[derive(Default)]
+struct AddForm {
+    /// 0 = name, 1 = ports, 2 = action.
+    field: usize,
+    name: String,
+    ports: String,
+    block: bool,
+}
+
+impl AddForm {
+    const FIELDS: usize = 3;
+
+    fn submit(&self, engine: &Engine) -> Result<String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            bail!("Name is required");
+        }
+        let ports: RemotePorts = self.ports.parse()?;
+        if ports.as_slice().is_empty() {
+            bail!("At least one port is required");
+        }
+        let action = if self.block {
+            WfpAction::Block
+        } else {
+            WfpAction::Permit
+        };
+        let key = engine.add_simple_tcp_filter_v4(name, ports.as_slice(), action)?;
+        Ok(format!("Added {} ({})", name, uuid_from_guid(key)))
+    }
+}
+
+struct TuiApp {
+    engine: Engine,
+    filters: Vec<FilterSummary>,
+    /// Indexes into `filters` that match the search text.
+    visible: Vec<usize>,
+    table: TableState,
+    search: String,
+    mode: Mode,
+    status: String,
+    quit: bool,
+}
+
+impl TuiApp {
+    fn new(engine: Engine) -> Self {
+        Self {
+            engine,
+            filters: Vec::new(),
+            visible: Vec::new(),
+            table: TableState::default(),
+            search: String::new(),
+            mode: Mode::Browse,
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(POLL_INTERVAL)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.on_key(key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn refresh(&mut self) {
+        match self.engine.snapshot() {
+            Ok(snapshot) => {
+                self.filters = snapshot.filters;
+                self.status = format!("{} filters", self.filters.len());
+            }
+            Err(err) => self.status = format!("Refresh failed: {err}"),
+        }
+        self.apply_search();
+    }
+
+    fn apply_search(&mut self) {
+        let needle = self.search.to_lowercase();
+        self.visible = self
+            .filters
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| {
+                needle.is_empty()
+                    || f.name.to_lowercase().contains(&needle)
+                    || f.layer.to_lowercase().contains(&needle)
+                    || f.id.to_string() == needle
+                    || uuid_from_guid(f.rule_key()).to_string().contains(&needle)
+                    || f.remote_port.is_some_and(|p| p.to_string() == needle)
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        let selected = match self.table.selected() {
+            _ if self.visible.is_empty() => None,
+            Some(row) => Some(row.min(self.visible.len() - 1)),
+            None => Some(0),
+        };
+        self.table.select(selected);
+    }
+
+    fn selected(&self) -> Option<&FilterSummary> {
+        let row = self.table.selected()?;
+        self.visible.get(row).map(|&idx| &self.filters[idx])
+    }
+
+    fn on_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.on_browse_key(key.code),
+            Mode::Search => match key.code {
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.apply_search();
+                }
+                KeyCode::Enter => {}
+                KeyCode::Backspace => {
+                    self.search.pop();
+                    self.apply_search();
+                    self.mode = Mode::Search;
+                }
+                KeyCode::Char(c) => {
+                    self.search.push(c);
+                    self.apply_search();
+                    self.mode = Mode::Search;
+                }
+                _ => self.mode = Mode::Search,
+            },
+            Mode::Add(mut form) => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Tab | KeyCode::Down => {
+                    form.field = (form.field + 1) % AddForm::FIELDS;
+                    self.mode = Mode::Add(form);
+                }
+                KeyCode::BackTab | KeyCode::Up => {
+                    form.field = (form.field + AddForm::FIELDS - 1) % AddForm::FIELDS;
+                    self.mode = Mode::Add(form);
+                }
+                KeyCode::Enter => match form.submit(&self.engine) {
+                    Ok(message) => {
+                        self.refresh();
+                        self.status = message;
+                    }
+                    Err(err) => {
+                        self.status = format!("Add failed: {err}");
+                        self.mode = Mode::Add(form);
+                    }
+                },
+                code => {
+                    match (form.field, code) {
+                        (2, KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right) => {
+                            form.block = !form.block
+                        }
+                        (0, KeyCode::Backspace) => {
+                            form.name.pop();
+                        }
+                        (1, KeyCode::Backspace) => {
+                            form.ports.pop();
+                        }
+                        (0, KeyCode::Char(c)) => form.name.push(c),
+                        (1, KeyCode::Char(c)) => form.ports.push(c),
+                        _ => {}
+                    }
+                    self.mode = Mode::Add(form);
+                }
+            },
+            Mode::ConfirmDelete => {
+                if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                    self.delete_selected();
+                } else {
+                    self.status = "Delete cancelled".into();
+                }
+            }
+        }
+    }
+
+    fn on_browse_key(&mut self, code: KeyCode) {
+        let rows = self.visible.len();
+        let current = self.table.selected().unwrap_or(0);
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Char('r') => self.refresh(),
+            KeyCode::Char('a') => self.mode = Mode::Add(AddForm::default()),
+            KeyCode::Char('d') | KeyCode::Delete => match self.selected() {
+                Some(filter) if filter.owned_by_app => self.mode = Mode::ConfirmDelete,
+                Some(_) => self.status = "Only rules created by this tool can be deleted".into(),
+                None => {}
+            },
+            KeyCode::Down | KeyCode::Char('j') if rows > 0 => {
+                self.table.select(Some((current + 1).min(rows - 1)))
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.table.select(Some(current.saturating_sub(1))),
+            KeyCode::PageDown if rows > 0 => self.table.select(Some((current + 20).min(rows - 1))),
+            KeyCode::PageUp => self.table.select(Some(current.saturating_sub(20))),
+            KeyCode::Home => self.table.select(Some(0)),
+            KeyCode::End if rows > 0 => self.table.select(Some(rows - 1)),
+            _ => {}
+        }
+    }
+
+    fn delete_selected(&mut self) {
+        let Some(filter) = self.selected() else {
+            return;
+        };
+        let (key, name) = (filter.rule_key(), filter.name.clone());
+        self.status = match self.engine.delete_filter_by_key(key) {
+            Ok(()) => {
+                self.refresh();
+                format!("Deleted {name}")
+            }
+            Err(err) => format!("Delete failed: {err}"),
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search_area, table_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let searching = matches!(self.mode, Mode::Search);
+        let search = Paragraph::new(self.search.as_str()).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Search (/) ")
+                .border_style(if searching {
+                    Style::new().yellow()
+                } else {
+                    Style::new()
+                }),
+        );
+        frame.render_widget(search, search_area);
+
+        let header = Row::new(["ID", "RULE KEY", "ACTION", "PORT", "OWNED", "LAYER", "NAME"])
+            .style(Style::new().add_modifier(Modifier::BOLD));
+        let rows = self.visible.iter().map(|&idx| {
+            let f = &self.filters[idx];
+            let action = match f.action {
+                WfpAction::Block => Cell::from(f.action.as_str()).red(),
+                _ => Cell::from(f.action.as_str()).green(),
+            };
+            Row::new([
+                Cell::from(f.id.to_string()),
+                Cell::from(uuid_from_guid(f.rule_key()).to_string()),
+                action,
+                Cell::from(f.remote_port.map_or("-".into(), |p| p.to_string())),
+                Cell::from(if f.owned_by_app { "yes" } else { "no" }),
+                Cell::from(f.layer.clone()),
+                Cell::from(f.name.clone()),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Length(36),
+                Constraint::Length(7),
+                Constraint::Length(5),
+                Constraint::Length(5),
+                Constraint::Percentage(30),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            " Filters {}/{} ",
+            self.visible.len(),
+            self.filters.len()
+        )))
+        .row_highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let help = "q quit  / search  a add  d delete  r refresh";
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                self.status.as_str().into(),
+                "  |  ".dark_gray(),
+                help.dark_gray(),
+            ])),
+            status_area,
+        );
+
+        match &self.mode {
+            Mode::Add(form) => draw_add_form(frame, form),
+            Mode::ConfirmDelete => {
+                let name = self.selected().map_or("", |f| f.name.as_str());
+                let area = centered(frame.area(), 50, 5);
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(format!(
+                        "Delete rule \"{name}\"?\n\ny = yes, any other key = no"
+                    ))
+                    .block(Block::default().borders(Borders::ALL).title(" Delete ")),
+                    area,
+                );
+            }
+            Mode::Browse | Mode::Search => {}
+        }
+    }
+}
+
+fn draw_add_form(frame: &mut Frame, form: &AddForm) {
+    let area = centered(frame.area(), 60, 9);
+    frame.render_widget(Clear, area);
+    let field = |idx: usize, label: &str, value: &str| {
+        let style = if form.field == idx {
+            Style::new().yellow()
+        } else {
+            Style::new()
+        };
+        Line::from(vec![
+            Span::styled(format!("{label:<8}"), style),
+            value.to_string().into(),
+        ])
+    };
+    let action = if form.block { "block" } else { "permit" };
+    let lines = vec![
+        field(0, "Name", &form.name),
+        field(1, "Ports", &form.ports),
+        field(2, "Action", &format!("< {action} >")),
+        Line::default(),
+        "Tab next field, Space toggles action, Enter adds, Esc cancels"
+            .dark_gray()
+            .into(),
+    ];
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Add outbound TCP rule "),
+        ),
+        area,
+    );
+}
+
+/// A `width` x `height` rectangle centred in `area`, clamped to fit.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,140 @@
+use std::net::IpAddr;
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    hit_counters::{HitCounters, Hits},
+    wfp::{self, guid_from_uuid, resolve_host, uuid_from_guid, Engine, FilterSummary},
+};
+
+/// Window without hits after which a rule is reported by default.
+pub const DEFAULT_DAYS: u32 = 90;
+
+/// An owned rule without a hit in the report's window.
+#[derive(Clone, Debug, Serialize)]
+pub struct IdleRule {
+    pub key: Uuid,
+    pub name: String,
+    pub hits: Hits,
+}
+
+/// An owned rule whose hostnames no longer resolve to every address it was
+/// installed with.
+#[derive(Clone, Debug, Serialize)]
+pub struct StaleRule {
+    pub key: Uuid,
+    pub name: String,
+    pub hosts: Vec<String>,
+    /// Installed addresses none of the hostnames resolve to now.
+    pub stale: Vec<IpAddr>,
+    /// Hostnames that no longer resolve at all.
+    pub unresolved: Vec<String>,
+}
+
+impl StaleRule {
+    /// `1.2.3.4, 5.6.7.8 stale; old.example.com not resolving`.
+    pub fn detail(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.stale.is_empty() {
+            let stale: Vec<String> = self.stale.iter().map(ToString::to_string).collect();
+            parts.push(format!("{} stale", stale.join(", ")));
+        }
+        if !self.unresolved.is_empty() {
+            parts.push(format!("{} not resolving", self.unresolved.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+/// Owned rules that may no longer be needed, as candidates for cleanup.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct UnusedReport {
+    pub days: u32,
+    pub idle: Vec<IdleRule>,
+    pub stale: Vec<StaleRule>,
+}
+
+impl UnusedReport {
+    /// Reports the owned rules among `filters` on this machine, resolving
+    /// their hostnames now.
+    pub fn build(filters: &[FilterSummary], days: u32) -> Result<Self> {
+        let counters = HitCounters::load()?;
+        let hosts = wfp::rule_hosts()?;
+        let cutoff = Utc::now() - Duration::days(i64::from(days));
+        let mut rules: Vec<&FilterSummary> = Vec::new();
+        for filter in filters.iter().filter(|f| f.owned_by_app) {
+            if rules.iter().all(|r| r.rule_key() != filter.rule_key()) {
+                rules.push(filter);
+            }
+        }
+        let mut report = Self {
+            days,
+            ..Self::default()
+        };
+        for rule in rules {
+            let key = rule.rule_key();
+            let hits = counters.rule(key, filters);
+            // Rules counted for less than the window cannot be called idle.
+            if hits.since <= cutoff && hits.last_hit.is_none_or(|last| last < cutoff) {
+                report.idle.push(IdleRule {
+                    key: uuid_from_guid(key),
+                    name: rule.name.clone(),
+                    hits,
+                });
+            }
+            let Some(recorded) = hosts.get(&uuid_from_guid(key)) else {
+                continue;
+            };
+            let mut current = Vec::new();
+            let mut unresolved = Vec::new();
+            for host in &recorded.hosts {
+                match resolve_host(host) {
+                    Ok(addrs) => current.extend(addrs),
+                    Err(_) => unresolved.push(host.clone()),
+                }
+            }
+            let stale: Vec<IpAddr> = recorded
+                .addresses
+                .iter()
+                .filter(|addr| !current.contains(addr))
+                .copied()
+                .collect();
+            if !stale.is_empty() || !unresolved.is_empty() {
+                report.stale.push(StaleRule {
+                    key: uuid_from_guid(key),
+                    name: rule.name.clone(),
+                    hosts: recorded.hosts.clone(),
+                    stale,
+                    unresolved,
+                });
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.idle.is_empty() && self.stale.is_empty()
+    }
+
+    /// Every reported rule, idle or stale, once.
+    pub fn keys(&self) -> Vec<Uuid> {
+        let mut keys: Vec<Uuid> = self.idle.iter().map(|r| r.key).collect();
+        for rule in &self.stale {
+            if !keys.contains(&rule.key) {
+                keys.push(rule.key);
+            }
+        }
+        keys
+    }
+}
+
+/// Removes the rules `keys` and returns how many were removed.
+pub fn remove_rules(engine: &Engine, keys: &[Uuid]) -> Result<usize> {
+    for key in keys {
+        engine.delete_filter_by_key(guid_from_uuid(*key))?;
+    }
+    Ok(keys.len())
+}
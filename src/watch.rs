@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::Local;
+
+use crate::{
+    alerts::{Alerts, EventFeed},
+    app_schedule, config,
+    diff::RuleDiff,
+    event_store::EventStore,
+    hit_counters::HitCounters,
+    scripting::{self, ScriptScheduler},
+    syslog,
+    wfp::{self, is_app_pattern, CancelToken, Engine, FilterConfig, FilterSummary},
+};
+use uuid::Uuid;
+#[cfg(windows)]
+use windows::Win32::{Foundation::BOOL, System::Console::SetConsoleCtrlHandler};
+
+const EVENT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const TICK: Duration = Duration::from_millis(500);
+
+pub struct WatchOptions {
+    /// Rule set to enforce; the owned rules at startup when `None`.
+    pub rules: Option<PathBuf>,
+    /// How often owned rules are compared with the enforced set.
+    pub interval: Duration,
+    pub repair: bool,
+}
+
+/// Runs the background duties of the GUI from a console until Ctrl+C or
+/// Ctrl+Break: owned rules are checked against the enforced set and put
+/// back when someone changes them, app patterns are rescanned so newly
+/// installed executables are covered, scheduled scripts and app windows run,
+/// and net events are saved to the history, counted per filter, forwarded
+/// to syslog and checked for alerts.
+pub fn run(options: &WatchOptions) -> Result<()> {
+    let stop = stop_on_ctrl_c()?;
+    let mut engine = Some(Engine::open()?);
+    let desired = match &options.rules {
+        Some(path) => config::load_rules_file(path)?,
+        None => owned_rules(engine.as_ref().expect("opened above"))?,
+    };
+    if let Some(cfg) = desired.iter().find(|cfg| cfg.key.is_none()) {
+        return Err(anyhow!(
+            "Rule '{}' has no key; watch can only enforce keyed rules",
+            cfg.name
+        ));
+    }
+    log(&format!(
+        "Watching {} rules (check every {}s, repair {}).",
+        desired.len(),
+        options.interval.as_secs(),
+        if options.repair { "on" } else { "off" }
+    ));
+
+    let store = EventStore::open(&EventStore::default_dir())?;
+    let mut alerts = Alerts::load()?;
+    let (event_tx, event_rx) = mpsc::channel();
+    let _feed = EventFeed::start(EVENT_POLL_INTERVAL, event_tx);
+    let (script_tx, script_rx) = mpsc::channel();
+    let _scheduler = ScriptScheduler::start(
+        scripting::load_schedule()?,
+        app_schedule::load_schedules()?,
+        script_tx,
+    );
+
+    let mut filters: Vec<FilterSummary> = Vec::new();
+    let mut app_matches: HashMap<Uuid, Vec<String>> = HashMap::new();
+    let mut last_check: Option<Instant> = None;
+    while !stop.is_cancelled() {
+        if last_check.is_none_or(|at| at.elapsed() >= options.interval) {
+            last_check = Some(Instant::now());
+            // Reopened after any failure in case BFE restarted.
+            let result = engine
+                .take()
+                .map_or_else(Engine::open, Ok)
+                .and_then(|opened| {
+                    filters = opened.snapshot_cancellable(&stop)?.filters;
+                    check_rules(&opened, &desired, options.repair)?;
+                    rescan_app_patterns(&opened, &desired, &mut app_matches);
+                    engine = Some(opened);
+                    Ok(())
+                });
+            match result {
+                Err(err) if err.is::<wfp::Cancelled>() => break,
+                Err(err) => log(&format!("Rule check failed: {err:#}")),
+                Ok(()) => {}
+            }
+        }
+
+        for batch in event_rx.try_iter() {
+            let events = match batch {
+                Ok(events) => events,
+                Err(err) => {
+                    log(&format!("Reading net events failed: {err:#}"));
+                    continue;
+                }
+            };
+            if let Err(err) = store.append(&events) {
+                log(&format!("Saving net events failed: {err:#}"));
+            }
+            if let Err(err) = HitCounters::add_events(&events, &filters) {
+                log(&format!("Saving hit counters failed: {err:#}"));
+            }
+            if let Err(err) = syslog::forward_events(&events) {
+                log(&format!("Syslog forwarding failed: {err:#}"));
+            }
+            for alert in alerts.evaluate(&events) {
+                log(&format!("Alert: {}", alert.message()));
+                if let Err(err) = alert.log().and_then(|()| alert.run_command()) {
+                    log(&format!("Alert {}: {err:#}", alert.rule.name));
+                }
+                alert.send_webhook(filters.iter().find(|f| f.id == alert.event.filter_id));
+            }
+        }
+
+        for output in script_rx.try_iter() {
+            let prefix = if output.error { "failed: " } else { "" };
+            log(&format!("[{}] {prefix}{}", output.script, output.line));
+        }
+
+        thread::sleep(TICK);
+    }
+    // The event feed and script scheduler stop as they are dropped.
+    log("Stopping.");
+    Ok(())
+}
+
+/// Cancelled on Ctrl+C or Ctrl+Break, so `run` can stop its workers and
+/// close the engine session instead of being killed mid-change.
+#[cfg(windows)]
+fn stop_on_ctrl_c() -> Result<CancelToken> {
+    static STOP: std::sync::OnceLock<CancelToken> = std::sync::OnceLock::new();
+    unsafe extern "system" fn handler(_ctrl_type: u32) -> BOOL {
+        if let Some(stop) = STOP.get() {
+            stop.cancel();
+        }
+        true.into()
+    }
+    let stop = STOP.get_or_init(CancelToken::new).clone();
+    unsafe { SetConsoleCtrlHandler(Some(handler), true) }
+        .map_err(|e| anyhow!("Cannot handle Ctrl+C: {e}"))?;
+    Ok(stop)
+}
+
+/// Off Windows the default handler ends the process.
+#[cfg(not(windows))]
+fn stop_on_ctrl_c() -> Result<CancelToken> {
+    Ok(CancelToken::new())
+}
+
+/// Owned rules as they would be exported, read from the filter stream so
+/// only our own filters are held.
+fn owned_rules(engine: &Engine) -> Result<Vec<FilterConfig>> {
+    wfp::owned_rules(engine.filters_iter()?)
+}
+
+/// Logs any drift between the owned rules and `desired`, and puts the
+/// desired rules back when `repair` is set.
+fn check_rules(engine: &Engine, desired: &[FilterConfig], repair: bool) -> Result<()> {
+    let current = owned_rules(engine)?;
+    let drift = RuleDiff::between(desired, &current);
+    if drift.is_empty() {
+        return Ok(());
+    }
+    log(&format!(
+        "Owned rules changed outside this session:\n{drift}"
+    ));
+    if repair {
+        let current_keys: Vec<_> = current.iter().filter_map(|cfg| cfg.key).collect();
+        let summary = engine.reconcile(desired, &current_keys)?;
+        log(&format!(
+            "Repaired: {} added, {} updated, {} removed.",
+            summary.added, summary.updated, summary.removed
+        ));
+    }
+    Ok(())
+}
+
+/// Reinstalls each rule whose app pattern now matches different
+/// executables than when it was last installed by this loop.
+fn rescan_app_patterns(
+    engine: &Engine,
+    desired: &[FilterConfig],
+    matches: &mut HashMap<Uuid, Vec<String>>,
+) {
+    if wfp::is_read_only() {
+        return;
+    }
+    for cfg in desired {
+        let (Some(key), Some(app)) = (cfg.key, cfg.app.as_deref()) else {
+            continue;
+        };
+        if !is_app_pattern(app) {
+            continue;
+        }
+        let apps = match cfg.app_paths() {
+            Ok(apps) => apps,
+            Err(err) => {
+                log(&format!("Rule '{}': {err:#}", cfg.name));
+                continue;
+            }
+        };
+        if matches.get(&key) == Some(&apps) {
+            continue;
+        }
+        match engine.import_filters(std::slice::from_ref(cfg)) {
+            Ok(_) => {
+                log(&format!(
+                    "Rule '{}': {app} now matches {} executables.",
+                    cfg.name,
+                    apps.len()
+                ));
+                matches.insert(key, apps);
+            }
+            Err(err) => log(&format!("Reinstalling rule '{}' failed: {err:#}", cfg.name)),
+        }
+    }
+}
+
+fn log(message: &str) {
+    println!(
+        "{} {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        message.trim_end()
+    );
+}
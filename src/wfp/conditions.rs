@@ -0,0 +1,790 @@
+use super::*;
+
+/// Comparison applied between a packet field and a condition value. Covers
+/// every `FWP_MATCH_TYPE` so filters installed by other products decode
+/// faithfully.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchType {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    GreaterOrEqual,
+    LessOrEqual,
+    Range,
+    FlagsAllSet,
+    FlagsAnySet,
+    FlagsNoneSet,
+    EqualCaseInsensitive,
+    Prefix,
+    NotPrefix,
+    /// A raw `FWP_MATCH_TYPE` newer than this list.
+    Other(u32),
+}
+
+impl MatchType {
+    pub const ALL: [MatchType; 13] = [
+        MatchType::Equal,
+        MatchType::NotEqual,
+        MatchType::Greater,
+        MatchType::Less,
+        MatchType::GreaterOrEqual,
+        MatchType::LessOrEqual,
+        MatchType::Range,
+        MatchType::FlagsAllSet,
+        MatchType::FlagsAnySet,
+        MatchType::FlagsNoneSet,
+        MatchType::EqualCaseInsensitive,
+        MatchType::Prefix,
+        MatchType::NotPrefix,
+    ];
+}
+
+impl std::fmt::Display for MatchType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MatchType::Equal => "==",
+            MatchType::NotEqual => "!=",
+            MatchType::Greater => ">",
+            MatchType::Less => "<",
+            MatchType::GreaterOrEqual => ">=",
+            MatchType::LessOrEqual => "<=",
+            MatchType::Range => "in",
+            MatchType::FlagsAllSet => "has all flags",
+            MatchType::FlagsAnySet => "has any flag",
+            MatchType::FlagsNoneSet => "has no flags",
+            MatchType::EqualCaseInsensitive => "==(i)",
+            MatchType::Prefix => "starts with",
+            MatchType::NotPrefix => "not starts with",
+            MatchType::Other(raw) => return write!(f, "match type {raw}"),
+        })
+    }
+}
+
+/// Packet field a condition tests. Well-known fields get names; anything
+/// else is kept by its condition GUID.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionField {
+    IpProtocol,
+    RemotePort,
+    LocalPort,
+    RemoteAddress,
+    LocalAddress,
+    AppId,
+    UserId,
+    LocalInterface,
+    Flags,
+    Direction,
+    /// IANA interface type of the local interface, e.g. 71 for Wi-Fi.
+    InterfaceType,
+    TunnelType,
+    Other(Uuid),
+}
+
+impl ConditionField {
+    const KNOWN: [(ConditionField, GUID); 12] = [
+        (ConditionField::IpProtocol, FWPM_CONDITION_IP_PROTOCOL),
+        (ConditionField::RemotePort, FWPM_CONDITION_IP_REMOTE_PORT),
+        (ConditionField::LocalPort, FWPM_CONDITION_IP_LOCAL_PORT),
+        (
+            ConditionField::RemoteAddress,
+            FWPM_CONDITION_IP_REMOTE_ADDRESS,
+        ),
+        (
+            ConditionField::LocalAddress,
+            FWPM_CONDITION_IP_LOCAL_ADDRESS,
+        ),
+        (ConditionField::AppId, FWPM_CONDITION_ALE_APP_ID),
+        (ConditionField::UserId, FWPM_CONDITION_ALE_USER_ID),
+        (
+            ConditionField::LocalInterface,
+            FWPM_CONDITION_IP_LOCAL_INTERFACE,
+        ),
+        (ConditionField::Flags, FWPM_CONDITION_FLAGS),
+        (ConditionField::Direction, FWPM_CONDITION_DIRECTION),
+        (ConditionField::InterfaceType, FWPM_CONDITION_INTERFACE_TYPE),
+        (ConditionField::TunnelType, FWPM_CONDITION_TUNNEL_TYPE),
+    ];
+
+    /// The fields with names, for picking one.
+    pub fn named() -> impl Iterator<Item = ConditionField> {
+        Self::KNOWN.into_iter().map(|(field, _)| field)
+    }
+
+    pub fn to_guid(self) -> GUID {
+        match self {
+            ConditionField::Other(id) => guid_from_uuid(id),
+            known => Self::KNOWN
+                .iter()
+                .find(|(field, _)| *field == known)
+                .map(|(_, guid)| *guid)
+                .expect("every named field has a GUID"),
+        }
+    }
+
+    pub fn from_guid(guid: GUID) -> Self {
+        Self::KNOWN
+            .iter()
+            .find(|(_, key)| *key == guid)
+            .map(|(field, _)| *field)
+            .unwrap_or(ConditionField::Other(uuid_from_guid(guid)))
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            ConditionField::IpProtocol => "protocol".into(),
+            ConditionField::RemotePort => "remote port".into(),
+            ConditionField::LocalPort => "local port".into(),
+            ConditionField::RemoteAddress => "remote address".into(),
+            ConditionField::LocalAddress => "local address".into(),
+            ConditionField::AppId => "app".into(),
+            ConditionField::UserId => "user".into(),
+            ConditionField::LocalInterface => "interface".into(),
+            ConditionField::Flags => "flags".into(),
+            ConditionField::Direction => "direction".into(),
+            ConditionField::InterfaceType => "interface type".into(),
+            ConditionField::TunnelType => "tunnel type".into(),
+            ConditionField::Other(id) => id.to_string(),
+        }
+    }
+}
+
+/// Value side of a condition. Types the model cannot represent yet are kept
+/// as `Unsupported` so decoding never fails on foreign filters. Byte values
+/// serialize as hex strings.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ConditionValue {
+    Uint8(u8),
+    Uint16(u16),
+    Uint32(u32),
+    Uint64(u64),
+    Unicode(String),
+    /// 16 raw bytes, e.g. an IPv6 address.
+    ByteArray16(#[serde(with = "hex_bytes")] [u8; 16]),
+    /// 6 raw bytes, e.g. a MAC address.
+    ByteArray6(#[serde(with = "hex_bytes")] [u8; 6]),
+    /// Variable-length bytes, e.g. an app ID.
+    ByteBlob(#[serde(with = "hex_bytes")] Vec<u8>),
+    /// A security identifier in binary form, e.g. an app container's
+    /// package SID.
+    Sid(#[serde(with = "hex_bytes")] Vec<u8>),
+    V4AddrMask {
+        addr: Ipv4Addr,
+        mask: Ipv4Addr,
+    },
+    V6AddrMask {
+        addr: Ipv6Addr,
+        prefix_length: u8,
+    },
+    /// Security descriptor in SDDL, e.g. for user or app container checks.
+    SecurityDescriptor(String),
+    Range {
+        low: Box<ConditionValue>,
+        high: Box<ConditionValue>,
+    },
+    Unsupported {
+        data_type: i32,
+    },
+}
+
+impl ConditionValue {
+    fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            ConditionValue::Uint8(_)
+                | ConditionValue::Uint16(_)
+                | ConditionValue::Uint32(_)
+                | ConditionValue::Uint64(_)
+        )
+    }
+
+    /// Integers and IPv6 addresses can bound a range.
+    fn is_range_bound(&self) -> bool {
+        self.is_integer() || matches!(self, ConditionValue::ByteArray16(_))
+    }
+
+    /// Whether this address value holds `addr`: equal to it, a network
+    /// containing it or a range spanning it. Values of the other address
+    /// family never do; `None` for values that are not addresses.
+    pub fn contains_address(&self, addr: IpAddr) -> Option<bool> {
+        let number = |value: &ConditionValue| match value {
+            ConditionValue::Uint32(v) => Some((false, u128::from(*v))),
+            ConditionValue::ByteArray16(bytes) => Some((true, u128::from_be_bytes(*bytes))),
+            _ => None,
+        };
+        let (v6, actual) = match addr {
+            IpAddr::V4(a) => (false, u128::from(u32::from(a))),
+            IpAddr::V6(a) => (true, u128::from(a)),
+        };
+        match self {
+            ConditionValue::Uint32(_) | ConditionValue::ByteArray16(_) => {
+                let (family, expected) = number(self)?;
+                Some(family == v6 && expected == actual)
+            }
+            ConditionValue::V4AddrMask { addr: net, mask } => {
+                let mask = u128::from(u32::from(*mask));
+                Some(!v6 && actual & mask == u128::from(u32::from(*net)) & mask)
+            }
+            ConditionValue::V6AddrMask {
+                addr: net,
+                prefix_length,
+            } => Some(
+                RemoteAddress {
+                    addr: IpAddr::V6(*net),
+                    prefix: *prefix_length,
+                }
+                .contains(addr),
+            ),
+            ConditionValue::Range { low, high } => {
+                let (family, low) = number(low)?;
+                let (_, high) = number(high)?;
+                Some(family == v6 && low <= actual && actual <= high)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ConditionValue {
+    /// Reads a value of `field` as typed, on a layer of IPv6 when `v6`:
+    /// numbers (`0x` hex too), protocol names, addresses and networks,
+    /// app paths, SDDL, quoted text, `0x` byte strings and `low..=high`
+    /// ranges. The inverse of [`Self::edit_text`].
+    pub fn parse(field: ConditionField, v6: bool, text: &str) -> Result<Self> {
+        let text = text.trim();
+        if let Some((low, high)) = text.split_once("..=") {
+            return Ok(ConditionValue::Range {
+                low: Box::new(Self::parse(field, v6, low)?),
+                high: Box::new(Self::parse(field, v6, high)?),
+            });
+        }
+        let invalid = || anyhow!("'{text}' is not {}", value_kind(field, v6));
+        let value = match field {
+            ConditionField::IpProtocol => ConditionValue::Uint8(parse_protocol(text)?),
+            ConditionField::RemotePort | ConditionField::LocalPort => {
+                ConditionValue::Uint16(parse_number(text).ok_or_else(invalid)?)
+            }
+            ConditionField::RemoteAddress | ConditionField::LocalAddress => {
+                let address: RemoteAddress = text.parse()?;
+                if address.addr.is_ipv6() != v6 {
+                    return Err(invalid());
+                }
+                address.condition_value()
+            }
+            ConditionField::AppId => match parse_hex(text) {
+                Some(bytes) => ConditionValue::ByteBlob(bytes),
+                // Device paths are app IDs already.
+                None if text.to_ascii_lowercase().starts_with("\\device\\") => {
+                    ConditionValue::ByteBlob(
+                        text.to_lowercase()
+                            .encode_utf16()
+                            .chain([0])
+                            .flat_map(u16::to_le_bytes)
+                            .collect(),
+                    )
+                }
+                None => app_id(text)?,
+            },
+            ConditionField::UserId => ConditionValue::SecurityDescriptor(text.to_string()),
+            ConditionField::LocalInterface => {
+                ConditionValue::Uint64(parse_number(text).ok_or_else(invalid)?)
+            }
+            ConditionField::Flags
+            | ConditionField::Direction
+            | ConditionField::InterfaceType
+            | ConditionField::TunnelType => {
+                ConditionValue::Uint32(parse_number(text).ok_or_else(invalid)?)
+            }
+            // The field's type is not known, so the text decides.
+            ConditionField::Other(_) => {
+                if let Some(quoted) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+                    ConditionValue::Unicode(quoted.to_string())
+                } else if let Some(bytes) = parse_hex(text) {
+                    ConditionValue::ByteBlob(bytes)
+                } else if let Some(sid) = parse_sid(text) {
+                    ConditionValue::Sid(sid)
+                } else if let Some(number) = parse_number::<u64>(text) {
+                    match u32::try_from(number) {
+                        Ok(number) => ConditionValue::Uint32(number),
+                        Err(_) => ConditionValue::Uint64(number),
+                    }
+                } else {
+                    text.parse::<RemoteAddress>()?.condition_value()
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    /// The value as [`Self::parse`] reads it back: like its display, but
+    /// with networks by prefix length, text unquoted for known fields and
+    /// app IDs as their paths.
+    pub fn edit_text(&self, field: ConditionField) -> String {
+        match self {
+            ConditionValue::V4AddrMask { addr, mask } => {
+                format!("{addr}/{}", u32::from(*mask).leading_ones())
+            }
+            ConditionValue::Unicode(text) if !matches!(field, ConditionField::Other(_)) => {
+                text.clone()
+            }
+            ConditionValue::ByteBlob(bytes) if field == ConditionField::AppId => {
+                utf16z(bytes).unwrap_or_else(|| self.to_string())
+            }
+            ConditionValue::Range { low, high } => {
+                format!("{}..={}", low.edit_text(field), high.edit_text(field))
+            }
+            value => value.to_string(),
+        }
+    }
+
+    /// The raw bytes of a byte value: blobs, SIDs and byte arrays.
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            ConditionValue::ByteArray16(bytes) => Some(bytes),
+            ConditionValue::ByteArray6(bytes) => Some(bytes),
+            ConditionValue::ByteBlob(bytes) | ConditionValue::Sid(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// What the bytes of a byte value most likely say: NUL-terminated
+    /// UTF-16 as in app IDs, printable ASCII or a SID for blobs, and
+    /// addresses for byte arrays. `None` when nothing fits.
+    pub fn decoded(&self) -> Option<String> {
+        match self {
+            ConditionValue::ByteBlob(bytes) => utf16z(bytes)
+                .filter(|text| !text.is_empty() && !text.chars().any(char::is_control))
+                .or_else(|| ascii_text(bytes))
+                .or_else(|| sid_string(bytes)),
+            ConditionValue::Sid(bytes) => sid_string(bytes),
+            ConditionValue::ByteArray16(_) | ConditionValue::ByteArray6(_) => {
+                Some(self.to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this is of the type `field` takes on a layer of IPv6 when
+    /// `v6`. Ranges are judged by their bounds.
+    fn fits(&self, field: ConditionField, v6: bool) -> bool {
+        let value = match self {
+            ConditionValue::Range { low, .. } => &**low,
+            value => value,
+        };
+        match field {
+            ConditionField::IpProtocol => matches!(value, ConditionValue::Uint8(_)),
+            ConditionField::RemotePort | ConditionField::LocalPort => {
+                matches!(value, ConditionValue::Uint16(_))
+            }
+            ConditionField::RemoteAddress | ConditionField::LocalAddress if v6 => matches!(
+                value,
+                ConditionValue::ByteArray16(_) | ConditionValue::V6AddrMask { .. }
+            ),
+            ConditionField::RemoteAddress | ConditionField::LocalAddress => matches!(
+                value,
+                ConditionValue::Uint32(_) | ConditionValue::V4AddrMask { .. }
+            ),
+            ConditionField::AppId => matches!(value, ConditionValue::ByteBlob(_)),
+            ConditionField::UserId => matches!(value, ConditionValue::SecurityDescriptor(_)),
+            ConditionField::LocalInterface => matches!(value, ConditionValue::Uint64(_)),
+            ConditionField::Flags
+            | ConditionField::Direction
+            | ConditionField::InterfaceType
+            | ConditionField::TunnelType => matches!(value, ConditionValue::Uint32(_)),
+            ConditionField::Other(_) => true,
+        }
+    }
+}
+
+/// What `field` takes, for error messages.
+fn value_kind(field: ConditionField, v6: bool) -> &'static str {
+    match field {
+        ConditionField::IpProtocol => "a protocol",
+        ConditionField::RemotePort | ConditionField::LocalPort => "a port",
+        ConditionField::RemoteAddress | ConditionField::LocalAddress if v6 => {
+            "an IPv6 address or network"
+        }
+        ConditionField::RemoteAddress | ConditionField::LocalAddress => {
+            "an IPv4 address or network"
+        }
+        ConditionField::AppId => "an application",
+        ConditionField::UserId => "a security descriptor",
+        ConditionField::LocalInterface => "an interface LUID",
+        ConditionField::Flags
+        | ConditionField::Direction
+        | ConditionField::InterfaceType
+        | ConditionField::TunnelType => "a 32-bit number",
+        ConditionField::Other(_) => "a value",
+    }
+}
+
+/// A decimal or `0x` hex number.
+fn parse_number<T: TryFrom<u64>>(text: &str) -> Option<T> {
+    let number = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    T::try_from(number).ok()
+}
+
+/// `0x` followed by an even number of hex digits, as bytes.
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let hex = text.strip_prefix("0x")?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Bytes that are NUL-terminated UTF-16LE, as text.
+pub(super) fn utf16z(bytes: &[u8]) -> Option<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    match units.split_last() {
+        Some((0, text)) => String::from_utf16(text).ok(),
+        _ => None,
+    }
+}
+
+/// Bytes that are printable ASCII, with or without a closing NUL.
+fn ascii_text(bytes: &[u8]) -> Option<String> {
+    let text = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    (!text.is_empty() && text.iter().all(|b| (0x20..0x7f).contains(b)))
+        .then(|| text.iter().map(|b| char::from(*b)).collect())
+}
+
+/// A binary SID as `S-1-15-2-…`: revision, a 48-bit big-endian authority,
+/// then as many little-endian subauthorities as the second byte counts.
+fn sid_string(bytes: &[u8]) -> Option<String> {
+    let (&[revision, count], rest) = bytes.split_first_chunk::<2>()?;
+    let (authority, subauthorities) = rest.split_first_chunk::<6>()?;
+    if revision != 1 || subauthorities.len() != 4 * usize::from(count) {
+        return None;
+    }
+    let authority = authority
+        .iter()
+        .fold(0u64, |acc, b| acc << 8 | u64::from(*b));
+    let mut sid = if authority < 1 << 32 {
+        format!("S-{revision}-{authority}")
+    } else {
+        format!("S-{revision}-0x{authority:012x}")
+    };
+    for sub in subauthorities.chunks_exact(4) {
+        let sub = u32::from_le_bytes([sub[0], sub[1], sub[2], sub[3]]);
+        sid += &format!("-{sub}");
+    }
+    Some(sid)
+}
+
+/// The inverse of [`sid_string`].
+fn parse_sid(text: &str) -> Option<Vec<u8>> {
+    let mut parts = text.split('-');
+    if !parts.next()?.eq_ignore_ascii_case("s") {
+        return None;
+    }
+    let revision: u8 = parts.next()?.parse().ok()?;
+    let authority: u64 = parse_number(parts.next()?).filter(|a| *a < 1 << 48)?;
+    let subauthorities = parts
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<u32>>>()?;
+    if revision != 1 || subauthorities.len() > 15 {
+        return None;
+    }
+    let mut bytes = vec![revision, subauthorities.len() as u8];
+    bytes.extend_from_slice(&authority.to_be_bytes()[2..]);
+    bytes.extend(subauthorities.iter().flat_map(|sub| sub.to_le_bytes()));
+    Some(bytes)
+}
+
+/// `bytes` as lines of 16 in hex, each after its offset and before its
+/// printable characters, e.g. `0000  5c 00 64 00  \.d.`.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, line)| {
+            let hex: Vec<String> = line.iter().map(|b| format!("{b:02x}")).collect();
+            let text: String = line
+                .iter()
+                .map(|b| match b {
+                    0x20..=0x7e => char::from(*b),
+                    _ => '.',
+                })
+                .collect();
+            format!("{:04x}  {:<47}  {text}", i * 16, hex.join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Which fields a layer classifies on, for the layers our rules and the
+/// condition editor use.
+#[derive(Clone, Copy, Debug)]
+pub struct LayerSchema {
+    pub v6: bool,
+    pub fields: &'static [ConditionField],
+}
+
+/// Fields of the ALE connect and receive/accept layers.
+const ALE_AUTH_FIELDS: &[ConditionField] = &[
+    ConditionField::IpProtocol,
+    ConditionField::RemotePort,
+    ConditionField::LocalPort,
+    ConditionField::RemoteAddress,
+    ConditionField::LocalAddress,
+    ConditionField::AppId,
+    ConditionField::UserId,
+    ConditionField::LocalInterface,
+    ConditionField::Flags,
+    ConditionField::InterfaceType,
+    ConditionField::TunnelType,
+];
+
+/// Listening sockets have no remote end and are TCP only.
+const ALE_LISTEN_FIELDS: &[ConditionField] = &[
+    ConditionField::LocalPort,
+    ConditionField::LocalAddress,
+    ConditionField::AppId,
+    ConditionField::UserId,
+    ConditionField::LocalInterface,
+    ConditionField::Flags,
+    ConditionField::InterfaceType,
+    ConditionField::TunnelType,
+];
+
+const ALE_FLOW_FIELDS: &[ConditionField] = &[
+    ConditionField::IpProtocol,
+    ConditionField::RemotePort,
+    ConditionField::LocalPort,
+    ConditionField::RemoteAddress,
+    ConditionField::LocalAddress,
+    ConditionField::AppId,
+    ConditionField::UserId,
+    ConditionField::LocalInterface,
+    ConditionField::Flags,
+    ConditionField::Direction,
+    ConditionField::InterfaceType,
+    ConditionField::TunnelType,
+];
+
+/// Transport layers see packets, not processes.
+const TRANSPORT_FIELDS: &[ConditionField] = &[
+    ConditionField::IpProtocol,
+    ConditionField::RemotePort,
+    ConditionField::LocalPort,
+    ConditionField::RemoteAddress,
+    ConditionField::LocalAddress,
+    ConditionField::LocalInterface,
+    ConditionField::Flags,
+    ConditionField::InterfaceType,
+    ConditionField::TunnelType,
+];
+
+impl LayerSchema {
+    /// The schema of `layer`; `None` for layers whose fields are not known
+    /// here, on which any condition is let through to BFE.
+    pub fn of(layer: GUID) -> Option<Self> {
+        let schemas = [
+            (FWPM_LAYER_ALE_AUTH_CONNECT_V4, false, ALE_AUTH_FIELDS),
+            (FWPM_LAYER_ALE_AUTH_CONNECT_V6, true, ALE_AUTH_FIELDS),
+            (FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4, false, ALE_AUTH_FIELDS),
+            (FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6, true, ALE_AUTH_FIELDS),
+            (FWPM_LAYER_ALE_AUTH_LISTEN_V4, false, ALE_LISTEN_FIELDS),
+            (FWPM_LAYER_ALE_AUTH_LISTEN_V6, true, ALE_LISTEN_FIELDS),
+            (FWPM_LAYER_ALE_FLOW_ESTABLISHED_V4, false, ALE_FLOW_FIELDS),
+            (FWPM_LAYER_ALE_FLOW_ESTABLISHED_V6, true, ALE_FLOW_FIELDS),
+            (FWPM_LAYER_INBOUND_TRANSPORT_V4, false, TRANSPORT_FIELDS),
+            (FWPM_LAYER_INBOUND_TRANSPORT_V6, true, TRANSPORT_FIELDS),
+            (FWPM_LAYER_OUTBOUND_TRANSPORT_V4, false, TRANSPORT_FIELDS),
+            (FWPM_LAYER_OUTBOUND_TRANSPORT_V6, true, TRANSPORT_FIELDS),
+        ];
+        schemas
+            .into_iter()
+            .find(|(key, _, _)| *key == layer)
+            .map(|(_, v6, fields)| Self { v6, fields })
+    }
+
+    /// Fails when the layer has no such field or the value is not of the
+    /// field's type. Fields without a name are let through.
+    pub fn check(&self, condition: &Condition) -> Result<()> {
+        let field = condition.field;
+        if !matches!(field, ConditionField::Other(_)) && !self.fields.contains(&field) {
+            return Err(anyhow!(
+                "This layer cannot filter on {}; it has {}",
+                field.label(),
+                self.fields
+                    .iter()
+                    .map(|f| f.label())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if !condition.value.fits(field, self.v6) {
+            return Err(anyhow!(
+                "Condition on {} needs {} here, not {}",
+                field.label(),
+                value_kind(field, self.v6),
+                condition.value
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Serializes byte values as lowercase hex strings.
+pub(super) mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: AsRef<[u8]>, S: Serializer>(
+        bytes: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::to_hex(bytes.as_ref()))
+    }
+
+    pub fn deserialize<'de, T: TryFrom<Vec<u8>>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        if text.len() % 2 != 0 {
+            return Err(D::Error::custom("hex string has an odd length"));
+        }
+        let bytes = (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(text.get(i..i + 2).unwrap_or("?"), 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| D::Error::custom(format!("invalid hex string '{text}'")))?;
+        let len = bytes.len();
+        T::try_from(bytes).map_err(|_| D::Error::custom(format!("unexpected length {len}")))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+impl std::fmt::Display for ConditionValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionValue::Uint8(v) => write!(f, "{v}"),
+            ConditionValue::Uint16(v) => write!(f, "{v}"),
+            ConditionValue::Uint32(v) => write!(f, "{v}"),
+            ConditionValue::Uint64(v) => write!(f, "{v}"),
+            ConditionValue::Unicode(v) => write!(f, "\"{v}\""),
+            ConditionValue::ByteArray16(bytes) => write!(f, "{}", Ipv6Addr::from(*bytes)),
+            ConditionValue::ByteArray6(bytes) => {
+                let parts: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+                write!(f, "{}", parts.join("-"))
+            }
+            ConditionValue::ByteBlob(bytes) => write!(f, "0x{}", to_hex(bytes)),
+            ConditionValue::Sid(bytes) => match sid_string(bytes) {
+                Some(sid) => write!(f, "{sid}"),
+                None => write!(f, "0x{}", to_hex(bytes)),
+            },
+            ConditionValue::V4AddrMask { addr, mask } => write!(f, "{addr}/{mask}"),
+            ConditionValue::V6AddrMask {
+                addr,
+                prefix_length,
+            } => write!(f, "{addr}/{prefix_length}"),
+            ConditionValue::SecurityDescriptor(sddl) => write!(f, "{sddl}"),
+            ConditionValue::Range { low, high } => write!(f, "{low}..={high}"),
+            ConditionValue::Unsupported { data_type } => write!(f, "<type {data_type}>"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Condition {
+    pub field: ConditionField,
+    #[serde(rename = "match")]
+    pub match_type: MatchType,
+    pub value: ConditionValue,
+}
+
+impl Condition {
+    pub fn new(field: ConditionField, match_type: MatchType, value: ConditionValue) -> Self {
+        Self {
+            field,
+            match_type,
+            value,
+        }
+    }
+
+    pub fn equal(field: ConditionField, value: ConditionValue) -> Self {
+        Self::new(field, MatchType::Equal, value)
+    }
+
+    /// Rejects match/value combinations that BFE would refuse with
+    /// FWP_E_TYPE_MISMATCH, so the error can name the offending condition.
+    pub fn validate(&self) -> Result<()> {
+        let ok = match (self.match_type, &self.value) {
+            (MatchType::Other(_), _) | (_, ConditionValue::Unsupported { .. }) => false,
+            (_, ConditionValue::Sid(bytes)) if sid_string(bytes).is_none() => false,
+            (MatchType::Range, ConditionValue::Range { low, high }) => {
+                low.is_range_bound()
+                    && std::mem::discriminant(&**low) == std::mem::discriminant(&**high)
+            }
+            (MatchType::Range, _) | (_, ConditionValue::Range { .. }) => false,
+            (MatchType::FlagsAllSet | MatchType::FlagsAnySet | MatchType::FlagsNoneSet, value) => {
+                value.is_integer()
+            }
+            (MatchType::EqualCaseInsensitive | MatchType::Prefix | MatchType::NotPrefix, value) => {
+                matches!(value, ConditionValue::Unicode(_))
+            }
+            (
+                MatchType::Greater
+                | MatchType::Less
+                | MatchType::GreaterOrEqual
+                | MatchType::LessOrEqual,
+                value,
+            ) => value.is_integer(),
+            (MatchType::Equal | MatchType::NotEqual, _) => true,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Condition on {} cannot use '{}' with value {}",
+                self.field.label(),
+                self.match_type,
+                self.value
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.field.label(),
+            self.match_type,
+            self.value
+        )
+    }
+}
+
+/// The port of a `remote port == n` condition, which is what rule exports
+/// carry.
+pub fn remote_port(conditions: &[Condition]) -> Option<u16> {
+    conditions.iter().find_map(|cond| match cond {
+        Condition {
+            field: ConditionField::RemotePort,
+            match_type: MatchType::Equal,
+            value: ConditionValue::Uint16(port),
+        } => Some(*port),
+        _ => None,
+    })
+}
@@ -0,0 +1,196 @@
+use super::*;
+
+/// A failed FWPM call, kept typed so callers can tell a missing privilege
+/// or a stopped BFE service apart from other failures.
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+#[error("{call} failed: 0x{status:08X}")]
+pub struct WfpError {
+    pub call: &'static str,
+    pub status: u32,
+}
+
+impl WfpError {
+    pub fn new(call: &'static str, status: u32) -> Self {
+        Self { call, status }
+    }
+
+    /// The caller lacks the rights for the call, usually because the
+    /// process is not elevated.
+    pub fn is_access_denied(&self) -> bool {
+        const ERROR_ACCESS_DENIED: u32 = 5;
+        const E_ACCESSDENIED: u32 = 0x8007_0005;
+        matches!(self.status, ERROR_ACCESS_DENIED | E_ACCESSDENIED)
+    }
+
+    /// Another session held a transaction for longer than our wait timeout.
+    pub fn is_timeout(&self) -> bool {
+        const FWP_E_TIMEOUT: u32 = 0x8032_0012;
+        self.status == FWP_E_TIMEOUT
+    }
+
+    /// The session's RPC connection to BFE broke, typically because the
+    /// service restarted; a new session may succeed where this one cannot.
+    pub fn is_session_lost(&self) -> bool {
+        const ERROR_INVALID_HANDLE: u32 = 6;
+        const RPC_S_INVALID_BINDING: u32 = 0x6A6;
+        const RPC_S_SERVER_UNAVAILABLE: u32 = 0x6BA;
+        const RPC_S_CALL_FAILED: u32 = 0x6BE;
+        const RPC_S_CALL_FAILED_DNE: u32 = 0x6BF;
+        const RPC_X_SS_IN_NULL_CONTEXT: u32 = 0x6EF;
+        const RPC_X_SS_CONTEXT_DAMAGED: u32 = 0x6F1;
+        matches!(
+            self.status,
+            ERROR_INVALID_HANDLE
+                | RPC_S_INVALID_BINDING
+                | RPC_S_SERVER_UNAVAILABLE
+                | RPC_S_CALL_FAILED
+                | RPC_S_CALL_FAILED_DNE
+                | RPC_X_SS_IN_NULL_CONTEXT
+                | RPC_X_SS_CONTEXT_DAMAGED
+        )
+    }
+
+    /// BFE is stopped or its RPC endpoint is not reachable.
+    pub fn is_bfe_unavailable(&self) -> bool {
+        const ERROR_SERVICE_NOT_ACTIVE: u32 = 0x426;
+        const RPC_S_SERVER_UNAVAILABLE: u32 = 0x6BA;
+        const EPT_S_NOT_REGISTERED: u32 = 0x6D9;
+        matches!(
+            self.status,
+            ERROR_SERVICE_NOT_ACTIVE | RPC_S_SERVER_UNAVAILABLE | EPT_S_NOT_REGISTERED
+        )
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub filters: Vec<FilterSummary>,
+    /// Boot-time policy, listed separately from the run-time `filters`.
+    pub boot_time_filters: Vec<FilterSummary>,
+    pub providers: Vec<NamedGuid>,
+    pub sublayers: Vec<NamedGuid>,
+    pub layers: Vec<NamedGuid>,
+}
+
+/// A [`Snapshot`] saved to a file with where and when it was taken, so the
+/// engine's state can be analyzed later without access to the machine.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedSnapshot {
+    pub machine: String,
+    pub taken: DateTime<Utc>,
+    pub snapshot: Snapshot,
+    /// Sublayer weights, which order filters across sublayers.
+    #[serde(default)]
+    pub sublayer_details: Vec<SublayerInfo>,
+}
+
+impl SavedSnapshot {
+    /// Enumerates `engine`, open on `server` or this machine, now.
+    pub fn take(engine: &Engine, server: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            machine: match server {
+                Some(server) => server.to_string(),
+                None => std::env::var("COMPUTERNAME").unwrap_or_else(|_| "localhost".into()),
+            },
+            taken: Utc::now(),
+            snapshot: engine.snapshot()?,
+            sublayer_details: engine.sublayer_details()?,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+        serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Invalid snapshot in {}: {e}", path.display()))
+    }
+
+    /// `HOST, 2026-10-16 14:03`.
+    pub fn label(&self) -> String {
+        format!(
+            "{}, {}",
+            self.machine,
+            self.taken
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+        )
+    }
+}
+
+/// How long each phase of one enumeration took, from
+/// [`Engine::timed_snapshot`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SnapshotTimings {
+    pub providers: std::time::Duration,
+    pub sublayers: std::time::Duration,
+    pub layers: std::time::Duration,
+    /// Walking the engine's filters, without decoding them.
+    pub filters: std::time::Duration,
+    /// Turning raw filters into [`FilterSummary`] values and naming them.
+    pub decode: std::time::Duration,
+    pub filter_count: usize,
+}
+
+impl SnapshotTimings {
+    pub const PHASES: [&'static str; 5] = ["providers", "sublayers", "layers", "filters", "decode"];
+
+    /// Durations in the order of [`SnapshotTimings::PHASES`].
+    pub fn phases(&self) -> [std::time::Duration; 5] {
+        [
+            self.providers,
+            self.sublayers,
+            self.layers,
+            self.filters,
+            self.decode,
+        ]
+    }
+}
+
+/// Display names for the keys a filter refers to.
+pub(super) struct FilterNames {
+    providers: HashMap<GUID, String>,
+    sublayers: HashMap<GUID, String>,
+    layers: HashMap<GUID, String>,
+}
+
+impl FilterNames {
+    pub(super) fn new(
+        providers: &[NamedGuid],
+        sublayers: &[NamedGuid],
+        layers: &[NamedGuid],
+    ) -> Self {
+        let map = |items: &[NamedGuid]| items.iter().map(|n| (n.key, n.name.clone())).collect();
+        Self {
+            providers: map(providers),
+            sublayers: map(sublayers),
+            layers: map(layers),
+        }
+    }
+
+    pub(super) fn apply(&self, filter: &mut FilterSummary) {
+        filter.layer = self
+            .layers
+            .get(&filter.layer_key)
+            .cloned()
+            .unwrap_or_else(|| format!("{:#?}", filter.layer_key));
+        filter.sublayer = self
+            .sublayers
+            .get(&filter.sublayer_key)
+            .cloned()
+            .unwrap_or_else(|| format!("{:#?}", filter.sublayer_key));
+        filter.provider = filter
+            .provider_key
+            .and_then(|key| {
+                self.providers
+                    .get(&key)
+                    .cloned()
+                    .or_else(|| known_provider(key).map(String::from))
+            })
+            .unwrap_or_else(|| String::from("<unknown provider>"));
+    }
+}
@@ -0,0 +1,265 @@
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetEventKind {
+    Drop,
+    Allow,
+}
+
+impl NetEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NetEventKind::Drop => "Blocked",
+            NetEventKind::Allow => "Allowed",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlowDirection {
+    Inbound,
+    Outbound,
+}
+
+impl FlowDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FlowDirection::Inbound => "inbound",
+            FlowDirection::Outbound => "outbound",
+        }
+    }
+}
+
+/// A classify verdict recorded by BFE. Header fields are optional because
+/// BFE only fills the ones it knew at classify time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NetEvent {
+    pub time: DateTime<Utc>,
+    pub kind: NetEventKind,
+    /// Runtime ID of the filter that decided the verdict.
+    pub filter_id: u64,
+    pub layer_id: u16,
+    pub direction: Option<FlowDirection>,
+    pub protocol: Option<u8>,
+    pub local_addr: Option<IpAddr>,
+    pub local_port: Option<u16>,
+    pub remote_addr: Option<IpAddr>,
+    pub remote_port: Option<u16>,
+    /// Application device path, e.g. `\device\harddiskvolume3\...\app.exe`.
+    pub app_id: Option<String>,
+}
+
+impl NetEvent {
+    /// `proto local -> remote`, leaving out whatever BFE did not record.
+    pub fn flow(&self) -> String {
+        let endpoint = |addr: Option<IpAddr>, port: Option<u16>| match (addr, port) {
+            (Some(IpAddr::V6(a)), Some(p)) => format!("[{a}]:{p}"),
+            (Some(a), Some(p)) => format!("{a}:{p}"),
+            (Some(a), None) => a.to_string(),
+            (None, Some(p)) => format!("*:{p}"),
+            (None, None) => "*".into(),
+        };
+        format!(
+            "{} {} -> {}",
+            self.protocol.map(protocol_name).unwrap_or("ip"),
+            endpoint(self.local_addr, self.local_port),
+            endpoint(self.remote_addr, self.remote_port)
+        )
+    }
+
+    /// A permit for this flow alone: the same remote address and protocol,
+    /// the service port (remote for outbound flows, local for inbound
+    /// ones) and the same application, each when the event recorded it.
+    pub fn permit(&self, name: &str) -> Result<FilterBuilder> {
+        let remote = self
+            .remote_addr
+            .ok_or_else(|| anyhow!("The event has no remote address to allow"))?;
+        let (layer, port_field, port) = match (self.direction, remote) {
+            (Some(FlowDirection::Outbound), IpAddr::V4(_)) => (
+                FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+                ConditionField::RemotePort,
+                self.remote_port,
+            ),
+            (Some(FlowDirection::Outbound), IpAddr::V6(_)) => (
+                FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+                ConditionField::RemotePort,
+                self.remote_port,
+            ),
+            (Some(FlowDirection::Inbound), IpAddr::V4(_)) => (
+                FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+                ConditionField::LocalPort,
+                self.local_port,
+            ),
+            (Some(FlowDirection::Inbound), IpAddr::V6(_)) => (
+                FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+                ConditionField::LocalPort,
+                self.local_port,
+            ),
+            (None, _) => return Err(anyhow!("The event does not record its direction")),
+        };
+        let address = match remote {
+            IpAddr::V4(addr) => ConditionValue::Uint32(u32::from(addr)),
+            IpAddr::V6(addr) => ConditionValue::ByteArray16(addr.octets()),
+        };
+        let mut builder = FilterBuilder::new(name, layer)
+            .action(WfpAction::Permit)
+            .condition(Condition::equal(ConditionField::RemoteAddress, address));
+        if let Some(protocol) = self.protocol {
+            builder = builder.condition(Condition::equal(
+                ConditionField::IpProtocol,
+                ConditionValue::Uint8(protocol),
+            ));
+        }
+        if let Some(port) = port {
+            builder = builder.condition(Condition::equal(port_field, ConditionValue::Uint16(port)));
+        }
+        if let Some(app) = &self.app_id {
+            // App IDs are the device path BFE reported, as a NUL-terminated
+            // UTF-16 blob.
+            let blob = app
+                .encode_utf16()
+                .chain([0])
+                .flat_map(u16::to_le_bytes)
+                .collect();
+            builder = builder.condition(Condition::equal(
+                ConditionField::AppId,
+                ConditionValue::ByteBlob(blob),
+            ));
+        }
+        Ok(builder)
+    }
+}
+
+/// Net event filter. Empty fields match everything.
+#[derive(Clone, Debug, Default)]
+pub struct NetEventQuery {
+    pub range: TimeRange,
+    pub protocol: Option<u8>,
+    pub local_port: Option<u16>,
+    pub remote_port: Option<u16>,
+    pub remote_address: Option<IpAddr>,
+    /// A full executable path (`C:\...\app.exe`), matched exactly by BFE,
+    /// or a fragment of the device path, matched case-insensitively.
+    pub app: Option<String>,
+    /// Runtime ID of the filter that decided the event.
+    pub filter_id: Option<u64>,
+}
+
+impl NetEventQuery {
+    /// Full path suitable for `FwpmGetAppIdFromFileName0`.
+    pub(super) fn app_path(&self) -> Option<&str> {
+        self.app
+            .as_deref()
+            .map(str::trim)
+            .filter(|app| app.len() > 2 && app.as_bytes()[1] == b':')
+    }
+
+    pub fn matches(&self, event: &NetEvent) -> bool {
+        let field = |want: Option<u16>, have: Option<u16>| want.is_none_or(|w| have == Some(w));
+        self.range.contains(event.time)
+            && self.protocol.is_none_or(|p| event.protocol == Some(p))
+            && field(self.local_port, event.local_port)
+            && field(self.remote_port, event.remote_port)
+            && self
+                .remote_address
+                .is_none_or(|a| event.remote_addr == Some(a))
+            && self.filter_id.is_none_or(|id| event.filter_id == id)
+            && self.app_matches(event)
+    }
+
+    fn app_matches(&self, event: &NetEvent) -> bool {
+        match (self.app.as_deref().map(str::trim), &event.app_id) {
+            (None | Some(""), _) => true,
+            // BFE already compared the device path.
+            _ if self.app_path().is_some() => true,
+            (Some(fragment), Some(app)) => app
+                .to_ascii_lowercase()
+                .contains(&fragment.to_ascii_lowercase()),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Inclusive time window over net events. Open ends are unbounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    /// Builds a range from two bounds in [`TimeRange::parse_bound`] syntax;
+    /// a blank bound leaves that end open.
+    pub fn parse(from: &str, to: &str) -> Result<Self> {
+        let bound = |text: &str| {
+            if text.trim().is_empty() {
+                Ok(None)
+            } else {
+                Self::parse_bound(text).map(Some)
+            }
+        };
+        Ok(Self {
+            from: bound(from)?,
+            to: bound(to)?,
+        })
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| time >= from) && self.to.is_none_or(|to| time <= to)
+    }
+
+    /// Parses one end of a range: RFC 3339 (`2024-05-01T12:00:00Z`), a UTC
+    /// date and time (`2024-05-01 12:00`), a UTC date, or an age counted
+    /// back from now (`30m`, `6h`, `7d`).
+    pub fn parse_bound(text: &str) -> Result<DateTime<Utc>> {
+        let text = text.trim();
+        if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+            return Ok(time.with_timezone(&Utc));
+        }
+        for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+            if let Ok(time) = NaiveDateTime::parse_from_str(text, format) {
+                return Ok(time.and_utc());
+            }
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+            return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+        }
+        let invalid = || anyhow!("Invalid time '{text}'");
+        let unit = text.chars().last().ok_or_else(invalid)?;
+        let amount: i64 = text[..text.len() - unit.len_utf8()]
+            .parse()
+            .map_err(|_| invalid())?;
+        let age = match unit {
+            's' => Duration::seconds(amount),
+            'm' => Duration::minutes(amount),
+            'h' => Duration::hours(amount),
+            'd' => Duration::days(amount),
+            _ => return Err(invalid()),
+        };
+        Ok(Utc::now() - age)
+    }
+}
+
+/// Parses a protocol name from [`protocol_name`] or a protocol number.
+pub fn parse_protocol(text: &str) -> Result<u8> {
+    let text = text.trim().to_ascii_lowercase();
+    match text.as_str() {
+        "icmp" => Ok(1),
+        "tcp" => Ok(6),
+        "udp" => Ok(17),
+        "icmpv6" => Ok(58),
+        _ => text
+            .parse()
+            .map_err(|_| anyhow!("Unknown protocol '{text}'")),
+    }
+}
+
+pub fn protocol_name(protocol: u8) -> &'static str {
+    match protocol {
+        1 => "icmp",
+        6 => "tcp",
+        17 => "udp",
+        58 => "icmpv6",
+        _ => "ip",
+    }
+}
@@ -0,0 +1,2096 @@
+use super::*;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WfpAction {
+    Permit,
+    Block,
+    Callout,
+}
+
+impl WfpAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WfpAction::Permit => "Permit",
+            WfpAction::Block => "Block",
+            WfpAction::Callout => "Callout",
+        }
+    }
+}
+
+/// How a filter hands traffic to its callout (`FWP_ACTION_CALLOUT_*`).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CalloutKind {
+    /// The callout returns the verdict.
+    Terminating,
+    /// The callout only looks; the verdict is left to other filters.
+    Inspection,
+    /// The callout may do either.
+    Unknown,
+}
+
+/// The callout a `Callout` action runs.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CalloutAction {
+    #[serde(with = "guid_serde")]
+    pub key: GUID,
+    pub kind: CalloutKind,
+}
+
+/// Describes a filter to install. Owns everything the native FWPM_FILTER0
+/// needs, so callers never touch the raw condition unions.
+#[derive(Clone, Debug)]
+pub struct FilterBuilder {
+    pub(super) key: GUID,
+    pub(super) name: String,
+    pub(super) layer: GUID,
+    pub(super) action: WfpAction,
+    pub(super) weight: Option<u64>,
+    pub(super) conditions: Vec<Condition>,
+    pub(super) rule: Option<GUID>,
+    pub(super) indexed: bool,
+    pub(super) sublayer: GUID,
+    pub(super) clear_action_right: bool,
+    pub(super) persistent: bool,
+    pub(super) callout: Option<CalloutAction>,
+    pub(super) provider_context: Option<GUID>,
+}
+
+impl FilterBuilder {
+    pub fn new(name: &str, layer: GUID) -> Self {
+        Self {
+            key: guid_from_uuid(Uuid::new_v4()),
+            name: name.to_string(),
+            layer,
+            action: WfpAction::Block,
+            weight: None,
+            conditions: Vec::new(),
+            rule: None,
+            indexed: false,
+            sublayer: SUBLAYER_KEY,
+            clear_action_right: false,
+            persistent: false,
+            callout: None,
+            provider_context: None,
+        }
+    }
+
+    /// The builder installing `filter` again as it is, under its key.
+    pub fn rebuild(filter: &FilterSummary) -> Self {
+        Self {
+            key: filter.key,
+            name: filter.name.clone(),
+            layer: filter.layer_key,
+            action: filter.action,
+            weight: filter.weight,
+            conditions: filter.conditions.clone(),
+            rule: filter.rule_key,
+            indexed: filter.indexed,
+            sublayer: filter.sublayer_key,
+            clear_action_right: filter.clear_action_right,
+            persistent: filter.persistent,
+            callout: filter.callout,
+            provider_context: filter.provider_context_key,
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn key(mut self, key: GUID) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn action(mut self, action: WfpAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Pins an explicit weight, bypassing the automatic [`WeightTier`]
+    /// policy.
+    pub fn weight(mut self, weight: u64) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    /// Drops a pinned weight, so the filter gets the next one in its tier.
+    pub fn auto_weight(mut self) -> Self {
+        self.weight = None;
+        self
+    }
+
+    /// Sets `FWPM_FILTER_FLAG_INDEXED` so BFE indexes the filter's address
+    /// conditions instead of scanning it linearly. Classification stays fast
+    /// with thousands of address filters on a layer.
+    pub fn indexed(mut self, indexed: bool) -> Self {
+        self.indexed = indexed;
+        self
+    }
+
+    /// Places the filter in one of our sublayers instead of the default one.
+    pub fn sublayer(mut self, sublayer: GUID) -> Self {
+        self.sublayer = sublayer;
+        self
+    }
+
+    /// Sets `FWPM_FILTER_FLAG_CLEAR_ACTION_RIGHT`, making a permit "hard":
+    /// blocks in lower-weight sublayers can no longer override it.
+    pub fn clear_action_right(mut self, clear: bool) -> Self {
+        self.clear_action_right = clear;
+        self
+    }
+
+    /// Sets `FWPM_FILTER_FLAG_PERSISTENT`, keeping the filter across BFE
+    /// restarts and reboots.
+    pub fn persistent(mut self, persistent: bool) -> Self {
+        self.persistent = persistent;
+        self
+    }
+
+    /// The callout a `Callout` action hands traffic to.
+    pub fn callout(mut self, callout: CalloutAction) -> Self {
+        self.callout = Some(callout);
+        self
+    }
+
+    /// Attaches a provider context, e.g. settings its callout reads, with
+    /// `FWPM_FILTER_FLAG_HAS_PROVIDER_CONTEXT`.
+    pub fn provider_context(mut self, key: GUID) -> Self {
+        self.provider_context = Some(key);
+        self
+    }
+
+    /// Keeps the callout and provider context of `filter`, for a rule
+    /// rebuilt from its ports in place of it.
+    pub fn bound_like(mut self, filter: &FilterSummary) -> Self {
+        self.callout = filter.callout;
+        self.provider_context = filter.provider_context_key;
+        self
+    }
+
+    /// Tier the automatic weight policy places this filter in.
+    pub fn tier(&self) -> WeightTier {
+        WeightTier::classify(self.action, &self.conditions)
+    }
+
+    /// Assigns the next weight in this filter's tier unless one was pinned.
+    /// Members of an expanded rule share the rule's weight.
+    pub fn allocate_weight(mut self, allocator: &mut WeightAllocator) -> Self {
+        if self.weight.is_none() {
+            let rule = self.rule.unwrap_or(self.key);
+            self.weight = Some(allocator.weight_for(rule, self.tier()));
+        }
+        self
+    }
+
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Tags the filter as a member of the logical rule `rule`. The key is
+    /// stored in the filter's providerData so the group survives restarts.
+    pub fn rule(mut self, rule: GUID) -> Self {
+        self.rule = Some(rule);
+        self
+    }
+
+    /// Expands a condition with OR semantics over `values` into one filter
+    /// per value, all tagged with this builder's key as their rule. Separate
+    /// filters (rather than repeated conditions on one filter) let net events
+    /// and hit counts show which value matched. A single value yields the
+    /// builder itself, keeping its key.
+    pub fn expand_any_of(
+        self,
+        field: ConditionField,
+        match_type: MatchType,
+        values: &[ConditionValue],
+    ) -> Vec<FilterBuilder> {
+        if let [value] = values {
+            return vec![self.condition(Condition::new(field, match_type, value.clone()))];
+        }
+        let rule = self.key;
+        values
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| {
+                self.clone()
+                    .key(member_key(rule, idx))
+                    .rule(rule)
+                    .condition(Condition::new(field, match_type, value.clone()))
+            })
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.action == WfpAction::Callout && self.callout.is_none() {
+            return Err(anyhow!(
+                "Filter '{}' hands traffic to a callout but names none",
+                self.name
+            ));
+        }
+        self.conditions.iter().try_for_each(Condition::validate)
+    }
+
+    /// Checks that the layer can classify on each condition, when it is one
+    /// whose fields are known (see [`LayerSchema`]).
+    pub fn check_layer(&self) -> Result<()> {
+        match LayerSchema::of(self.layer) {
+            Some(schema) => self.conditions.iter().try_for_each(|c| schema.check(c)),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces every condition with `conditions`.
+    pub fn conditions(mut self, conditions: Vec<Condition>) -> Self {
+        self.conditions = conditions;
+        self
+    }
+
+    /// The filter as a snapshot would report it once installed with `id`.
+    /// Layer, sublayer and provider names are left empty.
+    pub fn to_summary(&self, id: u64) -> FilterSummary {
+        FilterSummary {
+            id,
+            key: self.key,
+            rule_key: self.rule,
+            name: self.name.clone(),
+            layer: String::new(),
+            layer_key: self.layer,
+            sublayer: String::new(),
+            sublayer_key: self.sublayer,
+            provider: String::new(),
+            provider_key: Some(PROVIDER_KEY),
+            action: self.action,
+            remote_port: remote_port(&self.conditions),
+            conditions: self.conditions.clone(),
+            weight: self.weight,
+            weight_kind: match self.weight {
+                Some(_) => WeightKind::Exact,
+                None => WeightKind::Auto,
+            },
+            effective_weight: self.weight,
+            boot_time: false,
+            persistent: self.persistent,
+            clear_action_right: self.clear_action_right,
+            indexed: self.indexed,
+            owned_by_app: true,
+            callout: self.callout.filter(|_| self.action == WfpAction::Callout),
+            provider_context_key: self.provider_context,
+        }
+    }
+}
+
+/// Attributes to change across several of our filters at once; `None`
+/// leaves an attribute as each filter has it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BulkEdit {
+    pub action: Option<WfpAction>,
+    pub weight: Option<u64>,
+    pub persistent: Option<bool>,
+    /// The sublayer, i.e. the rule group.
+    pub sublayer: Option<GUID>,
+}
+
+impl BulkEdit {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `action Block, weight 42`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(action) = self.action {
+            parts.push(format!("action {}", action.as_str()));
+        }
+        if let Some(weight) = self.weight {
+            parts.push(format!("weight {weight}"));
+        }
+        if let Some(persistent) = self.persistent {
+            parts.push(format!(
+                "persistent {}",
+                if persistent { "yes" } else { "no" }
+            ));
+        }
+        if let Some(sublayer) = self.sublayer {
+            parts.push(format!("sublayer {}", uuid_from_guid(sublayer)));
+        }
+        parts.join(", ")
+    }
+
+    /// The builder reinstalling `filter` with the changes, under the same
+    /// key. A filter whose action changes gets a new weight in its new
+    /// tier unless one is given, and loses a hard permit's flag.
+    pub fn apply(&self, filter: &FilterSummary) -> Result<FilterBuilder> {
+        if !filter.owned_by_app {
+            return Err(anyhow!(
+                "Filter {} is not managed by this application",
+                filter.id
+            ));
+        }
+        let action = self.action.unwrap_or(filter.action);
+        let mut builder = FilterBuilder::rebuild(filter)
+            .action(action)
+            .persistent(self.persistent.unwrap_or(filter.persistent))
+            .sublayer(self.sublayer.unwrap_or(filter.sublayer_key))
+            .clear_action_right(filter.clear_action_right && action == filter.action);
+        builder.weight = match self.weight {
+            Some(weight) => Some(weight),
+            None if action != filter.action => None,
+            None => filter.weight,
+        };
+        builder.validate()?;
+        Ok(builder)
+    }
+}
+
+/// Priority band a filter's weight falls in. Within our sublayer higher
+/// weights are evaluated first, so allow rules sit above block rules, and
+/// catch-all blocks (no conditions, or only an interface or interface type)
+/// sit below everything as default-deny. Callouts go in the block band
+/// whatever their conditions, so they see traffic before a default-deny
+/// drops it. The band is the top byte of the 64-bit weight; the rest orders
+/// filters within the band.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WeightTier {
+    DefaultDeny,
+    Block,
+    Allow,
+}
+
+impl WeightTier {
+    const SLOT_MASK: u64 = 0x00FF_FFFF_FFFF_FFFF;
+
+    pub fn classify(action: WfpAction, conditions: &[Condition]) -> Self {
+        match action {
+            WfpAction::Permit => WeightTier::Allow,
+            WfpAction::Callout => WeightTier::Block,
+            WfpAction::Block
+                if conditions.iter().all(|c| {
+                    matches!(
+                        c.field,
+                        ConditionField::LocalInterface | ConditionField::InterfaceType
+                    )
+                }) =>
+            {
+                WeightTier::DefaultDeny
+            }
+            WfpAction::Block => WeightTier::Block,
+        }
+    }
+
+    fn band(self) -> u64 {
+        match self {
+            WeightTier::DefaultDeny => 0x40,
+            WeightTier::Block => 0x80,
+            WeightTier::Allow => 0xC0,
+        }
+    }
+
+    /// Highest weight in the tier, given to its first filter.
+    pub fn top(self) -> u64 {
+        (self.band() << 56) | Self::SLOT_MASK
+    }
+
+    pub fn of_weight(weight: u64) -> Option<Self> {
+        [
+            WeightTier::DefaultDeny,
+            WeightTier::Block,
+            WeightTier::Allow,
+        ]
+        .into_iter()
+        .find(|tier| tier.band() == weight >> 56)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WeightTier::DefaultDeny => "default-deny",
+            WeightTier::Block => "block",
+            WeightTier::Allow => "allow",
+        }
+    }
+}
+
+/// Hands out weights per [`WeightTier`], preserving insertion order: each
+/// new rule lands just below the lowest weight already used in its tier, so
+/// earlier rules keep precedence. Rules that already sit in the requested
+/// tier keep their weight, which keeps re-imports and edits stable.
+#[derive(Clone, Debug, Default)]
+pub struct WeightAllocator {
+    lowest: HashMap<WeightTier, u64>,
+    existing: HashMap<GUID, u64>,
+}
+
+impl WeightAllocator {
+    /// Records an installed rule's weight.
+    pub fn record(&mut self, rule: GUID, weight: u64) {
+        self.existing.insert(rule, weight);
+        if let Some(tier) = WeightTier::of_weight(weight) {
+            let lowest = self.lowest.entry(tier).or_insert(weight);
+            *lowest = (*lowest).min(weight);
+        }
+    }
+
+    pub fn weight_for(&mut self, rule: GUID, tier: WeightTier) -> u64 {
+        if let Some(&weight) = self.existing.get(&rule) {
+            if WeightTier::of_weight(weight) == Some(tier) {
+                return weight;
+            }
+        }
+        let weight = match self.lowest.get(&tier) {
+            // Saturate at the bottom of the band rather than spill into the
+            // tier below.
+            Some(&lowest) => lowest.saturating_sub(1).max(tier.band() << 56),
+            None => tier.top(),
+        };
+        self.record(rule, weight);
+        weight
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FilterSummary {
+    pub id: u64,
+    #[serde(with = "guid_serde")]
+    pub key: GUID,
+    /// Key of the multi-value rule this filter was expanded from, if any.
+    #[serde(with = "guid_serde::option")]
+    pub rule_key: Option<GUID>,
+    pub name: String,
+    pub layer: String,
+    #[serde(with = "guid_serde")]
+    pub layer_key: GUID,
+    pub sublayer: String,
+    #[serde(with = "guid_serde")]
+    pub sublayer_key: GUID,
+    pub provider: String,
+    #[serde(with = "guid_serde::option")]
+    pub provider_key: Option<GUID>,
+    pub action: WfpAction,
+    pub remote_port: Option<u16>,
+    pub conditions: Vec<Condition>,
+    pub weight: Option<u64>,
+    /// How `weight` was given when the filter was added.
+    pub weight_kind: WeightKind,
+    /// The 64-bit weight BFE arbitrates with inside the sublayer.
+    pub effective_weight: Option<u64>,
+    /// Enforced from boot until BFE starts (`FWPM_FILTER_FLAG_BOOTTIME`).
+    pub boot_time: bool,
+    /// Survives reboots and is reloaded by BFE (`FWPM_FILTER_FLAG_PERSISTENT`).
+    pub persistent: bool,
+    /// A permit that clears the action write right (a "hard" permit), which
+    /// lower-priority sublayers cannot override with a block.
+    pub clear_action_right: bool,
+    /// Address conditions are indexed by BFE (`FWPM_FILTER_FLAG_INDEXED`).
+    pub indexed: bool,
+    pub owned_by_app: bool,
+    /// The callout of a `Callout` action, when BFE names one.
+    #[serde(default)]
+    pub callout: Option<CalloutAction>,
+    /// The provider context the filter carries, if any.
+    #[serde(default, with = "guid_serde::option")]
+    pub provider_context_key: Option<GUID>,
+}
+
+/// The form of weight a filter was added with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeightKind {
+    /// No weight; BFE derives one from the filter's conditions.
+    Auto,
+    /// A 0–15 range selector; BFE picks a weight inside that sixteenth of
+    /// the 64-bit space.
+    Range(u8),
+    /// An exact 64-bit weight.
+    Exact,
+}
+
+impl FilterSummary {
+    /// Key that identifies the logical rule: the parent rule for expanded
+    /// members, otherwise the filter itself.
+    pub fn rule_key(&self) -> GUID {
+        self.rule_key.unwrap_or(self.key)
+    }
+
+    /// One of our allow filters with a remote address condition letting
+    /// `addr` through. Conditions on the same field are ORed, so one match
+    /// is enough.
+    pub fn permits_remote(&self, addr: IpAddr) -> bool {
+        self.owned_by_app
+            && self.action == WfpAction::Permit
+            && self
+                .conditions
+                .iter()
+                .filter(|c| c.field == ConditionField::RemoteAddress)
+                .any(|c| {
+                    let holds = c.value.contains_address(addr) == Some(true);
+                    match c.match_type {
+                        MatchType::NotEqual => !holds,
+                        _ => holds,
+                    }
+                })
+    }
+}
+
+/// Serializable view of a [`FilterSummary`] with keys as UUID strings, used
+/// wherever filters leave the process as JSON.
+#[derive(Clone, Debug, Serialize)]
+pub struct FilterRecord {
+    pub id: u64,
+    pub key: Uuid,
+    pub rule_key: Uuid,
+    pub name: String,
+    pub layer: String,
+    pub sublayer: String,
+    pub provider: String,
+    pub action: WfpAction,
+    pub weight: Option<u64>,
+    pub conditions: Vec<Condition>,
+    pub boot_time: bool,
+    pub persistent: bool,
+    pub indexed: bool,
+    pub owned: bool,
+}
+
+impl From<&FilterSummary> for FilterRecord {
+    fn from(f: &FilterSummary) -> Self {
+        Self {
+            id: f.id,
+            key: uuid_from_guid(f.key),
+            rule_key: uuid_from_guid(f.rule_key()),
+            name: f.name.clone(),
+            layer: f.layer.clone(),
+            sublayer: f.sublayer.clone(),
+            provider: f.provider.clone(),
+            action: f.action,
+            weight: f.weight,
+            conditions: f.conditions.clone(),
+            boot_time: f.boot_time,
+            persistent: f.persistent,
+            indexed: f.indexed,
+            owned: f.owned_by_app,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FilterConfig {
+    /// Persistent filterKey used to match the rule across imports. Assigned
+    /// on first import when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<Uuid>,
+    pub name: String,
+    /// A single port or a list; lists are installed as one filter per port
+    /// and managed as one rule. Rules limited by app or address may leave
+    /// it out to cover every port.
+    #[serde(default, skip_serializing_if = "RemotePorts::is_empty")]
+    pub remote_port: RemotePorts,
+    /// Ports on this machine, e.g. the port an inbound connection is
+    /// accepted on. Matched in addition to any remote ports.
+    #[serde(default, skip_serializing_if = "RemotePorts::is_empty")]
+    pub local_port: RemotePorts,
+    pub action: WfpAction,
+    /// Executable the rule is limited to, matched by app ID. May be a
+    /// pattern such as `%ProgramFiles%\Vendor\*`, covering every executable
+    /// it matches when the rule is installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// Remote addresses or networks; any address when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remote_address: Vec<RemoteAddress>,
+    /// Hostnames whose addresses are added to the remote addresses when
+    /// the rule is installed. They are recorded per rule (see
+    /// [`rule_hosts`]), as filters only keep the addresses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remote_host: Vec<String>,
+    /// When unset, TCP if ports are given and any protocol otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<RuleProtocol>,
+    #[serde(default, skip_serializing_if = "Direction::is_out")]
+    pub direction: Direction,
+    /// One of our sublayers, for rule groups arbitrated separately; the
+    /// default sublayer when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sublayer: Option<Uuid>,
+    /// Only applies on this kind of network, e.g. untrusted Wi-Fi; on every
+    /// interface when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<InterfaceMedia>,
+    /// Weight of every filter of the rule. Exports record it; imports
+    /// without one derive it from the rule's place in the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u64>,
+    /// Keys of rules that must be installed for this one to be, e.g. the
+    /// block-all rule its allow exceptions carve holes in. Rules imported
+    /// together are installed after their dependencies.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<Uuid>,
+    /// Provider context every filter of the rule carries, for its callout
+    /// to read; see [`RuleSet::provider_contexts`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_context: Option<Uuid>,
+}
+
+impl FilterConfig {
+    /// A rule in the original shape: outbound TCP to `remote_ports`.
+    pub fn tcp_ports(name: &str, remote_ports: RemotePorts, action: WfpAction) -> Self {
+        Self {
+            key: None,
+            name: name.to_string(),
+            remote_port: remote_ports,
+            local_port: RemotePorts::default(),
+            action,
+            app: None,
+            remote_address: Vec::new(),
+            remote_host: Vec::new(),
+            protocol: None,
+            direction: Direction::Out,
+            sublayer: None,
+            interface: None,
+            weight: None,
+            depends_on: Vec::new(),
+            provider_context: None,
+        }
+    }
+
+    /// Checks what the schema cannot, before any transaction is opened.
+    pub fn validate(&self) -> Result<()> {
+        let ports = self.remote_port.as_slice();
+        let local_ports = self.local_port.as_slice();
+        if ports.contains(&0) || local_ports.contains(&0) {
+            return Err(anyhow!("Rule '{}' needs non-zero ports", self.name));
+        }
+        if ports.is_empty()
+            && local_ports.is_empty()
+            && self.app.is_none()
+            && self.remote_address.is_empty()
+            && self.remote_host.is_empty()
+        {
+            return Err(anyhow!("Rule '{}' needs non-zero remote ports", self.name));
+        }
+        if self.key.is_some_and(|key| self.depends_on.contains(&key)) {
+            return Err(anyhow!("Rule '{}' cannot depend on itself", self.name));
+        }
+        if self.action == WfpAction::Callout {
+            return Err(anyhow!(
+                "Rule '{}' cannot hand traffic to a callout, as rules do not name one",
+                self.name
+            ));
+        }
+        if !(ports.is_empty() && local_ports.is_empty()) && self.protocol() == RuleProtocol::Any {
+            return Err(anyhow!(
+                "Rule '{}' has ports, so it must be TCP or UDP",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn protocol(&self) -> RuleProtocol {
+        self.protocol.unwrap_or(
+            if self.remote_port.is_empty() && self.local_port.is_empty() {
+                RuleProtocol::Any
+            } else {
+                RuleProtocol::Tcp
+            },
+        )
+    }
+
+    /// Executables the rule is limited to: the app itself, or what its
+    /// pattern matches now (see [`resolve_app_pattern`]).
+    pub fn app_paths(&self) -> Result<Vec<String>> {
+        match &self.app {
+            Some(app) if is_app_pattern(app) => Ok(resolve_app_pattern(app)?
+                .into_iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()),
+            Some(app) => Ok(vec![app.clone()]),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The remote addresses with those of the hostnames as they resolve now.
+    pub fn remote_addresses(&self) -> Result<Vec<RemoteAddress>> {
+        let mut addresses = self.remote_address.clone();
+        for host in &self.remote_host {
+            for addr in resolve_host(host)? {
+                let address = RemoteAddress::from(addr);
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+        Ok(addresses)
+    }
+
+    /// Outbound TCP to ports only, which installs and exports in the
+    /// original IPv4 shape.
+    pub(super) fn is_tcp_port_rule(&self) -> bool {
+        self.app.is_none()
+            && self.local_port.is_empty()
+            && self.remote_address.is_empty()
+            && self.interface.is_none()
+            && self.direction == Direction::Out
+            && self.protocol() == RuleProtocol::Tcp
+    }
+
+    /// The filters making up the rule. Port rules keep the original IPv4
+    /// TCP shape; other rules cover IPv4 and IPv6 as their addresses allow.
+    /// Resolving an app needs the executable to exist.
+    ///
+    /// Permits in a sublayer of their own are hard permits, so they win
+    /// over blocks in our lower-weight sublayers.
+    pub fn builders(&self, key: GUID) -> Result<Vec<FilterBuilder>> {
+        let mut builders = self.builders_in_default_sublayer(key)?;
+        if let Some(weight) = self.weight {
+            builders = builders.into_iter().map(|b| b.weight(weight)).collect();
+        }
+        if let Some(context) = self.provider_context.map(guid_from_uuid) {
+            builders = builders
+                .into_iter()
+                .map(|b| b.provider_context(context))
+                .collect();
+        }
+        let Some(sublayer) = self.sublayer.map(guid_from_uuid) else {
+            return Ok(builders);
+        };
+        let hard = self.action == WfpAction::Permit;
+        Ok(builders
+            .into_iter()
+            .map(|b| b.sublayer(sublayer).clear_action_right(hard))
+            .collect())
+    }
+
+    fn builders_in_default_sublayer(&self, key: GUID) -> Result<Vec<FilterBuilder>> {
+        // A port listed twice would give two filters the same member key.
+        let mut ports = self.remote_port.clone();
+        ports.normalize();
+        let ports = ports.as_slice();
+        if self.is_tcp_port_rule() {
+            return Ok(simple_tcp_rule_v4(key, &self.name, ports, self.action));
+        }
+        let mut common = Vec::new();
+        // One condition per executable; conditions on the same field are
+        // ORed.
+        for app in self.app_paths()? {
+            common.push(Condition::equal(ConditionField::AppId, app_id(&app)?));
+        }
+        if let Some(media) = self.interface {
+            // Conditions on the same field are ORed, so every type of the
+            // medium matches.
+            for if_type in media.if_types() {
+                common.push(Condition::equal(
+                    ConditionField::InterfaceType,
+                    ConditionValue::Uint32(*if_type),
+                ));
+            }
+        }
+        if let Some(protocol) = self.protocol().number() {
+            common.push(Condition::equal(
+                ConditionField::IpProtocol,
+                ConditionValue::Uint8(protocol),
+            ));
+        }
+        // ORed like the interface types, so one filter covers every port.
+        for port in self.local_port.as_slice() {
+            common.push(Condition::equal(
+                ConditionField::LocalPort,
+                ConditionValue::Uint16(*port),
+            ));
+        }
+        Ok(expand_rule(
+            key,
+            &self.name,
+            self.action,
+            self.direction,
+            &common,
+            &self.remote_addresses()?,
+            ports,
+        ))
+    }
+}
+
+/// Most executables one app pattern may stand for, so a pattern on a
+/// large tree does not turn into an enormous filter.
+pub const MAX_APP_PATTERN_MATCHES: usize = 256;
+
+/// Whether `app` is a pattern rather than one executable: it uses
+/// `%VARIABLE%`s, wildcards or ends in a directory separator.
+pub fn is_app_pattern(app: &str) -> bool {
+    let app = app.trim();
+    app.contains(['%', '*', '?']) || app.ends_with(['\\', '/'])
+}
+
+/// Executables an app pattern matches, sorted. Environment variables are
+/// expanded first. Wildcards are allowed in the last component only and
+/// match the names of `.exe` files anywhere below its directory, so
+/// `%ProgramFiles%\Vendor\*` (or just `...\Vendor\`) covers every
+/// executable the vendor installed. A pattern without wildcards is one
+/// path.
+pub fn resolve_app_pattern(pattern: &str) -> Result<Vec<PathBuf>> {
+    let expanded = expand_env_vars(pattern.trim())?;
+    let (dir, name) = match expanded.rfind(['\\', '/']) {
+        Some(idx) => (&expanded[..idx], &expanded[idx + 1..]),
+        None => (".", expanded.as_str()),
+    };
+    let name = if name.is_empty() { "*" } else { name };
+    if dir.contains(['*', '?']) {
+        return Err(anyhow!(
+            "'{pattern}': wildcards are only supported in the file name"
+        ));
+    }
+    if !name.contains(['*', '?']) {
+        return Ok(vec![PathBuf::from(expanded)]);
+    }
+    let mut found = Vec::new();
+    let mut pending = vec![PathBuf::from(dir)];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            std::fs::read_dir(&dir).map_err(|e| anyhow!("Cannot scan {}: {e}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let file_name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+            if file_name.ends_with(".exe") && wildcard_match(&name.to_ascii_lowercase(), &file_name)
+            {
+                found.push(path);
+            }
+        }
+        if found.len() > MAX_APP_PATTERN_MATCHES {
+            return Err(anyhow!(
+                "'{pattern}' matches more than {MAX_APP_PATTERN_MATCHES} executables; \
+                 narrow it down"
+            ));
+        }
+    }
+    if found.is_empty() {
+        // Without an app condition the rule would cover every app.
+        return Err(anyhow!("'{pattern}' matches no executables"));
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Replaces each `%NAME%` with the value of the environment variable.
+pub fn expand_env_vars(text: &str) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('%')
+            .ok_or_else(|| anyhow!("Unclosed % in '{text}'"))?;
+        let name = &after[..end];
+        let value =
+            std::env::var(name).map_err(|_| anyhow!("Environment variable %{name}% is not set"))?;
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `*` matches any run of characters, `?` any one character.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Greedy match, going back to the last `*` on a mismatch.
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Which ALE layers a rule is installed at.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Connections this machine makes.
+    #[default]
+    Out,
+    /// Connections this machine accepts.
+    In,
+}
+
+impl Direction {
+    pub fn is_out(&self) -> bool {
+        *self == Direction::Out
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Out => "out",
+            Direction::In => "in",
+        }
+    }
+
+    fn layers(self) -> [GUID; 2] {
+        match self {
+            Direction::Out => [
+                FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+                FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+            ],
+            Direction::In => [
+                FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+                FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+            ],
+        }
+    }
+}
+
+/// Kind of network an interface connects to, matched by IANA interface
+/// type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterfaceMedia {
+    Ethernet,
+    Wifi,
+    Cellular,
+    /// Tunnels and dial-up links, which is how most VPN adapters appear.
+    Vpn,
+}
+
+impl InterfaceMedia {
+    pub const ALL: [InterfaceMedia; 4] = [
+        InterfaceMedia::Ethernet,
+        InterfaceMedia::Wifi,
+        InterfaceMedia::Cellular,
+        InterfaceMedia::Vpn,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InterfaceMedia::Ethernet => "ethernet",
+            InterfaceMedia::Wifi => "wifi",
+            InterfaceMedia::Cellular => "cellular",
+            InterfaceMedia::Vpn => "vpn",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InterfaceMedia::Ethernet => "Ethernet",
+            InterfaceMedia::Wifi => "Wi-Fi",
+            InterfaceMedia::Cellular => "Cellular",
+            InterfaceMedia::Vpn => "VPN / tunnel",
+        }
+    }
+
+    /// `IF_TYPE_*` values from ipifcons.h.
+    pub fn if_types(self) -> &'static [u32] {
+        match self {
+            InterfaceMedia::Ethernet => &[6],
+            InterfaceMedia::Wifi => &[71],
+            InterfaceMedia::Cellular => &[243, 244],
+            InterfaceMedia::Vpn => &[23, 131],
+        }
+    }
+
+    /// The medium whose interface types are exactly those tested by the
+    /// interface type conditions in `conditions`.
+    pub fn of(conditions: &[Condition]) -> Option<Self> {
+        let mut types: Vec<u32> = conditions
+            .iter()
+            .filter(|c| c.field == ConditionField::InterfaceType)
+            .filter_map(|c| match c.value {
+                ConditionValue::Uint32(t) => Some(t),
+                _ => None,
+            })
+            .collect();
+        types.sort_unstable();
+        Self::ALL.into_iter().find(|m| m.if_types() == types)
+    }
+}
+
+impl std::str::FromStr for InterfaceMedia {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "ethernet" | "wired" => Ok(InterfaceMedia::Ethernet),
+            "wifi" | "wi-fi" | "wireless" => Ok(InterfaceMedia::Wifi),
+            "cellular" | "mobile" | "wwan" => Ok(InterfaceMedia::Cellular),
+            "vpn" | "tunnel" => Ok(InterfaceMedia::Vpn),
+            other => Err(anyhow!(
+                "Unknown interface type '{other}'; use ethernet, wifi, cellular or vpn"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleProtocol {
+    Tcp,
+    Udp,
+    Any,
+}
+
+impl RuleProtocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RuleProtocol::Tcp => "tcp",
+            RuleProtocol::Udp => "udp",
+            RuleProtocol::Any => "any",
+        }
+    }
+
+    /// IANA protocol number; `None` for any protocol.
+    pub fn number(self) -> Option<u8> {
+        match self {
+            RuleProtocol::Tcp => Some(6),
+            RuleProtocol::Udp => Some(17),
+            RuleProtocol::Any => None,
+        }
+    }
+}
+
+/// A remote host or network, written `10.0.0.0/8`, `192.0.2.1` or
+/// `2001:db8::/32`. Serialized in that form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RemoteAddress {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+
+impl RemoteAddress {
+    /// Whether `addr` is this host or falls in this network.
+    pub fn contains(self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+
+    pub(super) fn condition_value(self) -> ConditionValue {
+        match self.addr {
+            IpAddr::V4(addr) if self.prefix == 32 => ConditionValue::Uint32(u32::from(addr)),
+            IpAddr::V4(addr) => ConditionValue::V4AddrMask {
+                addr,
+                mask: Ipv4Addr::from(
+                    u32::MAX
+                        .checked_shl(32 - u32::from(self.prefix))
+                        .unwrap_or(0),
+                ),
+            },
+            IpAddr::V6(addr) if self.prefix == 128 => ConditionValue::ByteArray16(addr.octets()),
+            IpAddr::V6(addr) => ConditionValue::V6AddrMask {
+                addr,
+                prefix_length: self.prefix,
+            },
+        }
+    }
+
+    /// The address or network a remote address condition tests; `None` for
+    /// masks that are not a prefix.
+    pub(super) fn from_condition_value(value: &ConditionValue) -> Option<Self> {
+        let (addr, prefix) = match *value {
+            ConditionValue::Uint32(addr) => (IpAddr::V4(Ipv4Addr::from(addr)), 32),
+            ConditionValue::ByteArray16(octets) => (IpAddr::V6(octets.into()), 128),
+            ConditionValue::V4AddrMask { addr, mask } => {
+                let mask = u32::from(mask);
+                if mask.leading_ones() != mask.count_ones() {
+                    return None;
+                }
+                (IpAddr::V4(addr), mask.count_ones() as u8)
+            }
+            ConditionValue::V6AddrMask {
+                addr,
+                prefix_length,
+            } if prefix_length <= 128 => (IpAddr::V6(addr), prefix_length),
+            _ => return None,
+        };
+        Some(Self { addr, prefix })
+    }
+}
+
+impl std::str::FromStr for RemoteAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || anyhow!("'{text}' is not an address or network like 10.0.0.0/8");
+        let (addr, prefix) = match text.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (text, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(invalid)?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl From<IpAddr> for RemoteAddress {
+    /// The single host `addr`.
+    fn from(addr: IpAddr) -> Self {
+        Self {
+            addr,
+            prefix: if addr.is_ipv4() { 32 } else { 128 },
+        }
+    }
+}
+
+impl TryFrom<String> for RemoteAddress {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        text.parse()
+    }
+}
+
+impl From<RemoteAddress> for String {
+    fn from(address: RemoteAddress) -> Self {
+        address.to_string()
+    }
+}
+
+impl std::fmt::Display for RemoteAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let host = if self.addr.is_ipv4() { 32 } else { 128 };
+        if self.prefix == host {
+            write!(f, "{}", self.addr)
+        } else {
+            write!(f, "{}/{}", self.addr, self.prefix)
+        }
+    }
+}
+
+/// Whether `text` reads as a hostname rather than an address.
+pub fn is_hostname(text: &str) -> bool {
+    !text.is_empty()
+        && text.chars().any(|c| c.is_ascii_alphabetic())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// The addresses `host` resolves to now, sorted.
+pub fn resolve_host(host: &str) -> Result<Vec<IpAddr>> {
+    use std::net::ToSocketAddrs;
+    let mut addrs: Vec<IpAddr> = (host, 0)
+        .to_socket_addrs()
+        .map_err(|e| anyhow!("Cannot resolve {host}: {e}"))?
+        .map(|addr| addr.ip())
+        .collect();
+    addrs.sort();
+    addrs.dedup();
+    if addrs.is_empty() {
+        return Err(anyhow!("{host} has no addresses"));
+    }
+    Ok(addrs)
+}
+
+const RULE_HOSTS_FILE: &str = "rule_hosts.json";
+
+/// The hostnames a rule was installed with and the addresses they
+/// resolved to then.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleHosts {
+    pub hosts: Vec<String>,
+    pub addresses: Vec<IpAddr>,
+}
+
+/// [`RuleHosts`] of each installed rule with [`FilterConfig::remote_host`],
+/// by rule key.
+pub fn rule_hosts() -> Result<BTreeMap<Uuid, RuleHosts>> {
+    let path = crate::config::app_data_dir().join(RULE_HOSTS_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Invalid rule hostnames in {}: {e}", path.display())),
+        Err(_) => Ok(BTreeMap::new()),
+    }
+}
+
+/// Records the hostnames of the keyed rules just installed, dropping those
+/// of rules installed again without any. Best effort: the rules are in
+/// place either way.
+pub(super) fn remember_rule_hosts(configs: &[FilterConfig]) {
+    let Ok(mut recorded) = rule_hosts() else {
+        return;
+    };
+    let mut changed = false;
+    for cfg in configs {
+        let Some(key) = cfg.key else {
+            continue;
+        };
+        if cfg.remote_host.is_empty() {
+            changed |= recorded.remove(&key).is_some();
+            continue;
+        }
+        // Answered from the resolver cache filled by the install.
+        let addresses = cfg
+            .remote_host
+            .iter()
+            .filter_map(|host| resolve_host(host).ok())
+            .flatten()
+            .collect();
+        recorded.insert(
+            key,
+            RuleHosts {
+                hosts: cfg.remote_host.clone(),
+                addresses,
+            },
+        );
+        changed = true;
+    }
+    if changed {
+        let dir = crate::config::app_data_dir();
+        let _ = std::fs::create_dir_all(&dir).and_then(|()| {
+            std::fs::write(
+                dir.join(RULE_HOSTS_FILE),
+                serde_json::to_string_pretty(&recorded).unwrap_or_default(),
+            )
+        });
+    }
+}
+
+/// [`rules_from_filters`] over a stream such as [`Engine::filters_iter`],
+/// keeping only the owned filters in memory while it is read.
+pub fn owned_rules(
+    filters: impl Iterator<Item = Result<FilterSummary>>,
+) -> Result<Vec<FilterConfig>> {
+    let owned = filters
+        .filter(|f| f.as_ref().map_or(true, |f| f.owned_by_app))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(rules_from_filters(owned))
+}
+
+/// Folds owned filters back into the rules they were expanded from, in the
+/// order each rule is first seen. A rule is exported when all its filters
+/// have a shape [`FilterConfig::builders`] produces: a permit or block on
+/// the connect or accept layers, matching only a protocol, ports, a remote
+/// address, one app and an interface medium, and agreeing on all but the
+/// remote port and address. Apps come back as the device path BFE keeps,
+/// which imports as it is, and hostnames as the addresses they resolved to.
+pub fn rules_from_filters(filters: impl IntoIterator<Item = FilterSummary>) -> Vec<FilterConfig> {
+    // `None` once a filter of the rule turns out not to be exportable.
+    let mut rules: Vec<Option<(FilterConfig, RuleMember)>> = Vec::new();
+    let mut by_rule: HashMap<GUID, usize> = HashMap::new();
+    for filter in filters.into_iter().filter(|f| f.owned_by_app) {
+        let member = RuleMember::of(&filter);
+        let rule = filter.rule_key();
+        let Some(&idx) = by_rule.get(&rule) else {
+            by_rule.insert(rule, rules.len());
+            rules.push(member.map(|member| {
+                let mut config =
+                    FilterConfig::tcp_ports(&filter.name, RemotePorts::default(), filter.action);
+                config.key = Some(uuid_from_guid(rule));
+                config.sublayer = Some(filter.sublayer_key)
+                    .filter(|key| *key != SUBLAYER_KEY)
+                    .map(uuid_from_guid);
+                config.weight = filter.weight;
+                config.provider_context = filter.provider_context_key.map(uuid_from_guid);
+                member.fold_into(&mut config);
+                (config, member)
+            }));
+            continue;
+        };
+        let entry = &mut rules[idx];
+        match (entry.as_mut(), member) {
+            (Some((config, first)), Some(member)) if member.agrees_with(first) => {
+                member.fold_into(config)
+            }
+            _ => *entry = None,
+        }
+    }
+    rules
+        .into_iter()
+        .flatten()
+        .map(|(mut config, member)| {
+            config.remote_port.normalize();
+            // Left out where the ports imply it, as rules are written.
+            config.protocol = None;
+            config.protocol = Some(member.protocol).filter(|p| *p != config.protocol());
+            config
+        })
+        .collect()
+}
+
+/// What one filter says about the rule it was expanded from.
+struct RuleMember {
+    action: WfpAction,
+    direction: Direction,
+    protocol: RuleProtocol,
+    app: Option<String>,
+    interface: Option<InterfaceMedia>,
+    local_port: Vec<u16>,
+    remote_port: Option<u16>,
+    remote_address: Option<RemoteAddress>,
+}
+
+impl RuleMember {
+    /// `None` for filters no rule expands into.
+    fn of(filter: &FilterSummary) -> Option<Self> {
+        let direction = [Direction::Out, Direction::In]
+            .into_iter()
+            .find(|d| d.layers().contains(&filter.layer_key))?;
+        if filter.action == WfpAction::Callout {
+            return None;
+        }
+        let mut protocol = None;
+        let mut member = Self {
+            action: filter.action,
+            direction,
+            protocol: RuleProtocol::Any,
+            app: None,
+            interface: InterfaceMedia::of(&filter.conditions),
+            local_port: Vec::new(),
+            remote_port: None,
+            remote_address: None,
+        };
+        for condition in &filter.conditions {
+            if condition.match_type != MatchType::Equal {
+                return None;
+            }
+            match (condition.field, &condition.value) {
+                (ConditionField::IpProtocol, ConditionValue::Uint8(p)) if protocol.is_none() => {
+                    protocol = Some(*p)
+                }
+                (ConditionField::LocalPort, ConditionValue::Uint16(port)) => {
+                    member.local_port.push(*port)
+                }
+                (ConditionField::RemotePort, ConditionValue::Uint16(port))
+                    if member.remote_port.is_none() =>
+                {
+                    member.remote_port = Some(*port)
+                }
+                (ConditionField::RemoteAddress, value) if member.remote_address.is_none() => {
+                    member.remote_address = Some(RemoteAddress::from_condition_value(value)?)
+                }
+                (ConditionField::AppId, ConditionValue::ByteBlob(blob)) if member.app.is_none() => {
+                    member.app = Some(utf16z(blob)?)
+                }
+                (ConditionField::InterfaceType, _) if member.interface.is_some() => {}
+                _ => return None,
+            }
+        }
+        member.protocol = match protocol {
+            None => RuleProtocol::Any,
+            Some(6) => RuleProtocol::Tcp,
+            Some(17) => RuleProtocol::Udp,
+            Some(_) => return None,
+        };
+        member.local_port.sort_unstable();
+        Some(member)
+    }
+
+    /// Whether `self` and `other` can be members of one rule.
+    fn agrees_with(&self, other: &Self) -> bool {
+        self.action == other.action
+            && self.direction == other.direction
+            && self.protocol == other.protocol
+            && self.app == other.app
+            && self.interface == other.interface
+            && self.local_port == other.local_port
+    }
+
+    fn fold_into(&self, config: &mut FilterConfig) {
+        config.direction = self.direction;
+        config.app.clone_from(&self.app);
+        config.interface = self.interface;
+        config.local_port = RemotePorts::from(self.local_port.clone());
+        if let Some(port) = self.remote_port {
+            config.remote_port.push(port);
+        }
+        if let Some(address) = self.remote_address {
+            if !config.remote_address.contains(&address) {
+                config.remote_address.push(address);
+            }
+        }
+    }
+}
+
+/// Remote port(s) of a rule. Serialized as a bare number for one port so
+/// single-port exports keep their original shape.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RemotePorts {
+    One(u16),
+    Many(Vec<u16>),
+}
+
+impl Default for RemotePorts {
+    /// No ports.
+    fn default() -> Self {
+        RemotePorts::Many(Vec::new())
+    }
+}
+
+impl RemotePorts {
+    pub fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[u16] {
+        match self {
+            RemotePorts::One(port) => std::slice::from_ref(port),
+            RemotePorts::Many(ports) => ports,
+        }
+    }
+
+    pub fn push(&mut self, port: u16) {
+        let mut ports = self.as_slice().to_vec();
+        ports.push(port);
+        *self = RemotePorts::Many(ports);
+    }
+
+    /// Sorts and de-duplicates, collapsing to `One` where possible.
+    pub fn normalize(&mut self) {
+        let mut ports = self.as_slice().to_vec();
+        ports.sort_unstable();
+        ports.dedup();
+        *self = RemotePorts::from(ports);
+    }
+}
+
+impl From<Vec<u16>> for RemotePorts {
+    fn from(ports: Vec<u16>) -> Self {
+        match ports.as_slice() {
+            [port] => RemotePorts::One(*port),
+            _ => RemotePorts::Many(ports),
+        }
+    }
+}
+
+impl std::str::FromStr for RemotePorts {
+    type Err = anyhow::Error;
+
+    /// Parses a comma- or space-separated list such as `80, 443`.
+    fn from_str(text: &str) -> Result<Self> {
+        let ports = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                part.parse::<u16>()
+                    .ok()
+                    .filter(|port| *port != 0)
+                    .ok_or_else(|| anyhow!("'{part}' is not a port between 1 and 65535"))
+            })
+            .collect::<Result<Vec<u16>>>()?;
+        if ports.is_empty() {
+            return Err(anyhow!("At least one port is required"));
+        }
+        Ok(RemotePorts::from(ports))
+    }
+}
+
+impl std::fmt::Display for RemotePorts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = self.as_slice().iter().map(u16::to_string).collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Our provider as recorded in an export.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    pub key: Uuid,
+    pub name: String,
+}
+
+/// One of our sublayers as recorded in an export. Weight orders sublayers
+/// within each layer, higher first.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SublayerConfig {
+    pub key: Uuid,
+    pub name: String,
+    pub weight: u16,
+}
+
+/// One of our general provider contexts as recorded in an export: data
+/// for the callouts of the rules that name it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderContextConfig {
+    pub key: Uuid,
+    pub name: String,
+    #[serde(with = "super::conditions::hex_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// Where and when an export was made, so an import can show what it is
+/// about to apply.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportSource {
+    pub hostname: String,
+    /// Windows build such as `22631.3880`; not known for remote hosts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_build: Option<String>,
+    pub app_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub rules: usize,
+    pub sublayers: usize,
+}
+
+impl std::fmt::Display for ExportSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.hostname)?;
+        if let Some(build) = &self.os_build {
+            write!(f, " (Windows build {build})")?;
+        }
+        write!(
+            f,
+            ", SLS WFP Manager {}, exported {}: {} rules, {} sublayers",
+            self.app_version,
+            self.exported_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M"),
+            self.rules,
+            self.sublayers
+        )
+    }
+}
+
+/// A complete export: our provider, its sublayers and the rules, so a
+/// restore on a fresh machine recreates the whole ownership tree. Older
+/// exports hold only the rules, as a bare array.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct RuleSet {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ExportSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<ProviderConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sublayers: Vec<SublayerConfig>,
+    /// Added before the rules, which may name them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provider_contexts: Vec<ProviderContextConfig>,
+    pub filters: Vec<FilterConfig>,
+}
+
+impl RuleSet {
+    pub fn from_rules(filters: Vec<FilterConfig>) -> Self {
+        Self {
+            filters,
+            ..Self::default()
+        }
+    }
+
+    /// What is owned in `snapshot`, with sublayer weights from `sublayers`.
+    pub fn owned(snapshot: Snapshot, sublayers: &[SublayerInfo]) -> Self {
+        Self {
+            source: None,
+            provider: snapshot
+                .providers
+                .iter()
+                .find(|p| p.key == PROVIDER_KEY)
+                .map(|p| ProviderConfig {
+                    key: uuid_from_guid(p.key),
+                    name: p.name.clone(),
+                }),
+            sublayers: sublayers
+                .iter()
+                .filter(|s| s.provider_key == Some(PROVIDER_KEY))
+                .map(|s| SublayerConfig {
+                    key: uuid_from_guid(s.key),
+                    name: s.name.clone(),
+                    weight: s.weight,
+                })
+                .collect(),
+            provider_contexts: Vec::new(),
+            filters: rules_from_filters(snapshot.filters),
+        }
+    }
+
+    /// Records our general provider contexts among `contexts`.
+    pub fn with_provider_contexts(mut self, contexts: &[ProviderContextInfo]) -> Self {
+        self.provider_contexts = contexts
+            .iter()
+            .filter(|c| c.ours)
+            .filter_map(|c| {
+                Some(ProviderContextConfig {
+                    key: uuid_from_guid(c.key),
+                    name: c.name.clone(),
+                    data: c.data.clone()?,
+                })
+            })
+            .collect();
+        self
+    }
+
+    /// Records that the set is being exported now from `hostname`.
+    pub fn stamped(mut self, hostname: &str, os_build: Option<String>) -> Self {
+        self.source = Some(ExportSource {
+            hostname: hostname.to_string(),
+            os_build,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: Utc::now(),
+            rules: self.filters.len(),
+            sublayers: self.sublayers.len(),
+        });
+        self
+    }
+
+    /// One line on where the set came from and what it holds.
+    pub fn describe(&self) -> String {
+        match &self.source {
+            Some(source) => format!("From {source}"),
+            None => format!(
+                "{} rules, {} sublayers; the file does not say where it came from",
+                self.filters.len(),
+                self.sublayers.len()
+            ),
+        }
+    }
+
+    /// Checks every rule and its name, and that the provider is ours: rules
+    /// installed under another provider key would not be recognized as
+    /// owned.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(provider) = &self.provider {
+            if guid_from_uuid(provider.key) != PROVIDER_KEY {
+                return Err(anyhow!(
+                    "Rules were exported under provider {} ({}), not this tool's",
+                    provider.key,
+                    provider.name
+                ));
+            }
+        }
+        crate::naming::validate_configs(&self.filters)
+    }
+}
+
+/// `configs` with a weight pinned on every rule that has none: the top of
+/// its tier less its position among the file's rules in that tier. A file
+/// then installs with the same relative priority on every machine, whatever
+/// rules were there before.
+pub fn order_weights(configs: &[FilterConfig]) -> Vec<FilterConfig> {
+    let mut positions: HashMap<WeightTier, u64> = HashMap::new();
+    configs
+        .iter()
+        .map(|cfg| {
+            // Every rule has a condition, so none is default-deny.
+            let tier = match cfg.action {
+                WfpAction::Permit => WeightTier::Allow,
+                _ => WeightTier::Block,
+            };
+            let position = positions.entry(tier).or_default();
+            let mut cfg = cfg.clone();
+            cfg.weight = cfg.weight.or(Some(tier.top() - *position));
+            *position += 1;
+            cfg
+        })
+        .collect()
+}
+
+/// `configs` with every rule after those among them it depends on, and
+/// otherwise in their original order. Fails on a dependency cycle.
+pub fn order_dependencies(configs: &[FilterConfig]) -> Result<Vec<FilterConfig>> {
+    let mut pending: Vec<&FilterConfig> = configs.iter().collect();
+    let mut ordered: Vec<FilterConfig> = Vec::with_capacity(configs.len());
+    while !pending.is_empty() {
+        // The first rule none of whose dependencies are still pending.
+        let ready = pending.iter().position(|cfg| {
+            !cfg.depends_on.iter().any(|dep| {
+                pending
+                    .iter()
+                    .any(|other| other.key == Some(*dep) && other.key != cfg.key)
+            })
+        });
+        match ready {
+            Some(idx) => ordered.push(pending.remove(idx).clone()),
+            None => {
+                let names: Vec<&str> = pending.iter().map(|cfg| cfg.name.as_str()).collect();
+                return Err(anyhow!(
+                    "Rules depend on each other in a cycle: {}",
+                    names.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+/// Fails unless every dependency of `configs` is among the rules
+/// `installed` once the change is made.
+pub(super) fn check_dependencies(configs: &[FilterConfig], installed: &[Uuid]) -> Result<()> {
+    for cfg in configs {
+        if let Some(dep) = cfg.depends_on.iter().find(|dep| !installed.contains(dep)) {
+            return Err(anyhow!(
+                "Rule '{}' depends on rule {dep}, which is not installed",
+                cfg.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The original rule shape: outbound IPv4 TCP to one or more remote ports,
+/// expanded into one filter per port.
+pub fn simple_tcp_rule_v4(
+    key: GUID,
+    name: &str,
+    remote_ports: &[u16],
+    action: WfpAction,
+) -> Vec<FilterBuilder> {
+    let ports: Vec<ConditionValue> = remote_ports
+        .iter()
+        .map(|port| ConditionValue::Uint16(*port))
+        .collect();
+    FilterBuilder::new(name, FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+        .key(key)
+        .action(action)
+        .condition(Condition::equal(
+            ConditionField::IpProtocol,
+            ConditionValue::Uint8(6),
+        ))
+        .expand_any_of(ConditionField::RemotePort, MatchType::Equal, &ports)
+}
+
+/// Address rules at least this long are installed indexed; below it the
+/// linear scan is as fast and the index is not worth its memory.
+pub const INDEXED_RULE_MIN_ADDRESSES: usize = 64;
+
+/// Outbound rule matching any of `addresses`, expanded into one filter per
+/// address. Large lists, such as blocklists, are indexed.
+pub fn address_rule_v4(
+    key: GUID,
+    name: &str,
+    addresses: &[Ipv4Addr],
+    action: WfpAction,
+) -> Vec<FilterBuilder> {
+    let addresses: Vec<ConditionValue> = addresses
+        .iter()
+        .map(|addr| ConditionValue::Uint32(u32::from(*addr)))
+        .collect();
+    FilterBuilder::new(name, FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+        .key(key)
+        .action(action)
+        .indexed(addresses.len() >= INDEXED_RULE_MIN_ADDRESSES)
+        .expand_any_of(ConditionField::RemoteAddress, MatchType::Equal, &addresses)
+}
+
+/// Outbound rule for one application over IPv4 and IPv6, limited to TCP to
+/// `remote_ports` unless that is empty. `app_id` comes from [`app_id`].
+pub fn app_rule(
+    key: GUID,
+    name: &str,
+    app_id: &ConditionValue,
+    remote_ports: &[u16],
+    action: WfpAction,
+) -> Vec<FilterBuilder> {
+    let mut common = vec![Condition::equal(ConditionField::AppId, app_id.clone())];
+    if !remote_ports.is_empty() {
+        common.push(Condition::equal(
+            ConditionField::IpProtocol,
+            ConditionValue::Uint8(6),
+        ));
+    }
+    expand_rule(
+        key,
+        name,
+        action,
+        Direction::Out,
+        &common,
+        &[],
+        remote_ports,
+    )
+}
+
+/// Port of plain DNS.
+pub const DNS_PORT: u16 = 53;
+/// Port of DNS over TLS.
+pub const DOT_PORT: u16 = 853;
+/// Port DNS-over-HTTPS servers answer on.
+pub const DOH_PORT: u16 = 443;
+/// UDP port QUIC, and so HTTP/3, runs on.
+pub const QUIC_PORT: u16 = 443;
+
+/// Outbound rule that lets DNS on `ports` reach only `resolvers`: permits
+/// for each resolver, blocks for everything else on those ports, and
+/// blocks on [`DOH_PORT`] to `doh_servers` not among the resolvers. All
+/// filters are members of the one rule `key`.
+pub fn dns_lockdown_rule(
+    key: GUID,
+    name: &str,
+    resolvers: &[IpAddr],
+    ports: &[u16],
+    doh_servers: &[IpAddr],
+) -> Result<Vec<FilterBuilder>> {
+    // With no address, the permits would allow any resolver.
+    if resolvers.is_empty() {
+        return Err(anyhow!("DNS lockdown needs at least one allowed resolver"));
+    }
+    let allowed: Vec<RemoteAddress> = resolvers.iter().copied().map(RemoteAddress::from).collect();
+    let doh: Vec<RemoteAddress> = doh_servers
+        .iter()
+        .filter(|addr| !resolvers.contains(addr))
+        .copied()
+        .map(RemoteAddress::from)
+        .collect();
+    // Permits first, so the allocator gives each action one weight.
+    let mut builders = expand_rule(
+        key,
+        name,
+        WfpAction::Permit,
+        Direction::Out,
+        &[],
+        &allowed,
+        ports,
+    );
+    builders.extend(expand_rule(
+        key,
+        name,
+        WfpAction::Block,
+        Direction::Out,
+        &[],
+        &[],
+        ports,
+    ));
+    if !doh.is_empty() {
+        builders.extend(expand_rule(
+            key,
+            name,
+            WfpAction::Block,
+            Direction::Out,
+            &[],
+            &doh,
+            &[DOH_PORT],
+        ));
+    }
+    Ok(renumber_members(key, builders))
+}
+
+/// QUIC is HTTP/3 over UDP 443. Blocking it outbound makes browsers fall
+/// back to TCP, where TLS can be inspected. Limited to the applications in
+/// `app_ids` unless that is empty.
+pub fn quic_block_rule(key: GUID, name: &str, app_ids: &[ConditionValue]) -> Vec<FilterBuilder> {
+    let udp = Condition::equal(ConditionField::IpProtocol, ConditionValue::Uint8(17));
+    let commons: Vec<Vec<Condition>> = match app_ids {
+        [] => vec![vec![udp]],
+        apps => apps
+            .iter()
+            .map(|app| {
+                vec![
+                    udp.clone(),
+                    Condition::equal(ConditionField::AppId, app.clone()),
+                ]
+            })
+            .collect(),
+    };
+    let builders = commons
+        .iter()
+        .flat_map(|common| {
+            expand_rule(
+                key,
+                name,
+                WfpAction::Block,
+                Direction::Out,
+                common,
+                &[],
+                &[QUIC_PORT],
+            )
+        })
+        .collect();
+    renumber_members(key, builders)
+}
+
+/// Blocks every connection, in and out, on the interfaces with the LUIDs
+/// `luids`. Having no other conditions, the filters weigh in as
+/// default-deny, below the rules that allow traffic on them.
+pub fn interface_deny_rule(key: GUID, name: &str, luids: &[u64]) -> Vec<FilterBuilder> {
+    let builders = luids
+        .iter()
+        .flat_map(|luid| {
+            let interface = [Condition::equal(
+                ConditionField::LocalInterface,
+                ConditionValue::Uint64(*luid),
+            )];
+            [Direction::Out, Direction::In]
+                .into_iter()
+                .flat_map(move |direction| {
+                    expand_rule(key, name, WfpAction::Block, direction, &interface, &[], &[])
+                })
+        })
+        .collect();
+    renumber_members(key, builders)
+}
+
+/// Permits at the flow-established layers, for every application or only
+/// the ones with `app_ids`. Those layers only see connections already
+/// allowed, so the filters block nothing; with classify-allow net events
+/// on, each connection they match is recorded naming them.
+pub fn flow_monitor_rule(key: GUID, name: &str, app_ids: &[ConditionValue]) -> Vec<FilterBuilder> {
+    let apps: Vec<Option<&ConditionValue>> = match app_ids {
+        [] => vec![None],
+        apps => apps.iter().map(Some).collect(),
+    };
+    let builders = apps
+        .into_iter()
+        .flat_map(|app| {
+            [
+                FWPM_LAYER_ALE_FLOW_ESTABLISHED_V4,
+                FWPM_LAYER_ALE_FLOW_ESTABLISHED_V6,
+            ]
+            .map(|layer| {
+                let builder = FilterBuilder::new(name, layer)
+                    .rule(key)
+                    .action(WfpAction::Permit);
+                match app {
+                    Some(app) => {
+                        builder.condition(Condition::equal(ConditionField::AppId, app.clone()))
+                    }
+                    None => builder,
+                }
+            })
+        })
+        .collect();
+    renumber_members(key, builders)
+}
+
+/// Keys the members of a rule put together from several expansions, which
+/// each number their members from zero.
+fn renumber_members(key: GUID, builders: Vec<FilterBuilder>) -> Vec<FilterBuilder> {
+    builders
+        .into_iter()
+        .enumerate()
+        .map(|(idx, builder)| builder.key(member_key(key, idx)))
+        .collect()
+}
+
+/// Expands a rule into one filter per layer, address and port, each with
+/// the `common` conditions and tagged with `key` as its rule. Addresses only
+/// go to the layer of their IP version; with none, both layers are used.
+pub(super) fn expand_rule(
+    key: GUID,
+    name: &str,
+    action: WfpAction,
+    direction: Direction,
+    common: &[Condition],
+    addresses: &[RemoteAddress],
+    ports: &[u16],
+) -> Vec<FilterBuilder> {
+    let addresses: Vec<Option<RemoteAddress>> = match addresses {
+        [] => vec![None],
+        addresses => addresses.iter().copied().map(Some).collect(),
+    };
+    let ports: Vec<Option<u16>> = match ports {
+        [] => vec![None],
+        ports => ports.iter().copied().map(Some).collect(),
+    };
+    let indexed = addresses.len() >= INDEXED_RULE_MIN_ADDRESSES;
+    let mut builders = Vec::new();
+    for (layer, v6) in direction.layers().into_iter().zip([false, true]) {
+        for address in addresses
+            .iter()
+            .filter(|a| a.is_none_or(|a| a.addr.is_ipv6() == v6))
+        {
+            for port in &ports {
+                let mut builder = common.iter().cloned().fold(
+                    FilterBuilder::new(name, layer)
+                        .key(member_key(key, builders.len()))
+                        .rule(key)
+                        .action(action)
+                        .indexed(indexed),
+                    FilterBuilder::condition,
+                );
+                if let Some(address) = address {
+                    builder = builder.condition(Condition::equal(
+                        ConditionField::RemoteAddress,
+                        address.condition_value(),
+                    ));
+                }
+                if let Some(port) = port {
+                    builder = builder.condition(Condition::equal(
+                        ConditionField::RemotePort,
+                        ConditionValue::Uint16(*port),
+                    ));
+                }
+                builders.push(builder);
+            }
+        }
+    }
+    builders
+}
+
+/// Deterministic key for the `idx`-th member of an expanded rule, so
+/// re-importing the same rule reproduces the same member keys. Name-based,
+/// so rules with neighbouring keys cannot hand out each other's keys.
+pub(super) fn member_key(rule: GUID, idx: usize) -> GUID {
+    guid_from_uuid(Uuid::new_v5(
+        &uuid_from_guid(rule),
+        &(idx as u64).to_le_bytes(),
+    ))
+}
+
+pub fn guid_from_uuid(id: Uuid) -> GUID {
+    GUID::from_u128(id.as_u128())
+}
+
+pub fn uuid_from_guid(guid: GUID) -> Uuid {
+    Uuid::from_u128(guid.to_u128())
+}
+
+/// Serializes GUIDs as UUID strings, the form keys take in all our files.
+pub(super) mod guid_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use uuid::Uuid;
+
+    use super::{guid_from_uuid, uuid_from_guid, GUID};
+
+    pub fn serialize<S: Serializer>(guid: &GUID, serializer: S) -> Result<S::Ok, S::Error> {
+        uuid_from_guid(*guid).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<GUID, D::Error> {
+        Uuid::deserialize(deserializer).map(guid_from_uuid)
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            guid: &Option<GUID>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            guid.map(uuid_from_guid).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<GUID>, D::Error> {
+            Option::<Uuid>::deserialize(deserializer).map(|id| id.map(guid_from_uuid))
+        }
+    }
+}
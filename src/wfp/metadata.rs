@@ -0,0 +1,389 @@
+use super::*;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NamedGuid {
+    #[serde(with = "guid_serde")]
+    pub key: GUID,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Products by the key of their provider, for providers registered without
+/// a display name.
+const KNOWN_PROVIDERS: &str = include_str!("../../data/known_providers.json");
+/// Products added to, or renamed from, [`KNOWN_PROVIDERS`] on this
+/// machine, e.g. a VPN client or EDR agent in use here.
+pub const KNOWN_PROVIDERS_FILE: &str = "known_providers.json";
+
+/// The products [`known_provider`] attributes providers to. A local file
+/// that does not parse is left out, so names never stop a snapshot.
+pub fn known_providers() -> &'static BTreeMap<Uuid, String> {
+    static KNOWN: std::sync::OnceLock<BTreeMap<Uuid, String>> = std::sync::OnceLock::new();
+    KNOWN.get_or_init(|| {
+        let mut known: BTreeMap<Uuid, String> =
+            serde_json::from_str(KNOWN_PROVIDERS).expect("embedded provider table is valid");
+        let path = crate::config::app_data_dir().join(KNOWN_PROVIDERS_FILE);
+        let local = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<BTreeMap<Uuid, String>>(&text).ok());
+        known.extend(local.unwrap_or_default());
+        known
+    })
+}
+
+/// The product behind provider `key`, when it is a known one.
+pub fn known_provider(key: GUID) -> Option<&'static str> {
+    known_providers()
+        .get(&uuid_from_guid(key))
+        .map(String::as_str)
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SublayerInfo {
+    #[serde(with = "guid_serde")]
+    pub key: GUID,
+    pub name: String,
+    #[serde(with = "guid_serde::option")]
+    pub provider_key: Option<GUID>,
+    /// Higher weights are evaluated first within each layer.
+    pub weight: u16,
+    pub ours: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalloutInfo {
+    pub key: GUID,
+    pub id: u32,
+    pub name: String,
+    pub description: Option<String>,
+    pub provider_key: Option<GUID>,
+    pub layer_key: GUID,
+    /// Whether a driver has registered its classify functions for it;
+    /// until then its filters act as the callout's fallback.
+    pub registered: bool,
+    pub ours: bool,
+}
+
+/// Data filters carry for their callouts, e.g. the parameters an
+/// inspection callout reads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProviderContextInfo {
+    pub key: GUID,
+    pub id: u64,
+    pub name: String,
+    pub provider_key: Option<GUID>,
+    /// The bytes of a general context; `None` for the IPsec and other
+    /// typed contexts.
+    pub data: Option<Vec<u8>>,
+    pub ours: bool,
+}
+
+#[derive(Clone)]
+pub struct SessionInfo {
+    pub key: GUID,
+    pub name: String,
+    pub description: Option<String>,
+    pub process_id: u32,
+    pub username: String,
+    pub kernel_mode: bool,
+    /// 0 means BFE's default.
+    pub txn_wait_timeout_ms: u32,
+    /// Opened by this process.
+    pub ours: bool,
+}
+
+/// An IPsec-protected connection, as `FwpmConnectionEnum0` reports it.
+#[derive(Clone, Debug)]
+pub struct IpsecConnection {
+    pub id: u64,
+    pub local: IpAddr,
+    pub remote: IpAddr,
+    /// Tunnel mode rather than transport mode.
+    pub tunnel: bool,
+    /// `IKE`, `AuthIP` or `IKEv2`.
+    pub key_module: &'static str,
+    /// Main mode cipher, integrity and DH group, e.g. `AES-256 / SHA-256 / ECP-384`.
+    pub main_mode: String,
+    /// How the peer authenticated in main mode, then in extended mode for
+    /// AuthIP.
+    pub peer_auth: String,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub started: DateTime<Utc>,
+}
+
+/// A change reported by an IPsec connection subscription.
+#[derive(Clone, Debug)]
+pub enum IpsecEvent {
+    Added(IpsecConnection),
+    Deleted(u64),
+}
+
+/// What Windows reserved a system port for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SystemPortKind {
+    RpcEndpointMapper,
+    Teredo,
+    IpHttpsIn,
+    IpHttpsOut,
+}
+
+impl SystemPortKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::RpcEndpointMapper => "RPC endpoint mapper",
+            Self::Teredo => "Teredo",
+            Self::IpHttpsIn => "IP-HTTPS inbound",
+            Self::IpHttpsOut => "IP-HTTPS outbound",
+        }
+    }
+}
+
+/// Ports the system has allocated for one kind of traffic, as
+/// `FwpmSystemPortsGet0` reports them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SystemPorts {
+    pub kind: SystemPortKind,
+    pub ports: Vec<u16>,
+}
+
+/// System ports a block rule would cover. Rules limited to one app are
+/// left out, as they cannot cut the system service off.
+pub fn blocked_system_ports(
+    config: &FilterConfig,
+    system: &[SystemPorts],
+) -> Vec<(SystemPortKind, u16)> {
+    if config.action != WfpAction::Block || config.app.is_some() {
+        return Vec::new();
+    }
+    system
+        .iter()
+        .flat_map(|s| s.ports.iter().map(|port| (s.kind, *port)))
+        .filter(|(_, port)| {
+            config.remote_port.is_empty() || config.remote_port.as_slice().contains(port)
+        })
+        .collect()
+}
+
+/// A path a block rule leaves open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverageGap {
+    /// The rule is installed at `ALE_AUTH_CONNECT_V4` only.
+    Ipv6,
+    /// The rule blocks connections to the ports, not those accepted on them.
+    Inbound,
+}
+
+impl CoverageGap {
+    pub fn describe(self) -> &'static str {
+        match self {
+            CoverageGap::Ipv6 => "IPv6 connections are not blocked",
+            CoverageGap::Inbound => "inbound connections to these ports are not blocked",
+        }
+    }
+}
+
+/// Paths a block on ports alone, such as `block port 445`, leaves open.
+/// Rules limited by app or address are taken to be meant as narrow.
+pub fn coverage_gaps(config: &FilterConfig) -> Vec<CoverageGap> {
+    if config.action != WfpAction::Block
+        || config.remote_port.is_empty()
+        || config.app.is_some()
+        || !config.remote_address.is_empty()
+        || !config.remote_host.is_empty()
+        || !config.local_port.is_empty()
+    {
+        return Vec::new();
+    }
+    let mut gaps = Vec::new();
+    if config.is_tcp_port_rule() {
+        gaps.push(CoverageGap::Ipv6);
+    }
+    if config.direction == Direction::Out {
+        gaps.push(CoverageGap::Inbound);
+    }
+    gaps
+}
+
+/// Rules closing the [`coverage_gaps`] of `config`, to be added with it.
+/// Each has a key of its own, so it can be removed separately.
+pub fn companion_rules(config: &FilterConfig) -> Vec<FilterConfig> {
+    coverage_gaps(config)
+        .into_iter()
+        .map(|gap| {
+            let mut companion = config.clone();
+            companion.key = Some(Uuid::new_v4());
+            companion.weight = None;
+            match gap {
+                CoverageGap::Ipv6 => {
+                    companion.name = format!("{} (IPv6)", config.name);
+                    // Any IPv6 address, so only the IPv6 layer is covered.
+                    companion.remote_address = vec![RemoteAddress {
+                        addr: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                        prefix: 0,
+                    }];
+                }
+                CoverageGap::Inbound => {
+                    companion.name = format!("{} (inbound)", config.name);
+                    companion.direction = Direction::In;
+                    companion.local_port = std::mem::take(&mut companion.remote_port);
+                }
+            }
+            companion
+        })
+        .collect()
+}
+
+/// Engine-wide options, as `netsh wfp show options` lists them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineOptions {
+    pub collect_net_events: bool,
+    /// `FWPM_NET_EVENT_KEYWORD_*` flags for events collected beyond the
+    /// classify drops.
+    pub net_event_keywords: u32,
+    pub name_cache: bool,
+    pub monitor_ipsec_connections: bool,
+    /// `FWPM_ENGINE_OPTION_PACKET_QUEUE_*` flags.
+    pub packet_queuing: u32,
+    pub txn_watchdog_ms: u32,
+}
+
+impl EngineOptions {
+    /// Names of the set net event keywords.
+    pub fn keyword_names(&self) -> Vec<&'static str> {
+        [
+            (1, "inbound multicast"),
+            (2, "inbound broadcast"),
+            (4, "capability drop"),
+            (8, "capability allow"),
+            (16, "classify allow"),
+            (32, "port scanning drop"),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| self.net_event_keywords & flag != 0)
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// A one-screen overview of the engine, in the spirit of
+/// `netsh wfp show state`.
+#[derive(Clone, Debug)]
+pub struct EngineState {
+    pub options: EngineOptions,
+    pub sessions: usize,
+    pub providers: usize,
+    pub sublayers: usize,
+    pub layers: usize,
+    pub filters: usize,
+    pub owned_filters: usize,
+    pub boot_time_filters: usize,
+    pub callouts: usize,
+    /// Net events BFE currently holds.
+    pub net_events: usize,
+}
+
+impl EngineState {
+    pub fn load(engine: &Engine) -> Result<Self> {
+        let snapshot = engine.snapshot()?;
+        Ok(Self {
+            options: engine.engine_options()?,
+            sessions: engine.sessions()?.len(),
+            providers: snapshot.providers.len(),
+            sublayers: snapshot.sublayers.len(),
+            layers: snapshot.layers.len(),
+            filters: snapshot.filters.len(),
+            owned_filters: snapshot.filters.iter().filter(|f| f.owned_by_app).count(),
+            boot_time_filters: snapshot.boot_time_filters.len(),
+            callouts: engine.callouts()?.len(),
+            net_events: engine.net_events()?.len(),
+        })
+    }
+}
+
+/// Refuses to delete `sublayer` while filters in `users` still reference it,
+/// naming the first few so they can be found.
+pub(super) fn check_sublayer_unused(
+    sublayer: &SublayerInfo,
+    users: &[FilterSummary],
+) -> Result<()> {
+    if users.is_empty() {
+        return Ok(());
+    }
+    let mut names: Vec<String> = users
+        .iter()
+        .take(5)
+        .map(|f| format!("{} {}", f.id, f.name))
+        .collect();
+    if users.len() > names.len() {
+        names.push(format!("and {} more", users.len() - names.len()));
+    }
+    Err(anyhow!(
+        "Sublayer '{}' is still used by {} filter(s): {}. Delete them first or delete the sublayer with its filters",
+        sublayer.name,
+        users.len(),
+        names.join(", ")
+    ))
+}
+
+/// One of our provider contexts, unless a filter still carries it.
+pub(super) fn unused_provider_context(
+    contexts: Vec<ProviderContextInfo>,
+    filters: &[FilterSummary],
+    key: GUID,
+) -> Result<ProviderContextInfo> {
+    let context = contexts
+        .into_iter()
+        .find(|c| c.key == key && c.ours)
+        .ok_or_else(|| {
+            anyhow!(
+                "Provider context {} is not one of ours",
+                uuid_from_guid(key)
+            )
+        })?;
+    match filters
+        .iter()
+        .filter(|f| f.provider_context_key == Some(key))
+        .count()
+    {
+        0 => Ok(context),
+        users => Err(anyhow!(
+            "Provider context '{}' is still carried by {users} filter(s); delete or edit them first",
+            context.name
+        )),
+    }
+}
+
+pub(super) fn unused_callout(
+    callouts: Vec<CalloutInfo>,
+    filters: &[FilterSummary],
+    key: GUID,
+) -> Result<CalloutInfo> {
+    let callout = callouts
+        .into_iter()
+        .find(|c| c.key == key && c.ours)
+        .ok_or_else(|| anyhow!("Callout {} is not one of ours", uuid_from_guid(key)))?;
+    match filters
+        .iter()
+        .filter(|f| f.callout.is_some_and(|c| c.key == key))
+        .count()
+    {
+        0 => Ok(callout),
+        users => Err(anyhow!(
+            "Callout '{}' is still used by {users} filter(s); delete or edit them first",
+            callout.name
+        )),
+    }
+}
+
+/// One of our sublayers other than the default one, which stays.
+pub(super) fn custom_sublayer(sublayers: Vec<SublayerInfo>, key: GUID) -> Result<SublayerInfo> {
+    if key == SUBLAYER_KEY {
+        return Err(anyhow!("The default sublayer cannot be removed"));
+    }
+    sublayers
+        .into_iter()
+        .find(|s| s.key == key && s.ours)
+        .ok_or_else(|| anyhow!("Sublayer {} is not one of ours", uuid_from_guid(key)))
+}
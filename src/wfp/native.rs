@@ -0,0 +1,2688 @@
+use std::{cell::Cell, ffi::c_void, ptr, sync::mpsc::Sender, thread, time::Instant};
+
+use widestring::{U16CStr, U16CString};
+use windows::{
+    core::{w, PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{
+            CloseHandle, LocalFree, FILETIME, FWP_E_ALREADY_EXISTS, FWP_E_FILTER_NOT_FOUND,
+            FWP_E_INVALID_ENUMERATOR, HANDLE, HLOCAL,
+        },
+        NetworkManagement::WindowsFilteringPlatform::*,
+        Security::{
+            Authorization::{
+                ConvertSecurityDescriptorToStringSecurityDescriptorW,
+                ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+            },
+            DACL_SECURITY_INFORMATION, GROUP_SECURITY_INFORMATION, OWNER_SECURITY_INFORMATION,
+            PSECURITY_DESCRIPTOR, SACL_SECURITY_INFORMATION,
+        },
+        System::{
+            Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_DWORD, RRF_RT_REG_SZ},
+            Rpc::RPC_C_AUTHN_WINNT,
+        },
+    },
+};
+
+use super::*;
+
+impl WfpAction {
+    fn to_fwpm(self) -> FWP_ACTION_TYPE {
+        match self {
+            WfpAction::Permit => FWP_ACTION_PERMIT,
+            WfpAction::Block => FWP_ACTION_BLOCK,
+            WfpAction::Callout => FWP_ACTION_CALLOUT_TERMINATING,
+        }
+    }
+}
+
+impl CalloutKind {
+    fn to_fwp(self) -> FWP_ACTION_TYPE {
+        match self {
+            CalloutKind::Terminating => FWP_ACTION_CALLOUT_TERMINATING,
+            CalloutKind::Inspection => FWP_ACTION_CALLOUT_INSPECTION,
+            CalloutKind::Unknown => FWP_ACTION_CALLOUT_UNKNOWN,
+        }
+    }
+
+    fn from_fwp(action: FWP_ACTION_TYPE) -> Option<Self> {
+        [
+            CalloutKind::Terminating,
+            CalloutKind::Inspection,
+            CalloutKind::Unknown,
+        ]
+        .into_iter()
+        .find(|kind| kind.to_fwp() == action)
+    }
+}
+
+impl MatchType {
+    fn to_fwp(self) -> FWP_MATCH_TYPE {
+        match self {
+            MatchType::Equal => FWP_MATCH_EQUAL,
+            MatchType::NotEqual => FWP_MATCH_NOT_EQUAL,
+            MatchType::Greater => FWP_MATCH_GREATER,
+            MatchType::Less => FWP_MATCH_LESS,
+            MatchType::GreaterOrEqual => FWP_MATCH_GREATER_OR_EQUAL,
+            MatchType::LessOrEqual => FWP_MATCH_LESS_OR_EQUAL,
+            MatchType::Range => FWP_MATCH_RANGE,
+            MatchType::FlagsAllSet => FWP_MATCH_FLAGS_ALL_SET,
+            MatchType::FlagsAnySet => FWP_MATCH_FLAGS_ANY_SET,
+            MatchType::FlagsNoneSet => FWP_MATCH_FLAGS_NONE_SET,
+            MatchType::EqualCaseInsensitive => FWP_MATCH_EQUAL_CASE_INSENSITIVE,
+            MatchType::Prefix => FWP_MATCH_PREFIX,
+            MatchType::NotPrefix => FWP_MATCH_NOT_PREFIX,
+            MatchType::Other(raw) => FWP_MATCH_TYPE(raw as i32),
+        }
+    }
+
+    fn from_fwp(value: FWP_MATCH_TYPE) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|m| m.to_fwp() == value)
+            .unwrap_or(MatchType::Other(value.0 as u32))
+    }
+}
+
+impl FilterBuilder {
+    /// Adds the filter under our provider and sublayer. Callers must hold a
+    /// transaction.
+    fn install(&self, handle: HANDLE) -> Result<u64> {
+        self.validate()?;
+        let name_ws = U16CString::from_str(&self.name)?;
+        let mut provider_key = PROVIDER_KEY;
+        let mut weight = self.weight.unwrap_or_else(|| self.tier().top());
+        let mut encoded = EncodedConditions::encode(&self.conditions)?;
+        let mut rule_bytes = self.rule.map(|rule| uuid_from_guid(rule).into_bytes());
+        let provider_data = match rule_bytes.as_mut() {
+            Some(bytes) => FWP_BYTE_BLOB {
+                size: bytes.len() as u32,
+                data: bytes.as_mut_ptr(),
+            },
+            None => FWP_BYTE_BLOB::default(),
+        };
+
+        let mut flags = FWPM_FILTER_FLAG_NONE;
+        if self.indexed {
+            flags |= FWPM_FILTER_FLAG_INDEXED;
+        }
+        if self.clear_action_right {
+            flags |= FWPM_FILTER_FLAG_CLEAR_ACTION_RIGHT;
+        }
+        if self.persistent {
+            flags |= FWPM_FILTER_FLAG_PERSISTENT;
+        }
+        if self.provider_context.is_some() {
+            flags |= FWPM_FILTER_FLAG_HAS_PROVIDER_CONTEXT;
+        }
+        let action = match (self.action, self.callout) {
+            (WfpAction::Callout, Some(callout)) => FWPM_ACTION0 {
+                r#type: callout.kind.to_fwp(),
+                Anonymous: FWPM_ACTION0_0 {
+                    calloutKey: callout.key,
+                },
+            },
+            (action, _) => FWPM_ACTION0 {
+                r#type: action.to_fwpm(),
+                ..Default::default()
+            },
+        };
+        let filter = FWPM_FILTER0 {
+            filterKey: self.key,
+            displayData: FWPM_DISPLAY_DATA0 {
+                name: PWSTR(name_ws.as_ptr() as *mut _),
+                description: PWSTR::null(),
+            },
+            layerKey: self.layer,
+            subLayerKey: self.sublayer,
+            weight: FWP_VALUE0 {
+                r#type: FWP_UINT64,
+                Anonymous: FWP_VALUE0_0 {
+                    uint64: &mut weight,
+                },
+            },
+            numFilterConditions: encoded.conditions.len() as u32,
+            filterCondition: encoded.conditions.as_mut_ptr(),
+            action,
+            providerKey: &mut provider_key,
+            providerData: provider_data,
+            flags,
+            Anonymous: FWPM_FILTER0_0 {
+                providerContextKey: self.provider_context.unwrap_or_default(),
+            },
+            ..Default::default()
+        };
+
+        let mut id = 0u64;
+        let status = unsafe {
+            FwpmFilterAdd0(
+                handle,
+                &filter,
+                PSECURITY_DESCRIPTOR::default(),
+                Some(&mut id),
+            )
+        };
+        if status != 0 {
+            return Err(WfpError::new("FwpmFilterAdd0", status).into());
+        }
+        Ok(id)
+    }
+}
+
+/// Native condition array plus every buffer its union members point into.
+/// The boxes keep those pointers stable for as long as this value lives.
+#[allow(clippy::vec_box)]
+struct EncodedConditions {
+    conditions: Vec<FWPM_FILTER_CONDITION0>,
+    _u64s: Vec<Box<u64>>,
+    _ranges: Vec<Box<FWP_RANGE0>>,
+    _strings: Vec<U16CString>,
+    _arrays16: Vec<Box<FWP_BYTE_ARRAY16>>,
+    _arrays6: Vec<Box<FWP_BYTE_ARRAY6>>,
+    _blobs: Vec<(Box<FWP_BYTE_BLOB>, Vec<u8>)>,
+    _v4_masks: Vec<Box<FWP_V4_ADDR_AND_MASK>>,
+    _v6_masks: Vec<Box<FWP_V6_ADDR_AND_MASK>>,
+}
+
+impl EncodedConditions {
+    fn encode(conditions: &[Condition]) -> Result<Self> {
+        let mut out = Self {
+            conditions: Vec::with_capacity(conditions.len()),
+            _u64s: Vec::new(),
+            _ranges: Vec::new(),
+            _strings: Vec::new(),
+            _arrays16: Vec::new(),
+            _arrays6: Vec::new(),
+            _blobs: Vec::new(),
+            _v4_masks: Vec::new(),
+            _v6_masks: Vec::new(),
+        };
+        for condition in conditions {
+            let value = out.encode_value(&condition.value)?;
+            out.conditions.push(FWPM_FILTER_CONDITION0 {
+                fieldKey: condition.field.to_guid(),
+                matchType: condition.match_type.to_fwp(),
+                conditionValue: value,
+            });
+        }
+        Ok(out)
+    }
+
+    fn encode_value(&mut self, value: &ConditionValue) -> Result<FWP_CONDITION_VALUE0> {
+        let (r#type, anonymous) = match value {
+            ConditionValue::Uint8(v) => (FWP_UINT8, FWP_CONDITION_VALUE0_0 { uint8: *v }),
+            ConditionValue::Uint16(v) => (FWP_UINT16, FWP_CONDITION_VALUE0_0 { uint16: *v }),
+            ConditionValue::Uint32(v) => (FWP_UINT32, FWP_CONDITION_VALUE0_0 { uint32: *v }),
+            ConditionValue::Uint64(v) => (
+                FWP_UINT64,
+                FWP_CONDITION_VALUE0_0 {
+                    uint64: self.store_u64(*v),
+                },
+            ),
+            ConditionValue::Unicode(text) => (
+                FWP_UNICODE_STRING_TYPE,
+                FWP_CONDITION_VALUE0_0 {
+                    unicodeString: self.store_string(text)?,
+                },
+            ),
+            ConditionValue::ByteArray16(bytes) => (
+                FWP_BYTE_ARRAY16_TYPE,
+                FWP_CONDITION_VALUE0_0 {
+                    byteArray16: self.store_array16(*bytes),
+                },
+            ),
+            ConditionValue::ByteArray6(bytes) => (
+                FWP_BYTE_ARRAY6_TYPE,
+                FWP_CONDITION_VALUE0_0 {
+                    byteArray6: store(&mut self._arrays6, FWP_BYTE_ARRAY6 { byteArray6: *bytes }),
+                },
+            ),
+            ConditionValue::ByteBlob(bytes) => (
+                FWP_BYTE_BLOB_TYPE,
+                FWP_CONDITION_VALUE0_0 {
+                    byteBlob: self.store_blob(bytes.clone()),
+                },
+            ),
+            ConditionValue::Sid(bytes) => {
+                let blob = self.store_blob(bytes.clone());
+                (
+                    FWP_SID,
+                    FWP_CONDITION_VALUE0_0 {
+                        sid: unsafe { (*blob).data.cast() },
+                    },
+                )
+            }
+            ConditionValue::V4AddrMask { addr, mask } => (
+                FWP_V4_ADDR_MASK,
+                FWP_CONDITION_VALUE0_0 {
+                    v4AddrMask: store(
+                        &mut self._v4_masks,
+                        FWP_V4_ADDR_AND_MASK {
+                            addr: u32::from(*addr),
+                            mask: u32::from(*mask),
+                        },
+                    ),
+                },
+            ),
+            ConditionValue::V6AddrMask {
+                addr,
+                prefix_length,
+            } => (
+                FWP_V6_ADDR_MASK,
+                FWP_CONDITION_VALUE0_0 {
+                    v6AddrMask: store(
+                        &mut self._v6_masks,
+                        FWP_V6_ADDR_AND_MASK {
+                            addr: addr.octets(),
+                            prefixLength: *prefix_length,
+                        },
+                    ),
+                },
+            ),
+            ConditionValue::SecurityDescriptor(sddl) => (
+                FWP_SECURITY_DESCRIPTOR_TYPE,
+                FWP_CONDITION_VALUE0_0 {
+                    sd: self.store_blob(sddl_to_security_descriptor(sddl)?),
+                },
+            ),
+            ConditionValue::Range { low, high } => {
+                let mut range = Box::new(FWP_RANGE0 {
+                    valueLow: self.encode_scalar(low)?,
+                    valueHigh: self.encode_scalar(high)?,
+                });
+                let ptr: *mut FWP_RANGE0 = &mut *range;
+                self._ranges.push(range);
+                (FWP_RANGE_TYPE, FWP_CONDITION_VALUE0_0 { rangeValue: ptr })
+            }
+            ConditionValue::Unsupported { data_type } => {
+                return Err(anyhow!("Cannot encode condition value of type {data_type}"));
+            }
+        };
+        Ok(FWP_CONDITION_VALUE0 {
+            r#type,
+            Anonymous: anonymous,
+        })
+    }
+
+    /// Encodes a range bound, which uses the narrower FWP_VALUE0 union.
+    fn encode_scalar(&mut self, value: &ConditionValue) -> Result<FWP_VALUE0> {
+        let (r#type, anonymous) = match value {
+            ConditionValue::Uint8(v) => (FWP_UINT8, FWP_VALUE0_0 { uint8: *v }),
+            ConditionValue::Uint16(v) => (FWP_UINT16, FWP_VALUE0_0 { uint16: *v }),
+            ConditionValue::Uint32(v) => (FWP_UINT32, FWP_VALUE0_0 { uint32: *v }),
+            ConditionValue::Uint64(v) => (
+                FWP_UINT64,
+                FWP_VALUE0_0 {
+                    uint64: self.store_u64(*v),
+                },
+            ),
+            ConditionValue::ByteArray16(bytes) => (
+                FWP_BYTE_ARRAY16_TYPE,
+                FWP_VALUE0_0 {
+                    byteArray16: self.store_array16(*bytes),
+                },
+            ),
+            other => {
+                return Err(anyhow!(
+                    "Range bounds must be integers or IPv6 addresses, got {other}"
+                ))
+            }
+        };
+        Ok(FWP_VALUE0 {
+            r#type,
+            Anonymous: anonymous,
+        })
+    }
+
+    fn store_u64(&mut self, value: u64) -> *mut u64 {
+        store(&mut self._u64s, value)
+    }
+
+    fn store_string(&mut self, text: &str) -> Result<PWSTR> {
+        let wide = U16CString::from_str(text)?;
+        let ptr = PWSTR(wide.as_ptr() as *mut _);
+        self._strings.push(wide);
+        Ok(ptr)
+    }
+
+    fn store_array16(&mut self, bytes: [u8; 16]) -> *mut FWP_BYTE_ARRAY16 {
+        store(&mut self._arrays16, FWP_BYTE_ARRAY16 { byteArray16: bytes })
+    }
+
+    fn store_blob(&mut self, mut bytes: Vec<u8>) -> *mut FWP_BYTE_BLOB {
+        let mut blob = Box::new(FWP_BYTE_BLOB {
+            size: bytes.len() as u32,
+            data: bytes.as_mut_ptr(),
+        });
+        let ptr: *mut FWP_BYTE_BLOB = &mut *blob;
+        self._blobs.push((blob, bytes));
+        ptr
+    }
+}
+
+/// Boxes `value` into `arena` and returns a pointer that stays valid for as
+/// long as the arena does.
+fn store<T>(arena: &mut Vec<Box<T>>, value: T) -> *mut T {
+    let mut boxed = Box::new(value);
+    let ptr: *mut T = &mut *boxed;
+    arena.push(boxed);
+    ptr
+}
+
+/// Converts SDDL to the self-relative security descriptor BFE expects.
+fn sddl_to_security_descriptor(sddl: &str) -> Result<Vec<u8>> {
+    let wide = U16CString::from_str(sddl)?;
+    let mut sd = PSECURITY_DESCRIPTOR::default();
+    let mut size = 0u32;
+    unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            PCWSTR(wide.as_ptr()),
+            SDDL_REVISION_1,
+            &mut sd,
+            Some(&mut size),
+        )
+    }
+    .map_err(|e| anyhow!("Invalid security descriptor '{sddl}': {e}"))?;
+    let bytes = unsafe { std::slice::from_raw_parts(sd.0 as *const u8, size as usize) }.to_vec();
+    unsafe {
+        let _ = LocalFree(HLOCAL(sd.0));
+    }
+    Ok(bytes)
+}
+
+/// Renders a self-relative security descriptor as SDDL.
+unsafe fn security_descriptor_to_sddl(blob: &FWP_BYTE_BLOB) -> Option<String> {
+    if blob.data.is_null() {
+        return None;
+    }
+    let mut sddl = PWSTR::null();
+    ConvertSecurityDescriptorToStringSecurityDescriptorW(
+        PSECURITY_DESCRIPTOR(blob.data.cast()),
+        SDDL_REVISION_1,
+        OWNER_SECURITY_INFORMATION
+            | GROUP_SECURITY_INFORMATION
+            | DACL_SECURITY_INFORMATION
+            | SACL_SECURITY_INFORMATION,
+        &mut sddl,
+        None,
+    )
+    .ok()?;
+    let text = wide_string(sddl);
+    let _ = LocalFree(HLOCAL(sddl.0.cast()));
+    text
+}
+
+/// `len` items at `ptr`, or none when `ptr` is null.
+unsafe fn slice_or_empty<'a, T>(ptr: *const T, len: u32) -> &'a [T] {
+    if ptr.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len as usize)
+    }
+}
+
+unsafe fn blob_bytes(blob: &FWP_BYTE_BLOB) -> Vec<u8> {
+    if blob.data.is_null() {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(blob.data, blob.size as usize).to_vec()
+    }
+}
+
+/// Encodes `conditions` into native `FWPM_FILTER_CONDITION0` values and
+/// decodes them again, as an install followed by a snapshot would. Exposed
+/// for fuzzing and property tests.
+#[doc(hidden)]
+pub fn round_trip_conditions(conditions: &[Condition]) -> Result<Vec<Condition>> {
+    let encoded = EncodedConditions::encode(conditions)?;
+    Ok(encoded
+        .conditions
+        .iter()
+        .map(|cond| unsafe { decode_condition(cond) })
+        .collect())
+}
+
+/// Decodes a native condition. Callers guarantee the union pointers are
+/// valid, i.e. the condition comes from a live FWPM allocation.
+unsafe fn decode_condition(cond: &FWPM_FILTER_CONDITION0) -> Condition {
+    let value = &cond.conditionValue;
+    let decoded = match value.r#type {
+        FWP_RANGE_TYPE if !value.Anonymous.rangeValue.is_null() => {
+            let range = &*value.Anonymous.rangeValue;
+            ConditionValue::Range {
+                low: Box::new(decode_value(&range.valueLow)),
+                high: Box::new(decode_value(&range.valueHigh)),
+            }
+        }
+        FWP_UINT8 => ConditionValue::Uint8(value.Anonymous.uint8),
+        FWP_UINT16 => ConditionValue::Uint16(value.Anonymous.uint16),
+        FWP_UINT32 => ConditionValue::Uint32(value.Anonymous.uint32),
+        FWP_UINT64 if !value.Anonymous.uint64.is_null() => {
+            ConditionValue::Uint64(*value.Anonymous.uint64)
+        }
+        FWP_UNICODE_STRING_TYPE if !value.Anonymous.unicodeString.is_null() => {
+            ConditionValue::Unicode(
+                U16CStr::from_ptr_str(value.Anonymous.unicodeString.0).to_string_lossy(),
+            )
+        }
+        FWP_BYTE_ARRAY16_TYPE if !value.Anonymous.byteArray16.is_null() => {
+            ConditionValue::ByteArray16((*value.Anonymous.byteArray16).byteArray16)
+        }
+        FWP_BYTE_ARRAY6_TYPE if !value.Anonymous.byteArray6.is_null() => {
+            ConditionValue::ByteArray6((*value.Anonymous.byteArray6).byteArray6)
+        }
+        FWP_BYTE_BLOB_TYPE if !value.Anonymous.byteBlob.is_null() => {
+            ConditionValue::ByteBlob(blob_bytes(&*value.Anonymous.byteBlob))
+        }
+        FWP_SID if !value.Anonymous.sid.is_null() => {
+            // Eight bytes of header, then four per subauthority.
+            let sid = value.Anonymous.sid.cast::<u8>();
+            let len = 8 + 4 * usize::from(*sid.add(1));
+            ConditionValue::Sid(std::slice::from_raw_parts(sid, len).to_vec())
+        }
+        FWP_V4_ADDR_MASK if !value.Anonymous.v4AddrMask.is_null() => {
+            let mask = &*value.Anonymous.v4AddrMask;
+            ConditionValue::V4AddrMask {
+                addr: Ipv4Addr::from(mask.addr),
+                mask: Ipv4Addr::from(mask.mask),
+            }
+        }
+        FWP_V6_ADDR_MASK if !value.Anonymous.v6AddrMask.is_null() => {
+            let mask = &*value.Anonymous.v6AddrMask;
+            ConditionValue::V6AddrMask {
+                addr: Ipv6Addr::from(mask.addr),
+                prefix_length: mask.prefixLength,
+            }
+        }
+        FWP_SECURITY_DESCRIPTOR_TYPE if !value.Anonymous.sd.is_null() => {
+            match security_descriptor_to_sddl(&*value.Anonymous.sd) {
+                Some(sddl) => ConditionValue::SecurityDescriptor(sddl),
+                None => ConditionValue::Unsupported {
+                    data_type: FWP_SECURITY_DESCRIPTOR_TYPE.0,
+                },
+            }
+        }
+        other => ConditionValue::Unsupported { data_type: other.0 },
+    };
+    Condition {
+        field: ConditionField::from_guid(cond.fieldKey),
+        match_type: MatchType::from_fwp(cond.matchType),
+        value: decoded,
+    }
+}
+
+unsafe fn decode_value(value: &FWP_VALUE0) -> ConditionValue {
+    match value.r#type {
+        FWP_UINT8 => ConditionValue::Uint8(value.Anonymous.uint8),
+        FWP_UINT16 => ConditionValue::Uint16(value.Anonymous.uint16),
+        FWP_UINT32 => ConditionValue::Uint32(value.Anonymous.uint32),
+        FWP_UINT64 if !value.Anonymous.uint64.is_null() => {
+            ConditionValue::Uint64(*value.Anonymous.uint64)
+        }
+        FWP_UNICODE_STRING_TYPE if !value.Anonymous.unicodeString.is_null() => {
+            ConditionValue::Unicode(
+                U16CStr::from_ptr_str(value.Anonymous.unicodeString.0).to_string_lossy(),
+            )
+        }
+        FWP_BYTE_ARRAY16_TYPE if !value.Anonymous.byteArray16.is_null() => {
+            ConditionValue::ByteArray16((*value.Anonymous.byteArray16).byteArray16)
+        }
+        other => ConditionValue::Unsupported { data_type: other.0 },
+    }
+}
+
+/// A BFE session. The handle is swapped for a new one when BFE restarts
+/// under the session; see [`Engine::reopening`].
+pub struct Engine {
+    handle: Cell<HANDLE>,
+    /// The server the session was opened on, `None` for this machine.
+    server: Option<String>,
+    flags: u32,
+    /// Set while a call runs under [`Engine::reopening`], so the calls it
+    /// makes leave the retry to it.
+    in_call: Cell<bool>,
+}
+
+impl Engine {
+    pub fn open() -> Result<Self> {
+        Self::open_on(None)
+    }
+
+    /// Opens a session with BFE on `server` (a host name or address), or on
+    /// this machine when `None`. Remote sessions authenticate as the current
+    /// user over RPC, which must be an administrator on the server.
+    /// In read-only mode the provider is left alone, so enumerating works
+    /// on machines where it was never registered.
+    pub fn open_on(server: Option<&str>) -> Result<Self> {
+        let engine = Self::open_session(server, 0)?;
+        if !is_read_only() {
+            engine.ensure_provider_setup()?;
+        }
+        Ok(engine)
+    }
+
+    /// Whether the session is on this machine rather than a server.
+    pub fn is_local(&self) -> bool {
+        self.server.is_none()
+    }
+
+    /// Opens a dynamic session on this machine. BFE deletes every object it
+    /// adds when the session closes, including when the process dies.
+    pub fn open_dynamic() -> Result<Self> {
+        check_writable()?;
+        // The provider and sublayer must outlive the session, so they are
+        // registered from a regular one first.
+        Self::open()?;
+        Self::open_session(None, FWPM_SESSION_FLAG_DYNAMIC)
+    }
+
+    /// Opens a session without registering our provider and sublayer.
+    fn open_session(server: Option<&str>, flags: u32) -> Result<Self> {
+        let wide_server = server.map(U16CString::from_str).transpose()?;
+        // Named so other admins can tell who holds a transaction.
+        let name = U16CString::from_str(SESSION_NAME)?;
+        let description = U16CString::from_str(format!(
+            "{} {} (pid {})",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            std::process::id()
+        ))?;
+        unsafe {
+            let mut h = HANDLE::default();
+            let session = FWPM_SESSION0 {
+                displayData: FWPM_DISPLAY_DATA0 {
+                    name: PWSTR(name.as_ptr() as *mut _),
+                    description: PWSTR(description.as_ptr() as *mut _),
+                },
+                flags,
+                txnWaitTimeoutInMSec: TXN_WAIT_TIMEOUT_MS,
+                ..Default::default()
+            };
+            let server_ptr = wide_server
+                .as_ref()
+                .map_or(PCWSTR::null(), |s| PCWSTR(s.as_ptr()));
+            let status =
+                FwpmEngineOpen0(server_ptr, RPC_C_AUTHN_WINNT, None, Some(&session), &mut h);
+            if status != 0 {
+                return Err(WfpError::new("FwpmEngineOpen0", status).into());
+            }
+            Ok(Self {
+                handle: Cell::new(h),
+                server: server.map(str::to_string),
+                flags,
+                in_call: Cell::new(false),
+            })
+        }
+    }
+
+    fn handle(&self) -> HANDLE {
+        self.handle.get()
+    }
+
+    /// Runs `call`, and when it fails because the session's RPC connection
+    /// broke, usually a BFE restart, opens a new session, registers our
+    /// provider again and runs `call` once more. The first error is kept if
+    /// the session cannot be re-opened.
+    fn reopening<T>(&self, mut call: impl FnMut() -> Result<T>) -> Result<T> {
+        if self.in_call.replace(true) {
+            return call();
+        }
+        // Cleared however the outermost call ends, panics included, so a
+        // caught panic cannot stop later calls from reopening.
+        struct OuterCall<'a>(&'a Cell<bool>);
+        impl Drop for OuterCall<'_> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+        let _outer = OuterCall(&self.in_call);
+        match call() {
+            Err(err) if is_session_lost(&err) => match self.reopen() {
+                Ok(()) => call(),
+                Err(_) => Err(err),
+            },
+            result => result,
+        }
+    }
+
+    fn reopen(&self) -> Result<()> {
+        let fresh = Self::open_session(self.server.as_deref(), self.flags)?;
+        if !is_read_only() {
+            // As in `open_dynamic`, the provider must outlive a dynamic
+            // session, so it is registered from a regular one.
+            if self.flags & FWPM_SESSION_FLAG_DYNAMIC != 0 {
+                Self::open_on(self.server.as_deref())?;
+            } else {
+                fresh.ensure_provider_setup()?;
+            }
+        }
+        // `fresh` now holds the broken handle and closes it.
+        self.handle.swap(&fresh.handle);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        self.snapshot_cancellable(&CancelToken::new())
+    }
+
+    /// [`Engine::snapshot`] that stops between enumeration pages once
+    /// `cancel` is set.
+    pub fn snapshot_cancellable(&self, cancel: &CancelToken) -> Result<Snapshot> {
+        self.reopening(|| {
+            // The filter walk dominates on busy machines, so it runs on a second
+            // session while this one reads the metadata and boot-time filters.
+            // Names are filled in once both halves are back.
+            let (filters, metadata) = thread::scope(|scope| {
+                let server = self.server.as_deref();
+                let worker =
+                    scope.spawn(move || Self::open_session(server, 0)?.list_filters(cancel));
+                let metadata = self.enumerate_providers().and_then(|providers| {
+                    cancel.check()?;
+                    let sublayers = self.enumerate_sublayers()?;
+                    cancel.check()?;
+                    let layers = self.enumerate_layers()?;
+                    let boot_time_filters = self.list_boot_time_filters(&layers, cancel)?;
+                    Ok((providers, sublayers, layers, boot_time_filters))
+                });
+                let filters = worker
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow!("Filter enumeration panicked")));
+                (filters, metadata)
+            });
+            let (providers, sublayers, layers, mut boot_time_filters) = metadata?;
+            let mut filters = filters?;
+
+            let names = FilterNames::new(&providers, &sublayers, &layers);
+            for filter in filters.iter_mut().chain(&mut boot_time_filters) {
+                names.apply(filter);
+            }
+
+            Ok(Snapshot {
+                filters,
+                boot_time_filters,
+                providers,
+                sublayers,
+                layers,
+            })
+        })
+    }
+
+    /// Runs the enumeration behind [`Engine::snapshot`] one phase at a time
+    /// on this session, boot-time filters aside, and times each phase.
+    pub fn timed_snapshot(&self) -> Result<SnapshotTimings> {
+        self.reopening(|| {
+            let mut timings = SnapshotTimings::default();
+            let start = Instant::now();
+            let providers = self.enumerate_providers()?;
+            timings.providers = start.elapsed();
+            let start = Instant::now();
+            let sublayers = self.enumerate_sublayers()?;
+            timings.sublayers = start.elapsed();
+            let start = Instant::now();
+            let layers = self.enumerate_layers()?;
+            timings.layers = start.elapsed();
+
+            let mut filters = Vec::new();
+            let start = Instant::now();
+            self.for_each_filter(|filter| {
+                let decode = Instant::now();
+                filters.push(summarize_filter(filter));
+                timings.decode += decode.elapsed();
+            })?;
+            timings.filters = start.elapsed().saturating_sub(timings.decode);
+
+            let start = Instant::now();
+            let names = FilterNames::new(&providers, &sublayers, &layers);
+            for filter in &mut filters {
+                names.apply(filter);
+            }
+            timings.decode += start.elapsed();
+            timings.filter_count = filters.len();
+            Ok(timings)
+        })
+    }
+
+    /// Adds an outbound TCP rule for one or more remote ports and returns the
+    /// rule key.
+    pub fn add_simple_tcp_filter_v4(
+        &self,
+        name: &str,
+        remote_ports: &[u16],
+        action: WfpAction,
+    ) -> Result<GUID> {
+        crate::naming::NamingPolicy::load()?.check(name)?;
+        self.reopening(|| {
+            self.ensure_provider_setup()?;
+            begin_transaction(self.handle())?;
+            let key = guid_from_uuid(Uuid::new_v4());
+            let result = self.weight_allocator().and_then(|mut weights| {
+                self.install_simple_tcp_rule_v4_inner(
+                    &mut weights,
+                    key,
+                    name,
+                    remote_ports,
+                    action,
+                    None,
+                )
+                .map(|_| key)
+            });
+            finish_transaction(self.handle(), result)
+                .inspect(|_| audit_rule(AuditAction::Add, key, name, remote_ports, action))
+        })
+    }
+
+    /// Rewrites an owned rule identified by its persistent key. Unlike
+    /// runtime filter IDs, keys survive reboots and re-imports. All filters
+    /// expanded from the rule are replaced together.
+    pub fn update_filter_by_key(
+        &self,
+        key: GUID,
+        name: &str,
+        remote_ports: &[u16],
+        action: WfpAction,
+    ) -> Result<()> {
+        crate::naming::NamingPolicy::load()?.check(name)?;
+        self.reopening(|| {
+            self.ensure_provider_setup()?;
+            begin_transaction(self.handle())?;
+            let result = self.weight_allocator().and_then(|mut weights| {
+                let current = self.rule_member_inner(key)?;
+                match self.remove_rule_inner(key)? {
+                    0 => Err(anyhow!("Filter {} not found", uuid_from_guid(key))),
+                    _ => self.install_simple_tcp_rule_v4_inner(
+                        &mut weights,
+                        key,
+                        name,
+                        remote_ports,
+                        action,
+                        current.as_ref(),
+                    ),
+                }
+            });
+            finish_transaction(self.handle(), result)
+                .inspect(|_| audit_rule(AuditAction::Update, key, name, remote_ports, action))
+        })
+    }
+
+    /// Deletes an owned rule by key, including every filter expanded from it.
+    pub fn delete_filter_by_key(&self, key: GUID) -> Result<()> {
+        self.reopening(|| {
+            begin_transaction(self.handle())?;
+            let result = match self.remove_rule_inner(key) {
+                Ok(0) => Err(anyhow!("Filter {} not found", uuid_from_guid(key))),
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            };
+            finish_transaction(self.handle(), result).inspect(|()| {
+                syslog::audit(AuditRecord {
+                    action: AuditAction::Delete,
+                    rule: uuid_from_guid(key).to_string(),
+                    name: None,
+                    detail: "rule removed".into(),
+                })
+            })
+        })
+    }
+
+    pub fn delete_filter_by_id(&self, id: u64) -> Result<()> {
+        self.reopening(|| {
+            begin_transaction(self.handle())?;
+            let result = self.filter_by_id(id).and_then(|filter| {
+                if !filter.as_ref().is_some_and(is_owned) {
+                    return Err(anyhow!("Filter {id} is not managed by this application"));
+                }
+                let status = unsafe { FwpmFilterDeleteById0(self.handle(), id) };
+                if status != 0 {
+                    return Err(WfpError::new("FwpmFilterDeleteById0", status).into());
+                }
+                Ok(())
+            });
+            finish_transaction(self.handle(), result).inspect(|()| {
+                syslog::audit(AuditRecord {
+                    action: AuditAction::Delete,
+                    rule: format!("filter {id}"),
+                    name: None,
+                    detail: "filter removed".into(),
+                })
+            })
+        })
+    }
+
+    /// Deletes filters by key whatever their provider, e.g. the ones an
+    /// installer just added, in one transaction.
+    pub fn delete_foreign_filters(&self, keys: &[GUID]) -> Result<()> {
+        self.reopening(|| {
+            begin_transaction(self.handle())?;
+            let result = keys.iter().try_for_each(|key| {
+                let status = unsafe { FwpmFilterDeleteByKey0(self.handle(), key) };
+                if status != 0 {
+                    return Err(WfpError::new("FwpmFilterDeleteByKey0", status).into());
+                }
+                Ok(())
+            });
+            finish_transaction(self.handle(), result).inspect(|()| {
+                for key in keys {
+                    syslog::audit(AuditRecord {
+                        action: AuditAction::Delete,
+                        rule: uuid_from_guid(*key).to_string(),
+                        name: None,
+                        detail: "foreign filter removed".into(),
+                    });
+                }
+            })
+        })
+    }
+
+    fn filter_by_id(&self, id: u64) -> Result<FwpBox<FWPM_FILTER0>> {
+        let mut filter_ptr = ptr::null_mut();
+        let status = unsafe { FwpmFilterGetById0(self.handle(), id, &mut filter_ptr) };
+        let filter = unsafe { FwpBox::from_raw(filter_ptr) };
+        if status != 0 {
+            return Err(WfpError::new("FwpmFilterGetById0", status).into());
+        }
+        Ok(filter)
+    }
+
+    /// Our provider, sublayers and rules as a [`RuleSet`] document, stamped
+    /// with the host it came from. Filters expanded from one multi-port rule
+    /// are folded back into a single entry.
+    pub fn export_owned_filters(&self) -> Result<String> {
+        self.reopening(|| {
+            let set = RuleSet::owned(self.snapshot()?, &self.sublayer_details()?)
+                .with_provider_contexts(&self.provider_contexts()?);
+            let set = match &self.server {
+                Some(server) => set.stamped(server, None),
+                None => set.stamped(
+                    &std::env::var("COMPUTERNAME").unwrap_or_else(|_| "localhost".into()),
+                    os_build(),
+                ),
+            };
+            Ok(serde_json::to_string_pretty(&set)?)
+        })
+    }
+
+    /// Installs a filter described by `builder` in its own transaction,
+    /// assigning it a weight by tier unless the builder pins one.
+    pub fn add_filter(&self, builder: &FilterBuilder) -> Result<u64> {
+        check_names(std::slice::from_ref(builder))?;
+        self.reopening(|| {
+            self.ensure_provider_setup()?;
+            begin_transaction(self.handle())?;
+            let result = self.weight_allocator().and_then(|mut weights| {
+                builder
+                    .clone()
+                    .allocate_weight(&mut weights)
+                    .install(self.handle())
+            });
+            finish_transaction(self.handle(), result).inspect(|id| {
+                syslog::audit(AuditRecord {
+                    action: AuditAction::Add,
+                    rule: format!("filter {id}"),
+                    name: Some(builder.name.clone()),
+                    detail: format!("{} filter", builder.action.as_str()),
+                })
+            })
+        })
+    }
+
+    /// Replaces every filter of the rule `key` with `builders` in one
+    /// transaction, creating the rule if it is not installed. Returns how
+    /// many filters were removed.
+    pub fn replace_rule(&self, key: GUID, name: &str, builders: &[FilterBuilder]) -> Result<usize> {
+        check_names(builders)?;
+        self.reopening(|| {
+            self.ensure_provider_setup()?;
+            begin_transaction(self.handle())?;
+            let result = self.weight_allocator().and_then(|mut weights| {
+                let removed = self.remove_rule_inner(key)?;
+                for builder in builders {
+                    builder
+                        .clone()
+                        .allocate_weight(&mut weights)
+                        .install(self.handle())?;
+                }
+                Ok(removed)
+            });
+            finish_transaction(self.handle(), result).inspect(|removed| {
+                syslog::audit(AuditRecord {
+                    action: if *removed > 0 {
+                        AuditAction::Update
+                    } else {
+                        AuditAction::Add
+                    },
+                    rule: uuid_from_guid(key).to_string(),
+                    name: Some(name.to_string()),
+                    detail: format!("{} filters", builders.len()),
+                })
+            })
+        })
+    }
+
+    /// Reinstalls each of `filters` with `edit` applied, all in one
+    /// transaction, and returns how many were changed. The filters keep
+    /// their names, so those from before the naming policy stay editable.
+    pub fn bulk_edit(&self, filters: &[FilterSummary], edit: &BulkEdit) -> Result<usize> {
+        let builders = filters
+            .iter()
+            .map(|f| edit.apply(f))
+            .collect::<Result<Vec<_>>>()?;
+        self.reinstall(&builders, &format!("bulk edit: {}", edit.describe()))
+    }
+
+    /// Puts each of `builders` in place of the filter under its key, all in
+    /// one transaction. `detail` says why, for the audit log.
+    pub fn reinstall_filters(&self, builders: &[FilterBuilder], detail: &str) -> Result<usize> {
+        check_names(builders)?;
+        self.reinstall(builders, detail)
+    }
+
+    /// [`Self::reinstall_filters`] without the naming policy check.
+    fn reinstall(&self, builders: &[FilterBuilder], detail: &str) -> Result<usize> {
+        self.reopening(|| {
+            self.ensure_provider_setup()?;
+            begin_transaction(self.handle())?;
+            let result = self.weight_allocator().and_then(|mut weights| {
+                for builder in builders {
+                    let status = unsafe { FwpmFilterDeleteByKey0(self.handle(), &builder.key) };
+                    if status != 0 {
+                        return Err(WfpError::new("FwpmFilterDeleteByKey0", status).into());
+                    }
+                }
+                for builder in builders {
+                    builder
+                        .clone()
+                        .allocate_weight(&mut weights)
+                        .install(self.handle())?;
+                }
+                Ok(builders.len())
+            });
+            finish_transaction(self.handle(), result)
+                .inspect(|_| audit_reinstalled(builders, detail))
+        })
+    }
+
+    /// Imports rules inside one transaction. Rules carrying a key that is
+    /// already installed are replaced in place, so re-importing the same file
+    /// is idempotent; rules without a key get a fresh one.
+    pub fn import_filters(&self, configs: &[FilterConfig]) -> Result<ImportSummary> {
+        let configs = &with_keys(configs);
+        self.reopening(|| {
+            crate::naming::validate_configs(configs)?;
+            self.ensure_provider_setup()?;
+            begin_transaction(self.handle())?;
+            let result = self
+                .import_inner(configs, &CancelToken::new())
+                .and_then(|summary| self.check_dependencies(configs).map(|()| summary));
+            finish_transaction(self.handle(), result).inspect(|_| audit_imports(configs))
+        })
+    }
+
+    /// Adds a sublayer of ours for a group of rules. Within each layer,
+    /// sublayers are evaluated from the highest weight down.
+    pub fn create_sublayer(&self, name: &str, weight: u16) -> Result<GUID> {
+        self.reopening(|| {
+            if name.trim().is_empty() {
+                return Err(anyhow!("Sublayer name is required"));
+            }
+            let key = guid_from_uuid(Uuid::new_v4());
+            self.add_provider()?;
+            self.add_sublayer(key, name, weight)?;
+            audit_sublayer(
+                AuditAction::Add,
+                &SublayerInfo {
+                    key,
+                    name: name.to_string(),
+                    provider_key: Some(PROVIDER_KEY),
+                    weight,
+                    ours: true,
+                },
+            );
+            Ok(key)
+        })
+    }
+
+    /// Deletes one of our sublayers. While filters reference it this fails
+    /// with a list of them, unless `cascade` is set, in which case they are
+    /// deleted with the sublayer in one transaction.
+    pub fn delete_sublayer(&self, key: GUID, cascade: bool) -> Result<()> {
+        self.reopening(|| {
+            let sublayer = custom_sublayer(self.sublayer_details()?, key)?;
+            let users: Vec<FilterSummary> = self
+                .snapshot()?
+                .filters
+                .into_iter()
+                .filter(|f| f.sublayer_key == key)
+                .collect();
+            if !cascade {
+                check_sublayer_unused(&sublayer, &users)?;
+            }
+            begin_transaction(self.handle())?;
+            let result = users
+                .iter()
+                .try_for_each(|filter| {
+                    let status = unsafe { FwpmFilterDeleteById0(self.handle(), filter.id) };
+                    if status != 0 {
+                        return Err(WfpError::new("FwpmFilterDeleteById0", status).into());
+                    }
+                    Ok(())
+                })
+                .and_then(|()| {
+                    let status = unsafe { FwpmSubLayerDeleteByKey0(self.handle(), &key) };
+                    if status != 0 {
+                        return Err(WfpError::new("FwpmSubLayerDeleteByKey0", status).into());
+                    }
+                    Ok(())
+                });
+            finish_transaction(self.handle(), result)
+                .inspect(|()| audit_sublayer_delete(&sublayer, &users))
+        })
+    }
+
+    /// Adds a general provider context under our provider, holding `data`
+    /// for the callouts of the filters that will carry it. Contexts are
+    /// persistent, so persistent filters can carry them.
+    pub fn create_provider_context(&self, name: &str, data: &[u8]) -> Result<GUID> {
+        self.reopening(|| {
+            if name.trim().is_empty() {
+                return Err(anyhow!("Provider context name is required"));
+            }
+            let key = guid_from_uuid(Uuid::new_v4());
+            self.add_provider()?;
+            let id = self.add_provider_context(key, name, data)?;
+            audit_provider_context(
+                AuditAction::Add,
+                &ProviderContextInfo {
+                    key,
+                    id,
+                    name: name.to_string(),
+                    provider_key: Some(PROVIDER_KEY),
+                    data: Some(data.to_vec()),
+                    ours: true,
+                },
+            );
+            Ok(key)
+        })
+    }
+
+    /// Deletes one of our provider contexts; refused while filters carry
+    /// it.
+    pub fn delete_provider_context(&self, key: GUID) -> Result<()> {
+        self.reopening(|| {
+            check_writable()?;
+            // Checked and deleted in one transaction, so no filter can take
+            // the context in between. The filters are read on this session,
+            // as a second one would wait for the transaction.
+            begin_transaction(self.handle())?;
+            let result = self.list_filters(&CancelToken::new()).and_then(|filters| {
+                let context = unused_provider_context(self.provider_contexts()?, &filters, key)?;
+                let status = unsafe { FwpmProviderContextDeleteByKey0(self.handle(), &key) };
+                if status != 0 {
+                    return Err(WfpError::new("FwpmProviderContextDeleteByKey0", status).into());
+                }
+                Ok(context)
+            });
+            let context = finish_transaction(self.handle(), result)?;
+            audit_provider_context(AuditAction::Delete, &context);
+            Ok(())
+        })
+    }
+
+    /// Adds a persistent callout under our provider for a companion driver
+    /// to register its classify functions against; `key` is the one the
+    /// driver is built with. Filters can use it before the driver loads.
+    pub fn register_callout(
+        &self,
+        key: GUID,
+        name: &str,
+        description: &str,
+        layer: GUID,
+    ) -> Result<()> {
+        self.reopening(|| {
+            check_writable()?;
+            if name.trim().is_empty() {
+                return Err(anyhow!("Callout name is required"));
+            }
+            self.add_provider()?;
+            let callout_name = U16CString::from_str(name)?;
+            let callout_description = U16CString::from_str(description)?;
+            let callout = FWPM_CALLOUT0 {
+                calloutKey: key,
+                displayData: FWPM_DISPLAY_DATA0 {
+                    name: PWSTR(callout_name.as_ptr() as *mut _),
+                    description: if description.is_empty() {
+                        PWSTR::null()
+                    } else {
+                        PWSTR(callout_description.as_ptr() as *mut _)
+                    },
+                },
+                flags: FWPM_CALLOUT_FLAG_PERSISTENT,
+                providerKey: &PROVIDER_KEY as *const GUID as *mut GUID,
+                applicableLayer: layer,
+                ..Default::default()
+            };
+            let mut id = 0u32;
+            let status = unsafe {
+                FwpmCalloutAdd0(
+                    self.handle(),
+                    &callout,
+                    PSECURITY_DESCRIPTOR::default(),
+                    Some(&mut id),
+                )
+            };
+            if status != 0 {
+                return Err(WfpError::new("FwpmCalloutAdd0", status).into());
+            }
+            audit_callout(
+                AuditAction::Add,
+                &CalloutInfo {
+                    key,
+                    id,
+                    name: name.to_string(),
+                    description: (!description.is_empty()).then(|| description.to_string()),
+                    provider_key: Some(PROVIDER_KEY),
+                    layer_key: layer,
+                    registered: false,
+                    ours: true,
+                },
+            );
+            Ok(())
+        })
+    }
+
+    /// Deletes one of our callouts; refused while filters use it.
+    pub fn unregister_callout(&self, key: GUID) -> Result<()> {
+        self.reopening(|| {
+            check_writable()?;
+            let callout = unused_callout(self.callouts()?, &self.snapshot()?.filters, key)?;
+            let status = unsafe { FwpmCalloutDeleteByKey0(self.handle(), &key) };
+            if status != 0 {
+                return Err(WfpError::new("FwpmCalloutDeleteByKey0", status).into());
+            }
+            audit_callout(AuditAction::Delete, &callout);
+            Ok(())
+        })
+    }
+
+    /// Provider contexts, i.e. data filters hand to their callouts, of
+    /// every provider.
+    pub fn provider_contexts(&self) -> Result<Vec<ProviderContextInfo>> {
+        self.reopening(|| {
+            let mut out = Vec::new();
+            EnumHandle::open(
+                self.handle(),
+                "FwpmProviderContextCreateEnumHandle0",
+                |h| unsafe { FwpmProviderContextCreateEnumHandle0(self.handle(), None, h) },
+                |engine, h| unsafe { FwpmProviderContextDestroyEnumHandle0(engine, h) },
+            )?
+            .for_each(
+                "FwpmProviderContextEnum0",
+                |engine, h, entries, count| unsafe {
+                    FwpmProviderContextEnum0(engine, h, 128, entries, count)
+                },
+                |context: &FWPM_PROVIDER_CONTEXT0| {
+                    let provider_key = unsafe { context.providerKey.as_ref().copied() };
+                    let data = match context.r#type {
+                        FWPM_GENERAL_CONTEXT => unsafe {
+                            context
+                                .Anonymous
+                                .dataBuffer
+                                .as_ref()
+                                .map(|blob| blob_bytes(blob))
+                        },
+                        _ => None,
+                    };
+                    out.push(ProviderContextInfo {
+                        key: context.providerContextKey,
+                        id: context.providerContextId,
+                        name: display_name(&context.displayData),
+                        provider_key,
+                        data,
+                        ours: provider_key == Some(PROVIDER_KEY),
+                    })
+                },
+            )?;
+            Ok(out)
+        })
+    }
+
+    /// Imports a complete export in one transaction. Sublayers missing here
+    /// are added with their exported weights before the rules; existing
+    /// ones keep their weight.
+    pub fn import_rule_set(&self, set: &RuleSet) -> Result<ImportSummary> {
+        self.import_rule_set_cancellable(set, &CancelToken::new())
+    }
+
+    /// [`Engine::import_rule_set`] that checks `cancel` before each rule;
+    /// a cancelled import aborts its transaction, leaving nothing changed.
+    pub fn import_rule_set_cancellable(
+        &self,
+        set: &RuleSet,
+        cancel: &CancelToken,
+    ) -> Result<ImportSummary> {
+        let set = &RuleSet {
+            filters: with_keys(&set.filters),
+            ..set.clone()
+        };
+        self.reopening(|| {
+            set.validate()?;
+            self.add_provider()?;
+            begin_transaction(self.handle())?;
+            let result = set
+                .sublayers
+                .iter()
+                .try_for_each(|s| self.add_sublayer(guid_from_uuid(s.key), &s.name, s.weight))
+                .and_then(|()| self.add_sublayer(SUBLAYER_KEY, SUBLAYER_NAME, SUBLAYER_WEIGHT))
+                .and_then(|()| {
+                    set.provider_contexts.iter().try_for_each(|c| {
+                        self.add_provider_context(guid_from_uuid(c.key), &c.name, &c.data)
+                            .map(drop)
+                    })
+                })
+                .and_then(|()| self.import_inner(&order_weights(&set.filters), cancel))
+                .and_then(|summary| self.check_dependencies(&set.filters).map(|()| summary));
+            finish_transaction(self.handle(), result).inspect(|_| audit_imports(&set.filters))
+        })
+    }
+
+    /// Makes the rules supplied by an external source match `configs` in
+    /// one transaction: rules are added or replaced by key as on import, and
+    /// rules in `previous` (what the source supplied last time) that are no
+    /// longer listed are removed. Every config must carry a key.
+    pub fn reconcile(&self, configs: &[FilterConfig], previous: &[Uuid]) -> Result<ImportSummary> {
+        self.reopening(|| {
+            if let Some(cfg) = configs.iter().find(|cfg| cfg.key.is_none()) {
+                return Err(anyhow!("Rule '{}' from a rule source has no key", cfg.name));
+            }
+            crate::naming::validate_configs(configs)?;
+            self.ensure_provider_setup()?;
+            begin_transaction(self.handle())?;
+            let result = self
+                .import_inner(&order_weights(configs), &CancelToken::new())
+                .and_then(|mut summary| {
+                    for key in previous {
+                        if !configs.iter().any(|cfg| cfg.key == Some(*key)) {
+                            summary.removed += self.remove_rule_inner(guid_from_uuid(*key))?.min(1);
+                        }
+                    }
+                    self.check_dependencies(configs)?;
+                    Ok(summary)
+                });
+            finish_transaction(self.handle(), result).inspect(|_| {
+                audit_imports(configs);
+                for key in previous {
+                    if !configs.iter().any(|cfg| cfg.key == Some(*key)) {
+                        syslog::audit(AuditRecord {
+                            action: AuditAction::Delete,
+                            rule: key.to_string(),
+                            name: None,
+                            detail: "no longer supplied by its rule source".into(),
+                        });
+                    }
+                }
+            })
+        })
+    }
+
+    /// Adds or replaces each config by key. Callers must hold a transaction.
+    fn import_inner(
+        &self,
+        configs: &[FilterConfig],
+        cancel: &CancelToken,
+    ) -> Result<ImportSummary> {
+        let mut summary = ImportSummary::default();
+        let mut weights = self.weight_allocator()?;
+        for cfg in &order_dependencies(configs)? {
+            cancel.check()?;
+            let key = guid_from_uuid(cfg.key.unwrap_or_else(Uuid::new_v4));
+            let removed = self.remove_rule_inner(key)?;
+            for builder in cfg.builders(key)? {
+                builder
+                    .allocate_weight(&mut weights)
+                    .install(self.handle())?;
+            }
+            if removed > 0 {
+                summary.updated += 1;
+            } else {
+                summary.added += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Fails unless the rules `configs` depend on are installed. Callers
+    /// must hold a transaction, so the check sees the change being made.
+    fn check_dependencies(&self, configs: &[FilterConfig]) -> Result<()> {
+        let mut installed = Vec::new();
+        self.for_each_filter(|filter| {
+            if is_owned(filter) {
+                installed.push(uuid_from_guid(rule_tag(filter).unwrap_or(filter.filterKey)));
+            }
+        })?;
+        check_dependencies(configs, &installed)
+    }
+
+    /// Seeds a [`WeightAllocator`] with the weights of every owned rule.
+    fn weight_allocator(&self) -> Result<WeightAllocator> {
+        let mut allocator = WeightAllocator::default();
+        self.for_each_filter(|filter| {
+            if is_owned(filter) {
+                if let Some(weight) = decode_weight(&filter.weight) {
+                    allocator.record(rule_tag(filter).unwrap_or(filter.filterKey), weight);
+                }
+            }
+        })?;
+        Ok(allocator)
+    }
+
+    /// Removes every owned filter making up the rule `key`: the filter with
+    /// that key and any members expanded from it. WFP filters are immutable,
+    /// so updates are a remove followed by a re-add under the same keys.
+    /// Returns how many filters were removed; callers must hold a transaction.
+    fn remove_rule_inner(&self, key: GUID) -> Result<usize> {
+        let mut doomed = Vec::new();
+        if self.owned_filter_exists(&key)? {
+            doomed.push(key);
+        }
+        self.for_each_filter(|filter| {
+            if is_owned(filter) && rule_tag(filter) == Some(key) {
+                doomed.push(filter.filterKey);
+            }
+        })?;
+        for member in &doomed {
+            let status = unsafe { FwpmFilterDeleteByKey0(self.handle(), member) };
+            if status != 0 {
+                return Err(WfpError::new("FwpmFilterDeleteByKey0", status).into());
+            }
+        }
+        Ok(doomed.len())
+    }
+
+    /// Returns whether a filter with `key` is installed, failing if it exists
+    /// but is not managed by this application.
+    fn owned_filter_exists(&self, key: &GUID) -> Result<bool> {
+        let mut filter_ptr = ptr::null_mut();
+        let status = unsafe { FwpmFilterGetByKey0(self.handle(), key, &mut filter_ptr) };
+        let filter = unsafe { FwpBox::from_raw(filter_ptr) };
+        if status == FWP_E_FILTER_NOT_FOUND.0 as u32 {
+            return Ok(false);
+        }
+        if status != 0 {
+            return Err(WfpError::new("FwpmFilterGetByKey0", status).into());
+        }
+        if !filter.as_ref().is_some_and(is_owned) {
+            return Err(anyhow!(
+                "Filter {} is not managed by this application",
+                uuid_from_guid(*key)
+            ));
+        }
+        Ok(true)
+    }
+
+    /// One of the filters of our rule `key`, as installed.
+    fn rule_member_inner(&self, key: GUID) -> Result<Option<FilterSummary>> {
+        let mut member = None;
+        self.for_each_filter(|filter| {
+            if member.is_none()
+                && is_owned(filter)
+                && (filter.filterKey == key || rule_tag(filter) == Some(key))
+            {
+                member = Some(summarize_filter(filter));
+            }
+        })?;
+        Ok(member)
+    }
+
+    /// Installs the filters of a simple TCP rule. When it replaces
+    /// `current`, they keep its callout and provider context.
+    fn install_simple_tcp_rule_v4_inner(
+        &self,
+        weights: &mut WeightAllocator,
+        key: GUID,
+        name: &str,
+        remote_ports: &[u16],
+        action: WfpAction,
+        current: Option<&FilterSummary>,
+    ) -> Result<()> {
+        for builder in simple_tcp_rule_v4(key, name, remote_ports, action) {
+            let builder = match current {
+                Some(current) => builder.bound_like(current),
+                None => builder,
+            };
+            builder.allocate_weight(weights).install(self.handle())?;
+        }
+        Ok(())
+    }
+
+    fn ensure_provider_setup(&self) -> Result<()> {
+        self.add_provider()?;
+        self.add_sublayer(SUBLAYER_KEY, SUBLAYER_NAME, SUBLAYER_WEIGHT)
+    }
+
+    fn add_provider(&self) -> Result<()> {
+        check_writable()?;
+        let provider_name = U16CString::from_str(PROVIDER_NAME)?;
+        let provider = FWPM_PROVIDER0 {
+            providerKey: PROVIDER_KEY,
+            displayData: FWPM_DISPLAY_DATA0 {
+                name: PWSTR(provider_name.as_ptr() as *mut _),
+                description: PWSTR::null(),
+            },
+            ..Default::default()
+        };
+        let status =
+            unsafe { FwpmProviderAdd0(self.handle(), &provider, PSECURITY_DESCRIPTOR::default()) };
+        if status != 0 && status != FWP_E_ALREADY_EXISTS.0 as u32 {
+            return Err(WfpError::new("FwpmProviderAdd0", status).into());
+        }
+        Ok(())
+    }
+
+    /// Adds a sublayer under our provider. One that already exists is left
+    /// as it is, weight included.
+    fn add_sublayer(&self, key: GUID, name: &str, weight: u16) -> Result<()> {
+        check_writable()?;
+        let sublayer_name = U16CString::from_str(name)?;
+        let sublayer = FWPM_SUBLAYER0 {
+            subLayerKey: key,
+            displayData: FWPM_DISPLAY_DATA0 {
+                name: PWSTR(sublayer_name.as_ptr() as *mut _),
+                description: PWSTR::null(),
+            },
+            providerKey: &PROVIDER_KEY as *const GUID as *mut GUID,
+            weight,
+            ..Default::default()
+        };
+        let status =
+            unsafe { FwpmSubLayerAdd0(self.handle(), &sublayer, PSECURITY_DESCRIPTOR::default()) };
+        if status != 0 && status != FWP_E_ALREADY_EXISTS.0 as u32 {
+            return Err(WfpError::new("FwpmSubLayerAdd0", status).into());
+        }
+        Ok(())
+    }
+
+    /// Adds a general provider context under our provider. One that
+    /// already exists is left as it is, data included; its ID is then 0.
+    fn add_provider_context(&self, key: GUID, name: &str, data: &[u8]) -> Result<u64> {
+        check_writable()?;
+        let context_name = U16CString::from_str(name)?;
+        let mut data = data.to_vec();
+        let mut buffer = FWP_BYTE_BLOB {
+            size: data.len() as u32,
+            data: data.as_mut_ptr(),
+        };
+        let context = FWPM_PROVIDER_CONTEXT0 {
+            providerContextKey: key,
+            displayData: FWPM_DISPLAY_DATA0 {
+                name: PWSTR(context_name.as_ptr() as *mut _),
+                description: PWSTR::null(),
+            },
+            flags: FWPM_PROVIDER_CONTEXT_FLAG_PERSISTENT,
+            providerKey: &PROVIDER_KEY as *const GUID as *mut GUID,
+            r#type: FWPM_GENERAL_CONTEXT,
+            Anonymous: FWPM_PROVIDER_CONTEXT0_0 {
+                dataBuffer: &mut buffer,
+            },
+            ..Default::default()
+        };
+        let mut id = 0u64;
+        let status = unsafe {
+            FwpmProviderContextAdd0(
+                self.handle(),
+                &context,
+                PSECURITY_DESCRIPTOR::default(),
+                Some(&mut id),
+            )
+        };
+        if status != 0 && status != FWP_E_ALREADY_EXISTS.0 as u32 {
+            return Err(WfpError::new("FwpmProviderContextAdd0", status).into());
+        }
+        Ok(id)
+    }
+
+    /// Every run-time filter, named like those in [`Engine::snapshot`], read
+    /// from BFE one page at a time as the iterator advances so only a page
+    /// is held in memory. An error ends the stream.
+    pub fn filters_iter(&self) -> Result<impl Iterator<Item = Result<FilterSummary>> + '_> {
+        let names = FilterNames::new(
+            &self.enumerate_providers()?,
+            &self.enumerate_sublayers()?,
+            &self.enumerate_layers()?,
+        );
+        let handle = self.filter_enum(None)?;
+        Ok(FilterPages {
+            handle: Some(handle),
+            page: Vec::new().into_iter(),
+            names,
+        })
+    }
+
+    /// Every filter, with layer, sublayer and provider names left for
+    /// [`FilterNames::apply`].
+    fn list_filters(&self, cancel: &CancelToken) -> Result<Vec<FilterSummary>> {
+        let mut filters = Vec::new();
+        self.filter_enum(None)?.for_each_until(
+            "FwpmFilterEnum0",
+            |engine, h, entries, count| unsafe { FwpmFilterEnum0(engine, h, 128, entries, count) },
+            cancel,
+            |filter| filters.push(summarize_filter(filter)),
+        )?;
+        Ok(filters)
+    }
+
+    /// Boot-time filters enforced before BFE starts. They never show up in a
+    /// normal enumeration, and the boot-time enum flag only works with a
+    /// layer template, so every layer is queried in turn.
+    fn list_boot_time_filters(
+        &self,
+        layers: &[NamedGuid],
+        cancel: &CancelToken,
+    ) -> Result<Vec<FilterSummary>> {
+        let mut filters = Vec::new();
+        for layer in layers {
+            cancel.check()?;
+            let template = FWPM_FILTER_ENUM_TEMPLATE0 {
+                layerKey: layer.key,
+                enumType: FWP_FILTER_ENUM_OVERLAPPING,
+                flags: FWP_FILTER_ENUM_FLAG_BOOTTIME_ONLY,
+                actionMask: 0xFFFF_FFFF,
+                ..Default::default()
+            };
+            self.for_each_filter_in(Some(&template), |filter| {
+                filters.push(summarize_filter(filter))
+            })?;
+        }
+        Ok(filters)
+    }
+
+    /// Walks every filter on the engine page by page, handing each entry to
+    /// `visit` while its FWPM allocation is still alive.
+    fn for_each_filter(&self, visit: impl FnMut(&FWPM_FILTER0)) -> Result<()> {
+        self.for_each_filter_in(None, visit)
+    }
+
+    /// Like [`Engine::for_each_filter`], restricted to filters matching
+    /// `template`.
+    fn for_each_filter_in(
+        &self,
+        template: Option<&FWPM_FILTER_ENUM_TEMPLATE0>,
+        visit: impl FnMut(&FWPM_FILTER0),
+    ) -> Result<()> {
+        self.filter_enum(template)?.for_each(
+            "FwpmFilterEnum0",
+            |engine, h, entries, count| unsafe { FwpmFilterEnum0(engine, h, 128, entries, count) },
+            visit,
+        )
+    }
+
+    fn filter_enum(&self, template: Option<&FWPM_FILTER_ENUM_TEMPLATE0>) -> Result<EnumHandle> {
+        let template = template.map(|t| t as *const _);
+        EnumHandle::open(
+            self.handle(),
+            "FwpmFilterCreateEnumHandle0",
+            |h| unsafe { FwpmFilterCreateEnumHandle0(self.handle(), template, h) },
+            |engine, h| unsafe { FwpmFilterDestroyEnumHandle0(engine, h) },
+        )
+    }
+
+    fn enumerate_layers(&self) -> Result<Vec<NamedGuid>> {
+        let mut out = Vec::new();
+        EnumHandle::open(
+            self.handle(),
+            "FwpmLayerCreateEnumHandle0",
+            |h| unsafe { FwpmLayerCreateEnumHandle0(self.handle(), None, h) },
+            |engine, h| unsafe { FwpmLayerDestroyEnumHandle0(engine, h) },
+        )?
+        .for_each(
+            "FwpmLayerEnum0",
+            |engine, h, entries, count| unsafe { FwpmLayerEnum0(engine, h, 128, entries, count) },
+            |layer: &FWPM_LAYER0| {
+                out.push(NamedGuid {
+                    key: layer.layerKey,
+                    name: display_name(&layer.displayData),
+                    description: display_description(&layer.displayData),
+                })
+            },
+        )?;
+        Ok(out)
+    }
+
+    fn enumerate_providers(&self) -> Result<Vec<NamedGuid>> {
+        let mut out = Vec::new();
+        EnumHandle::open(
+            self.handle(),
+            "FwpmProviderCreateEnumHandle0",
+            |h| unsafe { FwpmProviderCreateEnumHandle0(self.handle(), None, h) },
+            |engine, h| unsafe { FwpmProviderDestroyEnumHandle0(engine, h) },
+        )?
+        .for_each(
+            "FwpmProviderEnum0",
+            |engine, h, entries, count| unsafe {
+                FwpmProviderEnum0(engine, h, 128, entries, count)
+            },
+            |provider: &FWPM_PROVIDER0| {
+                out.push(NamedGuid {
+                    key: provider.providerKey,
+                    name: wide_string(provider.displayData.name)
+                        .filter(|name| !name.is_empty())
+                        .or_else(|| known_provider(provider.providerKey).map(String::from))
+                        .unwrap_or_else(|| display_name(&provider.displayData)),
+                    description: display_description(&provider.displayData),
+                })
+            },
+        )?;
+        Ok(out)
+    }
+
+    fn enumerate_sublayers(&self) -> Result<Vec<NamedGuid>> {
+        let mut out = Vec::new();
+        self.for_each_sublayer(|sublayer| {
+            out.push(NamedGuid {
+                key: sublayer.subLayerKey,
+                name: display_name(&sublayer.displayData),
+                description: display_description(&sublayer.displayData),
+            });
+        })?;
+        Ok(out)
+    }
+
+    /// Every session open with BFE, including this one.
+    pub fn sessions(&self) -> Result<Vec<SessionInfo>> {
+        self.reopening(|| {
+            let mut out = Vec::new();
+            EnumHandle::open(
+                self.handle(),
+                "FwpmSessionCreateEnumHandle0",
+                |h| unsafe { FwpmSessionCreateEnumHandle0(self.handle(), None, h) },
+                |engine, h| unsafe { FwpmSessionDestroyEnumHandle0(engine, h) },
+            )?
+            .for_each(
+                "FwpmSessionEnum0",
+                |engine, h, entries, count| unsafe {
+                    FwpmSessionEnum0(engine, h, 128, entries, count)
+                },
+                |session: &FWPM_SESSION0| {
+                    out.push(SessionInfo {
+                        key: session.sessionKey,
+                        name: display_name(&session.displayData),
+                        description: display_description(&session.displayData),
+                        process_id: session.processId,
+                        username: wide_string(session.username).unwrap_or_default(),
+                        kernel_mode: session.kernelMode.as_bool(),
+                        txn_wait_timeout_ms: session.txnWaitTimeoutInMSec,
+                        ours: session.processId == std::process::id(),
+                    })
+                },
+            )?;
+            Ok(out)
+        })
+    }
+
+    /// Sublayers with the owner and weight that decide arbitration order.
+    pub fn sublayer_details(&self) -> Result<Vec<SublayerInfo>> {
+        self.reopening(|| {
+            let mut out = Vec::new();
+            self.for_each_sublayer(|sublayer| {
+                out.push(SublayerInfo {
+                    key: sublayer.subLayerKey,
+                    name: display_name(&sublayer.displayData),
+                    provider_key: unsafe { sublayer.providerKey.as_ref().copied() },
+                    weight: sublayer.weight,
+                    ours: unsafe { sublayer.providerKey.as_ref() } == Some(&PROVIDER_KEY),
+                });
+            })?;
+            Ok(out)
+        })
+    }
+
+    fn for_each_sublayer(&self, visit: impl FnMut(&FWPM_SUBLAYER0)) -> Result<()> {
+        EnumHandle::open(
+            self.handle(),
+            "FwpmSubLayerCreateEnumHandle0",
+            |h| unsafe { FwpmSubLayerCreateEnumHandle0(self.handle(), None, h) },
+            |engine, h| unsafe { FwpmSubLayerDestroyEnumHandle0(engine, h) },
+        )?
+        .for_each(
+            "FwpmSubLayerEnum0",
+            |engine, h, entries, count| unsafe {
+                FwpmSubLayerEnum0(engine, h, 128, entries, count)
+            },
+            visit,
+        )
+    }
+
+    /// Engine-wide options. Options this version of Windows does not know
+    /// read as off.
+    pub fn engine_options(&self) -> Result<EngineOptions> {
+        self.reopening(|| {
+            let get = |option: FWPM_ENGINE_OPTION| -> Result<u32> {
+                let mut value = ptr::null_mut();
+                let status = unsafe { FwpmEngineGetOption0(self.handle(), option, &mut value) };
+                let value = unsafe { FwpBox::from_raw(value) };
+                match status {
+                    0 => Ok(value
+                        .as_ref()
+                        .filter(|v| v.r#type == FWP_UINT32)
+                        .map_or(0, |v| unsafe { v.Anonymous.uint32 })),
+                    s if s == FWP_E_INVALID_ENUMERATOR.0 as u32 => Ok(0),
+                    s => Err(WfpError::new("FwpmEngineGetOption0", s).into()),
+                }
+            };
+            Ok(EngineOptions {
+                collect_net_events: get(FWPM_ENGINE_COLLECT_NET_EVENTS)? != 0,
+                net_event_keywords: get(FWPM_ENGINE_NET_EVENT_MATCH_ANY_KEYWORDS)?,
+                name_cache: get(FWPM_ENGINE_NAME_CACHE)? != 0,
+                monitor_ipsec_connections: get(FWPM_ENGINE_MONITOR_IPSEC_CONNECTIONS)? != 0,
+                packet_queuing: get(FWPM_ENGINE_PACKET_QUEUING)?,
+                txn_watchdog_ms: get(FWPM_ENGINE_TXN_WATCHDOG_TIMEOUT_IN_MSEC)?,
+            })
+        })
+    }
+
+    /// Turns on net event collection with classify-allow events, so
+    /// allowed connections are recorded as well as drops. The other
+    /// keywords are left as they are.
+    pub fn record_allow_events(&self) -> Result<()> {
+        self.reopening(|| {
+            check_writable()?;
+            let keywords =
+                self.engine_options()?.net_event_keywords | FWPM_NET_EVENT_KEYWORD_CLASSIFY_ALLOW;
+            for (option, value) in [
+                (FWPM_ENGINE_COLLECT_NET_EVENTS, 1),
+                (FWPM_ENGINE_NET_EVENT_MATCH_ANY_KEYWORDS, keywords),
+            ] {
+                let value = FWP_VALUE0 {
+                    r#type: FWP_UINT32,
+                    Anonymous: FWP_VALUE0_0 { uint32: value },
+                };
+                let status = unsafe { FwpmEngineSetOption0(self.handle(), option, &value) };
+                if status != 0 {
+                    return Err(WfpError::new("FwpmEngineSetOption0", status).into());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Ports the system has reserved, such as the RPC endpoint mapper and
+    /// Teredo ports.
+    pub fn system_ports(&self) -> Result<Vec<SystemPorts>> {
+        self.reopening(|| {
+            let mut ports_ptr = ptr::null_mut();
+            let status = unsafe { FwpmSystemPortsGet0(self.handle(), &mut ports_ptr) };
+            let ports = unsafe { FwpBox::from_raw(ports_ptr) };
+            if status != 0 {
+                return Err(WfpError::new("FwpmSystemPortsGet0", status).into());
+            }
+            let Some(ports) = ports.as_ref() else {
+                return Ok(Vec::new());
+            };
+            let types = unsafe { slice_or_empty(ports.types, ports.numTypes) };
+            Ok(types
+                .iter()
+                .filter_map(|by_type| {
+                    let kind = match by_type.r#type {
+                        FWPM_SYSTEM_PORT_RPC_EPMAP => SystemPortKind::RpcEndpointMapper,
+                        FWPM_SYSTEM_PORT_TEREDO => SystemPortKind::Teredo,
+                        FWPM_SYSTEM_PORT_IPHTTPS_IN => SystemPortKind::IpHttpsIn,
+                        FWPM_SYSTEM_PORT_IPHTTPS_OUT => SystemPortKind::IpHttpsOut,
+                        _ => return None,
+                    };
+                    let ports = unsafe { slice_or_empty(by_type.ports, by_type.numPorts) };
+                    Some(SystemPorts {
+                        kind,
+                        ports: ports.to_vec(),
+                    })
+                })
+                .collect())
+        })
+    }
+
+    /// Connections currently protected by IPsec.
+    pub fn ipsec_connections(&self) -> Result<Vec<IpsecConnection>> {
+        self.reopening(|| {
+            let mut out = Vec::new();
+            EnumHandle::open(
+                self.handle(),
+                "FwpmConnectionCreateEnumHandle0",
+                |h| unsafe { FwpmConnectionCreateEnumHandle0(self.handle(), None, h) },
+                |engine, h| unsafe { FwpmConnectionDestroyEnumHandle0(engine, h) },
+            )?
+            .for_each(
+                "FwpmConnectionEnum0",
+                |engine, h, entries, count| unsafe {
+                    FwpmConnectionEnum0(engine, h, 128, entries, count)
+                },
+                |connection: &FWPM_CONNECTION0| out.push(decode_connection(connection)),
+            )?;
+            Ok(out)
+        })
+    }
+
+    /// Sends each IPsec connection added or deleted from now on to `events`
+    /// until the subscription is dropped. The session is kept open for it.
+    pub fn subscribe_ipsec_connections(
+        self,
+        events: Sender<IpsecEvent>,
+    ) -> Result<IpsecSubscription> {
+        let sender = Box::new(events);
+        let subscription = FWPM_CONNECTION_SUBSCRIPTION0::default();
+        let mut handle = HANDLE::default();
+        let status = unsafe {
+            FwpmConnectionSubscribe0(
+                self.handle(),
+                &subscription,
+                Some(on_connection_event),
+                Some(&*sender as *const Sender<IpsecEvent> as *const c_void),
+                &mut handle,
+            )
+        };
+        if status != 0 {
+            return Err(WfpError::new("FwpmConnectionSubscribe0", status).into());
+        }
+        Ok(IpsecSubscription {
+            engine: self,
+            handle,
+            _sender: sender,
+        })
+    }
+
+    /// Registered callouts, i.e. kernel drivers that can inspect traffic and
+    /// return verdicts from inside filters.
+    pub fn callouts(&self) -> Result<Vec<CalloutInfo>> {
+        self.reopening(|| {
+            let mut out = Vec::new();
+            EnumHandle::open(
+                self.handle(),
+                "FwpmCalloutCreateEnumHandle0",
+                |h| unsafe { FwpmCalloutCreateEnumHandle0(self.handle(), None, h) },
+                |engine, h| unsafe { FwpmCalloutDestroyEnumHandle0(engine, h) },
+            )?
+            .for_each(
+                "FwpmCalloutEnum0",
+                |engine, h, entries, count| unsafe {
+                    FwpmCalloutEnum0(engine, h, 128, entries, count)
+                },
+                |callout: &FWPM_CALLOUT0| {
+                    let provider_key = unsafe { callout.providerKey.as_ref().copied() };
+                    out.push(CalloutInfo {
+                        key: callout.calloutKey,
+                        id: callout.calloutId,
+                        name: display_name(&callout.displayData),
+                        description: display_description(&callout.displayData),
+                        provider_key,
+                        layer_key: callout.applicableLayer,
+                        registered: callout.flags & FWPM_CALLOUT_FLAG_REGISTERED != 0,
+                        ours: provider_key == Some(PROVIDER_KEY),
+                    })
+                },
+            )?;
+            Ok(out)
+        })
+    }
+
+    /// Classify drop and allow events still held by BFE, oldest first. Allow
+    /// events are only recorded when allow auditing is enabled on the
+    /// machine.
+    pub fn net_events(&self) -> Result<Vec<NetEvent>> {
+        self.query_net_events(&NetEventQuery::default())
+    }
+
+    /// Net events matching `query`. The time window and every condition BFE
+    /// can evaluate go into an enum template so the filtering happens in
+    /// BFE; the rest is applied to the results.
+    pub fn query_net_events(&self, query: &NetEventQuery) -> Result<Vec<NetEvent>> {
+        self.query_net_events_cancellable(query, &CancelToken::new())
+    }
+
+    /// [`Engine::query_net_events`] that stops between pages once `cancel`
+    /// is set, for long backfills.
+    pub fn query_net_events_cancellable(
+        &self,
+        query: &NetEventQuery,
+        cancel: &CancelToken,
+    ) -> Result<Vec<NetEvent>> {
+        self.reopening(|| {
+            let mut encoded = EncodedConditions::encode(&query.conditions())?;
+            let app_id = match query.app_path() {
+                Some(path) => Some(AppIdBlob::from_path(path)?),
+                None => None,
+            };
+            if let Some(blob) = &app_id {
+                encoded.conditions.push(blob.condition());
+            }
+            let template = FWPM_NET_EVENT_ENUM_TEMPLATE0 {
+                startTime: utc_to_filetime(query.range.from.unwrap_or(DateTime::UNIX_EPOCH)),
+                endTime: utc_to_filetime(query.range.to.unwrap_or_else(Utc::now)),
+                numFilterConditions: encoded.conditions.len() as u32,
+                filterCondition: if encoded.conditions.is_empty() {
+                    ptr::null_mut()
+                } else {
+                    encoded.conditions.as_mut_ptr()
+                },
+            };
+
+            let mut out = Vec::new();
+            EnumHandle::open(
+                self.handle(),
+                "FwpmNetEventCreateEnumHandle0",
+                |h| unsafe { FwpmNetEventCreateEnumHandle0(self.handle(), Some(&template), h) },
+                |engine, h| unsafe { FwpmNetEventDestroyEnumHandle0(engine, h) },
+            )?
+            .for_each_until(
+                "FwpmNetEventEnum2",
+                |engine, h, entries, count| unsafe {
+                    FwpmNetEventEnum2(engine, h, 256, entries, count)
+                },
+                cancel,
+                |entry: &FWPM_NET_EVENT2| {
+                    if let Some(event) = unsafe { decode_net_event(entry) } {
+                        if query.matches(&event) {
+                            out.push(event);
+                        }
+                    }
+                },
+            )?;
+            Ok(out)
+        })
+    }
+}
+
+/// A live IPsec connection subscription; dropping it unsubscribes.
+pub struct IpsecSubscription {
+    engine: Engine,
+    handle: HANDLE,
+    /// Handed to BFE as the callback context, so it must outlive the
+    /// subscription.
+    _sender: Box<Sender<IpsecEvent>>,
+}
+
+impl Drop for IpsecSubscription {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = FwpmConnectionUnsubscribe0(self.engine.handle(), self.handle);
+        }
+    }
+}
+
+unsafe extern "system" fn on_connection_event(
+    context: *mut c_void,
+    event_type: FWPM_CONNECTION_EVENT_TYPE,
+    connection: *const FWPM_CONNECTION0,
+) {
+    let sender = &*(context as *const Sender<IpsecEvent>);
+    let Some(connection) = connection.as_ref() else {
+        return;
+    };
+    let event = match event_type {
+        FWPM_CONNECTION_EVENT_ADD => IpsecEvent::Added(decode_connection(connection)),
+        FWPM_CONNECTION_EVENT_DELETE => IpsecEvent::Deleted(connection.connectionId),
+        _ => return,
+    };
+    // The receiver may be gone while unsubscribing.
+    let _ = sender.send(event);
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = FwpmEngineClose0(self.handle());
+            let _ = CloseHandle(self.handle());
+        }
+    }
+}
+
+impl NetEventQuery {
+    /// Conditions BFE evaluates in the enum template. IPv6 addresses are
+    /// left to [`NetEventQuery::matches`] since the condition model has no
+    /// 16-byte array value.
+    fn conditions(&self) -> Vec<Condition> {
+        let mut conditions = Vec::new();
+        if let Some(proto) = self.protocol {
+            conditions.push(Condition::equal(
+                ConditionField::IpProtocol,
+                ConditionValue::Uint8(proto),
+            ));
+        }
+        if let Some(port) = self.local_port {
+            conditions.push(Condition::equal(
+                ConditionField::LocalPort,
+                ConditionValue::Uint16(port),
+            ));
+        }
+        if let Some(port) = self.remote_port {
+            conditions.push(Condition::equal(
+                ConditionField::RemotePort,
+                ConditionValue::Uint16(port),
+            ));
+        }
+        if let Some(IpAddr::V4(addr)) = self.remote_address {
+            conditions.push(Condition::equal(
+                ConditionField::RemoteAddress,
+                ConditionValue::Uint32(u32::from(addr)),
+            ));
+        }
+        conditions
+    }
+}
+
+/// App ID of the executable at `path` as BFE matches it: its NT device path,
+/// lowercased, as a UTF-16 blob. A device path, as exports carry, is taken
+/// as it is, since BFE cannot open the file by it.
+pub fn app_id(path: &str) -> Result<ConditionValue> {
+    if path.to_ascii_lowercase().starts_with(r"\device\") {
+        let bytes = path
+            .to_lowercase()
+            .encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        return Ok(ConditionValue::ByteBlob(bytes));
+    }
+    let blob = AppIdBlob::from_path(path)?;
+    let bytes = blob.0.as_ref().map(|b| unsafe { blob_bytes(b) });
+    Ok(ConditionValue::ByteBlob(bytes.unwrap_or_default()))
+}
+
+/// App ID blob allocated by BFE for an executable path.
+struct AppIdBlob(FwpBox<FWP_BYTE_BLOB>);
+
+impl AppIdBlob {
+    fn from_path(path: &str) -> Result<Self> {
+        let wide = U16CString::from_str(path)?;
+        let mut blob: *mut FWP_BYTE_BLOB = ptr::null_mut();
+        let status = unsafe { FwpmGetAppIdFromFileName0(PCWSTR(wide.as_ptr()), &mut blob) };
+        if status != 0 {
+            return Err(anyhow!(
+                "FwpmGetAppIdFromFileName0 failed for {path}: 0x{status:08X}"
+            ));
+        }
+        Ok(Self(unsafe { FwpBox::from_raw(blob) }))
+    }
+
+    fn condition(&self) -> FWPM_FILTER_CONDITION0 {
+        FWPM_FILTER_CONDITION0 {
+            fieldKey: FWPM_CONDITION_ALE_APP_ID,
+            matchType: FWP_MATCH_EQUAL,
+            conditionValue: FWP_CONDITION_VALUE0 {
+                r#type: FWP_BYTE_BLOB_TYPE,
+                Anonymous: FWP_CONDITION_VALUE0_0 {
+                    byteBlob: self.0.as_ptr(),
+                },
+            },
+        }
+    }
+}
+
+/// `msFwpDirection` values reported in classify events.
+const FWP_DIRECTION_IN: u32 = 0x3900;
+const FWP_DIRECTION_OUT: u32 = 0x3901;
+
+/// Seconds between the FILETIME epoch (1601) and the Unix epoch.
+const FILETIME_UNIX_OFFSET: i64 = 11_644_473_600;
+
+fn decode_connection(connection: &FWPM_CONNECTION0) -> IpsecConnection {
+    let (local, remote) = unsafe {
+        if connection.ipVersion == FWP_IP_VERSION_V6 {
+            (
+                IpAddr::V6(Ipv6Addr::from(connection.Anonymous1.localV6Address)),
+                IpAddr::V6(Ipv6Addr::from(connection.Anonymous2.remoteV6Address)),
+            )
+        } else {
+            (
+                IpAddr::V4(Ipv4Addr::from(connection.Anonymous1.localV4Address)),
+                IpAddr::V4(Ipv4Addr::from(connection.Anonymous2.remoteV4Address)),
+            )
+        }
+    };
+    let crypto = &connection.mmCrypto;
+    let cipher = match crypto.cipherAlgorithm.algoIdentifier {
+        IKEEXT_CIPHER_DES => "DES",
+        IKEEXT_CIPHER_3DES => "3DES",
+        IKEEXT_CIPHER_AES_128 => "AES-128",
+        IKEEXT_CIPHER_AES_192 => "AES-192",
+        IKEEXT_CIPHER_AES_256 => "AES-256",
+        IKEEXT_CIPHER_AES_GCM_128_16ICV => "AES-GCM-128",
+        IKEEXT_CIPHER_AES_GCM_256_16ICV => "AES-GCM-256",
+        _ => "unknown cipher",
+    };
+    let integrity = match crypto.integrityAlgorithm.algoIdentifier {
+        IKEEXT_INTEGRITY_MD5 => "MD5",
+        IKEEXT_INTEGRITY_SHA1 => "SHA-1",
+        IKEEXT_INTEGRITY_SHA_256 => "SHA-256",
+        IKEEXT_INTEGRITY_SHA_384 => "SHA-384",
+        _ => "unknown integrity",
+    };
+    let dh = match crypto.dhGroup {
+        IKEEXT_DH_GROUP_NONE => "no DH",
+        IKEEXT_DH_GROUP_1 => "DH1",
+        IKEEXT_DH_GROUP_2 => "DH2",
+        IKEEXT_DH_GROUP_14 => "DH14",
+        IKEEXT_DH_ECP_256 => "ECP-256",
+        IKEEXT_DH_ECP_384 => "ECP-384",
+        IKEEXT_DH_GROUP_24 => "DH24",
+        _ => "unknown DH",
+    };
+    let main_auth = auth_method_name(connection.mmPeer.authenticationMethodType);
+    // Only AuthIP has an extended mode.
+    let peer_auth = if connection.keyModuleType != IKEEXT_KEY_MODULE_AUTHIP {
+        main_auth.to_string()
+    } else {
+        format!(
+            "{main_auth}, then {}",
+            auth_method_name(connection.emPeer.authenticationMethodType)
+        )
+    };
+    IpsecConnection {
+        id: connection.connectionId,
+        local,
+        remote,
+        tunnel: connection.ipsecTrafficModeType == IPSEC_TRAFFIC_TYPE_TUNNEL,
+        key_module: match connection.keyModuleType {
+            IKEEXT_KEY_MODULE_IKE => "IKE",
+            IKEEXT_KEY_MODULE_AUTHIP => "AuthIP",
+            IKEEXT_KEY_MODULE_IKEV2 => "IKEv2",
+            _ => "unknown",
+        },
+        main_mode: format!("{cipher} / {integrity} / {dh}"),
+        peer_auth,
+        bytes_in: connection.bytesTransferredIn,
+        bytes_out: connection.bytesTransferredOut,
+        started: filetime_to_utc(connection.startSysTime),
+    }
+}
+
+fn auth_method_name(method: IKEEXT_AUTHENTICATION_METHOD_TYPE) -> &'static str {
+    match method {
+        IKEEXT_PRESHARED_KEY => "pre-shared key",
+        IKEEXT_CERTIFICATE => "certificate",
+        IKEEXT_KERBEROS => "Kerberos",
+        IKEEXT_ANONYMOUS => "anonymous",
+        IKEEXT_SSL => "SSL",
+        IKEEXT_NTLM_V2 => "NTLMv2",
+        IKEEXT_IPV6_CGA => "IPv6 CGA",
+        IKEEXT_CERTIFICATE_ECDSA_P256 | IKEEXT_CERTIFICATE_ECDSA_P384 => "ECDSA certificate",
+        IKEEXT_SSL_ECDSA_P256 | IKEEXT_SSL_ECDSA_P384 => "ECDSA SSL",
+        IKEEXT_EAP => "EAP",
+        _ => "unknown",
+    }
+}
+
+unsafe fn decode_net_event(event: &FWPM_NET_EVENT2) -> Option<NetEvent> {
+    let (kind, filter_id, layer_id, direction) = match event.r#type {
+        FWPM_NET_EVENT_TYPE_CLASSIFY_DROP => {
+            let drop = event.Anonymous.classifyDrop.as_ref()?;
+            (
+                NetEventKind::Drop,
+                drop.filterId,
+                drop.layerId,
+                drop.msFwpDirection,
+            )
+        }
+        FWPM_NET_EVENT_TYPE_CLASSIFY_ALLOW => {
+            let allow = event.Anonymous.classifyAllow.as_ref()?;
+            (
+                NetEventKind::Allow,
+                allow.filterId,
+                allow.layerId,
+                allow.msFwpDirection,
+            )
+        }
+        _ => return None,
+    };
+
+    let header = &event.header;
+    let has = |flag: u32| header.flags & flag != 0;
+    let v6 = header.ipVersion == FWP_IP_VERSION_V6;
+    let address = |v4: u32, v6_bytes: [u8; 16]| -> IpAddr {
+        if v6 {
+            IpAddr::V6(Ipv6Addr::from(v6_bytes))
+        } else {
+            IpAddr::V4(Ipv4Addr::from(v4))
+        }
+    };
+
+    Some(NetEvent {
+        time: filetime_to_utc(header.timeStamp),
+        kind,
+        filter_id,
+        layer_id,
+        direction: match direction {
+            FWP_DIRECTION_IN => Some(FlowDirection::Inbound),
+            FWP_DIRECTION_OUT => Some(FlowDirection::Outbound),
+            _ => None,
+        },
+        protocol: has(FWPM_NET_EVENT_FLAG_IP_PROTOCOL_SET).then_some(header.ipProtocol),
+        local_addr: has(FWPM_NET_EVENT_FLAG_LOCAL_ADDR_SET).then(|| {
+            address(
+                header.Anonymous1.localAddrV4,
+                header.Anonymous1.localAddrV6.byteArray16,
+            )
+        }),
+        local_port: has(FWPM_NET_EVENT_FLAG_LOCAL_PORT_SET).then_some(header.localPort),
+        remote_addr: has(FWPM_NET_EVENT_FLAG_REMOTE_ADDR_SET).then(|| {
+            address(
+                header.Anonymous2.remoteAddrV4,
+                header.Anonymous2.remoteAddrV6.byteArray16,
+            )
+        }),
+        remote_port: has(FWPM_NET_EVENT_FLAG_REMOTE_PORT_SET).then_some(header.remotePort),
+        app_id: if has(FWPM_NET_EVENT_FLAG_APP_ID_SET) {
+            decode_app_id(&header.appId)
+        } else {
+            None
+        },
+    })
+}
+
+pub fn filetime_to_utc(time: FILETIME) -> DateTime<Utc> {
+    let ticks = (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+    let secs = (ticks / 10_000_000) as i64 - FILETIME_UNIX_OFFSET;
+    let nanos = (ticks % 10_000_000) as u32 * 100;
+    DateTime::from_timestamp(secs, nanos).unwrap_or_default()
+}
+
+fn utc_to_filetime(time: DateTime<Utc>) -> FILETIME {
+    let secs = (time.timestamp() + FILETIME_UNIX_OFFSET).max(0) as u64;
+    let ticks = secs * 10_000_000 + u64::from(time.timestamp_subsec_nanos() / 100);
+    FILETIME {
+        dwLowDateTime: ticks as u32,
+        dwHighDateTime: (ticks >> 32) as u32,
+    }
+}
+
+/// App IDs are NUL-terminated UTF-16 device paths stored in a byte blob.
+unsafe fn decode_app_id(blob: &FWP_BYTE_BLOB) -> Option<String> {
+    if blob.data.is_null() || blob.size < 2 {
+        return None;
+    }
+    let units = std::slice::from_raw_parts(blob.data as *const u16, blob.size as usize / 2);
+    let end = units.iter().position(|&u| u == 0).unwrap_or(units.len());
+    Some(String::from_utf16_lossy(&units[..end]))
+}
+
+/// Decodes a filter. Layer, sublayer and provider names are left empty
+/// until [`FilterNames::apply`] fills them in.
+fn summarize_filter(filter: &FWPM_FILTER0) -> FilterSummary {
+    let name = wide_string(filter.displayData.name).unwrap_or_else(|| String::from("<no name>"));
+
+    let provider_key = if filter.providerKey.is_null() {
+        None
+    } else {
+        Some(unsafe { *filter.providerKey })
+    };
+
+    let action = match filter.action.r#type {
+        FWP_ACTION_PERMIT => WfpAction::Permit,
+        FWP_ACTION_BLOCK => WfpAction::Block,
+        _ => WfpAction::Callout,
+    };
+    let callout = CalloutKind::from_fwp(filter.action.r#type).map(|kind| CalloutAction {
+        key: unsafe { filter.action.Anonymous.calloutKey },
+        kind,
+    });
+
+    let conditions: Vec<Condition> = if filter.filterCondition.is_null() {
+        Vec::new()
+    } else {
+        unsafe {
+            std::slice::from_raw_parts(filter.filterCondition, filter.numFilterConditions as usize)
+                .iter()
+                .map(|cond| decode_condition(cond))
+                .collect()
+        }
+    };
+    let remote_port = remote_port(&conditions);
+
+    let owned = is_owned(filter);
+
+    FilterSummary {
+        id: filter.filterId,
+        key: filter.filterKey,
+        rule_key: if owned { rule_tag(filter) } else { None },
+        name,
+        layer: String::new(),
+        layer_key: filter.layerKey,
+        sublayer: String::new(),
+        sublayer_key: filter.subLayerKey,
+        provider: String::new(),
+        provider_key,
+        action,
+        remote_port,
+        conditions,
+        weight: decode_weight(&filter.weight),
+        weight_kind: match filter.weight.r#type {
+            FWP_UINT8 => WeightKind::Range(unsafe { filter.weight.Anonymous.uint8 }),
+            FWP_UINT64 => WeightKind::Exact,
+            _ => WeightKind::Auto,
+        },
+        effective_weight: decode_weight(&filter.effectiveWeight),
+        boot_time: filter.flags.0 & FWPM_FILTER_FLAG_BOOTTIME.0 != 0,
+        persistent: filter.flags.0 & FWPM_FILTER_FLAG_PERSISTENT.0 != 0,
+        clear_action_right: filter.flags.0 & FWPM_FILTER_FLAG_CLEAR_ACTION_RIGHT.0 != 0,
+        indexed: filter.flags.0 & FWPM_FILTER_FLAG_INDEXED.0 != 0,
+        owned_by_app: owned,
+        callout,
+        provider_context_key: (filter.flags.0 & FWPM_FILTER_FLAG_HAS_PROVIDER_CONTEXT.0 != 0)
+            .then_some(unsafe { filter.Anonymous.providerContextKey }),
+    }
+}
+
+/// Reads a filter weight. BFE reports weights it assigned as UINT64; an
+/// explicit UINT8 weight is a 0–15 band selector.
+fn decode_weight(weight: &FWP_VALUE0) -> Option<u64> {
+    unsafe {
+        match weight.r#type {
+            FWP_UINT64 if !weight.Anonymous.uint64.is_null() => Some(*weight.Anonymous.uint64),
+            FWP_UINT8 => Some(u64::from(weight.Anonymous.uint8)),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the rule key stored in an owned filter's providerData by
+/// [`FilterBuilder::rule`].
+fn rule_tag(filter: &FWPM_FILTER0) -> Option<GUID> {
+    let blob = &filter.providerData;
+    if blob.size != 16 || blob.data.is_null() {
+        return None;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(blob.data, 16) };
+    Uuid::from_slice(bytes).ok().map(guid_from_uuid)
+}
+
+fn is_owned(filter: &FWPM_FILTER0) -> bool {
+    !filter.providerKey.is_null() && unsafe { *filter.providerKey } == PROVIDER_KEY
+}
+
+/// Copies a NUL-terminated string owned by BFE; `None` when null.
+fn wide_string(s: PWSTR) -> Option<String> {
+    if s.is_null() {
+        None
+    } else {
+        Some(unsafe { U16CStr::from_ptr_str(s.0) }.to_string_lossy())
+    }
+}
+
+fn display_name(display: &FWPM_DISPLAY_DATA0) -> String {
+    wide_string(display.name).unwrap_or_else(|| String::from("<unnamed>"))
+}
+
+fn display_description(display: &FWPM_DISPLAY_DATA0) -> Option<String> {
+    wide_string(display.description)
+}
+
+/// Whether `err` came from a call on a session whose RPC connection broke.
+fn is_session_lost(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<WfpError>()
+        .is_some_and(WfpError::is_session_lost)
+}
+
+/// Starts a transaction; every change goes through one, so this is also
+/// where read-only mode refuses them.
+fn begin_transaction(handle: HANDLE) -> Result<()> {
+    check_writable()?;
+    let status = unsafe { FwpmTransactionBegin0(handle, 0) };
+    match WfpError::new("FwpmTransactionBegin0", status) {
+        _ if status == 0 => Ok(()),
+        err if err.is_timeout() => Err(anyhow::Error::new(err).context(
+            "Another WFP session is holding a transaction; the Sessions view shows who is connected",
+        )),
+        err => Err(err.into()),
+    }
+}
+
+fn finish_transaction<T>(handle: HANDLE, result: Result<T>) -> Result<T> {
+    match result {
+        Ok(value) => {
+            let status = unsafe { FwpmTransactionCommit0(handle) };
+            if status != 0 {
+                Err(WfpError::new("FwpmTransactionCommit0", status).into())
+            } else {
+                Ok(value)
+            }
+        }
+        Err(e) => {
+            abort_transaction(handle);
+            Err(e)
+        }
+    }
+}
+
+fn abort_transaction(handle: HANDLE) {
+    let _ = unsafe { FwpmTransactionAbort0(handle) };
+}
+
+/// A single object allocated by BFE, freed with `FwpmFreeMemory0` on drop.
+struct FwpBox<T>(*mut T);
+
+impl<T> FwpBox<T> {
+    /// # Safety
+    /// `ptr` must be null or an allocation BFE handed to the caller that
+    /// nothing else frees.
+    unsafe fn from_raw(ptr: *mut T) -> Self {
+        Self(ptr)
+    }
+
+    fn as_ref(&self) -> Option<&T> {
+        unsafe { self.0.as_ref() }
+    }
+
+    fn as_ptr(&self) -> *mut T {
+        self.0
+    }
+}
+
+impl<T> Drop for FwpBox<T> {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            let mut ptr = self.0.cast::<c_void>();
+            unsafe { FwpmFreeMemory0(&mut ptr) };
+        }
+    }
+}
+
+/// A page of entries from one of the `Fwpm*Enum` calls. BFE allocates the
+/// pointer array and the entries in one block, freed together on drop.
+struct FwpArray<T> {
+    ptr: *mut *mut T,
+    len: usize,
+}
+
+impl<T> FwpArray<T> {
+    /// # Safety
+    /// `ptr` must be null or an array of `len` entry pointers returned by
+    /// BFE that nothing else frees.
+    unsafe fn from_raw(ptr: *mut *mut T, len: usize) -> Self {
+        Self {
+            ptr,
+            len: if ptr.is_null() { 0 } else { len },
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        let entries: &[*mut T] = if self.ptr.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+        };
+        entries
+            .iter()
+            .filter_map(|&entry| unsafe { entry.as_ref() })
+    }
+}
+
+impl<T> Drop for FwpArray<T> {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            let mut ptr = self.ptr.cast::<c_void>();
+            unsafe { FwpmFreeMemory0(&mut ptr) };
+        }
+    }
+}
+
+/// An open `Fwpm*CreateEnumHandle0` handle, destroyed on drop so early
+/// returns cannot leak it.
+struct EnumHandle {
+    engine: HANDLE,
+    handle: HANDLE,
+    destroy: fn(HANDLE, HANDLE) -> u32,
+}
+
+impl EnumHandle {
+    fn open(
+        engine: HANDLE,
+        call: &'static str,
+        create: impl FnOnce(&mut HANDLE) -> u32,
+        destroy: fn(HANDLE, HANDLE) -> u32,
+    ) -> Result<Self> {
+        let mut handle = HANDLE::default();
+        let status = create(&mut handle);
+        if status != 0 {
+            return Err(WfpError::new(call, status).into());
+        }
+        Ok(Self {
+            engine,
+            handle,
+            destroy,
+        })
+    }
+
+    /// Pages through the enumeration with `next_page`, which wraps the
+    /// matching `Fwpm*Enum` call, handing each entry to `visit` while its
+    /// page is still allocated.
+    fn for_each<T>(
+        &self,
+        call: &'static str,
+        next_page: impl FnMut(HANDLE, HANDLE, *mut *mut *mut T, *mut u32) -> u32,
+        visit: impl FnMut(&T),
+    ) -> Result<()> {
+        self.for_each_until(call, next_page, &CancelToken::new(), visit)
+    }
+
+    /// [`EnumHandle::for_each`] that checks `cancel` before every page.
+    fn for_each_until<T>(
+        &self,
+        call: &'static str,
+        mut next_page: impl FnMut(HANDLE, HANDLE, *mut *mut *mut T, *mut u32) -> u32,
+        cancel: &CancelToken,
+        mut visit: impl FnMut(&T),
+    ) -> Result<()> {
+        loop {
+            cancel.check()?;
+            let page = self.next_page(call, &mut next_page)?;
+            if page.is_empty() {
+                return Ok(());
+            }
+            page.iter().for_each(&mut visit);
+        }
+    }
+
+    /// The next page of the enumeration; empty once it is exhausted.
+    fn next_page<T>(
+        &self,
+        call: &'static str,
+        next_page: impl FnOnce(HANDLE, HANDLE, *mut *mut *mut T, *mut u32) -> u32,
+    ) -> Result<FwpArray<T>> {
+        let mut entries = ptr::null_mut();
+        let mut count = 0u32;
+        let status = next_page(self.engine, self.handle, &mut entries, &mut count);
+        let page = unsafe { FwpArray::from_raw(entries, count as usize) };
+        if status != 0 {
+            return Err(WfpError::new(call, status).into());
+        }
+        Ok(page)
+    }
+}
+
+impl Drop for EnumHandle {
+    fn drop(&mut self) {
+        let _ = (self.destroy)(self.engine, self.handle);
+    }
+}
+
+/// Filters decoded one enumeration page at a time, from
+/// [`Engine::filters_iter`].
+struct FilterPages {
+    /// `None` once the enumeration is finished or has failed.
+    handle: Option<EnumHandle>,
+    page: std::vec::IntoIter<FilterSummary>,
+    names: FilterNames,
+}
+
+impl Iterator for FilterPages {
+    type Item = Result<FilterSummary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(mut filter) = self.page.next() {
+                self.names.apply(&mut filter);
+                return Some(Ok(filter));
+            }
+            let page = self.handle.as_ref()?.next_page(
+                "FwpmFilterEnum0",
+                |engine, h, entries, count| unsafe {
+                    FwpmFilterEnum0(engine, h, 128, entries, count)
+                },
+            );
+            match page {
+                Ok(page) if !page.is_empty() => {
+                    self.page = page
+                        .iter()
+                        .map(summarize_filter)
+                        .collect::<Vec<_>>()
+                        .into_iter();
+                }
+                Ok(_) => {
+                    self.handle = None;
+                    return None;
+                }
+                Err(err) => {
+                    self.handle = None;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+/// `CurrentBuild.UBR` of this machine, e.g. `22631.3880`.
+fn os_build() -> Option<String> {
+    let key = w!(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion");
+    let mut buf = [0u16; 32];
+    let mut size = std::mem::size_of_val(&buf) as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            key,
+            w!("CurrentBuild"),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buf.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+    };
+    status.ok().ok()?;
+    // The size includes the terminating null.
+    let build = String::from_utf16_lossy(&buf[..(size as usize / 2).saturating_sub(1)]);
+    let mut ubr = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            key,
+            w!("UBR"),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(ptr::addr_of_mut!(ubr).cast()),
+            Some(&mut size),
+        )
+    };
+    Some(match status.ok() {
+        Ok(()) => format!("{build}.{ubr}"),
+        Err(_) => build,
+    })
+}
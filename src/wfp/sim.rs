@@ -0,0 +1,1047 @@
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc::Sender, Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+
+use super::*;
+
+const FWPM_NET_EVENT_KEYWORD_CLASSIFY_ALLOW: u32 = 16;
+const FWP_E_CALLOUT_NOT_FOUND: u32 = 0x8032_0001;
+const FWP_E_FILTER_NOT_FOUND: u32 = 0x8032_0003;
+const FWP_E_LAYER_NOT_FOUND: u32 = 0x8032_0004;
+const FWP_E_ALREADY_EXISTS: u32 = 0x8032_0009;
+const FWP_E_PROVIDER_CONTEXT_NOT_FOUND: u32 = 0x8032_0006;
+const FWP_E_SUBLAYER_NOT_FOUND: u32 = 0x8032_0007;
+
+/// Stand-in for `windows::core::GUID` on hosts without the Windows API.
+#[cfg(not(windows))]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct GUID {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+#[cfg(not(windows))]
+impl GUID {
+    pub const fn from_values(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Self {
+        Self {
+            data1,
+            data2,
+            data3,
+            data4,
+        }
+    }
+
+    pub const fn from_u128(uuid: u128) -> Self {
+        Self {
+            data1: (uuid >> 96) as u32,
+            data2: (uuid >> 80 & 0xffff) as u16,
+            data3: (uuid >> 64 & 0xffff) as u16,
+            data4: (uuid as u64).to_be_bytes(),
+        }
+    }
+
+    pub const fn to_u128(&self) -> u128 {
+        ((self.data1 as u128) << 96)
+            + ((self.data2 as u128) << 80)
+            + ((self.data3 as u128) << 64)
+            + u64::from_be_bytes(self.data4) as u128
+    }
+}
+
+/// Same format as `windows::core::GUID`, so labels match on every host.
+#[cfg(not(windows))]
+impl std::fmt::Debug for GUID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:X}", Uuid::from_u128(self.to_u128()).hyphenated())
+    }
+}
+
+// Well-known keys from fwpmu.h, for hosts without the Windows API.
+#[cfg(not(windows))]
+pub const FWPM_LAYER_ALE_AUTH_CONNECT_V4: GUID =
+    GUID::from_u128(0xc38d57d1_05a7_4c33_904f_7fbceee60e82);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_ALE_AUTH_CONNECT_V6: GUID =
+    GUID::from_u128(0x4a72393b_319f_44bc_84c3_ba54dcb3b6b4);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_ALE_AUTH_LISTEN_V4: GUID =
+    GUID::from_u128(0x88bb5dad_76d7_4227_9c71_df0a3ed7be7e);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_ALE_AUTH_LISTEN_V6: GUID =
+    GUID::from_u128(0x7ac9de24_17dd_4814_b4bd_a9fbc95a321b);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4: GUID =
+    GUID::from_u128(0xe1cd9fe7_f4b5_4273_96c0_592e487b8650);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6: GUID =
+    GUID::from_u128(0xa3b42c97_9f04_4672_b87e_cee9c483257f);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_ALE_FLOW_ESTABLISHED_V4: GUID =
+    GUID::from_u128(0xaf80470a_5596_4c13_9992_539e6fe57967);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_ALE_FLOW_ESTABLISHED_V6: GUID =
+    GUID::from_u128(0x7021d2b3_dfa4_406e_afeb_6afaf7e70efd);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_INBOUND_TRANSPORT_V4: GUID =
+    GUID::from_u128(0x5926dfc8_e3cf_4426_a283_dc393f5d0f9d);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_INBOUND_TRANSPORT_V6: GUID =
+    GUID::from_u128(0x634a869f_fc23_4b90_b0c1_bf620a36ae6f);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_OUTBOUND_TRANSPORT_V4: GUID =
+    GUID::from_u128(0x09e61aea_d214_46e2_9b21_b26b0b2f28c8);
+#[cfg(not(windows))]
+pub const FWPM_LAYER_OUTBOUND_TRANSPORT_V6: GUID =
+    GUID::from_u128(0xe1735bde_013f_4655_b351_a49e15762df0);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_IP_PROTOCOL: GUID =
+    GUID::from_u128(0x3971ef2b_623e_4f9a_8cb1_6e79b806b9a7);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_IP_REMOTE_PORT: GUID =
+    GUID::from_u128(0xc35a604d_d22b_4e1a_91b4_68f674ee674b);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_IP_LOCAL_PORT: GUID =
+    GUID::from_u128(0x0c1ba1af_5765_453f_af22_a8f791ac775b);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_IP_REMOTE_ADDRESS: GUID =
+    GUID::from_u128(0xb235ae9a_1d64_49b8_a44c_5ff3d9095045);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_IP_LOCAL_ADDRESS: GUID =
+    GUID::from_u128(0xd9ee00de_c1ef_4617_bfe3_ffd8f5a08957);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_ALE_APP_ID: GUID = GUID::from_u128(0xd78e1e87_8644_4ea5_9437_d809ecefc971);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_ALE_USER_ID: GUID =
+    GUID::from_u128(0xaf043a0a_b34d_4f86_979c_c90371af6e66);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_IP_LOCAL_INTERFACE: GUID =
+    GUID::from_u128(0x4cd62a49_59c3_4969_b7f3_bda5d32890a4);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_FLAGS: GUID = GUID::from_u128(0x632ce23b_5167_435c_86d7_e903684aa80c);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_DIRECTION: GUID = GUID::from_u128(0x8784c146_ca97_44d6_9fd1_19fb1840cbf7);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_INTERFACE_TYPE: GUID =
+    GUID::from_u128(0xdaf8cd14_e09e_4c93_a5ae_c5c13b73ffca);
+#[cfg(not(windows))]
+pub const FWPM_CONDITION_TUNNEL_TYPE: GUID =
+    GUID::from_u128(0x77a40437_8779_4868_a261_f5a902f1c0cd);
+
+/// Layers the simulated BFE reports, with their display names on Windows.
+const LAYERS: [(GUID, &str); 12] = [
+    (FWPM_LAYER_ALE_AUTH_CONNECT_V4, "ALE Connect v4 Layer"),
+    (FWPM_LAYER_ALE_AUTH_CONNECT_V6, "ALE Connect v6 Layer"),
+    (FWPM_LAYER_ALE_AUTH_LISTEN_V4, "ALE Listen v4 Layer"),
+    (FWPM_LAYER_ALE_AUTH_LISTEN_V6, "ALE Listen v6 Layer"),
+    (
+        FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+        "ALE Receive/Accept v4 Layer",
+    ),
+    (
+        FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+        "ALE Receive/Accept v6 Layer",
+    ),
+    (
+        FWPM_LAYER_ALE_FLOW_ESTABLISHED_V4,
+        "ALE Flow Established v4 Layer",
+    ),
+    (
+        FWPM_LAYER_ALE_FLOW_ESTABLISHED_V6,
+        "ALE Flow Established v6 Layer",
+    ),
+    (
+        FWPM_LAYER_INBOUND_TRANSPORT_V4,
+        "Inbound Transport v4 Layer",
+    ),
+    (
+        FWPM_LAYER_INBOUND_TRANSPORT_V6,
+        "Inbound Transport v6 Layer",
+    ),
+    (
+        FWPM_LAYER_OUTBOUND_TRANSPORT_V4,
+        "Outbound Transport v4 Layer",
+    ),
+    (
+        FWPM_LAYER_OUTBOUND_TRANSPORT_V6,
+        "Outbound Transport v6 Layer",
+    ),
+];
+
+/// What BFE holds for one simulated machine.
+#[derive(Clone, Default)]
+struct Machine {
+    filters: Vec<FilterSummary>,
+    last_id: u64,
+    providers: Vec<NamedGuid>,
+    sublayers: Vec<SublayerInfo>,
+    sessions: Vec<SessionInfo>,
+    provider_contexts: Vec<ProviderContextInfo>,
+    callouts: Vec<CalloutInfo>,
+    /// `FWPM_NET_EVENT_KEYWORD_*` flags turned on.
+    net_event_keywords: u32,
+    /// Filters added by dynamic sessions, with the session that owns each.
+    dynamic_filters: Vec<(GUID, GUID)>,
+}
+
+/// Simulated machines by lowercase host name; `""` is this machine. They
+/// live until the process exits.
+static MACHINES: Mutex<BTreeMap<String, Machine>> = Mutex::new(BTreeMap::new());
+
+fn machines() -> MutexGuard<'static, BTreeMap<String, Machine>> {
+    MACHINES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Machine {
+    fn filter(&self, key: GUID) -> Option<&FilterSummary> {
+        self.filters.iter().find(|f| f.key == key)
+    }
+
+    fn install(&mut self, builder: &FilterBuilder) -> Result<u64> {
+        builder.validate()?;
+        if builder.sublayer != SUBLAYER_KEY
+            && !self.sublayers.iter().any(|s| s.key == builder.sublayer)
+        {
+            return Err(WfpError::new("FwpmFilterAdd0", FWP_E_SUBLAYER_NOT_FOUND).into());
+        }
+        if builder
+            .provider_context
+            .is_some_and(|key| !self.provider_contexts.iter().any(|c| c.key == key))
+        {
+            return Err(WfpError::new("FwpmFilterAdd0", FWP_E_PROVIDER_CONTEXT_NOT_FOUND).into());
+        }
+        if builder
+            .callout
+            .is_some_and(|callout| !self.callouts.iter().any(|c| c.key == callout.key))
+        {
+            return Err(WfpError::new("FwpmFilterAdd0", FWP_E_CALLOUT_NOT_FOUND).into());
+        }
+        if self.filter(builder.key).is_some() {
+            return Err(WfpError::new("FwpmFilterAdd0", FWP_E_ALREADY_EXISTS).into());
+        }
+        self.last_id += 1;
+        let mut filter = builder.to_summary(self.last_id);
+        filter.weight = Some(builder.weight.unwrap_or_else(|| builder.tier().top()));
+        filter.weight_kind = WeightKind::Exact;
+        filter.effective_weight = filter.weight;
+        self.filters.push(filter);
+        Ok(self.last_id)
+    }
+
+    /// Removes every owned filter making up the rule `key`, as
+    /// `Engine::remove_rule_inner` does against BFE.
+    fn remove_rule(&mut self, key: GUID) -> Result<usize> {
+        if self.filter(key).is_some_and(|f| !f.owned_by_app) {
+            return Err(anyhow!(
+                "Filter {} is not managed by this application",
+                uuid_from_guid(key)
+            ));
+        }
+        let before = self.filters.len();
+        self.filters
+            .retain(|f| !(f.owned_by_app && (f.key == key || f.rule_key == Some(key))));
+        Ok(before - self.filters.len())
+    }
+
+    fn weight_allocator(&self) -> WeightAllocator {
+        let mut allocator = WeightAllocator::default();
+        for filter in self.filters.iter().filter(|f| f.owned_by_app) {
+            if let Some(weight) = filter.weight {
+                allocator.record(filter.rule_key(), weight);
+            }
+        }
+        allocator
+    }
+
+    /// Installs the filters of a simple TCP rule. When it replaces
+    /// `current`, they keep its callout and provider context.
+    fn install_simple_tcp_rule_v4(
+        &mut self,
+        weights: &mut WeightAllocator,
+        key: GUID,
+        name: &str,
+        remote_ports: &[u16],
+        action: WfpAction,
+        current: Option<&FilterSummary>,
+    ) -> Result<()> {
+        for builder in simple_tcp_rule_v4(key, name, remote_ports, action) {
+            let builder = match current {
+                Some(current) => builder.bound_like(current),
+                None => builder,
+            };
+            self.install(&builder.allocate_weight(weights))?;
+        }
+        Ok(())
+    }
+
+    fn import(&mut self, configs: &[FilterConfig]) -> Result<ImportSummary> {
+        let mut summary = ImportSummary::default();
+        let mut weights = self.weight_allocator();
+        for cfg in &order_dependencies(configs)? {
+            let key = guid_from_uuid(cfg.key.unwrap_or_else(Uuid::new_v4));
+            let removed = self.remove_rule(key)?;
+            for builder in cfg.builders(key)? {
+                self.install(&builder.allocate_weight(&mut weights))?;
+            }
+            if removed > 0 {
+                summary.updated += 1;
+            } else {
+                summary.added += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Fails unless the rules `configs` depend on are installed.
+    fn check_dependencies(&self, configs: &[FilterConfig]) -> Result<()> {
+        let installed: Vec<Uuid> = self
+            .filters
+            .iter()
+            .filter(|f| f.owned_by_app)
+            .map(|f| uuid_from_guid(f.rule_key()))
+            .collect();
+        check_dependencies(configs, &installed)
+    }
+
+    fn ensure_provider_setup(&mut self) {
+        if !self.providers.iter().any(|p| p.key == PROVIDER_KEY) {
+            self.providers.push(NamedGuid {
+                key: PROVIDER_KEY,
+                name: PROVIDER_NAME.into(),
+                description: None,
+            });
+        }
+        self.add_sublayer(SUBLAYER_KEY, SUBLAYER_NAME, SUBLAYER_WEIGHT);
+    }
+
+    /// Adds a sublayer under our provider unless one with `key` exists.
+    fn add_sublayer(&mut self, key: GUID, name: &str, weight: u16) {
+        if !self.sublayers.iter().any(|s| s.key == key) {
+            self.sublayers.push(SublayerInfo {
+                key,
+                name: name.into(),
+                provider_key: Some(PROVIDER_KEY),
+                weight,
+                ours: true,
+            });
+        }
+    }
+
+    /// Adds a general provider context under our provider unless one with
+    /// `key` exists.
+    fn add_provider_context(&mut self, key: GUID, name: &str, data: &[u8]) -> ProviderContextInfo {
+        if let Some(context) = self.provider_contexts.iter().find(|c| c.key == key) {
+            return context.clone();
+        }
+        let context = ProviderContextInfo {
+            key,
+            id: self.provider_contexts.len() as u64 + 1,
+            name: name.into(),
+            provider_key: Some(PROVIDER_KEY),
+            data: Some(data.to_vec()),
+            ours: true,
+        };
+        self.provider_contexts.push(context.clone());
+        context
+    }
+
+    fn layers(&self) -> Vec<NamedGuid> {
+        LAYERS
+            .iter()
+            .map(|(key, name)| NamedGuid {
+                key: *key,
+                name: name.to_string(),
+                description: None,
+            })
+            .collect()
+    }
+
+    fn sublayers(&self) -> Vec<NamedGuid> {
+        self.sublayers
+            .iter()
+            .map(|s| NamedGuid {
+                key: s.key,
+                name: s.name.clone(),
+                description: None,
+            })
+            .collect()
+    }
+}
+
+/// Stands in for a connection subscription; nothing is ever sent.
+pub struct IpsecSubscription;
+
+/// In-memory engine used by the `simulation` feature. It keeps the same
+/// API and rules as the BFE-backed engine so the GUI, CLI and tests run on
+/// hosts without WFP. Each host name gets its own simulated machine, shared
+/// by every engine opened on it in this process.
+pub struct Engine {
+    machine: String,
+    session: GUID,
+    dynamic: bool,
+}
+
+impl Engine {
+    pub fn open() -> Result<Self> {
+        Self::open_on(None)
+    }
+
+    pub fn open_on(server: Option<&str>) -> Result<Self> {
+        let engine = Self::open_session(server, false);
+        if !is_read_only() {
+            engine.ensure_provider_setup();
+        }
+        Ok(engine)
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.machine.is_empty()
+    }
+
+    /// Opens a dynamic session; filters it adds are removed when it drops.
+    pub fn open_dynamic() -> Result<Self> {
+        check_writable()?;
+        Self::open()?;
+        Ok(Self::open_session(None, true))
+    }
+
+    fn open_session(server: Option<&str>, dynamic: bool) -> Self {
+        let engine = Self {
+            machine: server.unwrap_or_default().trim().to_ascii_lowercase(),
+            session: guid_from_uuid(Uuid::new_v4()),
+            dynamic,
+        };
+        let username = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_default();
+        engine.with_machine(|machine| {
+            machine.sessions.push(SessionInfo {
+                key: engine.session,
+                name: SESSION_NAME.into(),
+                description: Some(format!(
+                    "{} {} (pid {})",
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION"),
+                    std::process::id()
+                )),
+                process_id: std::process::id(),
+                username,
+                kernel_mode: false,
+                txn_wait_timeout_ms: TXN_WAIT_TIMEOUT_MS,
+                ours: true,
+            })
+        });
+        engine
+    }
+
+    fn with_machine<T>(&self, read: impl FnOnce(&mut Machine) -> T) -> T {
+        read(machines().entry(self.machine.clone()).or_default())
+    }
+
+    /// Runs `change` against a copy of the machine and keeps the copy only
+    /// if it succeeds, like a BFE transaction. The lock is held throughout,
+    /// so other engines wait as they would for a real transaction.
+    fn transaction<T>(&self, change: impl FnOnce(&mut Machine) -> Result<T>) -> Result<T> {
+        check_writable()?;
+        let mut machines = machines();
+        let machine = machines.entry(self.machine.clone()).or_default();
+        let mut staged = machine.clone();
+        let value = change(&mut staged)?;
+        if self.dynamic {
+            let added: Vec<GUID> = staged
+                .filters
+                .iter()
+                .filter(|f| machine.filter(f.key).is_none())
+                .map(|f| f.key)
+                .collect();
+            staged
+                .dynamic_filters
+                .extend(added.into_iter().map(|key| (self.session, key)));
+        }
+        *machine = staged;
+        Ok(value)
+    }
+
+    fn ensure_provider_setup(&self) {
+        self.with_machine(Machine::ensure_provider_setup)
+    }
+
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        self.snapshot_cancellable(&CancelToken::new())
+    }
+
+    /// The simulated machine is read in one step, so `cancel` is only
+    /// checked before it.
+    pub fn snapshot_cancellable(&self, cancel: &CancelToken) -> Result<Snapshot> {
+        cancel.check()?;
+        Ok(self.with_machine(|machine| {
+            let providers = machine.providers.clone();
+            let sublayers = machine.sublayers();
+            let layers = machine.layers();
+            let names = FilterNames::new(&providers, &sublayers, &layers);
+            let (mut boot_time_filters, mut filters): (Vec<_>, Vec<_>) =
+                machine.filters.iter().cloned().partition(|f| f.boot_time);
+            for filter in filters.iter_mut().chain(&mut boot_time_filters) {
+                names.apply(filter);
+            }
+            Snapshot {
+                filters,
+                boot_time_filters,
+                providers,
+                sublayers,
+                layers,
+            }
+        }))
+    }
+
+    /// Every run-time filter, named like those in [`Engine::snapshot`]. The
+    /// simulated machine is in memory already, so this is a copy.
+    pub fn filters_iter(&self) -> Result<impl Iterator<Item = Result<FilterSummary>> + '_> {
+        Ok(self.snapshot()?.filters.into_iter().map(Ok))
+    }
+
+    /// Times the same phases as the native engine against the simulated
+    /// machine.
+    pub fn timed_snapshot(&self) -> Result<SnapshotTimings> {
+        Ok(self.with_machine(|machine| {
+            let mut timings = SnapshotTimings::default();
+            let start = Instant::now();
+            let providers = machine.providers.clone();
+            timings.providers = start.elapsed();
+            let start = Instant::now();
+            let sublayers = machine.sublayers();
+            timings.sublayers = start.elapsed();
+            let start = Instant::now();
+            let layers = machine.layers();
+            timings.layers = start.elapsed();
+            let start = Instant::now();
+            let mut filters: Vec<_> = machine
+                .filters
+                .iter()
+                .filter(|f| !f.boot_time)
+                .cloned()
+                .collect();
+            timings.filters = start.elapsed();
+            let start = Instant::now();
+            let names = FilterNames::new(&providers, &sublayers, &layers);
+            for filter in &mut filters {
+                names.apply(filter);
+            }
+            timings.decode = start.elapsed();
+            timings.filter_count = filters.len();
+            timings
+        }))
+    }
+
+    pub fn add_simple_tcp_filter_v4(
+        &self,
+        name: &str,
+        remote_ports: &[u16],
+        action: WfpAction,
+    ) -> Result<GUID> {
+        crate::naming::NamingPolicy::load()?.check(name)?;
+        let key = guid_from_uuid(Uuid::new_v4());
+        self.transaction(|machine| {
+            let mut weights = machine.weight_allocator();
+            machine.install_simple_tcp_rule_v4(&mut weights, key, name, remote_ports, action, None)
+        })?;
+        audit_rule(AuditAction::Add, key, name, remote_ports, action);
+        Ok(key)
+    }
+
+    pub fn update_filter_by_key(
+        &self,
+        key: GUID,
+        name: &str,
+        remote_ports: &[u16],
+        action: WfpAction,
+    ) -> Result<()> {
+        crate::naming::NamingPolicy::load()?.check(name)?;
+        self.transaction(|machine| {
+            let mut weights = machine.weight_allocator();
+            let current = machine
+                .filters
+                .iter()
+                .find(|f| f.owned_by_app && f.rule_key() == key)
+                .cloned();
+            match machine.remove_rule(key)? {
+                0 => Err(anyhow!("Filter {} not found", uuid_from_guid(key))),
+                _ => machine.install_simple_tcp_rule_v4(
+                    &mut weights,
+                    key,
+                    name,
+                    remote_ports,
+                    action,
+                    current.as_ref(),
+                ),
+            }
+        })?;
+        audit_rule(AuditAction::Update, key, name, remote_ports, action);
+        Ok(())
+    }
+
+    pub fn delete_filter_by_key(&self, key: GUID) -> Result<()> {
+        self.transaction(|machine| match machine.remove_rule(key)? {
+            0 => Err(anyhow!("Filter {} not found", uuid_from_guid(key))),
+            _ => Ok(()),
+        })?;
+        syslog::audit(AuditRecord {
+            action: AuditAction::Delete,
+            rule: uuid_from_guid(key).to_string(),
+            name: None,
+            detail: "rule removed".into(),
+        });
+        Ok(())
+    }
+
+    pub fn delete_filter_by_id(&self, id: u64) -> Result<()> {
+        self.transaction(|machine| {
+            let idx = machine
+                .filters
+                .iter()
+                .position(|f| f.id == id)
+                .ok_or(WfpError::new("FwpmFilterGetById0", FWP_E_FILTER_NOT_FOUND))?;
+            if !machine.filters[idx].owned_by_app {
+                return Err(anyhow!("Filter {id} is not managed by this application"));
+            }
+            machine.filters.remove(idx);
+            Ok(())
+        })?;
+        syslog::audit(AuditRecord {
+            action: AuditAction::Delete,
+            rule: format!("filter {id}"),
+            name: None,
+            detail: "filter removed".into(),
+        });
+        Ok(())
+    }
+
+    pub fn delete_foreign_filters(&self, keys: &[GUID]) -> Result<()> {
+        self.transaction(|machine| {
+            for key in keys {
+                let idx =
+                    machine
+                        .filters
+                        .iter()
+                        .position(|f| f.key == *key)
+                        .ok_or(WfpError::new(
+                            "FwpmFilterDeleteByKey0",
+                            FWP_E_FILTER_NOT_FOUND,
+                        ))?;
+                machine.filters.remove(idx);
+            }
+            Ok(())
+        })?;
+        for key in keys {
+            syslog::audit(AuditRecord {
+                action: AuditAction::Delete,
+                rule: uuid_from_guid(*key).to_string(),
+                name: None,
+                detail: "foreign filter removed".into(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn export_owned_filters(&self) -> Result<String> {
+        let host = match self.machine.as_str() {
+            "" => "localhost",
+            remote => remote,
+        };
+        let set = RuleSet::owned(self.snapshot()?, &self.sublayer_details()?)
+            .with_provider_contexts(&self.provider_contexts()?)
+            .stamped(host, None);
+        Ok(serde_json::to_string_pretty(&set)?)
+    }
+
+    pub fn add_filter(&self, builder: &FilterBuilder) -> Result<u64> {
+        check_names(std::slice::from_ref(builder))?;
+        let id = self.transaction(|machine| {
+            let mut weights = machine.weight_allocator();
+            machine.install(&builder.clone().allocate_weight(&mut weights))
+        })?;
+        syslog::audit(AuditRecord {
+            action: AuditAction::Add,
+            rule: format!("filter {id}"),
+            name: Some(builder.name.clone()),
+            detail: format!("{} filter", builder.action.as_str()),
+        });
+        Ok(id)
+    }
+
+    pub fn replace_rule(&self, key: GUID, name: &str, builders: &[FilterBuilder]) -> Result<usize> {
+        check_names(builders)?;
+        let removed = self.transaction(|machine| {
+            let mut weights = machine.weight_allocator();
+            let removed = machine.remove_rule(key)?;
+            for builder in builders {
+                machine.install(&builder.clone().allocate_weight(&mut weights))?;
+            }
+            Ok(removed)
+        })?;
+        syslog::audit(AuditRecord {
+            action: if removed > 0 {
+                AuditAction::Update
+            } else {
+                AuditAction::Add
+            },
+            rule: uuid_from_guid(key).to_string(),
+            name: Some(name.to_string()),
+            detail: format!("{} filters", builders.len()),
+        });
+        Ok(removed)
+    }
+
+    pub fn bulk_edit(&self, filters: &[FilterSummary], edit: &BulkEdit) -> Result<usize> {
+        let builders = filters
+            .iter()
+            .map(|f| edit.apply(f))
+            .collect::<Result<Vec<_>>>()?;
+        self.reinstall(&builders, &format!("bulk edit: {}", edit.describe()))
+    }
+
+    pub fn reinstall_filters(&self, builders: &[FilterBuilder], detail: &str) -> Result<usize> {
+        check_names(builders)?;
+        self.reinstall(builders, detail)
+    }
+
+    fn reinstall(&self, builders: &[FilterBuilder], detail: &str) -> Result<usize> {
+        self.transaction(|machine| {
+            let mut weights = machine.weight_allocator();
+            for builder in builders {
+                let idx = machine
+                    .filters
+                    .iter()
+                    .position(|f| f.key == builder.key)
+                    .ok_or(WfpError::new(
+                        "FwpmFilterDeleteByKey0",
+                        FWP_E_FILTER_NOT_FOUND,
+                    ))?;
+                machine.filters.remove(idx);
+            }
+            for builder in builders {
+                machine.install(&builder.clone().allocate_weight(&mut weights))?;
+            }
+            Ok(())
+        })?;
+        audit_reinstalled(builders, detail);
+        Ok(builders.len())
+    }
+
+    pub fn import_filters(&self, configs: &[FilterConfig]) -> Result<ImportSummary> {
+        let configs = &with_keys(configs);
+        crate::naming::validate_configs(configs)?;
+        let summary = self.transaction(|machine| {
+            let summary = machine.import(configs)?;
+            machine.check_dependencies(configs)?;
+            Ok(summary)
+        })?;
+        audit_imports(configs);
+        Ok(summary)
+    }
+
+    pub fn create_sublayer(&self, name: &str, weight: u16) -> Result<GUID> {
+        check_writable()?;
+        if name.trim().is_empty() {
+            return Err(anyhow!("Sublayer name is required"));
+        }
+        let sublayer = SublayerInfo {
+            key: guid_from_uuid(Uuid::new_v4()),
+            name: name.to_string(),
+            provider_key: Some(PROVIDER_KEY),
+            weight,
+            ours: true,
+        };
+        self.with_machine(|machine| {
+            machine.ensure_provider_setup();
+            machine.sublayers.push(sublayer.clone());
+        });
+        audit_sublayer(AuditAction::Add, &sublayer);
+        Ok(sublayer.key)
+    }
+
+    pub fn delete_sublayer(&self, key: GUID, cascade: bool) -> Result<()> {
+        let sublayer = custom_sublayer(self.sublayer_details()?, key)?;
+        let removed = self.transaction(|machine| {
+            let users: Vec<FilterSummary> = machine
+                .filters
+                .iter()
+                .filter(|f| f.sublayer_key == key)
+                .cloned()
+                .collect();
+            if !cascade {
+                check_sublayer_unused(&sublayer, &users)?;
+            }
+            machine.filters.retain(|f| f.sublayer_key != key);
+            machine.sublayers.retain(|s| s.key != key);
+            Ok(users)
+        })?;
+        audit_sublayer_delete(&sublayer, &removed);
+        Ok(())
+    }
+
+    pub fn create_provider_context(&self, name: &str, data: &[u8]) -> Result<GUID> {
+        check_writable()?;
+        if name.trim().is_empty() {
+            return Err(anyhow!("Provider context name is required"));
+        }
+        let context = self.with_machine(|machine| {
+            machine.ensure_provider_setup();
+            machine.add_provider_context(guid_from_uuid(Uuid::new_v4()), name, data)
+        });
+        audit_provider_context(AuditAction::Add, &context);
+        Ok(context.key)
+    }
+
+    pub fn delete_provider_context(&self, key: GUID) -> Result<()> {
+        let context = self.transaction(|machine| {
+            let context =
+                unused_provider_context(machine.provider_contexts.clone(), &machine.filters, key)?;
+            machine.provider_contexts.retain(|c| c.key != key);
+            Ok(context)
+        })?;
+        audit_provider_context(AuditAction::Delete, &context);
+        Ok(())
+    }
+
+    pub fn provider_contexts(&self) -> Result<Vec<ProviderContextInfo>> {
+        Ok(self.with_machine(|machine| machine.provider_contexts.clone()))
+    }
+
+    pub fn import_rule_set(&self, set: &RuleSet) -> Result<ImportSummary> {
+        self.import_rule_set_cancellable(set, &CancelToken::new())
+    }
+
+    /// Checks `cancel` before committing; a cancelled import leaves the
+    /// machine unchanged.
+    pub fn import_rule_set_cancellable(
+        &self,
+        set: &RuleSet,
+        cancel: &CancelToken,
+    ) -> Result<ImportSummary> {
+        let set = &RuleSet {
+            filters: with_keys(&set.filters),
+            ..set.clone()
+        };
+        set.validate()?;
+        let summary = self.transaction(|machine| {
+            cancel.check()?;
+            machine.ensure_provider_setup();
+            for sublayer in &set.sublayers {
+                machine.add_sublayer(
+                    guid_from_uuid(sublayer.key),
+                    &sublayer.name,
+                    sublayer.weight,
+                );
+            }
+            for context in &set.provider_contexts {
+                machine.add_provider_context(
+                    guid_from_uuid(context.key),
+                    &context.name,
+                    &context.data,
+                );
+            }
+            let summary = machine.import(&order_weights(&set.filters))?;
+            machine.check_dependencies(&set.filters)?;
+            cancel.check()?;
+            Ok(summary)
+        })?;
+        audit_imports(&set.filters);
+        Ok(summary)
+    }
+
+    pub fn reconcile(&self, configs: &[FilterConfig], previous: &[Uuid]) -> Result<ImportSummary> {
+        if let Some(cfg) = configs.iter().find(|cfg| cfg.key.is_none()) {
+            return Err(anyhow!("Rule '{}' from a rule source has no key", cfg.name));
+        }
+        crate::naming::validate_configs(configs)?;
+        let dropped: Vec<Uuid> = previous
+            .iter()
+            .filter(|key| !configs.iter().any(|cfg| cfg.key == Some(**key)))
+            .copied()
+            .collect();
+        let summary = self.transaction(|machine| {
+            let mut summary = machine.import(&order_weights(configs))?;
+            for key in &dropped {
+                summary.removed += machine.remove_rule(guid_from_uuid(*key))?.min(1);
+            }
+            machine.check_dependencies(configs)?;
+            Ok(summary)
+        })?;
+        audit_imports(configs);
+        for key in dropped {
+            syslog::audit(AuditRecord {
+                action: AuditAction::Delete,
+                rule: key.to_string(),
+                name: None,
+                detail: "no longer supplied by its rule source".into(),
+            });
+        }
+        Ok(summary)
+    }
+
+    pub fn sessions(&self) -> Result<Vec<SessionInfo>> {
+        Ok(self.with_machine(|machine| machine.sessions.clone()))
+    }
+
+    pub fn sublayer_details(&self) -> Result<Vec<SublayerInfo>> {
+        Ok(self.with_machine(|machine| machine.sublayers.clone()))
+    }
+
+    /// The options BFE starts with on a default install, with the net event
+    /// keywords turned on since.
+    pub fn engine_options(&self) -> Result<EngineOptions> {
+        Ok(EngineOptions {
+            collect_net_events: true,
+            net_event_keywords: self.with_machine(|machine| machine.net_event_keywords),
+            name_cache: true,
+            ..EngineOptions::default()
+        })
+    }
+
+    pub fn record_allow_events(&self) -> Result<()> {
+        self.transaction(|machine| {
+            machine.net_event_keywords |= FWPM_NET_EVENT_KEYWORD_CLASSIFY_ALLOW;
+            Ok(())
+        })
+    }
+
+    /// The RPC endpoint mapper and Teredo ports of a typical machine.
+    pub fn system_ports(&self) -> Result<Vec<SystemPorts>> {
+        Ok(vec![
+            SystemPorts {
+                kind: SystemPortKind::RpcEndpointMapper,
+                ports: vec![135],
+            },
+            SystemPorts {
+                kind: SystemPortKind::Teredo,
+                ports: vec![3544],
+            },
+        ])
+    }
+
+    /// The simulation negotiates no IPsec.
+    pub fn ipsec_connections(&self) -> Result<Vec<IpsecConnection>> {
+        Ok(Vec::new())
+    }
+
+    pub fn subscribe_ipsec_connections(
+        self,
+        _events: Sender<IpsecEvent>,
+    ) -> Result<IpsecSubscription> {
+        Ok(IpsecSubscription)
+    }
+
+    /// No drivers load in the simulation, so callouts stay unregistered.
+    pub fn register_callout(
+        &self,
+        key: GUID,
+        name: &str,
+        description: &str,
+        layer: GUID,
+    ) -> Result<()> {
+        if name.trim().is_empty() {
+            return Err(anyhow!("Callout name is required"));
+        }
+        let callout = self.transaction(|machine| {
+            if !machine.layers().iter().any(|l| l.key == layer) {
+                return Err(WfpError::new("FwpmCalloutAdd0", FWP_E_LAYER_NOT_FOUND).into());
+            }
+            if machine.callouts.iter().any(|c| c.key == key) {
+                return Err(WfpError::new("FwpmCalloutAdd0", FWP_E_ALREADY_EXISTS).into());
+            }
+            machine.ensure_provider_setup();
+            let callout = CalloutInfo {
+                key,
+                id: machine.callouts.len() as u32 + 1,
+                name: name.into(),
+                description: (!description.is_empty()).then(|| description.into()),
+                provider_key: Some(PROVIDER_KEY),
+                layer_key: layer,
+                registered: false,
+                ours: true,
+            };
+            machine.callouts.push(callout.clone());
+            Ok(callout)
+        })?;
+        audit_callout(AuditAction::Add, &callout);
+        Ok(())
+    }
+
+    pub fn unregister_callout(&self, key: GUID) -> Result<()> {
+        let callout = self.transaction(|machine| {
+            let callout = unused_callout(machine.callouts.clone(), &machine.filters, key)?;
+            machine.callouts.retain(|c| c.key != key);
+            Ok(callout)
+        })?;
+        audit_callout(AuditAction::Delete, &callout);
+        Ok(())
+    }
+
+    pub fn callouts(&self) -> Result<Vec<CalloutInfo>> {
+        Ok(self.with_machine(|machine| machine.callouts.clone()))
+    }
+
+    /// No traffic passes through the simulation, so there are no events.
+    pub fn net_events(&self) -> Result<Vec<NetEvent>> {
+        self.query_net_events(&NetEventQuery::default())
+    }
+
+    pub fn query_net_events(&self, query: &NetEventQuery) -> Result<Vec<NetEvent>> {
+        self.query_net_events_cancellable(query, &CancelToken::new())
+    }
+
+    pub fn query_net_events_cancellable(
+        &self,
+        _query: &NetEventQuery,
+        cancel: &CancelToken,
+    ) -> Result<Vec<NetEvent>> {
+        cancel.check()?;
+        Ok(Vec::new())
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let session = self.session;
+        self.with_machine(|machine| {
+            machine.sessions.retain(|s| s.key != session);
+            let owned: Vec<GUID> = machine
+                .dynamic_filters
+                .iter()
+                .filter(|(owner, _)| *owner == session)
+                .map(|(_, key)| *key)
+                .collect();
+            machine.filters.retain(|f| !owned.contains(&f.key));
+            machine
+                .dynamic_filters
+                .retain(|(owner, _)| *owner != session);
+        });
+    }
+}
+
+/// Stands in for `FwpmGetAppIdFromFileName0`, which would also resolve the
+/// drive letter to a device path.
+pub fn app_id(path: &str) -> Result<ConditionValue> {
+    if path.trim().is_empty() {
+        return Err(anyhow!("Application path is required"));
+    }
+    let bytes = path
+        .to_lowercase()
+        .encode_utf16()
+        .chain([0])
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    Ok(ConditionValue::ByteBlob(bytes))
+}
+
+/// The simulated engine stores conditions exactly as given, so a round
+/// trip only checks that BFE would have accepted them.
+#[doc(hidden)]
+pub fn round_trip_conditions(conditions: &[Condition]) -> Result<Vec<Condition>> {
+    conditions.iter().try_for_each(Condition::validate)?;
+    Ok(conditions.to_vec())
+}
@@ -0,0 +1,173 @@
+use super::*;
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Puts the process in viewer-only mode: engines opened afterwards only
+/// enumerate, without registering our provider, and every change fails
+/// with [`ReadOnlyError`].
+pub fn set_read_only() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// A change refused in viewer-only mode.
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+#[error("Read-only mode: changes to the filtering engine are disabled")]
+pub struct ReadOnlyError;
+
+pub(super) fn check_writable() -> Result<()> {
+    if is_read_only() {
+        return Err(ReadOnlyError.into());
+    }
+    Ok(())
+}
+
+/// Asks a long-running engine operation to stop at its next step. Clones
+/// share the flag, so one can go to the worker and one to a Cancel button.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fails with [`Cancelled`] once cancelled. A change in progress is
+    /// rolled back with its transaction.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+}
+
+/// An operation stopped through its [`CancelToken`].
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+#[error("Operation cancelled")]
+pub struct Cancelled;
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    /// Rules dropped by [`Engine::reconcile`]; always 0 for imports.
+    pub removed: usize,
+}
+
+/// `configs` with a fresh key for each rule that has none, assigned before
+/// the import so the audit trail names the key it was installed under.
+pub(super) fn with_keys(configs: &[FilterConfig]) -> Vec<FilterConfig> {
+    configs
+        .iter()
+        .map(|cfg| FilterConfig {
+            key: Some(cfg.key.unwrap_or_else(Uuid::new_v4)),
+            ..cfg.clone()
+        })
+        .collect()
+}
+
+/// Audits rules just installed, and records their hostnames. Every rule
+/// carries a key by then; see [`with_keys`].
+pub(super) fn audit_imports(configs: &[FilterConfig]) {
+    remember_rule_hosts(configs);
+    for cfg in configs {
+        syslog::audit(AuditRecord {
+            action: AuditAction::Import,
+            rule: cfg.key.map(|k| k.to_string()).unwrap_or_default(),
+            name: Some(cfg.name.clone()),
+            detail: crate::rule_expr::format(cfg),
+        })
+    }
+}
+
+pub(super) fn audit_sublayer(action: AuditAction, sublayer: &SublayerInfo) {
+    syslog::audit(AuditRecord {
+        action,
+        rule: format!("sublayer {}", uuid_from_guid(sublayer.key)),
+        name: Some(sublayer.name.clone()),
+        detail: format!("weight {}", sublayer.weight),
+    });
+}
+
+/// Records a sublayer deletion and the filters removed along with it.
+pub(super) fn audit_sublayer_delete(sublayer: &SublayerInfo, removed: &[FilterSummary]) {
+    for filter in removed {
+        syslog::audit(AuditRecord {
+            action: AuditAction::Delete,
+            rule: format!("filter {}", filter.id),
+            name: Some(filter.name.clone()),
+            detail: "filter removed with its sublayer".into(),
+        });
+    }
+    audit_sublayer(AuditAction::Delete, sublayer);
+}
+
+pub(super) fn audit_provider_context(action: AuditAction, context: &ProviderContextInfo) {
+    syslog::audit(AuditRecord {
+        action,
+        rule: format!("provider context {}", uuid_from_guid(context.key)),
+        name: Some(context.name.clone()),
+        detail: format!(
+            "{} byte(s) of data",
+            context.data.as_ref().map_or(0, Vec::len)
+        ),
+    });
+}
+
+pub(super) fn audit_callout(action: AuditAction, callout: &CalloutInfo) {
+    syslog::audit(AuditRecord {
+        action,
+        rule: format!("callout {}", uuid_from_guid(callout.key)),
+        name: Some(callout.name.clone()),
+        detail: format!("at layer {}", uuid_from_guid(callout.layer_key)),
+    });
+}
+
+pub(super) fn audit_reinstalled(builders: &[FilterBuilder], detail: &str) {
+    for builder in builders {
+        syslog::audit(AuditRecord {
+            action: AuditAction::Update,
+            rule: uuid_from_guid(builder.key).to_string(),
+            name: Some(builder.name.clone()),
+            detail: detail.to_string(),
+        });
+    }
+}
+
+pub(super) fn audit_rule(
+    action: AuditAction,
+    key: GUID,
+    name: &str,
+    ports: &[u16],
+    rule: WfpAction,
+) {
+    let ports: Vec<String> = ports.iter().map(u16::to_string).collect();
+    syslog::audit(AuditRecord {
+        action,
+        rule: uuid_from_guid(key).to_string(),
+        name: Some(name.to_string()),
+        detail: format!("{} remote TCP {}", rule.as_str(), ports.join(", ")),
+    });
+}
+
+/// Checks the names of filters about to be installed against the saved
+/// naming policy, as every write path does.
+pub(super) fn check_names(builders: &[FilterBuilder]) -> Result<()> {
+    if builders.is_empty() {
+        return Ok(());
+    }
+    let policy = crate::naming::NamingPolicy::load()?;
+    builders.iter().try_for_each(|b| policy.check(&b.name))
+}
@@ -0,0 +1,84 @@
+// App paths given as patterns: environment variables are expanded and
+// wildcards match the executables below a directory, each of which gets its
+// own app ID condition.
+
+use std::{fs, path::PathBuf};
+
+use sls_wfp_gui::wfp::{
+    expand_env_vars, guid_from_uuid, is_app_pattern, resolve_app_pattern, ConditionField,
+    FilterConfig, RemotePorts, WfpAction,
+};
+use uuid::Uuid;
+
+/// A fresh directory holding `files`, relative to it.
+fn tree(files: &[&str]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("app-patterns-{}", Uuid::new_v4()));
+    for file in files {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+    root
+}
+
+#[test]
+fn patterns_are_told_apart_from_paths() {
+    assert!(is_app_pattern(r"%ProgramFiles%\Vendor\app.exe"));
+    assert!(is_app_pattern(r"C:\Vendor\*"));
+    assert!(is_app_pattern(r"C:\Vendor\app?.exe"));
+    assert!(is_app_pattern(r"C:\Vendor\"));
+    assert!(!is_app_pattern(r"C:\Vendor\app.exe"));
+}
+
+#[test]
+fn environment_variables_are_expanded() {
+    std::env::set_var("APP_PATTERNS_TEST_DIR", "/opt/vendor");
+    assert_eq!(
+        expand_env_vars("%APP_PATTERNS_TEST_DIR%/app.exe").unwrap(),
+        "/opt/vendor/app.exe"
+    );
+    assert!(expand_env_vars("%APP_PATTERNS_UNSET_VARIABLE%/app.exe").is_err());
+    assert!(expand_env_vars("%APP_PATTERNS_TEST_DIR/app.exe").is_err());
+}
+
+#[test]
+fn wildcards_match_executables_in_subdirectories() {
+    let root = tree(&[
+        "Vendor.exe",
+        "bin/Helper.EXE",
+        "bin/readme.txt",
+        "other.dll",
+    ]);
+    std::env::set_var("APP_PATTERNS_TEST_ROOT", &root);
+
+    let all = resolve_app_pattern("%APP_PATTERNS_TEST_ROOT%/*").unwrap();
+    assert_eq!(
+        all,
+        vec![root.join("Vendor.exe"), root.join("bin/Helper.EXE")]
+    );
+    assert_eq!(
+        resolve_app_pattern(&format!("{}/", root.display())).unwrap(),
+        all
+    );
+    assert_eq!(
+        resolve_app_pattern("%APP_PATTERNS_TEST_ROOT%/help*").unwrap(),
+        vec![root.join("bin/Helper.EXE")]
+    );
+    // Matching nothing would leave a rule without an app condition.
+    assert!(resolve_app_pattern("%APP_PATTERNS_TEST_ROOT%/none*.exe").is_err());
+    assert!(resolve_app_pattern("%APP_PATTERNS_TEST_ROOT%/*/app.exe").is_err());
+
+    let mut config = FilterConfig::tcp_ports("Vendor", RemotePorts::One(443), WfpAction::Block);
+    config.app = Some(format!("{}/*", root.display()));
+    let builders = config.builders(guid_from_uuid(Uuid::new_v4())).unwrap();
+    assert!(builders.iter().all(|b| {
+        b.to_summary(0)
+            .conditions
+            .iter()
+            .filter(|c| c.field == ConditionField::AppId)
+            .count()
+            == 2
+    }));
+
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,67 @@
+// The engine-thread API against the simulated engine: futures resolve with
+// what the synchronous calls return, from any executor.
+#![cfg(feature = "simulation")]
+
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake},
+    thread::{self, Thread},
+};
+
+use sls_wfp_gui::{
+    async_engine::AsyncEngine,
+    rule_expr,
+    wfp::{guid_from_uuid, uuid_from_guid},
+};
+use uuid::Uuid;
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Minimal executor: polls on this thread, parking until woken.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Arc::new(Unpark(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+#[test]
+fn add_snapshot_and_delete_resolve_in_order() {
+    let engine = AsyncEngine::open_on(Some("async-engine-test")).unwrap();
+    let mut config = rule_expr::parse("block tcp port 4455").unwrap();
+    let key = guid_from_uuid(Uuid::new_v4());
+    config.key = Some(uuid_from_guid(key));
+
+    // Queued together; the engine thread runs them one after another.
+    let added = engine.add(vec![config]);
+    let snapshot = engine.snapshot();
+    assert_eq!(block_on(added).unwrap().added, 1);
+    let filters = block_on(snapshot).unwrap().filters;
+    assert!(filters.iter().any(|f| f.rule_key() == key));
+
+    block_on(engine.delete(key)).unwrap();
+    let filters = block_on(engine.snapshot()).unwrap().filters;
+    assert!(!filters.iter().any(|f| f.rule_key() == key));
+}
+
+#[test]
+fn a_panicking_job_fails_only_its_own_future() {
+    let engine = AsyncEngine::open_on(Some("async-engine-panic")).unwrap();
+    let result = block_on(engine.run(|_| -> anyhow::Result<()> { panic!("job failed") }));
+    assert!(result.is_err());
+    // The engine thread survives the panic and runs the next job.
+    assert!(block_on(engine.snapshot()).is_ok());
+}
@@ -0,0 +1,118 @@
+// Bulk edits: every selected filter changes in one transaction and keeps
+// its key, and nothing changes when one of them cannot be edited. Edits of
+// callout filters keep the callout and provider context.
+#![cfg(feature = "simulation")]
+
+use sls_wfp_gui::{
+    rule_expr,
+    wfp::{
+        guid_from_uuid, BulkEdit, CalloutAction, CalloutKind, Engine, FilterBuilder, FilterSummary,
+        WfpAction, FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+    },
+};
+use uuid::Uuid;
+
+fn owned(engine: &Engine) -> Vec<FilterSummary> {
+    let mut filters: Vec<FilterSummary> = engine
+        .snapshot()
+        .unwrap()
+        .filters
+        .into_iter()
+        .filter(|f| f.owned_by_app)
+        .collect();
+    filters.sort_by_key(|f| f.id);
+    filters
+}
+
+#[test]
+fn selected_filters_change_together() {
+    let engine = Engine::open_on(Some("bulk-edit")).unwrap();
+    let configs: Vec<_> = [
+        "allow out tcp to 192.0.2.1 port 443",
+        "block out udp port 53",
+    ]
+    .into_iter()
+    .map(|expr| {
+        let mut config = rule_expr::parse(expr).unwrap();
+        config.key = Some(Uuid::new_v4());
+        config
+    })
+    .collect();
+    engine.import_filters(&configs).unwrap();
+    let group = engine.create_sublayer("Bulk", 0x4000).unwrap();
+    let before = owned(&engine);
+
+    let edit = BulkEdit {
+        action: Some(WfpAction::Block),
+        persistent: Some(true),
+        sublayer: Some(group),
+        ..BulkEdit::default()
+    };
+    assert_eq!(engine.bulk_edit(&before, &edit).unwrap(), before.len());
+
+    let after = owned(&engine);
+    assert_eq!(after.len(), before.len());
+    for filter in &before {
+        let edited = after.iter().find(|f| f.key == filter.key).unwrap();
+        assert_eq!(edited.action, WfpAction::Block);
+        assert!(edited.persistent);
+        assert_eq!(edited.sublayer_key, group);
+        assert_eq!(edited.conditions, filter.conditions);
+        assert_eq!(edited.rule_key, filter.rule_key);
+    }
+
+    let mut stale = after.clone();
+    stale[0].key = guid_from_uuid(Uuid::new_v4());
+    let weight = BulkEdit {
+        weight: Some(7),
+        ..BulkEdit::default()
+    };
+    assert!(engine.bulk_edit(&stale, &weight).is_err());
+    assert!(owned(&engine).iter().all(|f| f.weight != Some(7)));
+}
+
+#[test]
+fn callout_filters_keep_their_callout_when_edited() {
+    let engine = Engine::open_on(Some("callout-edit")).unwrap();
+    let callout = CalloutAction {
+        key: guid_from_uuid(Uuid::new_v4()),
+        kind: CalloutKind::Inspection,
+    };
+    engine
+        .register_callout(
+            callout.key,
+            "Web inspector",
+            "",
+            FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+        )
+        .unwrap();
+    let context = engine.create_provider_context("Web", &[7]).unwrap();
+    let builder = FilterBuilder::new("Inspect web", FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+        .action(WfpAction::Callout)
+        .callout(callout)
+        .provider_context(context);
+    engine.add_filter(&builder).unwrap();
+    let key = owned(&engine)[0].key;
+
+    engine
+        .update_filter_by_key(key, "Inspect web", &[80, 443], WfpAction::Callout)
+        .unwrap();
+    let updated = owned(&engine);
+    assert_eq!(updated.len(), 2);
+    for filter in &updated {
+        assert_eq!(filter.callout, Some(callout));
+        assert_eq!(filter.provider_context_key, Some(context));
+    }
+
+    let edit = BulkEdit {
+        weight: Some(9),
+        ..BulkEdit::default()
+    };
+    engine.bulk_edit(&updated, &edit).unwrap();
+    assert!(owned(&engine).iter().all(|f| f.callout == Some(callout)));
+
+    // A callout filter cannot be made without its callout.
+    let bare =
+        FilterBuilder::new("Bare", FWPM_LAYER_ALE_AUTH_CONNECT_V4).action(WfpAction::Callout);
+    assert!(engine.add_filter(&bare).is_err());
+}
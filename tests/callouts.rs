@@ -0,0 +1,51 @@
+// Callouts registered for a companion driver: filters can only use ones
+// that exist, and ours stay until no filter uses them.
+#![cfg(feature = "simulation")]
+
+use sls_wfp_gui::wfp::{
+    guid_from_uuid, CalloutAction, CalloutKind, Engine, FilterBuilder, WfpAction,
+    FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+};
+use uuid::Uuid;
+
+#[test]
+fn callouts_stay_while_filters_use_them() {
+    let engine = Engine::open_on(Some("callouts")).unwrap();
+    let key = guid_from_uuid(Uuid::new_v4());
+    let builder = FilterBuilder::new("Inspect", FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+        .action(WfpAction::Callout)
+        .callout(CalloutAction {
+            key,
+            kind: CalloutKind::Inspection,
+        });
+    assert!(engine.add_filter(&builder).is_err());
+
+    engine
+        .register_callout(
+            key,
+            "Connect inspector",
+            "Logs outbound connects",
+            FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+        )
+        .unwrap();
+    assert!(engine
+        .register_callout(key, "Again", "", FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+        .is_err());
+    assert!(engine
+        .register_callout(guid_from_uuid(Uuid::new_v4()), "Nowhere", "", key)
+        .is_err());
+    let callouts = engine.callouts().unwrap();
+    assert_eq!(callouts.len(), 1);
+    assert!(callouts[0].ours && !callouts[0].registered);
+    assert_eq!(
+        callouts[0].description.as_deref(),
+        Some("Logs outbound connects")
+    );
+
+    engine.add_filter(&builder).unwrap();
+    assert!(engine.unregister_callout(key).is_err());
+    let filter = engine.snapshot().unwrap().filters[0].key;
+    engine.delete_filter_by_key(filter).unwrap();
+    engine.unregister_callout(key).unwrap();
+    assert!(engine.callouts().unwrap().is_empty());
+}
@@ -0,0 +1,47 @@
+// Cancel tokens: a cancelled change is rolled back whole, so the engine is
+// left exactly as it was.
+#![cfg(feature = "simulation")]
+
+use sls_wfp_gui::{
+    rule_expr,
+    wfp::{uuid_from_guid, CancelToken, Cancelled, Engine, RuleSet},
+};
+use uuid::Uuid;
+
+#[test]
+fn a_cancelled_import_leaves_the_machine_unchanged() {
+    let source = Engine::open_on(Some("cancel-source")).unwrap();
+    let context = source
+        .create_provider_context("Inspection settings", &[5, 6])
+        .unwrap();
+    let mut config = rule_expr::parse("block out tcp port 8081").unwrap();
+    config.key = Some(Uuid::new_v4());
+    config.provider_context = Some(uuid_from_guid(context));
+    source.import_filters(&[config]).unwrap();
+    let set: RuleSet = serde_json::from_str(&source.export_owned_filters().unwrap()).unwrap();
+
+    let target = Engine::open_on(Some("cancel-target")).unwrap();
+    let state = |engine: &Engine| {
+        (
+            serde_json::to_value(engine.snapshot().unwrap()).unwrap(),
+            serde_json::to_value(engine.sublayer_details().unwrap()).unwrap(),
+            engine.provider_contexts().unwrap(),
+        )
+    };
+    let before = state(&target);
+
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let err = target
+        .import_rule_set_cancellable(&set, &cancel)
+        .unwrap_err();
+    assert!(err.is::<Cancelled>());
+    assert_eq!(state(&target), before);
+
+    // The same set goes in once it is not cancelled.
+    let summary = target
+        .import_rule_set_cancellable(&set, &CancelToken::new())
+        .unwrap();
+    assert_eq!(summary.added, 1);
+    assert_ne!(state(&target), before);
+}
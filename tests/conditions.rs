@@ -0,0 +1,309 @@
+// Property tests for the condition model: anything a FilterBuilder accepts
+// must come back unchanged after encoding to FWPM_FILTER_CONDITION0 and
+// decoding again. Address values are also checked for the hosts they hold,
+// values typed into the condition editor for how they read back, byte
+// values for what they decode to, layers for the fields they have, and
+// match types this build does not know for their raw value.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use proptest::prelude::*;
+use sls_wfp_gui::{
+    rule_expr,
+    wfp::{
+        hex_dump, round_trip_conditions, Condition, ConditionField, ConditionValue, FilterBuilder,
+        LayerSchema, MatchType, FWPM_LAYER_ALE_AUTH_CONNECT_V4, FWPM_LAYER_ALE_AUTH_LISTEN_V4,
+        GUID,
+    },
+};
+use uuid::Uuid;
+
+/// SDDL strings already in the canonical form BFE hands back.
+const SDDL: [&str; 3] = [
+    "O:SYG:SYD:(A;;CC;;;WD)",
+    "O:BAG:BAD:(A;;CC;;;BA)(A;;CC;;;SY)",
+    "O:LSG:LSD:(D;;CC;;;AN)",
+];
+
+fn field() -> impl Strategy<Value = ConditionField> {
+    prop_oneof![
+        Just(ConditionField::IpProtocol),
+        Just(ConditionField::RemotePort),
+        Just(ConditionField::LocalPort),
+        Just(ConditionField::RemoteAddress),
+        Just(ConditionField::LocalAddress),
+        Just(ConditionField::AppId),
+        Just(ConditionField::UserId),
+        Just(ConditionField::LocalInterface),
+        Just(ConditionField::Flags),
+        Just(ConditionField::Direction),
+        any::<u128>().prop_map(|id| ConditionField::Other(Uuid::from_u128(id))),
+    ]
+}
+
+fn integer() -> impl Strategy<Value = ConditionValue> {
+    prop_oneof![
+        any::<u8>().prop_map(ConditionValue::Uint8),
+        any::<u16>().prop_map(ConditionValue::Uint16),
+        any::<u32>().prop_map(ConditionValue::Uint32),
+        any::<u64>().prop_map(ConditionValue::Uint64),
+    ]
+}
+
+/// Values only `equal` and `not_equal` accept.
+fn opaque() -> impl Strategy<Value = ConditionValue> {
+    prop_oneof![
+        any::<[u8; 16]>().prop_map(ConditionValue::ByteArray16),
+        any::<[u8; 6]>().prop_map(ConditionValue::ByteArray6),
+        proptest::collection::vec(any::<u8>(), 0..64).prop_map(ConditionValue::ByteBlob),
+        (
+            any::<[u8; 6]>(),
+            proptest::collection::vec(any::<u32>(), 0..=15)
+        )
+            .prop_map(|(authority, subs)| {
+                let mut sid = vec![1, subs.len() as u8];
+                sid.extend(authority);
+                sid.extend(subs.iter().flat_map(|s| s.to_le_bytes()));
+                ConditionValue::Sid(sid)
+            }),
+        (any::<u32>(), any::<u32>()).prop_map(|(addr, mask)| ConditionValue::V4AddrMask {
+            addr: Ipv4Addr::from(addr),
+            mask: Ipv4Addr::from(mask),
+        }),
+        (any::<u128>(), 0..=128u8).prop_map(|(addr, prefix_length)| {
+            ConditionValue::V6AddrMask {
+                addr: Ipv6Addr::from(addr),
+                prefix_length,
+            }
+        }),
+        proptest::sample::select(SDDL.to_vec())
+            .prop_map(|sddl| ConditionValue::SecurityDescriptor(sddl.to_string())),
+    ]
+}
+
+fn range() -> impl Strategy<Value = ConditionValue> {
+    let bounds = prop_oneof![
+        (any::<u8>(), any::<u8>())
+            .prop_map(|(l, h)| (ConditionValue::Uint8(l), ConditionValue::Uint8(h))),
+        (any::<u16>(), any::<u16>())
+            .prop_map(|(l, h)| (ConditionValue::Uint16(l), ConditionValue::Uint16(h))),
+        (any::<u32>(), any::<u32>())
+            .prop_map(|(l, h)| (ConditionValue::Uint32(l), ConditionValue::Uint32(h))),
+        (any::<u64>(), any::<u64>())
+            .prop_map(|(l, h)| (ConditionValue::Uint64(l), ConditionValue::Uint64(h))),
+        (any::<[u8; 16]>(), any::<[u8; 16]>()).prop_map(|(l, h)| (
+            ConditionValue::ByteArray16(l),
+            ConditionValue::ByteArray16(h)
+        )),
+    ];
+    bounds.prop_map(|(low, high)| ConditionValue::Range {
+        low: Box::new(low),
+        high: Box::new(high),
+    })
+}
+
+/// Match type and value pairs that pass [`Condition::validate`].
+fn match_and_value() -> impl Strategy<Value = (MatchType, ConditionValue)> {
+    let equality = prop_oneof![Just(MatchType::Equal), Just(MatchType::NotEqual)];
+    prop_oneof![
+        (
+            proptest::sample::select(vec![
+                MatchType::Equal,
+                MatchType::NotEqual,
+                MatchType::Greater,
+                MatchType::Less,
+                MatchType::GreaterOrEqual,
+                MatchType::LessOrEqual,
+                MatchType::FlagsAllSet,
+                MatchType::FlagsAnySet,
+                MatchType::FlagsNoneSet,
+            ]),
+            integer(),
+        ),
+        (
+            proptest::sample::select(vec![
+                MatchType::Equal,
+                MatchType::NotEqual,
+                MatchType::EqualCaseInsensitive,
+                MatchType::Prefix,
+                MatchType::NotPrefix,
+            ]),
+            "[^\\x00]{0,40}".prop_map(ConditionValue::Unicode),
+        ),
+        (equality, opaque()),
+        (Just(MatchType::Range), range()),
+    ]
+}
+
+fn condition() -> impl Strategy<Value = Condition> {
+    (field(), match_and_value())
+        .prop_map(|(field, (match_type, value))| Condition::new(field, match_type, value))
+}
+
+proptest! {
+    #[test]
+    fn conditions_survive_native_round_trip(
+        conditions in proptest::collection::vec(condition(), 1..6)
+    ) {
+        let builder = conditions.iter().cloned().fold(
+            FilterBuilder::new("proptest", FWPM_LAYER_ALE_AUTH_CONNECT_V4),
+            FilterBuilder::condition,
+        );
+        prop_assert!(builder.validate().is_ok());
+        let built = builder.to_summary(0).conditions;
+        prop_assert_eq!(round_trip_conditions(&built).unwrap(), conditions);
+    }
+
+    #[test]
+    fn conditions_survive_json_round_trip(condition in condition()) {
+        let json = serde_json::to_string(&condition).unwrap();
+        prop_assert_eq!(serde_json::from_str::<Condition>(&json).unwrap(), condition);
+    }
+}
+
+#[test]
+fn address_values_hold_hosts_by_exact_network_and_range() {
+    let host: IpAddr = "10.1.2.3".parse().unwrap();
+    let v6: IpAddr = "2001:db8::5".parse().unwrap();
+    let exact = ConditionValue::Uint32(u32::from(Ipv4Addr::new(10, 1, 2, 3)));
+    let network = ConditionValue::V4AddrMask {
+        addr: Ipv4Addr::new(10, 0, 0, 0),
+        mask: Ipv4Addr::new(255, 0, 0, 0),
+    };
+    let range = ConditionValue::Range {
+        low: Box::new(ConditionValue::Uint32(u32::from(Ipv4Addr::new(
+            10, 1, 2, 0,
+        )))),
+        high: Box::new(ConditionValue::Uint32(u32::from(Ipv4Addr::new(
+            10, 1, 2, 9,
+        )))),
+    };
+    for value in [&exact, &network, &range] {
+        assert_eq!(value.contains_address(host), Some(true), "{value}");
+        // The other family never matches.
+        assert_eq!(value.contains_address(v6), Some(false), "{value}");
+    }
+    assert_eq!(
+        network.contains_address("11.1.2.3".parse().unwrap()),
+        Some(false)
+    );
+    let prefix = ConditionValue::V6AddrMask {
+        addr: "2001:db8::".parse::<Ipv6Addr>().unwrap(),
+        prefix_length: 32,
+    };
+    assert_eq!(prefix.contains_address(v6), Some(true));
+    assert_eq!(ConditionValue::Uint16(80).contains_address(host), None);
+}
+
+#[test]
+fn typed_values_read_back_as_edited() {
+    let cases = [
+        (ConditionField::IpProtocol, false, "tcp", "6"),
+        (
+            ConditionField::RemotePort,
+            false,
+            "8000..=8080",
+            "8000..=8080",
+        ),
+        (
+            ConditionField::RemoteAddress,
+            false,
+            "10.0.0.0/8",
+            "10.0.0.0/8",
+        ),
+        (
+            ConditionField::LocalAddress,
+            true,
+            "2001:db8::/32",
+            "2001:db8::/32",
+        ),
+        (
+            ConditionField::AppId,
+            false,
+            "\\device\\harddiskvolume3\\app.exe",
+            "\\device\\harddiskvolume3\\app.exe",
+        ),
+        (ConditionField::InterfaceType, false, "0x47", "71"),
+    ];
+    for (field, v6, typed, shown) in cases {
+        let value = ConditionValue::parse(field, v6, typed).unwrap();
+        assert_eq!(value.edit_text(field), shown, "{typed}");
+        assert_eq!(ConditionValue::parse(field, v6, shown).unwrap(), value);
+    }
+    assert!(ConditionValue::parse(ConditionField::RemoteAddress, true, "10.0.0.1").is_err());
+    assert!(ConditionValue::parse(ConditionField::RemotePort, false, "70000").is_err());
+}
+
+#[test]
+fn byte_values_decode_to_what_they_hold() {
+    let app_id = ConditionValue::parse(
+        ConditionField::AppId,
+        false,
+        "\\device\\harddiskvolume3\\app.exe",
+    )
+    .unwrap();
+    assert_eq!(
+        app_id.decoded().as_deref(),
+        Some("\\device\\harddiskvolume3\\app.exe")
+    );
+    assert!(hex_dump(app_id.bytes().unwrap())
+        .starts_with("0000  5c 00 64 00 65 00 76 00 69 00 63 00 65 00 5c 00  \\.d.e.v.i.c.e.\\."));
+    assert_eq!(
+        ConditionValue::ByteBlob(b"corp-vpn".to_vec())
+            .decoded()
+            .as_deref(),
+        Some("corp-vpn")
+    );
+    assert_eq!(
+        ConditionValue::ByteBlob(vec![0xff, 0x00, 0x13]).decoded(),
+        None
+    );
+
+    // An app container's package SID, typed as it is shown.
+    let typed =
+        "S-1-15-2-2434737943-167758768-3180539153-984336765-1107280622-3591121930-2677285773";
+    let field = ConditionField::Other(Uuid::from_u128(9));
+    let sid = ConditionValue::parse(field, false, typed).unwrap();
+    assert!(matches!(&sid, ConditionValue::Sid(bytes) if bytes.len() == 8 + 4 * 8));
+    assert_eq!(sid.to_string(), typed);
+    assert!(Condition::equal(field, sid).validate().is_ok());
+    let truncated = Condition::equal(field, ConditionValue::Sid(vec![1, 2, 0, 0, 0, 0, 0, 15]));
+    assert!(truncated.validate().is_err());
+}
+
+#[test]
+fn layers_refuse_fields_and_values_they_do_not_have() {
+    let connect = LayerSchema::of(FWPM_LAYER_ALE_AUTH_CONNECT_V4).unwrap();
+    let port = Condition::equal(ConditionField::RemotePort, ConditionValue::Uint16(443));
+    assert!(connect.check(&port).is_ok());
+    let wide_port = Condition::equal(ConditionField::RemotePort, ConditionValue::Uint32(443));
+    assert!(connect.check(&wide_port).is_err());
+    let v6_address = Condition::equal(
+        ConditionField::RemoteAddress,
+        ConditionValue::ByteArray16([0; 16]),
+    );
+    assert!(connect.check(&v6_address).is_err());
+
+    let listen = LayerSchema::of(FWPM_LAYER_ALE_AUTH_LISTEN_V4).unwrap();
+    assert!(listen.check(&port).is_err());
+    assert!(LayerSchema::of(GUID::from_u128(7)).is_none());
+
+    // Every rule we build fits its layer.
+    let config = rule_expr::parse("allow in udp from 192.0.2.0/24, 2001:db8::1 port 53").unwrap();
+    for builder in config.builders(GUID::from_u128(8)).unwrap() {
+        builder.check_layer().unwrap();
+    }
+}
+
+#[test]
+fn unknown_match_types_read_back_as_themselves() {
+    let condition = Condition::new(
+        ConditionField::RemotePort,
+        MatchType::Other(42),
+        ConditionValue::Uint16(443),
+    );
+    assert_eq!(condition.to_string(), "remote port match type 42 443");
+    assert!(condition.validate().is_err());
+    let json = serde_json::to_string(&condition).unwrap();
+    assert_eq!(serde_json::from_str::<Condition>(&json).unwrap(), condition);
+}
@@ -0,0 +1,89 @@
+// Rules that depend on others: dependencies are installed first, and an
+// import or reconcile that would leave a dependency missing is refused
+// before anything is committed.
+#![cfg(feature = "simulation")]
+
+use sls_wfp_gui::{
+    rule_expr,
+    wfp::{order_dependencies, Engine, FilterConfig},
+};
+use uuid::Uuid;
+
+fn keyed(expression: &str) -> FilterConfig {
+    let mut config = rule_expr::parse(expression).unwrap();
+    config.key = Some(Uuid::new_v4());
+    config
+}
+
+/// A block-all on 443 and an allow exception listed before it.
+fn block_and_exception() -> (FilterConfig, FilterConfig) {
+    let block = keyed("block out tcp port 443 name \"Block HTTPS\"");
+    let mut allow = keyed("permit to 192.0.2.10 port 443 name \"Allow proxy\"");
+    allow.depends_on.push(block.key.unwrap());
+    (block, allow)
+}
+
+fn owned_rules(engine: &Engine) -> usize {
+    engine
+        .snapshot()
+        .unwrap()
+        .filters
+        .iter()
+        .filter(|f| f.owned_by_app)
+        .count()
+}
+
+#[test]
+fn dependencies_come_first() {
+    let (block, allow) = block_and_exception();
+    let ordered = order_dependencies(&[allow.clone(), block.clone()]).unwrap();
+    assert_eq!(ordered[0].key, block.key);
+    assert_eq!(ordered[1].key, allow.key);
+
+    let mut cyclic = block;
+    cyclic.depends_on.push(allow.key.unwrap());
+    let Err(err) = order_dependencies(&[allow, cyclic]) else {
+        panic!("a cycle cannot be ordered");
+    };
+    assert!(err.to_string().contains("cycle"));
+}
+
+#[test]
+fn missing_dependencies_are_refused() {
+    let engine = Engine::open_on(Some("dependencies-missing")).unwrap();
+    let (block, allow) = block_and_exception();
+
+    let err = engine
+        .import_filters(std::slice::from_ref(&allow))
+        .unwrap_err();
+    assert!(err.to_string().contains("not installed"));
+    assert_eq!(owned_rules(&engine), 0);
+
+    engine
+        .import_filters(&[allow.clone(), block.clone()])
+        .unwrap();
+    assert!(owned_rules(&engine) > 0);
+
+    // Dropping the block while keeping its exception is refused.
+    let previous = [block.key.unwrap(), allow.key.unwrap()];
+    assert!(engine
+        .reconcile(std::slice::from_ref(&allow), &previous)
+        .is_err());
+    engine.reconcile(&[allow, block], &previous).unwrap();
+}
+
+#[test]
+fn dependencies_round_trip_through_expressions() {
+    let key = Uuid::new_v4();
+    let config = rule_expr::parse(&format!("permit port 443 depends {key}")).unwrap();
+    assert_eq!(config.depends_on, vec![key]);
+    assert_eq!(
+        rule_expr::parse(&rule_expr::format(&config))
+            .unwrap()
+            .depends_on,
+        vec![key]
+    );
+    let mut own = keyed("permit port 443");
+    own.depends_on.push(own.key.unwrap());
+    assert!(own.validate().is_err());
+}
@@ -0,0 +1,55 @@
+// The DNS lockdown rule: permits for the allowed resolvers above blocks on
+// the DNS ports, all members of one rule with distinct keys.
+
+use std::{collections::HashSet, net::IpAddr};
+
+use sls_wfp_gui::wfp::{
+    dns_lockdown_rule, guid_from_uuid, ConditionField, ConditionValue, WfpAction, DNS_PORT,
+    DOH_PORT, DOT_PORT,
+};
+use uuid::Uuid;
+
+#[test]
+fn resolvers_are_permitted_and_other_dns_is_blocked() {
+    let key = guid_from_uuid(Uuid::new_v4());
+    let resolver: IpAddr = "192.0.2.53".parse().unwrap();
+    let doh: Vec<IpAddr> = vec![resolver, "198.51.100.1".parse().unwrap()];
+    let filters: Vec<_> = dns_lockdown_rule(key, "DNS", &[resolver], &[DNS_PORT, DOT_PORT], &doh)
+        .unwrap()
+        .iter()
+        .map(|b| b.to_summary(0))
+        .collect();
+
+    let permits: Vec<_> = filters
+        .iter()
+        .filter(|f| f.action == WfpAction::Permit)
+        .collect();
+    // One per port, on the IPv4 layer only.
+    assert_eq!(permits.len(), 2);
+    assert!(permits.iter().all(|f| f.conditions.iter().any(|c| {
+        c.field == ConditionField::RemoteAddress && c.value == ConditionValue::Uint32(0xC000_0235)
+    })));
+    // Both ports on both layers, then DoH to the one server not allowed.
+    let blocks: Vec<_> = filters
+        .iter()
+        .filter(|f| f.action == WfpAction::Block)
+        .collect();
+    assert_eq!(blocks.len(), 5);
+    assert_eq!(
+        blocks
+            .iter()
+            .filter(|f| f.remote_port == Some(DOH_PORT))
+            .count(),
+        1
+    );
+
+    assert!(filters.iter().all(|f| f.rule_key() == key));
+    let keys: HashSet<_> = filters.iter().map(|f| f.key).collect();
+    assert_eq!(keys.len(), filters.len());
+}
+
+#[test]
+fn no_resolvers_is_refused() {
+    let key = guid_from_uuid(Uuid::new_v4());
+    assert!(dns_lockdown_rule(key, "DNS", &[], &[DNS_PORT], &[]).is_err());
+}
@@ -0,0 +1,33 @@
+// The C API checks what C callers hand it: an action outside SlsWfpAction
+// is rejected rather than read as a Rust enum.
+#![cfg(feature = "simulation")]
+
+use std::{ffi::CStr, ptr};
+
+use sls_wfp_gui::ffi::{
+    sls_wfp_add_tcp_filter, sls_wfp_engine_close, sls_wfp_engine_open, sls_wfp_last_error,
+    SlsWfpAction, SlsWfpResult,
+};
+
+#[test]
+fn unknown_actions_are_invalid_arguments() {
+    let engine = sls_wfp_engine_open();
+    assert!(!engine.is_null());
+    let name = c"FFI rule";
+    let ports = [8443u16];
+    let add = |action: u32| unsafe {
+        sls_wfp_add_tcp_filter(
+            engine,
+            name.as_ptr(),
+            ports.as_ptr(),
+            ports.len(),
+            action,
+            ptr::null_mut(),
+        )
+    };
+    assert_eq!(add(7), SlsWfpResult::InvalidArgument);
+    let error = unsafe { CStr::from_ptr(sls_wfp_last_error()) };
+    assert!(error.to_str().unwrap().contains("unknown action 7"));
+    assert_eq!(add(SlsWfpAction::Block as u32), SlsWfpResult::Ok);
+    unsafe { sls_wfp_engine_close(engine) };
+}
@@ -0,0 +1,38 @@
+// Connection monitoring: permits on the flow-established layers only, so
+// connections are recorded without changing what is allowed.
+
+use sls_wfp_gui::wfp::{
+    app_id, flow_monitor_rule, guid_from_uuid, ConditionField, WfpAction,
+    FWPM_LAYER_ALE_FLOW_ESTABLISHED_V4, FWPM_LAYER_ALE_FLOW_ESTABLISHED_V6,
+};
+use uuid::Uuid;
+
+#[test]
+fn monitoring_only_permits_established_flows() {
+    let key = guid_from_uuid(Uuid::new_v4());
+    let layers = [
+        FWPM_LAYER_ALE_FLOW_ESTABLISHED_V4,
+        FWPM_LAYER_ALE_FLOW_ESTABLISHED_V6,
+    ];
+    let everyone: Vec<_> = flow_monitor_rule(key, "Monitor", &[])
+        .iter()
+        .map(|b| b.to_summary(0))
+        .collect();
+    assert_eq!(everyone.len(), 2);
+    assert!(everyone.iter().all(|f| f.conditions.is_empty()));
+
+    let apps = [
+        app_id(r"C:\Program Files\Browser\browser.exe").unwrap(),
+        app_id(r"C:\Tools\client.exe").unwrap(),
+    ];
+    let filters: Vec<_> = flow_monitor_rule(key, "Monitor", &apps)
+        .iter()
+        .map(|b| b.to_summary(0))
+        .collect();
+    assert_eq!(filters.len(), 4);
+    for filter in &filters {
+        assert_eq!(filter.action, WfpAction::Permit);
+        assert!(layers.contains(&filter.layer_key));
+        assert_eq!(filter.conditions[0].field, ConditionField::AppId);
+    }
+}
@@ -0,0 +1,252 @@
+// Rule files from other firewalls: what maps onto app rules, what is
+// skipped, and that keys stay stable across imports. Also filters read back
+// from `netsh wfp show filters` output.
+
+use sls_wfp_gui::{
+    config::{self, RuleFormat},
+    importers::{self, FilterPresence},
+    wfp::{
+        ConditionField, ConditionValue, MatchType, RemotePorts, WeightKind, WfpAction,
+        FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+    },
+};
+
+const SIMPLEWALL: &str = r#"<?xml version="1.0" ?>
+<root timestamp="1700000000" type="3" version="5">
+    <apps>
+        <item path="C:\Program Files\Mozilla Firefox\firefox.exe" timestamp="1" is_enabled="true" />
+        <item path="C:\Tools\old.exe" timestamp="1" is_enabled="false" />
+    </apps>
+    <rules_custom>
+        <item name="Mail" rule="25;587" dir="0" protocol="6" apps="C:\Apps\mail.exe" is_block="true" is_enabled="true" />
+        <item name="Web" rule="80;443" dir="0" protocol="6" is_enabled="true" />
+        <item name="LAN" rule="192.168.0.0/16" dir="0" protocol="0" apps="C:\Apps\mail.exe" is_enabled="true" />
+        <item name="DNS" rule="53" dir="0" protocol="17" is_enabled="true" />
+    </rules_custom>
+</root>
+"#;
+
+const TINYWALL: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<ConfigContainer xmlns:i="http://www.w3.org/2001/XMLSchema-instance">
+  <AppExceptions>
+    <FirewallExceptionV3>
+      <Subject i:type="ExecutableSubject">
+        <ExecutablePath>C:\Games\game.exe</ExecutablePath>
+      </Subject>
+      <Policy i:type="UnrestrictedPolicy" />
+    </FirewallExceptionV3>
+    <FirewallExceptionV3>
+      <Subject i:type="ExecutableSubject">
+        <ExecutablePath>C:\Apps\agent.exe</ExecutablePath>
+      </Subject>
+      <Policy i:type="TcpUdpPolicy">
+        <AllowedRemoteTcpConnectPorts>443, 8443</AllowedRemoteTcpConnectPorts>
+      </Policy>
+    </FirewallExceptionV3>
+    <FirewallExceptionV3>
+      <Subject i:type="ExecutableSubject">
+        <ExecutablePath>C:\Apps\spy.exe</ExecutablePath>
+      </Subject>
+      <Policy i:type="HardBlockPolicy" />
+    </FirewallExceptionV3>
+    <FirewallExceptionV3>
+      <Subject i:type="AppContainerSubject">
+        <Sid>S-1-15-2-1</Sid>
+      </Subject>
+      <Policy i:type="UnrestrictedPolicy" />
+    </FirewallExceptionV3>
+  </AppExceptions>
+</ConfigContainer>
+"#;
+
+#[test]
+fn simplewall_profile_maps_apps_and_port_rules() {
+    assert_eq!(RuleFormat::sniff(SIMPLEWALL), RuleFormat::Simplewall);
+    let configs = config::parse_rules(SIMPLEWALL, RuleFormat::Simplewall).unwrap();
+    let summary: Vec<_> = configs
+        .iter()
+        .map(|c| {
+            (
+                c.name.as_str(),
+                c.app.as_deref(),
+                c.action,
+                c.remote_port.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                "simplewall: firefox.exe",
+                Some(r"C:\Program Files\Mozilla Firefox\firefox.exe"),
+                WfpAction::Permit,
+                RemotePorts::default(),
+            ),
+            (
+                "Mail (mail.exe)",
+                Some(r"C:\Apps\mail.exe"),
+                WfpAction::Block,
+                RemotePorts::Many(vec![25, 587]),
+            ),
+            (
+                "Web",
+                None,
+                WfpAction::Permit,
+                RemotePorts::Many(vec![80, 443])
+            ),
+        ]
+    );
+
+    // Keys are derived from the rule, so a second import updates in place.
+    let again = config::parse_rules(SIMPLEWALL, RuleFormat::Simplewall).unwrap();
+    let keys = |configs: &[sls_wfp_gui::wfp::FilterConfig]| -> Vec<_> {
+        configs.iter().map(|c| c.key.unwrap()).collect()
+    };
+    assert_eq!(keys(&configs), keys(&again));
+}
+
+#[test]
+fn tinywall_export_maps_exceptions() {
+    assert_eq!(RuleFormat::sniff(TINYWALL), RuleFormat::TinyWall);
+    let configs = config::parse_rules(TINYWALL, RuleFormat::TinyWall).unwrap();
+    let summary: Vec<_> = configs
+        .iter()
+        .map(|c| (c.app.as_deref().unwrap(), c.action, c.remote_port.clone()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                r"C:\Games\game.exe",
+                WfpAction::Permit,
+                RemotePorts::default()
+            ),
+            (
+                r"C:\Apps\agent.exe",
+                WfpAction::Permit,
+                RemotePorts::Many(vec![443, 8443]),
+            ),
+            (r"C:\Apps\spy.exe", WfpAction::Block, RemotePorts::default()),
+        ]
+    );
+}
+
+#[test]
+fn foreign_file_without_usable_rules_is_rejected() {
+    let empty = r#"<root><apps><item path="C:\a.exe" is_enabled="false" /></apps></root>"#;
+    assert!(config::parse_rules(empty, RuleFormat::Simplewall).is_err());
+}
+
+const NETSH_FILTERS: &str = r#"<?xml version="1.0"?>
+<wfpdiag>
+ <filters numItems="2">
+  <item>
+   <filterKey>{3e8a1f2c-1b6d-4e0a-9f41-6a0b9c2d7e11}</filterKey>
+   <displayData>
+    <name>Block SMB</name>
+    <description/>
+   </displayData>
+   <flags numItems="1">
+    <item>FWPM_FILTER_FLAG_PERSISTENT</item>
+   </flags>
+   <providerKey>FWPM_PROVIDER_MPSSVC_WF</providerKey>
+   <layerKey>FWPM_LAYER_ALE_AUTH_CONNECT_V4</layerKey>
+   <subLayerKey>FWPM_SUBLAYER_MPSSVC_WF</subLayerKey>
+   <weight>
+    <type>FWP_UINT8</type>
+    <uint8>12</uint8>
+   </weight>
+   <filterCondition numItems="2">
+    <item>
+     <fieldKey>FWPM_CONDITION_IP_REMOTE_PORT</fieldKey>
+     <matchType>FWP_MATCH_EQUAL</matchType>
+     <conditionValue>
+      <type>FWP_UINT16</type>
+      <uint16>445</uint16>
+     </conditionValue>
+    </item>
+    <item>
+     <fieldKey>FWPM_CONDITION_IP_REMOTE_ADDRESS</fieldKey>
+     <matchType>FWP_MATCH_EQUAL</matchType>
+     <conditionValue>
+      <type>FWP_V4_ADDR_MASK</type>
+      <v4AddrMask>
+       <addr>10.0.0.0</addr>
+       <mask>255.0.0.0</mask>
+      </v4AddrMask>
+     </conditionValue>
+    </item>
+   </filterCondition>
+   <action>
+    <type>FWP_ACTION_BLOCK</type>
+    <filterType/>
+   </action>
+   <filterId>70123</filterId>
+   <effectiveWeight>
+    <type>FWP_UINT64</type>
+    <uint64>13835058055282163712</uint64>
+   </effectiveWeight>
+  </item>
+  <item>
+   <filterKey>{5b0c2e77-8d1a-4f3b-a2c4-0e9f8d7c6b55}</filterKey>
+   <displayData>
+    <name>Vendor inspection</name>
+   </displayData>
+   <flags/>
+   <layerKey>FWPM_LAYER_VENDOR_UNKNOWN</layerKey>
+   <subLayerKey>{b3cdd441-af90-41ba-a745-7c6008ff2300}</subLayerKey>
+   <weight>
+    <type>FWP_EMPTY</type>
+   </weight>
+   <filterCondition numItems="1">
+    <item>
+     <fieldKey>FWPM_CONDITION_ALE_USER_ID</fieldKey>
+     <matchType>FWP_MATCH_EQUAL</matchType>
+     <conditionValue>
+      <type>FWP_TOKEN_ACCESS_INFORMATION_TYPE</type>
+     </conditionValue>
+    </item>
+   </filterCondition>
+   <action>
+    <type>FWP_ACTION_CALLOUT_INSPECTION</type>
+   </action>
+   <filterId>70124</filterId>
+  </item>
+ </filters>
+</wfpdiag>
+"#;
+
+#[test]
+fn netsh_filters_are_read_back_and_compared() {
+    let filters = importers::netsh_filters(NETSH_FILTERS).unwrap();
+    assert_eq!(filters.len(), 2);
+    let smb = &filters[0];
+    assert_eq!(smb.id, 70123);
+    assert_eq!(smb.name, "Block SMB");
+    assert_eq!(smb.layer_key, FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+    assert_eq!(smb.provider, "Windows Defender Firewall");
+    assert_eq!(smb.action, WfpAction::Block);
+    assert_eq!(smb.remote_port, Some(445));
+    assert_eq!(smb.weight_kind, WeightKind::Range(12));
+    assert!(smb.persistent && !smb.boot_time);
+    assert_eq!(smb.conditions[1].field, ConditionField::RemoteAddress);
+    assert_eq!(smb.conditions[1].match_type, MatchType::Equal);
+    assert_eq!(smb.conditions[1].value.to_string(), "10.0.0.0/255.0.0.0");
+
+    assert_eq!(FilterPresence::of(smb, &[]), FilterPresence::Missing);
+    assert_eq!(
+        FilterPresence::of(smb, std::slice::from_ref(smb)),
+        FilterPresence::Same
+    );
+    let mut changed = smb.clone();
+    changed.conditions[0].value = ConditionValue::Uint16(139);
+    assert_eq!(FilterPresence::of(smb, &[changed]), FilterPresence::Differs);
+
+    assert!(importers::adopt_filter(smb).is_ok());
+    // Unknown layers and callouts cannot be rebuilt here.
+    let vendor = &filters[1];
+    assert_eq!(vendor.action, WfpAction::Callout);
+    assert!(importers::adopt_filter(vendor).is_err());
+    assert!(importers::netsh_filters("<wfpdiag/>").is_err());
+}
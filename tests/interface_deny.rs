@@ -0,0 +1,57 @@
+// Default-deny by interface: every connection on the chosen adapters is
+// blocked, in and out, beneath the rules that allow traffic.
+
+use std::collections::HashSet;
+
+use sls_wfp_gui::wfp::{
+    guid_from_uuid, interface_deny_rule, Condition, ConditionField, ConditionValue, WeightTier,
+    WfpAction,
+};
+use uuid::Uuid;
+
+#[test]
+fn denied_interfaces_block_both_ways_below_other_rules() {
+    let key = guid_from_uuid(Uuid::new_v4());
+    let (cellular, wifi) = (0x0006_0000_0100_0000, 0x0047_0000_0200_0000);
+    let builders = interface_deny_rule(key, "Deny", &[cellular, wifi]);
+    assert_eq!(builders.len(), 8);
+    for builder in &builders {
+        assert_eq!(builder.tier(), WeightTier::DefaultDeny);
+    }
+    let filters: Vec<_> = builders.iter().map(|b| b.to_summary(0)).collect();
+    // Connect and accept, for IPv4 and IPv6.
+    let layers_on = |luid: u64| {
+        filters
+            .iter()
+            .filter(|f| f.conditions[0].value == ConditionValue::Uint64(luid))
+            .map(|f| f.layer_key)
+            .collect::<HashSet<_>>()
+            .len()
+    };
+    assert_eq!((layers_on(cellular), layers_on(wifi)), (4, 4));
+}
+
+#[test]
+fn blocks_on_an_interface_type_are_default_deny_and_callouts_are_not() {
+    // IF_TYPE_IEEE80211: every Wi-Fi adapter.
+    let wifi = [Condition::equal(
+        ConditionField::InterfaceType,
+        ConditionValue::Uint32(71),
+    )];
+    assert_eq!(
+        WeightTier::classify(WfpAction::Block, &wifi),
+        WeightTier::DefaultDeny
+    );
+    assert_eq!(
+        WeightTier::classify(WfpAction::Callout, &wifi),
+        WeightTier::Block
+    );
+    assert_eq!(
+        WeightTier::classify(WfpAction::Callout, &[]),
+        WeightTier::Block
+    );
+    assert_eq!(
+        WeightTier::classify(WfpAction::Permit, &[]),
+        WeightTier::Allow
+    );
+}
@@ -0,0 +1,65 @@
+// The panic button: its block-all filters live in a dynamic session, so
+// they are in place while the lockdown is alive and gone once it drops. A
+// remote session is kept connected, and the lockdown is refused while no
+// allow rule covers it.
+#![cfg(feature = "simulation")]
+
+use std::sync::{Mutex, PoisonError};
+
+use sls_wfp_gui::{
+    lockdown::{self, Lockdown, RemoteSession},
+    rule_expr,
+    wfp::{guid_from_uuid, Engine, WfpAction},
+};
+use uuid::Uuid;
+
+/// A lockdown only runs on this machine's engine, which the tests share.
+static LOCAL: Mutex<()> = Mutex::new(());
+
+#[test]
+fn lockdown_filters_last_as_long_as_the_lockdown() {
+    let _local = LOCAL.lock().unwrap_or_else(PoisonError::into_inner);
+    let engine = Engine::open().unwrap();
+    let lockdown = Lockdown::start(lockdown::DEFAULT_DURATION, &[]).unwrap();
+    let filters = engine.snapshot().unwrap().filters;
+    assert_eq!(filters.len(), 4);
+    assert!(filters.iter().all(|f| f.action == WfpAction::Block));
+    assert!(!lockdown.expired());
+
+    drop(lockdown);
+    assert!(engine.snapshot().unwrap().filters.is_empty());
+}
+
+#[test]
+fn remote_sessions_need_an_allow_rule_and_stay_connected() {
+    let _local = LOCAL.lock().unwrap_or_else(PoisonError::into_inner);
+    let engine = Engine::open().unwrap();
+    let admin = RemoteSession {
+        protocol: "RDP",
+        remote: "203.0.113.5".parse().unwrap(),
+    };
+    let refused = Lockdown::start(lockdown::DEFAULT_DURATION, std::slice::from_ref(&admin));
+    assert!(refused.is_err_and(|err| err.to_string().contains("203.0.113.5")));
+    assert!(engine.snapshot().unwrap().filters.is_empty());
+
+    let mut allow = rule_expr::parse("allow in tcp from 203.0.113.5 local port 3389").unwrap();
+    let key = Uuid::new_v4();
+    allow.key = Some(key);
+    engine.import_filters(&[allow]).unwrap();
+    let lockdown =
+        Lockdown::start(lockdown::DEFAULT_DURATION, std::slice::from_ref(&admin)).unwrap();
+    let kept: Vec<_> = engine
+        .snapshot()
+        .unwrap()
+        .filters
+        .into_iter()
+        .filter(|f| f.weight == Some(u64::MAX))
+        .collect();
+    // In and out, above the lockdown's blocks.
+    assert_eq!(kept.len(), 2);
+    assert!(kept.iter().all(|f| f.permits_remote(admin.remote)));
+
+    drop(lockdown);
+    engine.delete_filter_by_key(guid_from_uuid(key)).unwrap();
+    assert!(engine.snapshot().unwrap().filters.is_empty());
+}
@@ -0,0 +1,68 @@
+// Naming policy: names breaking it are refused with what they break, and
+// the rename migration makes them follow it with as little change as it
+// takes.
+
+use sls_wfp_gui::{
+    naming::NamingPolicy,
+    wfp::{FilterBuilder, FWPM_LAYER_ALE_AUTH_CONNECT_V4},
+};
+
+fn policy() -> NamingPolicy {
+    NamingPolicy {
+        prefix: "CORP-".into(),
+        max_length: Some(16),
+        forbidden: "|;".into(),
+    }
+}
+
+#[test]
+fn names_breaking_the_policy_are_refused() {
+    let policy = policy();
+    assert!(policy.check("CORP-Block SMB").is_ok());
+    assert_eq!(
+        policy.violations("Block|SMB over the internet"),
+        [
+            "must start with \"CORP-\"",
+            "must be at most 16 characters",
+            "must not contain \"|\"",
+        ]
+    );
+    assert!(NamingPolicy::default().check("any | name").is_ok());
+}
+
+#[test]
+fn renames_follow_the_policy() {
+    let policy = policy();
+    assert_eq!(policy.conform("Block|SMB"), "CORP-BlockSMB");
+    assert_eq!(policy.conform("Block SMB outbound"), "CORP-Block SMB o");
+    assert_eq!(policy.conform("Block SMB  rule"), "CORP-Block SMB");
+    assert_eq!(policy.conform("CORP-DNS"), "CORP-DNS");
+    for name in ["Block|SMB", "Block SMB outbound", ";;;"] {
+        assert!(policy.check(&policy.conform(name)).is_ok(), "{name}");
+    }
+
+    let filters = [
+        FilterBuilder::new("CORP-Kept", FWPM_LAYER_ALE_AUTH_CONNECT_V4).to_summary(1),
+        FilterBuilder::new("Renamed", FWPM_LAYER_ALE_AUTH_CONNECT_V4).to_summary(2),
+    ];
+    let renames = policy.renames(&filters);
+    assert_eq!(renames.len(), 1);
+    assert_eq!(renames[0].0.id, 2);
+    assert_eq!(renames[0].1, "CORP-Renamed");
+
+    // Trimming the cut-off end leaves a prefix ending in a space alone.
+    let spaced = NamingPolicy {
+        prefix: "CORP ".into(),
+        max_length: Some(7),
+        forbidden: String::new(),
+    };
+    assert_eq!(spaced.conform("  DNS"), "CORP ");
+    assert_eq!(spaced.conform("DNS"), "CORP DN");
+    assert!(spaced.check(&spaced.conform("  DNS")).is_ok());
+
+    let unusable = NamingPolicy {
+        max_length: Some(5),
+        ..policy
+    };
+    assert!(unusable.validate().is_err());
+}
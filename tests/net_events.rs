@@ -0,0 +1,101 @@
+// Allowing a flow from a recorded event: the permit lands on the layer for
+// its direction and address family, keyed on the service port and the
+// application's device path.
+
+use std::net::IpAddr;
+
+use chrono::Utc;
+use sls_wfp_gui::wfp::{
+    ConditionField, ConditionValue, FlowDirection, NetEvent, NetEventKind, WfpAction,
+    FWPM_LAYER_ALE_AUTH_CONNECT_V4, FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+    FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4, FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+};
+
+const APP: &str = r"\device\harddiskvolume3\tools\client.exe";
+
+fn event(direction: FlowDirection, remote: &str) -> NetEvent {
+    NetEvent {
+        time: Utc::now(),
+        kind: NetEventKind::Drop,
+        filter_id: 0,
+        layer_id: 0,
+        direction: Some(direction),
+        protocol: Some(6),
+        local_addr: None,
+        local_port: Some(50_000),
+        remote_addr: Some(remote.parse().unwrap()),
+        remote_port: Some(443),
+        app_id: Some(APP.into()),
+    }
+}
+
+#[test]
+fn permits_follow_the_direction_and_address_family() {
+    let cases = [
+        (
+            FlowDirection::Outbound,
+            "192.0.2.10",
+            FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+            ConditionField::RemotePort,
+            443,
+        ),
+        (
+            FlowDirection::Outbound,
+            "2001:db8::10",
+            FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+            ConditionField::RemotePort,
+            443,
+        ),
+        (
+            FlowDirection::Inbound,
+            "192.0.2.10",
+            FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+            ConditionField::LocalPort,
+            50_000,
+        ),
+        (
+            FlowDirection::Inbound,
+            "2001:db8::10",
+            FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+            ConditionField::LocalPort,
+            50_000,
+        ),
+    ];
+    let blob: Vec<u8> = APP
+        .encode_utf16()
+        .chain([0])
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    for (direction, remote, layer, port_field, port) in cases {
+        let filter = event(direction, remote)
+            .permit("Allow client")
+            .unwrap()
+            .to_summary(0);
+        assert_eq!(filter.action, WfpAction::Permit);
+        assert_eq!(filter.layer_key, layer, "{direction:?} {remote}");
+        let value = |field: ConditionField| {
+            filter
+                .conditions
+                .iter()
+                .find(|c| c.field == field)
+                .map(|c| c.value.clone())
+        };
+        let address = match remote.parse::<IpAddr>().unwrap() {
+            IpAddr::V4(addr) => ConditionValue::Uint32(u32::from(addr)),
+            IpAddr::V6(addr) => ConditionValue::ByteArray16(addr.octets()),
+        };
+        assert_eq!(value(ConditionField::RemoteAddress), Some(address));
+        assert_eq!(value(port_field), Some(ConditionValue::Uint16(port)));
+        assert_eq!(
+            value(ConditionField::AppId),
+            Some(ConditionValue::ByteBlob(blob.clone()))
+        );
+    }
+}
+
+#[test]
+fn events_without_a_direction_cannot_be_allowed() {
+    let mut event = event(FlowDirection::Outbound, "192.0.2.10");
+    event.direction = None;
+    assert!(event.permit("Allow client").is_err());
+}
@@ -0,0 +1,50 @@
+// Provider contexts: rules carry the contexts they name, exports include
+// them, and a restore elsewhere adds them before the rules.
+#![cfg(feature = "simulation")]
+
+use sls_wfp_gui::{
+    rule_expr,
+    wfp::{guid_from_uuid, uuid_from_guid, Engine, RuleSet},
+};
+use uuid::Uuid;
+
+#[test]
+fn contexts_travel_with_the_rules_that_carry_them() {
+    let engine = Engine::open_on(Some("contexts-source")).unwrap();
+    let context = engine
+        .create_provider_context("Inspection settings", &[1, 2, 3, 4])
+        .unwrap();
+    let mut config = rule_expr::parse("block out tcp port 8080").unwrap();
+    config.key = Some(Uuid::new_v4());
+    config.provider_context = Some(uuid_from_guid(context));
+    engine.import_filters(&[config.clone()]).unwrap();
+    assert!(engine
+        .snapshot()
+        .unwrap()
+        .filters
+        .iter()
+        .any(|f| f.owned_by_app && f.provider_context_key == Some(context)));
+    assert!(engine.delete_provider_context(context).is_err());
+
+    let set: RuleSet = serde_json::from_str(&engine.export_owned_filters().unwrap()).unwrap();
+    assert_eq!(set.provider_contexts.len(), 1);
+    assert_eq!(set.provider_contexts[0].data, [1, 2, 3, 4]);
+    assert_eq!(
+        set.filters[0].provider_context,
+        Some(uuid_from_guid(context))
+    );
+
+    let target = Engine::open_on(Some("contexts-target")).unwrap();
+    assert!(target.import_filters(&[config]).is_err());
+    target.import_rule_set(&set).unwrap();
+    let restored = target.provider_contexts().unwrap();
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored[0].key, context);
+    assert_eq!(restored[0].data.as_deref(), Some(&[1, 2, 3, 4][..]));
+
+    engine
+        .delete_filter_by_key(guid_from_uuid(set.filters[0].key.unwrap()))
+        .unwrap();
+    engine.delete_provider_context(context).unwrap();
+    assert!(engine.provider_contexts().unwrap().is_empty());
+}
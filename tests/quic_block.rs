@@ -0,0 +1,37 @@
+// The QUIC block preset: outbound UDP on the QUIC port, once per layer for
+// everyone or once per layer and app.
+
+use sls_wfp_gui::wfp::{
+    app_id, guid_from_uuid, quic_block_rule, ConditionField, ConditionValue, WfpAction, QUIC_PORT,
+};
+use uuid::Uuid;
+
+#[test]
+fn quic_is_blocked_per_app_on_both_layers() {
+    let key = guid_from_uuid(Uuid::new_v4());
+    let udp = ConditionValue::Uint8(17);
+    let everyone = quic_block_rule(key, "QUIC", &[]);
+    assert_eq!(everyone.len(), 2);
+
+    let apps = [
+        app_id(r"C:\Program Files\Browser\browser.exe").unwrap(),
+        app_id(r"C:\Tools\client.exe").unwrap(),
+    ];
+    let filters: Vec<_> = quic_block_rule(key, "QUIC", &apps)
+        .iter()
+        .map(|b| b.to_summary(0))
+        .collect();
+    assert_eq!(filters.len(), 4);
+    for filter in &filters {
+        assert_eq!(filter.action, WfpAction::Block);
+        assert_eq!(filter.remote_port, Some(QUIC_PORT));
+        assert!(filter
+            .conditions
+            .iter()
+            .any(|c| c.field == ConditionField::IpProtocol && c.value == udp));
+        assert!(filter
+            .conditions
+            .iter()
+            .any(|c| c.field == ConditionField::AppId));
+    }
+}
@@ -0,0 +1,217 @@
+// The one-line rule language: what an expression parses into, how the rule
+// expands into filters, and that formatting reads back the same rule.
+
+use std::net::IpAddr;
+
+use sls_wfp_gui::{
+    rule_expr,
+    wfp::{
+        blocked_system_ports, companion_rules, coverage_gaps, rules_from_filters, ConditionField,
+        CoverageGap, Direction, FilterSummary, InterfaceMedia, RemotePorts, RuleProtocol,
+        SystemPortKind, SystemPorts, WfpAction, FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+        FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4, FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6, GUID,
+    },
+};
+
+#[test]
+fn block_to_network_expands_per_family() {
+    let config = rule_expr::parse("block out tcp to 10.0.0.0/8, 2001:db8::/32 port 445").unwrap();
+    assert_eq!(config.action, WfpAction::Block);
+    assert_eq!(config.direction, Direction::Out);
+    assert_eq!(config.protocol(), RuleProtocol::Tcp);
+    assert_eq!(config.remote_port, RemotePorts::One(445));
+    assert_eq!(
+        config.name,
+        "block out tcp to 10.0.0.0/8,2001:db8::/32 port 445"
+    );
+
+    // Each address only goes to the layer of its IP version.
+    let builders = config.builders(GUID::from_u128(1)).unwrap();
+    assert_eq!(builders.len(), 2);
+
+    let again = rule_expr::parse(&rule_expr::format(&config)).unwrap();
+    assert_eq!(again.remote_address, config.remote_address);
+    assert_eq!(again.remote_port, config.remote_port);
+}
+
+#[test]
+fn allow_rules_let_their_remote_addresses_through() {
+    let summaries = |text: &str| -> Vec<FilterSummary> {
+        let config = rule_expr::parse(text).unwrap();
+        let builders = config.builders(GUID::from_u128(2)).unwrap();
+        builders.iter().map(|b| b.to_summary(0)).collect()
+    };
+    let admin: IpAddr = "198.51.100.7".parse().unwrap();
+    let other: IpAddr = "203.0.113.7".parse().unwrap();
+
+    let allow = summaries("allow tcp from 198.51.100.0/24");
+    assert!(allow.iter().any(|f| f.permits_remote(admin)));
+    assert!(!allow.iter().any(|f| f.permits_remote(other)));
+
+    let block = summaries("block tcp from 198.51.100.0/24");
+    assert!(!block.iter().any(|f| f.permits_remote(admin)));
+}
+
+#[test]
+fn from_implies_inbound_and_names_are_kept() {
+    let config =
+        rule_expr::parse(r#"allow udp from 192.0.2.1 port 53,5353 name "Local DNS""#).unwrap();
+    assert_eq!(config.direction, Direction::In);
+    assert_eq!(config.protocol(), RuleProtocol::Udp);
+    assert_eq!(config.remote_address[0].to_string(), "192.0.2.1");
+    assert_eq!(config.remote_port, RemotePorts::Many(vec![53, 5353]));
+    assert_eq!(config.name, "Local DNS");
+}
+
+#[test]
+fn contradictions_are_rejected() {
+    for text in [
+        "block in to 10.0.0.0/8",
+        "block any port 445",
+        "block tcp",
+        "drop tcp port 80",
+        "block to 10.0.0.0/33",
+        r#"permit app "C:\app.exe"#,
+        "callout tcp port 80",
+    ] {
+        assert!(rule_expr::parse(text).is_err(), "{text}");
+    }
+}
+
+#[test]
+fn clauses_are_given_at_most_once() {
+    for text in [
+        r#"block app "C:\a.exe" app "C:\b.exe""#,
+        r#"block port 80 name "A" name "B""#,
+        "block port 80 port 443",
+        "block port 80 ports 443",
+        "block tcp udp port 80",
+        "block to 10.0.0.1 to 10.0.0.2",
+        "block in from 10.0.0.1 in",
+        "block local port 80 local port 81",
+        "block port 80 on wifi on vpn",
+    ] {
+        let Err(err) = rule_expr::parse(text) else {
+            panic!("{text} parsed");
+        };
+        assert!(err.to_string().contains("given twice"), "{text}: {err}");
+    }
+    // A direction may still agree with the address that implies it.
+    assert!(rule_expr::parse("block out to 10.0.0.1").is_ok());
+}
+
+#[test]
+fn broad_blocks_hit_system_ports() {
+    let system = [SystemPorts {
+        kind: SystemPortKind::RpcEndpointMapper,
+        ports: vec![135],
+    }];
+    let blocked = |text: &str| blocked_system_ports(&rule_expr::parse(text).unwrap(), &system);
+    assert_eq!(
+        blocked("block tcp port 135,445"),
+        [(SystemPortKind::RpcEndpointMapper, 135)]
+    );
+    assert_eq!(blocked("block to 10.0.0.0/8").len(), 1);
+    assert!(blocked("block tcp port 445").is_empty());
+    assert!(blocked("permit tcp port 135").is_empty());
+    assert!(blocked(r#"block app "C:\a.exe""#).is_empty());
+}
+
+#[test]
+fn port_blocks_report_the_paths_they_leave_open() {
+    let config = rule_expr::parse("block port 445").unwrap();
+    assert_eq!(
+        coverage_gaps(&config),
+        [CoverageGap::Ipv6, CoverageGap::Inbound]
+    );
+    assert!(coverage_gaps(&rule_expr::parse("permit port 445").unwrap()).is_empty());
+    assert!(coverage_gaps(&rule_expr::parse("block to 10.0.0.0/8 port 445").unwrap()).is_empty());
+
+    let companions = companion_rules(&config);
+    assert_eq!(companions.len(), 2);
+    let layers = |index: usize| -> Vec<GUID> {
+        companions[index]
+            .builders(GUID::from_u128(4))
+            .unwrap()
+            .iter()
+            .map(|b| b.to_summary(0).layer_key)
+            .collect()
+    };
+    assert_eq!(layers(0), [FWPM_LAYER_ALE_AUTH_CONNECT_V6]);
+    assert_eq!(
+        layers(1),
+        [
+            FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+            FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6
+        ]
+    );
+    // Inbound, 445 is the port connections are accepted on.
+    let inbound = &companions[1];
+    assert_eq!(rule_expr::format(inbound), "block in tcp local port 445");
+    let summary = inbound.builders(GUID::from_u128(5)).unwrap()[0].to_summary(0);
+    assert!(summary
+        .conditions
+        .iter()
+        .any(|c| c.field == ConditionField::LocalPort));
+    assert!(companions.iter().all(|c| coverage_gaps(c).is_empty()));
+}
+
+#[test]
+fn interface_rules_match_every_type_of_the_medium() {
+    let config = rule_expr::parse("block tcp port 445 on wifi").unwrap();
+    assert_eq!(config.interface, Some(InterfaceMedia::Wifi));
+    assert_eq!(config.name, "block out tcp port 445 on wifi");
+
+    let cellular = rule_expr::parse("block to 10.0.0.0/8 on cellular").unwrap();
+    let builders = cellular.builders(GUID::from_u128(2)).unwrap();
+    let summary = builders[0].to_summary(1);
+    assert_eq!(InterfaceMedia::of(&summary.conditions), cellular.interface);
+
+    // Both address families are installed, and the members export as one
+    // rule again, with its interface type.
+    let key = GUID::from_u128(3);
+    let summaries: Vec<_> = config
+        .builders(key)
+        .unwrap()
+        .iter()
+        .enumerate()
+        .map(|(id, builder)| {
+            let mut summary = builder.to_summary(id as u64);
+            summary.owned_by_app = true;
+            summary
+        })
+        .collect();
+    assert_eq!(summaries.len(), 2);
+    let exported = rules_from_filters(summaries);
+    assert_eq!(exported.len(), 1);
+    assert_eq!(exported[0].interface, Some(InterfaceMedia::Wifi));
+    assert_eq!(exported[0].remote_port, RemotePorts::One(445));
+}
+
+#[test]
+fn networks_contain_their_addresses() {
+    let config = rule_expr::parse("block out to 10.0.0.0/8, 2001:db8::/32").unwrap();
+    let [v4, v6] = config.remote_address[..] else {
+        panic!("two networks expected");
+    };
+    assert!(v4.contains("10.200.0.1".parse().unwrap()));
+    assert!(!v4.contains("11.0.0.1".parse().unwrap()));
+    assert!(v6.contains("2001:db8::53".parse().unwrap()));
+    assert!(!v6.contains("2001:db9::53".parse().unwrap()));
+    assert!(!v4.contains("2001:db8::53".parse().unwrap()));
+}
+
+#[test]
+fn hostnames_are_kept_apart_from_addresses() {
+    let config = rule_expr::parse("block to 192.0.2.1, localhost port 443").unwrap();
+    assert_eq!(config.remote_address.len(), 1);
+    assert_eq!(config.remote_host, vec!["localhost".to_string()]);
+    assert_eq!(
+        rule_expr::format(&config),
+        "block out tcp to 192.0.2.1,localhost port 443"
+    );
+    let addresses = config.remote_addresses().unwrap();
+    assert!(addresses.len() > 1);
+    assert!(addresses.iter().any(|a| a.addr.is_loopback()));
+    assert!(rule_expr::parse("block to 10.0.0.0/99").is_err());
+}
@@ -0,0 +1,61 @@
+// Snapshots saved to a file for offline analysis: every filter, with its
+// keys and decoded conditions, reads back as it was enumerated.
+#![cfg(feature = "simulation")]
+
+use sls_wfp_gui::{
+    rule_expr,
+    wfp::{uuid_from_guid, Engine, SavedSnapshot},
+};
+use uuid::Uuid;
+
+#[test]
+fn saved_snapshots_read_back_whole() {
+    let engine = Engine::open_on(Some("snapshots-saved")).unwrap();
+    let mut config = rule_expr::parse("block out tcp to 192.0.2.0/24 port 443,8443").unwrap();
+    config.key = Some(Uuid::new_v4());
+    engine.import_filters(&[config]).unwrap();
+
+    let saved = SavedSnapshot::take(&engine, Some("customer-pc")).unwrap();
+    let path = std::env::temp_dir().join(format!("snapshot-{}.json", Uuid::new_v4()));
+    saved.save(&path).unwrap();
+    let loaded = SavedSnapshot::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.machine, "customer-pc");
+    assert_eq!(loaded.taken, saved.taken);
+    let keys = |s: &SavedSnapshot| -> Vec<(Uuid, Option<Uuid>, String)> {
+        s.snapshot
+            .filters
+            .iter()
+            .map(|f| {
+                (
+                    uuid_from_guid(f.key),
+                    f.provider_key.map(uuid_from_guid),
+                    f.layer.clone(),
+                )
+            })
+            .collect()
+    };
+    assert_eq!(keys(&loaded), keys(&saved));
+    let owned = loaded
+        .snapshot
+        .filters
+        .iter()
+        .find(|f| f.owned_by_app)
+        .unwrap();
+    let original = saved
+        .snapshot
+        .filters
+        .iter()
+        .find(|f| f.key == owned.key)
+        .unwrap();
+    assert_eq!(owned.conditions, original.conditions);
+    assert_eq!(owned.weight_kind, original.weight_kind);
+    assert_eq!(
+        loaded.snapshot.sublayers.len(),
+        saved.snapshot.sublayers.len()
+    );
+    assert_eq!(loaded.sublayer_details.len(), saved.sublayer_details.len());
+
+    assert!(SavedSnapshot::load(&path).is_err());
+}